@@ -0,0 +1,84 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rusqlite::{params, Connection};
+use rusty_files::storage::Database;
+use rusty_files::FileEntry;
+use std::path::PathBuf;
+
+const LOOKUP_SQL: &str = "SELECT id, name FROM files WHERE path = ?1";
+
+fn populated_database(file_count: usize) -> Database {
+    let db = Database::in_memory(1).unwrap();
+    let files: Vec<FileEntry> = (0..file_count)
+        .map(|i| FileEntry::new(PathBuf::from(format!("/data/file_{}.rs", i))))
+        .collect();
+    db.insert_files_batch(&files).unwrap();
+    db
+}
+
+fn benchmark_single_insert(c: &mut Criterion) {
+    let db = Database::in_memory(1).unwrap();
+    let mut counter = 0u64;
+
+    c.bench_function("db_insert_single", |b| {
+        b.iter(|| {
+            counter += 1;
+            let entry = FileEntry::new(PathBuf::from(format!("/data/single_{}.txt", counter)));
+            black_box(db.insert_file(&entry).unwrap());
+        });
+    });
+}
+
+fn benchmark_search_by_name(c: &mut Criterion) {
+    let db = populated_database(1000);
+
+    c.bench_function("db_search_by_name", |b| {
+        b.iter(|| {
+            black_box(db.search_by_name("file_5", 100).unwrap());
+        });
+    });
+}
+
+fn benchmark_statement_preparation(c: &mut Criterion) {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch("CREATE TABLE files (id INTEGER PRIMARY KEY, path TEXT UNIQUE, name TEXT)")
+        .unwrap();
+    for i in 0..1000 {
+        conn.execute(
+            "INSERT INTO files (path, name) VALUES (?1, ?2)",
+            params![format!("/data/file_{}.rs", i), format!("file_{}.rs", i)],
+        )
+        .unwrap();
+    }
+
+    let mut group = c.benchmark_group("db_lookup");
+
+    group.bench_function("prepare", |b| {
+        b.iter(|| {
+            let mut stmt = conn.prepare(LOOKUP_SQL).unwrap();
+            let id: i64 = stmt
+                .query_row(params!["/data/file_500.rs"], |row| row.get(0))
+                .unwrap();
+            black_box(id);
+        });
+    });
+
+    group.bench_function("prepare_cached", |b| {
+        b.iter(|| {
+            let mut stmt = conn.prepare_cached(LOOKUP_SQL).unwrap();
+            let id: i64 = stmt
+                .query_row(params!["/data/file_500.rs"], |row| row.get(0))
+                .unwrap();
+            black_box(id);
+        });
+    });
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    benchmark_single_insert,
+    benchmark_search_by_name,
+    benchmark_statement_preparation
+);
+criterion_main!(benches);
@@ -1,10 +1,10 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId};
 use rusty_files::SearchEngine;
 use std::fs;
-use std::path::PathBuf;
+use std::path::Path;
 use tempfile::TempDir;
 
-fn create_test_files(dir: &PathBuf, count: usize) {
+fn create_test_files(dir: &Path, count: usize) {
     for i in 0..count {
         let file_path = dir.join(format!("test_file_{}.txt", i));
         fs::write(file_path, format!("content {}", i)).unwrap();
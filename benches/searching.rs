@@ -1,6 +1,10 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use rusty_files::{MatchMode, Query, SearchEngine};
+use rusty_files::search::SearchExecutor;
+use rusty_files::storage::{Database, FileBloomFilter, LruCache, NameIndex};
+use rusty_files::{FileEntry, MatchMode, Query, QueryParser, SearchConfig, SearchEngine};
 use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
 use tempfile::TempDir;
 
 fn setup_indexed_engine(file_count: usize) -> (TempDir, SearchEngine) {
@@ -83,8 +87,87 @@ fn benchmark_complex_query(c: &mut Criterion) {
     });
 }
 
+// Autocomplete fires the same filters with a growing pattern; only the first query plans.
+fn benchmark_repeated_structure_setup(c: &mut Criterion) {
+    let executor = SearchExecutor::new(
+        Arc::new(Database::in_memory(2).unwrap()),
+        Arc::new(SearchConfig::default()),
+        Arc::new(LruCache::new(100)),
+        Arc::new(FileBloomFilter::default()),
+    );
+    let queries: Vec<Query> = ["n", "no", "not", "note", "notes"]
+        .iter()
+        .map(|prefix| {
+            QueryParser::parse(&format!("{} ext:md,txt is:file mode:fuzzy", prefix)).unwrap()
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("query_plan_setup");
+    group.bench_function("uncached", |b| {
+        b.iter(|| {
+            for query in &queries {
+                black_box(executor.plan(query));
+            }
+        });
+    });
+    group.bench_function("cached", |b| {
+        b.iter(|| {
+            for query in &queries {
+                black_box(executor.cached_plan(query));
+            }
+        });
+    });
+    group.finish();
+}
+
+// A substring of a rare name makes SQLite scan every row; the name index scans its own
+// compact copy instead.
+fn benchmark_name_index(c: &mut Criterion) {
+    let db = Arc::new(Database::in_memory(2).unwrap());
+    let entries: Vec<FileEntry> = (0..500_000)
+        .map(|i| FileEntry::new(PathBuf::from(format!("/data/dir_{}/file_{}.rs", i % 1000, i))))
+        .collect();
+    for chunk in entries.chunks(10_000) {
+        db.insert_files_batch(chunk).unwrap();
+    }
+    let names = Arc::new(NameIndex::new(Arc::clone(&db)));
+    names.refresh().unwrap();
+    let executor = || {
+        SearchExecutor::new(
+            Arc::clone(&db),
+            Arc::new(SearchConfig::default()),
+            Arc::new(LruCache::new(100)),
+            Arc::new(FileBloomFilter::default()),
+        )
+    };
+    let (sql, indexed) = (executor(), executor().with_name_index(names));
+    let queries = [
+        QueryParser::parse("file_499999").unwrap(),
+        QueryParser::parse("scope:path dir_999/file_4999").unwrap(),
+    ];
+
+    let mut group = c.benchmark_group("name_index_500k");
+    group.bench_function("sqlite", |b| {
+        b.iter(|| {
+            for query in &queries {
+                black_box(sql.execute(query).unwrap());
+            }
+        });
+    });
+    group.bench_function("in_memory", |b| {
+        b.iter(|| {
+            for query in &queries {
+                black_box(indexed.execute(query).unwrap());
+            }
+        });
+    });
+    group.finish();
+}
+
 criterion_group!(
     benches,
+    benchmark_repeated_structure_setup,
+    benchmark_name_index,
     benchmark_simple_search,
     benchmark_pattern_search,
     benchmark_fuzzy_search,
@@ -0,0 +1,60 @@
+use rusty_files::server::client::{ClientError, PeerClient};
+use std::time::Duration;
+
+use crate::output::OutputFormatter;
+
+/// How long `--follow` waits before reconnecting to a server that went away.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Prints a server's activity after `since`. With `follow`, keeps printing new events as
+/// they happen; a dropped connection is resumed from the last event printed, so none are
+/// lost unless the server no longer buffers them, which it reports as a gap.
+pub fn run(
+    formatter: &OutputFormatter,
+    server: &str,
+    api_key: Option<String>,
+    since: Option<u64>,
+    follow: bool,
+    json: bool,
+) -> Result<(), ClientError> {
+    let print = |record: &rusty_files::server::activity::ActivityRecord| {
+        if json {
+            if let Ok(line) = serde_json::to_string(record) {
+                formatter.print_line(&line);
+            }
+        } else {
+            formatter.print_activity(record);
+        }
+    };
+
+    actix_rt::System::new().block_on(async move {
+        let client = PeerClient::connect(server, api_key);
+        if !follow {
+            for record in client.activity(since).await?.events {
+                print(&record);
+            }
+            return Ok(());
+        }
+
+        let mut since = since;
+        let mut first = true;
+        loop {
+            let from = since;
+            let followed = client
+                .follow_activity(from, |record| {
+                    since = Some(record.id);
+                    print(&record);
+                })
+                .await;
+            match followed {
+                // Refused, or not there to begin with: retrying would not help.
+                Err(err @ ClientError::Status { .. }) => return Err(err),
+                Err(err @ ClientError::Request(_)) if first => return Err(err),
+                Err(err) => eprintln!("Connection lost ({}); reconnecting", err),
+                Ok(()) => eprintln!("Server closed the connection; reconnecting"),
+            }
+            first = false;
+            actix_rt::time::sleep(RECONNECT_DELAY).await;
+        }
+    })
+}
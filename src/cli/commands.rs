@@ -1,26 +1,213 @@
-use crate::output::OutputFormatter;
-use rusty_files::core::{Result, SearchEngine};
-use rusty_files::search::QueryParser;
-use indicatif::{ProgressBar, ProgressStyle};
-use std::path::PathBuf;
+use crate::output::{FormatOptions, OutputFormatter, SharedBuffer};
+use crate::pager::Pager;
+use crate::theme::Theme;
+use crate::ExportFormat;
+use chrono::{DateTime, Utc};
+use rusty_files::storage::settings::keys;
+use rusty_files::utils::{find_workspace_root, normalize_path};
+use rusty_files::{
+    AuditEventType, CancellationToken, FileEntry, IndexManifest, LeaseWait, ListingSort,
+    MaintenanceScheduler, Pagination, PathLayout, Progress, Query, QueryParser, RequestId, Result,
+    ResultLimit, RootProgressCallback, ScriptInfo, SearchConfig, SearchEngine, SearchError,
+    SearchResult, SliceOutcome, SortKey, UpdateStats, WatchEvent, WatchOptions, WriteLeaseGuard,
+};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::sync::mpsc::RecvTimeoutError;
 use std::sync::Mutex;
+use std::time::Duration;
+
+pub fn open_engine(
+    index_path: &Path,
+    create_if_missing: bool,
+    in_memory: bool,
+    config: SearchConfig,
+) -> Result<SearchEngine> {
+    if in_memory {
+        SearchEngine::in_memory_with_config(config)
+    } else if create_if_missing {
+        SearchEngine::with_config(index_path, config)
+    } else {
+        SearchEngine::open_existing_with_config(index_path, config)
+    }
+}
+
+pub fn open_error_message(err: &SearchError) -> String {
+    match err {
+        SearchError::IndexNotFound(path) => format!(
+            "no index found at {} — run `filesearch index <dir>` first",
+            path.display()
+        ),
+        err => format!("Failed to initialize search engine: {}", err),
+    }
+}
+
+// Streaming walks report total = 0 until the walk is done, so only show a ratio when known.
+fn progress_message(progress: &Progress) -> String {
+    if progress.total > 0 {
+        format!(
+            "{}/{} files ({}%)",
+            progress.current, progress.total, progress.percentage as u64
+        )
+    } else {
+        format!("{} files", progress.current)
+    }
+}
+
+/// A bar for work measured in known totals, such as pages or content blobs.
+fn page_progress_bar() -> ProgressBar {
+    let pb = ProgressBar::new(0);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("[{elapsed_precise}] {bar:40.green} {pos}/{len} {msg}")
+            .unwrap(),
+    );
+    pb
+}
+
+fn parse_query(query: &str, params: &[String]) -> Result<Query> {
+    if params.is_empty() {
+        QueryParser::parse(query)
+    } else {
+        let params: Vec<&str> = params.iter().map(String::as_str).collect();
+        QueryParser::parse_template(query, &params)
+    }
+}
+
+/// Where a search looks when its query has no `under:` of its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchRoot {
+    /// The whole index.
+    Global,
+    /// The workspace around the working directory, or the whole index outside any workspace.
+    Workspace,
+    Path(PathBuf),
+}
+
+impl SearchRoot {
+    pub fn from_flags(global: bool, root: Option<PathBuf>) -> Self {
+        match root {
+            Some(dir) => SearchRoot::Path(dir),
+            None if global => SearchRoot::Global,
+            None => SearchRoot::Workspace,
+        }
+    }
+
+    /// The canonical directory to search, with relative paths taken from `cwd`.
+    pub fn resolve(&self, cwd: &Path, markers: &[String]) -> Option<PathBuf> {
+        let dir = match self {
+            SearchRoot::Global => return None,
+            SearchRoot::Workspace => find_workspace_root(cwd, markers)?,
+            SearchRoot::Path(dir) => cwd.join(dir),
+        };
+        Some(normalize_path(dir))
+    }
+}
+
+/// Limits `query` to `root` unless it already has an `under:`, which is resolved against
+/// `cwd` like `--root`.
+pub fn scope_query(query: &mut Query, root: &SearchRoot, cwd: &Path, markers: &[String]) {
+    query.under = match query.under.take() {
+        Some(dir) => SearchRoot::Path(dir).resolve(cwd, markers),
+        None => root.resolve(cwd, markers),
+    };
+}
+
+fn confirm(prompt: &str) -> Result<bool> {
+    print!("{} [y/N] ", prompt);
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+fn sensitive_omissions(omitted: usize) -> String {
+    format!("Left out {} sensitive entries; pass --include-sensitive to export them", omitted)
+}
+
+/// Targets the index recorded for the links among `entries`.
+fn link_targets<'a>(
+    engine: &SearchEngine,
+    entries: impl IntoIterator<Item = &'a FileEntry>,
+) -> Result<HashMap<i64, PathBuf>> {
+    let ids: Vec<i64> = entries
+        .into_iter()
+        .filter(|entry| entry.is_symlink)
+        .filter_map(|entry| entry.id)
+        .collect();
+    engine.link_targets(&ids)
+}
+
+fn search_json(query: &Query, limit: ResultLimit, results: &[SearchResult]) -> serde_json::Value {
+    serde_json::json!({
+        "effective_query": query.effective(),
+        "effective_limit": limit.effective_limit,
+        "limit_clamped": limit.limit_clamped,
+        "results": results,
+    })
+}
 
 pub struct CommandExecutor {
     engine: Arc<Mutex<SearchEngine>>,
     formatter: OutputFormatter,
+    /// Identifies this invocation's searches in the slow-query log.
+    request_id: RequestId,
+    pager: Option<Pager>,
 }
 
 impl CommandExecutor {
-    pub fn new(engine: SearchEngine, use_colors: bool, verbose: bool) -> Self {
+    pub fn new(engine: SearchEngine, theme: Theme, verbose: bool) -> Self {
         Self {
             engine: Arc::new(Mutex::new(engine)),
-            formatter: OutputFormatter::new(use_colors, verbose),
+            formatter: OutputFormatter::new(theme, verbose),
+            request_id: RequestId::new(),
+            pager: None,
+        }
+    }
+
+    pub fn with_format(mut self, format: FormatOptions) -> Self {
+        self.formatter = self.formatter.with_format(format);
+        self
+    }
+
+    pub fn with_pager(mut self, pager: Option<Pager>) -> Self {
+        self.pager = pager;
+        self
+    }
+
+    /// Collects what `print` writes and sends it through the pager when it is long enough.
+    fn paged(&self, print: impl FnOnce()) {
+        let Some(pager) = &self.pager else {
+            return print();
+        };
+        let buffer = SharedBuffer::default();
+        let previous = self.formatter.redirect(Box::new(buffer.clone()));
+        print();
+        self.formatter.redirect(previous);
+
+        let output = buffer.take();
+        if !pager.should_page(&output) {
+            return self.formatter.write_raw(&output);
+        }
+        if let Err(err) = pager.page(&output) {
+            log::debug!("cannot start pager: {}", err);
+            self.formatter.write_raw(&output);
         }
     }
 
-    pub fn index(&self, path: PathBuf, show_progress: bool) -> Result<()> {
+    pub fn index(
+        &self,
+        path: PathBuf,
+        show_progress: bool,
+        quick: bool,
+        lease: Option<LeaseWait>,
+    ) -> Result<()> {
         let engine = self.engine.lock().unwrap();
+        let _lease = self.write_lease(&engine, lease)?;
 
         self.formatter.print_header(&format!(
             "Indexing directory: {}",
@@ -40,89 +227,504 @@ impl CommandExecutor {
         };
 
         let pb_clone = progress_bar.clone();
-        let callback = move |progress: rusty_files::core::types::Progress| {
+        let callback = move |progress: Progress| {
             if let Some(ref pb) = pb_clone {
-                pb.set_message(format!(
-                    "{}/{} files ({}%)",
-                    progress.current, progress.total, progress.percentage as u64
-                ));
+                pb.set_message(progress_message(&progress));
             }
         };
 
-        let count = engine.index_directory(&path, Some(Box::new(callback)))?;
+        let report = if quick {
+            engine.index_quick(&path, Some(Box::new(callback)))?
+        } else {
+            engine.index_directory_with_report(&path, Some(Box::new(callback)))?
+        };
 
         if let Some(pb) = progress_bar {
             pb.finish_with_message("Indexing complete");
         }
 
+        self.formatter.print_skipped(&report.skipped, report.failed_batches);
+        self.formatter.print_content_skipped(&report.content_skipped);
+        self.formatter.print_possibly_inconsistent(&report.possibly_inconsistent);
         self.formatter.print_success(&format!(
             "Successfully indexed {} files",
-            count
+            report.indexed
         ));
+        if quick && !engine.enrichment_progress()?.is_empty() {
+            self.formatter.print_info(&format!(
+                "Content is not searchable yet; run `filesearch enrich {}`",
+                path.display()
+            ));
+        }
 
         Ok(())
     }
 
-    pub fn update(&self, path: PathBuf, show_progress: bool) -> Result<()> {
+    pub fn enrich(&self, path: Option<PathBuf>, show_progress: bool) -> Result<()> {
         let engine = self.engine.lock().unwrap();
 
-        self.formatter.print_header(&format!(
-            "Updating index for: {}",
-            path.display()
-        ));
+        self.formatter.print_header(&match &path {
+            Some(path) => format!("Enriching: {}", path.display()),
+            None => "Enriching quick-indexed roots".to_string(),
+        });
 
-        let progress_bar = if show_progress {
-            let pb = ProgressBar::new_spinner();
+        let progress_bar = show_progress.then(|| {
+            let pb = ProgressBar::new(0);
             pb.set_style(
-                ProgressStyle::default_spinner()
-                    .template("{spinner:.green} [{elapsed_precise}] {msg}")
+                ProgressStyle::default_bar()
+                    .template("[{elapsed_precise}] {bar:40.green} {pos}/{len} {msg}")
                     .unwrap(),
             );
-            Some(pb)
-        } else {
-            None
-        };
+            pb
+        });
 
         let pb_clone = progress_bar.clone();
-        let callback = move |progress: rusty_files::core::types::Progress| {
+        let callback = move |progress: Progress| {
             if let Some(ref pb) = pb_clone {
-                pb.set_message(format!("{}", progress.message));
+                pb.set_length(progress.total as u64);
+                pb.set_position(progress.current as u64);
             }
         };
 
-        let stats = engine.update_index(&path, Some(Box::new(callback)))?;
+        let stats = engine.enrich(
+            path.as_deref(),
+            Some(Box::new(callback)),
+            &CancellationToken::new(),
+        )?;
 
         if let Some(pb) = progress_bar {
+            pb.finish_with_message("Enrichment complete");
+        }
+
+        self.formatter
+            .print_success(&format!("Read the content of {} files", stats.enriched));
+        if stats.remaining > 0 {
+            self.formatter
+                .print_info(&format!("{} files are still pending", stats.remaining));
+        }
+
+        Ok(())
+    }
+
+    pub fn index_manifest(
+        &self,
+        manifest_path: PathBuf,
+        parallel: Option<usize>,
+        show_progress: bool,
+        lease: Option<LeaseWait>,
+    ) -> Result<()> {
+        let mut manifest = IndexManifest::from_file(&manifest_path)?;
+        if parallel.is_some() {
+            manifest.parallel = parallel;
+        }
+
+        let engine = self.engine.lock().unwrap();
+        let _lease = self.write_lease(&engine, lease)?;
+
+        self.formatter.print_header(&format!(
+            "Indexing {} roots from manifest: {}",
+            manifest.roots.len(),
+            manifest_path.display()
+        ));
+
+        let progress_bars: HashMap<PathBuf, ProgressBar> = if show_progress {
+            let multi = MultiProgress::new();
+            let style = ProgressStyle::default_spinner()
+                .template("{spinner:.green} [{elapsed_precise}] {prefix}: {msg}")
+                .unwrap();
+            manifest
+                .roots
+                .iter()
+                .map(|root| {
+                    let pb = multi.add(ProgressBar::new_spinner());
+                    pb.set_style(style.clone());
+                    pb.set_prefix(root.path.display().to_string());
+                    pb.set_message("waiting");
+                    (root.path.clone(), pb)
+                })
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        let bars = Arc::new(progress_bars);
+        let callback_bars = Arc::clone(&bars);
+        let callback: RootProgressCallback = Arc::new(move |root, progress| {
+            if let Some(pb) = callback_bars.get(root) {
+                pb.set_message(progress_message(&progress));
+            }
+        });
+
+        let reports = engine.index_with_manifest(manifest, Some(callback))?;
+
+        for pb in bars.values() {
+            pb.finish();
+        }
+
+        self.formatter.print_root_reports(&reports);
+
+        let failed = reports.iter().filter(|report| !report.is_success()).count();
+        let indexed: usize = reports.iter().map(|report| report.indexed).sum();
+        if failed > 0 {
+            self.formatter.print_warning(&format!(
+                "{} of {} roots failed to index",
+                failed,
+                reports.len()
+            ));
+        }
+        self.formatter.print_success(&format!(
+            "Successfully indexed {} files across {} roots",
+            indexed,
+            reports.len() - failed
+        ));
+
+        Ok(())
+    }
+
+    /// Updates `paths`, or with `all` every root recorded in the index, one after another.
+    /// A failed root is reported and the rest still run, unless `continue_on_error` is off:
+    /// then the update stops there and returns its error.
+    pub fn update(
+        &self,
+        paths: Vec<PathBuf>,
+        all: bool,
+        continue_on_error: bool,
+        show_progress: bool,
+        lease: Option<LeaseWait>,
+    ) -> Result<()> {
+        let engine = self.engine.lock().unwrap();
+        let _lease = self.write_lease(&engine, lease)?;
+
+        let paths: Vec<PathBuf> = if all {
+            engine.indexed_roots()?.into_iter().map(|root| root.path).collect()
+        } else {
+            paths
+        };
+        if paths.is_empty() {
+            return Err(SearchError::Configuration(
+                "no indexed roots are recorded yet; run `filesearch index <dir>` first".to_string(),
+            ));
+        }
+
+        match paths.as_slice() {
+            [path] => self
+                .formatter
+                .print_header(&format!("Updating index for: {}", path.display())),
+            _ => self
+                .formatter
+                .print_header(&format!("Updating {} roots", paths.len())),
+        }
+
+        // A fresh spinner for each root, so its timer and message start over.
+        let current: Arc<Mutex<Option<(PathBuf, ProgressBar)>>> = Arc::default();
+        let callback_current = Arc::clone(&current);
+        let callback: RootProgressCallback = Arc::new(move |root, progress| {
+            let mut current = callback_current.lock().unwrap();
+            if current.as_ref().map(|(path, _)| path.as_path()) != Some(root) {
+                if let Some((_, pb)) = current.take() {
+                    pb.finish_with_message("Update complete");
+                }
+                let pb = ProgressBar::new_spinner();
+                pb.set_style(
+                    ProgressStyle::default_spinner()
+                        .template("{spinner:.green} [{elapsed_precise}] {prefix}: {msg}")
+                        .unwrap(),
+                );
+                pb.set_prefix(root.display().to_string());
+                *current = Some((root.to_path_buf(), pb));
+            }
+            if let Some((_, pb)) = current.as_ref() {
+                pb.set_message(progress_message(&progress));
+            }
+        });
+
+        let mut results = engine.update_roots(
+            &paths,
+            continue_on_error,
+            show_progress.then_some(callback),
+        )?;
+
+        if let Some((_, pb)) = current.lock().unwrap().take() {
             pb.finish_with_message("Update complete");
         }
 
-        self.formatter.print_update_stats(&stats);
-        self.formatter.print_success("Index updated successfully");
+        if paths.len() == 1 {
+            let (_, result) = results.remove(0);
+            self.formatter.print_update_stats(&result?);
+            self.formatter.print_success("Index updated successfully");
+            return Ok(());
+        }
+
+        self.formatter.print_update_reports(&results);
+        let mut total = UpdateStats::default();
+        for stats in results.iter().filter_map(|(_, result)| result.as_ref().ok()) {
+            total.merge(stats);
+        }
+        self.formatter.print_update_stats(&total);
+
+        let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+        if failed > 0 {
+            self.formatter.print_warning(&format!(
+                "{} of {} roots failed to update",
+                failed,
+                results.len()
+            ));
+        }
+        if results.len() < paths.len() {
+            self.formatter.print_warning(&format!(
+                "Stopped after the failure; {} roots were not updated",
+                paths.len() - results.len()
+            ));
+            if let Some((_, Err(err))) = results.pop() {
+                return Err(err);
+            }
+        }
+        self.formatter.print_success(&format!(
+            "Updated {} roots",
+            results.len() - failed
+        ));
+
+        Ok(())
+    }
+
+    /// Takes the index's write lease for a command, saying so while another writer holds
+    /// it. `None` waits as long as `write_lease_wait_secs` allows.
+    fn write_lease(
+        &self,
+        engine: &SearchEngine,
+        lease: Option<LeaseWait>,
+    ) -> Result<WriteLeaseGuard> {
+        let wait = lease.unwrap_or_else(|| {
+            LeaseWait::Wait(Duration::from_secs(engine.get_config().write_lease_wait_secs))
+        });
+        engine.acquire_write_lease(wait, |holder| {
+            self.formatter.print_info(&format!(
+                "Waiting for writer pid {} on {}...",
+                holder.pid, holder.hostname
+            ))
+        })
+    }
+
+    /// Parses `query` and limits it to `root`, noting the directory searched unless `quiet`.
+    fn scoped_query(
+        &self,
+        engine: &SearchEngine,
+        query: &str,
+        params: &[String],
+        root: &SearchRoot,
+        quiet: bool,
+    ) -> Result<Query> {
+        let mut parsed = parse_query(query, params)?;
+        let explicit = parsed.under.is_some();
+        let cwd = std::env::current_dir()?;
+        scope_query(&mut parsed, root, &cwd, &engine.get_config().workspace_markers);
+
+        if let (Some(dir), false) = (&parsed.under, quiet) {
+            self.formatter.print_info(&if explicit || *root != SearchRoot::Workspace {
+                format!("Searching under {}", dir.display())
+            } else {
+                format!(
+                    "Searching workspace {} (use --global for the whole index)",
+                    dir.display()
+                )
+            });
+        }
+
+        Ok(parsed)
+    }
+
+    /// Shebang details of the scripts among `results`, fetched only for verbose output.
+    fn result_scripts(
+        &self,
+        engine: &SearchEngine,
+        results: &[SearchResult],
+    ) -> Result<HashMap<i64, ScriptInfo>> {
+        if !self.formatter.is_verbose() {
+            return Ok(HashMap::new());
+        }
+        let ids: Vec<i64> = results.iter().filter_map(|result| result.file.id).collect();
+        engine.scripts(&ids)
+    }
+
+    /// Targets recorded in the index for the links among `results`, shown after their names.
+    fn result_link_targets(
+        &self,
+        engine: &SearchEngine,
+        results: &[SearchResult],
+    ) -> Result<HashMap<i64, PathBuf>> {
+        link_targets(engine, results.iter().map(|result| &result.file))
+    }
+
+    /// With `params`, `query` is a template whose `{}` placeholders they fill. `context`
+    /// and `sort` override the query's `context:` and `sort:`.
+    pub fn search(
+        &self,
+        query: String,
+        params: &[String],
+        root: &SearchRoot,
+        json: bool,
+        context: Option<usize>,
+        sort: Option<SortKey>,
+    ) -> Result<()> {
+        let engine = self.engine.lock().unwrap();
+
+        let mut parsed_query = self.scoped_query(&engine, &query, params, root, json)?;
+        if let Some(lines) = context {
+            parsed_query.context_lines = lines;
+        }
+        if sort.is_some() {
+            parsed_query.sort_by = sort;
+        }
+        let limit = engine.result_limit(&parsed_query)?;
+        let results = engine.search_with_query_traced(&parsed_query, Some(&self.request_id))?;
+
+        if json {
+            let output = search_json(&parsed_query, limit, &results);
+            let content = serde_json::to_string_pretty(&output)
+                .map_err(|e| SearchError::Configuration(e.to_string()))?;
+            self.formatter.print_line(&content);
+            return Ok(());
+        }
+
+        let scripts = self.result_scripts(&engine, &results)?;
+        let link_targets = self.result_link_targets(&engine, &results)?;
+        self.paged(|| {
+            self.formatter
+                .print_search_results(&results, &query, &scripts, &link_targets);
+            self.formatter.print_tag_filter(&parsed_query.tags);
+            for warning in &parsed_query.warnings {
+                self.formatter.print_warning(warning);
+            }
+            if limit.limit_clamped {
+                self.formatter.print_warning(&format!(
+                    "Requested limit exceeds the configured maximum; showing at most {} results",
+                    limit.effective_limit
+                ));
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Tags every result of `query` with `tag`, asking first unless `yes` is set.
+    pub fn tag_results(
+        &self,
+        query: String,
+        params: &[String],
+        root: &SearchRoot,
+        tag: String,
+        yes: bool,
+    ) -> Result<()> {
+        let engine = self.engine.lock().unwrap();
+        let parsed_query = self.scoped_query(&engine, &query, params, root, false)?;
+        let results = engine.search_with_query_traced(&parsed_query, Some(&self.request_id))?;
+
+        let scripts = self.result_scripts(&engine, &results)?;
+        let link_targets = self.result_link_targets(&engine, &results)?;
+        self.formatter
+            .print_search_results(&results, &query, &scripts, &link_targets);
+        if results.is_empty() {
+            return Ok(());
+        }
+
+        let prompt = format!("Tag {} files as '{}'?", results.len(), tag);
+        if !yes && !confirm(&prompt)? {
+            self.formatter.print_info("No files were tagged");
+            return Ok(());
+        }
+
+        let ids: Vec<i64> = results.iter().filter_map(|result| result.file.id).collect();
+        let tagged = engine.tag_files(&ids, &tag)?;
+        self.formatter
+            .print_success(&format!("Tagged {} files as '{}'", tagged, tag));
 
         Ok(())
     }
 
-    pub fn search(&self, query: String) -> Result<()> {
+    pub fn list_tags(&self) -> Result<()> {
         let engine = self.engine.lock().unwrap();
+        let tags = engine.list_tags()?;
 
-        let parsed_query = QueryParser::parse(&query)?;
-        let results = engine.search_with_query(&parsed_query)?;
+        self.formatter.print_tags(&tags);
 
-        self.formatter.print_search_results(&results, &query);
+        Ok(())
+    }
+
+    pub fn meta_get(&self, key: &str) -> Result<()> {
+        let engine = self.engine.lock().unwrap();
+        match engine.setting(key)? {
+            Some(setting) => self.formatter.print_line(&setting.value),
+            None => self.formatter.print_info(&format!("No setting named {}", key)),
+        }
+        Ok(())
+    }
+
+    /// Without `force`, only `user.*` keys may be set: the index reads every other key and
+    /// a hand-edited value can leave it unreadable.
+    pub fn meta_set(&self, key: &str, value: &str, force: bool) -> Result<()> {
+        if !force && !keys::is_user_settable(key) {
+            return Err(SearchError::Configuration(format!(
+                "{} is managed by the index; set user.* keys or pass --force",
+                key
+            )));
+        }
+
+        let engine = self.engine.lock().unwrap();
+        engine.set_setting(key, value)?;
+        self.formatter.print_success(&format!("Set {}", key));
+        Ok(())
+    }
 
+    pub fn meta_list(&self) -> Result<()> {
+        let engine = self.engine.lock().unwrap();
+        self.formatter.print_settings(&engine.settings()?);
         Ok(())
     }
 
+    /// Expects an engine opened in memory; the index is discarded when the command exits.
+    pub fn search_in_memory(
+        &self,
+        root: PathBuf,
+        query: String,
+        params: &[String],
+        json: bool,
+        context: Option<usize>,
+        sort: Option<SortKey>,
+    ) -> Result<()> {
+        {
+            let engine = self.engine.lock().unwrap();
+            let report = engine.index_directory_with_report(&root, None)?;
+            if !json {
+                self.formatter.print_skipped(&report.skipped, report.failed_batches);
+            }
+        }
+
+        self.search(query, params, &SearchRoot::Global, json, context, sort)
+    }
+
     pub fn stats(&self) -> Result<()> {
         let engine = self.engine.lock().unwrap();
         let stats = engine.get_stats()?;
 
         self.formatter.print_index_stats(&stats);
+        self.formatter.print_capabilities(engine.capabilities());
+        if self.formatter.is_verbose() {
+            self.formatter.print_header("Settings");
+            self.formatter.print_line("");
+            self.formatter.print_settings(&engine.settings()?);
+        }
 
         Ok(())
     }
 
-    pub fn verify(&self, path: PathBuf) -> Result<()> {
+    /// With `list_problems`, prints the missing and outdated paths, or writes them to the
+    /// file it names, one `<kind>\t<path>` line each.
+    pub fn verify(
+        &self,
+        path: PathBuf,
+        show_progress: bool,
+        list_problems: Option<Option<PathBuf>>,
+    ) -> Result<()> {
         let engine = self.engine.lock().unwrap();
 
         self.formatter.print_header(&format!(
@@ -130,10 +732,57 @@ impl CommandExecutor {
             path.display()
         ));
 
-        let stats = engine.verify_index(&path)?;
+        let progress_bar = if show_progress {
+            let pb = ProgressBar::new(0);
+            pb.set_style(
+                ProgressStyle::default_bar()
+                    .template("[{elapsed_precise}] {bar:40.green} {pos}/{len} {msg}")
+                    .unwrap(),
+            );
+            Some(pb)
+        } else {
+            None
+        };
+
+        let pb_clone = progress_bar.clone();
+        let callback = move |progress: Progress| {
+            if let Some(ref pb) = pb_clone {
+                pb.set_length(progress.total as u64);
+                pb.set_position(progress.current as u64);
+            }
+        };
+
+        let stats = engine.verify_index(&path, Some(Box::new(callback)))?;
+
+        if let Some(pb) = progress_bar {
+            pb.finish_with_message("Verification complete");
+        }
 
         self.formatter.print_verification_stats(&stats);
 
+        match &list_problems {
+            Some(Some(file)) => {
+                let mut out = std::io::BufWriter::new(std::fs::File::create(file)?);
+                for problem in &stats.problems {
+                    writeln!(out, "{}\t{}", problem.kind.as_str(), problem.path.display())?;
+                }
+                out.flush()?;
+                self.formatter.print_info(&format!(
+                    "Wrote {} problem paths to {}",
+                    stats.problems.len(),
+                    file.display()
+                ));
+            }
+            Some(None) => self.formatter.print_verification_problems(&stats),
+            None => {}
+        }
+        if list_problems.is_some() && stats.unlisted_problems() > 0 {
+            self.formatter.print_warning(&format!(
+                "{} more problem paths were not listed",
+                stats.unlisted_problems()
+            ));
+        }
+
         if stats.health_percentage() < 80.0 {
             self.formatter.print_warning(
                 "Index health is below 80%. Consider running 'update' command.",
@@ -142,10 +791,30 @@ impl CommandExecutor {
             self.formatter.print_success("Index is in good health");
         }
 
+        if let Some(drift) = engine.stats_drift()? {
+            let (stored, counted) = (drift.stored, drift.counted);
+            let differences: Vec<String> = [
+                ("files", stored.files, counted.files),
+                ("directories", stored.directories, counted.directories),
+                ("total size", stored.total_size, counted.total_size),
+                ("content indexed", stored.content_indexed, counted.content_indexed),
+            ]
+            .into_iter()
+            .filter(|(_, stored, counted)| stored != counted)
+            .map(|(name, stored, counted)| format!("{} {} (counted {})", name, stored, counted))
+            .collect();
+            self.formatter.print_warning(&format!(
+                "Index statistics had drifted: {}",
+                differences.join(", ")
+            ));
+            engine.recompute_stats()?;
+            self.formatter.print_info("Recomputed the index statistics");
+        }
+
         Ok(())
     }
 
-    pub fn watch(&self, path: PathBuf) -> Result<()> {
+    pub fn watch(&self, path: PathBuf, initial_scan: bool, recursive: bool) -> Result<()> {
         let mut engine = self.engine.lock().unwrap();
 
         self.formatter.print_header(&format!(
@@ -153,16 +822,76 @@ impl CommandExecutor {
             path.display()
         ));
 
-        engine.start_watching(&path)?;
+        let options = WatchOptions {
+            initial_scan,
+            recursive,
+            ..WatchOptions::default()
+        };
+        engine.start_watching_with_options(&path, options)?;
+        let events = engine.subscribe_watch_events().ok_or_else(|| {
+            SearchError::NotInitialized("Watcher did not start".to_string())
+        })?;
 
         self.formatter.print_success("Watch started. Press Ctrl+C to stop.");
 
-        std::thread::park();
+        let mut maintenance = engine
+            .config()
+            .auto_maintenance
+            .then(|| MaintenanceScheduler::new(engine.config()));
+        loop {
+            let event = match maintenance.as_mut() {
+                Some(scheduler) => match events.recv_timeout(scheduler.poll_interval()) {
+                    Ok(event) => event,
+                    Err(RecvTimeoutError::Timeout) => {
+                        self.run_idle_maintenance(&engine, scheduler);
+                        continue;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break,
+                },
+                None => match events.recv() {
+                    Ok(event) => event,
+                    Err(_) => break,
+                },
+            };
+
+            match event {
+                WatchEvent::Degraded { root, error } => self.formatter.print_warning(&format!(
+                    "Watch on {} degraded: {} (retrying)",
+                    root.display(),
+                    error
+                )),
+                WatchEvent::Recovered { root, caught_up } => {
+                    self.formatter.print_success(&format!(
+                        "Watch on {} recovered ({} changes caught up)",
+                        root.display(),
+                        caught_up
+                    ))
+                }
+                WatchEvent::Failed { root, error } => {
+                    return Err(SearchError::Watch(format!(
+                        "Watch on {} failed: {}",
+                        root.display(),
+                        error
+                    )));
+                }
+                WatchEvent::InitialScanProgress { progress, .. } => {
+                    self.formatter.print_info(&progress.message)
+                }
+                WatchEvent::InitialScanComplete { root, indexed } => {
+                    self.formatter.print_success(&format!(
+                        "Initial scan of {} complete ({} changes indexed)",
+                        root.display(),
+                        indexed
+                    ))
+                }
+                WatchEvent::IndexChanged { .. } => {}
+            }
+        }
 
         Ok(())
     }
 
-    pub fn clear(&self, confirm: bool) -> Result<()> {
+    pub fn clear(&self, confirm: bool, include_audit: bool, undo: bool) -> Result<()> {
         if !confirm {
             self.formatter.print_warning(
                 "This will delete all indexed data. Use --confirm to proceed.",
@@ -174,13 +903,174 @@ impl CommandExecutor {
 
         self.formatter.print_header("Clearing index...");
 
-        engine.clear_index()?;
+        let cleared = if undo {
+            engine.clear_index()
+        } else {
+            engine.clear_index_without_undo()
+        };
+        if let Err(SearchError::UndoSnapshotTooLarge(limit)) = &cleared {
+            self.formatter.print_warning(&format!(
+                "The undo snapshot would exceed {}; rerun with --no-undo to clear without one",
+                self.formatter.format().size(*limit)
+            ));
+        }
+        cleared?;
+
+        if include_audit {
+            let removed = engine.clear_audit_log()?;
+            self.formatter
+                .print_info(&format!("Removed {} audit records", removed));
+        }
 
         self.formatter.print_success("Index cleared successfully");
 
         Ok(())
     }
 
+    pub fn list_undo_snapshots(&self) -> Result<()> {
+        let engine = self.engine.lock().unwrap();
+        let snapshots = engine.undoable_operations()?;
+
+        self.formatter.print_undo_snapshots(&snapshots);
+
+        Ok(())
+    }
+
+    pub fn undo(&self) -> Result<()> {
+        let engine = self.engine.lock().unwrap();
+
+        match engine.undo_last()? {
+            Some(report) => self.formatter.print_success(&format!(
+                "Restored {} entries from the {} snapshot taken {}",
+                report.restored,
+                report.snapshot.operation,
+                self.formatter.format().timestamp(report.snapshot.created_at)
+            )),
+            None => self.formatter.print_info("Nothing to undo"),
+        }
+
+        Ok(())
+    }
+
+    pub fn audit(
+        &self,
+        path: PathBuf,
+        time_range: (Option<DateTime<Utc>>, Option<DateTime<Utc>>),
+        event_types: Vec<AuditEventType>,
+        limit: usize,
+    ) -> Result<()> {
+        let engine = self.engine.lock().unwrap();
+
+        // Watched roots are canonicalized before events are recorded.
+        let path = dunce::canonicalize(&path).unwrap_or(path);
+        let records = engine.query_audit_log(Some(&path), time_range, &event_types, 0, limit)?;
+
+        self.formatter.print_audit_log(&records, &path);
+
+        Ok(())
+    }
+
+    pub fn save_search(&self, name: String, query: String, sort: Option<ListingSort>) -> Result<()> {
+        let engine = self.engine.lock().unwrap();
+        let saved = engine.save_search(&name, &query, sort)?;
+
+        self.formatter
+            .print_success(&format!("Saved search '{}': {}", saved.name, saved.query));
+
+        Ok(())
+    }
+
+    pub fn list_saved_searches(&self) -> Result<()> {
+        let engine = self.engine.lock().unwrap();
+        let searches = engine.saved_searches()?;
+
+        self.formatter.print_saved_searches(&searches);
+
+        Ok(())
+    }
+
+    pub fn run_saved_search(&self, name: String) -> Result<()> {
+        let query = {
+            let engine = self.engine.lock().unwrap();
+            engine
+                .saved_search(&name)?
+                .ok_or(SearchError::SavedSearchNotFound(name))?
+                .query
+        };
+
+        self.search(query, &[], &SearchRoot::Global, false, None, None)
+    }
+
+    pub fn delete_saved_search(&self, name: String) -> Result<()> {
+        let engine = self.engine.lock().unwrap();
+
+        if !engine.delete_saved_search(&name)? {
+            return Err(SearchError::SavedSearchNotFound(name));
+        }
+        self.formatter
+            .print_success(&format!("Deleted saved search '{}'", name));
+
+        Ok(())
+    }
+
+    pub fn smart(&self, name: String, limit: usize, offset: usize) -> Result<()> {
+        let engine = self.engine.lock().unwrap();
+        let pagination = Pagination {
+            limit: Some(limit),
+            offset,
+        };
+        let listing = engine.run_saved_as_listing(&name, pagination)?;
+        let link_targets = link_targets(&engine, &listing.entries)?;
+
+        self.formatter.print_listing(&listing, &link_targets);
+
+        Ok(())
+    }
+
+    /// Runs every enabled task once with `once`; otherwise keeps running passes whenever the
+    /// index has been idle long enough, until interrupted.
+    pub fn maintain(&self, once: bool) -> Result<()> {
+        let engine = self.engine.lock().unwrap();
+
+        if once {
+            self.formatter.print_header("Running maintenance...");
+            let tasks = engine.run_maintenance()?;
+            for task in &tasks {
+                self.formatter.print_info(&format!("Ran {}", task.as_str()));
+            }
+            self.formatter.print_success(&format!("Ran {} maintenance tasks", tasks.len()));
+            return Ok(());
+        }
+
+        self.formatter
+            .print_success("Maintaining the index while it is idle. Press Ctrl+C to stop.");
+        let mut scheduler = MaintenanceScheduler::new(engine.config());
+        loop {
+            std::thread::sleep(scheduler.poll_interval());
+            self.run_idle_maintenance(&engine, &mut scheduler);
+        }
+    }
+
+    /// Runs maintenance slices back to back until the pass finishes or stops for a reason
+    /// other than its time running out.
+    fn run_idle_maintenance(&self, engine: &SearchEngine, scheduler: &mut MaintenanceScheduler) {
+        loop {
+            match scheduler.run_slice(engine) {
+                Ok(SliceOutcome::Yielded) => continue,
+                Ok(SliceOutcome::Finished) => {
+                    self.formatter.print_info("Index maintenance finished");
+                    return;
+                }
+                Ok(SliceOutcome::NotDue | SliceOutcome::Preempted) => return,
+                Err(e) => {
+                    self.formatter
+                        .print_warning(&format!("Index maintenance failed: {}", e));
+                    return;
+                }
+            }
+        }
+    }
+
     pub fn vacuum(&self) -> Result<()> {
         let engine = self.engine.lock().unwrap();
 
@@ -193,7 +1083,141 @@ impl CommandExecutor {
         Ok(())
     }
 
-    pub fn export(&self, output_path: PathBuf, query: Option<String>) -> Result<()> {
+    pub fn rebuild_fts(&self, show_progress: bool) -> Result<()> {
+        let engine = self.engine.lock().unwrap();
+
+        self.formatter.print_header("Rebuilding full-text index...");
+
+        let progress_bar = show_progress.then(page_progress_bar);
+        let pb_clone = progress_bar.clone();
+        let callback = move |progress: Progress| {
+            if let Some(ref pb) = pb_clone {
+                pb.set_length(progress.total as u64);
+                pb.set_position(progress.current as u64);
+            }
+        };
+
+        let report = engine.rebuild_fts(Some(Box::new(callback)))?;
+
+        if let Some(pb) = progress_bar {
+            pb.finish_with_message("Rebuild complete");
+        }
+
+        self.formatter.print_success(&format!(
+            "Reindexed content of {} files ({} distinct)",
+            report.files, report.blobs
+        ));
+        if report.skipped > 0 {
+            self.formatter.print_info(&format!(
+                "Skipped {} files with no stored content; re-index them to restore it",
+                report.skipped
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn backup(&self, dest: &Path, compress: bool, show_progress: bool) -> Result<()> {
+        let engine = self.engine.lock().unwrap();
+
+        self.formatter.print_header("Backing up index...");
+
+        let progress_bar = show_progress.then(page_progress_bar);
+        let pb_clone = progress_bar.clone();
+        let callback = move |progress: Progress| {
+            if let Some(ref pb) = pb_clone {
+                pb.set_length(progress.total as u64);
+                pb.set_position(progress.current as u64);
+            }
+        };
+
+        let report = engine.backup(dest, Some(Box::new(callback)))?;
+
+        if let Some(pb) = progress_bar {
+            pb.finish_with_message("Backup complete");
+        }
+
+        let (path, size) = if compress {
+            let path = rusty_files::storage::backup::compress(&report.path)?;
+            let size = std::fs::metadata(&path).map_or(0, |m| m.len());
+            (path, size)
+        } else {
+            (report.path, report.size_bytes)
+        };
+        self.formatter.print_success(&format!(
+            "Backed up {} pages to {} ({})",
+            report.pages,
+            path.display(),
+            self.formatter.format().size(size)
+        ));
+
+        Ok(())
+    }
+
+    /// Restores into the open engine, which this process then exits without using again;
+    /// the next command to open the index sees the restored contents and migrates them if
+    /// the backup predates the current schema.
+    pub fn restore(&self, source: &Path, show_progress: bool) -> Result<()> {
+        let engine = self.engine.lock().unwrap();
+
+        self.formatter
+            .print_header(&format!("Restoring index from {}...", source.display()));
+
+        let progress_bar = show_progress.then(page_progress_bar);
+        let pb_clone = progress_bar.clone();
+        let callback = move |progress: Progress| {
+            if let Some(ref pb) = pb_clone {
+                pb.set_length(progress.total as u64);
+                pb.set_position(progress.current as u64);
+            }
+        };
+
+        let report = engine.restore(source, Some(Box::new(callback)))?;
+
+        if let Some(pb) = progress_bar {
+            pb.finish_with_message("Restore complete");
+        }
+
+        self.formatter.print_success(&format!(
+            "Restored {} pages (schema version {})",
+            report.pages, report.schema_version
+        ));
+
+        Ok(())
+    }
+
+    pub fn migrate(&self, compact: bool) -> Result<()> {
+        let engine = self.engine.lock().unwrap();
+
+        if !compact {
+            self.formatter.print_info("Index schema is up to date");
+            return Ok(());
+        }
+        if engine.path_layout() == PathLayout::Compact {
+            self.formatter.print_info("Index already uses compact path storage");
+            return Ok(());
+        }
+
+        self.formatter.print_header("Converting index to compact path storage...");
+
+        let converted = engine.convert_to_compact_paths()?;
+
+        self.formatter.print_success(&format!("Compacted {} entries", converted));
+
+        Ok(())
+    }
+
+    pub fn export(
+        &self,
+        output_path: Option<PathBuf>,
+        query: Option<String>,
+        format: Option<ExportFormat>,
+        include_sensitive: bool,
+    ) -> Result<()> {
+        let output_path = output_path.ok_or_else(|| {
+            SearchError::Configuration("--output is required when exporting search results".to_string())
+        })?;
+
         let engine = self.engine.lock().unwrap();
 
         self.formatter.print_header(&format!(
@@ -201,21 +1225,39 @@ impl CommandExecutor {
             output_path.display()
         ));
 
-        let results = if let Some(q) = query {
-            engine.search(&q)?
+        let mut results = if let Some(q) = query {
+            let parsed = QueryParser::parse(&q)?;
+            engine.search_with_query_traced(&parsed, Some(&self.request_id))?
         } else {
             vec![]
         };
+        let found = results.len();
+        if !include_sensitive {
+            results.retain(|result| !engine.sensitive().is_sensitive(&result.file.path));
+        }
 
-        let output_str = if output_path.extension().and_then(|s| s.to_str()) == Some("json") {
-            serde_json::to_string_pretty(&results)
-                .map_err(|e| rusty_files::core::error::SearchError::Configuration(e.to_string()))?
-        } else {
-            results
+        let format = format.unwrap_or(
+            if output_path.extension().and_then(|s| s.to_str()) == Some("json") {
+                ExportFormat::Json
+            } else {
+                ExportFormat::Text
+            },
+        );
+
+        let output_str = match format {
+            ExportFormat::Json => serde_json::to_string_pretty(&results)
+                .map_err(|e| SearchError::Configuration(e.to_string()))?,
+            ExportFormat::Jsonl => results
+                .iter()
+                .map(serde_json::to_string)
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(|e| SearchError::Configuration(e.to_string()))?
+                .join("\n"),
+            ExportFormat::Text => results
                 .iter()
                 .map(|r| r.file.path.display().to_string())
                 .collect::<Vec<_>>()
-                .join("\n")
+                .join("\n"),
         };
 
         std::fs::write(&output_path, output_str)?;
@@ -224,6 +1266,63 @@ impl CommandExecutor {
             "Exported {} results",
             results.len()
         ));
+        if results.len() < found {
+            self.formatter.print_warning(&sensitive_omissions(found - results.len()));
+        }
+
+        Ok(())
+    }
+
+    pub fn dump(&self, output_path: Option<PathBuf>) -> Result<()> {
+        let engine = self.engine.lock().unwrap();
+        let dump = engine.logical_dump()?;
+
+        match output_path {
+            Some(path) => std::fs::write(path, dump)?,
+            None => {
+                let stdout = std::io::stdout();
+                let mut handle = stdout.lock();
+                handle.write_all(dump.as_bytes())?;
+                handle.flush()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn export_changes(
+        &self,
+        output_path: Option<PathBuf>,
+        since: DateTime<Utc>,
+        format: Option<ExportFormat>,
+        include_sensitive: bool,
+    ) -> Result<()> {
+        if !matches!(format, None | Some(ExportFormat::Jsonl)) {
+            return Err(SearchError::Configuration(
+                "Change exports only support --format jsonl".to_string(),
+            ));
+        }
+
+        let engine = self.engine.lock().unwrap();
+
+        let export = match output_path {
+            Some(ref path) => {
+                let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+                engine.export_changes(since, file, include_sensitive)?
+            }
+            None => {
+                let stdout = std::io::stdout();
+                let mut handle = stdout.lock();
+                let export = engine.export_changes(since, &mut handle, include_sensitive)?;
+                handle.flush()?;
+                export
+            }
+        };
+
+        eprintln!("Next cursor: {}", export.cursor.to_rfc3339());
+        if export.omitted_sensitive > 0 {
+            eprintln!("{}", sensitive_omissions(export.omitted_sensitive));
+        }
 
         Ok(())
     }
@@ -232,6 +1331,7 @@ impl CommandExecutor {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rusty_files::{DateStyle, SearchConfigBuilder, SizeUnits};
     use std::fs;
     use tempfile::TempDir;
 
@@ -244,9 +1344,9 @@ mod tests {
 
         let index_path = temp_dir.path().join("index.db");
         let engine = SearchEngine::new(&index_path).unwrap();
-        let executor = CommandExecutor::new(engine, false, false);
+        let executor = CommandExecutor::new(engine, Theme::plain(), false);
 
-        let result = executor.index(data_dir, false);
+        let result = executor.index(data_dir, false, false, None);
         assert!(result.is_ok());
     }
 
@@ -259,12 +1359,137 @@ mod tests {
 
         let index_path = temp_dir.path().join("index.db");
         let engine = SearchEngine::new(&index_path).unwrap();
-        let executor = CommandExecutor::new(engine, false, false);
+        let executor = CommandExecutor::new(engine, Theme::plain(), false);
 
-        executor.index(data_dir, false).unwrap();
+        executor.index(data_dir, false, false, None).unwrap();
 
-        let result = executor.search("test".to_string());
+        let global = SearchRoot::Global;
+        let result = executor.search("test".to_string(), &[], &global, false, None, None);
         assert!(result.is_ok());
+
+        let params = ["-test ext:rs".to_string()];
+        assert!(executor.search("{} ext:txt".to_string(), &params, &global, false, None, None).is_ok());
+        assert!(executor.search("{} {} ext:txt".to_string(), &params, &global, false, None, None).is_err());
+    }
+
+    #[test]
+    fn test_search_root_from_flags() {
+        let dir = PathBuf::from("/srv/app");
+        assert_eq!(SearchRoot::from_flags(false, None), SearchRoot::Workspace);
+        assert_eq!(SearchRoot::from_flags(true, None), SearchRoot::Global);
+        assert_eq!(SearchRoot::from_flags(false, Some(dir.clone())), SearchRoot::Path(dir));
+    }
+
+    #[test]
+    fn test_scope_query_injects_workspace_prefix() {
+        let temp_dir = TempDir::new().unwrap();
+        let tree = temp_dir.path().join("tree");
+        let workspace = tree.join("app");
+        let other = tree.join("other");
+        fs::create_dir_all(workspace.join("src")).unwrap();
+        fs::create_dir(&other).unwrap();
+        fs::write(workspace.join("Cargo.toml"), "").unwrap();
+        fs::write(workspace.join("src/main.rs"), "").unwrap();
+        fs::write(other.join("main.rs"), "").unwrap();
+
+        let engine = SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+        engine.index_directory(&tree, None).unwrap();
+        let markers = engine.get_config().workspace_markers.clone();
+        let cwd = workspace.join("src");
+        let scoped = |input: &str, root: &SearchRoot| {
+            let mut query = QueryParser::parse(input).unwrap();
+            scope_query(&mut query, root, &cwd, &markers);
+            let results = engine.search_with_query(&query).unwrap();
+            let mut paths: Vec<PathBuf> = results.into_iter().map(|r| r.file.path).collect();
+            paths.sort();
+            (query.under, paths)
+        };
+        let workspace = normalize_path(&workspace);
+        let other = normalize_path(&other);
+
+        let (under, paths) = scoped("main", &SearchRoot::Workspace);
+        assert_eq!(under, Some(workspace.clone()));
+        assert_eq!(paths, vec![workspace.join("src/main.rs")]);
+
+        let (under, paths) = scoped("main", &SearchRoot::Global);
+        assert_eq!(under, None);
+        assert_eq!(paths, vec![workspace.join("src/main.rs"), other.join("main.rs")]);
+
+        let (under, paths) = scoped("main", &SearchRoot::Path(PathBuf::from("../../other")));
+        assert_eq!(under, Some(other.clone()));
+        assert_eq!(paths, vec![other.join("main.rs")]);
+
+        let (under, _) = scoped("main under:../../other", &SearchRoot::Workspace);
+        assert_eq!(under, Some(other));
+    }
+
+    #[test]
+    fn test_workspace_root_falls_back_to_global() {
+        let temp_dir = TempDir::new().unwrap();
+        let markers = vec!["no-such-marker-7f3a2c".to_string()];
+        assert_eq!(SearchRoot::Workspace.resolve(temp_dir.path(), &markers), None);
+    }
+
+    #[test]
+    fn test_search_json_limit_combinations() {
+        let temp_dir = TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        fs::create_dir(&data_dir).unwrap();
+        for i in 0..6 {
+            fs::write(data_dir.join(format!("note{}.txt", i)), "content").unwrap();
+        }
+
+        let engine = SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .max_search_results(4)
+            .build()
+            .unwrap();
+        engine.index_directory(&data_dir, None).unwrap();
+
+        let run = |query: &str| -> Result<serde_json::Value> {
+            let parsed = QueryParser::parse(query)?;
+            let limit = engine.result_limit(&parsed)?;
+            let results = engine.search_with_query(&parsed)?;
+            Ok(search_json(&parsed, limit, &results))
+        };
+
+        for (query, count, clamped) in [
+            ("note limit:2", 2, false),
+            ("note limit:10", 4, true),
+            ("note", 4, false),
+        ] {
+            let output = run(query).unwrap();
+            assert_eq!(output["results"].as_array().unwrap().len(), count, "{}", query);
+            assert_eq!(output["effective_limit"], count, "{}", query);
+            assert_eq!(output["limit_clamped"], clamped, "{}", query);
+        }
+
+        assert!(matches!(run("note limit:0"), Err(SearchError::InvalidQuery(_))));
+    }
+
+    #[test]
+    fn test_search_json_ignores_display_formatting() {
+        let temp_dir = TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        fs::create_dir(&data_dir).unwrap();
+        fs::write(data_dir.join("report.txt"), vec![b'x'; 1500]).unwrap();
+        let config = SearchConfigBuilder::new()
+            .cli_size_units(SizeUnits::Decimal)
+            .cli_decimal_separator(',')
+            .cli_date_format(DateStyle::parse("%d.%m.%Y").unwrap())
+            .build();
+        let engine = SearchEngine::with_config(temp_dir.path().join("index.db"), config).unwrap();
+        engine.index_directory(&data_dir, None).unwrap();
+
+        let parsed = QueryParser::parse("report size:>1KB").unwrap();
+        let limit = engine.result_limit(&parsed).unwrap();
+        let results = engine.search_with_query(&parsed).unwrap();
+        let output = search_json(&parsed, limit, &results);
+
+        assert_eq!(output["effective_query"]["size"][0]["display"], "size > 1.00 KB");
+        assert_eq!(output["results"][0]["file"]["size"], 1500);
+        let modified = output["results"][0]["file"]["modified_at"].as_str().unwrap();
+        assert!(DateTime::parse_from_rfc3339(modified).is_ok(), "{}", modified);
     }
 
     #[test]
@@ -272,9 +1497,129 @@ mod tests {
         let temp_dir = TempDir::new().unwrap();
         let index_path = temp_dir.path().join("index.db");
         let engine = SearchEngine::new(&index_path).unwrap();
-        let executor = CommandExecutor::new(engine, false, false);
+        let executor = CommandExecutor::new(engine, Theme::plain(), false);
 
         let result = executor.stats();
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_update_reports_each_root() {
+        let temp_dir = TempDir::new().unwrap();
+        let docs = temp_dir.path().join("docs");
+        let notes = temp_dir.path().join("notes");
+        let engine = SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+        for dir in [&docs, &notes] {
+            fs::create_dir(dir).unwrap();
+            engine.index_directory(dir, None).unwrap();
+            fs::write(dir.join("new.txt"), "content").unwrap();
+        }
+
+        let executor = CommandExecutor::new(engine, Theme::plain(), false);
+        let buffer = SharedBuffer::default();
+        executor.formatter.redirect(Box::new(buffer.clone()));
+
+        executor.update(Vec::new(), true, true, false, None).unwrap();
+        let output = String::from_utf8(buffer.take()).unwrap();
+        assert!(output.contains("Update Summary by Root"), "{}", output);
+        assert!(output.contains(&docs.display().to_string()), "{}", output);
+        assert!(output.contains(&notes.display().to_string()), "{}", output);
+
+        let missing = temp_dir.path().join("missing");
+        let roots = vec![missing.clone(), docs.clone()];
+        executor.update(roots.clone(), false, true, false, None).unwrap();
+        let output = String::from_utf8(buffer.take()).unwrap();
+        assert!(output.contains(&missing.display().to_string()), "{}", output);
+
+        let err = executor.update(roots, false, false, false, None).unwrap_err();
+        assert!(matches!(err, SearchError::PathNotFound(path) if path == missing));
+    }
+
+    #[test]
+    fn test_update_all_needs_recorded_roots() {
+        let temp_dir = TempDir::new().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+        let executor = CommandExecutor::new(engine, Theme::plain(), false);
+
+        let err = executor.update(Vec::new(), true, true, false, None).unwrap_err();
+        assert!(err.to_string().contains("no indexed roots"));
+    }
+
+    #[test]
+    fn test_open_engine_without_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("filesearch.db");
+
+        let err = open_engine(&index_path, false, false, SearchConfig::default())
+            .err()
+            .unwrap();
+        assert_eq!(err.code(), rusty_files::ErrorCode::IndexNotFound);
+        assert_eq!(err.path(), Some(index_path.as_path()));
+        let message = open_error_message(&err);
+        assert!(message.contains(&index_path.display().to_string()));
+        assert!(message.contains("filesearch index"));
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+
+        assert!(open_engine(&index_path, true, false, SearchConfig::default()).is_ok());
+        assert!(index_path.is_file());
+    }
+
+    #[test]
+    fn test_search_in_memory_leaves_no_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("checkout");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("lib.rs"), "fn main() {}").unwrap();
+        let index_path = temp_dir.path().join("filesearch.db");
+
+        let engine = open_engine(&index_path, false, true, SearchConfig::default()).unwrap();
+        let executor = CommandExecutor::new(engine, Theme::plain(), false);
+        executor
+            .search_in_memory(root.clone(), "lib".to_string(), &[], true, None, None)
+            .unwrap();
+
+        assert!(!index_path.exists());
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+        let engine = executor.engine.lock().unwrap();
+        assert_eq!(engine.search("lib").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_meta_set_is_limited_to_user_keys() {
+        let temp_dir = TempDir::new().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+        let executor = CommandExecutor::new(engine, Theme::plain(), false);
+
+        executor.meta_set("user.owner", "ops", false).unwrap();
+        let err = executor
+            .meta_set(keys::PATH_LAYOUT, "compact", false)
+            .unwrap_err();
+        assert!(matches!(err, SearchError::Configuration(_)));
+        assert!(executor.meta_set("owner", "ops", false).is_err());
+
+        executor.meta_set("lease.note", "x", true).unwrap();
+        let engine = executor.engine.lock().unwrap();
+        assert_eq!(engine.setting("user.owner").unwrap().unwrap().value, "ops");
+        assert_eq!(engine.setting(keys::PATH_LAYOUT).unwrap().unwrap().value, "legacy");
+        assert_eq!(engine.setting("lease.note").unwrap().unwrap().value, "x");
+    }
+
+    #[test]
+    fn test_maintain_once_records_every_enabled_task() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = rusty_files::SearchConfigBuilder::new()
+            .disabled_maintenance_tasks(vec![rusty_files::MaintenanceTask::Checkpoint])
+            .build();
+        let engine = SearchEngine::with_config(temp_dir.path().join("index.db"), config).unwrap();
+        let executor = CommandExecutor::new(engine, Theme::plain(), false);
+
+        executor.maintain(true).unwrap();
+
+        let engine = executor.engine.lock().unwrap();
+        for status in engine.maintenance_status().unwrap() {
+            assert_eq!(status.last_run.is_some(), status.enabled, "{:?}", status.task);
+        }
+        let key = keys::maintenance_last_run(rusty_files::MaintenanceTask::FtsMerge);
+        assert!(engine.setting(&key).unwrap().is_some());
+    }
 }
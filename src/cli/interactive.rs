@@ -1,36 +1,232 @@
-use crate::output::OutputFormatter;
-use rusty_files::core::{Result, SearchEngine};
+use crate::commands::{scope_query, SearchRoot};
+use crate::output::{FormatOptions, OutputFormatter};
+use crate::theme::Theme;
+use rusty_files::{ExtensionCategory, QueryParser, Result, SearchEngine, SearchResult};
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEvent},
+    cursor::MoveToColumn,
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType},
 };
+use serde::Serialize;
+use std::collections::HashMap;
 use std::io::{self, Write};
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::Mutex;
 
+const FILTER_KEYS: &[&str] = &[
+    "ext", "size", "modified", "created", "accessed", "mode", "case", "is", "scope", "tag",
+    "lang", "subject", "from", "to", "title", "under", "category", "hidden", "depth", "limit",
+    "offset",
+];
+const MODE_VALUES: &[&str] = &["exact", "insensitive", "fuzzy", "regex", "glob"];
+const CASE_VALUES: &[&str] = &["sensitive", "insensitive"];
+const SCOPE_VALUES: &[&str] = &["name", "path", "content", "all"];
+const HIDDEN_VALUES: &[&str] = &["true", "false", "only"];
+const KIND_VALUES: &[&str] = &["file", "dir", "symlink"];
+const COMMANDS: &[&str] = &[
+    ":help", ":stats", ":clear", ":history", ":run ", ":scope ", ":quit",
+];
+const EXTENSION_SUGGESTIONS: usize = 20;
+
+/// A candidate for the text between `start` and the cursor.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Completion {
+    /// Byte offset in the input where the completed text begins.
+    pub start: usize,
+    pub text: String,
+}
+
+impl Completion {
+    fn new(start: usize, text: impl Into<String>) -> Self {
+        Self {
+            start,
+            text: text.into(),
+        }
+    }
+
+    /// Replaces `start..cursor` in `input` with the candidate, keeping whatever follows the
+    /// cursor. Returns the cursor position just after the inserted text.
+    pub fn apply(&self, input: &mut String, cursor: usize) -> usize {
+        input.replace_range(self.start..cursor, &self.text);
+        self.start + self.text.len()
+    }
+}
+
+/// Completes query-language input. Data read from the index is fetched on first use and
+/// kept for the completer's lifetime.
+#[derive(Default)]
+pub struct Completer {
+    extensions: Option<Vec<String>>,
+    saved_searches: Option<Vec<String>>,
+}
+
+impl Completer {
+    /// Completion candidates for the token ending at `cursor`, a byte offset into `input`.
+    ///
+    /// Filter keys complete to `key:`, and `mode:`, `scope:`, `is:` and `ext:` complete their
+    /// values; list values such as `ext:rs,to` complete the part after the last comma.
+    /// Saved search names complete after `:run ` or `@`.
+    pub fn complete(
+        &mut self,
+        engine: &SearchEngine,
+        input: &str,
+        cursor: usize,
+    ) -> Vec<Completion> {
+        let mut cursor = cursor.min(input.len());
+        while !input.is_char_boundary(cursor) {
+            cursor -= 1;
+        }
+
+        let before = &input[..cursor];
+        let start = before
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let token = &before[start..];
+
+        if input.starts_with(":run ") && start > 0 {
+            return matching(self.saved_search_names(engine), token, start, "");
+        }
+        if let Some(name) = token.strip_prefix('@') {
+            return matching(self.saved_search_names(engine), name, start, "@");
+        }
+        if start == 0 && token.starts_with(':') {
+            return matching(COMMANDS.iter().copied(), token, start, "");
+        }
+
+        let Some((key, value)) = token.split_once(':') else {
+            return FILTER_KEYS
+                .iter()
+                .filter(|key| starts_with_ignore_case(key, token))
+                .map(|key| Completion::new(start, format!("{}:", key)))
+                .collect();
+        };
+
+        let (value_start, prefix) = match value.rfind(',') {
+            Some(comma) => (start + key.len() + 2 + comma, &value[comma + 1..]),
+            None => (start + key.len() + 1, value),
+        };
+        match key.to_lowercase().as_str() {
+            "mode" => matching(MODE_VALUES.iter().copied(), prefix, value_start, ""),
+            "case" => matching(CASE_VALUES.iter().copied(), prefix, value_start, ""),
+            "scope" => matching(SCOPE_VALUES.iter().copied(), prefix, value_start, ""),
+            "is" => matching(KIND_VALUES.iter().copied(), prefix, value_start, ""),
+            "category" => matching(ExtensionCategory::NAMES, prefix, value_start, ""),
+            "hidden" => matching(HIDDEN_VALUES.iter().copied(), prefix, value_start, ""),
+            "ext" | "extension" => matching(self.extensions(engine), prefix, value_start, ""),
+            _ => Vec::new(),
+        }
+    }
+
+    fn extensions(&mut self, engine: &SearchEngine) -> impl Iterator<Item = &str> {
+        self.extensions
+            .get_or_insert_with(|| {
+                let top = engine.top_extensions(EXTENSION_SUGGESTIONS);
+                top.map(|top| top.into_iter().map(|(ext, _)| ext).collect())
+                    .unwrap_or_default()
+            })
+            .iter()
+            .map(String::as_str)
+    }
+
+    fn saved_search_names(&mut self, engine: &SearchEngine) -> impl Iterator<Item = &str> {
+        self.saved_searches
+            .get_or_insert_with(|| {
+                let saved = engine.saved_searches();
+                saved
+                    .map(|saved| saved.into_iter().map(|s| s.name).collect())
+                    .unwrap_or_default()
+            })
+            .iter()
+            .map(String::as_str)
+    }
+}
+
+/// Repeated Tab presses stepping through the candidates of a single completion.
+struct CompletionCycle {
+    candidates: Vec<Completion>,
+    index: usize,
+    /// Cursor position right after the candidate applied last.
+    end: usize,
+}
+
+impl CompletionCycle {
+    fn start(candidates: Vec<Completion>, cursor: usize, forward: bool) -> Option<Self> {
+        let index = if forward { 0 } else { candidates.len().checked_sub(1)? };
+        Some(Self {
+            candidates,
+            index,
+            end: cursor,
+        })
+    }
+
+    fn step(&mut self, forward: bool) {
+        let len = self.candidates.len();
+        self.index = if forward {
+            (self.index + 1) % len
+        } else {
+            (self.index + len - 1) % len
+        };
+    }
+
+    /// Swaps the previously applied candidate for the current one; returns the new cursor.
+    fn apply(&mut self, input: &mut String) -> usize {
+        self.end = self.candidates[self.index].apply(input, self.end);
+        self.end
+    }
+}
+
 pub struct InteractiveMode {
     engine: Arc<Mutex<SearchEngine>>,
     formatter: OutputFormatter,
     history: Vec<String>,
     history_index: usize,
+    completions: Completer,
+    /// Directory that searches without `under:` are limited to; `None` searches everything.
+    scope: Option<PathBuf>,
 }
 
 impl InteractiveMode {
-    pub fn new(engine: SearchEngine) -> Self {
+    /// Starts scoped to the workspace around the working directory, if there is one.
+    pub fn new(engine: SearchEngine, theme: Theme) -> Self {
+        let scope = std::env::current_dir().ok().and_then(|cwd| {
+            SearchRoot::Workspace.resolve(&cwd, &engine.get_config().workspace_markers)
+        });
         Self {
             engine: Arc::new(Mutex::new(engine)),
-            formatter: OutputFormatter::new(true, false),
+            formatter: OutputFormatter::new(theme, false),
             history: Vec::new(),
             history_index: 0,
+            completions: Completer::default(),
+            scope,
         }
     }
 
+    pub fn with_format(mut self, format: FormatOptions) -> Self {
+        self.formatter = self.formatter.with_format(format);
+        self
+    }
+
+    /// `[name] > ` with the last component of the scope, or `[global] > `.
+    fn prompt(&self) -> String {
+        let label = match &self.scope {
+            Some(dir) => dir.file_name().map_or_else(
+                || dir.display().to_string(),
+                |name| name.to_string_lossy().into_owned(),
+            ),
+            None => "global".to_string(),
+        };
+        format!("[{}] > ", label)
+    }
+
     pub fn run(&mut self) -> Result<()> {
         self.print_welcome();
 
         loop {
-            print!("\n> ");
+            print!("\n{}", self.prompt());
             io::stdout().flush()?;
 
             let input = self.read_line()?;
@@ -59,9 +255,22 @@ impl InteractiveMode {
         println!("  :stats   - Show index statistics");
         println!("  :quit    - Exit interactive mode");
         println!();
+        self.print_scope();
     }
 
-    fn handle_command(&self, input: &str) -> Result<bool> {
+    fn handle_command(&mut self, input: &str) -> Result<bool> {
+        if let Some(name) = input.strip_prefix(":run ").or_else(|| input.strip_prefix('@')) {
+            self.run_saved_search(name.trim())?;
+            return Ok(false);
+        }
+
+        if let Some(arg) = input.strip_prefix(":scope") {
+            if arg.is_empty() || arg.starts_with(' ') {
+                self.change_scope(arg.trim())?;
+                return Ok(false);
+            }
+        }
+
         if input.starts_with(':') {
             match input {
                 ":quit" | ":q" | ":exit" => return Ok(true),
@@ -89,15 +298,104 @@ impl InteractiveMode {
         }
     }
 
+    /// `global` searches the whole index, `here` the working directory and anything else
+    /// the directory it names; no argument prints the current scope.
+    fn change_scope(&mut self, arg: &str) -> Result<()> {
+        let root = match arg {
+            "" => {
+                self.print_scope();
+                return Ok(());
+            }
+            "global" => SearchRoot::Global,
+            "here" => SearchRoot::Path(PathBuf::from(".")),
+            dir => SearchRoot::Path(PathBuf::from(dir)),
+        };
+
+        let cwd = std::env::current_dir()?;
+        let scope = root.resolve(&cwd, &[]);
+        if let Some(ref dir) = scope {
+            if !dir.is_dir() {
+                self.formatter
+                    .print_error(&format!("Not a directory: {}", dir.display()));
+                return Ok(());
+            }
+        }
+
+        self.scope = scope;
+        self.print_scope();
+        Ok(())
+    }
+
+    fn print_scope(&self) {
+        match &self.scope {
+            Some(dir) => self
+                .formatter
+                .print_info(&format!("Searching under {}", dir.display())),
+            None => self.formatter.print_info("Searching the whole index"),
+        }
+    }
+
+    /// A query with problems is shown with each one underlined instead of being run.
     fn execute_search(&self, query: &str) -> Result<()> {
-        let engine = self.engine.lock().unwrap();
-        let results = engine.search(query)?;
+        if let Err(diagnostics) = QueryParser::validate(query) {
+            self.formatter.print_query_diagnostics(query, &diagnostics);
+            return Ok(());
+        }
+        let results = self.search(query)?;
 
-        self.formatter.print_search_results(&results, query);
+        self.formatter.print_search_results(&results, query, &HashMap::new(), &HashMap::new());
 
         Ok(())
     }
 
+    /// Runs `query` within the current scope unless it has an `under:` of its own.
+    fn search(&self, query: &str) -> Result<Vec<SearchResult>> {
+        let engine = self.engine.lock().unwrap();
+        let mut parsed = QueryParser::parse(query)?;
+        let root = self.scope.clone().map_or(SearchRoot::Global, SearchRoot::Path);
+        let cwd = std::env::current_dir()?;
+        scope_query(&mut parsed, &root, &cwd, &engine.get_config().workspace_markers);
+
+        engine.search_with_query(&parsed)
+    }
+
+    fn run_saved_search(&self, name: &str) -> Result<()> {
+        let saved = self.engine.lock().unwrap().saved_search(name)?;
+
+        match saved {
+            Some(saved) => self.execute_search(&saved.query),
+            None => {
+                self.formatter.print_error(&format!("No saved search named '{}'", name));
+                Ok(())
+            }
+        }
+    }
+
+    /// Completion candidates for the token ending at `cursor`; see [`Completer::complete`].
+    pub fn complete(&mut self, input: &str, cursor: usize) -> Vec<Completion> {
+        let engine = self.engine.lock().unwrap();
+        self.completions.complete(&engine, input, cursor)
+    }
+
+    /// Handles Tab (`forward`) and Shift-Tab, returning the new cursor position.
+    fn cycle_completion(
+        &mut self,
+        cycle: &mut Option<CompletionCycle>,
+        input: &mut String,
+        cursor: usize,
+        forward: bool,
+    ) -> usize {
+        match cycle {
+            Some(state) => state.step(forward),
+            None => match CompletionCycle::start(self.complete(input, cursor), cursor, forward) {
+                Some(state) => *cycle = Some(state),
+                None => return cursor,
+            },
+        }
+
+        cycle.as_mut().map_or(cursor, |state| state.apply(input))
+    }
+
     fn print_help(&self) {
         self.formatter.print_header("Interactive Mode Help");
         println!();
@@ -107,12 +405,16 @@ impl InteractiveMode {
         println!("  pattern size:>1MB          - Search with size filter");
         println!("  pattern modified:today     - Search with date filter");
         println!("  pattern mode:fuzzy         - Use fuzzy matching");
+        println!("  pattern under:<dir>        - Search only under a directory");
+        println!("  Tab / Shift-Tab            - Cycle through completions");
         println!();
         println!("Commands:");
         println!("  :help, :h                  - Show this help");
         println!("  :stats                     - Show index statistics");
         println!("  :clear                     - Clear screen");
         println!("  :history                   - Show search history");
+        println!("  :run <name>, @<name>       - Run a saved search");
+        println!("  :scope global|here|<dir>   - Change where searches look");
         println!("  :quit, :q, :exit           - Exit interactive mode");
         println!();
     }
@@ -153,7 +455,9 @@ impl InteractiveMode {
         Ok(input)
     }
 
+    /// Line editing with history, cursor movement and Tab completion; needs a terminal.
     pub fn run_with_raw_mode(&mut self) -> Result<()> {
+        self.print_welcome();
         enable_raw_mode()?;
 
         let result = self.run_raw_mode_loop();
@@ -164,60 +468,120 @@ impl InteractiveMode {
     }
 
     fn run_raw_mode_loop(&mut self) -> Result<()> {
-        self.print_welcome();
-
         let mut input = String::new();
+        let mut cursor = 0;
+        let mut cycle: Option<CompletionCycle> = None;
 
         loop {
-            print!("\r> {}", input);
-            io::stdout().flush()?;
+            self.redraw_prompt(&input, cursor)?;
+
+            if let Event::Key(KeyEvent { code, modifiers, .. }) = event::read()? {
+                if !matches!(code, KeyCode::Tab | KeyCode::BackTab) {
+                    cycle = None;
+                }
 
-            if let Event::Key(KeyEvent { code, .. }) = event::read()? {
                 match code {
-                    KeyCode::Enter => {
+                    KeyCode::Tab | KeyCode::BackTab => {
+                        let forward = code == KeyCode::Tab;
+                        cursor = self.cycle_completion(&mut cycle, &mut input, cursor, forward);
+                    }
+                    KeyCode::Enter if !input.is_empty() => {
+                        // Results are printed with plain newlines, which raw mode
+                        // would not return to the first column.
+                        disable_raw_mode()?;
                         println!();
-                        if !input.is_empty() {
-                            if self.handle_command(&input)? {
-                                break;
-                            }
-                            self.history.push(input.clone());
-                            self.history_index = self.history.len();
-                            input.clear();
+                        let quit = self.handle_command(&input);
+                        enable_raw_mode()?;
+                        if quit? {
+                            break;
                         }
+                        self.history.push(input.clone());
+                        self.history_index = self.history.len();
+                        input.clear();
+                        cursor = 0;
+                    }
+                    KeyCode::Char('c' | 'd') if modifiers.contains(KeyModifiers::CONTROL) => {
+                        break;
                     }
                     KeyCode::Char(c) => {
-                        input.push(c);
+                        input.insert(cursor, c);
+                        cursor += c.len_utf8();
                     }
                     KeyCode::Backspace => {
-                        input.pop();
+                        if let Some((i, _)) = input[..cursor].char_indices().next_back() {
+                            input.remove(i);
+                            cursor = i;
+                        }
+                    }
+                    KeyCode::Left => {
+                        if let Some((i, _)) = input[..cursor].char_indices().next_back() {
+                            cursor = i;
+                        }
+                    }
+                    KeyCode::Right => {
+                        if let Some(c) = input[cursor..].chars().next() {
+                            cursor += c.len_utf8();
+                        }
                     }
                     KeyCode::Esc => {
                         break;
                     }
-                    KeyCode::Up => {
-                        if self.history_index > 0 {
-                            self.history_index -= 1;
-                            input = self.history[self.history_index].clone();
-                        }
+                    KeyCode::Up if self.history_index > 0 => {
+                        self.history_index -= 1;
+                        input = self.history[self.history_index].clone();
+                        cursor = input.len();
                     }
                     KeyCode::Down => {
-                        if self.history_index < self.history.len() - 1 {
+                        if self.history_index + 1 < self.history.len() {
                             self.history_index += 1;
                             input = self.history[self.history_index].clone();
                         } else {
                             self.history_index = self.history.len();
                             input.clear();
                         }
+                        cursor = input.len();
                     }
                     _ => {}
                 }
             }
         }
 
+        print!("\r\n");
+        Ok(())
+    }
+
+    fn redraw_prompt(&self, input: &str, cursor: usize) -> Result<()> {
+        let mut stdout = io::stdout();
+        execute!(stdout, MoveToColumn(0), Clear(ClearType::CurrentLine))?;
+        let prompt = self.prompt();
+        print!("{}{}", prompt, input);
+        let column = prompt.chars().count() + input[..cursor].chars().count();
+        execute!(stdout, MoveToColumn(column.min(u16::MAX as usize) as u16))?;
+        stdout.flush()?;
         Ok(())
     }
 }
 
+fn starts_with_ignore_case(candidate: &str, prefix: &str) -> bool {
+    candidate
+        .get(..prefix.len())
+        .is_some_and(|head| head.eq_ignore_ascii_case(prefix))
+}
+
+/// Candidates from `values` starting with `prefix`, in their original order.
+fn matching<'a>(
+    values: impl IntoIterator<Item = &'a str>,
+    prefix: &str,
+    start: usize,
+    lead: &str,
+) -> Vec<Completion> {
+    values
+        .into_iter()
+        .filter(|value| starts_with_ignore_case(value, prefix))
+        .map(|value| Completion::new(start, format!("{}{}", lead, value)))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -228,6 +592,132 @@ mod tests {
         let temp_dir = TempDir::new().unwrap();
         let index_path = temp_dir.path().join("index.db");
         let engine = SearchEngine::new(&index_path).unwrap();
-        let _interactive = InteractiveMode::new(engine);
+        let _interactive = InteractiveMode::new(engine, Theme::plain());
+    }
+
+    fn interactive(temp_dir: &TempDir) -> InteractiveMode {
+        let engine = SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+        InteractiveMode::new(engine, Theme::plain())
+    }
+
+    fn texts(completions: &[Completion]) -> Vec<&str> {
+        completions.iter().map(|c| c.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_complete_filter_keys() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut mode = interactive(&temp_dir);
+
+        let completions = mode.complete("report ex", 9);
+        assert_eq!(completions, vec![Completion::new(7, "ext:")]);
+        assert_eq!(texts(&mode.complete("report M", 8)), vec!["modified:", "mode:"]);
+        assert!(mode.complete("report xyz", 10).is_empty());
+        assert_eq!(texts(&mode.complete(":hi", 3)), vec![":history"]);
+    }
+
+    #[test]
+    fn test_complete_enumerated_values() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut mode = interactive(&temp_dir);
+
+        assert_eq!(mode.complete("x mode:fu", 9), vec![Completion::new(7, "fuzzy")]);
+        assert_eq!(texts(&mode.complete("scope:", 6)), SCOPE_VALUES.to_vec());
+        assert_eq!(mode.complete("is:dir,sy", 9), vec![Completion::new(7, "symlink")]);
+        assert_eq!(mode.complete("category:im", 11), vec![Completion::new(9, "image")]);
+        assert_eq!(mode.complete("hidden:o", 8), vec![Completion::new(7, "only")]);
+        assert_eq!(mode.complete("case:s", 6), vec![Completion::new(5, "sensitive")]);
+        assert!(mode.complete("size:>1", 7).is_empty());
+    }
+
+    #[test]
+    fn test_complete_extensions_from_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        std::fs::create_dir(&root).unwrap();
+        for name in ["a.rs", "b.rs", "c.rs", "notes.md", "Cargo.toml"] {
+            std::fs::write(root.join(name), "x").unwrap();
+        }
+        let engine = SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+        engine.index_directory(&root, None).unwrap();
+        let mut mode = InteractiveMode::new(engine, Theme::plain());
+
+        let all = mode.complete("main ext:", 9);
+        assert_eq!(texts(&all)[..2], ["rs", "md"]);
+        assert_eq!(mode.complete("main ext:rs,m", 13), vec![Completion::new(12, "md")]);
+    }
+
+    #[test]
+    fn test_complete_with_cursor_inside_token() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut mode = interactive(&temp_dir);
+        let mut input = "ex report".to_string();
+
+        let completions = mode.complete(&input, 2);
+        assert_eq!(completions, vec![Completion::new(0, "ext:")]);
+        let cursor = completions[0].apply(&mut input, 2);
+        assert_eq!((input.as_str(), cursor), ("ext: report", 4));
+    }
+
+    #[test]
+    fn test_scope_command_limits_searches() {
+        let temp_dir = TempDir::new().unwrap();
+        let tree = temp_dir.path().join("tree");
+        let first = tree.join("first");
+        let second = tree.join("second");
+        for dir in [&first, &second] {
+            std::fs::create_dir_all(dir).unwrap();
+            std::fs::write(dir.join("notes.txt"), "x").unwrap();
+        }
+        let mut mode = interactive(&temp_dir);
+        mode.engine
+            .lock()
+            .unwrap()
+            .index_directory(&tree, None)
+            .unwrap();
+
+        mode.handle_command(":scope global").unwrap();
+        assert_eq!(mode.scope, None);
+        assert_eq!(mode.prompt(), "[global] > ");
+        assert_eq!(mode.search("notes").unwrap().len(), 2);
+
+        let first = rusty_files::utils::normalize_path(&first);
+        mode.handle_command(&format!(":scope {}", first.display())).unwrap();
+        assert_eq!(mode.scope.as_ref(), Some(&first));
+        assert_eq!(mode.prompt(), "[first] > ");
+        let results = mode.search("notes").unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].file.path.starts_with(&first));
+
+        let elsewhere = format!("notes under:{}", second.display());
+        assert_eq!(mode.search(&elsewhere).unwrap().len(), 1);
+
+        mode.handle_command(&format!(":scope {}", second.join("missing").display()))
+            .unwrap();
+        assert_eq!(mode.scope.as_ref(), Some(&first));
+        assert_eq!(mode.complete(":sc", 3), vec![Completion::new(0, ":scope ")]);
+    }
+
+    #[test]
+    fn test_complete_saved_searches_and_cycle() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut mode = interactive(&temp_dir);
+        {
+            let engine = mode.engine.lock().unwrap();
+            engine.save_search("weekly", "report", None).unwrap();
+            engine.save_search("work", "todo", None).unwrap();
+        }
+
+        assert_eq!(texts(&mode.complete(":run we", 7)), vec!["weekly"]);
+        assert_eq!(texts(&mode.complete("@w", 2)), vec!["@weekly", "@work"]);
+
+        let mut input = "@w".to_string();
+        let mut cycle = None;
+        let cursor = mode.cycle_completion(&mut cycle, &mut input, 2, true);
+        assert_eq!((input.as_str(), cursor), ("@weekly", 7));
+        mode.cycle_completion(&mut cycle, &mut input, cursor, true);
+        assert_eq!(input, "@work");
+        mode.cycle_completion(&mut cycle, &mut input, cursor, false);
+        assert_eq!(input, "@weekly");
     }
 }
@@ -1,13 +1,24 @@
-use clap::{Parser, Subcommand};
-use rusty_files::SearchEngine;
+use chrono::{DateTime, Utc};
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+use rusty_files::{AuditEventType, DateStyle, LeaseWait, ListingSort, SizeUnits, SortKey};
 use std::path::PathBuf;
 
+mod activity;
 mod commands;
 mod interactive;
 mod output;
+mod pager;
+mod rpc;
+mod theme;
 
-use commands::CommandExecutor;
+use commands::{open_engine, open_error_message, CommandExecutor, SearchRoot};
 use interactive::InteractiveMode;
+use output::{FormatOptions, OutputFormatter};
+use pager::Pager;
+use rpc::RpcServer;
+use rusty_files::{SearchConfig, SearchError};
+use std::io::IsTerminal;
+use theme::{colors_enabled, ColorChoice, Theme};
 
 #[derive(Parser)]
 #[command(
@@ -23,9 +34,36 @@ struct Cli {
     #[arg(short, long, global = true, help = "Enable verbose output")]
     verbose: bool,
 
-    #[arg(long, global = true, help = "Disable colored output")]
+    #[arg(long, global = true, help = "Search configuration file (TOML or JSON)")]
+    config: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_enum,
+        global = true,
+        default_value_t = ColorChoice::Auto,
+        help = "When to use colored output"
+    )]
+    color: ColorChoice,
+
+    #[arg(long, global = true, help = "Disable colored output (same as --color=never)")]
     no_color: bool,
 
+    #[arg(
+        long,
+        global = true,
+        value_name = "STYLE",
+        value_parser = parse_date_style,
+        help = "Show dates as relative, iso or a strftime pattern like \"%d.%m.%Y %H:%M\""
+    )]
+    date_format: Option<DateStyle>,
+
+    #[arg(long, global = true, help = "Show sizes in powers of 1000 instead of 1024")]
+    si: bool,
+
+    #[arg(long, global = true, help = "Never pipe long search output through a pager")]
+    no_pager: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -34,8 +72,44 @@ struct Cli {
 enum Commands {
     #[command(about = "Build index for a directory")]
     Index {
-        #[arg(help = "Directory to index")]
-        path: PathBuf,
+        #[arg(
+            help = "Directory to index",
+            required_unless_present = "manifest",
+            conflicts_with = "manifest"
+        )]
+        path: Option<PathBuf>,
+
+        #[arg(long, value_name = "FILE", help = "Index every root declared in a TOML/JSON manifest")]
+        manifest: Option<PathBuf>,
+
+        #[arg(long, value_name = "N", requires = "manifest", help = "Index up to N manifest roots at once")]
+        parallel: Option<usize>,
+
+        #[arg(short, long, help = "Show progress")]
+        progress: bool,
+
+        #[arg(
+            long,
+            conflicts_with = "manifest",
+            help = "Index names and metadata only; read content later with `enrich`"
+        )]
+        quick: bool,
+
+        #[arg(long, help = "Fail right away if another process is writing to the index")]
+        no_wait: bool,
+
+        #[arg(
+            long,
+            conflicts_with = "no_wait",
+            help = "Take over the index from another writer, even a live one"
+        )]
+        steal: bool,
+    },
+
+    #[command(about = "Read the content that quick indexing deferred")]
+    Enrich {
+        #[arg(help = "Quick-indexed directory to enrich (defaults to all of them)")]
+        path: Option<PathBuf>,
 
         #[arg(short, long, help = "Show progress")]
         progress: bool,
@@ -43,17 +117,104 @@ enum Commands {
 
     #[command(about = "Update existing index")]
     Update {
-        #[arg(help = "Directory to update")]
-        path: PathBuf,
+        #[arg(
+            help = "Directories to update",
+            required_unless_present = "all",
+            conflicts_with = "all"
+        )]
+        paths: Vec<PathBuf>,
+
+        #[arg(long, help = "Update every root recorded in the index")]
+        all: bool,
+
+        #[arg(
+            long,
+            action = ArgAction::Set,
+            default_value_t = true,
+            num_args = 0..=1,
+            default_missing_value = "true",
+            help = "Keep updating the other roots after one fails; pass false to stop"
+        )]
+        continue_on_error: bool,
 
         #[arg(short, long, help = "Show progress")]
         progress: bool,
+
+        #[arg(long, help = "Fail right away if another process is writing to the index")]
+        no_wait: bool,
+
+        #[arg(
+            long,
+            conflicts_with = "no_wait",
+            help = "Take over the index from another writer, even a live one"
+        )]
+        steal: bool,
     },
 
     #[command(about = "Search for files")]
     Search {
         #[arg(help = "Search query")]
         query: String,
+
+        #[arg(
+            long = "param",
+            value_name = "VALUE",
+            allow_hyphen_values = true,
+            help = "Fill the next {} placeholder in the query with VALUE as literal text"
+        )]
+        params: Vec<String>,
+
+        #[arg(long, help = "Print results and the effective query as JSON")]
+        json: bool,
+
+        #[arg(
+            long,
+            value_name = "DIR",
+            help = "Index DIR in memory and search it, leaving no index file behind"
+        )]
+        memory: Option<PathBuf>,
+
+        #[arg(
+            long,
+            conflicts_with_all = ["root", "memory"],
+            help = "Search the whole index instead of the current workspace"
+        )]
+        global: bool,
+
+        #[arg(
+            long,
+            value_name = "DIR",
+            conflicts_with = "memory",
+            help = "Search only under DIR instead of the current workspace"
+        )]
+        root: Option<PathBuf>,
+
+        #[arg(
+            long,
+            value_name = "TAG",
+            conflicts_with_all = ["json", "memory"],
+            help = "Tag every result with TAG after confirmation"
+        )]
+        tag_as: Option<String>,
+
+        #[arg(long, requires = "tag_as", help = "Tag without asking for confirmation")]
+        yes: bool,
+
+        #[arg(
+            short = 'C',
+            long,
+            value_name = "N",
+            help = "Show N lines around each matching line of content results, like grep -C"
+        )]
+        context: Option<usize>,
+
+        #[arg(
+            long,
+            value_name = "KEY",
+            value_parser = parse_sort_key,
+            help = "Order results by size, modified, name or score instead of relevance; prefix with - for descending"
+        )]
+        sort: Option<SortKey>,
     },
 
     #[command(about = "Show index statistics")]
@@ -63,34 +224,330 @@ enum Commands {
     Verify {
         #[arg(help = "Directory to verify")]
         path: PathBuf,
+
+        #[arg(short, long, help = "Show progress")]
+        progress: bool,
+
+        #[arg(
+            long,
+            value_name = "FILE",
+            num_args = 0..=1,
+            help = "List missing and outdated paths, or write them to FILE"
+        )]
+        list_problems: Option<Option<PathBuf>>,
     },
 
     #[command(about = "Watch directory for changes")]
     Watch {
         #[arg(help = "Directory to watch")]
         path: PathBuf,
+
+        #[arg(long, help = "Do not index files that already exist under the directory")]
+        no_initial_scan: bool,
+
+        #[arg(long, help = "Only watch the directory's direct children, not subdirectories")]
+        no_recursive: bool,
     },
 
     #[command(about = "Clear index")]
     Clear {
         #[arg(long, help = "Confirm deletion")]
         confirm: bool,
+
+        #[arg(long, help = "Also delete the audit log")]
+        include_audit: bool,
+
+        #[arg(long, help = "Skip the undo snapshot taken before clearing")]
+        no_undo: bool,
+    },
+
+    #[command(about = "Restore the index from the last undo snapshot")]
+    Undo {
+        #[arg(long, help = "List available snapshots instead of restoring")]
+        list: bool,
+    },
+
+    #[command(about = "Show audit log records for a path")]
+    Audit {
+        #[arg(help = "File or directory to show records for")]
+        path: PathBuf,
+
+        #[arg(long, help = "Only show records at or after this RFC 3339 timestamp")]
+        since: Option<DateTime<Utc>>,
+
+        #[arg(long, help = "Only show records at or before this RFC 3339 timestamp")]
+        until: Option<DateTime<Utc>>,
+
+        #[arg(
+            long = "type",
+            value_name = "TYPE",
+            value_parser = parse_audit_event_type,
+            help = "Only show created, modified, deleted or renamed events (repeatable)"
+        )]
+        event_types: Vec<AuditEventType>,
+
+        #[arg(long, default_value_t = 100, help = "Maximum number of records to show")]
+        limit: usize,
+    },
+
+    #[command(about = "Manage saved searches")]
+    Saved {
+        #[command(subcommand)]
+        action: SavedAction,
+    },
+
+    #[command(about = "Manage user tags")]
+    Tags {
+        #[command(subcommand)]
+        action: TagsAction,
+    },
+
+    #[command(about = "Read or change the settings stored in the index, for debugging")]
+    Meta {
+        #[command(subcommand)]
+        action: MetaAction,
+    },
+
+    #[command(about = "List a saved search like a folder, newest first")]
+    Smart {
+        #[arg(help = "Saved search name")]
+        name: String,
+
+        #[arg(long, default_value_t = 100, help = "Maximum number of entries to show")]
+        limit: usize,
+
+        #[arg(long, default_value_t = 0, help = "Number of entries to skip")]
+        offset: usize,
     },
 
     #[command(about = "Optimize database")]
     Vacuum,
 
-    #[command(about = "Export search results")]
+    #[command(about = "Run index maintenance whenever the index is idle, until interrupted")]
+    Maintain {
+        #[arg(long, help = "Run every maintenance task once right away, then exit")]
+        once: bool,
+    },
+
+    #[command(about = "Rebuild the full-text index from stored content without re-reading files")]
+    RebuildFts {
+        #[arg(short, long, help = "Show progress")]
+        progress: bool,
+    },
+
+    #[command(about = "Copy the index to a file while it stays in use")]
+    Backup {
+        #[arg(help = "File to write the backup to")]
+        dest: PathBuf,
+
+        #[arg(long, help = "Gzip the finished backup into <DEST>.gz")]
+        compress: bool,
+
+        #[arg(short, long, help = "Show progress")]
+        progress: bool,
+    },
+
+    #[command(about = "Replace the index with a backup, plain or gzipped")]
+    Restore {
+        #[arg(help = "Backup to restore")]
+        source: PathBuf,
+
+        #[arg(short, long, help = "Show progress")]
+        progress: bool,
+    },
+
+    #[command(about = "Print the indexed files and content for comparing indexes")]
+    Dump {
+        #[arg(
+            long,
+            required = true,
+            help = "Sorted by path, without row ids or timestamps (the only format so far)"
+        )]
+        canonical: bool,
+
+        #[arg(short, long, help = "Write the dump to a file instead of stdout")]
+        output: Option<PathBuf>,
+    },
+
+    #[command(about = "Migrate the index to a different storage layout")]
+    Migrate {
+        #[arg(long, help = "Store paths as parent directory references plus file names")]
+        compact: bool,
+    },
+
+    #[command(about = "Export search results or index changes")]
     Export {
-        #[arg(short, long, help = "Output file path")]
-        output: PathBuf,
+        #[arg(short, long, help = "Output file path (defaults to stdout for --since)")]
+        output: Option<PathBuf>,
 
         #[arg(short, long, help = "Search query to export")]
         query: Option<String>,
+
+        #[arg(long, help = "Export index changes since this RFC 3339 timestamp or cursor")]
+        since: Option<DateTime<Utc>>,
+
+        #[arg(long, value_enum, help = "Output format")]
+        format: Option<ExportFormat>,
+
+        #[arg(long, help = "Include files matching sensitive_patterns")]
+        include_sensitive: bool,
     },
 
     #[command(about = "Start interactive search mode")]
     Interactive,
+
+    #[command(about = "Serve JSON-RPC over stdin/stdout for editor integrations")]
+    Rpc {
+        #[arg(long, value_name = "DIR", help = "Watch DIR and notify the client of index changes")]
+        watch: Option<PathBuf>,
+    },
+
+    #[command(about = "Show what a running server has been doing")]
+    Activity {
+        #[arg(long, default_value = "http://127.0.0.1:8080", help = "Server to ask")]
+        server: String,
+
+        #[arg(long, help = "API key, when the server requires one")]
+        api_key: Option<String>,
+
+        #[arg(long, value_name = "ID", help = "Only events after this id")]
+        since: Option<u64>,
+
+        #[arg(short, long, help = "Keep printing events as they happen")]
+        follow: bool,
+
+        #[arg(long, help = "Print events as JSON lines")]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum SavedAction {
+    #[command(about = "Save a query under a name, replacing any existing one")]
+    Save {
+        #[arg(help = "Saved search name")]
+        name: String,
+
+        #[arg(help = "Search query")]
+        query: String,
+
+        #[arg(
+            long,
+            value_parser = parse_listing_sort,
+            help = "Listing order: modified_desc, modified_asc, name_asc or size_desc"
+        )]
+        sort: Option<ListingSort>,
+    },
+
+    #[command(about = "List saved searches")]
+    List,
+
+    #[command(about = "Run a saved search")]
+    Run {
+        #[arg(help = "Saved search name")]
+        name: String,
+    },
+
+    #[command(about = "Delete a saved search")]
+    Delete {
+        #[arg(help = "Saved search name")]
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum TagsAction {
+    #[command(about = "List tags with the number of files carrying each")]
+    List,
+}
+
+#[derive(Subcommand)]
+enum MetaAction {
+    #[command(about = "Print the value of one setting")]
+    Get {
+        #[arg(help = "Setting key, e.g. storage.path_layout")]
+        key: String,
+    },
+
+    #[command(about = "Store a setting; only user.* keys without --force")]
+    Set {
+        #[arg(help = "Setting key")]
+        key: String,
+
+        #[arg(help = "Raw value to store")]
+        value: String,
+
+        #[arg(long, help = "Allow keys the index manages itself")]
+        force: bool,
+    },
+
+    #[command(about = "List every stored setting")]
+    List,
+}
+
+impl Commands {
+    fn creates_index(&self) -> bool {
+        matches!(
+            self,
+            Commands::Index { .. }
+                | Commands::Update { .. }
+                | Commands::Watch { .. }
+                | Commands::Rpc { watch: Some(_) }
+        )
+    }
+
+    fn in_memory(&self) -> bool {
+        matches!(self, Commands::Search { memory: Some(_), .. })
+    }
+
+    fn json_output(&self) -> bool {
+        matches!(self, Commands::Search { json: true, .. })
+    }
+}
+
+/// With `--json`, the error goes to stdout as `{"error": {"code", "message", "path"}}` so
+/// scripts parsing the output can tell failures apart by code.
+fn exit_with_error(err: &SearchError, message: &str, json: bool) -> ! {
+    if json {
+        println!("{}", serde_json::json!({ "error": err }));
+    } else {
+        eprintln!("{}", message);
+    }
+    std::process::exit(1);
+}
+
+/// `--no-wait` and `--steal`; `None` waits for another writer as long as the config allows.
+fn lease_wait(no_wait: bool, steal: bool) -> Option<LeaseWait> {
+    if steal {
+        Some(LeaseWait::Steal)
+    } else if no_wait {
+        Some(LeaseWait::NoWait)
+    } else {
+        None
+    }
+}
+
+fn parse_listing_sort(input: &str) -> Result<ListingSort, String> {
+    ListingSort::parse(input).ok_or_else(|| format!("unknown listing sort: {}", input))
+}
+
+fn parse_sort_key(input: &str) -> Result<SortKey, String> {
+    SortKey::parse(input).ok_or_else(|| format!("unknown sort: {}", input))
+}
+
+fn parse_date_style(input: &str) -> Result<DateStyle, String> {
+    DateStyle::try_from(input.to_string())
+}
+
+fn parse_audit_event_type(input: &str) -> Result<AuditEventType, String> {
+    AuditEventType::parse(input).ok_or_else(|| format!("unknown audit event type: {}", input))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    Jsonl,
+    Text,
 }
 
 fn main() {
@@ -102,41 +559,247 @@ fn main() {
         .index
         .unwrap_or_else(|| PathBuf::from("./filesearch.db"));
 
-    let engine = match SearchEngine::new(&index_path) {
-        Ok(e) => e,
-        Err(err) => {
-            eprintln!("Failed to initialize search engine: {}", err);
+    let config = match cli.config {
+        Some(ref path) => match SearchConfig::from_file(path) {
+            Ok(config) => config,
+            Err(err) => {
+                eprintln!("Failed to load configuration {}: {}", path.display(), err);
+                std::process::exit(1);
+            }
+        },
+        None => SearchConfig::default(),
+    };
+
+    let color = if cli.no_color { ColorChoice::Never } else { cli.color };
+    let use_colors = colors_enabled(
+        color,
+        std::env::var_os("NO_COLOR").as_deref(),
+        std::io::stdout().is_terminal(),
+    );
+    // Keep the colored crate's own detection from second-guessing the decision above.
+    colored::control::set_override(use_colors);
+    let theme = Theme::resolve(config.cli_theme, use_colors);
+    let mut format = FormatOptions::from_config(&config);
+    if cli.si {
+        format.size_units = SizeUnits::Decimal;
+    }
+    if let Some(style) = cli.date_format {
+        format.date_style = style;
+    }
+    let pager = Pager::detect(
+        std::io::stdout().is_terminal(),
+        config.cli_pager && !cli.no_pager,
+        config.cli_pager_threshold,
+        std::env::var_os("PAGER").as_deref(),
+    );
+
+    // Asks a server; no local index is opened.
+    if let Commands::Activity {
+        server,
+        api_key,
+        since,
+        follow,
+        json,
+    } = cli.command
+    {
+        let formatter = OutputFormatter::new(theme, cli.verbose).with_format(format);
+        if let Err(err) = activity::run(&formatter, &server, api_key, since, follow, json) {
+            eprintln!("Error: {}", err);
             std::process::exit(1);
         }
+        return;
+    }
+
+    let json_output = cli.command.json_output();
+    let engine = match open_engine(
+        &index_path,
+        cli.command.creates_index(),
+        cli.command.in_memory(),
+        config,
+    ) {
+        Ok(e) => e,
+        Err(err) => exit_with_error(&err, &open_error_message(&err), json_output),
     };
 
-    let executor = CommandExecutor::new(engine, !cli.no_color, cli.verbose);
+    if let Commands::Interactive = cli.command {
+        let mut interactive = InteractiveMode::new(engine, theme).with_format(format);
+        let result = if std::io::stdin().is_terminal() {
+            interactive.run_with_raw_mode()
+        } else {
+            interactive.run()
+        };
+        if let Err(err) = result {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    let result = match cli.command {
-        Commands::Index { path, progress } => executor.index(path, progress),
-        Commands::Update { path, progress } => executor.update(path, progress),
-        Commands::Search { query } => executor.search(query),
-        Commands::Stats => executor.stats(),
-        Commands::Verify { path } => executor.verify(path),
-        Commands::Watch { path } => executor.watch(path),
-        Commands::Clear { confirm } => executor.clear(confirm),
-        Commands::Vacuum => executor.vacuum(),
-        Commands::Export { output, query } => executor.export(output, query),
-        Commands::Interactive => {
-            let engine = match SearchEngine::new(&index_path) {
-                Ok(e) => e,
+    if let Commands::Rpc { watch } = cli.command {
+        let mut engine = engine;
+        let server = match watch {
+            Some(root) => match engine.start_watching(&root) {
+                Ok(()) => {
+                    let events = engine.subscribe_watch_events();
+                    let server = RpcServer::new(engine);
+                    match events {
+                        Some(events) => server.with_events(events),
+                        None => server,
+                    }
+                }
                 Err(err) => {
-                    eprintln!("Failed to initialize search engine: {}", err);
+                    eprintln!("Error: {}", err);
                     std::process::exit(1);
                 }
-            };
-            let mut interactive = InteractiveMode::new(engine);
-            interactive.run()
+            },
+            None => RpcServer::new(engine),
+        };
+        // stdout carries protocol messages only; everything else goes to stderr.
+        match server.serve(std::io::stdin().lock(), std::io::stdout()) {
+            Ok(clean) => std::process::exit(if clean { 0 } else { 1 }),
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let executor = CommandExecutor::new(engine, theme, cli.verbose)
+        .with_format(format)
+        .with_pager(pager);
+
+    let result = match cli.command {
+        Commands::Index {
+            path,
+            manifest,
+            parallel,
+            progress,
+            quick,
+            no_wait,
+            steal,
+        } => match manifest {
+            Some(manifest) => {
+                executor.index_manifest(manifest, parallel, progress, lease_wait(no_wait, steal))
+            }
+            None => executor.index(
+                path.expect("clap requires a path without --manifest"),
+                progress,
+                quick,
+                lease_wait(no_wait, steal),
+            ),
+        },
+        Commands::Enrich { path, progress } => executor.enrich(path, progress),
+        Commands::Update {
+            paths,
+            all,
+            continue_on_error,
+            progress,
+            no_wait,
+            steal,
+        } => executor.update(
+            paths,
+            all,
+            continue_on_error,
+            progress,
+            lease_wait(no_wait, steal),
+        ),
+        Commands::Search {
+            query,
+            params,
+            json,
+            memory,
+            global,
+            root,
+            tag_as,
+            yes,
+            context,
+            sort,
+        } => {
+            let root = SearchRoot::from_flags(global, root);
+            match (memory, tag_as) {
+                (Some(dir), _) => {
+                    executor.search_in_memory(dir, query, &params, json, context, sort)
+                }
+                (None, Some(tag)) => executor.tag_results(query, &params, &root, tag, yes),
+                (None, None) => executor.search(query, &params, &root, json, context, sort),
+            }
+        }
+        Commands::Stats => executor.stats(),
+        Commands::Verify {
+            path,
+            progress,
+            list_problems,
+        } => executor.verify(path, progress, list_problems),
+        Commands::Watch {
+            path,
+            no_initial_scan,
+            no_recursive,
+        } => executor.watch(path, !no_initial_scan, !no_recursive),
+        Commands::Clear {
+            confirm,
+            include_audit,
+            no_undo,
+        } => executor.clear(confirm, include_audit, !no_undo),
+        Commands::Undo { list } => {
+            if list {
+                executor.list_undo_snapshots()
+            } else {
+                executor.undo()
+            }
+        }
+        Commands::Audit {
+            path,
+            since,
+            until,
+            event_types,
+            limit,
+        } => executor.audit(path, (since, until), event_types, limit),
+        Commands::Saved { action } => match action {
+            SavedAction::Save { name, query, sort } => executor.save_search(name, query, sort),
+            SavedAction::List => executor.list_saved_searches(),
+            SavedAction::Run { name } => executor.run_saved_search(name),
+            SavedAction::Delete { name } => executor.delete_saved_search(name),
+        },
+        Commands::Tags { action } => match action {
+            TagsAction::List => executor.list_tags(),
+        },
+        Commands::Meta { action } => match action {
+            MetaAction::Get { key } => executor.meta_get(&key),
+            MetaAction::Set { key, value, force } => executor.meta_set(&key, &value, force),
+            MetaAction::List => executor.meta_list(),
+        },
+        Commands::Smart {
+            name,
+            limit,
+            offset,
+        } => executor.smart(name, limit, offset),
+        Commands::Vacuum => executor.vacuum(),
+        Commands::Maintain { once } => executor.maintain(once),
+        Commands::RebuildFts { progress } => executor.rebuild_fts(progress),
+        Commands::Backup {
+            dest,
+            compress,
+            progress,
+        } => executor.backup(&dest, compress, progress),
+        Commands::Restore { source, progress } => executor.restore(&source, progress),
+        Commands::Dump { canonical: _, output } => executor.dump(output),
+        Commands::Migrate { compact } => executor.migrate(compact),
+        Commands::Export {
+            output,
+            query,
+            since,
+            format,
+            include_sensitive,
+        } => match since {
+            Some(since) => executor.export_changes(output, since, format, include_sensitive),
+            None => executor.export(output, query, format, include_sensitive),
+        },
+        Commands::Interactive | Commands::Rpc { .. } | Commands::Activity { .. } => {
+            unreachable!()
         }
     };
 
     if let Err(err) = result {
-        eprintln!("Error: {}", err);
-        std::process::exit(1);
+        exit_with_error(&err, &format!("Error: {}", err), json_output);
     }
 }
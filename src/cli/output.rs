@@ -1,49 +1,226 @@
-use rusty_files::core::types::{IndexStats, SearchResult};
-use rusty_files::filters::{format_date, format_relative_date, format_size};
-use rusty_files::indexer::{UpdateStats, VerificationStats};
-use colored::*;
+use chrono::{DateTime, Utc};
+use rusty_files::{
+    format_date_with, format_size_with, timestamp_sanity, AuditRecord, Capabilities, ContentSkip,
+    DateStyle, FileEntry, IndexStats, Listing, MatchField, QueryDiagnostic, RootIndexReport,
+    SavedSearch, ScriptInfo, SearchConfig, SearchResult, Setting, SizeUnits, SkippedEntry,
+    TimestampSanity, UndoSnapshot, UpdateStats, VerificationStats,
+};
+use crate::theme::{Style, Theme};
+use rusty_files::server::activity::{ActivityEvent, ActivityRecord};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// How sizes and dates are rendered for people. JSON and other machine output always uses
+/// the canonical forms.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatOptions {
+    pub size_units: SizeUnits,
+    /// Dates of results and listings; stats and logs show `Relative` as `Iso`.
+    pub date_style: DateStyle,
+    pub decimal_separator: char,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            size_units: SizeUnits::Binary,
+            date_style: DateStyle::Relative,
+            decimal_separator: '.',
+        }
+    }
+}
+
+impl FormatOptions {
+    pub fn from_config(config: &SearchConfig) -> Self {
+        Self {
+            size_units: config.cli_size_units,
+            date_style: config.cli_date_format.clone(),
+            decimal_separator: config.cli_decimal_separator,
+        }
+    }
+
+    pub fn size(&self, bytes: u64) -> String {
+        format_size_with(bytes, self.size_units, self.decimal_separator)
+    }
+
+    pub fn date(&self, date: DateTime<Utc>) -> String {
+        format_date_with(date, &self.date_style)
+    }
+
+    /// An absolute date, for stats and logs where "3 days ago" reads worse than the time.
+    pub fn timestamp(&self, date: DateTime<Utc>) -> String {
+        match self.date_style {
+            DateStyle::Relative => format_date_with(date, &DateStyle::Iso),
+            ref style => format_date_with(date, style),
+        }
+    }
+}
+
+pub type Writer = Box<dyn Write + Send>;
+
+/// Where output goes. Once a write fails, e.g. because `head` closed the pipe, further
+/// output is dropped instead of failing every command that prints.
+struct Sink {
+    writer: Writer,
+    closed: bool,
+}
+
+/// An in-memory writer whose contents can be taken while the formatter still holds it.
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
 
 pub struct OutputFormatter {
-    use_colors: bool,
+    theme: Theme,
     verbose: bool,
+    format: FormatOptions,
+    sink: Mutex<Sink>,
 }
 
 impl OutputFormatter {
-    pub fn new(use_colors: bool, verbose: bool) -> Self {
-        Self { use_colors, verbose }
+    /// Writes to stdout; errors always go to stderr.
+    pub fn new(theme: Theme, verbose: bool) -> Self {
+        Self::with_writer(theme, verbose, io::stdout())
+    }
+
+    pub fn with_writer(theme: Theme, verbose: bool, writer: impl Write + Send + 'static) -> Self {
+        Self {
+            theme,
+            verbose,
+            format: FormatOptions::default(),
+            sink: Mutex::new(Sink {
+                writer: Box::new(writer),
+                closed: false,
+            }),
+        }
+    }
+
+    pub fn with_format(mut self, format: FormatOptions) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sends output to `writer` from now on and returns the previous writer.
+    pub fn redirect(&self, writer: Writer) -> Writer {
+        std::mem::replace(&mut self.sink.lock().unwrap().writer, writer)
     }
 
-    pub fn print_search_results(&self, results: &[SearchResult], query: &str) {
+    /// Writes `bytes` as they are, such as output rendered earlier or JSON.
+    pub fn write_raw(&self, bytes: &[u8]) {
+        let mut sink = self.sink.lock().unwrap();
+        if sink.closed {
+            return;
+        }
+        if let Err(err) = sink.writer.write_all(bytes).and_then(|()| sink.writer.flush()) {
+            if err.kind() != io::ErrorKind::BrokenPipe {
+                log::debug!("stopping output: {}", err);
+            }
+            sink.closed = true;
+        }
+    }
+
+    fn write(&self, text: &str) {
+        self.write_raw(text.as_bytes());
+    }
+
+    pub fn print_line(&self, text: &str) {
+        self.write(&format!("{}\n", text));
+    }
+
+    fn print_table(&self, headers: &[&str], rows: &[Vec<String>]) {
+        self.write(&format_table(headers, rows, &self.theme));
+    }
+
+    pub fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+
+    pub fn format(&self) -> &FormatOptions {
+        &self.format
+    }
+
+    /// `scripts` holds the shebang details of results that are scripts, keyed by file id;
+    /// they are shown in verbose mode.
+    pub fn print_search_results(
+        &self,
+        results: &[SearchResult],
+        query: &str,
+        scripts: &HashMap<i64, ScriptInfo>,
+        link_targets: &HashMap<i64, PathBuf>,
+    ) {
         if results.is_empty() {
             self.print_info(&format!("No results found for query: {}", query));
             return;
         }
 
         self.print_header(&format!("Found {} results for: {}", results.len(), query));
-        println!();
+        self.print_line("");
 
         for (idx, result) in results.iter().enumerate() {
-            self.print_search_result(idx + 1, result);
+            let script = result.file.id.and_then(|id| scripts.get(&id));
+            let link_target = link_target(&result.file, link_targets);
+            self.print_search_result(idx + 1, result, script, link_target);
         }
 
-        println!();
+        self.print_line("");
         self.print_summary(&format!("Total: {} results", results.len()));
     }
 
-    pub fn print_search_result(&self, index: usize, result: &SearchResult) {
+    pub fn print_search_result(
+        &self,
+        index: usize,
+        result: &SearchResult,
+        script: Option<&ScriptInfo>,
+        link_target: Option<&Path>,
+    ) {
+        self.write(&self.format_search_result(index, result, script, link_target));
+    }
+
+    pub fn format_search_result(
+        &self,
+        index: usize,
+        result: &SearchResult,
+        script: Option<&ScriptInfo>,
+        link_target: Option<&Path>,
+    ) -> String {
         let file = &result.file;
+        let theme = &self.theme;
 
         let index_str = format!("[{}]", index);
-        let name = &file.name;
+        let name = &display_name(file, link_target);
         let path = file.path.display().to_string();
 
-        if self.use_colors {
-            print!("{} ", index_str.bright_black());
-            print!("{} ", name.bright_white().bold());
-            println!("{}", path.bright_black());
+        let mut output = if theme.is_plain() {
+            format!("{} {} ({})\n", index_str, name, path)
         } else {
-            println!("[{}] {} ({})", index, name, path);
-        }
+            // `display_name` starts with the name, so name matches line up with it.
+            let name_spans = match_spans(result, MatchField::Name);
+            let path_spans = match_spans(result, MatchField::Path);
+            format!(
+                "{} {} {}\n",
+                theme.detail.paint(&index_str),
+                paint_matches(name, &name_spans, theme.filename, theme.highlight),
+                paint_matches(&path, &path_spans, theme.path, theme.highlight)
+            )
+        };
 
         if self.verbose {
             let mut details = Vec::new();
@@ -52,69 +229,328 @@ impl OutputFormatter {
                 details.push(format!("ext: {}", ext));
             }
 
-            details.push(format!("size: {}", format_size(file.size)));
+            if let Some(script) = script {
+                let executable = if script.is_executable { "executable " } else { "" };
+                details.push(format!("{}{} script", executable, script.language));
+            }
+
+            if let Some(target) = link_target {
+                details.push(format!("store: {}", target.display()));
+            }
+
+            details.push(format!("size: {}", self.format.size(file.size)));
 
             if let Some(modified) = file.modified_at {
-                details.push(format!("modified: {}", format_relative_date(modified)));
+                let modified = match timestamp_sanity(modified, Utc::now()) {
+                    TimestampSanity::Plausible => self.format.date(modified),
+                    // A relative age means nothing for a skewed clock; show the stored value.
+                    sanity => format!("{} ({})", self.format.timestamp(modified), sanity.as_str()),
+                };
+                details.push(format!("modified: {}", modified));
             }
 
             if result.score > 0.0 {
                 details.push(format!("score: {:.2}", result.score));
             }
 
-            let details_str = details.join(" | ");
-            if self.use_colors {
-                println!("  {}", details_str.bright_black());
-            } else {
-                println!("  {}", details_str);
+            for field in [MatchField::Name, MatchField::Path] {
+                let columns: Vec<String> = result
+                    .matches
+                    .iter()
+                    .filter(|location| location.field == field)
+                    .map(|location| location.column.to_string())
+                    .collect();
+                if !columns.is_empty() {
+                    let field = if field == MatchField::Name { "name" } else { "path" };
+                    details.push(format!("{} match: col {}", field, columns.join(", ")));
+                }
             }
+
+            output.push_str(&format!("  {}\n", theme.detail.paint(&details.join(" | "))));
         }
 
         if let Some(ref snippet) = result.snippet {
-            if self.use_colors {
-                println!("  {}", snippet.as_str().bright_yellow());
-            } else {
-                println!("  {}", snippet);
+            output.push_str(&format!("  {}\n", theme.snippet.paint(snippet)));
+        }
+
+        // grep -C style: `N:` marks the matching line, `N-` the lines around it. Matching
+        // lines without context (no `context:N`) repeat the snippet, so only verbose output
+        // lists those, with their column.
+        let content = || {
+            result
+                .matches
+                .iter()
+                .filter(|location| location.field == MatchField::Content)
+        };
+        let has_context = content().any(|location| {
+            location.context_start_line != location.line || location.context.contains('\n')
+        });
+        let blocks = content().filter(|_| self.verbose || has_context);
+        for (i, location) in blocks.enumerate() {
+            if i > 0 {
+                output.push_str(&format!("    {}\n", theme.detail.paint("--")));
             }
+            for (offset, line) in location.context.split('\n').enumerate() {
+                let number = location.context_start_line + offset;
+                let line = if number == location.line && self.verbose {
+                    theme.snippet.paint(&format!("{}:{}: {}", number, location.column, line))
+                } else if number == location.line {
+                    theme.snippet.paint(&format!("{}: {}", number, line))
+                } else {
+                    theme.detail.paint(&format!("{}- {}", number, line))
+                };
+                output.push_str(&format!("    {}\n", line));
+            }
+        }
+
+        output.push('\n');
+        output
+    }
+
+    /// In verbose mode, notes which tags a `tag:` filter matched against.
+    pub fn print_tag_filter(&self, tags: &[String]) {
+        if self.verbose && !tags.is_empty() {
+            self.print_info(&format!("Tag filter: {} (user tags)", tags.join(", ")));
+        }
+    }
+
+    pub fn print_tags(&self, tags: &[(String, usize)]) {
+        if tags.is_empty() {
+            self.print_info("No tags");
+            return;
         }
 
-        println!();
+        let rows: Vec<Vec<String>> = tags
+            .iter()
+            .map(|(tag, count)| vec![tag.clone(), count.to_string()])
+            .collect();
+
+        self.print_table(&["Tag", "Files"], &rows);
+    }
+
+    pub fn print_settings(&self, settings: &[Setting]) {
+        if settings.is_empty() {
+            self.print_info("No settings");
+            return;
+        }
+
+        let rows: Vec<Vec<String>> = settings
+            .iter()
+            .map(|setting| {
+                vec![
+                    setting.key.clone(),
+                    setting.value.clone(),
+                    self.format.timestamp(setting.updated_at),
+                ]
+            })
+            .collect();
+
+        self.print_table(&["Key", "Value", "Updated"], &rows);
+    }
+
+    pub fn print_undo_snapshots(&self, snapshots: &[UndoSnapshot]) {
+        if snapshots.is_empty() {
+            self.print_info("No undo snapshots");
+            return;
+        }
+
+        let rows: Vec<Vec<String>> = snapshots
+            .iter()
+            .map(|snapshot| {
+                vec![
+                    snapshot.operation.clone(),
+                    self.format.timestamp(snapshot.created_at),
+                    self.format.size(snapshot.size_bytes),
+                ]
+            })
+            .collect();
+
+        self.print_table(&["Operation", "Taken", "Size"], &rows);
     }
 
     pub fn print_index_stats(&self, stats: &IndexStats) {
         self.print_header("Index Statistics");
-        println!();
+        self.print_line("");
 
         self.print_stat("Total Files", &stats.total_files.to_string());
         self.print_stat("Total Directories", &stats.total_directories.to_string());
-        self.print_stat("Total Size", &format_size(stats.total_size));
+        self.print_stat("Total Size", &self.format.size(stats.total_size));
+        if stats.store_aliases > 0 {
+            self.print_stat("Store Aliases", &stats.store_aliases.to_string());
+        }
         self.print_stat(
             "Indexed Files (Content)",
             &stats.indexed_files.to_string(),
         );
-        self.print_stat("Last Update", &format_date(stats.last_update));
-        self.print_stat("Index Size", &format_size(stats.index_size));
+        self.print_stat("Last Update", &self.format.timestamp(stats.last_update));
+        self.print_stat("Index Size", &self.format.size(stats.index_size));
+        if let Some(quota) = &stats.quota {
+            self.print_stat(
+                "Quota Usage",
+                &format!(
+                    "{:.1}% of {}",
+                    quota.usage_percent(),
+                    self.format.size(quota.limit_bytes)
+                ),
+            );
+        }
+        for progress in &stats.enrichment {
+            self.print_stat(
+                &format!("Enriching {}", progress.root.display()),
+                &format!(
+                    "{:.1}% ({} of {} files pending)",
+                    progress.percent(),
+                    progress.pending,
+                    progress.total
+                ),
+            );
+        }
+        if let Some(lease) = &stats.write_lease {
+            self.print_stat(
+                "Write Lease",
+                &format!(
+                    "pid {} on {} since {} (heartbeat {})",
+                    lease.pid,
+                    lease.hostname,
+                    self.format.timestamp(lease.acquired_at),
+                    self.format.timestamp(lease.heartbeat_at)
+                ),
+            );
+        }
+        if let Some(usage) = &stats.name_index {
+            self.print_stat(
+                "Name Index",
+                &format!(
+                    "{} entries in {}{}",
+                    usage.entries,
+                    self.format.size(usage.memory_bytes),
+                    if usage.current { "" } else { " (rebuilding)" }
+                ),
+            );
+        }
+        for status in &stats.maintenance {
+            let last_run = match (status.enabled, status.last_run) {
+                (false, _) => "disabled".to_string(),
+                (true, Some(at)) => self.format.timestamp(at),
+                (true, None) => "never".to_string(),
+            };
+            self.print_stat(&format!("Last {}", status.task.as_str()), &last_run);
+        }
 
-        println!();
+        self.print_line("");
+    }
+
+    pub fn print_capabilities(&self, capabilities: &Capabilities) {
+        if !self.verbose {
+            return;
+        }
+
+        self.print_header("Capabilities");
+        self.print_line("");
+
+        let flag = |enabled: bool| if enabled { "yes" } else { "no" };
+        self.print_stat("Content Search", flag(capabilities.content_search));
+        self.print_stat("Fuzzy Search", flag(capabilities.fuzzy_search));
+        self.print_stat("Access Tracking", flag(capabilities.access_tracking));
+        self.print_stat("File Hashes", flag(capabilities.file_hashes));
+        self.print_stat("Watch Backend", &capabilities.watch_backend);
+        self.print_stat("Read Only", flag(capabilities.read_only));
+
+        self.print_line("");
     }
 
     pub fn print_update_stats(&self, stats: &UpdateStats) {
         self.print_header("Index Update Summary");
-        println!();
+        self.print_line("");
 
         self.print_stat("Files Added", &stats.added.to_string());
         self.print_stat("Files Updated", &stats.updated.to_string());
         self.print_stat("Files Removed", &stats.removed.to_string());
+        if !stats.skipped.is_empty() {
+            self.print_stat("Files Skipped", &stats.skipped.len().to_string());
+        }
+        if !stats.possibly_inconsistent.is_empty() {
+            self.print_stat(
+                "Changed While Read",
+                &stats.possibly_inconsistent.len().to_string(),
+            );
+        }
         self.print_stat("Total Changes", &stats.total().to_string());
 
-        println!();
+        self.print_line("");
+    }
+
+    pub fn print_skipped(&self, skipped: &[SkippedEntry], failed_batches: usize) {
+        if skipped.is_empty() {
+            return;
+        }
+
+        self.print_warning(&format!(
+            "{} files were not indexed after {} failed batches",
+            skipped.len(),
+            failed_batches
+        ));
+
+        if self.verbose {
+            for entry in skipped {
+                self.print_line(&format!(
+                    "  {}: {}",
+                    self.theme.path.paint(&entry.path.display().to_string()),
+                    self.theme.detail.paint(&entry.error)
+                ));
+            }
+        }
+    }
+
+    pub fn print_content_skipped(&self, skipped: &[ContentSkip]) {
+        if skipped.is_empty() {
+            return;
+        }
+
+        self.print_warning(&format!(
+            "{} files were indexed without their content",
+            skipped.len()
+        ));
+
+        if self.verbose {
+            for entry in skipped {
+                self.print_line(&format!(
+                    "  {}: {}",
+                    self.theme.path.paint(&entry.path.display().to_string()),
+                    self.theme.detail.paint(entry.reason.as_str())
+                ));
+            }
+        }
+    }
+
+    pub fn print_possibly_inconsistent(&self, paths: &[PathBuf]) {
+        if paths.is_empty() {
+            return;
+        }
+
+        self.print_warning(&format!(
+            "{} files changed while their content was read; the next update reads them again",
+            paths.len()
+        ));
+
+        if self.verbose {
+            for path in paths {
+                self.print_line(&format!(
+                    "  {}",
+                    self.theme.path.paint(&path.display().to_string())
+                ));
+            }
+        }
     }
 
     pub fn print_verification_stats(&self, stats: &VerificationStats) {
         self.print_header("Index Verification Results");
-        println!();
+        self.print_line("");
 
         self.print_stat("Total Indexed", &stats.total_indexed.to_string());
+        if stats.cancelled {
+            self.print_stat("Checked", &format!("{} (cancelled)", stats.checked()));
+        }
         self.print_stat("Valid", &stats.valid.to_string());
         self.print_stat("Outdated", &stats.outdated.to_string());
         self.print_stat("Missing", &stats.missing.to_string());
@@ -123,84 +559,429 @@ impl OutputFormatter {
             &format!("{:.1}%", stats.health_percentage()),
         );
 
-        println!();
+        self.print_line("");
     }
 
-    fn print_stat(&self, label: &str, value: &str) {
-        if self.use_colors {
-            println!("  {}: {}", label.cyan(), value.white());
-        } else {
-            println!("  {}: {}", label, value);
+    pub fn print_verification_problems(&self, stats: &VerificationStats) {
+        if stats.problems.is_empty() {
+            self.print_info("No problem paths found");
+            return;
         }
+
+        let rows: Vec<Vec<String>> = stats
+            .problems
+            .iter()
+            .map(|problem| {
+                vec![
+                    problem.kind.as_str().to_string(),
+                    problem.path.display().to_string(),
+                ]
+            })
+            .collect();
+
+        self.print_table(&["Status", "Path"], &rows);
+        self.print_line("");
+    }
+
+    pub fn print_root_reports(&self, reports: &[RootIndexReport]) {
+        self.print_header("Manifest Indexing Summary");
+        self.print_line("");
+
+        let rows: Vec<Vec<String>> = reports
+            .iter()
+            .map(|report| {
+                vec![
+                    report.path.display().to_string(),
+                    report.indexed.to_string(),
+                    report.skipped.to_string(),
+                    format!("{:.2}s", report.duration.as_secs_f64()),
+                    report.error.clone().unwrap_or_else(|| "ok".to_string()),
+                ]
+            })
+            .collect();
+
+        self.print_table(&["Root", "Files", "Skipped", "Duration", "Status"], &rows);
+        self.print_line("");
+    }
+
+    pub fn print_update_reports(&self, results: &[(PathBuf, rusty_files::Result<UpdateStats>)]) {
+        self.print_header("Update Summary by Root");
+        self.print_line("");
+
+        let rows: Vec<Vec<String>> = results
+            .iter()
+            .map(|(path, result)| {
+                let mut row = vec![path.display().to_string()];
+                match result {
+                    Ok(stats) => row.extend([
+                        stats.added.to_string(),
+                        stats.updated.to_string(),
+                        stats.removed.to_string(),
+                        stats.skipped.len().to_string(),
+                        "ok".to_string(),
+                    ]),
+                    Err(err) => {
+                        row.extend(["-", "-", "-", "-"].map(String::from));
+                        row.push(err.to_string());
+                    }
+                }
+                row
+            })
+            .collect();
+
+        self.print_table(&["Root", "Added", "Updated", "Removed", "Skipped", "Status"], &rows);
+        self.print_line("");
+    }
+
+    pub fn print_saved_searches(&self, searches: &[SavedSearch]) {
+        if searches.is_empty() {
+            self.print_info("No saved searches");
+            return;
+        }
+
+        let rows: Vec<Vec<String>> = searches
+            .iter()
+            .map(|search| {
+                vec![
+                    search.name.clone(),
+                    search.query.clone(),
+                    search.sort.unwrap_or_default().as_str().to_string(),
+                    search
+                        .last_run_at
+                        .map(|at| self.format.date(at))
+                        .unwrap_or_else(|| "never".to_string()),
+                    search
+                        .last_result_count
+                        .map(|count| count.to_string())
+                        .unwrap_or_else(|| "-".to_string()),
+                ]
+            })
+            .collect();
+
+        self.print_table(&["Name", "Query", "Sort", "Last run", "Results"], &rows);
+    }
+
+    pub fn print_listing(&self, listing: &Listing, link_targets: &HashMap<i64, PathBuf>) {
+        if listing.entries.is_empty() {
+            self.print_info(&format!("No entries in: {}", listing.name));
+            return;
+        }
+
+        self.print_header(&format!("{} ({})", listing.name, listing.sort.as_str()));
+        self.print_line("");
+
+        let rows: Vec<Vec<String>> = listing
+            .entries
+            .iter()
+            .map(|entry| {
+                vec![
+                    entry
+                        .modified_at
+                        .map(|at| self.format.timestamp(at))
+                        .unwrap_or_else(|| "-".to_string()),
+                    if entry.is_directory {
+                        "-".to_string()
+                    } else {
+                        self.format.size(entry.size)
+                    },
+                    display_name(entry, link_target(entry, link_targets)),
+                    entry
+                        .parent_path
+                        .as_ref()
+                        .map(|parent| parent.display().to_string())
+                        .unwrap_or_default(),
+                ]
+            })
+            .collect();
+
+        self.print_table(&["Modified", "Size", "Name", "Location"], &rows);
+        self.print_line("");
+        self.print_summary(&format!(
+            "Showing {}-{} of {} entries",
+            listing.offset + 1,
+            listing.offset + listing.entries.len(),
+            listing.total
+        ));
+    }
+
+    pub fn print_audit_log(&self, records: &[AuditRecord], path: &std::path::Path) {
+        if records.is_empty() {
+            self.print_info(&format!("No audit records for: {}", path.display()));
+            return;
+        }
+
+        self.print_header(&format!("Audit log for: {}", path.display()));
+        self.print_line("");
+
+        let rows: Vec<Vec<String>> = records
+            .iter()
+            .map(|record| {
+                vec![
+                    self.format.timestamp(record.occurred_at),
+                    record.event_type.as_str().to_string(),
+                    record.path.display().to_string(),
+                    record
+                        .size
+                        .map(|size| self.format.size(size))
+                        .unwrap_or_else(|| "-".to_string()),
+                    record
+                        .file_hash
+                        .as_deref()
+                        .map(|hash| hash.chars().take(12).collect())
+                        .unwrap_or_else(|| "-".to_string()),
+                    record.watch_id.clone().unwrap_or_else(|| "-".to_string()),
+                ]
+            })
+            .collect();
+
+        self.print_table(&["Time", "Event", "Path", "Size", "Hash", "Watch"], &rows);
+        self.print_line("");
+        self.print_summary(&format!("Total: {} records", records.len()));
+    }
+
+    pub fn print_activity(&self, record: &ActivityRecord) {
+        self.write(&self.format_activity(record));
+    }
+
+    /// One line per event: its id, when it happened and what it was.
+    pub fn format_activity(&self, record: &ActivityRecord) -> String {
+        let id = format!("#{}", record.id);
+        let time = self.format.timestamp(record.timestamp);
+        let text = describe_activity(&record.event);
+        if self.theme.is_plain() {
+            return format!("{} {} {}\n", id, time, text);
+        }
+        let style = match record.event {
+            ActivityEvent::Error { .. } => self.theme.error,
+            ActivityEvent::Gap { .. } | ActivityEvent::WatchDegraded { .. } => self.theme.warning,
+            _ => self.theme.value,
+        };
+        format!(
+            "{} {} {}\n",
+            self.theme.detail.paint(&id),
+            self.theme.detail.paint(&time),
+            style.paint(&text)
+        )
+    }
+
+    fn print_stat(&self, label: &str, value: &str) {
+        self.print_line(&format!(
+            "  {}: {}",
+            self.theme.label.paint(label),
+            self.theme.value.paint(value)
+        ));
     }
 
     pub fn print_header(&self, text: &str) {
-        if self.use_colors {
-            println!("{}", text.bright_green().bold());
-        } else {
-            println!("{}", text);
-            println!("{}", "=".repeat(text.len()));
+        self.print_line(&self.theme.header.paint(text));
+        if self.theme.is_plain() {
+            self.print_line(&"=".repeat(text.len()));
         }
     }
 
     pub fn print_info(&self, text: &str) {
-        if self.use_colors {
-            println!("{}", text.bright_blue());
-        } else {
-            println!("{}", text);
-        }
+        self.print_line(&self.theme.info.paint(text));
     }
 
     pub fn print_success(&self, text: &str) {
-        if self.use_colors {
-            println!("{} {}", "✓".green(), text.green());
+        if self.theme.is_plain() {
+            self.print_line(&format!("[SUCCESS] {}", text));
         } else {
-            println!("[SUCCESS] {}", text);
+            let style = &self.theme.success;
+            self.print_line(&format!("{} {}", style.paint("✓"), style.paint(text)));
         }
     }
 
     pub fn print_error(&self, text: &str) {
-        if self.use_colors {
-            eprintln!("{} {}", "✗".red(), text.red());
-        } else {
+        if self.theme.is_plain() {
             eprintln!("[ERROR] {}", text);
+        } else {
+            let style = &self.theme.error;
+            eprintln!("{} {}", style.paint("✗"), style.paint(text));
+        }
+    }
+
+    /// Each problem under the query, with carets marking the part it is about.
+    pub fn print_query_diagnostics(&self, input: &str, diagnostics: &[QueryDiagnostic]) {
+        for diagnostic in diagnostics {
+            let span = diagnostic.span.clone();
+            let carets = "^".repeat(input[span.clone()].chars().count().max(1));
+            self.print_line(&format!("  {}", input));
+            self.print_line(&format!(
+                "  {}{}",
+                " ".repeat(input[..span.start].chars().count()),
+                self.theme.error.paint(&carets)
+            ));
+            if self.theme.is_plain() {
+                self.print_line(&format!("[ERROR] {}", diagnostic));
+            } else {
+                let style = &self.theme.error;
+                let message = diagnostic.to_string();
+                self.print_line(&format!("{} {}", style.paint("✗"), style.paint(&message)));
+            }
         }
     }
 
     pub fn print_warning(&self, text: &str) {
-        if self.use_colors {
-            println!("{} {}", "⚠".yellow(), text.yellow());
+        if self.theme.is_plain() {
+            self.print_line(&format!("[WARNING] {}", text));
         } else {
-            println!("[WARNING] {}", text);
+            let style = &self.theme.warning;
+            self.print_line(&format!("{} {}", style.paint("⚠"), style.paint(text)));
         }
     }
 
     pub fn print_summary(&self, text: &str) {
-        if self.use_colors {
-            println!("{}", text.bright_white().bold());
-        } else {
-            println!("{}", text);
+        self.print_line(&self.theme.summary.paint(text));
+    }
+}
+
+impl Default for OutputFormatter {
+    fn default() -> Self {
+        Self::new(Theme::default(), false)
+    }
+}
+
+fn describe_activity(event: &ActivityEvent) -> String {
+    let took = |took_ms: u64| format!(" in {} ms", took_ms);
+    match event {
+        ActivityEvent::IndexStarted { path, quick } => {
+            let quick = if *quick { " (quick)" } else { "" };
+            format!("index started: {}{}", path.display(), quick)
+        }
+        ActivityEvent::IndexFinished {
+            path,
+            indexed,
+            skipped,
+            complete,
+            took_ms,
+        } => format!(
+            "index {}: {}, {} indexed, {} skipped{}",
+            if *complete { "finished" } else { "incomplete" },
+            path.display(),
+            indexed,
+            skipped,
+            took(*took_ms)
+        ),
+        ActivityEvent::UpdateFinished {
+            roots,
+            added,
+            updated,
+            removed,
+            failed,
+            took_ms,
+        } => {
+            let failed = if *failed > 0 { format!(", {} failed", failed) } else { String::new() };
+            format!(
+                "update finished: {} root(s){}, {} added, {} updated, {} removed{}",
+                roots.len(),
+                failed,
+                added,
+                updated,
+                removed,
+                took(*took_ms)
+            )
+        }
+        ActivityEvent::WatchStarted { watch_id, path } => {
+            format!("watch started: {} ({})", path.display(), watch_id)
+        }
+        ActivityEvent::WatchStopped { watch_id, path } => {
+            format!("watch stopped: {} ({})", path.display(), watch_id)
+        }
+        ActivityEvent::WatchApplied { path, changes } => {
+            format!("watch applied {} change(s) under {}", changes, path.display())
+        }
+        ActivityEvent::WatchScanFinished { path, indexed } => {
+            format!("watch scan finished: {}, {} indexed", path.display(), indexed)
+        }
+        ActivityEvent::WatchDegraded { path, error } => {
+            format!("watch degraded: {}: {}", path.display(), error)
+        }
+        ActivityEvent::WatchRecovered { path, caught_up } => {
+            format!("watch recovered: {}, {} caught up", path.display(), caught_up)
+        }
+        ActivityEvent::MaintenanceFinished { task, took_ms } => {
+            format!("maintenance finished: {}{}", task, took_ms.map(took).unwrap_or_default())
+        }
+        ActivityEvent::Error {
+            operation,
+            path,
+            message,
+        } => match path {
+            Some(path) => format!("{} failed on {}: {}", operation, path.display(), message),
+            None => format!("{} failed: {}", operation, message),
+        },
+        ActivityEvent::Gap { missed } => {
+            format!("{} event(s) missed; they are no longer buffered", missed)
         }
     }
+}
 
-    pub fn print_progress(&self, message: &str) {
-        if self.use_colors {
-            print!("\r{}", message.bright_black());
-        } else {
-            print!("\r{}", message);
+/// `link_target` is where the index recorded that a link points, if it did.
+pub fn display_name(file: &FileEntry, link_target: Option<&Path>) -> String {
+    if file.is_symlink {
+        match link_target {
+            Some(target) => format!("{} -> {}", file.name, target.display()),
+            None => format!("{} ->", file.name),
         }
-        use std::io::Write;
-        std::io::stdout().flush().ok();
+    } else if file.is_directory {
+        format!("{}/", file.name)
+    } else {
+        file.name.clone()
     }
 }
 
-impl Default for OutputFormatter {
-    fn default() -> Self {
-        Self::new(true, false)
+fn link_target<'a>(file: &FileEntry, link_targets: &'a HashMap<i64, PathBuf>) -> Option<&'a Path> {
+    file.id
+        .and_then(|id| link_targets.get(&id))
+        .map(PathBuf::as_path)
+}
+
+/// The `(offset, length)` character spans of the result's matches in `field`, whose columns
+/// count characters from 1.
+fn match_spans(result: &SearchResult, field: MatchField) -> Vec<(usize, usize)> {
+    result
+        .matches
+        .iter()
+        .filter(|location| location.field == field)
+        .map(|location| (location.column.saturating_sub(1), location.length))
+        .collect()
+}
+
+/// `text` painted with `base`, but for the `(offset, length)` character spans in it, which get
+/// `highlight`.
+fn paint_matches(text: &str, spans: &[(usize, usize)], base: Style, highlight: Style) -> String {
+    match_runs(text, spans)
+        .into_iter()
+        .map(|(run, in_match)| if in_match { highlight } else { base }.paint(&run))
+        .collect()
+}
+
+/// `text` cut where the `(offset, length)` character spans in it start and end, with whether
+/// each piece is in one. There is always at least one piece.
+fn match_runs(text: &str, spans: &[(usize, usize)]) -> Vec<(String, bool)> {
+    let mut highlighted = vec![false; text.chars().count()];
+    for &(offset, length) in spans {
+        for flag in highlighted.iter_mut().skip(offset).take(length) {
+            *flag = true;
+        }
+    }
+
+    let mut runs = Vec::new();
+    let mut run = String::new();
+    let mut in_match = false;
+    for (c, &flag) in text.chars().zip(&highlighted) {
+        if flag != in_match && !run.is_empty() {
+            runs.push((std::mem::take(&mut run), in_match));
+        }
+        in_match = flag;
+        run.push(c);
     }
+    runs.push((run, in_match));
+    runs
 }
 
-pub fn print_table(headers: &[&str], rows: &[Vec<String>], use_colors: bool) {
+pub fn format_table(headers: &[&str], rows: &[Vec<String>], theme: &Theme) -> String {
     let mut col_widths = vec![0; headers.len()];
 
     for (i, header) in headers.iter().enumerate() {
@@ -221,25 +1002,396 @@ pub fn print_table(headers: &[&str], rows: &[Vec<String>], use_colors: bool) {
         .collect::<Vec<_>>()
         .join("+");
 
-    if use_colors {
-        for (i, header) in headers.iter().enumerate() {
-            print!("| {:<width$} ", header.cyan(), width = col_widths[i]);
-        }
-    } else {
-        for (i, header) in headers.iter().enumerate() {
-            print!("| {:<width$} ", header, width = col_widths[i]);
-        }
+    let mut output = String::new();
+
+    // Pad before painting so escape codes don't count towards the column width.
+    for (i, header) in headers.iter().enumerate() {
+        let padded = format!("{:<width$}", header, width = col_widths[i]);
+        output.push_str(&format!("| {} ", theme.label.paint(&padded)));
     }
-    println!("|");
+    output.push_str("|\n");
 
-    println!("+{}+", separator);
+    output.push_str(&format!("+{}+\n", separator));
 
     for row in rows {
         for (i, cell) in row.iter().enumerate() {
             if i < col_widths.len() {
-                print!("| {:<width$} ", cell, width = col_widths[i]);
+                output.push_str(&format!("| {:<width$} ", cell, width = col_widths[i]));
             }
         }
-        println!("|");
+        output.push_str("|\n");
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusty_files::MatchLocation;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_display_name_marks_entry_kinds() {
+        let file = FileEntry::new(PathBuf::from("/data/backup.tar"));
+        let mut dir = FileEntry::new(PathBuf::from("/data/backup"));
+        dir.is_directory = true;
+        let mut link = FileEntry::new(PathBuf::from("/nonexistent/backup-latest"));
+        link.is_symlink = true;
+
+        assert_eq!(display_name(&file, None), "backup.tar");
+        assert_eq!(display_name(&dir, None), "backup/");
+        assert_eq!(display_name(&link, None), "backup-latest ->");
+    }
+
+    #[test]
+    fn test_display_name_shows_the_indexed_link_target() {
+        let mut link = FileEntry::new(PathBuf::from("/nonexistent/latest"));
+        link.is_symlink = true;
+        let target = Path::new("/store/backup.tar");
+
+        assert_eq!(display_name(&link, Some(target)), "latest -> /store/backup.tar");
+
+        let plain = OutputFormatter::new(Theme::plain(), false);
+        let result = SearchResult {
+            file: link,
+            score: 0.0,
+            snippet: None,
+            matches: vec![],
+        };
+        assert_eq!(
+            plain.format_search_result(1, &result, None, Some(target)),
+            "[1] latest -> /store/backup.tar (/nonexistent/latest)\n\n"
+        );
+    }
+
+    #[test]
+    fn test_plain_search_result_snapshot() {
+        let mut file = FileEntry::new(PathBuf::from("/srv/docs/report.md"));
+        file.size = 2048;
+        let result = SearchResult {
+            file,
+            score: 0.87,
+            snippet: Some("quarterly report draft".to_string()),
+            matches: vec![],
+        };
+
+        let formatter = OutputFormatter::new(Theme::plain(), true);
+        assert_eq!(
+            formatter.format_search_result(3, &result, None, None),
+            "[3] report.md (/srv/docs/report.md)\n  ext: md | size: 2.00 KB | score: 0.87\n  quarterly report draft\n\n"
+        );
+    }
+
+    #[test]
+    fn test_result_context_blocks_mark_the_matching_line() {
+        let location = |line, context: &str, context_start_line| MatchLocation {
+            line,
+            column: 1,
+            length: 4,
+            context: context.to_string(),
+            context_start_line,
+            field: Default::default(),
+        };
+        let result = SearchResult {
+            file: FileEntry::new(PathBuf::from("/srv/docs/notes.txt")),
+            score: 0.0,
+            snippet: None,
+            matches: vec![
+                location(2, "intro\nTODO: fix\noutro", 1),
+                location(9, "TODO: ship", 9),
+            ],
+        };
+
+        let formatter = OutputFormatter::new(Theme::plain(), false);
+        assert_eq!(
+            formatter.format_search_result(1, &result, None, None),
+            "[1] notes.txt (/srv/docs/notes.txt)\n    1- intro\n    2: TODO: fix\n    3- outro\n    --\n    9: TODO: ship\n\n"
+        );
+    }
+
+    #[test]
+    fn test_verbose_result_lists_every_match_location() {
+        let result = SearchResult {
+            file: FileEntry::new(PathBuf::from("/srv/docs/todo.txt")),
+            score: 0.0,
+            snippet: None,
+            matches: vec![
+                MatchLocation::in_text(MatchField::Name, "todo.txt", 0, 4),
+                MatchLocation {
+                    line: 3,
+                    column: 7,
+                    length: 4,
+                    context: "fixed todo items".to_string(),
+                    context_start_line: 3,
+                    field: MatchField::Content,
+                },
+            ],
+        };
+
+        let verbose = OutputFormatter::new(Theme::plain(), true);
+        assert_eq!(
+            verbose.format_search_result(1, &result, None, None),
+            "[1] todo.txt (/srv/docs/todo.txt)\n  ext: txt | size: 0 B | name match: col 1\n    3:7: fixed todo items\n\n"
+        );
+        let quiet = OutputFormatter::new(Theme::plain(), false);
+        assert_eq!(
+            quiet.format_search_result(1, &result, None, None),
+            "[1] todo.txt (/srv/docs/todo.txt)\n\n"
+        );
+    }
+
+    #[test]
+    fn test_name_and_path_matches_are_highlighted() {
+        let result = SearchResult {
+            file: FileEntry::new(PathBuf::from("/srv/todo/todo-list.txt")),
+            score: 0.0,
+            snippet: None,
+            matches: vec![
+                MatchLocation::in_text(MatchField::Name, "todo-list.txt", 0, 4),
+                MatchLocation::in_text(MatchField::Name, "todo-list.txt", 5, 4),
+                MatchLocation::in_text(MatchField::Path, "/srv/todo/todo-list.txt", 5, 4),
+            ],
+        };
+
+        let runs = |text: &str, field| -> Vec<(String, bool)> {
+            match_runs(text, &match_spans(&result, field))
+        };
+        let run = |text: &str, in_match| (text.to_string(), in_match);
+        assert_eq!(
+            runs("todo-list.txt", MatchField::Name),
+            [run("todo", true), run("-", false), run("list", true), run(".txt", false)]
+        );
+        assert_eq!(
+            runs("/srv/todo/todo-list.txt", MatchField::Path),
+            [run("/srv/", false), run("todo", true), run("/todo-list.txt", false)]
+        );
+        assert_eq!(runs("", MatchField::Name), [run("", false)]);
+
+        let plain = OutputFormatter::new(Theme::plain(), false);
+        assert_eq!(
+            plain.format_search_result(1, &result, None, None),
+            "[1] todo-list.txt (/srv/todo/todo-list.txt)\n\n"
+        );
+    }
+
+    #[test]
+    fn test_plain_activity_snapshot() {
+        use chrono::TimeZone;
+
+        let formatter = OutputFormatter::new(Theme::plain(), false);
+        let timestamp = Utc.with_ymd_and_hms(2024, 6, 1, 8, 0, 0).unwrap();
+        let line = |id, event| formatter.format_activity(&ActivityRecord { id, timestamp, event });
+
+        assert_eq!(
+            line(
+                3,
+                ActivityEvent::IndexFinished {
+                    path: PathBuf::from("/srv/docs"),
+                    indexed: 120,
+                    skipped: 2,
+                    complete: true,
+                    took_ms: 850,
+                }
+            ),
+            "#3 2024-06-01 08:00:00 UTC index finished: /srv/docs, 120 indexed, 2 skipped in 850 ms\n"
+        );
+        assert_eq!(
+            line(
+                4,
+                ActivityEvent::Error {
+                    operation: "watch".to_string(),
+                    path: Some(PathBuf::from("/srv/docs")),
+                    message: "too many open files".to_string(),
+                }
+            ),
+            "#4 2024-06-01 08:00:00 UTC watch failed on /srv/docs: too many open files\n"
+        );
+        assert_eq!(
+            line(9, ActivityEvent::Gap { missed: 5 }),
+            "#9 2024-06-01 08:00:00 UTC 5 event(s) missed; they are no longer buffered\n"
+        );
+    }
+
+    #[test]
+    fn test_verbose_result_shows_raw_skewed_mtime() {
+        use chrono::TimeZone;
+
+        let mut file = FileEntry::new(PathBuf::from("/srv/docs/report"));
+        file.modified_at = Some(Utc.with_ymd_and_hms(2099, 3, 1, 12, 0, 0).unwrap());
+        let result = SearchResult {
+            file,
+            score: 0.0,
+            snippet: None,
+            matches: vec![],
+        };
+
+        let formatter = OutputFormatter::new(Theme::plain(), true);
+        assert_eq!(
+            formatter.format_search_result(1, &result, None, None),
+            "[1] report (/srv/docs/report)\n  size: 0 B | modified: 2099-03-01 12:00:00 UTC (future)\n\n"
+        );
+    }
+
+    #[test]
+    fn test_verbose_result_marks_executable_scripts() {
+        let result = SearchResult {
+            file: FileEntry::new(PathBuf::from("/srv/bin/deploy")),
+            score: 0.0,
+            snippet: None,
+            matches: vec![],
+        };
+        let mut script = ScriptInfo {
+            interpreter: "bash".to_string(),
+            language: "bash".to_string(),
+            is_executable: true,
+        };
+
+        let formatter = OutputFormatter::new(Theme::plain(), true);
+        assert_eq!(
+            formatter.format_search_result(1, &result, Some(&script), None),
+            "[1] deploy (/srv/bin/deploy)\n  executable bash script | size: 0 B\n\n"
+        );
+        script.is_executable = false;
+        assert!(formatter
+            .format_search_result(1, &result, Some(&script), None)
+            .contains("  bash script | "));
+        let quiet = OutputFormatter::new(Theme::plain(), false);
+        assert_eq!(
+            quiet.format_search_result(1, &result, Some(&script), None),
+            "[1] deploy (/srv/bin/deploy)\n\n"
+        );
+    }
+
+    #[test]
+    fn test_format_options_golden_output() {
+        use chrono::TimeZone;
+
+        let mut file = FileEntry::new(PathBuf::from("/srv/data/archive.bin"));
+        file.size = 1_572_864;
+        file.modified_at = Some(Utc.with_ymd_and_hms(2024, 12, 24, 14, 30, 0).unwrap());
+        let result = SearchResult {
+            file,
+            score: 0.5,
+            snippet: None,
+            matches: vec![],
+        };
+        let locale = DateStyle::parse("%d.%m.%Y %H:%M").unwrap();
+
+        // Relative ages past four weeks are shown as the full date.
+        let golden = [
+            (SizeUnits::Binary, DateStyle::Relative, '.', "1.50 MB", "2024-12-24 14:30:00 UTC"),
+            (SizeUnits::Binary, DateStyle::Relative, ',', "1,50 MB", "2024-12-24 14:30:00 UTC"),
+            (SizeUnits::Binary, DateStyle::Iso, '.', "1.50 MB", "2024-12-24 14:30:00 UTC"),
+            (SizeUnits::Binary, DateStyle::Iso, ',', "1,50 MB", "2024-12-24 14:30:00 UTC"),
+            (SizeUnits::Binary, locale.clone(), '.', "1.50 MB", "24.12.2024 14:30"),
+            (SizeUnits::Binary, locale.clone(), ',', "1,50 MB", "24.12.2024 14:30"),
+            (SizeUnits::Decimal, DateStyle::Relative, '.', "1.57 MB", "2024-12-24 14:30:00 UTC"),
+            (SizeUnits::Decimal, DateStyle::Relative, ',', "1,57 MB", "2024-12-24 14:30:00 UTC"),
+            (SizeUnits::Decimal, DateStyle::Iso, '.', "1.57 MB", "2024-12-24 14:30:00 UTC"),
+            (SizeUnits::Decimal, DateStyle::Iso, ',', "1,57 MB", "2024-12-24 14:30:00 UTC"),
+            (SizeUnits::Decimal, locale.clone(), '.', "1.57 MB", "24.12.2024 14:30"),
+            (SizeUnits::Decimal, locale, ',', "1,57 MB", "24.12.2024 14:30"),
+        ];
+        for (size_units, date_style, decimal_separator, size, date) in golden {
+            let format = FormatOptions {
+                size_units,
+                date_style,
+                decimal_separator,
+            };
+            let formatter = OutputFormatter::new(Theme::plain(), true).with_format(format.clone());
+            assert_eq!(
+                formatter.format_search_result(1, &result, None, None),
+                format!(
+                    "[1] archive.bin (/srv/data/archive.bin)\n  ext: bin | size: {} | \
+                     modified: {} | score: 0.50\n\n",
+                    size, date
+                ),
+                "{:?}",
+                format
+            );
+            assert_eq!(format.timestamp(result.file.modified_at.unwrap()), date, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_relative_dates_are_for_results_only() {
+        let format = FormatOptions::default();
+        let date = Utc::now() - chrono::Duration::days(3);
+        assert_eq!(format.date(date), "3 days ago");
+        assert!(format.timestamp(date).ends_with(" UTC"));
+    }
+
+    #[test]
+    fn test_plain_table_has_no_escape_codes() {
+        let rows = vec![vec!["/srv/docs".to_string(), "12".to_string()]];
+        assert_eq!(
+            format_table(&["Root", "Files"], &rows, &Theme::plain()),
+            "| Root      | Files |\n+-----------+-------+\n| /srv/docs | 12    |\n"
+        );
+    }
+
+    #[test]
+    fn test_output_goes_to_the_writer() {
+        let buffer = SharedBuffer::default();
+        let formatter = OutputFormatter::with_writer(Theme::plain(), false, buffer.clone());
+        let result = SearchResult::new(FileEntry::new(PathBuf::from("/srv/docs/report.md")), 0.5);
+
+        formatter.print_search_results(&[result], "report", &HashMap::new(), &HashMap::new());
+        formatter.print_warning("slow");
+        let output = String::from_utf8(buffer.take()).unwrap();
+        assert!(output.contains("[1] report.md (/srv/docs/report.md)\n"), "{}", output);
+        assert!(output.ends_with("[WARNING] slow\n"), "{}", output);
+
+        let previous = formatter.redirect(Box::new(io::sink()));
+        formatter.print_info("dropped");
+        formatter.redirect(previous);
+        formatter.print_info("kept");
+        assert_eq!(buffer.take(), b"kept\n");
+    }
+
+    #[test]
+    fn test_query_diagnostics_underline_their_span() {
+        let buffer = SharedBuffer::default();
+        let formatter = OutputFormatter::with_writer(Theme::plain(), false, buffer.clone());
+        let input = "münchen mod:regex size:1XB";
+        let diagnostics = rusty_files::QueryParser::validate(input).unwrap_err();
+
+        formatter.print_query_diagnostics(input, &diagnostics);
+        assert_eq!(
+            String::from_utf8(buffer.take()).unwrap(),
+            "  münchen mod:regex size:1XB\n\
+             \x20         ^^^\n\
+             [ERROR] Unknown key 'mod', did you mean 'mode'?\n\
+             \x20 münchen mod:regex size:1XB\n\
+             \x20                        ^^^\n\
+             [ERROR] Invalid size filter: 1XB\n"
+        );
+    }
+
+    /// A pipe whose reader has gone away, as with `filesearch search x | head -1`.
+    struct ClosedPipe(Arc<Mutex<usize>>);
+
+    impl Write for ClosedPipe {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            *self.0.lock().unwrap() += 1;
+            Err(io::Error::from(io::ErrorKind::BrokenPipe))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_broken_pipe_stops_output_quietly() {
+        let writes = Arc::new(Mutex::new(0));
+        let formatter =
+            OutputFormatter::with_writer(Theme::plain(), false, ClosedPipe(writes.clone()));
+
+        formatter.print_info("first");
+        formatter.print_table(&["Root"], &[vec!["/srv".to_string()]]);
+        formatter.print_line("last");
+        assert_eq!(*writes.lock().unwrap(), 1);
     }
 }
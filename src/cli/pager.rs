@@ -0,0 +1,105 @@
+use std::ffi::OsStr;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// Pages long output through `$PAGER`, or `less -RFX` when it is unset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pager {
+    command: Vec<String>,
+    threshold: usize,
+}
+
+impl Pager {
+    /// Never pages when stdout is not a terminal or paging is off; an empty `PAGER`
+    /// turns it off too.
+    pub fn detect(
+        is_tty: bool,
+        enabled: bool,
+        threshold: usize,
+        pager_env: Option<&OsStr>,
+    ) -> Option<Pager> {
+        if !is_tty || !enabled {
+            return None;
+        }
+        let command: Vec<String> = match pager_env {
+            Some(value) => value.to_string_lossy().split_whitespace().map(String::from).collect(),
+            None => vec!["less".to_string(), "-RFX".to_string()],
+        };
+        if command.is_empty() {
+            return None;
+        }
+        Some(Pager { command, threshold })
+    }
+
+    pub fn should_page(&self, output: &[u8]) -> bool {
+        output.iter().filter(|&&byte| byte == b'\n').count() > self.threshold
+    }
+
+    /// Writes `output` through the pager and waits for it to exit. Fails only when the
+    /// pager cannot start, leaving the output to be written directly; quitting the pager
+    /// before reading everything is not an error.
+    pub fn page(&self, output: &[u8]) -> io::Result<()> {
+        let mut child = Command::new(&self.command[0])
+            .args(&self.command[1..])
+            .stdin(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            if let Err(err) = stdin.write_all(output) {
+                if err.kind() != io::ErrorKind::BrokenPipe {
+                    log::debug!("pager stopped reading: {}", err);
+                }
+            }
+        }
+        if let Err(err) = child.wait() {
+            log::debug!("waiting for pager: {}", err);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pager_decision_matrix() {
+        let less = Some(vec!["less", "-RFX"]);
+        let most = Some(vec!["most", "-s"]);
+
+        for (is_tty, enabled, pager_env, expected) in [
+            (true, true, None, less.clone()),
+            (true, true, Some("most -s"), most.clone()),
+            (true, true, Some(""), None),
+            (true, true, Some("  "), None),
+            (true, false, None, None),
+            (true, false, Some("most -s"), None),
+            (false, true, None, None),
+            (false, true, Some("most -s"), None),
+            (false, false, None, None),
+        ] {
+            let pager = Pager::detect(is_tty, enabled, 40, pager_env.map(OsStr::new));
+            assert_eq!(
+                pager.map(|pager| pager.command),
+                expected.as_ref().map(|args| args.iter().map(|arg| arg.to_string()).collect()),
+                "tty={} enabled={} PAGER={:?}",
+                is_tty,
+                enabled,
+                pager_env
+            );
+        }
+    }
+
+    #[test]
+    fn test_pages_only_output_over_the_threshold() {
+        let pager = Pager::detect(true, true, 2, None).unwrap();
+        assert!(!pager.should_page(b""));
+        assert!(!pager.should_page(b"one\ntwo\n"));
+        assert!(pager.should_page(b"one\ntwo\nthree\n"));
+    }
+
+    #[test]
+    fn test_missing_pager_is_reported() {
+        let pager = Pager::detect(true, true, 0, Some(OsStr::new("no-such-pager-here"))).unwrap();
+        assert!(pager.page(b"result\n").is_err());
+    }
+}
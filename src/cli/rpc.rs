@@ -0,0 +1,816 @@
+use crate::interactive::{Completer, Completion};
+use rusty_files::{ErrorCode, Result, SearchEngine, SearchError};
+use rusty_files::server::api::{build_query, convert_entry, convert_result};
+use rusty_files::server::{
+    FieldSelection, FileResult, ListingEntry, SearchRequest, SearchResponse, Selected,
+};
+use rusty_files::WatchEvent;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+const JSONRPC_VERSION: &str = "2.0";
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+// Codes reserved by the Language Server Protocol, which editor clients already understand.
+const SERVER_NOT_INITIALIZED: i64 = -32002;
+const REQUEST_CANCELLED: i64 = -32800;
+
+/// Minimum time between two `indexChanged` notifications.
+const NOTIFY_INTERVAL: Duration = Duration::from_secs(1);
+/// How often the notifier checks for new changes and for the session ending.
+const NOTIFY_POLL: Duration = Duration::from_millis(100);
+const DEFAULT_RECENT_LIMIT: usize = 50;
+
+/// Methods answered on the thread pool; every other request is answered inline.
+const ENGINE_METHODS: &[&str] = &["search", "suggest", "recent"];
+
+type RpcResult = std::result::Result<Value, RpcError>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RequestId {
+    Number(i64),
+    String(String),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    pub jsonrpc: String,
+    /// Absent for notifications, which get no response.
+    #[serde(default)]
+    pub id: Option<RequestId>,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Response {
+    pub jsonrpc: String,
+    /// `null` when the request was too malformed to read its id.
+    pub id: Option<RequestId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    /// For engine errors, their `ErrorReport`: the stable `code`, `message` and `path`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Box<Value>>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl ToString) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+            data: None,
+        }
+    }
+
+    /// Errors in the request's query are invalid params; everything else is internal.
+    fn engine(err: SearchError) -> Self {
+        let code = match err.code() {
+            ErrorCode::InvalidQuery
+            | ErrorCode::InvalidRegex
+            | ErrorCode::InvalidGlob
+            | ErrorCode::InvalidContentQuery => INVALID_PARAMS,
+            _ => INTERNAL_ERROR,
+        };
+        Self {
+            data: serde_json::to_value(&err).ok().map(Box::new),
+            ..Self::new(code, &err)
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Notification<T> {
+    jsonrpc: &'static str,
+    method: &'static str,
+    params: T,
+}
+
+#[derive(Debug, Deserialize)]
+struct CancelParams {
+    id: RequestId,
+}
+
+#[derive(Debug, Deserialize)]
+struct SuggestParams {
+    query: String,
+    /// Byte offset of the cursor in `query`; the end of the query when absent.
+    #[serde(default)]
+    cursor: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RecentParams {
+    #[serde(default)]
+    limit: Option<usize>,
+    #[serde(default)]
+    fields: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize)]
+struct SuggestResult {
+    completions: Vec<Completion>,
+}
+
+#[derive(Debug, Serialize)]
+struct RecentResult {
+    entries: Selected<ListingEntry>,
+}
+
+#[derive(Debug, Serialize)]
+struct IndexChanged {
+    changes: usize,
+}
+
+impl Response {
+    fn success(id: Option<RequestId>, result: Value) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn failure(id: Option<RequestId>, code: i64, message: impl ToString) -> Self {
+        Self::error(id, RpcError::new(code, message))
+    }
+
+    fn error(id: Option<RequestId>, error: RpcError) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            result: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// Reads one `Content-Length` framed message body. Returns `None` at end of input.
+pub fn read_message<R: BufRead>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut content_length = None;
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return match content_length {
+                None => Ok(None),
+                Some(_) => Err(io::ErrorKind::UnexpectedEof.into()),
+            };
+        }
+
+        let header = line.trim_end_matches(['\r', '\n']);
+        if header.is_empty() {
+            break;
+        }
+        // Other headers, such as Content-Type, carry nothing we need.
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                let length = value.trim().parse::<usize>().map_err(|e| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("Content-Length: {}", e))
+                })?;
+                content_length = Some(length);
+            }
+        }
+    }
+
+    let length = content_length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "message without a Content-Length header")
+    })?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+/// Writes `body` with its `Content-Length` header in a single write.
+pub fn write_message<W: Write>(writer: &mut W, body: &[u8]) -> io::Result<()> {
+    let mut message = format!("Content-Length: {}\r\n\r\n", body.len()).into_bytes();
+    message.extend_from_slice(body);
+    writer.write_all(&message)?;
+    writer.flush()
+}
+
+/// JSON-RPC 2.0 over a pair of streams, framed like the Language Server Protocol, for editor
+/// plugins that keep `filesearch rpc` running as a child process.
+///
+/// Requests are read in order on the calling thread. `search`, `suggest` and `recent` run on a
+/// thread pool sized by `thread_count`, so a slow search does not hold up the others, and can
+/// be cancelled with `$/cancelRequest` until they finish. Only protocol messages are written
+/// to the output; logs go to stderr.
+pub struct RpcServer {
+    engine: RwLock<SearchEngine>,
+    events: Mutex<Option<Receiver<WatchEvent>>>,
+    initialized: AtomicBool,
+    shutting_down: AtomicBool,
+    pending: Mutex<HashMap<RequestId, Arc<AtomicBool>>>,
+}
+
+impl RpcServer {
+    pub fn new(engine: SearchEngine) -> Self {
+        Self {
+            engine: RwLock::new(engine),
+            events: Mutex::new(None),
+            initialized: AtomicBool::new(false),
+            shutting_down: AtomicBool::new(false),
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Turns changes reported by `events` into `indexChanged` notifications, at most one per
+    /// [`NOTIFY_INTERVAL`].
+    pub fn with_events(self, events: Receiver<WatchEvent>) -> Self {
+        *self.events.lock().unwrap() = Some(events);
+        self
+    }
+
+    /// Serves requests until `exit` or the end of `input`. Returns whether the session ended
+    /// cleanly, with `shutdown` before `exit`.
+    pub fn serve<R, W>(&self, mut input: R, output: W) -> Result<bool>
+    where
+        R: BufRead,
+        W: Write + Send,
+    {
+        let threads = self.engine.read().unwrap().get_config().thread_count.max(1);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .map_err(|e| SearchError::Configuration(e.to_string()))?;
+        let output = Mutex::new(output);
+        let done = AtomicBool::new(false);
+        let events = self.events.lock().unwrap().take();
+
+        std::thread::scope(|threads| {
+            if let Some(events) = events {
+                threads.spawn(|| self.notify_changes(events, &output, &done));
+            }
+
+            let result = pool.in_place_scope(|scope| loop {
+                let Some(body) = read_message(&mut input)? else {
+                    return Ok(false);
+                };
+
+                let request = match parse_request(&body) {
+                    Ok(request) => request,
+                    Err(response) => {
+                        self.send(&output, &response);
+                        continue;
+                    }
+                };
+
+                match request.method.as_str() {
+                    "exit" => return Ok(self.shutting_down.load(Ordering::Relaxed)),
+                    "$/cancelRequest" => self.cancel(request.params),
+                    method if ENGINE_METHODS.contains(&method) => {
+                        match (request.id.clone(), self.refusal(&request)) {
+                            (None, _) => {}
+                            (Some(_), Some(refusal)) => self.send(&output, &refusal),
+                            (Some(id), None) => {
+                                let cancelled = Arc::new(AtomicBool::new(false));
+                                self.pending
+                                    .lock()
+                                    .unwrap()
+                                    .insert(id.clone(), Arc::clone(&cancelled));
+
+                                let output = &output;
+                                scope.spawn(move |_| {
+                                    let response = self.answer(&request, &cancelled);
+                                    self.pending.lock().unwrap().remove(&id);
+                                    self.send(output, &response);
+                                });
+                            }
+                        }
+                    }
+                    method => {
+                        let initializing = method == "initialize";
+                        if let Some(response) = self.respond_inline(request) {
+                            let initialized = initializing && response.error.is_none();
+                            self.send(&output, &response);
+                            // Notifications may only follow the initialize response.
+                            if initialized {
+                                self.initialized.store(true, Ordering::Relaxed);
+                            }
+                        }
+                    }
+                }
+            });
+
+            done.store(true, Ordering::Relaxed);
+            result
+        })
+    }
+
+    fn refusal(&self, request: &Request) -> Option<Response> {
+        if self.shutting_down.load(Ordering::Relaxed) {
+            return Some(Response::failure(
+                request.id.clone(),
+                INVALID_REQUEST,
+                "Server is shutting down",
+            ));
+        }
+        if !self.initialized.load(Ordering::Relaxed) && request.method != "initialize" {
+            return Some(Response::failure(
+                request.id.clone(),
+                SERVER_NOT_INITIALIZED,
+                "Server has not been initialized",
+            ));
+        }
+        None
+    }
+
+    /// Handles everything but the engine methods on the reading thread. Returns the response
+    /// to send, if any; notifications never get one.
+    fn respond_inline(&self, request: Request) -> Option<Response> {
+        request.id.as_ref()?;
+        if let Some(refusal) = self.refusal(&request) {
+            return Some(refusal);
+        }
+
+        let id = request.id.clone();
+        Some(match request.method.as_str() {
+            "initialize" => match self.initialize() {
+                Ok(result) => Response::success(id, result),
+                Err(e) => Response::error(id, RpcError::engine(e)),
+            },
+            "shutdown" => {
+                self.shutting_down.store(true, Ordering::Relaxed);
+                Response::success(id, Value::Null)
+            }
+            method => {
+                Response::failure(id, METHOD_NOT_FOUND, format!("Unknown method: {}", method))
+            }
+        })
+    }
+
+    fn initialize(&self) -> Result<Value> {
+        let engine = self.engine.read().unwrap();
+        let result = serde_json::json!({
+            "serverInfo": {
+                "name": "filesearch",
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+            "methods": ENGINE_METHODS,
+            "capabilities": engine.capabilities(),
+            "stats": engine.get_stats()?,
+        });
+        Ok(result)
+    }
+
+    fn cancel(&self, params: Value) {
+        if let Ok(CancelParams { id }) = serde_json::from_value(params) {
+            if let Some(cancelled) = self.pending.lock().unwrap().get(&id) {
+                cancelled.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn answer(&self, request: &Request, cancelled: &AtomicBool) -> Response {
+        let id = request.id.clone();
+        let cancelled_response =
+            || Response::failure(id.clone(), REQUEST_CANCELLED, "Request cancelled");
+
+        let engine = self.engine.read().unwrap();
+        if cancelled.load(Ordering::Relaxed) {
+            return cancelled_response();
+        }
+
+        let params = request.params.clone();
+        let result = match request.method.as_str() {
+            "search" => search(&engine, params),
+            "suggest" => suggest(&engine, params),
+            _ => recent(&engine, params),
+        };
+
+        // The engine cannot stop a query midway, so a late cancel discards its result.
+        if cancelled.load(Ordering::Relaxed) {
+            return cancelled_response();
+        }
+        match result {
+            Ok(result) => Response::success(id, result),
+            Err(error) => Response::error(id, error),
+        }
+    }
+
+    /// Forwards watch changes as `indexChanged` until `done`, holding them back until the
+    /// client is initialized and while the last notification is under a second old.
+    fn notify_changes<W: Write>(
+        &self,
+        events: Receiver<WatchEvent>,
+        output: &Mutex<W>,
+        done: &AtomicBool,
+    ) {
+        let mut changes = 0;
+        let mut last_sent: Option<Instant> = None;
+
+        while !done.load(Ordering::Relaxed) {
+            match events.recv_timeout(NOTIFY_POLL) {
+                Ok(event) => {
+                    changes += changes_in(&event);
+                    changes += events.try_iter().map(|event| changes_in(&event)).sum::<usize>();
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            let due = last_sent.map_or(true, |sent| sent.elapsed() >= NOTIFY_INTERVAL);
+            if changes > 0 && due && self.initialized.load(Ordering::Relaxed) {
+                let notification = Notification {
+                    jsonrpc: JSONRPC_VERSION,
+                    method: "indexChanged",
+                    params: IndexChanged { changes },
+                };
+                self.send(output, &notification);
+                changes = 0;
+                last_sent = Some(Instant::now());
+            }
+        }
+    }
+
+    fn send<W: Write, T: Serialize>(&self, output: &Mutex<W>, message: &T) {
+        let written = serde_json::to_vec(message)
+            .map_err(io::Error::from)
+            .and_then(|body| write_message(&mut *output.lock().unwrap(), &body));
+        if let Err(e) = written {
+            log::error!("Failed to write RPC message: {}", e);
+        }
+    }
+}
+
+fn search(engine: &SearchEngine, params: Value) -> RpcResult {
+    let started = Instant::now();
+    let req: SearchRequest = params_of(params)?;
+    let query = build_query(&req, engine.get_config().max_search_results)
+        .map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+    let fields = FieldSelection::parse::<FileResult, _>(req.fields.as_deref())
+        .map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+    let limit = engine.result_limit(&query).map_err(RpcError::engine)?;
+    let outcome = engine.search_paged(&query, None).map_err(RpcError::engine)?;
+    let content_partial = engine.content_coverage_partial(&query).map_err(RpcError::engine)?;
+
+    let total = outcome.total_matched;
+    let response = SearchResponse {
+        has_more: req.offset.saturating_add(outcome.results.len()) < total
+            || outcome.total_estimated && outcome.results.len() == limit.effective_limit,
+        results: Selected::new(
+            outcome.results.into_iter().map(convert_result).collect(),
+            fields,
+        ),
+        total,
+        total_estimated: outcome.total_estimated,
+        took_ms: started.elapsed().as_millis() as u64,
+        effective_limit: limit.effective_limit,
+        limit_clamped: req.limit > limit.effective_limit,
+        effective_query: query.effective(),
+        content_partial,
+        peers: None,
+    };
+    to_value(&response)
+}
+
+fn suggest(engine: &SearchEngine, params: Value) -> RpcResult {
+    let params: SuggestParams = params_of(params)?;
+    let cursor = params.cursor.unwrap_or(params.query.len());
+    // A fresh completer per request, so extensions indexed meanwhile are offered.
+    let completions = Completer::default().complete(engine, &params.query, cursor);
+    to_value(&SuggestResult { completions })
+}
+
+fn recent(engine: &SearchEngine, params: Value) -> RpcResult {
+    let params: RecentParams = match params {
+        Value::Null => RecentParams::default(),
+        params => params_of(params)?,
+    };
+    let fields = FieldSelection::parse::<ListingEntry, _>(params.fields.as_deref())
+        .map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+    let entries = engine
+        .recent_files(params.limit.unwrap_or(DEFAULT_RECENT_LIMIT))
+        .map_err(RpcError::engine)?;
+    to_value(&RecentResult {
+        entries: Selected::new(entries.into_iter().map(convert_entry).collect(), fields),
+    })
+}
+
+fn parse_request(body: &[u8]) -> std::result::Result<Request, Response> {
+    let value: Value = serde_json::from_slice(body)
+        .map_err(|e| Response::failure(None, PARSE_ERROR, format!("Parse error: {}", e)))?;
+    // Answer invalid requests under their own id when it can be read.
+    let id = value
+        .get("id")
+        .and_then(|id| serde_json::from_value::<RequestId>(id.clone()).ok());
+
+    let request: Request = serde_json::from_value(value)
+        .map_err(|e| Response::failure(id.clone(), INVALID_REQUEST, e.to_string()))?;
+    if request.jsonrpc != JSONRPC_VERSION {
+        return Err(Response::failure(
+            id,
+            INVALID_REQUEST,
+            format!("Unsupported jsonrpc version: {}", request.jsonrpc),
+        ));
+    }
+    Ok(request)
+}
+
+fn params_of<T: for<'de> Deserialize<'de>>(params: Value) -> std::result::Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e))
+}
+
+fn to_value<T: Serialize>(value: &T) -> RpcResult {
+    serde_json::to_value(value).map_err(|e| RpcError::new(INTERNAL_ERROR, e))
+}
+
+fn changes_in(event: &WatchEvent) -> usize {
+    match event {
+        WatchEvent::IndexChanged { changes, .. } => *changes,
+        WatchEvent::InitialScanComplete { indexed, .. } => *indexed,
+        WatchEvent::Recovered { caught_up, .. } => *caught_up,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::io::{BufReader, Read};
+    use std::sync::mpsc::{self, Sender};
+    use std::thread::JoinHandle;
+    use tempfile::TempDir;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// One end of an in-memory pipe; reads block until the other end writes or goes away.
+    struct PipeReader {
+        chunks: Receiver<Vec<u8>>,
+        chunk: Vec<u8>,
+        pos: usize,
+    }
+
+    impl Read for PipeReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.pos == self.chunk.len() {
+                match self.chunks.recv_timeout(TIMEOUT) {
+                    Ok(chunk) => (self.chunk, self.pos) = (chunk, 0),
+                    Err(RecvTimeoutError::Disconnected) => return Ok(0),
+                    Err(RecvTimeoutError::Timeout) => return Err(io::ErrorKind::TimedOut.into()),
+                }
+            }
+            let read = buf.len().min(self.chunk.len() - self.pos);
+            buf[..read].copy_from_slice(&self.chunk[self.pos..self.pos + read]);
+            self.pos += read;
+            Ok(read)
+        }
+    }
+
+    struct PipeWriter(Sender<Vec<u8>>);
+
+    impl Write for PipeWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0
+                .send(buf.to_vec())
+                .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn pipe() -> (PipeWriter, BufReader<PipeReader>) {
+        let (sender, chunks) = mpsc::channel();
+        let reader = PipeReader {
+            chunks,
+            chunk: Vec::new(),
+            pos: 0,
+        };
+        (PipeWriter(sender), BufReader::new(reader))
+    }
+
+    struct Client {
+        input: Option<PipeWriter>,
+        output: BufReader<PipeReader>,
+        server: Option<JoinHandle<Result<bool>>>,
+    }
+
+    impl Client {
+        fn start(server: Arc<RpcServer>) -> Self {
+            let (input, server_input) = pipe();
+            let (server_output, output) = pipe();
+            let handle = std::thread::spawn(move || server.serve(server_input, server_output));
+            Self {
+                input: Some(input),
+                output,
+                server: Some(handle),
+            }
+        }
+
+        fn send_raw(&mut self, body: &[u8]) {
+            write_message(self.input.as_mut().unwrap(), body).unwrap();
+        }
+
+        fn send(&mut self, message: Value) {
+            self.send_raw(message.to_string().as_bytes());
+        }
+
+        fn request(&mut self, id: i64, method: &str, params: Value) {
+            self.send(json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}));
+        }
+
+        fn receive(&mut self) -> Value {
+            let body = read_message(&mut self.output).unwrap().expect("server closed output");
+            serde_json::from_slice(&body).unwrap()
+        }
+
+        fn call(&mut self, id: i64, method: &str, params: Value) -> Value {
+            self.request(id, method, params);
+            let response = self.receive();
+            assert_eq!(response["id"], id, "{}", response);
+            response
+        }
+
+        /// Closes the input and returns whether the server reported a clean exit.
+        fn finish(mut self) -> bool {
+            self.input.take();
+            self.server.take().unwrap().join().unwrap().unwrap()
+        }
+    }
+
+    fn indexed_engine(temp_dir: &TempDir) -> SearchEngine {
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir(&root).unwrap();
+        for name in ["main.rs", "lib.rs", "notes.md"] {
+            std::fs::write(root.join(name), "fn main() {}").unwrap();
+        }
+        let engine = SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+        engine.index_directory(&root, None).unwrap();
+        engine
+    }
+
+    #[test]
+    fn test_handshake_search_and_shutdown() {
+        let temp_dir = TempDir::new().unwrap();
+        let server = Arc::new(RpcServer::new(indexed_engine(&temp_dir)));
+        let mut client = Client::start(server);
+
+        let early = client.call(1, "search", json!({"query": "main"}));
+        assert_eq!(early["error"]["code"], SERVER_NOT_INITIALIZED);
+
+        let init = client.call(2, "initialize", json!({}));
+        assert_eq!(init["result"]["serverInfo"]["name"], "filesearch");
+        assert_eq!(init["result"]["stats"]["total_files"], 3);
+        assert!(init["result"]["capabilities"]["watch_backend"].is_string());
+        client.send(json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}));
+
+        let filters = json!({"extensions": ["rs"]});
+        let search = client.call(3, "search", json!({"query": "main", "filters": filters}));
+        let results = search["result"]["results"].as_array().unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0]["name"], "main.rs");
+        assert_eq!(search["result"]["effective_query"]["extensions"], json!(["rs"]));
+
+        let suggest = client.call(4, "suggest", json!({"query": "main ext:m"}));
+        assert_eq!(suggest["result"]["completions"], json!([{"start": 9, "text": "md"}]));
+
+        let recent = client.call(5, "recent", json!({"limit": 2}));
+        assert_eq!(recent["result"]["entries"].as_array().unwrap().len(), 2);
+
+        assert_eq!(client.call(6, "shutdown", Value::Null)["result"], Value::Null);
+        let refused = client.call(7, "search", json!({"query": "main"}));
+        assert_eq!(refused["error"]["code"], INVALID_REQUEST);
+        client.send(json!({"jsonrpc": "2.0", "method": "exit"}));
+        assert!(client.finish());
+    }
+
+    #[test]
+    fn test_cancelled_request_gets_cancel_error() {
+        let temp_dir = TempDir::new().unwrap();
+        let server = Arc::new(RpcServer::new(indexed_engine(&temp_dir)));
+        let mut client = Client::start(Arc::clone(&server));
+        client.call(1, "initialize", json!({}));
+
+        // Holding the engine keeps the search queued until the cancel has been read; the
+        // answer to the unknown method proves it was, since requests are read in order.
+        let engine = server.engine.write().unwrap();
+        client.request(2, "search", json!({"query": "main"}));
+        client.send(json!({"jsonrpc": "2.0", "method": "$/cancelRequest", "params": {"id": 2}}));
+        let unknown = client.call(3, "reindex", Value::Null);
+        assert_eq!(unknown["error"]["code"], METHOD_NOT_FOUND);
+        drop(engine);
+
+        let cancelled = client.receive();
+        assert_eq!(cancelled["id"], 2);
+        assert_eq!(cancelled["error"]["code"], REQUEST_CANCELLED);
+        assert!(cancelled.get("result").is_none());
+
+        // Ending without shutdown is not a clean exit.
+        assert!(!client.finish());
+    }
+
+    #[test]
+    fn test_malformed_requests_get_error_responses() {
+        let temp_dir = TempDir::new().unwrap();
+        let server = Arc::new(RpcServer::new(indexed_engine(&temp_dir)));
+        let mut client = Client::start(server);
+        client.call(1, "initialize", json!({}));
+
+        client.send_raw(b"{\"jsonrpc\": \"2.0\", \"id\": 2, \"method\"");
+        let unparsable = client.receive();
+        assert_eq!(unparsable["id"], Value::Null);
+        assert_eq!(unparsable["error"]["code"], PARSE_ERROR);
+
+        client.send(json!({"jsonrpc": "2.0", "id": 3}));
+        let missing_method = client.receive();
+        assert_eq!(missing_method["id"], 3);
+        assert_eq!(missing_method["error"]["code"], INVALID_REQUEST);
+
+        client.send(json!({"jsonrpc": "1.0", "id": "four", "method": "search"}));
+        assert_eq!(client.receive()["error"]["code"], INVALID_REQUEST);
+
+        let bad_params = client.call(5, "search", json!({"limit": 5}));
+        assert_eq!(bad_params["error"]["code"], INVALID_PARAMS);
+        let zero_limit = client.call(6, "search", json!({"query": "main", "limit": 0}));
+        assert_eq!(zero_limit["error"]["code"], INVALID_PARAMS);
+
+        // The session survives all of them.
+        let search = client.call(7, "search", json!({"query": "notes"}));
+        assert_eq!(search["result"]["total"], 1);
+    }
+
+    #[test]
+    fn test_index_changes_are_batched_into_notifications() {
+        let temp_dir = TempDir::new().unwrap();
+        let (events, receiver) = mpsc::channel();
+        let root = temp_dir.path().join("project");
+        for changes in [2, 3] {
+            let event = WatchEvent::IndexChanged {
+                root: root.clone(),
+                changes,
+            };
+            events.send(event).unwrap();
+        }
+
+        let server = RpcServer::new(indexed_engine(&temp_dir)).with_events(receiver);
+        let mut client = Client::start(Arc::new(server));
+        // Changes seen before initialize are held back rather than sent ahead of its response.
+        client.call(1, "initialize", json!({}));
+        let first = client.receive();
+        assert_eq!(first["method"], "indexChanged");
+        assert_eq!(first["params"], json!({"changes": 5}));
+        assert!(first.get("id").is_none());
+        let first_at = Instant::now();
+
+        for changes in [1, 4] {
+            let event = WatchEvent::InitialScanComplete {
+                root: root.clone(),
+                indexed: changes,
+            };
+            events.send(event).unwrap();
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        let second = client.receive();
+        assert_eq!(second["params"]["changes"], 5);
+        assert!(first_at.elapsed() >= NOTIFY_INTERVAL - Duration::from_millis(100));
+
+        client.send(json!({"jsonrpc": "2.0", "method": "exit"}));
+        assert!(!client.finish());
+    }
+
+    #[test]
+    fn test_framing_round_trip() {
+        let mut framed = Vec::new();
+        write_message(&mut framed, b"{}").unwrap();
+        write_message(&mut framed, b"[1]").unwrap();
+        assert!(framed.starts_with(b"Content-Length: 2\r\n\r\n{}"));
+
+        let mut with_type = b"Content-Type: application/vscode-jsonrpc\r\n".to_vec();
+        with_type.extend_from_slice(&framed);
+        let mut reader = io::Cursor::new(with_type);
+        assert_eq!(read_message(&mut reader).unwrap().unwrap(), b"{}");
+        assert_eq!(read_message(&mut reader).unwrap().unwrap(), b"[1]");
+        assert!(read_message(&mut reader).unwrap().is_none());
+
+        let mut headerless = io::Cursor::new(b"\r\n{}".to_vec());
+        let err = read_message(&mut headerless).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,196 @@
+use clap::ValueEnum;
+use colored::{Color, Colorize};
+use rusty_files::CliTheme;
+use std::ffi::OsStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+/// `always`/`never` win over the environment; `auto` colors only a terminal without `NO_COLOR`.
+pub fn colors_enabled(choice: ColorChoice, no_color: Option<&OsStr>, is_tty: bool) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        // NO_COLOR only counts when set to a non-empty value.
+        ColorChoice::Auto => is_tty && no_color.map_or(true, OsStr::is_empty),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Style {
+    color: Option<Color>,
+    bold: bool,
+}
+
+impl Style {
+    pub const PLAIN: Style = Style {
+        color: None,
+        bold: false,
+    };
+
+    const fn color(color: Color) -> Self {
+        Self {
+            color: Some(color),
+            bold: false,
+        }
+    }
+
+    const fn bold(self) -> Self {
+        Self { bold: true, ..self }
+    }
+
+    pub fn paint(&self, text: &str) -> String {
+        if *self == Self::PLAIN {
+            return text.to_string();
+        }
+
+        let mut styled = text.normal();
+        if let Some(color) = self.color {
+            styled = styled.color(color);
+        }
+        if self.bold {
+            styled = styled.bold();
+        }
+        styled.to_string()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Theme {
+    pub header: Style,
+    pub info: Style,
+    pub summary: Style,
+    pub label: Style,
+    pub value: Style,
+    pub path: Style,
+    pub filename: Style,
+    pub detail: Style,
+    pub success: Style,
+    pub warning: Style,
+    pub error: Style,
+    pub snippet: Style,
+    /// What a name or path match is painted with, inside the file name or path.
+    pub highlight: Style,
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            header: Style::color(Color::BrightGreen).bold(),
+            info: Style::color(Color::BrightBlue),
+            summary: Style::color(Color::BrightWhite).bold(),
+            label: Style::color(Color::Cyan),
+            value: Style::color(Color::White),
+            path: Style::color(Color::BrightBlack),
+            filename: Style::color(Color::BrightWhite).bold(),
+            detail: Style::color(Color::BrightBlack),
+            success: Style::color(Color::Green),
+            warning: Style::color(Color::Yellow),
+            error: Style::color(Color::Red),
+            snippet: Style::color(Color::BrightYellow),
+            highlight: Style::color(Color::BrightYellow).bold(),
+        }
+    }
+
+    // Bright and white variants wash out on light backgrounds, so this sticks to the
+    // terminal's default foreground plus dark base colors.
+    pub fn light() -> Self {
+        Self {
+            header: Style::color(Color::Blue).bold(),
+            info: Style::color(Color::Blue),
+            summary: Style::PLAIN.bold(),
+            label: Style::color(Color::Blue),
+            value: Style::PLAIN,
+            path: Style::color(Color::Blue),
+            filename: Style::PLAIN.bold(),
+            detail: Style::PLAIN,
+            success: Style::color(Color::Green),
+            warning: Style::color(Color::Magenta),
+            error: Style::color(Color::Red).bold(),
+            snippet: Style::color(Color::Magenta),
+            highlight: Style::color(Color::Magenta).bold(),
+        }
+    }
+
+    pub fn plain() -> Self {
+        Self {
+            header: Style::PLAIN,
+            info: Style::PLAIN,
+            summary: Style::PLAIN,
+            label: Style::PLAIN,
+            value: Style::PLAIN,
+            path: Style::PLAIN,
+            filename: Style::PLAIN,
+            detail: Style::PLAIN,
+            success: Style::PLAIN,
+            warning: Style::PLAIN,
+            error: Style::PLAIN,
+            snippet: Style::PLAIN,
+            highlight: Style::PLAIN,
+        }
+    }
+
+    pub fn resolve(name: CliTheme, use_colors: bool) -> Self {
+        match name {
+            _ if !use_colors => Self::plain(),
+            CliTheme::Dark => Self::dark(),
+            CliTheme::Light => Self::light(),
+            CliTheme::Plain => Self::plain(),
+        }
+    }
+
+    pub fn is_plain(&self) -> bool {
+        *self == Self::plain()
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_detection_matrix() {
+        let set = Some(OsStr::new("1"));
+        let empty = Some(OsStr::new(""));
+
+        for (choice, no_color, is_tty, expected) in [
+            (ColorChoice::Auto, None, true, true),
+            (ColorChoice::Auto, None, false, false),
+            (ColorChoice::Auto, set, true, false),
+            (ColorChoice::Auto, set, false, false),
+            (ColorChoice::Auto, empty, true, true),
+            (ColorChoice::Always, None, false, true),
+            (ColorChoice::Always, set, false, true),
+            (ColorChoice::Always, set, true, true),
+            (ColorChoice::Never, None, true, false),
+            (ColorChoice::Never, set, true, false),
+        ] {
+            assert_eq!(
+                colors_enabled(choice, no_color, is_tty),
+                expected,
+                "{:?} NO_COLOR={:?} tty={}",
+                choice,
+                no_color,
+                is_tty
+            );
+        }
+    }
+
+    #[test]
+    fn test_disabled_colors_force_plain_theme() {
+        assert!(Theme::resolve(CliTheme::Dark, false).is_plain());
+        assert!(Theme::resolve(CliTheme::Plain, true).is_plain());
+        assert_eq!(Theme::resolve(CliTheme::Light, true), Theme::light());
+        assert_eq!(Theme::plain().warning.paint("careful"), "careful");
+    }
+}
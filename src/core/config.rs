@@ -1,7 +1,25 @@
+use crate::core::error::{IoResultExt, Result, SearchError};
+use crate::core::maintenance::MaintenanceWindow;
+use crate::core::types::MaintenanceTask;
+use crate::filters::{DateStyle, SizeUnits, DEFAULT_SENSITIVE_PATTERNS};
+use crate::storage::chunks::{DEFAULT_FTS_CHUNK_BYTES, MIN_FTS_CHUNK_BYTES};
+use crate::storage::schema::DEFAULT_FTS_TOKENIZER;
+use globset::Glob;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Largest `max_file_size_for_content`; content is read into memory whole.
+pub const MAX_CONTENT_FILE_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// Most memory the bloom filter may take for `bloom_filter_capacity` entries at
+/// `bloom_filter_error_rate`.
+pub const MAX_BLOOM_FILTER_BYTES: u64 = 1024 * 1024 * 1024;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
 pub struct SearchConfig {
     pub index_path: PathBuf,
     pub thread_count: usize,
@@ -10,16 +28,164 @@ pub struct SearchConfig {
     pub enable_fuzzy_search: bool,
     pub fuzzy_threshold: f64,
     pub cache_size: usize,
+    /// Compiled query plans kept for reuse by queries that differ only in their pattern or
+    /// limit; 0 plans every query from scratch.
+    pub query_plan_cache_size: usize,
+    /// Paths recently looked up and not found, answered without asking the index again; 0
+    /// disables the cache.
+    pub miss_cache_size: usize,
+    /// How long a cached miss stands. Writes through this engine drop misses at once; this
+    /// bounds how long a write by another process can go unnoticed.
+    pub miss_cache_ttl_ms: u64,
+    /// Keeps every entry's name and path in memory and answers plain name and path searches
+    /// from there, saving it beside the index as `<index>.names` for the next start.
+    pub fast_name_index: bool,
     pub bloom_filter_capacity: usize,
     pub bloom_filter_error_rate: f64,
     pub max_search_results: usize,
+    pub candidate_multiplier: usize,
+    /// Searches taking at least this long are logged as slow queries, with their request id.
+    /// 0 logs every search.
+    pub slow_query_ms: u64,
+    /// Size of the first batch written by a build or the synchronizer. Later batches are
+    /// resized so each commit takes about `target_batch_ms`.
     pub batch_size: usize,
+    /// Bounds of the adapted batch size. Equal bounds turn adaptation off.
+    pub min_batch_size: usize,
+    pub max_batch_size: usize,
+    /// Commit time batches are sized toward: the size doubles while commits take under half
+    /// of it and halves when one takes longer.
+    pub target_batch_ms: u64,
     pub follow_symlinks: bool,
+    /// Content-addressed package stores, such as pnpm's or Nix's. Links resolving into one are
+    /// indexed by their own path as store aliases, without content, so each store file is
+    /// read once, where the store itself is indexed. Stores that don't exist are ignored.
+    pub symlink_store_roots: Vec<PathBuf>,
+    pub max_depth: Option<usize>,
     pub index_hidden_files: bool,
+    /// Index the directories below each root as entries of their own, for `type:dir`.
+    /// Without it only files and links are indexed.
+    pub index_directories: bool,
+    /// Build indexes that come out the same from the same tree: walked paths are sorted and
+    /// written in path order, so row ids follow the paths, and entries are stamped with a
+    /// frozen time instead of the clock. Walks are buffered in full, so meant for fixtures.
+    pub deterministic: bool,
     pub exclusion_patterns: Vec<String>,
+    /// Globs a file must match to be indexed; empty indexes every file that is not excluded.
+    /// Directories are traversed regardless, and exclusions still win.
+    pub inclusion_patterns: Vec<String>,
+    pub exclude_transient_files: bool,
+    /// Globs for files holding secrets, matched against the path and each of its components.
+    /// Their content is never read, their names are masked in logs and exports leave them
+    /// out unless asked to include them. Searches still find them.
+    pub sensitive_patterns: Vec<String>,
     pub watch_debounce_ms: u64,
     pub enable_access_tracking: bool,
-    pub db_pool_size: u32,
+    /// Connections reserved for searches and other reads; they are opened read-only.
+    pub read_pool_size: u32,
+    /// Connections used for indexing and other writes. SQLite runs one write transaction at
+    /// a time, so more than two rarely helps.
+    pub write_pool_size: u32,
+    /// Deprecated in favour of `read_pool_size` and `write_pool_size`. When set, it overrides
+    /// both: one connection goes to the write pool and the rest to the read pool.
+    pub db_pool_size: Option<u32>,
+    pub normalize_unicode: bool,
+    /// FTS5 tokenizer for content search, e.g. `unicode61 remove_diacritics 2` or `trigram`.
+    /// Existing indexes keep their tokenizer until `filesearch rebuild-fts` is run.
+    pub fts_tokenizer: String,
+    /// Most bytes of a file's text in one full-text row. Longer text is split into chunks on
+    /// line boundaries, each matched and ranked on its own; a file ranks by its best chunk.
+    /// Existing content keeps its chunks until `filesearch rebuild-fts` is run.
+    pub fts_chunk_bytes: usize,
+    /// Most matching chunks per file that `context:N` looks for matching lines in.
+    pub fts_max_chunk_hits: usize,
+    /// Characters kept either side of the match in the snippet of each content search
+    /// result. 0 leaves results without snippets.
+    pub snippet_context_chars: usize,
+    /// Matches located per result: lines of content searches, occurrences in the name or
+    /// path of others. 0 leaves results without locations unless `context:N` asks for them.
+    pub max_match_locations: usize,
+    /// Create new indexes with prefix-compressed paths. Existing indexes keep their layout
+    /// until converted with `filesearch migrate --compact`.
+    pub compact_paths: bool,
+    pub deletion_retention_days: u64,
+    pub audit_retention_days: u64,
+    /// How long indexing waits for another process's write lease before failing.
+    pub write_lease_wait_secs: u64,
+    /// A write lease without a heartbeat for this long is treated as abandoned.
+    pub write_lease_stale_secs: u64,
+    /// Snapshot the index before destructive operations such as clearing it, so they can
+    /// be undone. Turning it off makes those operations faster but final.
+    pub enable_undo: bool,
+    /// Largest compressed snapshot taken automatically. Operations on a bigger index fail
+    /// until they are run explicitly without undo.
+    pub undo_max_snapshot_bytes: u64,
+    pub undo_retention_days: u64,
+    /// Older snapshots beyond this many are deleted.
+    pub undo_max_snapshots: usize,
+    /// Hard budget for the database file plus its write-ahead log. Indexing stops with
+    /// `SearchError::QuotaExceeded` rather than commit a batch that would not fit.
+    pub max_index_size_bytes: Option<u64>,
+    pub quota_eviction: QuotaEviction,
+    /// Read pixel dimensions from image headers while indexing, for `width:` and `height:`.
+    pub extract_image_metadata: bool,
+    /// Read the first bytes of extension-less files for a `#!` line, so scripts count as
+    /// source code for `ext:code` and can be found with `lang:`.
+    pub detect_shebangs: bool,
+    /// Read header fields of `.eml` files and the title and description of `.html` pages for
+    /// `subject:`, `from:`, `to:` and `title:`. Needs the `document-fields` feature.
+    pub extract_document_fields: bool,
+    /// Record the owner, group and permission bits of each entry and its directory while
+    /// indexing, so a server can hide what a user could not read. Unix only.
+    pub record_ownership: bool,
+    /// Thumbnails kept in `<index>.thumbs/`; the oldest are deleted past this total.
+    pub thumbnail_cache_max_bytes: u64,
+    /// Roots whose files carry local-time timestamps even though their filesystem type does
+    /// not say so, e.g. FAT volumes behind a network share.
+    pub assume_local_time_paths: Vec<PathBuf>,
+    /// Modification times within this many seconds of the indexed value count as unchanged.
+    /// Covers FAT's two-second granularity.
+    pub mtime_tolerance_secs: u64,
+    pub cli_theme: CliTheme,
+    /// Sizes in CLI output step by 1024 (`binary`) or 1000 (`decimal`).
+    pub cli_size_units: SizeUnits,
+    /// Dates in CLI search results: `relative`, `iso` or a strftime pattern. Stats and logs
+    /// show `relative` as `iso`. JSON output is not affected.
+    pub cli_date_format: DateStyle,
+    /// Decimal separator of sizes in CLI output, e.g. `,`.
+    pub cli_decimal_separator: char,
+    /// Pipe long CLI search output through `$PAGER` (or `less -RFX`) when stdout is a terminal.
+    pub cli_pager: bool,
+    /// Search output longer than this many lines is paged.
+    pub cli_pager_threshold: usize,
+    /// Files or directories marking a project root. CLI searches are limited to the nearest
+    /// directory above the working directory that holds one of them.
+    pub workspace_markers: Vec<String>,
+    pub on_batch_error: BatchErrorPolicy,
+    pub content_sampling: ContentSampling,
+    /// Checked in order before `content_sampling`; the first matching rule wins.
+    pub content_sampling_rules: Vec<ContentSamplingRule>,
+    /// Wall-clock budget for reading and analyzing one file's content. Files that take longer
+    /// are indexed without content and reported as timed out. 0 disables the budget.
+    pub content_analysis_timeout_ms: u64,
+    /// How progress callbacks passed to indexing, updates and verification are called.
+    pub progress_delivery: ProgressDelivery,
+    /// Least time between two progress callbacks, except under `ProgressDelivery::Inline`.
+    /// Updates in between are coalesced into the latest one.
+    pub progress_interval_ms: u64,
+    /// Run maintenance in the background of `filesearch watch` while the index is idle.
+    pub auto_maintenance: bool,
+    /// Time without searches or index operations before background maintenance starts.
+    pub idle_threshold_secs: u64,
+    /// How long background maintenance works before checking in; it also stops between
+    /// steps as soon as a search or index operation starts.
+    pub maintenance_slice_ms: u64,
+    /// Blackout during which background maintenance does not run, e.g. `Mon-Fri 09:00-18:00`.
+    pub maintenance_window: Option<MaintenanceWindow>,
+    /// Tasks that neither background maintenance nor `filesearch maintain` runs.
+    pub disabled_maintenance_tasks: Vec<MaintenanceTask>,
+    /// Maintenance deletes access-log entries older than this.
+    pub access_log_retention_days: u64,
 }
 
 impl Default for SearchConfig {
@@ -32,47 +198,348 @@ impl Default for SearchConfig {
             enable_fuzzy_search: true,
             fuzzy_threshold: 0.7,
             cache_size: 1000,
+            query_plan_cache_size: 64,
+            miss_cache_size: 4096,
+            miss_cache_ttl_ms: 5000,
+            fast_name_index: false,
             bloom_filter_capacity: 10_000_000,
             bloom_filter_error_rate: 0.0001,
             max_search_results: 1000,
+            candidate_multiplier: 2,
+            slow_query_ms: 1000,
             batch_size: 1000,
+            min_batch_size: 1,
+            max_batch_size: 50_000,
+            target_batch_ms: 250,
             follow_symlinks: false,
+            symlink_store_roots: default_store_roots(),
+            max_depth: None,
             index_hidden_files: false,
+            index_directories: false,
+            deterministic: false,
             exclusion_patterns: vec![
                 ".git".to_string(),
                 "node_modules".to_string(),
                 "target".to_string(),
                 ".DS_Store".to_string(),
             ],
+            inclusion_patterns: Vec::new(),
+            exclude_transient_files: true,
+            sensitive_patterns: DEFAULT_SENSITIVE_PATTERNS.iter().map(|p| p.to_string()).collect(),
             watch_debounce_ms: 500,
             enable_access_tracking: true,
-            db_pool_size: 10,
+            read_pool_size: 8,
+            write_pool_size: 2,
+            db_pool_size: None,
+            normalize_unicode: true,
+            fts_tokenizer: DEFAULT_FTS_TOKENIZER.to_string(),
+            fts_chunk_bytes: DEFAULT_FTS_CHUNK_BYTES,
+            fts_max_chunk_hits: 3,
+            snippet_context_chars: 60,
+            max_match_locations: 5,
+            compact_paths: false,
+            deletion_retention_days: 30,
+            audit_retention_days: 365,
+            write_lease_wait_secs: 300,
+            write_lease_stale_secs: 30,
+            enable_undo: true,
+            undo_max_snapshot_bytes: 256 * 1024 * 1024,
+            undo_retention_days: 7,
+            undo_max_snapshots: 5,
+            max_index_size_bytes: None,
+            quota_eviction: QuotaEviction::default(),
+            extract_image_metadata: false,
+            detect_shebangs: true,
+            extract_document_fields: true,
+            record_ownership: false,
+            thumbnail_cache_max_bytes: 64 * 1024 * 1024,
+            assume_local_time_paths: Vec::new(),
+            mtime_tolerance_secs: 2,
+            cli_theme: CliTheme::default(),
+            cli_size_units: SizeUnits::default(),
+            cli_date_format: DateStyle::default(),
+            cli_decimal_separator: '.',
+            cli_pager: true,
+            cli_pager_threshold: 40,
+            workspace_markers: vec![
+                ".git".to_string(),
+                "Cargo.toml".to_string(),
+                "package.json".to_string(),
+                ".hg".to_string(),
+            ],
+            on_batch_error: BatchErrorPolicy::default(),
+            content_sampling: ContentSampling::default(),
+            content_sampling_rules: vec![ContentSamplingRule {
+                extensions: vec!["log".to_string()],
+                path_prefix: None,
+                sampling: ContentSampling::Tail {
+                    tail_bytes: 64 * 1024,
+                },
+            }],
+            content_analysis_timeout_ms: 5000,
+            progress_delivery: ProgressDelivery::default(),
+            progress_interval_ms: 100,
+            auto_maintenance: true,
+            idle_threshold_secs: 60,
+            maintenance_slice_ms: 200,
+            maintenance_window: None,
+            disabled_maintenance_tasks: Vec::new(),
+            access_log_retention_days: 90,
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CliTheme {
+    #[default]
+    Dark,
+    Light,
+    Plain,
+}
+
+/// What indexing does when writing a batch of entries to the database fails.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchErrorPolicy {
+    /// Stop indexing and return the error; earlier batches stay committed.
+    #[default]
+    Abort,
+    /// Record the batch's paths as skipped and carry on with the next batch.
+    Skip,
+    /// Retry the batch with exponential backoff, then insert its entries one by one
+    /// on the final attempt so a single bad entry only skips itself.
+    Retry { attempts: u32, backoff_ms: u64 },
+}
+
+/// How indexing makes room once `max_index_size_bytes` is reached.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaEviction {
+    /// Refuse batches that do not fit; nothing already indexed is touched.
+    #[default]
+    RejectNew,
+    /// Drop the content search data of the least recently accessed files, keeping their
+    /// metadata, until the batch fits.
+    EvictLeastRecentlyAccessed,
+}
+
+/// How a progress callback is called during indexing. Each is guarded against panics: a
+/// callback that panics is logged once and not called again for the rest of the run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProgressDelivery {
+    /// Every update, on the indexing thread.
+    Inline,
+    /// At most one update per `progress_interval_ms`, on the indexing thread.
+    #[default]
+    Throttled,
+    /// At most one update per `progress_interval_ms`, on a thread of its own, so indexing
+    /// never waits for the callback. Updates the callback has not caught up with are dropped.
+    Async,
+}
+
+/// Which part of a file's text is analyzed for content search.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentSampling {
+    /// The start of the file, up to `max_file_size_for_content`.
+    #[default]
+    Head,
+    /// The last `tail_bytes`, starting at the first full line. Suits logs, where recent
+    /// entries are at the end; files of any size are sampled.
+    Tail { tail_bytes: u64 },
+    /// Both ends of the file, joined by a marker line.
+    HeadAndTail { head_bytes: u64, tail_bytes: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentSamplingRule {
+    /// Extensions without the dot; empty matches any extension.
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    #[serde(default)]
+    pub path_prefix: Option<PathBuf>,
+    pub sampling: ContentSampling,
+}
+
+impl ContentSamplingRule {
+    pub fn matches(&self, path: &Path) -> bool {
+        let extension_matches = self.extensions.is_empty()
+            || path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| self.extensions.iter().any(|e| e.eq_ignore_ascii_case(ext)));
+        let prefix_matches = self
+            .path_prefix
+            .as_ref()
+            .map_or(true, |prefix| path.starts_with(prefix));
+
+        extension_matches && prefix_matches
+    }
+}
+
+/// A setting of a [`SearchConfig`] that is out of range or inconsistent with another.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{field} = {value} is invalid: expected {allowed}")]
+#[non_exhaustive]
+pub struct ConfigError {
+    pub field: String,
+    pub value: String,
+    pub allowed: String,
+}
+
+impl ConfigError {
+    fn new(field: impl Into<String>, value: impl ToString, allowed: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            value: value.to_string(),
+            allowed: allowed.into(),
+        }
+    }
+}
+
+impl From<Vec<ConfigError>> for SearchError {
+    fn from(errors: Vec<ConfigError>) -> Self {
+        let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        SearchError::Configuration(messages.join("; "))
+    }
+}
+
 impl SearchConfig {
-    pub fn from_file(path: &PathBuf) -> crate::core::error::Result<Self> {
-        let content = std::fs::read_to_string(path)?;
+    pub fn from_file(path: &PathBuf) -> Result<Self> {
+        let content = std::fs::read_to_string(path).at_path(path)?;
         let config: Self = if path.extension().and_then(|s| s.to_str()) == Some("json") {
             serde_json::from_str(&content)
-                .map_err(|e| crate::core::error::SearchError::Configuration(e.to_string()))?
+                .map_err(|e| SearchError::Configuration(e.to_string()))?
         } else {
             toml::from_str(&content)
-                .map_err(|e| crate::core::error::SearchError::Configuration(e.to_string()))?
+                .map_err(|e| SearchError::Configuration(e.to_string()))?
         };
+        config.validate()?;
         Ok(config)
     }
 
-    pub fn to_file(&self, path: &PathBuf) -> crate::core::error::Result<()> {
+    /// Checks every setting that would otherwise fail or misbehave deep inside indexing or
+    /// search, and returns all the problems found rather than the first.
+    pub fn validate(&self) -> std::result::Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
+        let mut at_least_one = |field: &str, value: u64| {
+            if value == 0 {
+                errors.push(ConfigError::new(field, value, "at least 1"));
+            }
+        };
+        at_least_one("thread_count", self.thread_count as u64);
+        at_least_one("cache_size", self.cache_size as u64);
+        at_least_one("max_search_results", self.max_search_results as u64);
+        at_least_one("batch_size", self.batch_size as u64);
+        at_least_one("min_batch_size", self.min_batch_size as u64);
+        at_least_one("target_batch_ms", self.target_batch_ms);
+        at_least_one("read_pool_size", self.read_pool_size as u64);
+        at_least_one("write_pool_size", self.write_pool_size as u64);
+        at_least_one("bloom_filter_capacity", self.bloom_filter_capacity as u64);
+        at_least_one("fts_max_chunk_hits", self.fts_max_chunk_hits as u64);
+        if let Some(total) = self.db_pool_size {
+            at_least_one("db_pool_size", total as u64);
+        }
+
+        if self.index_path.as_os_str().is_empty() {
+            errors.push(ConfigError::new("index_path", "\"\"", "a non-empty path"));
+        }
+        if self.cli_decimal_separator.is_ascii_digit() {
+            errors.push(ConfigError::new(
+                "cli_decimal_separator",
+                format!("{:?}", self.cli_decimal_separator),
+                "a character other than a digit",
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.fuzzy_threshold) {
+            errors.push(ConfigError::new(
+                "fuzzy_threshold",
+                self.fuzzy_threshold,
+                "a number from 0.0 to 1.0",
+            ));
+        }
+        if self.fts_chunk_bytes < MIN_FTS_CHUNK_BYTES {
+            errors.push(ConfigError::new(
+                "fts_chunk_bytes",
+                self.fts_chunk_bytes,
+                format!("at least {} bytes", MIN_FTS_CHUNK_BYTES),
+            ));
+        }
+        if self.max_batch_size < self.min_batch_size {
+            errors.push(ConfigError::new(
+                "max_batch_size",
+                self.max_batch_size,
+                format!("at least min_batch_size ({})", self.min_batch_size),
+            ));
+        }
+        if self.max_file_size_for_content > MAX_CONTENT_FILE_SIZE {
+            errors.push(ConfigError::new(
+                "max_file_size_for_content",
+                self.max_file_size_for_content,
+                format!("at most {} bytes", MAX_CONTENT_FILE_SIZE),
+            ));
+        }
+
+        let error_rate = self.bloom_filter_error_rate;
+        if !(error_rate > 0.0 && error_rate < 1.0) {
+            errors.push(ConfigError::new(
+                "bloom_filter_error_rate",
+                error_rate,
+                "a number between 0.0 and 1.0, exclusive",
+            ));
+        } else if self.bloom_filter_capacity > 0 {
+            let bytes = bloom_filter_bytes(self.bloom_filter_capacity, error_rate);
+            if bytes > MAX_BLOOM_FILTER_BYTES as f64 {
+                errors.push(ConfigError::new(
+                    "bloom_filter_capacity",
+                    format!(
+                        "{} (about {} MB at error rate {})",
+                        self.bloom_filter_capacity,
+                        (bytes / (1024.0 * 1024.0)).ceil(),
+                        error_rate
+                    ),
+                    format!(
+                        "a capacity and error rate needing at most {} MB",
+                        MAX_BLOOM_FILTER_BYTES / (1024 * 1024)
+                    ),
+                ));
+            }
+        }
+
+        for (field, patterns) in [
+            ("exclusion_patterns", &self.exclusion_patterns),
+            ("inclusion_patterns", &self.inclusion_patterns),
+            ("sensitive_patterns", &self.sensitive_patterns),
+        ] {
+            for (i, pattern) in patterns.iter().enumerate() {
+                if let Err(e) = Glob::new(pattern) {
+                    errors.push(ConfigError::new(
+                        format!("{}[{}]", field, i),
+                        format!("{:?}", pattern),
+                        format!("a valid glob ({})", e.kind()),
+                    ));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn to_file(&self, path: &PathBuf) -> Result<()> {
         let content = if path.extension().and_then(|s| s.to_str()) == Some("json") {
             serde_json::to_string_pretty(self)
-                .map_err(|e| crate::core::error::SearchError::Configuration(e.to_string()))?
+                .map_err(|e| SearchError::Configuration(e.to_string()))?
         } else {
             toml::to_string_pretty(self)
-                .map_err(|e| crate::core::error::SearchError::Configuration(e.to_string()))?
+                .map_err(|e| SearchError::Configuration(e.to_string()))?
         };
-        std::fs::write(path, content)?;
+        std::fs::write(path, content).at_path(path)?;
         Ok(())
     }
 }
@@ -123,26 +590,87 @@ impl SearchConfigBuilder {
         self
     }
 
+    pub fn query_plan_cache_size(mut self, size: usize) -> Self {
+        self.config.query_plan_cache_size = size;
+        self
+    }
+
+    pub fn miss_cache_size(mut self, size: usize) -> Self {
+        self.config.miss_cache_size = size;
+        self
+    }
+
+    pub fn miss_cache_ttl_ms(mut self, ttl_ms: u64) -> Self {
+        self.config.miss_cache_ttl_ms = ttl_ms;
+        self
+    }
+
+    pub fn fast_name_index(mut self, enabled: bool) -> Self {
+        self.config.fast_name_index = enabled;
+        self
+    }
+
     pub fn max_search_results(mut self, max: usize) -> Self {
         self.config.max_search_results = max;
         self
     }
 
+    pub fn candidate_multiplier(mut self, multiplier: usize) -> Self {
+        self.config.candidate_multiplier = multiplier;
+        self
+    }
+
+    pub fn slow_query_ms(mut self, threshold_ms: u64) -> Self {
+        self.config.slow_query_ms = threshold_ms;
+        self
+    }
+
     pub fn batch_size(mut self, size: usize) -> Self {
         self.config.batch_size = size;
         self
     }
 
+    pub fn batch_size_bounds(mut self, min: usize, max: usize) -> Self {
+        self.config.min_batch_size = min;
+        self.config.max_batch_size = max;
+        self
+    }
+
+    pub fn target_batch_ms(mut self, target_ms: u64) -> Self {
+        self.config.target_batch_ms = target_ms;
+        self
+    }
+
     pub fn follow_symlinks(mut self, follow: bool) -> Self {
         self.config.follow_symlinks = follow;
         self
     }
 
+    pub fn symlink_store_roots(mut self, roots: Vec<PathBuf>) -> Self {
+        self.config.symlink_store_roots = roots;
+        self
+    }
+
+    pub fn max_depth(mut self, depth: Option<usize>) -> Self {
+        self.config.max_depth = depth;
+        self
+    }
+
     pub fn index_hidden_files(mut self, index: bool) -> Self {
         self.config.index_hidden_files = index;
         self
     }
 
+    pub fn index_directories(mut self, index: bool) -> Self {
+        self.config.index_directories = index;
+        self
+    }
+
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.config.deterministic = deterministic;
+        self
+    }
+
     pub fn exclusion_patterns(mut self, patterns: Vec<String>) -> Self {
         self.config.exclusion_patterns = patterns;
         self
@@ -153,6 +681,26 @@ impl SearchConfigBuilder {
         self
     }
 
+    pub fn inclusion_patterns(mut self, patterns: Vec<String>) -> Self {
+        self.config.inclusion_patterns = patterns;
+        self
+    }
+
+    pub fn add_inclusion_pattern<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.config.inclusion_patterns.push(pattern.into());
+        self
+    }
+
+    pub fn sensitive_patterns(mut self, patterns: Vec<String>) -> Self {
+        self.config.sensitive_patterns = patterns;
+        self
+    }
+
+    pub fn exclude_transient_files(mut self, exclude: bool) -> Self {
+        self.config.exclude_transient_files = exclude;
+        self
+    }
+
     pub fn watch_debounce_ms(mut self, ms: u64) -> Self {
         self.config.watch_debounce_ms = ms;
         self
@@ -163,13 +711,250 @@ impl SearchConfigBuilder {
         self
     }
 
+    pub fn read_pool_size(mut self, size: u32) -> Self {
+        self.config.read_pool_size = size;
+        self
+    }
+
+    pub fn write_pool_size(mut self, size: u32) -> Self {
+        self.config.write_pool_size = size;
+        self
+    }
+
+    #[deprecated(note = "use `read_pool_size` and `write_pool_size` instead")]
     pub fn db_pool_size(mut self, size: u32) -> Self {
-        self.config.db_pool_size = size;
+        self.config.db_pool_size = Some(size);
+        self
+    }
+
+    pub fn normalize_unicode(mut self, enable: bool) -> Self {
+        self.config.normalize_unicode = enable;
+        self
+    }
+
+    pub fn compact_paths(mut self, enable: bool) -> Self {
+        self.config.compact_paths = enable;
+        self
+    }
+
+    pub fn deletion_retention_days(mut self, days: u64) -> Self {
+        self.config.deletion_retention_days = days;
+        self
+    }
+
+    pub fn audit_retention_days(mut self, days: u64) -> Self {
+        self.config.audit_retention_days = days;
+        self
+    }
+
+    pub fn write_lease_wait_secs(mut self, secs: u64) -> Self {
+        self.config.write_lease_wait_secs = secs;
+        self
+    }
+
+    pub fn write_lease_stale_secs(mut self, secs: u64) -> Self {
+        self.config.write_lease_stale_secs = secs;
+        self
+    }
+
+    pub fn enable_undo(mut self, enable: bool) -> Self {
+        self.config.enable_undo = enable;
         self
     }
 
+    pub fn undo_max_snapshot_bytes(mut self, bytes: u64) -> Self {
+        self.config.undo_max_snapshot_bytes = bytes;
+        self
+    }
+
+    pub fn undo_retention_days(mut self, days: u64) -> Self {
+        self.config.undo_retention_days = days;
+        self
+    }
+
+    pub fn undo_max_snapshots(mut self, count: usize) -> Self {
+        self.config.undo_max_snapshots = count;
+        self
+    }
+
+    pub fn assume_local_time_paths(mut self, paths: Vec<PathBuf>) -> Self {
+        self.config.assume_local_time_paths = paths;
+        self
+    }
+
+    pub fn mtime_tolerance_secs(mut self, secs: u64) -> Self {
+        self.config.mtime_tolerance_secs = secs;
+        self
+    }
+
+    pub fn cli_theme(mut self, theme: CliTheme) -> Self {
+        self.config.cli_theme = theme;
+        self
+    }
+
+    pub fn cli_size_units(mut self, units: SizeUnits) -> Self {
+        self.config.cli_size_units = units;
+        self
+    }
+
+    pub fn cli_date_format(mut self, style: DateStyle) -> Self {
+        self.config.cli_date_format = style;
+        self
+    }
+
+    pub fn cli_decimal_separator(mut self, separator: char) -> Self {
+        self.config.cli_decimal_separator = separator;
+        self
+    }
+
+    pub fn cli_pager(mut self, enabled: bool) -> Self {
+        self.config.cli_pager = enabled;
+        self
+    }
+
+    pub fn cli_pager_threshold(mut self, lines: usize) -> Self {
+        self.config.cli_pager_threshold = lines;
+        self
+    }
+
+    pub fn max_index_size_bytes(mut self, bytes: u64) -> Self {
+        self.config.max_index_size_bytes = Some(bytes);
+        self
+    }
+
+    pub fn quota_eviction(mut self, eviction: QuotaEviction) -> Self {
+        self.config.quota_eviction = eviction;
+        self
+    }
+
+    pub fn extract_image_metadata(mut self, enable: bool) -> Self {
+        self.config.extract_image_metadata = enable;
+        self
+    }
+
+    pub fn detect_shebangs(mut self, enable: bool) -> Self {
+        self.config.detect_shebangs = enable;
+        self
+    }
+
+    pub fn extract_document_fields(mut self, enable: bool) -> Self {
+        self.config.extract_document_fields = enable;
+        self
+    }
+
+    pub fn record_ownership(mut self, enable: bool) -> Self {
+        self.config.record_ownership = enable;
+        self
+    }
+
+    pub fn fts_tokenizer(mut self, tokenizer: impl Into<String>) -> Self {
+        self.config.fts_tokenizer = tokenizer.into();
+        self
+    }
+
+    pub fn fts_chunk_bytes(mut self, bytes: usize) -> Self {
+        self.config.fts_chunk_bytes = bytes;
+        self
+    }
+
+    pub fn fts_max_chunk_hits(mut self, hits: usize) -> Self {
+        self.config.fts_max_chunk_hits = hits;
+        self
+    }
+
+    pub fn snippet_context_chars(mut self, chars: usize) -> Self {
+        self.config.snippet_context_chars = chars;
+        self
+    }
+
+    pub fn max_match_locations(mut self, locations: usize) -> Self {
+        self.config.max_match_locations = locations;
+        self
+    }
+
+    pub fn thumbnail_cache_max_bytes(mut self, bytes: u64) -> Self {
+        self.config.thumbnail_cache_max_bytes = bytes;
+        self
+    }
+
+    pub fn workspace_markers(mut self, markers: Vec<String>) -> Self {
+        self.config.workspace_markers = markers;
+        self
+    }
+
+    pub fn on_batch_error(mut self, policy: BatchErrorPolicy) -> Self {
+        self.config.on_batch_error = policy;
+        self
+    }
+
+    pub fn content_sampling(mut self, sampling: ContentSampling) -> Self {
+        self.config.content_sampling = sampling;
+        self
+    }
+
+    pub fn content_sampling_rules(mut self, rules: Vec<ContentSamplingRule>) -> Self {
+        self.config.content_sampling_rules = rules;
+        self
+    }
+
+    pub fn content_analysis_timeout_ms(mut self, timeout_ms: u64) -> Self {
+        self.config.content_analysis_timeout_ms = timeout_ms;
+        self
+    }
+
+    pub fn progress_delivery(mut self, delivery: ProgressDelivery) -> Self {
+        self.config.progress_delivery = delivery;
+        self
+    }
+
+    pub fn progress_interval_ms(mut self, interval_ms: u64) -> Self {
+        self.config.progress_interval_ms = interval_ms;
+        self
+    }
+
+    pub fn auto_maintenance(mut self, enable: bool) -> Self {
+        self.config.auto_maintenance = enable;
+        self
+    }
+
+    pub fn idle_threshold_secs(mut self, secs: u64) -> Self {
+        self.config.idle_threshold_secs = secs;
+        self
+    }
+
+    pub fn maintenance_slice_ms(mut self, slice_ms: u64) -> Self {
+        self.config.maintenance_slice_ms = slice_ms;
+        self
+    }
+
+    pub fn maintenance_window(mut self, window: MaintenanceWindow) -> Self {
+        self.config.maintenance_window = Some(window);
+        self
+    }
+
+    pub fn disabled_maintenance_tasks(mut self, tasks: Vec<MaintenanceTask>) -> Self {
+        self.config.disabled_maintenance_tasks = tasks;
+        self
+    }
+
+    pub fn access_log_retention_days(mut self, days: u64) -> Self {
+        self.config.access_log_retention_days = days;
+        self
+    }
+
+    /// The config, or every problem [`SearchConfig::validate`] found in it.
+    pub fn try_build(self) -> std::result::Result<SearchConfig, Vec<ConfigError>> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+
+    /// # Panics
+    ///
+    /// If the config is invalid, with every problem in the message; use
+    /// [`try_build`](Self::try_build) to handle them instead.
     pub fn build(self) -> SearchConfig {
-        self.config
+        self.try_build()
+            .unwrap_or_else(|errors| panic!("{}", SearchError::from(errors)))
     }
 }
 
@@ -179,8 +964,395 @@ impl Default for SearchConfigBuilder {
     }
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IndexManifest {
+    #[serde(rename = "root", default)]
+    pub roots: Vec<ManifestRoot>,
+    #[serde(default)]
+    pub parallel: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestRoot {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub exclusions: Vec<String>,
+    #[serde(default)]
+    pub follow_symlinks: Option<bool>,
+    #[serde(default)]
+    pub content_search: Option<bool>,
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    #[serde(default)]
+    pub schedule: Option<String>,
+}
+
+impl IndexManifest {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).at_path(path)?;
+        let mut manifest = if path.extension().and_then(|s| s.to_str()) == Some("json") {
+            Self::from_json_str(&content)?
+        } else {
+            Self::from_toml_str(&content)?
+        };
+
+        // Relative roots are resolved against the manifest's own directory.
+        if let Some(base) = path.parent() {
+            for root in &mut manifest.roots {
+                if root.path.is_relative() {
+                    root.path = base.join(&root.path);
+                }
+            }
+        }
+
+        Ok(manifest)
+    }
+
+    pub fn from_toml_str(content: &str) -> Result<Self> {
+        toml::from_str(content)
+            .map_err(|e| SearchError::Configuration(format!("Invalid manifest: {}", e)))
+    }
+
+    pub fn from_json_str(content: &str) -> Result<Self> {
+        serde_json::from_str(content)
+            .map_err(|e| SearchError::Configuration(format!("Invalid manifest: {}", e)))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.roots.is_empty() {
+            return Err(SearchError::Configuration(
+                "Manifest declares no [[root]] entries".to_string(),
+            ));
+        }
+
+        if self.parallel == Some(0) {
+            return Err(SearchError::Configuration(
+                "Manifest parallel must be at least 1".to_string(),
+            ));
+        }
+
+        let mut seen = HashSet::new();
+        for root in &self.roots {
+            if !root.path.exists() {
+                return Err(SearchError::Configuration(format!(
+                    "Manifest root does not exist: {}",
+                    root.path.display()
+                )));
+            }
+            if !root.path.is_dir() {
+                return Err(SearchError::Configuration(format!(
+                    "Manifest root is not a directory: {}",
+                    root.path.display()
+                )));
+            }
+
+            let canonical = dunce::canonicalize(&root.path).at_path(&root.path)?;
+            if !seen.insert(canonical) {
+                return Err(SearchError::Configuration(format!(
+                    "Manifest root is listed more than once: {}",
+                    root.path.display()
+                )));
+            }
+
+            for pattern in &root.exclusions {
+                Glob::new(pattern).map_err(|e| {
+                    SearchError::Configuration(format!(
+                        "Invalid exclusion '{}' for root {}: {}",
+                        pattern,
+                        root.path.display(),
+                        e
+                    ))
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ManifestRoot {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn apply_to(&self, base: &SearchConfig) -> SearchConfig {
+        let mut config = base.clone();
+        if let Some(follow) = self.follow_symlinks {
+            config.follow_symlinks = follow;
+        }
+        if let Some(enable) = self.content_search {
+            config.enable_content_search = enable;
+        }
+        if self.max_depth.is_some() {
+            config.max_depth = self.max_depth;
+        }
+        config
+    }
+
+    // Every option resolved against the base config, as recorded for the root.
+    pub fn effective(&self, base: &SearchConfig) -> Self {
+        let config = self.apply_to(base);
+        Self {
+            path: self.path.clone(),
+            exclusions: self.exclusions.clone(),
+            follow_symlinks: Some(config.follow_symlinks),
+            content_search: Some(config.enable_content_search),
+            max_depth: config.max_depth,
+            schedule: self.schedule.clone(),
+        }
+    }
+}
+
+// Bits of an optimally sized bloom filter, -n ln(p) / ln(2)^2, in bytes.
+fn bloom_filter_bytes(capacity: usize, error_rate: f64) -> f64 {
+    let bits = -(capacity as f64) * error_rate.ln() / std::f64::consts::LN_2.powi(2);
+    bits / 8.0
+}
+
+// Where pnpm and Nix keep their stores unless told otherwise.
+fn default_store_roots() -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+    if let Some(home) = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")) {
+        let home = PathBuf::from(home);
+        roots.push(home.join(".pnpm-store"));
+        roots.push(home.join(".local/share/pnpm/store"));
+    }
+    roots.push(PathBuf::from("/nix/store"));
+    roots
+}
+
 fn num_cpus() -> usize {
     std::thread::available_parallelism()
         .map(|n| n.get())
         .unwrap_or(4)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    const MANIFEST: &str = r#"
+parallel = 2
+
+[[root]]
+path = "projects"
+exclusions = ["*.log"]
+content_search = true
+max_depth = 3
+schedule = "hourly"
+
+[[root]]
+path = "docs"
+follow_symlinks = true
+"#;
+
+    #[test]
+    fn test_manifest_parses_roots_relative_to_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let manifest_path = temp_dir.path().join("roots.toml");
+        fs::write(&manifest_path, MANIFEST).unwrap();
+
+        let manifest = IndexManifest::from_file(&manifest_path).unwrap();
+        assert_eq!(manifest.parallel, Some(2));
+        assert_eq!(manifest.roots.len(), 2);
+
+        let projects = &manifest.roots[0];
+        assert_eq!(projects.path, temp_dir.path().join("projects"));
+        assert_eq!(projects.exclusions, vec!["*.log".to_string()]);
+        assert_eq!(projects.content_search, Some(true));
+        assert_eq!(projects.max_depth, Some(3));
+        assert_eq!(projects.schedule.as_deref(), Some("hourly"));
+
+        let docs = &manifest.roots[1];
+        assert!(docs.exclusions.is_empty());
+        assert_eq!(docs.follow_symlinks, Some(true));
+        assert_eq!(docs.content_search, None);
+
+        let config = projects.apply_to(&SearchConfig::default());
+        assert!(config.enable_content_search);
+        assert_eq!(config.max_depth, Some(3));
+        assert!(!docs.apply_to(&SearchConfig::default()).enable_content_search);
+    }
+
+    #[test]
+    fn test_manifest_rejects_unknown_keys() {
+        let err = IndexManifest::from_toml_str("[[root]]\npath = \"a\"\nexclude = []\n").unwrap_err();
+        assert!(err.to_string().contains("exclude"));
+    }
+
+    #[test]
+    fn test_manifest_validation_errors() {
+        let temp_dir = TempDir::new().unwrap();
+        let existing = temp_dir.path().join("existing");
+        fs::create_dir(&existing).unwrap();
+
+        let err = IndexManifest::default().validate().unwrap_err();
+        assert!(err.to_string().contains("no [[root]]"));
+
+        let missing = temp_dir.path().join("missing");
+        let manifest = IndexManifest {
+            roots: vec![ManifestRoot::new(&existing), ManifestRoot::new(&missing)],
+            parallel: None,
+        };
+        let err = manifest.validate().unwrap_err();
+        assert!(err.to_string().contains(&missing.display().to_string()));
+
+        let manifest = IndexManifest {
+            roots: vec![
+                ManifestRoot::new(&existing),
+                ManifestRoot::new(existing.join("..").join("existing")),
+            ],
+            parallel: None,
+        };
+        let err = manifest.validate().unwrap_err();
+        assert!(err.to_string().contains("more than once"));
+
+        let mut root = ManifestRoot::new(&existing);
+        root.exclusions = vec!["[".to_string()];
+        let manifest = IndexManifest {
+            roots: vec![root],
+            parallel: None,
+        };
+        assert!(manifest.validate().is_err());
+    }
+
+    #[test]
+    fn test_content_sampling_round_trips_through_toml() {
+        let config: SearchConfig = toml::from_str(
+            r#"
+            content_sampling = { head_and_tail = { head_bytes = 4096, tail_bytes = 65536 } }
+
+            [[content_sampling_rules]]
+            extensions = ["log", "txt"]
+            path_prefix = "/var/log"
+            sampling = { tail = { tail_bytes = 65536 } }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.content_sampling,
+            ContentSampling::HeadAndTail {
+                head_bytes: 4096,
+                tail_bytes: 65536
+            }
+        );
+        assert_eq!(config.content_sampling_rules.len(), 1);
+        assert!(config.content_sampling_rules[0].matches(Path::new("/var/log/syslog.txt")));
+
+        let reparsed: SearchConfig = toml::from_str(&toml::to_string(&config).unwrap()).unwrap();
+        assert_eq!(reparsed.content_sampling_rules, config.content_sampling_rules);
+        assert_eq!(SearchConfig::default().content_sampling, ContentSampling::Head);
+    }
+
+    type Breakage = fn(&mut SearchConfig);
+
+    fn invalid_fields(config: &SearchConfig) -> Vec<String> {
+        match config.validate() {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.into_iter().map(|e| e.field).collect(),
+        }
+    }
+
+    #[test]
+    fn test_validate_checks_each_rule() {
+        assert!(SearchConfig::default().validate().is_ok());
+
+        let cases: Vec<(&str, Breakage)> = vec![
+            ("index_path", |c| c.index_path = PathBuf::new()),
+            ("thread_count", |c| c.thread_count = 0),
+            ("max_file_size_for_content", |c| {
+                c.max_file_size_for_content = MAX_CONTENT_FILE_SIZE + 1
+            }),
+            ("fuzzy_threshold", |c| c.fuzzy_threshold = 7.0),
+            ("fuzzy_threshold", |c| c.fuzzy_threshold = -0.1),
+            ("fuzzy_threshold", |c| c.fuzzy_threshold = f64::NAN),
+            ("cache_size", |c| c.cache_size = 0),
+            ("max_search_results", |c| c.max_search_results = 0),
+            ("batch_size", |c| c.batch_size = 0),
+            ("min_batch_size", |c| c.min_batch_size = 0),
+            ("max_batch_size", |c| c.max_batch_size = 0),
+            ("target_batch_ms", |c| c.target_batch_ms = 0),
+            ("read_pool_size", |c| c.read_pool_size = 0),
+            ("write_pool_size", |c| c.write_pool_size = 0),
+            ("db_pool_size", |c| c.db_pool_size = Some(0)),
+            ("bloom_filter_capacity", |c| c.bloom_filter_capacity = 0),
+            ("bloom_filter_capacity", |c| c.bloom_filter_capacity = 1_000_000_000),
+            ("bloom_filter_error_rate", |c| c.bloom_filter_error_rate = 0.0),
+            ("bloom_filter_error_rate", |c| c.bloom_filter_error_rate = 1.0),
+            ("fts_chunk_bytes", |c| c.fts_chunk_bytes = 100),
+            ("fts_max_chunk_hits", |c| c.fts_max_chunk_hits = 0),
+            ("exclusion_patterns[1]", |c| {
+                c.exclusion_patterns = vec!["*.log".to_string(), "[".to_string()]
+            }),
+            ("inclusion_patterns[0]", |c| c.inclusion_patterns = vec!["{a".to_string()]),
+            ("sensitive_patterns[0]", |c| c.sensitive_patterns = vec!["[".to_string()]),
+        ];
+        for (field, break_it) in cases {
+            let mut config = SearchConfig::default();
+            break_it(&mut config);
+            assert_eq!(invalid_fields(&config), vec![field]);
+        }
+
+        // Boundaries are allowed.
+        let config = SearchConfig {
+            fuzzy_threshold: 1.0,
+            max_file_size_for_content: MAX_CONTENT_FILE_SIZE,
+            db_pool_size: Some(1),
+            bloom_filter_capacity: 1,
+            min_batch_size: 8,
+            max_batch_size: 8,
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_reports_every_problem() {
+        let config = SearchConfig {
+            fuzzy_threshold: 7.0,
+            batch_size: 0,
+            thread_count: 0,
+            ..Default::default()
+        };
+
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 3);
+        assert_eq!(
+            errors[0].to_string(),
+            "thread_count = 0 is invalid: expected at least 1"
+        );
+        let message = SearchError::from(errors).to_string();
+        assert!(message.contains("fuzzy_threshold = 7 is invalid: expected a number from 0.0 to 1.0"));
+        assert!(message.contains("batch_size = 0"));
+
+        let err = SearchConfigBuilder::new()
+            .batch_size(0)
+            .cache_size(0)
+            .try_build()
+            .unwrap_err();
+        assert_eq!(err.len(), 2);
+        let panic = std::panic::catch_unwind(|| SearchConfigBuilder::new().batch_size(0).build());
+        assert!(panic.is_err());
+    }
+
+    #[test]
+    fn test_from_file_validates_after_parsing() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.toml");
+        fs::write(&path, "fuzzy_threshold = 7.0\nbatch_size = 0\n").unwrap();
+
+        let err = SearchConfig::from_file(&path).unwrap_err();
+        assert!(matches!(err, SearchError::Configuration(_)), "{:?}", err);
+        assert!(err.to_string().contains("fuzzy_threshold"));
+        assert!(err.to_string().contains("batch_size"));
+    }
+}
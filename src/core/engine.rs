@@ -1,13 +1,46 @@
-use crate::core::config::{SearchConfig, SearchConfigBuilder};
-use crate::core::error::Result;
-use crate::core::types::{IndexStats, ProgressCallback, SearchResult};
-use crate::filters::ExclusionFilter;
-use crate::indexer::{IndexBuilder, IncrementalIndexer};
-use crate::search::{Query, QueryParser, SearchExecutor};
-use crate::storage::{Database, FileBloomFilter, LruCache};
-use crate::watcher::FileSystemMonitor;
+use crate::core::config::{
+    IndexManifest, ManifestRoot, ProgressDelivery, SearchConfig, SearchConfigBuilder,
+};
+use crate::core::error::{Result, SearchError};
+use crate::core::maintenance::{MaintenanceStep, MaintenanceWindow};
+use crate::core::metrics::{EngineCounters, EngineMetrics};
+use crate::core::request::RequestId;
+use crate::core::types::{
+    AuditEventType, AuditRecord, BackupReport, CancellationToken, Capabilities, ChangeExport,
+    ChangeRecord, DocumentField,
+    EnrichmentProgress, EnrichmentStats, ExclusionRule, ExclusionRuleType, FileEntry,
+    FilePermissions, FtsRebuildReport, ImageDimensions, IndexReport, IndexStats,
+    IndexedRoot, LeaseWait, Listing, ListingSort, MaintenanceStatus, MaintenanceTask,
+    Pagination, Progress, ProgressCallback,
+    QuotaUsage, RestoreReport, RootIndexReport, RootProgressCallback, SavedSearch, ScriptInfo, SearchResult,
+    SearchOutcome, SearchScope, StatsCounters, StatsDrift, Thumbnail, TimestampAdjustment,
+    UndoReport, UndoSnapshot, WriteLease,
+};
+use crate::filters::{
+    get_extension_category, ExclusionFilter, ExtensionCategory, FilterDecision, SensitiveFilter,
+};
+use crate::indexer::{IndexBuilder, IncrementalIndexer, TimestampClock, WallClock};
+use crate::search::{Query, QueryParser, ResultLimit, SearchExecutor, Visibility};
+use crate::storage::thumbnails::render_thumbnail;
+use crate::storage::{
+    Database, FileBloomFilter, LruCache, MissCache, NameIndex, PathLayout, PoolSizes,
+    RestoreSource, Setting, ThumbnailCache, UndoLog, WriteLeaseGuard, WriteLeaseKeeper,
+};
+use crate::utils::hash::hash_string;
+use crate::utils::normalizer::{default_normalizer, TextNormalizer};
+use crate::watcher::{FileSystemMonitor, WatchEvent, WatchOptions, WatchStatus, WATCH_BACKEND};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Instant;
+
+/// Pages one step of the `fts_merge` maintenance task writes.
+const FTS_MERGE_PAGES: usize = 256;
+
+/// Rows one step of a pruning maintenance task deletes.
+const MAINTENANCE_BATCH: usize = 1000;
 
 pub struct SearchEngine {
     database: Arc<Database>,
@@ -18,7 +51,16 @@ pub struct SearchEngine {
     index_builder: Arc<IndexBuilder>,
     incremental_indexer: Arc<IncrementalIndexer>,
     search_executor: Arc<SearchExecutor>,
+    name_index: Option<Arc<NameIndex>>,
+    capabilities: Capabilities,
+    metrics: Arc<EngineCounters>,
+    write_lease: Arc<WriteLeaseKeeper>,
+    /// The clock given to the builder, if any, for builders made later on.
+    wall_clock: Option<WallClock>,
     monitor: Option<FileSystemMonitor>,
+    undo: Option<UndoLog>,
+    thumbnails: Option<ThumbnailCache>,
+    sensitive: SensitiveFilter,
 }
 
 impl SearchEngine {
@@ -27,41 +69,186 @@ impl SearchEngine {
         Self::with_config(index_path, config)
     }
 
+    pub fn create_or_open<P: AsRef<Path>>(index_path: P) -> Result<Self> {
+        Self::new(index_path)
+    }
+
+    pub fn open_existing<P: AsRef<Path>>(index_path: P) -> Result<Self> {
+        Self::open_existing_with_config(index_path, SearchConfig::default())
+    }
+
+    pub fn open_existing_with_config<P: AsRef<Path>>(
+        index_path: P,
+        config: SearchConfig,
+    ) -> Result<Self> {
+        Self::ensure_index_exists(index_path.as_ref())?;
+        Self::with_config(index_path, config)
+    }
+
+    fn ensure_index_exists(index_path: &Path) -> Result<()> {
+        if index_path.is_file() {
+            Ok(())
+        } else {
+            Err(SearchError::IndexNotFound(index_path.to_path_buf()))
+        }
+    }
+
     pub fn with_config<P: AsRef<Path>>(index_path: P, config: SearchConfig) -> Result<Self> {
-        let database = Arc::new(Database::new(index_path, config.db_pool_size)?);
-        let config = Arc::new(config);
+        let normalizer = default_normalizer(config.normalize_unicode);
+        Self::with_config_and_normalizer(index_path, config, normalizer)
+    }
+
+    pub fn with_config_and_normalizer<P: AsRef<Path>>(
+        index_path: P,
+        config: SearchConfig,
+        normalizer: Arc<dyn TextNormalizer>,
+    ) -> Result<Self> {
+        Self::open(index_path.as_ref(), config, normalizer, None)
+    }
+
+    fn open(
+        index_path: &Path,
+        config: SearchConfig,
+        normalizer: Arc<dyn TextNormalizer>,
+        wall_clock: Option<WallClock>,
+    ) -> Result<Self> {
+        config.validate()?;
+        let undo = config
+            .enable_undo
+            .then(|| UndoLog::for_index(index_path, &config));
+        let database = Database::with_layout(
+            index_path,
+            Self::pool_sizes_for(&config),
+            Self::path_layout_for(&config),
+        )?;
+        let thumbnails = ThumbnailCache::for_index(index_path, &config);
+        let mut engine =
+            Self::from_database(database, config, normalizer, wall_clock, Some(index_path))?;
+        engine.undo = undo;
+        engine.thumbnails = Some(thumbnails);
+        Ok(engine)
+    }
+
+    /// Creates an engine whose index lives only in memory and is dropped with it.
+    pub fn in_memory() -> Result<Self> {
+        Self::in_memory_with_config(SearchConfig::default())
+    }
+
+    pub fn in_memory_with_config(config: SearchConfig) -> Result<Self> {
+        config.validate()?;
+        let normalizer = default_normalizer(config.normalize_unicode);
+        let database = Database::in_memory_with_layout(
+            Self::pool_sizes_for(&config),
+            Self::path_layout_for(&config),
+        )?;
+        Self::from_database(database, config, normalizer, None, None)
+    }
 
-        let exclusion_rules = database.get_exclusion_rules()?;
-        let exclusion_filter = if exclusion_rules.is_empty() {
-            Arc::new(ExclusionFilter::from_patterns(&config.exclusion_patterns)?)
+    fn path_layout_for(config: &SearchConfig) -> PathLayout {
+        if config.compact_paths {
+            PathLayout::Compact
         } else {
-            Arc::new(ExclusionFilter::new(exclusion_rules)?)
-        };
+            PathLayout::Legacy
+        }
+    }
 
-        let cache = Arc::new(LruCache::new(config.cache_size));
-        let bloom_filter = Arc::new(FileBloomFilter::new(
-            config.bloom_filter_capacity,
-            config.bloom_filter_error_rate,
-        ));
+    fn pool_sizes_for(config: &SearchConfig) -> PoolSizes {
+        match config.db_pool_size {
+            Some(total) => {
+                log::warn!(
+                    "db_pool_size is deprecated; set read_pool_size and write_pool_size instead"
+                );
+                PoolSizes::from(total)
+            }
+            None => PoolSizes::new(config.read_pool_size, config.write_pool_size),
+        }
+    }
 
-        let index_builder = Arc::new(IndexBuilder::new(
-            Arc::clone(&database),
-            Arc::clone(&config),
-            Arc::clone(&exclusion_filter),
-        ));
+    /// `index_path` is where the index file lives, if it has one, for the files kept beside it.
+    fn from_database(
+        database: Database,
+        config: SearchConfig,
+        normalizer: Arc<dyn TextNormalizer>,
+        wall_clock: Option<WallClock>,
+        index_path: Option<&Path>,
+    ) -> Result<Self> {
+        if config.compact_paths && database.path_layout() == PathLayout::Legacy {
+            log::warn!(
+                "compact_paths is enabled but this index stores full paths; \
+                 run `filesearch migrate --compact` to convert it"
+            );
+        }
 
-        let incremental_indexer = Arc::new(IncrementalIndexer::new(
-            Arc::clone(&database),
-            Arc::clone(&config),
-            Arc::clone(&exclusion_filter),
-        ));
+        let metrics = Arc::new(EngineCounters::new());
+        let misses = MissCache::new(
+            config.miss_cache_size,
+            std::time::Duration::from_millis(config.miss_cache_ttl_ms),
+        )
+        .with_metrics(Arc::clone(&metrics));
+        let database = Arc::new(
+            database
+                .with_normalizer(normalizer)
+                .with_fts_tokenizer(config.fts_tokenizer.clone())
+                .with_fts_chunk_bytes(config.fts_chunk_bytes)
+                .with_miss_cache(misses),
+        );
+        let config = Arc::new(config);
+
+        let exclusion_filter = ExclusionFilter::new(Self::exclusion_rules(&database, &config)?)?;
+        let exclusion_filter =
+            Arc::new(exclusion_filter.with_transient_artifacts(config.exclude_transient_files));
 
-        let search_executor = Arc::new(SearchExecutor::new(
+        let cache = Arc::new(LruCache::new(config.cache_size).with_metrics(Arc::clone(&metrics)));
+        let bloom_filter = Arc::new(
+            FileBloomFilter::new(config.bloom_filter_capacity, config.bloom_filter_error_rate)
+                .with_metrics(Arc::clone(&metrics)),
+        );
+
+        let clock = Self::timestamp_clock(&database, &config, wall_clock);
+        let index_builder = Arc::new(
+            IndexBuilder::new(
+                Arc::clone(&database),
+                Arc::clone(&config),
+                Arc::clone(&exclusion_filter),
+            )
+            .with_clock(clock.clone())
+            .with_metrics(Arc::clone(&metrics)),
+        );
+
+        let incremental_indexer = Arc::new(
+            IncrementalIndexer::new(
+                Arc::clone(&database),
+                Arc::clone(&config),
+                Arc::clone(&exclusion_filter),
+            )
+            .with_clock(clock)
+            .with_metrics(Arc::clone(&metrics)),
+        );
+
+        let name_index = config.fast_name_index.then(|| {
+            let names = NameIndex::new(Arc::clone(&database));
+            let names = match index_path {
+                Some(path) => names.with_sidecar(NameIndex::sidecar_for(path)),
+                None => names,
+            };
+            Arc::new(names)
+        });
+        let mut search_executor = SearchExecutor::new(
             Arc::clone(&database),
             Arc::clone(&config),
             Arc::clone(&cache),
             Arc::clone(&bloom_filter),
-        ));
+        )
+        .with_metrics(Arc::clone(&metrics));
+        if let Some(names) = &name_index {
+            names.start();
+            search_executor = search_executor.with_name_index(Arc::clone(names));
+        }
+        let search_executor = Arc::new(search_executor);
+
+        let capabilities = Self::probe_capabilities(&database, &config)?;
+        let write_lease = Arc::new(WriteLeaseKeeper::new(Arc::clone(&database), &config));
+        let sensitive = SensitiveFilter::from_config(&config);
 
         Ok(Self {
             database,
@@ -72,7 +259,15 @@ impl SearchEngine {
             index_builder,
             incremental_indexer,
             search_executor,
+            name_index,
+            capabilities,
+            metrics,
+            write_lease,
+            wall_clock,
             monitor: None,
+            undo: None,
+            thumbnails: None,
+            sensitive,
         })
     }
 
@@ -80,228 +275,3311 @@ impl SearchEngine {
         SearchEngineBuilder::new()
     }
 
+    fn timestamp_clock(
+        database: &Arc<Database>,
+        config: &Arc<SearchConfig>,
+        wall_clock: Option<WallClock>,
+    ) -> TimestampClock {
+        let clock = TimestampClock::new(Arc::clone(database), Arc::clone(config));
+        match wall_clock {
+            Some(now) => clock.with_wall_clock(now),
+            None => clock,
+        }
+    }
+
+    fn probe_capabilities(database: &Database, config: &SearchConfig) -> Result<Capabilities> {
+        Ok(Capabilities {
+            content_search: config.enable_content_search && database.has_full_text_index()?,
+            fuzzy_search: config.enable_fuzzy_search,
+            access_tracking: config.enable_access_tracking,
+            // Indexing records metadata only; hashes are computed for audit records, not entries.
+            file_hashes: false,
+            watch_backend: WATCH_BACKEND.to_string(),
+            read_only: database.is_read_only()?,
+        })
+    }
+
+    /// What this engine can serve. Request handlers gate features on this same value.
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    // Stored rules take precedence over the configured patterns; exclusions and inclusions
+    // are replaced independently.
+    fn exclusion_rules(database: &Database, config: &SearchConfig) -> Result<Vec<ExclusionRule>> {
+        let configured = |patterns: &[String], rule_type: ExclusionRuleType| {
+            patterns
+                .iter()
+                .map(|pattern| ExclusionRule {
+                    pattern: pattern.clone(),
+                    rule_type: rule_type.clone(),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let (inclusions, mut rules): (Vec<_>, Vec<_>) = database
+            .get_exclusion_rules()?
+            .into_iter()
+            .partition(|rule| rule.rule_type == ExclusionRuleType::Include);
+
+        if rules.is_empty() {
+            rules = configured(&config.exclusion_patterns, ExclusionRuleType::Glob);
+        }
+        if inclusions.is_empty() {
+            rules.extend(configured(&config.inclusion_patterns, ExclusionRuleType::Include));
+        } else {
+            rules.extend(inclusions);
+        }
+
+        Ok(rules)
+    }
+
     pub fn index_directory<P: AsRef<Path>>(
         &self,
         root: P,
         progress_callback: Option<ProgressCallback>,
     ) -> Result<usize> {
-        self.index_builder.build(root, progress_callback)
+        let _lease = self.lease()?;
+        let indexed = self.index_builder.build(root.as_ref(), progress_callback)?;
+        self.record_root(root.as_ref(), indexed)?;
+        Ok(indexed)
     }
 
-    pub fn update_index<P: AsRef<Path>>(
+    pub fn index_directory_with_report<P: AsRef<Path>>(
         &self,
         root: P,
         progress_callback: Option<ProgressCallback>,
-    ) -> Result<crate::indexer::UpdateStats> {
-        self.incremental_indexer.update(root, progress_callback)
+    ) -> Result<IndexReport> {
+        let _lease = self.lease()?;
+        let report = self.index_builder.build_with_report(root.as_ref(), progress_callback)?;
+        self.record_root(root.as_ref(), report.indexed)?;
+        Ok(report)
     }
 
-    pub fn search(&self, query_str: &str) -> Result<Vec<SearchResult>> {
-        let query = QueryParser::parse(query_str)?;
-        self.search_executor.execute(&query)
+    /// Indexes names, paths and metadata only, leaving content and image details for
+    /// [`enrich`](Self::enrich). Name searches work as soon as it returns; content searches
+    /// cover the root once it has been enriched.
+    pub fn index_quick<P: AsRef<Path>>(
+        &self,
+        root: P,
+        progress_callback: Option<ProgressCallback>,
+    ) -> Result<IndexReport> {
+        let _lease = self.lease()?;
+        let report = self.index_builder.build_quick_with_report(root.as_ref(), progress_callback)?;
+        self.record_root(root.as_ref(), report.indexed)?;
+        Ok(report)
     }
 
-    pub fn search_with_query(&self, query: &Query) -> Result<Vec<SearchResult>> {
-        self.search_executor.execute(query)
+    /// Remembers a directory indexed with the engine's own settings, so
+    /// [`update_all`](Self::update_all) covers it.
+    fn record_root(&self, root: &Path, file_count: usize) -> Result<()> {
+        self.database.record_indexed_root(&IndexedRoot {
+            path: root.to_path_buf(),
+            options: ManifestRoot::new(root).effective(&self.config),
+            file_count,
+            indexed_at: Utc::now(),
+        })
     }
 
-    pub fn start_watching<P: AsRef<Path>>(&mut self, root: P) -> Result<()> {
-        if self.monitor.is_none() {
-            let mut monitor = FileSystemMonitor::new(
-                Arc::clone(&self.database),
-                Arc::clone(&self.config),
-                Arc::clone(&self.exclusion_filter),
-            );
-
-            monitor.start(root)?;
-            self.monitor = Some(monitor);
+    /// Reads the content that quick builds deferred, for `root` or every quick-built root.
+    /// Stops at the next batch once `cancel` is cancelled; progress is kept, so a later call
+    /// carries on from there.
+    pub fn enrich(
+        &self,
+        root: Option<&Path>,
+        progress_callback: Option<ProgressCallback>,
+        cancel: &CancellationToken,
+    ) -> Result<EnrichmentStats> {
+        let _lease = self.lease()?;
+        let stats = self.index_builder.enrich(root, progress_callback, cancel)?;
+        if stats.enriched > 0 {
+            self.cache.clear();
         }
+        Ok(stats)
+    }
 
-        Ok(())
+    /// Quick-built roots with content still to read. Content searches may miss files under
+    /// them until they are enriched.
+    pub fn enrichment_progress(&self) -> Result<Vec<EnrichmentProgress>> {
+        self.database.enrichment_progress()
     }
 
-    pub fn stop_watching(&mut self) -> Result<()> {
-        if let Some(mut monitor) = self.monitor.take() {
-            monitor.stop()?;
-        }
-        Ok(())
+    /// Whether `query` matches content while quick-indexed roots are still being enriched, so
+    /// files under them may be missing from its results.
+    pub fn content_coverage_partial(&self, query: &Query) -> Result<bool> {
+        Ok(query.scope == SearchScope::Content && !self.enrichment_progress()?.is_empty())
     }
 
-    pub fn is_watching(&self) -> bool {
-        self.monitor.as_ref().map(|m| m.is_running()).unwrap_or(false)
+    pub fn index_with_manifest(
+        &self,
+        manifest: IndexManifest,
+        progress_callback: Option<RootProgressCallback>,
+    ) -> Result<Vec<RootIndexReport>> {
+        use rayon::prelude::*;
+
+        manifest.validate()?;
+        let _lease = self.lease()?;
+
+        let base_rules = Self::exclusion_rules(&self.database, &self.config)?;
+        let database = &self.database;
+        let config = &self.config;
+        let metrics = &self.metrics;
+        let wall_clock = self.wall_clock;
+
+        let index_root = |root: &ManifestRoot| {
+            let started = Instant::now();
+            let result = Self::index_manifest_root(
+                database,
+                config,
+                metrics,
+                wall_clock,
+                &base_rules,
+                root,
+                progress_callback.clone(),
+            );
+
+            RootIndexReport {
+                path: root.path.clone(),
+                indexed: result.as_ref().map_or(0, |report| report.indexed),
+                skipped: result.as_ref().map_or(0, |report| report.skipped.len()),
+                duration: started.elapsed(),
+                error: result.err().map(|e| e.to_string()),
+            }
+        };
+
+        let reports = match manifest.parallel.unwrap_or(1) {
+            1 => manifest.roots.iter().map(index_root).collect(),
+            threads => rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .map_err(|e| SearchError::Configuration(e.to_string()))?
+                .install(|| manifest.roots.par_iter().map(index_root).collect()),
+        };
+
+        Ok(reports)
     }
 
-    pub fn get_stats(&self) -> Result<IndexStats> {
-        self.database.get_stats()
+    fn index_manifest_root(
+        database: &Arc<Database>,
+        config: &SearchConfig,
+        metrics: &Arc<EngineCounters>,
+        wall_clock: Option<WallClock>,
+        base_rules: &[ExclusionRule],
+        root: &ManifestRoot,
+        progress_callback: Option<RootProgressCallback>,
+    ) -> Result<IndexReport> {
+        let mut rules = base_rules.to_vec();
+        rules.extend(root.exclusions.iter().map(|pattern| ExclusionRule {
+            pattern: pattern.clone(),
+            rule_type: ExclusionRuleType::Glob,
+        }));
+        let exclusion_filter =
+            ExclusionFilter::new(rules)?.with_transient_artifacts(config.exclude_transient_files);
+
+        let root_config = Arc::new(root.apply_to(config));
+        let builder = IndexBuilder::new(
+            Arc::clone(database),
+            Arc::clone(&root_config),
+            Arc::new(exclusion_filter),
+        )
+        .with_clock(Self::timestamp_clock(database, &root_config, wall_clock))
+        .with_metrics(Arc::clone(metrics));
+
+        let callback = progress_callback.map(|callback| {
+            let path = root.path.clone();
+            Box::new(move |progress| callback(&path, progress)) as ProgressCallback
+        });
+
+        let report = builder.build_with_report(&root.path, callback)?;
+
+        database.record_indexed_root(&IndexedRoot {
+            path: root.path.clone(),
+            options: root.effective(config),
+            file_count: report.indexed,
+            indexed_at: Utc::now(),
+        })?;
+
+        Ok(report)
     }
 
-    pub fn clear_index(&self) -> Result<()> {
-        self.database.clear_all()?;
-        self.cache.clear();
-        self.bloom_filter.clear();
-        Ok(())
+    pub fn indexed_roots(&self) -> Result<Vec<IndexedRoot>> {
+        self.database.get_indexed_roots()
     }
 
-    pub fn vacuum(&self) -> Result<()> {
-        self.database.vacuum()
+    /// Roots indexed from local-time filesystems and the UTC offset applied to each.
+    pub fn timestamp_adjustments(&self) -> Result<Vec<TimestampAdjustment>> {
+        self.database.get_timestamp_adjustments()
     }
 
-    pub fn verify_index<P: AsRef<Path>>(
+    pub fn update_index<P: AsRef<Path>>(
         &self,
         root: P,
-    ) -> Result<crate::indexer::VerificationStats> {
-        self.incremental_indexer.verify_index(root)
+        progress_callback: Option<ProgressCallback>,
+    ) -> Result<crate::indexer::UpdateStats> {
+        let _lease = self.lease()?;
+        self.incremental_indexer.update(root, progress_callback)
     }
 
-    pub fn add_exclusion_pattern(&self, pattern: String) -> Result<()> {
-        use crate::core::types::{ExclusionRule, ExclusionRuleType};
+    /// Updates each of `roots` in turn, reporting progress per root. A root that no longer
+    /// exists fails rather than having its entries dropped. A failed root doesn't stop the
+    /// rest unless `continue_on_error` is off; the roots after it are then left out.
+    pub fn update_roots(
+        &self,
+        roots: &[PathBuf],
+        continue_on_error: bool,
+        progress_callback: Option<RootProgressCallback>,
+    ) -> Result<Vec<(PathBuf, Result<crate::indexer::UpdateStats>)>> {
+        let _lease = self.lease()?;
 
-        let rule = ExclusionRule {
-            pattern,
-            rule_type: ExclusionRuleType::Glob,
-        };
+        let mut results = Vec::with_capacity(roots.len());
+        for root in roots {
+            let callback = progress_callback.clone().map(|callback| {
+                let path = root.clone();
+                Box::new(move |progress| callback(&path, progress)) as ProgressCallback
+            });
+            let result = if root.exists() {
+                self.incremental_indexer.update(root, callback)
+            } else {
+                Err(SearchError::PathNotFound(root.clone()))
+            };
+            let failed = result.is_err();
+            results.push((root.clone(), result));
+            if failed && !continue_on_error {
+                break;
+            }
+        }
+        Ok(results)
+    }
 
-        self.database.add_exclusion_rule(&rule)?;
-        Ok(())
+    /// Updates every root recorded by indexing, carrying on past roots that fail. It is an
+    /// error when no root has been recorded yet.
+    pub fn update_all(
+        &self,
+        progress_callback: Option<RootProgressCallback>,
+    ) -> Result<Vec<(PathBuf, Result<crate::indexer::UpdateStats>)>> {
+        let roots: Vec<PathBuf> = self.indexed_roots()?.into_iter().map(|root| root.path).collect();
+        if roots.is_empty() {
+            return Err(SearchError::Configuration(
+                "no indexed roots are recorded yet; index a directory first".to_string(),
+            ));
+        }
+        self.update_roots(&roots, true, progress_callback)
     }
 
-    pub fn get_config(&self) -> &SearchConfig {
-        &self.config
+    pub fn search(&self, query_str: &str) -> Result<Vec<SearchResult>> {
+        let query = QueryParser::parse(query_str)?;
+        self.search_with_query(&query)
     }
 
-    pub fn cache_stats(&self) -> (usize, bool) {
-        (self.cache.len(), self.cache.is_empty())
+    pub fn search_with_query(&self, query: &Query) -> Result<Vec<SearchResult>> {
+        self.search_with_query_traced(query, None)
     }
-}
 
-pub struct SearchEngineBuilder {
-    config_builder: SearchConfigBuilder,
-    index_path: Option<PathBuf>,
-}
+    /// Like [`search_with_query`](Self::search_with_query), with `request_id` recorded on
+    /// the search's span and on its timing and slow-query events.
+    pub fn search_with_query_traced(
+        &self,
+        query: &Query,
+        request_id: Option<&RequestId>,
+    ) -> Result<Vec<SearchResult>> {
+        self.ensure_supported(query)?;
+        self.search_executor.execute_traced(query, request_id)
+    }
 
-impl SearchEngineBuilder {
-    pub fn new() -> Self {
-        Self {
-            config_builder: SearchConfigBuilder::new(),
-            index_path: None,
-        }
+    /// Runs `query` for the page its `offset` and `max_results` select, also reporting how
+    /// many results matched in all.
+    pub fn search_paged(
+        &self,
+        query: &Query,
+        request_id: Option<&RequestId>,
+    ) -> Result<SearchOutcome> {
+        self.ensure_supported(query)?;
+        self.search_executor.execute_paged(query, request_id)
     }
 
-    pub fn index_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
-        self.index_path = Some(path.into());
-        self.config_builder = self.config_builder.index_path(
-            self.index_path.as_ref().unwrap().clone()
-        );
-        self
+    /// Like [`search_paged`](Self::search_paged), with only the matches `visible` keeps
+    /// paged and counted.
+    pub fn search_paged_visible(
+        &self,
+        query: &Query,
+        request_id: Option<&RequestId>,
+        visible: Visibility,
+    ) -> Result<SearchOutcome> {
+        self.ensure_supported(query)?;
+        self.search_executor.execute_paged_visible(query, request_id, visible)
     }
 
-    pub fn thread_count(mut self, count: usize) -> Self {
-        self.config_builder = self.config_builder.thread_count(count);
-        self
+    fn ensure_supported(&self, query: &Query) -> Result<()> {
+        if query.scope == SearchScope::Content && !self.capabilities.content_search {
+            return Err(SearchError::FeatureUnavailable(
+                "content search is disabled for this index".to_string(),
+            ));
+        }
+        Ok(())
     }
 
-    pub fn enable_content_search(mut self, enable: bool) -> Self {
-        self.config_builder = self.config_builder.enable_content_search(enable);
-        self
+    pub fn result_limit(&self, query: &Query) -> Result<ResultLimit> {
+        query.result_limit(self.config.max_search_results)
     }
 
-    pub fn enable_fuzzy_search(mut self, enable: bool) -> Self {
-        self.config_builder = self.config_builder.enable_fuzzy_search(enable);
-        self
+    /// Stores `query` under `name`, replacing any existing search with that name.
+    pub fn save_search(
+        &self,
+        name: &str,
+        query: &str,
+        sort: Option<ListingSort>,
+    ) -> Result<SavedSearch> {
+        let name = name.trim();
+        if name.is_empty() || name.contains('/') {
+            return Err(SearchError::InvalidQuery(format!(
+                "Invalid saved search name: '{}'",
+                name
+            )));
+        }
+        QueryParser::parse(query)?;
+
+        self.database.save_search(&SavedSearch {
+            name: name.to_string(),
+            query: query.to_string(),
+            sort,
+            created_at: Utc::now(),
+            last_run_at: None,
+            last_result_count: None,
+        })?;
+
+        self.database
+            .get_saved_search(name)?
+            .ok_or_else(|| SearchError::SavedSearchNotFound(name.to_string()))
     }
 
-    pub fn cache_size(mut self, size: usize) -> Self {
-        self.config_builder = self.config_builder.cache_size(size);
-        self
+    pub fn saved_search(&self, name: &str) -> Result<Option<SavedSearch>> {
+        self.database.get_saved_search(name)
     }
 
-    pub fn max_search_results(mut self, max: usize) -> Self {
-        self.config_builder = self.config_builder.max_search_results(max);
-        self
+    pub fn saved_searches(&self) -> Result<Vec<SavedSearch>> {
+        self.database.list_saved_searches()
     }
 
-    pub fn exclusion_patterns(mut self, patterns: Vec<String>) -> Self {
-        self.config_builder = self.config_builder.exclusion_patterns(patterns);
-        self
+    pub fn delete_saved_search(&self, name: &str) -> Result<bool> {
+        self.database.delete_saved_search(name)
     }
 
-    pub fn build(self) -> Result<SearchEngine> {
-        let config = self.config_builder.build();
-        let index_path = self.index_path.unwrap_or_else(|| config.index_path.clone());
+    /// Tags the indexed files with `ids`, e.g. every result of a search. Returns how many
+    /// files gained the tag; ids that are not in the index are skipped.
+    pub fn tag_files(&self, ids: &[i64], tag: &str) -> Result<usize> {
+        self.database.tag_files(ids, validate_tag(tag)?)
+    }
 
-        SearchEngine::with_config(index_path, config)
+    pub fn untag_files(&self, ids: &[i64], tag: &str) -> Result<usize> {
+        self.database.untag_files(ids, validate_tag(tag)?)
     }
-}
 
-impl Default for SearchEngineBuilder {
-    fn default() -> Self {
-        Self::new()
+    /// Every user tag with the number of files carrying it, most used first.
+    pub fn list_tags(&self) -> Result<Vec<(String, usize)>> {
+        self.database.list_tags()
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
-    use tempfile::TempDir;
+    pub fn find_by_tag(&self, tag: &str, limit: usize) -> Result<Vec<FileEntry>> {
+        self.database.find_by_tags(&[validate_tag(tag)?.to_string()], limit)
+    }
 
-    #[test]
-    fn test_search_engine_creation() {
-        let temp_dir = TempDir::new().unwrap();
-        let index_path = temp_dir.path().join("index.db");
+    /// Pixel size recorded for image file `id` by indexing with `extract_image_metadata`.
+    pub fn image_dimensions(&self, id: i64) -> Result<Option<ImageDimensions>> {
+        self.database.get_image_dimensions(id)
+    }
 
-        let engine = SearchEngine::new(&index_path).unwrap();
-        assert!(!engine.is_watching());
+    /// Interpreters recorded with `detect_shebangs` for the scripts among `ids`.
+    pub fn scripts(&self, ids: &[i64]) -> Result<HashMap<i64, ScriptInfo>> {
+        self.database.scripts_for(ids)
     }
 
-    #[test]
-    fn test_search_engine_builder() {
-        let temp_dir = TempDir::new().unwrap();
-        let index_path = temp_dir.path().join("index.db");
+    /// Where the store aliases among `ids` point: links into a `symlink_store_roots` store,
+    /// which are indexed by their own path without content.
+    pub fn link_targets(&self, ids: &[i64]) -> Result<HashMap<i64, PathBuf>> {
+        self.database.link_targets_for(ids)
+    }
 
-        let engine = SearchEngine::builder()
-            .index_path(index_path)
-            .thread_count(4)
-            .enable_content_search(false)
-            .build()
-            .unwrap();
+    /// Ownership recorded with `record_ownership` for the entries among `ids`.
+    pub fn permissions(&self, ids: &[i64]) -> Result<HashMap<i64, FilePermissions>> {
+        self.database.permissions_for(ids)
+    }
 
-        assert_eq!(engine.get_config().thread_count, 4);
+    /// Whether any entry has its ownership recorded.
+    pub fn has_permissions(&self) -> Result<bool> {
+        self.database.has_permissions()
     }
 
-    #[test]
-    fn test_indexing_and_search() {
-        let temp_dir = TempDir::new().unwrap();
-        let root = temp_dir.path().join("data");
-        fs::create_dir(&root).unwrap();
+    /// Email headers and HTML head fields recorded with `extract_document_fields` for the
+    /// files among `ids`. An email's `date` field is its `Date` header in RFC 3339 UTC, kept
+    /// apart from the file's own timestamps.
+    pub fn document_fields(&self, ids: &[i64]) -> Result<HashMap<i64, Vec<DocumentField>>> {
+        self.database.document_fields_for(ids)
+    }
 
-        fs::write(root.join("test1.txt"), "content1").unwrap();
-        fs::write(root.join("test2.txt"), "content2").unwrap();
+    /// A JPEG thumbnail of image file `id`, at most `size` pixels on its longer side.
+    ///
+    /// The first request for a size decodes the whole image and caches the result in
+    /// `<index>.thumbs/`; async callers should run this on a blocking thread.
+    pub fn thumbnail(&self, id: i64, size: u32) -> Result<Thumbnail> {
+        let Some(cache) = &self.thumbnails else {
+            return Err(SearchError::FeatureUnavailable(
+                "thumbnails are cached next to the index, so in-memory indexes have none"
+                    .to_string(),
+            ));
+        };
 
-        let index_path = temp_dir.path().join("index.db");
-        let engine = SearchEngine::new(&index_path).unwrap();
+        let entry = self
+            .database
+            .find_by_id(id)?
+            .ok_or(SearchError::EntryNotFound(id))?;
+        let is_image = entry
+            .extension
+            .as_deref()
+            .is_some_and(|ext| get_extension_category(ext) == ExtensionCategory::Image);
+        if entry.is_directory || !is_image {
+            return Err(SearchError::UnsupportedMedia(entry.path));
+        }
 
-        let count = engine.index_directory(&root, None).unwrap();
-        assert!(count > 0);
+        let metadata = match std::fs::metadata(&entry.path) {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => return Err(SearchError::PathNotFound(entry.path)),
+        };
+        // Identical images share thumbnails when content hashes are kept; otherwise the key
+        // follows the file's size and modification time.
+        let key = entry.file_hash.clone().unwrap_or_else(|| {
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+                .unwrap_or_default();
+            hash_string(&format!(
+                "{}\0{}\0{}",
+                entry.path.display(),
+                metadata.len(),
+                modified.as_nanos()
+            ))
+        });
 
-        let results = engine.search("test").unwrap();
-        assert!(!results.is_empty());
+        if let Some(bytes) = cache.get(&key, size) {
+            return Ok(Thumbnail {
+                bytes,
+                key: format!("{}-{}", key, size),
+                cached: true,
+            });
+        }
+
+        let bytes = render_thumbnail(&entry.path, size)?;
+        if let Err(e) = cache.put(&key, size, &bytes) {
+            log::warn!("Failed to cache thumbnail for {}: {}", entry.path.display(), e);
+        }
+        Ok(Thumbnail {
+            bytes,
+            key: format!("{}-{}", key, size),
+            cached: false,
+        })
     }
 
-    #[test]
-    fn test_stats() {
-        let temp_dir = TempDir::new().unwrap();
-        let root = temp_dir.path().join("data");
-        fs::create_dir(&root).unwrap();
+    /// Runs a saved search and returns one page of it sorted like a directory listing,
+    /// newest first unless the search stores its own order.
+    pub fn run_saved_as_listing(&self, name: &str, pagination: Pagination) -> Result<Listing> {
+        let saved = self
+            .database
+            .get_saved_search(name)?
+            .ok_or_else(|| SearchError::SavedSearchNotFound(name.to_string()))?;
 
-        fs::write(root.join("file.txt"), "content").unwrap();
+        let query = QueryParser::parse(&saved.query)?;
+        let mut entries: Vec<_> = self
+            .search_with_query(&query)?
+            .into_iter()
+            .map(|result| result.file)
+            .collect();
 
-        let index_path = temp_dir.path().join("index.db");
-        let engine = SearchEngine::new(&index_path).unwrap();
+        let sort = saved.sort.unwrap_or_default();
+        sort.sort(&mut entries);
 
-        engine.index_directory(&root, None).unwrap();
+        let total = entries.len();
+        self.database
+            .record_saved_search_run(&saved.name, total, Utc::now())?;
 
-        let stats = engine.get_stats().unwrap();
-        assert!(stats.total_files > 0);
+        let entries: Vec<_> = entries
+            .into_iter()
+            .skip(pagination.offset)
+            .take(pagination.limit.unwrap_or(usize::MAX))
+            .collect();
+
+        let mut fingerprint = format!("{}|{}|{}", saved.query, sort.as_str(), total);
+        for entry in &entries {
+            fingerprint.push_str(&format!(
+                "|{}:{}:{}",
+                entry.path.display(),
+                entry.size,
+                entry.modified_at.map(|t| t.timestamp()).unwrap_or_default()
+            ));
+        }
+
+        Ok(Listing {
+            name: saved.name,
+            entries,
+            total,
+            offset: pagination.offset,
+            sort,
+            etag: hash_string(&fingerprint),
+        })
+    }
+
+    pub fn start_watching<P: AsRef<Path>>(&mut self, root: P) -> Result<()> {
+        self.start_watching_with_options(root, WatchOptions::default())
+    }
+
+    pub fn start_watching_with_options<P: AsRef<Path>>(
+        &mut self,
+        root: P,
+        options: WatchOptions,
+    ) -> Result<()> {
+        if self.monitor.is_none() {
+            let mut monitor = FileSystemMonitor::new(
+                Arc::clone(&self.database),
+                Arc::clone(&self.config),
+                Arc::clone(&self.exclusion_filter),
+            )
+            .with_metrics(Arc::clone(&self.metrics))
+            .with_write_lease(Arc::clone(&self.write_lease));
+
+            monitor.start_with_options(root, options)?;
+            self.monitor = Some(monitor);
+        }
+
+        Ok(())
+    }
+
+    pub fn stop_watching(&mut self) -> Result<()> {
+        if let Some(mut monitor) = self.monitor.take() {
+            monitor.stop()?;
+        }
+        Ok(())
+    }
+
+    pub fn is_watching(&self) -> bool {
+        self.monitor.as_ref().map(|m| m.is_running()).unwrap_or(false)
+    }
+
+    pub fn watch_status(&self) -> Option<WatchStatus> {
+        self.monitor.as_ref().map(|m| m.status())
+    }
+
+    pub fn subscribe_watch_events(&self) -> Option<std::sync::mpsc::Receiver<WatchEvent>> {
+        self.monitor.as_ref().map(|m| m.subscribe())
+    }
+
+    /// The `limit` most common file extensions in the index, most frequent first.
+    pub fn top_extensions(&self, limit: usize) -> Result<Vec<(String, usize)>> {
+        self.database.top_extensions(limit)
+    }
+
+    /// The `limit` most recently modified files in the index, newest first.
+    pub fn recent_files(&self, limit: usize) -> Result<Vec<FileEntry>> {
+        self.database.recently_modified(limit)
+    }
+
+    pub fn get_stats(&self) -> Result<IndexStats> {
+        let mut stats = self.database.get_stats()?;
+        stats.quota = self.quota_usage()?;
+        stats.enrichment = self.enrichment_progress()?;
+        stats.write_lease = self.write_lease()?;
+        stats.maintenance = self.maintenance_status()?;
+        stats.name_index = self.name_index.as_ref().map(|names| names.usage()).transpose()?;
+        Ok(stats)
+    }
+
+    /// Builds the in-memory name index now if the index changed since it was last built,
+    /// instead of waiting for a search to start a rebuild in the background. Returns whether
+    /// it was rebuilt; always `false` without `fast_name_index`.
+    pub fn refresh_name_index(&self) -> Result<bool> {
+        match &self.name_index {
+            Some(names) => names.refresh(),
+            None => Ok(false),
+        }
+    }
+
+    /// Compares the stored statistics counters with a full recount; `None` when they agree.
+    pub fn stats_drift(&self) -> Result<Option<StatsDrift>> {
+        self.database.stats_drift()
+    }
+
+    /// Rebuilds the statistics counters from a full scan, repairing any drift.
+    pub fn recompute_stats(&self) -> Result<StatsCounters> {
+        let _lease = self.lease()?;
+        self.database.recompute_stats()
+    }
+
+    /// Holds the index's write lease until the guard is dropped, keeping writers in other
+    /// processes out between the operations run meanwhile. Operations that write take the
+    /// lease on their own, waiting up to `write_lease_wait_secs`; while a guard is held they
+    /// reuse it instead. `on_wait` is told about each other writer found in the way.
+    pub fn acquire_write_lease(
+        &self,
+        wait: LeaseWait,
+        on_wait: impl FnMut(&WriteLease),
+    ) -> Result<WriteLeaseGuard> {
+        self.write_lease.acquire_with(wait, on_wait)
+    }
+
+    /// The writer holding the index's write lease, if any, which may be this engine.
+    pub fn write_lease(&self) -> Result<Option<WriteLease>> {
+        self.write_lease.current()
+    }
+
+    /// Read-only indexes cannot record a lease, and nothing writes to them anyway.
+    fn lease(&self) -> Result<Option<WriteLeaseGuard>> {
+        if self.capabilities.read_only {
+            return Ok(None);
+        }
+        self.write_lease.acquire().map(Some)
+    }
+
+    /// How much of `max_index_size_bytes` the index uses, or `None` without a quota.
+    pub fn quota_usage(&self) -> Result<Option<QuotaUsage>> {
+        let Some(limit_bytes) = self.config.max_index_size_bytes else {
+            return Ok(None);
+        };
+
+        Ok(Some(QuotaUsage {
+            limit_bytes,
+            used_bytes: self.database.storage_size()?,
+        }))
+    }
+
+    pub fn config(&self) -> &SearchConfig {
+        &self.config
+    }
+
+    /// The paths `sensitive_patterns` match, for masking them in logs and leaving them out
+    /// of what leaves the engine.
+    pub fn sensitive(&self) -> &SensitiveFilter {
+        &self.sensitive
+    }
+
+    /// Runs every enabled maintenance task to completion, whether or not the engine is idle.
+    /// Returns the tasks that ran.
+    pub fn run_maintenance(&self) -> Result<Vec<MaintenanceTask>> {
+        let _lease = self.lease()?;
+        let tasks: Vec<_> = MaintenanceTask::ALL
+            .into_iter()
+            .filter(|task| self.maintenance_enabled(*task))
+            .collect();
+
+        for &task in &tasks {
+            while self.maintenance_step(task)? != MaintenanceStep::Done {}
+        }
+        Ok(tasks)
+    }
+
+    /// When each maintenance task last completed on this index.
+    pub fn maintenance_status(&self) -> Result<Vec<MaintenanceStatus>> {
+        MaintenanceTask::ALL
+            .into_iter()
+            .map(|task| {
+                Ok(MaintenanceStatus {
+                    task,
+                    enabled: self.maintenance_enabled(task),
+                    last_run: self.database.maintenance_last_run(task)?,
+                })
+            })
+            .collect()
+    }
+
+    fn maintenance_enabled(&self, task: MaintenanceTask) -> bool {
+        !self.config.disabled_maintenance_tasks.contains(&task)
+    }
+
+    /// Does one bounded unit of `task`'s work, recording its run once nothing is left.
+    pub(crate) fn maintenance_step(&self, task: MaintenanceTask) -> Result<MaintenanceStep> {
+        let Some(_lease) = self.write_lease.try_acquire()? else {
+            return Ok(MaintenanceStep::LeaseHeld);
+        };
+
+        let done = match task {
+            MaintenanceTask::FtsMerge => self.database.merge_fts(FTS_MERGE_PAGES)?,
+            MaintenanceTask::Analyze => {
+                self.database.analyze()?;
+                true
+            }
+            MaintenanceTask::Checkpoint => {
+                self.database.checkpoint_passive()?;
+                true
+            }
+            MaintenanceTask::PruneOrphans => {
+                self.database.prune_orphan_blobs(MAINTENANCE_BATCH)? < MAINTENANCE_BATCH
+            }
+            MaintenanceTask::AccessLogRetention => {
+                let retention = Duration::days(self.config.access_log_retention_days as i64);
+                let pruned = self
+                    .database
+                    .prune_access_log(Utc::now() - retention, MAINTENANCE_BATCH)?;
+                pruned < MAINTENANCE_BATCH
+            }
+        };
+
+        if !done {
+            return Ok(MaintenanceStep::More);
+        }
+        self.database.record_maintenance_run(task, Utc::now())?;
+        Ok(MaintenanceStep::Done)
+    }
+
+    /// What the maintenance scheduler watches to tell whether the engine is idle.
+    pub(crate) fn activity(&self) -> &EngineCounters {
+        &self.metrics
+    }
+
+    /// Returns a snapshot of the engine's activity since it was created.
+    ///
+    /// Reading it is cheap, so embedders can poll it to feed their own monitoring:
+    ///
+    /// ```no_run
+    /// use rusty_files::SearchEngine;
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// let engine = Arc::new(SearchEngine::new("./index.db")?);
+    /// let reporter = Arc::clone(&engine);
+    /// std::thread::spawn(move || loop {
+    ///     let metrics = reporter.metrics();
+    ///     println!("{}", serde_json::to_string(&metrics).unwrap());
+    ///     std::thread::sleep(Duration::from_secs(60));
+    /// });
+    ///
+    /// engine.index_directory("/path/to/dir", None)?;
+    /// # Ok::<(), rusty_files::SearchError>(())
+    /// ```
+    pub fn metrics(&self) -> EngineMetrics {
+        let mut metrics = self.metrics.snapshot();
+        let (in_use, idle) = self.database.pool_usage();
+        metrics.db_connections_in_use = in_use;
+        metrics.db_connections_idle = idle;
+        metrics
+    }
+
+    pub fn query_audit_log(
+        &self,
+        path_prefix: Option<&Path>,
+        time_range: (Option<DateTime<Utc>>, Option<DateTime<Utc>>),
+        event_types: &[AuditEventType],
+        after_id: i64,
+        limit: usize,
+    ) -> Result<Vec<AuditRecord>> {
+        self.database
+            .query_audit_log(path_prefix, time_range, event_types, after_id, limit)
+    }
+
+    pub fn clear_audit_log(&self) -> Result<usize> {
+        self.database.clear_audit_log()
+    }
+
+    /// Removes every entry, first saving an undo snapshot unless undo is disabled or the
+    /// index is in memory. Fails with `UndoSnapshotTooLarge` and leaves the index untouched
+    /// when the snapshot would pass `undo_max_snapshot_bytes`.
+    pub fn clear_index(&self) -> Result<()> {
+        let _lease = self.lease()?;
+        if let Some(undo) = &self.undo {
+            undo.snapshot("clear_index", &self.database, self.config.batch_size)?;
+        }
+        self.clear_index_without_undo()
+    }
+
+    pub fn clear_index_without_undo(&self) -> Result<()> {
+        let _lease = self.lease()?;
+        self.database.clear_all()?;
+        self.cache.clear();
+        self.bloom_filter.clear();
+        Ok(())
+    }
+
+    /// Undo snapshots available for this index, newest first.
+    pub fn undoable_operations(&self) -> Result<Vec<UndoSnapshot>> {
+        match &self.undo {
+            Some(undo) => undo.list(),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Restores the entries saved by the newest snapshot and then discards it.
+    ///
+    /// Snapshots hold entry metadata only, so content search data and user tags come
+    /// back after the affected roots are re-indexed.
+    pub fn undo_last(&self) -> Result<Option<UndoReport>> {
+        let Some(undo) = &self.undo else {
+            return Ok(None);
+        };
+        let Some(snapshot) = undo.list()?.into_iter().next() else {
+            return Ok(None);
+        };
+        let _lease = self.lease()?;
+
+        let restored = undo.restore(&snapshot, self.config.batch_size, |batch| {
+            self.database.insert_files_batch(batch)
+        })?;
+        undo.remove(&snapshot)?;
+        self.cache.clear();
+
+        Ok(Some(UndoReport { snapshot, restored }))
+    }
+
+    pub fn path_layout(&self) -> PathLayout {
+        self.database.path_layout()
+    }
+
+    /// Every setting stored in the index, ordered by key.
+    pub fn settings(&self) -> Result<Vec<Setting>> {
+        self.database.settings()
+    }
+
+    pub fn setting(&self, key: &str) -> Result<Option<Setting>> {
+        self.database.setting(key)
+    }
+
+    /// Stores a raw setting value. Only `user.` keys are meant to be set by hand; the index
+    /// reads the rest and trusts them to have been written by itself.
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<()> {
+        self.database.set_setting(key, value)
+    }
+
+    /// Converts a legacy index to compact path storage and reclaims the freed space,
+    /// returning how many entries were compacted.
+    pub fn convert_to_compact_paths(&self) -> Result<usize> {
+        let _lease = self.lease()?;
+        let converted = self.database.convert_to_compact_paths()?;
+        self.cache.clear();
+        self.database.vacuum()?;
+        Ok(converted)
+    }
+
+    /// Rebuilds the content search index from the text stored at indexing time, without
+    /// reading any file: after changing `fts_tokenizer`, or when the full-text table is
+    /// damaged. Content searches keep using the old table until the new one replaces it.
+    pub fn rebuild_fts(
+        &self,
+        progress_callback: Option<ProgressCallback>,
+    ) -> Result<FtsRebuildReport> {
+        let _lease = self.lease()?;
+        let report = self.database.rebuild_fts(self.config.batch_size, |current, total| {
+            if let Some(callback) = &progress_callback {
+                callback(Progress::new(current, total, "Rebuilding full-text index".to_string()));
+            }
+        })?;
+        self.cache.clear();
+        Ok(report)
+    }
+
+    /// Copies the index to `dest` while it stays in use, as one consistent snapshot taken
+    /// when the backup starts. See [`Database::backup_to`].
+    pub fn backup(
+        &self,
+        dest: &Path,
+        progress_callback: Option<ProgressCallback>,
+    ) -> Result<BackupReport> {
+        self.database.backup_to(dest, |current, total| {
+            if let Some(callback) = &progress_callback {
+                callback(Progress::new(current, total, "Backing up index".to_string()));
+            }
+        })
+    }
+
+    /// Replaces the index's contents with the backup at `source`, plain or gzipped, after
+    /// checking it is an intact index with a schema this build can migrate.
+    ///
+    /// Caches and settings loaded at open still describe the old contents, and an older
+    /// backup is only migrated when the index is opened, so drop this engine and open the
+    /// index again before using it.
+    pub fn restore(
+        &self,
+        source: &Path,
+        progress_callback: Option<ProgressCallback>,
+    ) -> Result<RestoreReport> {
+        let _lease = self.lease()?;
+        let backup = RestoreSource::open(source, &self.config.index_path)?;
+        let pages = self.database.restore_from(backup.path(), |current, total| {
+            if let Some(callback) = &progress_callback {
+                callback(Progress::new(current, total, "Restoring index".to_string()));
+            }
+        })?;
+        self.cache.clear();
+        if let Some(names) = &self.name_index {
+            names.invalidate();
+        }
+        Ok(RestoreReport {
+            pages,
+            schema_version: backup.schema_version(),
+        })
+    }
+
+    /// The index's files and content in a canonical text form. See
+    /// [`Database::logical_dump`].
+    pub fn logical_dump(&self) -> Result<String> {
+        self.database.logical_dump()
+    }
+
+    pub fn vacuum(&self) -> Result<()> {
+        let _lease = self.lease()?;
+        let retention = Duration::days(self.config.deletion_retention_days as i64);
+        self.database.prune_deletions(Utc::now() - retention)?;
+        let audit_retention = Duration::days(self.config.audit_retention_days as i64);
+        self.database.prune_audit_log(Utc::now() - audit_retention)?;
+        self.database.vacuum()
+    }
+
+    /// Writes the changes since `since` without sensitive entries; see
+    /// [`export_changes`](Self::export_changes). Returns the cursor to resume from.
+    pub fn export_changes_since<W: Write>(
+        &self,
+        since: DateTime<Utc>,
+        writer: W,
+    ) -> Result<DateTime<Utc>> {
+        Ok(self.export_changes(since, writer, false)?.cursor)
+    }
+
+    /// Writes the deletions and then the entries changed since `since` as JSON lines, ending
+    /// with a cursor record. Entries and deletions of sensitive paths are left out and
+    /// counted unless `include_sensitive` is set.
+    pub fn export_changes<W: Write>(
+        &self,
+        since: DateTime<Utc>,
+        mut writer: W,
+        include_sensitive: bool,
+    ) -> Result<ChangeExport> {
+        let mut cursor = since;
+        let mut omitted_sensitive = 0;
+        let mut omitted = |path: &Path| {
+            let omit = !include_sensitive && self.sensitive.is_sensitive(path);
+            omitted_sensitive += omit as usize;
+            omit
+        };
+
+        // Deletions go first so a path that was removed and re-created ends up present.
+        for (path, deleted_at) in self.database.get_deletions_since(since)? {
+            cursor = cursor.max(deleted_at);
+            if !omitted(&path) {
+                Self::write_change(&mut writer, &ChangeRecord::Delete { path, deleted_at })?;
+            }
+        }
+
+        let mut after_id = 0;
+        loop {
+            let batch = self
+                .database
+                .get_files_changed_since(since, after_id, self.config.batch_size)?;
+            if batch.is_empty() {
+                break;
+            }
+
+            for entry in batch {
+                after_id = entry.id.unwrap_or(after_id);
+                cursor = cursor.max(entry.indexed_at).max(entry.last_verified);
+                if !omitted(&entry.path) {
+                    Self::write_change(&mut writer, &ChangeRecord::Upsert { entry })?;
+                }
+            }
+        }
+
+        let record = ChangeRecord::Cursor {
+            cursor,
+            omitted_sensitive,
+        };
+        Self::write_change(&mut writer, &record)?;
+        writer.flush()?;
+
+        Ok(ChangeExport {
+            cursor,
+            omitted_sensitive,
+        })
+    }
+
+    fn write_change<W: Write>(writer: &mut W, record: &ChangeRecord) -> Result<()> {
+        serde_json::to_writer(&mut *writer, record).map_err(std::io::Error::from)?;
+        writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    pub fn verify_index<P: AsRef<Path>>(
+        &self,
+        root: P,
+        progress_callback: Option<ProgressCallback>,
+    ) -> Result<crate::indexer::VerificationStats> {
+        self.verify_index_with_cancellation(root, progress_callback, &CancellationToken::new())
+    }
+
+    /// Like [`verify_index`](Self::verify_index), stopping early once `cancel` is cancelled
+    /// with the stats gathered so far.
+    pub fn verify_index_with_cancellation<P: AsRef<Path>>(
+        &self,
+        root: P,
+        progress_callback: Option<ProgressCallback>,
+        cancel: &CancellationToken,
+    ) -> Result<crate::indexer::VerificationStats> {
+        self.incremental_indexer.verify_index(root, progress_callback, cancel)
+    }
+
+    pub fn add_exclusion_pattern(&self, pattern: String) -> Result<()> {
+        let rule = ExclusionRule {
+            pattern,
+            rule_type: ExclusionRuleType::Glob,
+        };
+
+        self.database.add_exclusion_rule(&rule)?;
+        Ok(())
+    }
+
+    /// Stores an inclusion glob; like exclusion rules, it applies from the next open.
+    pub fn add_inclusion_pattern(&self, pattern: String) -> Result<()> {
+        let rule = ExclusionRule {
+            pattern,
+            rule_type: ExclusionRuleType::Include,
+        };
+
+        self.database.add_exclusion_rule(&rule)?;
+        Ok(())
+    }
+
+    /// Whether the filters would index `path` as a file, and if not, which rule stops it.
+    pub fn filter_decision<P: AsRef<Path>>(&self, path: P) -> FilterDecision {
+        self.exclusion_filter.decision(path)
+    }
+
+    pub fn get_config(&self) -> &SearchConfig {
+        &self.config
+    }
+
+    pub fn cache_stats(&self) -> (usize, bool) {
+        (self.cache.len(), self.cache.is_empty())
+    }
+}
+
+pub struct SearchEngineBuilder {
+    config_builder: SearchConfigBuilder,
+    index_path: Option<PathBuf>,
+    normalizer: Option<Arc<dyn TextNormalizer>>,
+    wall_clock: Option<WallClock>,
+    create_if_missing: bool,
+    in_memory: bool,
+}
+
+impl SearchEngineBuilder {
+    pub fn new() -> Self {
+        Self {
+            config_builder: SearchConfigBuilder::new(),
+            index_path: None,
+            normalizer: None,
+            wall_clock: None,
+            create_if_missing: true,
+            in_memory: false,
+        }
+    }
+
+    pub fn index_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.index_path = Some(path.into());
+        self.config_builder = self.config_builder.index_path(
+            self.index_path.as_ref().unwrap().clone()
+        );
+        self
+    }
+
+    pub fn thread_count(mut self, count: usize) -> Self {
+        self.config_builder = self.config_builder.thread_count(count);
+        self
+    }
+
+    pub fn batch_size(mut self, size: usize) -> Self {
+        self.config_builder = self.config_builder.batch_size(size);
+        self
+    }
+
+    pub fn enable_content_search(mut self, enable: bool) -> Self {
+        self.config_builder = self.config_builder.enable_content_search(enable);
+        self
+    }
+
+    pub fn enable_fuzzy_search(mut self, enable: bool) -> Self {
+        self.config_builder = self.config_builder.enable_fuzzy_search(enable);
+        self
+    }
+
+    pub fn enable_access_tracking(mut self, enable: bool) -> Self {
+        self.config_builder = self.config_builder.enable_access_tracking(enable);
+        self
+    }
+
+    pub fn cache_size(mut self, size: usize) -> Self {
+        self.config_builder = self.config_builder.cache_size(size);
+        self
+    }
+
+    pub fn max_search_results(mut self, max: usize) -> Self {
+        self.config_builder = self.config_builder.max_search_results(max);
+        self
+    }
+
+    pub fn candidate_multiplier(mut self, multiplier: usize) -> Self {
+        self.config_builder = self.config_builder.candidate_multiplier(multiplier);
+        self
+    }
+
+    pub fn slow_query_ms(mut self, threshold_ms: u64) -> Self {
+        self.config_builder = self.config_builder.slow_query_ms(threshold_ms);
+        self
+    }
+
+    pub fn snippet_context_chars(mut self, chars: usize) -> Self {
+        self.config_builder = self.config_builder.snippet_context_chars(chars);
+        self
+    }
+
+    pub fn max_match_locations(mut self, locations: usize) -> Self {
+        self.config_builder = self.config_builder.max_match_locations(locations);
+        self
+    }
+
+    pub fn index_directories(mut self, index: bool) -> Self {
+        self.config_builder = self.config_builder.index_directories(index);
+        self
+    }
+
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.config_builder = self.config_builder.deterministic(deterministic);
+        self
+    }
+
+    pub fn exclusion_patterns(mut self, patterns: Vec<String>) -> Self {
+        self.config_builder = self.config_builder.exclusion_patterns(patterns);
+        self
+    }
+
+    pub fn inclusion_patterns(mut self, patterns: Vec<String>) -> Self {
+        self.config_builder = self.config_builder.inclusion_patterns(patterns);
+        self
+    }
+
+    pub fn exclude_transient_files(mut self, exclude: bool) -> Self {
+        self.config_builder = self.config_builder.exclude_transient_files(exclude);
+        self
+    }
+
+    pub fn normalize_unicode(mut self, enable: bool) -> Self {
+        self.config_builder = self.config_builder.normalize_unicode(enable);
+        self
+    }
+
+    pub fn compact_paths(mut self, enable: bool) -> Self {
+        self.config_builder = self.config_builder.compact_paths(enable);
+        self
+    }
+
+    pub fn fast_name_index(mut self, enable: bool) -> Self {
+        self.config_builder = self.config_builder.fast_name_index(enable);
+        self
+    }
+
+    pub fn extract_image_metadata(mut self, enable: bool) -> Self {
+        self.config_builder = self.config_builder.extract_image_metadata(enable);
+        self
+    }
+
+    pub fn detect_shebangs(mut self, enable: bool) -> Self {
+        self.config_builder = self.config_builder.detect_shebangs(enable);
+        self
+    }
+
+    pub fn symlink_store_roots(mut self, roots: Vec<PathBuf>) -> Self {
+        self.config_builder = self.config_builder.symlink_store_roots(roots);
+        self
+    }
+
+    pub fn extract_document_fields(mut self, enable: bool) -> Self {
+        self.config_builder = self.config_builder.extract_document_fields(enable);
+        self
+    }
+
+    pub fn record_ownership(mut self, enable: bool) -> Self {
+        self.config_builder = self.config_builder.record_ownership(enable);
+        self
+    }
+
+    pub fn progress_delivery(mut self, delivery: ProgressDelivery) -> Self {
+        self.config_builder = self.config_builder.progress_delivery(delivery);
+        self
+    }
+
+    pub fn progress_interval_ms(mut self, interval_ms: u64) -> Self {
+        self.config_builder = self.config_builder.progress_interval_ms(interval_ms);
+        self
+    }
+
+    pub fn fts_tokenizer(mut self, tokenizer: impl Into<String>) -> Self {
+        self.config_builder = self.config_builder.fts_tokenizer(tokenizer);
+        self
+    }
+
+    pub fn idle_threshold_secs(mut self, secs: u64) -> Self {
+        self.config_builder = self.config_builder.idle_threshold_secs(secs);
+        self
+    }
+
+    pub fn maintenance_slice_ms(mut self, slice_ms: u64) -> Self {
+        self.config_builder = self.config_builder.maintenance_slice_ms(slice_ms);
+        self
+    }
+
+    pub fn maintenance_window(mut self, window: MaintenanceWindow) -> Self {
+        self.config_builder = self.config_builder.maintenance_window(window);
+        self
+    }
+
+    pub fn disabled_maintenance_tasks(mut self, tasks: Vec<MaintenanceTask>) -> Self {
+        self.config_builder = self.config_builder.disabled_maintenance_tasks(tasks);
+        self
+    }
+
+    pub fn with_normalizer(mut self, normalizer: Arc<dyn TextNormalizer>) -> Self {
+        self.normalizer = Some(normalizer);
+        self
+    }
+
+    /// Stamps indexed entries with the time `clock` reads instead of the system time, e.g. a
+    /// fixed one so builds can be compared.
+    pub fn with_clock(mut self, clock: WallClock) -> Self {
+        self.wall_clock = Some(clock);
+        self
+    }
+
+    pub fn create_if_missing(mut self, create: bool) -> Self {
+        self.create_if_missing = create;
+        self
+    }
+
+    /// Keeps the index in memory; `index_path` and `create_if_missing` are then ignored.
+    pub fn in_memory(mut self, in_memory: bool) -> Self {
+        self.in_memory = in_memory;
+        self
+    }
+
+    pub fn build(self) -> Result<SearchEngine> {
+        let config = self.config_builder.try_build()?;
+        let normalizer = self
+            .normalizer
+            .unwrap_or_else(|| default_normalizer(config.normalize_unicode));
+
+        if self.in_memory {
+            let layout = SearchEngine::path_layout_for(&config);
+            let pool_sizes = SearchEngine::pool_sizes_for(&config);
+            let database = Database::in_memory_with_layout(pool_sizes, layout)?;
+            return SearchEngine::from_database(
+                database,
+                config,
+                normalizer,
+                self.wall_clock,
+                None,
+            );
+        }
+
+        let index_path = self.index_path.unwrap_or_else(|| config.index_path.clone());
+        if !self.create_if_missing {
+            SearchEngine::ensure_index_exists(&index_path)?;
+        }
+
+        SearchEngine::open(&index_path, config, normalizer, self.wall_clock)
+    }
+}
+
+impl Default for SearchEngineBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Tags are written into queries as `tag:a,b`, so they cannot hold whitespace or commas.
+fn validate_tag(tag: &str) -> Result<&str> {
+    let tag = tag.trim();
+    if tag.is_empty() || tag.contains(|c: char| c.is_whitespace() || c == ',') {
+        return Err(SearchError::InvalidQuery(format!("Invalid tag: '{}'", tag)));
+    }
+    Ok(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::error::{ErrorCode, ErrorReport};
+    use crate::core::types::MatchField;
+    use crate::filters::ExclusionSource;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_search_engine_creation() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("index.db");
+
+        let engine = SearchEngine::new(&index_path).unwrap();
+        assert!(!engine.is_watching());
+    }
+
+    #[test]
+    fn test_open_existing_missing_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("missing.db");
+
+        let err = SearchEngine::open_existing(&index_path).err().unwrap();
+        assert!(matches!(err, SearchError::IndexNotFound(_)));
+        assert!(err.to_string().contains(&index_path.display().to_string()));
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+
+        let result = SearchEngine::builder()
+            .index_path(&index_path)
+            .create_if_missing(false)
+            .build();
+        assert!(matches!(result, Err(SearchError::IndexNotFound(_))));
+        assert!(!index_path.exists());
+    }
+
+    #[test]
+    fn test_create_or_open_then_open_existing() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("notes.txt"), "hello").unwrap();
+        let index_path = temp_dir.path().join("index.db");
+
+        let engine = SearchEngine::create_or_open(&index_path).unwrap();
+        engine.index_directory(&root, None).unwrap();
+        assert!(index_path.is_file());
+        drop(engine);
+
+        let engine = SearchEngine::open_existing(&index_path).unwrap();
+        assert_eq!(engine.search("notes").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_search_engine_builder() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("index.db");
+
+        let engine = SearchEngine::builder()
+            .index_path(index_path)
+            .thread_count(4)
+            .enable_content_search(false)
+            .build()
+            .unwrap();
+
+        assert_eq!(engine.get_config().thread_count, 4);
+    }
+
+    #[test]
+    fn test_invalid_config_is_rejected_before_opening_the_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("index.db");
+
+        let config = SearchConfig {
+            fuzzy_threshold: 7.0,
+            cache_size: 0,
+            ..Default::default()
+        };
+        let err = SearchEngine::with_config(&index_path, config.clone()).err().unwrap();
+        assert!(matches!(err, SearchError::Configuration(_)), "{:?}", err);
+        assert!(err.to_string().contains("fuzzy_threshold = 7"));
+        assert!(err.to_string().contains("cache_size = 0"));
+        assert!(!index_path.exists());
+        assert!(SearchEngine::in_memory_with_config(config).is_err());
+
+        let err = SearchEngine::builder()
+            .index_path(&index_path)
+            .batch_size(0)
+            .build()
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("batch_size = 0"));
+    }
+
+    #[test]
+    fn test_deprecated_db_pool_size_maps_to_split_pools() {
+        let split: SearchConfig = toml::from_str("read_pool_size = 4\nwrite_pool_size = 1").unwrap();
+        assert_eq!(SearchEngine::pool_sizes_for(&split), PoolSizes::new(4, 1));
+
+        let legacy: SearchConfig = toml::from_str("db_pool_size = 10").unwrap();
+        assert_eq!(SearchEngine::pool_sizes_for(&legacy), PoolSizes::new(9, 1));
+
+        let tiny: SearchConfig = toml::from_str("db_pool_size = 1").unwrap();
+        assert_eq!(SearchEngine::pool_sizes_for(&tiny), PoolSizes::new(1, 1));
+        assert_eq!(SearchEngine::pool_sizes_for(&SearchConfig::default()), PoolSizes::new(8, 2));
+    }
+
+    #[test]
+    fn test_indexing_and_search() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("data");
+        fs::create_dir(&root).unwrap();
+
+        fs::write(root.join("test1.txt"), "content1").unwrap();
+        fs::write(root.join("test2.txt"), "content2").unwrap();
+
+        let index_path = temp_dir.path().join("index.db");
+        let engine = SearchEngine::new(&index_path).unwrap();
+
+        let count = engine.index_directory(&root, None).unwrap();
+        assert!(count > 0);
+
+        let results = engine.search("test").unwrap();
+        assert!(!results.is_empty());
+    }
+
+    #[test]
+    fn test_transient_files_toggle() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("data");
+        fs::create_dir(&root).unwrap();
+
+        fs::write(root.join("notes.txt"), "keep").unwrap();
+        fs::write(root.join("notes.txt.swp"), "swap").unwrap();
+        fs::write(root.join("notes.txt~"), "backup").unwrap();
+
+        let engine = SearchEngine::new(temp_dir.path().join("default.db")).unwrap();
+        assert_eq!(engine.index_directory(&root, None).unwrap(), 1);
+
+        let engine = SearchEngine::builder()
+            .index_path(temp_dir.path().join("disabled.db"))
+            .exclude_transient_files(false)
+            .build()
+            .unwrap();
+        assert_eq!(engine.index_directory(&root, None).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_inclusion_patterns_combine_with_exclusions() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("repo");
+        fs::create_dir_all(root.join("src/vendor")).unwrap();
+        fs::write(root.join("src/lib.rs"), "").unwrap();
+        fs::write(root.join("src/vendor/dep.rs"), "").unwrap();
+        fs::write(root.join("config.toml"), "").unwrap();
+        fs::write(root.join("logo.png"), "").unwrap();
+
+        let index_path = temp_dir.path().join("index.db");
+        let engine = SearchEngine::builder()
+            .index_path(&index_path)
+            .exclusion_patterns(vec!["**/vendor/**".to_string()])
+            .inclusion_patterns(vec!["*.rs".to_string(), "*.toml".to_string()])
+            .build()
+            .unwrap();
+        assert_eq!(engine.index_directory(&root, None).unwrap(), 2);
+        assert_eq!(
+            engine.filter_decision(root.join("src/vendor/dep.rs")),
+            FilterDecision::Excluded(ExclusionSource::Rule)
+        );
+        assert_eq!(engine.filter_decision(root.join("logo.png")), FilterDecision::NotIncluded);
+
+        // A stored inclusion replaces the configured ones but keeps the configured exclusions.
+        engine.add_inclusion_pattern("*.png".to_string()).unwrap();
+        drop(engine);
+        let engine = SearchEngine::builder()
+            .index_path(&index_path)
+            .exclusion_patterns(vec!["**/vendor/**".to_string()])
+            .inclusion_patterns(vec!["*.rs".to_string()])
+            .build()
+            .unwrap();
+        assert!(engine.filter_decision(root.join("logo.png")).is_included());
+        assert_eq!(engine.filter_decision(root.join("src/lib.rs")), FilterDecision::NotIncluded);
+        assert!(!engine.filter_decision(root.join("src/vendor/x.png")).is_included());
+    }
+
+    #[test]
+    fn test_type_dir_finds_indexed_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("repo");
+        fs::create_dir_all(root.join("src/bin")).unwrap();
+        fs::write(root.join("src/main.rs"), "").unwrap();
+        fs::write(root.join("src_notes.txt"), "").unwrap();
+
+        let engine = SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .index_directories(true)
+            .build()
+            .unwrap();
+        engine.index_directory(&root, None).unwrap();
+
+        let results = engine.search("src type:dir").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].file.path, root.join("src"));
+        assert!(results[0].file.is_directory);
+
+        let names: Vec<String> = engine
+            .search("src type:file")
+            .unwrap()
+            .into_iter()
+            .map(|r| r.file.name)
+            .collect();
+        assert_eq!(names, vec!["src_notes.txt"]);
+    }
+
+    #[test]
+    fn test_watch_recovers_after_root_recreated() {
+        use crate::watcher::WatchState;
+        use std::time::Duration;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("build");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("before.txt"), "before").unwrap();
+
+        let mut engine = SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+        engine.index_directory(&root, None).unwrap();
+        let options = WatchOptions {
+            initial_scan: false,
+            ..WatchOptions::default()
+        };
+        engine.start_watching_with_options(&root, options).unwrap();
+        let events = engine.subscribe_watch_events().unwrap();
+        // Deleting and recreating files also reports the changes applied to the index.
+        let next_state_change = |timeout| loop {
+            match events.recv_timeout(timeout).unwrap() {
+                WatchEvent::IndexChanged { .. } => continue,
+                other => break other,
+            }
+        };
+        assert!(engine.is_watching());
+        assert!(engine.watch_status().unwrap().initial_scan_state.is_none());
+
+        fs::remove_dir_all(&root).unwrap();
+        match next_state_change(Duration::from_secs(5)) {
+            WatchEvent::Degraded { .. } => {}
+            other => panic!("expected degraded event, got {:?}", other),
+        }
+        assert_eq!(engine.watch_status().unwrap().state, WatchState::Degraded);
+        assert!(!engine.is_watching());
+
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("during_gap.txt"), "missed").unwrap();
+        match next_state_change(Duration::from_secs(10)) {
+            WatchEvent::Recovered { caught_up, .. } => assert!(caught_up > 0),
+            other => panic!("expected recovered event, got {:?}", other),
+        }
+        assert_eq!(engine.watch_status().unwrap().state, WatchState::Healthy);
+
+        let results = engine.search("during_gap").unwrap();
+        assert_eq!(results.len(), 1);
+
+        engine.stop_watching().unwrap();
+    }
+
+    #[test]
+    fn test_initial_scan_indexes_existing_files() {
+        use crate::watcher::InitialScanState;
+        use std::time::{Duration, Instant};
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("inbox");
+        fs::create_dir(&root).unwrap();
+        for i in 0..2000 {
+            fs::write(root.join(format!("existing{}.txt", i)), "x").unwrap();
+        }
+
+        let config = SearchConfig {
+            watch_debounce_ms: 0,
+            ..SearchConfig::default()
+        };
+        let mut engine =
+            SearchEngine::with_config(temp_dir.path().join("index.db"), config).unwrap();
+        engine.start_watching(&root).unwrap();
+        fs::write(root.join("arrived_late.txt"), "late").unwrap();
+
+        let wait_until = |done: &dyn Fn() -> bool| {
+            let deadline = Instant::now() + Duration::from_secs(20);
+            while !done() {
+                assert!(Instant::now() < deadline, "timed out waiting for the watch");
+                std::thread::sleep(Duration::from_millis(50));
+            }
+        };
+        wait_until(&|| {
+            matches!(
+                engine.watch_status().unwrap().initial_scan_state,
+                Some(InitialScanState::Complete)
+            )
+        });
+        wait_until(&|| engine.search("arrived_late").unwrap().len() == 1);
+        // Give the queued create event time to be applied on top of the scan.
+        std::thread::sleep(Duration::from_millis(500));
+
+        assert_eq!(engine.search("existing1999").unwrap().len(), 1);
+        assert_eq!(engine.search("arrived_late").unwrap().len(), 1);
+        assert_eq!(engine.get_stats().unwrap().total_files, 2001);
+
+        engine.stop_watching().unwrap();
+    }
+
+    #[test]
+    fn test_content_search_expands_shared_blob() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("vendor");
+        for i in 0..50 {
+            let dir = root.join(format!("crate{}", i));
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("LICENSE"), "Permission is hereby granted, free of charge").unwrap();
+        }
+
+        let engine = SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .enable_content_search(true)
+            .build()
+            .unwrap();
+        engine.index_directory(&root, None).unwrap();
+
+        let results = engine.search("hereby scope:content").unwrap();
+        assert_eq!(results.len(), 50);
+    }
+
+    #[test]
+    fn test_name_and_content_terms_must_both_match() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("data");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("widget-config.txt"), "no match here").unwrap();
+        fs::write(root.join("widget-client.txt"), "client timeout reached").unwrap();
+        fs::write(root.join("notes.txt"), "connection timeout").unwrap();
+
+        let engine = SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .enable_content_search(true)
+            .build()
+            .unwrap();
+        engine.index_directory(&root, None).unwrap();
+
+        assert_eq!(names(&engine, "name:widget content:timeout"), vec!["widget-client.txt"]);
+        assert_eq!(names(&engine, "content:timeout widget"), vec!["widget-client.txt"]);
+        assert_eq!(
+            names(&engine, "widget OR notes content:timeout"),
+            vec!["notes.txt", "widget-client.txt"]
+        );
+        assert_eq!(names(&engine, "widget scope:path content:timeout"), vec!["widget-client.txt"]);
+        assert!(names(&engine, "name:widget content:missing").is_empty());
+        assert_eq!(names(&engine, "content:timeout").len(), 2);
+    }
+
+    #[test]
+    fn test_capabilities_follow_config_and_gate_content_search() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("data");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("notes.txt"), "quarterly figures").unwrap();
+
+        for (content, fuzzy, access) in [(false, true, true), (true, false, false)] {
+            let engine = SearchEngine::builder()
+                .index_path(temp_dir.path().join(format!("index-{}.db", content)))
+                .enable_content_search(content)
+                .enable_fuzzy_search(fuzzy)
+                .enable_access_tracking(access)
+                .build()
+                .unwrap();
+            engine.index_directory(&root, None).unwrap();
+
+            let capabilities = engine.capabilities();
+            assert_eq!(capabilities.content_search, content);
+            assert_eq!(capabilities.fuzzy_search, fuzzy);
+            assert_eq!(capabilities.access_tracking, access);
+            assert!(!capabilities.read_only);
+            assert!(!capabilities.watch_backend.is_empty());
+
+            let result = engine.search("quarterly scope:content");
+            if content {
+                assert_eq!(result.unwrap().len(), 1);
+            } else {
+                assert!(matches!(result, Err(SearchError::FeatureUnavailable(_))));
+                assert_eq!(engine.search("notes").unwrap().len(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_diacritics_folding_both_directions() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("data");
+        fs::create_dir(&root).unwrap();
+
+        fs::write(root.join("München-Fotos.jpg"), "photo").unwrap();
+        fs::write(root.join("cafe.txt"), "plain").unwrap();
+
+        let index_path = temp_dir.path().join("index.db");
+        let engine = SearchEngine::new(&index_path).unwrap();
+        engine.index_directory(&root, None).unwrap();
+
+        let results = engine.search("munchen").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].file.name, "München-Fotos.jpg");
+
+        let results = engine.search("café").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].file.name, "cafe.txt");
+    }
+
+    #[test]
+    fn test_normalize_unicode_opt_out() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("data");
+        fs::create_dir(&root).unwrap();
+
+        fs::write(root.join("München-Fotos.jpg"), "photo").unwrap();
+
+        let engine = SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .normalize_unicode(false)
+            .build()
+            .unwrap();
+        engine.index_directory(&root, None).unwrap();
+
+        assert!(engine.search("munchen").unwrap().is_empty());
+        assert_eq!(engine.search("münchen").unwrap().len(), 1);
+    }
+
+    struct TransliteratingNormalizer;
+
+    impl TextNormalizer for TransliteratingNormalizer {
+        fn normalize<'a>(&self, s: &'a str) -> std::borrow::Cow<'a, str> {
+            s.chars()
+                .map(|c| match c {
+                    'п' | 'П' => "p".to_string(),
+                    'р' => "r".to_string(),
+                    'и' => "i".to_string(),
+                    'в' => "v".to_string(),
+                    'е' => "e".to_string(),
+                    'т' => "t".to_string(),
+                    other => other.to_lowercase().to_string(),
+                })
+                .collect::<String>()
+                .into()
+        }
+    }
+
+    #[test]
+    fn test_custom_normalizer_is_honored() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("data");
+        fs::create_dir(&root).unwrap();
+
+        fs::write(root.join("Привет.txt"), "hello").unwrap();
+
+        let engine = SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .with_normalizer(Arc::new(TransliteratingNormalizer))
+            .build()
+            .unwrap();
+        engine.index_directory(&root, None).unwrap();
+
+        let results = engine.search("privet").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].file.name, "Привет.txt");
+    }
+
+    #[test]
+    fn test_exports_leave_out_sensitive_entries_unless_asked() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("data");
+        fs::create_dir(&root).unwrap();
+        for name in ["notes.txt", "server.pem", "deploy.key"] {
+            fs::write(root.join(name), name).unwrap();
+        }
+
+        let engine = SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+        engine.index_directory(&root, None).unwrap();
+        fs::remove_file(root.join("server.pem")).unwrap();
+        engine.update_index(&root, None).unwrap();
+
+        let export = |include_sensitive: bool| {
+            let mut feed = Vec::new();
+            let export = engine
+                .export_changes(DateTime::<Utc>::UNIX_EPOCH, &mut feed, include_sensitive)
+                .unwrap();
+            let records: Vec<ChangeRecord> = feed
+                .split(|b| *b == b'\n')
+                .filter(|line| !line.is_empty())
+                .map(|line| serde_json::from_slice(line).unwrap())
+                .collect();
+            (export.omitted_sensitive, records)
+        };
+
+        let (omitted, records) = export(false);
+        assert_eq!(omitted, 2);
+        assert_eq!(records.len(), 2);
+        assert!(matches!(&records[0], ChangeRecord::Upsert { entry } if entry.name == "notes.txt"));
+        assert!(matches!(records[1], ChangeRecord::Cursor { omitted_sensitive: 2, .. }));
+
+        let (omitted, records) = export(true);
+        assert_eq!(omitted, 0);
+        assert_eq!(records.len(), 4);
+        assert!(matches!(
+            &records[0],
+            ChangeRecord::Delete { path, .. } if path.ends_with("server.pem")
+        ));
+    }
+
+    fn apply_change_feed(state: &mut std::collections::HashMap<PathBuf, u64>, feed: &[u8]) {
+        for line in feed.split(|b| *b == b'\n').filter(|l| !l.is_empty()) {
+            match serde_json::from_slice::<ChangeRecord>(line).unwrap() {
+                ChangeRecord::Upsert { entry } => {
+                    state.insert(entry.path, entry.size);
+                }
+                ChangeRecord::Delete { path, .. } => {
+                    state.remove(&path);
+                }
+                ChangeRecord::Cursor { .. } => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_export_changes_since_reproduces_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("data");
+        fs::create_dir(&root).unwrap();
+
+        fs::write(root.join("keep.txt"), "keep").unwrap();
+        fs::write(root.join("modify.txt"), "before").unwrap();
+        fs::write(root.join("delete.txt"), "delete").unwrap();
+
+        let engine = SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+        engine.index_directory(&root, None).unwrap();
+
+        let mut full = Vec::new();
+        let cursor = engine
+            .export_changes_since(DateTime::<Utc>::UNIX_EPOCH, &mut full)
+            .unwrap();
+
+        let mut replica = std::collections::HashMap::new();
+        apply_change_feed(&mut replica, &full);
+        assert_eq!(replica.len(), 3);
+
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        fs::write(root.join("added.txt"), "added").unwrap();
+        fs::write(root.join("modify.txt"), "after modification").unwrap();
+        fs::remove_file(root.join("delete.txt")).unwrap();
+        engine.update_index(&root, None).unwrap();
+
+        let mut changes = Vec::new();
+        let next_cursor = engine.export_changes_since(cursor, &mut changes).unwrap();
+        assert!(next_cursor >= cursor);
+        apply_change_feed(&mut replica, &changes);
+
+        let mut current = std::collections::HashMap::new();
+        let mut now = Vec::new();
+        engine
+            .export_changes_since(DateTime::<Utc>::UNIX_EPOCH, &mut now)
+            .unwrap();
+        apply_change_feed(&mut current, &now);
+
+        assert_eq!(replica, current);
+        assert!(replica.contains_key(&root.join("added.txt")));
+        assert!(!replica.contains_key(&root.join("delete.txt")));
+        assert_eq!(replica[&root.join("modify.txt")], "after modification".len() as u64);
+    }
+
+    #[test]
+    fn test_stats() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("data");
+        fs::create_dir(&root).unwrap();
+
+        fs::write(root.join("file.txt"), "content").unwrap();
+
+        let index_path = temp_dir.path().join("index.db");
+        let engine = SearchEngine::new(&index_path).unwrap();
+
+        engine.index_directory(&root, None).unwrap();
+
+        let stats = engine.get_stats().unwrap();
+        assert!(stats.total_files > 0);
+    }
+
+    #[test]
+    fn test_name_index_answers_searches_and_survives_reopening() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("data");
+        fs::create_dir(&root).unwrap();
+        for name in ["report.pdf", "Report-final.PDF", "notes.txt"] {
+            fs::write(root.join(name), "content").unwrap();
+        }
+
+        let index_path = temp_dir.path().join("index.db");
+        let open = || {
+            SearchEngine::builder()
+                .index_path(&index_path)
+                .fast_name_index(true)
+                .build()
+                .unwrap()
+        };
+        let engine = open();
+        engine.index_directory(&root, None).unwrap();
+        engine.refresh_name_index().unwrap();
+        assert!(!engine.refresh_name_index().unwrap());
+
+        assert_eq!(engine.search("report").unwrap().len(), 2);
+        assert_eq!(engine.metrics().name_index_hits, 1);
+        let usage = engine.get_stats().unwrap().name_index.unwrap();
+        assert!(usage.current);
+        assert!(usage.entries >= 3);
+        assert!(NameIndex::sidecar_for(&index_path).exists());
+        drop(engine);
+
+        // The saved copy is still current, so the reopened engine answers from it at once.
+        let engine = open();
+        assert!(engine.get_stats().unwrap().name_index.unwrap().current);
+        assert_eq!(engine.search("report").unwrap().len(), 2);
+        assert_eq!(engine.metrics().name_index_hits, 1);
+
+        let plain = SearchEngine::new(&index_path).unwrap();
+        assert!(plain.get_stats().unwrap().name_index.is_none());
+        assert!(!plain.refresh_name_index().unwrap());
+    }
+
+    #[test]
+    fn test_index_with_manifest_applies_per_root_options() {
+        let temp_dir = TempDir::new().unwrap();
+        for name in ["alpha", "beta"] {
+            let root = temp_dir.path().join(name);
+            fs::create_dir_all(root.join("nested")).unwrap();
+            fs::write(root.join("app.log"), "log").unwrap();
+            fs::write(root.join("readme.txt"), "readme").unwrap();
+            fs::write(root.join("nested").join("deep.txt"), "deep").unwrap();
+        }
+
+        let manifest_path = temp_dir.path().join("manifest.toml");
+        fs::write(
+            &manifest_path,
+            r#"
+parallel = 2
+
+[[root]]
+path = "alpha"
+exclusions = ["*.log"]
+max_depth = 1
+content_search = true
+
+[[root]]
+path = "beta"
+"#,
+        )
+        .unwrap();
+
+        let index_path = temp_dir.path().join("index.db");
+        let engine = SearchEngine::new(&index_path).unwrap();
+        let manifest = IndexManifest::from_file(&manifest_path).unwrap();
+
+        let reports = engine.index_with_manifest(manifest, None).unwrap();
+        assert_eq!(reports.len(), 2);
+        assert!(reports.iter().all(|report| report.is_success()));
+        assert_eq!(reports[0].indexed, 1);
+        assert_eq!(reports[1].indexed, 3);
+
+        let logs = engine.search("app").unwrap();
+        assert_eq!(logs.len(), 1);
+        assert!(logs[0].file.path.starts_with(temp_dir.path().join("beta")));
+
+        let deep = engine.search("deep").unwrap();
+        assert_eq!(deep.len(), 1);
+        assert!(deep[0].file.path.starts_with(temp_dir.path().join("beta")));
+
+        let roots = engine.indexed_roots().unwrap();
+        assert_eq!(roots.len(), 2);
+        assert_eq!(roots[0].options.exclusions, vec!["*.log".to_string()]);
+        assert_eq!(roots[0].options.max_depth, Some(1));
+        assert_eq!(roots[0].options.content_search, Some(true));
+        assert_eq!(roots[0].file_count, 1);
+        assert!(roots[1].options.exclusions.is_empty());
+        assert_eq!(roots[1].options.max_depth, None);
+        assert_eq!(roots[1].options.content_search, Some(false));
+        assert_eq!(roots[1].options.follow_symlinks, Some(false));
+    }
+
+    #[test]
+    fn test_index_with_manifest_rejects_missing_root() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("index.db");
+        let engine = SearchEngine::new(&index_path).unwrap();
+
+        let missing = temp_dir.path().join("missing");
+        let manifest = IndexManifest {
+            roots: vec![ManifestRoot::new(&missing)],
+            parallel: None,
+        };
+
+        let err = engine.index_with_manifest(manifest, None).unwrap_err();
+        assert!(err.to_string().contains(&missing.display().to_string()));
+        assert!(engine.indexed_roots().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_update_all_covers_every_indexed_root() {
+        let temp_dir = TempDir::new().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+        let err = engine.update_all(None).unwrap_err();
+        assert!(err.to_string().contains("no indexed roots"));
+
+        let roots: Vec<PathBuf> = ["docs", "src"]
+            .iter()
+            .map(|name| temp_dir.path().join(name))
+            .collect();
+        for root in &roots {
+            fs::create_dir(root).unwrap();
+            fs::write(root.join("old.txt"), "old").unwrap();
+            engine.index_directory(root, None).unwrap();
+        }
+        fs::write(roots[0].join("new.txt"), "new").unwrap();
+        fs::write(roots[1].join("new.txt"), "new").unwrap();
+        fs::write(roots[1].join("newer.txt"), "newer").unwrap();
+        fs::remove_file(roots[1].join("old.txt")).unwrap();
+
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let callback_seen = Arc::clone(&seen);
+        let callback: RootProgressCallback = Arc::new(move |root, _| {
+            let mut seen = callback_seen.lock().unwrap();
+            if seen.last().map(PathBuf::as_path) != Some(root) {
+                seen.push(root.to_path_buf());
+            }
+        });
+
+        let results = engine.update_all(Some(callback)).unwrap();
+        let mut counts: Vec<(PathBuf, usize, usize)> = results
+            .into_iter()
+            .map(|(path, result)| {
+                let stats = result.unwrap();
+                (path, stats.added, stats.removed)
+            })
+            .collect();
+        counts.sort();
+        assert_eq!(
+            counts,
+            vec![(roots[0].clone(), 1, 0), (roots[1].clone(), 2, 1)]
+        );
+        let mut seen = seen.lock().unwrap().clone();
+        seen.sort();
+        assert_eq!(seen, roots);
+    }
+
+    #[test]
+    fn test_update_roots_continues_or_stops_at_a_failure() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("data");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("file.txt"), "content").unwrap();
+        let missing = temp_dir.path().join("missing");
+
+        let engine = SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+        let roots = vec![missing.clone(), root.clone()];
+
+        let results = engine.update_roots(&roots, true, None).unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].1.is_err());
+        assert_eq!(results[1].1.as_ref().unwrap().added, 1);
+
+        fs::write(root.join("other.txt"), "content").unwrap();
+        let results = engine.update_roots(&roots, false, None).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, missing);
+        assert!(results[0].1.is_err());
+        assert_eq!(engine.get_stats().unwrap().total_files, 1);
+    }
+
+    #[test]
+    fn test_saved_search_listing_pages_and_records_runs() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("downloads");
+        fs::create_dir(&root).unwrap();
+        for (name, size) in [("small.log", 1), ("large.log", 300), ("medium.log", 20)] {
+            fs::write(root.join(name), "x".repeat(size)).unwrap();
+        }
+
+        let engine = SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+        engine.index_directory(&root, None).unwrap();
+        engine
+            .save_search("logs", "log ext:log", Some(ListingSort::SizeDesc))
+            .unwrap();
+        assert!(engine.save_search("bad/name", "ext:log", None).is_err());
+
+        let page = Pagination {
+            limit: Some(2),
+            offset: 1,
+        };
+        let listing = engine.run_saved_as_listing("logs", page).unwrap();
+        let names: Vec<_> = listing.entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["medium.log", "small.log"]);
+        assert_eq!(listing.total, 3);
+        assert_eq!(listing.sort, ListingSort::SizeDesc);
+
+        let saved = engine.saved_search("logs").unwrap().unwrap();
+        assert_eq!(saved.last_result_count, Some(3));
+        assert!(saved.last_run_at.is_some());
+
+        assert!(engine.delete_saved_search("logs").unwrap());
+        assert!(matches!(
+            engine.run_saved_as_listing("logs", Pagination::default()),
+            Err(SearchError::SavedSearchNotFound(_))
+        ));
+    }
+
+    fn write_scripts(root: &Path) {
+        for (name, body) in [
+            ("job-deploy", "#!/usr/bin/env bash\nset -e\n"),
+            ("job-tests", "#!/usr/bin/python3 -u\nimport sys\n"),
+            ("job-backup", "#! /usr/bin/perl -w\n"),
+            ("job-serve", "#!/usr/bin/env node\n"),
+            ("job-notes", "remember to rotate keys\n"),
+            ("job-tool.py", "print('hi')\n"),
+            ("job-readme.md", "#!/bin/sh\n"),
+        ] {
+            fs::write(root.join(name), body).unwrap();
+        }
+    }
+
+    fn names(engine: &SearchEngine, query: &str) -> Vec<String> {
+        let mut names: Vec<String> = engine
+            .search(query)
+            .unwrap()
+            .into_iter()
+            .map(|result| result.file.name)
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_shebang_scripts_join_code_category_and_languages() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("bin");
+        fs::create_dir(&root).unwrap();
+        write_scripts(&root);
+
+        let engine = SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+        engine.index_directory(&root, None).unwrap();
+
+        assert_eq!(
+            names(&engine, "job ext:code"),
+            vec!["job-backup", "job-deploy", "job-serve", "job-tests", "job-tool.py"]
+        );
+        assert_eq!(names(&engine, "job ext:code,md").len(), 6);
+        assert_eq!(names(&engine, "job lang:python"), vec!["job-tests", "job-tool.py"]);
+        assert_eq!(names(&engine, "job lang:bash,perl"), vec!["job-backup", "job-deploy"]);
+        assert_eq!(names(&engine, "job lang:node"), vec!["job-serve"]);
+        assert!(names(&engine, "job lang:sh").is_empty());
+
+        let id = engine.search("job-deploy").unwrap()[0].file.id.unwrap();
+        let script = &engine.scripts(&[id]).unwrap()[&id];
+        assert_eq!((script.interpreter.as_str(), script.language.as_str()), ("bash", "bash"));
+    }
+
+    #[test]
+    fn test_extension_search_ignores_case() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("data");
+        fs::create_dir(&root).unwrap();
+        for name in ["img_0001.JPG", "img_0002.jpg", "img_0003.Jpg", "img_0004.png"] {
+            fs::write(root.join(name), "").unwrap();
+        }
+
+        let engine = SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+        engine.index_directory(&root, None).unwrap();
+
+        let jpgs = vec!["img_0001.JPG", "img_0002.jpg", "img_0003.Jpg"];
+        assert_eq!(names(&engine, "img ext:jpg"), jpgs);
+        assert_eq!(names(&engine, "img ext:JPG"), jpgs);
+    }
+
+    #[test]
+    fn test_shebang_detection_follows_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("bin");
+        fs::create_dir(&root).unwrap();
+        write_scripts(&root);
+
+        let quick = SearchEngine::new(temp_dir.path().join("quick.db")).unwrap();
+        quick.index_quick(&root, None).unwrap();
+        assert_eq!(names(&quick, "job lang:perl"), vec!["job-backup"]);
+
+        let disabled = SearchEngine::builder()
+            .index_path(temp_dir.path().join("off.db"))
+            .detect_shebangs(false)
+            .build()
+            .unwrap();
+        disabled.index_directory(&root, None).unwrap();
+        assert_eq!(names(&disabled, "job ext:code"), vec!["job-tool.py"]);
+        assert_eq!(names(&disabled, "job lang:python"), vec!["job-tool.py"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_ownership_is_recorded_when_enabled() {
+        use crate::core::types::Ownership;
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("data");
+        fs::create_dir(&root).unwrap();
+        fs::set_permissions(&root, fs::Permissions::from_mode(0o750)).unwrap();
+        let secret = root.join("secret.txt");
+        fs::write(&secret, "hush").unwrap();
+        fs::set_permissions(&secret, fs::Permissions::from_mode(0o600)).unwrap();
+
+        let off = SearchEngine::new(temp_dir.path().join("off.db")).unwrap();
+        off.index_directory(&root, None).unwrap();
+        assert!(!off.has_permissions().unwrap());
+
+        let engine = SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .record_ownership(true)
+            .build()
+            .unwrap();
+        engine.index_directory(&root, None).unwrap();
+        assert!(engine.has_permissions().unwrap());
+
+        let id = engine.search("secret").unwrap()[0].file.id.unwrap();
+        let permissions = engine.permissions(&[id]).unwrap()[&id];
+        let metadata = fs::metadata(&secret).unwrap();
+        let owner = Ownership {
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            mode: 0o600,
+        };
+        assert_eq!(permissions.entry, owner);
+        assert_eq!(permissions.parent.unwrap().mode, 0o750);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_store_aliases_share_one_indexed_copy() {
+        const PROJECTS: usize = 20;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("workspace");
+        let store = root.join("store");
+        let package = store.join("lodash@4.17.21");
+        fs::create_dir_all(package.join("fp")).unwrap();
+        fs::write(package.join("index.js"), "module.exports = lodashUniqueMarker").unwrap();
+        fs::write(package.join("fp/map.js"), "module.exports = fpMap").unwrap();
+        fs::write(store.join("readme.md"), "store readme").unwrap();
+        for i in 0..PROJECTS {
+            let modules = root.join(format!("app-{}/node_modules", i));
+            fs::create_dir_all(&modules).unwrap();
+            std::os::unix::fs::symlink(&package, modules.join("lodash")).unwrap();
+        }
+        std::os::unix::fs::symlink(store.join("readme.md"), root.join("STORE.md")).unwrap();
+
+        let engine = SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .enable_content_search(true)
+            .symlink_store_roots(vec![store.clone(), temp_dir.path().join("missing")])
+            .build()
+            .unwrap();
+        engine.index_directory(&root, None).unwrap();
+
+        // Only the store's own files are read; every link is found by its logical path.
+        let content = engine.search("lodashUniqueMarker scope:content").unwrap();
+        assert_eq!(content.len(), 1);
+        assert_eq!(content[0].file.path, package.join("index.js"));
+
+        let logical = root.join("app-7/node_modules/lodash/fp/map.js");
+        let found = engine.search("map.js").unwrap();
+        assert_eq!(found.len(), PROJECTS + 1);
+        let alias = found.iter().find(|r| r.file.path == logical).unwrap();
+        assert!(alias.file.is_symlink);
+        let id = alias.file.id.unwrap();
+        let physical = dunce::canonicalize(package.join("fp/map.js")).unwrap();
+        assert_eq!(engine.link_targets(&[id]).unwrap()[&id], physical);
+
+        let stats = engine.get_stats().unwrap();
+        assert_eq!(stats.store_aliases, PROJECTS * 2 + 1);
+        assert_eq!(stats.total_files, 3 + PROJECTS * 2 + 1);
+        assert_eq!(stats.indexed_files, 3);
+        let store_size = ["lodash@4.17.21/index.js", "lodash@4.17.21/fp/map.js", "readme.md"]
+            .iter()
+            .map(|file| fs::metadata(store.join(file)).unwrap().len())
+            .sum::<u64>();
+        assert_eq!(stats.total_size, store_size);
+
+        // Updates keep new links as aliases too.
+        let modules = root.join("app-new/node_modules");
+        fs::create_dir_all(&modules).unwrap();
+        std::os::unix::fs::symlink(&package, modules.join("lodash")).unwrap();
+        engine.update_index(&root, None).unwrap();
+        let stats = engine.get_stats().unwrap();
+        assert_eq!(stats.store_aliases, (PROJECTS + 1) * 2 + 1);
+        assert_eq!(stats.indexed_files, 3);
+        assert_eq!(engine.search("lodashUniqueMarker scope:content").unwrap().len(), 1);
+    }
+
+    #[cfg(feature = "document-fields")]
+    fn write_documents(root: &Path) {
+        for (name, body) in [
+            (
+                "q3.eml",
+                "From: =?UTF-8?Q?Acme_Buchhaltung?= <billing@acme.com>\r\n\
+                 To: me@example.org\r\n\
+                 Subject: =?UTF-8?B?SW52b2ljZSDDnGJlcnNpY2h0?=\r\n\
+                 Date: Tue, 14 Mar 2023 09:30:00 +0100\r\n\r\nPlease pay.\r\n",
+            ),
+            (
+                "lunch.eml",
+                "From: friend@example.org\r\nSubject: Lunch?\r\n\r\nnoon\r\n",
+            ),
+            (
+                "offer.html",
+                "<html><head><title>Acme &amp; Sons &mdash; Invoice Portal</title></head>\
+                 <body>pay here</body></html>",
+            ),
+            ("invoice.txt", "Subject: invoice\n"),
+        ] {
+            fs::write(root.join(name), body).unwrap();
+        }
+    }
+
+    #[cfg(feature = "document-fields")]
+    #[test]
+    fn test_document_fields_filter_and_search() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("mail");
+        fs::create_dir(&root).unwrap();
+        write_documents(&root);
+
+        let engine = SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .enable_content_search(true)
+            .build()
+            .unwrap();
+        engine.index_directory(&root, None).unwrap();
+
+        assert_eq!(names(&engine, "subject:invoice"), vec!["q3.eml"]);
+        assert_eq!(names(&engine, "subject:ÜBERSICHT"), vec!["q3.eml"]);
+        assert_eq!(names(&engine, "from:acme.com"), vec!["q3.eml"]);
+        assert_eq!(names(&engine, "from:example.org subject:lunch"), vec!["lunch.eml"]);
+        assert!(names(&engine, "from:acme.com subject:lunch").is_empty());
+        assert_eq!(names(&engine, "title:acme"), vec!["offer.html"]);
+        assert_eq!(names(&engine, "q3 from:acme"), vec!["q3.eml"]);
+        assert_eq!(names(&engine, "übersicht scope:content"), vec!["q3.eml"]);
+        assert_eq!(names(&engine, "pay scope:content subject:invoice"), vec!["q3.eml"]);
+
+        let id = engine.search("offer").unwrap()[0].file.id.unwrap();
+        let fields = &engine.document_fields(&[id]).unwrap()[&id];
+        assert_eq!(fields[0], DocumentField::new("title", "Acme & Sons — Invoice Portal"));
+
+        // The Date header is kept as a field; the file keeps its own modification time.
+        let id = engine.search("q3").unwrap()[0].file.id.unwrap();
+        let fields = &engine.document_fields(&[id]).unwrap()[&id];
+        assert!(fields.contains(&DocumentField::new("date", "2023-03-14T08:30:00+00:00")));
+        let modified = engine.search("q3").unwrap()[0].file.modified_at.unwrap();
+        assert!(modified.timestamp() > 1_700_000_000);
+    }
+
+    #[cfg(feature = "document-fields")]
+    #[test]
+    fn test_document_fields_follow_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("mail");
+        fs::create_dir(&root).unwrap();
+        write_documents(&root);
+
+        let quick = SearchEngine::new(temp_dir.path().join("quick.db")).unwrap();
+        quick.index_quick(&root, None).unwrap();
+        assert_eq!(names(&quick, "subject:invoice"), vec!["q3.eml"]);
+
+        let disabled = SearchEngine::builder()
+            .index_path(temp_dir.path().join("off.db"))
+            .enable_content_search(true)
+            .extract_document_fields(false)
+            .build()
+            .unwrap();
+        disabled.index_directory(&root, None).unwrap();
+        assert!(names(&disabled, "subject:invoice").is_empty());
+        // Undecoded headers are still searchable as plain content.
+        assert_eq!(names(&disabled, "lunch scope:content"), vec!["lunch.eml"]);
+    }
+
+    #[test]
+    fn test_tagged_result_set_follows_the_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("docs");
+        fs::create_dir(&root).unwrap();
+        for name in ["invoice-jan.pdf", "invoice-feb.pdf", "notes.txt"] {
+            fs::write(root.join(name), name).unwrap();
+        }
+
+        let engine = SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+        engine.index_directory(&root, None).unwrap();
+
+        let ids: Vec<i64> = engine
+            .search("invoice")
+            .unwrap()
+            .iter()
+            .filter_map(|result| result.file.id)
+            .collect();
+        assert_eq!(engine.tag_files(&ids, "tax-2023").unwrap(), 2);
+        assert!(engine.tag_files(&ids, "tax 2023").is_err());
+        assert_eq!(engine.list_tags().unwrap(), vec![("tax-2023".to_string(), 2)]);
+        assert_eq!(engine.search("tag:tax-2023").unwrap().len(), 2);
+        assert_eq!(engine.search("feb tag:tax-2023").unwrap().len(), 1);
+
+        fs::remove_file(root.join("invoice-jan.pdf")).unwrap();
+        engine.update_index(&root, None).unwrap();
+        let tagged = engine.find_by_tag("tax-2023", 10).unwrap();
+        assert_eq!(tagged.len(), 1);
+        assert_eq!(tagged[0].name, "invoice-feb.pdf");
+
+        assert_eq!(engine.untag_files(&ids, "tax-2023").unwrap(), 1);
+        assert!(engine.list_tags().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_in_memory_engine_indexes_without_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("checkout");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("Cargo.toml"), "[package]").unwrap();
+        let index_path = temp_dir.path().join("index.db");
+
+        let engine = SearchEngine::builder()
+            .index_path(&index_path)
+            .in_memory(true)
+            .build()
+            .unwrap();
+        assert_eq!(engine.index_directory(&root, None).unwrap(), 1);
+        assert_eq!(engine.search("Cargo").unwrap().len(), 1);
+        assert!(!index_path.exists());
+
+        let other = SearchEngine::in_memory().unwrap();
+        assert!(other.search("Cargo").unwrap().is_empty());
+    }
+
+    /// Runs one fixed scenario and records every observable path, relative to the indexed root.
+    fn path_layout_snapshot(compact: bool) -> Vec<String> {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("workspace");
+        for dir in ["docs/reports/2024", "vendor/lib/src/deep/er", "München"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        let files = [
+            ("docs/reports/2024/annual report.md", "annual numbers"),
+            ("docs/readme.md", "read me"),
+            ("vendor/lib/src/deep/er/mod.rs", "pub mod er;"),
+            ("vendor/lib/LICENSE", "Permission is hereby granted"),
+            ("München/straße.txt", "grüße"),
+            ("stale.log", "old"),
+        ];
+        for (path, content) in files {
+            fs::write(root.join(path), content).unwrap();
+        }
+
+        let engine = SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .enable_content_search(true)
+            .compact_paths(compact)
+            .build()
+            .unwrap();
+        let expected = if compact { PathLayout::Compact } else { PathLayout::Legacy };
+        assert_eq!(engine.path_layout(), expected);
+
+        let relative = |path: &Path| {
+            path.strip_prefix(&root)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .replace('\\', "/")
+        };
+        let indexed = engine.index_directory(&root, None).unwrap();
+        let mut snapshot = vec![format!("indexed {}", indexed)];
+        for query in [
+            "report",
+            "re ext:md",
+            "München scope:path",
+            "lib/src scope:path",
+            "**/deep/**/*.rs mode:glob scope:path",
+            r"reports/\d+/.*\.md$ mode:regex scope:path",
+            "hereby scope:content",
+        ] {
+            let mut paths: Vec<_> = engine
+                .search(query)
+                .unwrap()
+                .iter()
+                .map(|result| relative(&result.file.path))
+                .collect();
+            paths.sort();
+            snapshot.push(format!("{} => {:?}", query, paths));
+        }
+
+        fs::remove_file(root.join("stale.log")).unwrap();
+        fs::write(root.join("vendor/lib/src/deep/added.rs"), "fn added() {}").unwrap();
+        let update = engine.update_index(&root, None).unwrap();
+        snapshot.push(format!("update +{} -{}", update.added, update.removed));
+
+        let verification = engine.verify_index(&root, None).unwrap();
+        snapshot.push(format!(
+            "verify {} {} {}",
+            verification.total_indexed, verification.outdated, verification.missing
+        ));
+
+        let mut feed = Vec::new();
+        engine.export_changes_since(DateTime::<Utc>::UNIX_EPOCH, &mut feed).unwrap();
+        let mut changes: Vec<_> = feed
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .filter_map(|line| match serde_json::from_slice::<ChangeRecord>(line).unwrap() {
+                ChangeRecord::Upsert { entry } => Some(format!(
+                    "upsert {} in {}",
+                    relative(&entry.path),
+                    entry.parent_path.as_deref().map(relative).unwrap_or_default()
+                )),
+                ChangeRecord::Delete { path, .. } => Some(format!("delete {}", relative(&path))),
+                ChangeRecord::Cursor { .. } => None,
+            })
+            .collect();
+        changes.sort();
+        snapshot.extend(changes);
+
+        engine.clear_index().unwrap();
+        snapshot.push(format!("cleared {}", engine.get_stats().unwrap().total_files));
+        snapshot
+    }
+
+    #[test]
+    fn test_compact_paths_behave_like_full_paths() {
+        let legacy = path_layout_snapshot(false);
+        let compact = path_layout_snapshot(true);
+
+        assert_eq!(legacy, compact);
+        assert!(legacy.contains(&"indexed 6".to_string()), "{:#?}", legacy);
+        assert!(legacy.contains(&"delete stale.log".to_string()), "{:#?}", legacy);
+    }
+
+    #[test]
+    fn test_metrics_track_searches_and_index_operations() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("data");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("notes.txt"), "content").unwrap();
+
+        let engine = SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .build()
+            .unwrap();
+        let initial = engine.metrics();
+        assert_eq!(initial.total_searches, 0);
+        assert_eq!(initial.index_operations, 0);
+        assert!(initial.db_connections_idle > 0);
+        assert_eq!(initial.db_connections_in_use, 0);
+
+        engine.index_directory(&root, None).unwrap();
+        engine.update_index(&root, None).unwrap();
+        engine.search("notes").unwrap();
+        engine.search("*.txt mode:glob").unwrap();
+        engine.search("notes mode:exact").unwrap();
+        assert!(engine.search("[ mode:regex").is_err());
+
+        let metrics = engine.metrics();
+        assert_eq!(metrics.index_operations, 2);
+        assert_eq!(metrics.total_searches, 3);
+        assert_eq!(metrics.searches_by_mode.case_insensitive, 1);
+        assert_eq!(metrics.searches_by_mode.glob, 1);
+        assert_eq!(metrics.searches_by_mode.exact, 1);
+        assert_eq!(metrics.searches_by_mode.regex, 0);
+        assert!(metrics.avg_search_time_ms > 0.0);
+        assert!(metrics.last_index_time_ms > 0.0);
+        assert_eq!(metrics.watch_events_processed, 0);
+    }
+
+    fn undo_fixture(config: SearchConfig) -> (TempDir, PathBuf, SearchEngine) {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("data");
+        fs::create_dir_all(root.join("docs")).unwrap();
+        for i in 0..20 {
+            fs::write(root.join("docs").join(format!("note{}.txt", i)), "x".repeat(i)).unwrap();
+        }
+        let index_path = temp_dir.path().join("index.db");
+        let engine = SearchEngine::with_config(&index_path, config).unwrap();
+        engine.index_directory(&root, None).unwrap();
+        (temp_dir, root, engine)
+    }
+
+    #[test]
+    fn test_undo_restores_cleared_index() {
+        let (_temp_dir, root, engine) = undo_fixture(SearchConfig::default());
+        let sample_path = root.join("docs").join("note7.txt");
+        let before = engine.database.find_by_path(&sample_path).unwrap().unwrap();
+        let total = engine.get_stats().unwrap().total_files;
+        assert!(engine.undoable_operations().unwrap().is_empty());
+
+        engine.clear_index().unwrap();
+        assert_eq!(engine.get_stats().unwrap().total_files, 0);
+        let operations = engine.undoable_operations().unwrap();
+        assert_eq!(operations.len(), 1);
+        assert_eq!(operations[0].operation, "clear_index");
+
+        let report = engine.undo_last().unwrap().unwrap();
+        assert_eq!(report.restored, total);
+        assert_eq!(engine.get_stats().unwrap().total_files, total);
+
+        let after = engine.database.find_by_path(&sample_path).unwrap().unwrap();
+        assert_eq!(after.size, before.size);
+        assert_eq!(after.modified_at, before.modified_at);
+        assert_eq!(after.indexed_at, before.indexed_at);
+        assert_eq!(engine.search("note7").unwrap().len(), 1);
+
+        assert!(engine.undoable_operations().unwrap().is_empty());
+        assert!(engine.undo_last().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_clear_refuses_oversized_undo_snapshot() {
+        let config = SearchConfig {
+            undo_max_snapshot_bytes: 128,
+            ..SearchConfig::default()
+        };
+        let (_temp_dir, _root, engine) = undo_fixture(config);
+        let total = engine.get_stats().unwrap().total_files;
+
+        let err = engine.clear_index().unwrap_err();
+        assert!(matches!(err, SearchError::UndoSnapshotTooLarge(128)));
+        assert_eq!(engine.get_stats().unwrap().total_files, total);
+        assert!(engine.undoable_operations().unwrap().is_empty());
+
+        engine.clear_index_without_undo().unwrap();
+        assert_eq!(engine.get_stats().unwrap().total_files, 0);
+        assert!(engine.undo_last().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_quick_index_enrichment_resumes_after_reopening() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("docs");
+        fs::create_dir(&root).unwrap();
+        for i in 0..12 {
+            fs::write(root.join(format!("report{:02}.txt", i)), "quarterly figures").unwrap();
+        }
+        let index_path = temp_dir.path().join("index.db");
+        let open = || {
+            SearchEngine::builder()
+                .index_path(&index_path)
+                .enable_content_search(true)
+                .batch_size(5)
+                .build()
+                .unwrap()
+        };
+
+        let engine = open();
+        assert_eq!(engine.index_quick(&root, None).unwrap().indexed, 12);
+        assert_eq!(engine.search("report03").unwrap().len(), 1);
+        let query = QueryParser::parse("quarterly scope:content").unwrap();
+        assert!(engine.search_with_query(&query).unwrap().is_empty());
+        assert!(engine.content_coverage_partial(&query).unwrap());
+        assert!(!engine.content_coverage_partial(&QueryParser::parse("report").unwrap()).unwrap());
+
+        let stats = engine.get_stats().unwrap();
+        assert_eq!(stats.indexed_files, 0);
+        assert_eq!(
+            stats.enrichment,
+            vec![EnrichmentProgress { root: root.clone(), total: 12, pending: 12 }]
+        );
+
+        let cancel = CancellationToken::new();
+        let stop = cancel.clone();
+        let callback: ProgressCallback = Box::new(move |_| stop.cancel());
+        let stats = engine.enrich(Some(&root), Some(callback), &cancel).unwrap();
+        assert!(stats.cancelled);
+        assert_eq!((stats.enriched, stats.remaining), (5, 7));
+        drop(engine);
+
+        let engine = open();
+        assert_eq!(engine.get_stats().unwrap().enrichment[0].pending, 7);
+        let stats = engine.enrich(None, None, &CancellationToken::new()).unwrap();
+        assert_eq!((stats.enriched, stats.remaining, stats.cancelled), (7, 0, false));
+        assert!(engine.enrichment_progress().unwrap().is_empty());
+        assert!(!engine.content_coverage_partial(&query).unwrap());
+        assert_eq!(engine.search_with_query(&query).unwrap().len(), 12);
+        assert_eq!(engine.get_stats().unwrap().indexed_files, 12);
+    }
+
+    #[test]
+    fn test_content_search_with_context_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("docs");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("plan.txt"), "intro\nquarterly figures\noutro\n\nquarterly close").unwrap();
+        fs::write(root.join("other.txt"), "nothing to see").unwrap();
+
+        let engine = SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .enable_content_search(true)
+            .build()
+            .unwrap();
+        engine.index_directory(&root, None).unwrap();
+
+        let results = engine.search("quarterly scope:content context:1").unwrap();
+        assert_eq!(results.len(), 1);
+        let blocks: Vec<_> = results[0]
+            .matches
+            .iter()
+            .map(|m| (m.line, m.context_start_line, m.context.as_str()))
+            .collect();
+        assert_eq!(
+            blocks,
+            vec![(2, 1, "intro\nquarterly figures\noutro"), (5, 4, "\nquarterly close")]
+        );
+
+        // Without `context:`, only the matching lines themselves.
+        let results = engine.search("quarterly scope:content").unwrap();
+        let lines: Vec<_> = results[0]
+            .matches
+            .iter()
+            .map(|m| (m.line, m.context_start_line, m.context.as_str()))
+            .collect();
+        assert_eq!(lines, vec![(2, 2, "quarterly figures"), (5, 5, "quarterly close")]);
+    }
+
+    #[test]
+    fn test_content_results_carry_a_highlighted_snippet() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("docs");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("plan.txt"), "intro\nthe Quarterly figures are in\noutro").unwrap();
+        fs::write(root.join("memo.txt"), "see the quarterly plan").unwrap();
+
+        let engine = SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .enable_content_search(true)
+            .snippet_context_chars(8)
+            .build()
+            .unwrap();
+        engine.index_directory(&root, None).unwrap();
+
+        let mut snippets: Vec<_> = engine
+            .search("quarterly scope:content")
+            .unwrap()
+            .into_iter()
+            .map(|result| (result.file.name, result.snippet))
+            .collect();
+        snippets.sort();
+        assert_eq!(
+            snippets,
+            vec![
+                ("memo.txt".to_string(), Some("see the **quarterly** plan".to_string())),
+                ("plan.txt".to_string(), Some("…tro the **Quarterly** figures…".to_string())),
+            ]
+        );
+        // Name searches read no content.
+        assert_eq!(engine.search("plan").unwrap()[0].snippet, None);
+
+        // A file deleted since it was indexed is still found, without a snippet.
+        fs::remove_file(root.join("memo.txt")).unwrap();
+        let results = engine.search("quarterly scope:content").unwrap();
+        assert_eq!(results.len(), 2);
+        let memo = results.iter().find(|result| result.file.name == "memo.txt").unwrap();
+        assert_eq!(memo.snippet, None);
+    }
+
+    #[test]
+    fn test_results_locate_their_matches() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("docs");
+        fs::create_dir(&root).unwrap();
+        let text: String = (1..=8).map(|i| format!("line {} has a todo item\n", i)).collect();
+        fs::write(root.join("todo_list_todo.txt"), text).unwrap();
+
+        let engine = SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .enable_content_search(true)
+            .max_match_locations(3)
+            .build()
+            .unwrap();
+        engine.index_directory(&root, None).unwrap();
+
+        let result = &engine.search("item scope:content").unwrap()[0];
+        let lines: Vec<(usize, usize, usize, &str)> = result
+            .matches
+            .iter()
+            .map(|m| (m.line, m.column, m.length, m.context.as_str()))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                (1, 19, 4, "line 1 has a todo item"),
+                (2, 19, 4, "line 2 has a todo item"),
+                (3, 19, 4, "line 3 has a todo item"),
+            ]
+        );
+        assert!(result.matches.iter().all(|m| m.field == MatchField::Content));
+
+        let result = &engine.search("TODO").unwrap()[0];
+        let names: Vec<(MatchField, usize, usize)> =
+            result.matches.iter().map(|m| (m.field, m.column, m.length)).collect();
+        assert_eq!(names, [(MatchField::Name, 1, 4), (MatchField::Name, 11, 4)]);
+        assert_eq!(result.matches[0].context, "todo_list_todo.txt");
+
+        let result = &engine.search("docs/todo scope:path").unwrap()[0];
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(result.matches[0].field, MatchField::Path);
+        let path = &result.matches[0].context;
+        let start: usize = path.find("docs/todo").unwrap();
+        assert_eq!(result.matches[0].column, path[..start].chars().count() + 1);
+
+        // Fuzzy matches are located as runs of matched characters.
+        let result = &engine.search("todolist mode:fuzzy").unwrap()[0];
+        let runs: Vec<(MatchField, usize, usize)> =
+            result.matches.iter().map(|m| (m.field, m.column, m.length)).collect();
+        assert_eq!(runs, [(MatchField::Name, 1, 4), (MatchField::Name, 6, 4)]);
+
+        let engine = SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .enable_content_search(true)
+            .max_match_locations(0)
+            .build()
+            .unwrap();
+        assert!(engine.search("todo").unwrap()[0].matches.is_empty());
+        assert!(engine.search("item scope:content").unwrap()[0].matches.is_empty());
+        assert_eq!(engine.search("item scope:content context:1").unwrap()[0].matches.len(), 8);
+    }
+
+    #[test]
+    fn test_name_locations_cover_the_folded_characters() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("docs");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("Café_Straße.txt"), "composed").unwrap();
+        fs::write(root.join("Cafe\u{301}-notes.txt"), "decomposed").unwrap();
+
+        let engine = SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+        engine.index_directory(&root, None).unwrap();
+
+        let located = |query: &str| -> Vec<String> {
+            let mut located: Vec<String> = engine
+                .search(query)
+                .unwrap()
+                .iter()
+                .flat_map(|result| &result.matches)
+                .map(|m| m.context.chars().skip(m.column - 1).take(m.length).collect())
+                .collect();
+            located.sort();
+            located
+        };
+        assert_eq!(located("cafe"), ["Cafe\u{301}", "Café"]);
+        assert_eq!(located("strasse"), ["Straße"]);
+    }
+
+    #[test]
+    fn test_all_scope_searches_names_paths_and_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("docs");
+        fs::create_dir_all(root.join("quarterly")).unwrap();
+        fs::write(root.join("quarterly-plan.md"), "nothing to see").unwrap();
+        fs::write(root.join("quarterly").join("notes.txt"), "misc").unwrap();
+        fs::write(root.join("ledger.txt"), "the quarterly figures").unwrap();
+        fs::write(root.join("other.txt"), "unrelated").unwrap();
+
+        let engine = SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .enable_content_search(true)
+            .build()
+            .unwrap();
+        engine.index_directory(&root, None).unwrap();
+
+        let results = engine.search("quarterly scope:all").unwrap();
+        let found: Vec<(&str, Option<MatchField>)> = results
+            .iter()
+            .map(|r| (r.file.name.as_str(), r.matches.first().map(|m| m.field)))
+            .collect();
+        // The name hit ranks above the file found by its directory and the one by its content.
+        assert_eq!(
+            found,
+            [
+                ("quarterly-plan.md", Some(MatchField::Name)),
+                ("notes.txt", Some(MatchField::Path)),
+                ("ledger.txt", Some(MatchField::Content)),
+            ]
+        );
+
+        // Punctuation is looked for in content as text, not read as full-text syntax.
+        assert_eq!(names(&engine, "quarterly-plan scope:all"), vec!["quarterly-plan.md"]);
+        assert_eq!(names(&engine, "*.md mode:glob scope:all"), vec!["quarterly-plan.md"]);
+    }
+
+    #[test]
+    fn test_context_lines_come_from_the_matching_chunk() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("dumps");
+        fs::create_dir(&root).unwrap();
+        let mut dump: String = (1..=10_000)
+            .map(|i| format!("INSERT INTO t VALUES ({});\n", i))
+            .collect();
+        dump = dump.replace("VALUES (9001);", "VALUES ('zebracorn');");
+        fs::write(root.join("backup.sql"), &dump).unwrap();
+
+        // Sampling a head this large indexes the whole file.
+        let config = SearchConfigBuilder::new()
+            .enable_content_search(true)
+            .content_sampling(crate::core::config::ContentSampling::HeadAndTail {
+                head_bytes: 1024 * 1024,
+                tail_bytes: 0,
+            })
+            .build();
+        let engine = SearchEngine::with_config(temp_dir.path().join("index.db"), config).unwrap();
+        engine.index_directory(&root, None).unwrap();
+
+        let results = engine.search("zebracorn scope:content context:1").unwrap();
+        assert_eq!(results.len(), 1);
+        let id = results[0].file.id.unwrap();
+        let hits = engine.database.content_chunk_hits(id, "zebracorn", 3).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert!(hits[0].chunk_index > 0);
+        let blocks: Vec<_> = results[0]
+            .matches
+            .iter()
+            .map(|m| (m.line, m.context_start_line, m.context.as_str()))
+            .collect();
+        assert_eq!(
+            blocks,
+            vec![(
+                9001,
+                9000,
+                "INSERT INTO t VALUES (9000);\n\
+                 INSERT INTO t VALUES ('zebracorn');\n\
+                 INSERT INTO t VALUES (9002);"
+            )]
+        );
+
+        // Re-indexing changed content leaves no rows of the old chunks behind.
+        fs::write(root.join("backup.sql"), dump.replace("zebracorn", "okapi")).unwrap();
+        engine.update_index(&root, None).unwrap();
+        assert!(engine.search("zebracorn scope:content").unwrap().is_empty());
+        assert!(engine.database.content_chunk_hits(id, "zebracorn", 3).unwrap().is_empty());
+        let okapi = engine.database.content_chunk_hits(id, "okapi", 3).unwrap();
+        assert_eq!(
+            (okapi[0].chunk_index, okapi[0].byte_offset),
+            (hits[0].chunk_index, hits[0].byte_offset)
+        );
+    }
+
+    #[test]
+    fn test_rebuild_fts_restores_content_search() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("docs");
+        fs::create_dir(&root).unwrap();
+        for i in 0..6 {
+            fs::write(root.join(format!("report{}.txt", i)), format!("quarterly figures {}", i))
+                .unwrap();
+        }
+        fs::write(root.join("copy.txt"), "quarterly figures 0").unwrap();
+        fs::write(root.join("lost.txt"), "misplaced minutes").unwrap();
+
+        let index_path = temp_dir.path().join("index.db");
+        let engine = SearchEngine::builder()
+            .index_path(&index_path)
+            .enable_content_search(true)
+            .build()
+            .unwrap();
+        engine.index_directory(&root, None).unwrap();
+        assert_eq!(engine.search("quarterly scope:content").unwrap().len(), 7);
+
+        let conn = rusqlite::Connection::open(&index_path).unwrap();
+        conn.execute_batch(
+            "DELETE FROM files_fts_data WHERE id > 10;
+             UPDATE content_blobs SET content_preview = NULL
+             WHERE hash = (SELECT content_hash FROM file_contents fc
+                           JOIN files f ON f.id = fc.file_id WHERE f.name = 'lost.txt');",
+        )
+        .unwrap();
+        assert!(engine.search("quarterly scope:content").is_err());
+        conn.execute_batch("DROP TABLE files_fts").unwrap();
+        assert!(engine.search("quarterly scope:content").is_err());
+        let content_rows: i64 = conn
+            .query_row("SELECT COUNT(*) FROM file_contents", [], |row| row.get(0))
+            .unwrap();
+
+        let updates = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = Arc::clone(&updates);
+        let callback: ProgressCallback =
+            Box::new(move |progress| seen.lock().unwrap().push(progress.current));
+        let report = engine.rebuild_fts(Some(callback)).unwrap();
+
+        assert_eq!(report.files + report.skipped, content_rows as usize);
+        assert_eq!(report, FtsRebuildReport { blobs: 6, files: 7, skipped: 1 });
+        assert_eq!(updates.lock().unwrap().last(), Some(&6));
+        assert_eq!(engine.search("quarterly scope:content").unwrap().len(), 7);
+        assert!(engine.search("misplaced scope:content").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_restore_brings_back_the_backed_up_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("docs");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("kept.txt"), "").unwrap();
+
+        let index_path = temp_dir.path().join("index.db");
+        let open = || SearchEngine::builder().index_path(&index_path).build().unwrap();
+        let engine = open();
+        engine.index_directory(&root, None).unwrap();
+        let backup = engine.backup(&temp_dir.path().join("backup.db"), None).unwrap();
+        let compressed = crate::storage::backup::compress(&backup.path).unwrap();
+
+        fs::write(root.join("later.txt"), "").unwrap();
+        engine.index_directory(&root, None).unwrap();
+        assert_eq!(engine.search("later").unwrap().len(), 1);
+
+        let report = engine.restore(&compressed, None).unwrap();
+        assert_eq!(report.schema_version, crate::storage::schema::CURRENT_SCHEMA_VERSION);
+        assert_eq!(report.pages, backup.pages);
+        drop(engine);
+
+        let engine = open();
+        assert!(engine.search("later").unwrap().is_empty());
+        assert_eq!(engine.search("kept").unwrap().len(), 1);
+        assert!(engine.restore(&root.join("kept.txt"), None).is_err());
+    }
+
+    #[test]
+    fn test_deterministic_builds_dump_the_same() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("fixture");
+        for dir in ["src/nested", "docs", "empty"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for (name, body) in [
+            ("src/main.rs", "fn main() {}"),
+            ("src/nested/lib.rs", "pub fn shared() {}"),
+            ("src/nested/copy.rs", "pub fn shared() {}"),
+            ("docs/Straße.md", "Grüße aus dem Index"),
+            ("docs/a.txt", "alpha"),
+            ("README", "read me first"),
+        ] {
+            fs::write(root.join(name), body).unwrap();
+        }
+
+        let build = |name: &str, deterministic: bool| {
+            let engine = SearchEngine::builder()
+                .index_path(temp_dir.path().join(name))
+                .enable_content_search(true)
+                .deterministic(deterministic)
+                .batch_size(2)
+                .build()
+                .unwrap();
+            engine.index_directory(&root, None).unwrap();
+            engine
+        };
+
+        let first = build("first.db", true);
+        let second = build("second.db", true);
+        let dump = first.logical_dump().unwrap();
+        assert_eq!(dump, second.logical_dump().unwrap());
+        assert_eq!(dump.lines().count(), 6 + 5);
+        assert!(dump.contains("\"name_normalized\":\"strasse.md\""), "{}", dump);
+
+        // Row ids follow the paths, and every entry carries the frozen time.
+        let mut entries = first.database.get_all_files(100, 0).unwrap();
+        entries.sort_by_key(|entry| entry.id);
+        let paths: Vec<&PathBuf> = entries.iter().map(|entry| &entry.path).collect();
+        let mut sorted = paths.clone();
+        sorted.sort();
+        assert_eq!(paths, sorted);
+        assert!(entries.iter().all(|entry| entry.indexed_at == crate::indexer::frozen_time()
+            && entry.last_verified == crate::indexer::frozen_time()));
+
+        // An ordinary build differs in ids and timestamps only, which the dump leaves out.
+        let normal = build("normal.db", false);
+        assert_eq!(normal.logical_dump().unwrap(), dump);
+
+        let clocked = SearchEngine::builder()
+            .index_path(temp_dir.path().join("clocked.db"))
+            .with_clock(|| DateTime::from_timestamp(1_700_000_000, 0).unwrap())
+            .build()
+            .unwrap();
+        clocked.index_directory(&root, None).unwrap();
+        let indexed = &clocked.search("main").unwrap()[0].file;
+        assert_eq!(indexed.indexed_at.timestamp(), 1_700_000_000);
+    }
+
+    #[test]
+    fn test_rebuild_fts_applies_tokenizer_change() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("docs");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("notes.txt"), "quarterly figures").unwrap();
+        let index_path = temp_dir.path().join("index.db");
+
+        let engine = SearchEngine::builder()
+            .index_path(&index_path)
+            .enable_content_search(true)
+            .build()
+            .unwrap();
+        engine.index_directory(&root, None).unwrap();
+        assert!(engine.search("arterl scope:content").unwrap().is_empty());
+        drop(engine);
+
+        let engine = SearchEngine::builder()
+            .index_path(&index_path)
+            .enable_content_search(true)
+            .fts_tokenizer("trigram")
+            .build()
+            .unwrap();
+        // The tokenizer only changes once the table is rebuilt.
+        assert!(engine.search("arterl scope:content").unwrap().is_empty());
+        engine.rebuild_fts(None).unwrap();
+        assert_eq!(engine.search("arterl scope:content").unwrap().len(), 1);
+
+        let engine = SearchEngine::builder()
+            .index_path(&index_path)
+            .fts_tokenizer("no_such_tokenizer")
+            .build()
+            .unwrap();
+        let err = engine.rebuild_fts(None).unwrap_err();
+        assert!(matches!(err, SearchError::Configuration(_)), "{:?}", err);
+    }
+
+    #[test]
+    fn test_errors_carry_stable_codes() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("notes.txt"), "quarterly figures").unwrap();
+
+        let missing_index = temp_dir.path().join("missing.db");
+        let err = SearchEngine::open_existing(&missing_index).err().unwrap();
+        assert_eq!(err.code().as_str(), "index.not_found");
+        assert_eq!(err.path(), Some(missing_index.as_path()));
+
+        let engine = SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .enable_content_search(true)
+            .build()
+            .unwrap();
+        let missing_root = temp_dir.path().join("gone");
+        let err = engine.index_directory(&missing_root, None).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::NotFound);
+        assert_eq!(err.path(), Some(missing_root.as_path()));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let locked = temp_dir.path().join("locked");
+            fs::create_dir(&locked).unwrap();
+            fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+            // Root can read it anyway, leaving nothing to check.
+            if fs::read_dir(&locked).is_err() {
+                let err = engine.index_directory(&locked, None).unwrap_err();
+                assert_eq!(err.code().as_str(), "io.permission_denied");
+                assert_eq!(err.path(), Some(locked.as_path()));
+            }
+            fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        engine.index_directory(&root, None).unwrap();
+        let err = engine.search("notes[ mode:regex").unwrap_err();
+        assert_eq!(err.code().as_str(), "invalid_query.regex");
+        let err = engine.search(r#"\"quarterly scope:content"#).unwrap_err();
+        assert_eq!(err.code().as_str(), "invalid_query.content", "{:?}", err);
+
+        let report: ErrorReport =
+            serde_json::from_value(serde_json::to_value(&err).unwrap()).unwrap();
+        assert_eq!(report.code, ErrorCode::InvalidContentQuery);
+        assert_eq!(report.message, err.to_string());
+    }
+
+    fn lease_engine(index_path: &Path, wait_secs: u64) -> SearchEngine {
+        let config = SearchConfig {
+            write_lease_wait_secs: wait_secs,
+            ..SearchConfig::default()
+        };
+        SearchEngine::with_config(index_path, config).unwrap()
+    }
+
+    #[test]
+    fn test_writer_waits_for_the_write_lease_of_another_engine() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("index.db");
+        let root = temp_dir.path().join("data");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("a.txt"), "a").unwrap();
+
+        let daemon = lease_engine(&index_path, 5);
+        let manual = Arc::new(lease_engine(&index_path, 5));
+        let held = daemon.acquire_write_lease(LeaseWait::NoWait, |_| {}).unwrap();
+
+        let waiter = {
+            let manual = Arc::clone(&manual);
+            let root = root.clone();
+            std::thread::spawn(move || {
+                let mut waited_on = Vec::new();
+                let lease = manual
+                    .acquire_write_lease(LeaseWait::Wait(std::time::Duration::from_secs(5)), |h| {
+                        waited_on.push(h.holder_id.clone())
+                    })
+                    .unwrap();
+                let updated = manual.update_index(&root, None).unwrap();
+                drop(lease);
+                (waited_on, updated.added)
+            })
+        };
+
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        let holder = daemon.write_lease().unwrap().unwrap();
+        assert_eq!(holder.holder_id, daemon.write_lease.holder_id());
+        assert_eq!(daemon.get_stats().unwrap().write_lease, Some(holder.clone()));
+        drop(held);
+
+        let (waited_on, added) = waiter.join().unwrap();
+        assert_eq!(waited_on, vec![holder.holder_id]);
+        assert_eq!(added, 1);
+        assert!(manual.write_lease().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_writer_fails_fast_without_waiting() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("index.db");
+        let root = temp_dir.path().join("data");
+        fs::create_dir(&root).unwrap();
+
+        let daemon = lease_engine(&index_path, 5);
+        let manual = lease_engine(&index_path, 0);
+        let _held = daemon.acquire_write_lease(LeaseWait::NoWait, |_| {}).unwrap();
+
+        let Err(err) = manual.acquire_write_lease(LeaseWait::NoWait, |_| {}) else {
+            panic!("the lease is held by another engine");
+        };
+        assert_eq!(err.code(), ErrorCode::WriteLeaseHeld);
+        assert!(matches!(
+            err,
+            SearchError::WriteLeaseHeld { pid, .. } if pid == std::process::id()
+        ));
+
+        // Operations wait `write_lease_wait_secs`, which is zero here, before failing.
+        let err = manual.index_directory(&root, None).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::WriteLeaseHeld);
+        assert_eq!(manual.get_stats().unwrap().total_files, 0);
+    }
+
+    #[test]
+    fn test_stale_write_lease_is_reclaimed() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("index.db");
+        let root = temp_dir.path().join("data");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("a.txt"), "a").unwrap();
+
+        let engine = lease_engine(&index_path, 0);
+        // A writer that crashed a minute ago, well past the 30 second staleness limit.
+        let crashed_at = Utc::now() - Duration::minutes(1);
+        let crashed = WriteLease {
+            holder_id: "crashed".to_string(),
+            pid: 4242,
+            hostname: "elsewhere".to_string(),
+            acquired_at: crashed_at,
+            heartbeat_at: crashed_at,
+        };
+        engine
+            .database
+            .try_acquire_write_lease(&crashed, Duration::seconds(30), false)
+            .unwrap();
+        assert_eq!(engine.write_lease().unwrap(), Some(crashed));
+
+        assert_eq!(engine.index_directory(&root, None).unwrap(), 1);
+        assert!(engine.write_lease().unwrap().is_none());
     }
 }
@@ -1,7 +1,11 @@
-use std::path::PathBuf;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum SearchError {
     #[error("Database error: {0}")]
     Database(#[from] rusqlite::Error),
@@ -9,9 +13,29 @@ pub enum SearchError {
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
 
+    /// An I/O failure on a known path other than a missing file or denied access, which
+    /// become [`PathNotFound`](Self::PathNotFound) and
+    /// [`PermissionDenied`](Self::PermissionDenied); see [`SearchError::io_at`].
+    #[error("I/O error on {path}: {source}")]
+    IoAt {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
     #[error("Invalid query: {0}")]
     InvalidQuery(String),
 
+    #[error("Invalid regex: {0}")]
+    InvalidRegex(String),
+
+    #[error("Invalid glob pattern: {0}")]
+    InvalidGlob(String),
+
+    /// SQLite rejected the text of a content search as FTS5 query syntax.
+    #[error("Invalid content query: {0}")]
+    InvalidContentQuery(String),
+
     #[error("Path not found: {0}")]
     PathNotFound(PathBuf),
 
@@ -27,6 +51,9 @@ pub enum SearchError {
     #[error("Pool error: {0}")]
     Pool(String),
 
+    #[error("Timed out waiting for a database connection: {0}")]
+    PoolTimeout(String),
+
     #[error("Watch error: {0}")]
     Watch(String),
 
@@ -41,11 +68,321 @@ pub enum SearchError {
 
     #[error("Not initialized: {0}")]
     NotInitialized(String),
+
+    #[error("Index not found: {0}")]
+    IndexNotFound(PathBuf),
+
+    #[error("No indexed entry with id {0}")]
+    EntryNotFound(i64),
+
+    /// The file is not an image this build can decode.
+    #[error("Unsupported media type: {0}")]
+    UnsupportedMedia(PathBuf),
+
+    #[error("Saved search not found: {0}")]
+    SavedSearchNotFound(String),
+
+    #[error("Feature unavailable: {0}")]
+    FeatureUnavailable(String),
+
+    #[error("Undo snapshot would exceed the {0}-byte limit")]
+    UndoSnapshotTooLarge(u64),
+
+    /// Indexing stopped before a batch that would not fit in `max_index_size_bytes`.
+    /// Batches committed before it, `indexed` entries in all, stay in the index.
+    #[error("Index quota of {limit} bytes reached at {used} bytes after {indexed} entries")]
+    QuotaExceeded { limit: u64, used: u64, indexed: usize },
+
+    /// Another process is writing to the index and did not release its write lease in time.
+    #[error("Index is being written by pid {pid} on {hostname}")]
+    WriteLeaseHeld { pid: u32, hostname: String },
+
+    /// Reading or analyzing a file's content ran past `content_analysis_timeout_ms`.
+    #[error("Content analysis of {path} exceeded its {budget_ms} ms budget")]
+    ContentTimeout { path: PathBuf, budget_ms: u64 },
+
+    /// A FIFO, socket or device, whose content is never read.
+    #[error("Not a regular file: {0}")]
+    NotRegularFile(PathBuf),
+}
+
+impl SearchError {
+    /// Wraps an I/O error on `path`, telling a missing file and denied access apart from
+    /// other failures.
+    pub fn io_at<P: Into<PathBuf>>(path: P, err: std::io::Error) -> Self {
+        match err.kind() {
+            ErrorKind::NotFound => SearchError::PathNotFound(path.into()),
+            ErrorKind::PermissionDenied => SearchError::PermissionDenied(path.into()),
+            _ => SearchError::IoAt {
+                path: path.into(),
+                source: err,
+            },
+        }
+    }
+
+    /// Gives a path-less I/O error the path it happened on; other errors are unchanged.
+    pub fn at_path<P: AsRef<Path>>(self, path: P) -> Self {
+        match self {
+            SearchError::Io(err) => SearchError::io_at(path.as_ref(), err),
+            err => err,
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            SearchError::Database(e) => sqlite_code(e),
+            SearchError::Io(e) | SearchError::IoAt { source: e, .. } => io_code(e),
+            SearchError::InvalidQuery(_) => ErrorCode::InvalidQuery,
+            SearchError::InvalidRegex(_) => ErrorCode::InvalidRegex,
+            SearchError::InvalidGlob(_) => ErrorCode::InvalidGlob,
+            SearchError::InvalidContentQuery(_) => ErrorCode::InvalidContentQuery,
+            SearchError::PathNotFound(_) => ErrorCode::NotFound,
+            SearchError::PermissionDenied(_) => ErrorCode::PermissionDenied,
+            SearchError::IndexCorrupted(_) => ErrorCode::IndexCorrupted,
+            SearchError::Configuration(_) => ErrorCode::Configuration,
+            SearchError::Pool(_) => ErrorCode::Pool,
+            SearchError::PoolTimeout(_) => ErrorCode::PoolTimeout,
+            SearchError::Watch(_) => ErrorCode::Watch,
+            SearchError::Encoding(_) => ErrorCode::Encoding,
+            SearchError::Parse(_) => ErrorCode::Parse,
+            SearchError::Cancelled => ErrorCode::Cancelled,
+            SearchError::NotInitialized(_) => ErrorCode::NotInitialized,
+            SearchError::IndexNotFound(_) => ErrorCode::IndexNotFound,
+            SearchError::EntryNotFound(_) => ErrorCode::EntryNotFound,
+            SearchError::UnsupportedMedia(_) => ErrorCode::UnsupportedMedia,
+            SearchError::SavedSearchNotFound(_) => ErrorCode::SavedSearchNotFound,
+            SearchError::FeatureUnavailable(_) => ErrorCode::FeatureUnavailable,
+            SearchError::UndoSnapshotTooLarge(_) => ErrorCode::UndoSnapshotTooLarge,
+            SearchError::QuotaExceeded { .. } => ErrorCode::QuotaExceeded,
+            SearchError::WriteLeaseHeld { .. } => ErrorCode::WriteLeaseHeld,
+            SearchError::ContentTimeout { .. } => ErrorCode::ContentTimeout,
+            SearchError::NotRegularFile(_) => ErrorCode::NotRegularFile,
+        }
+    }
+
+    /// The file or directory the error concerns, when there is one.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            SearchError::IoAt { path, .. }
+            | SearchError::PathNotFound(path)
+            | SearchError::PermissionDenied(path)
+            | SearchError::IndexNotFound(path)
+            | SearchError::UnsupportedMedia(path)
+            | SearchError::ContentTimeout { path, .. }
+            | SearchError::NotRegularFile(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    pub fn report(&self) -> ErrorReport {
+        ErrorReport {
+            code: self.code(),
+            message: self.to_string(),
+            path: self.path().map(Path::to_path_buf),
+        }
+    }
+}
+
+/// Serializes as its [`ErrorReport`].
+impl Serialize for SearchError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        self.report().serialize(serializer)
+    }
+}
+
+/// Attaches the path an I/O operation was working on to its error.
+pub trait IoResultExt<T> {
+    fn at_path<P: AsRef<Path>>(self, path: P) -> Result<T>;
+}
+
+impl<T> IoResultExt<T> for std::io::Result<T> {
+    fn at_path<P: AsRef<Path>>(self, path: P) -> Result<T> {
+        self.map_err(|e| SearchError::io_at(path.as_ref(), e))
+    }
+}
+
+/// What an error carries across process boundaries: the HTTP API, `--json` output and
+/// JSON-RPC error data.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorReport {
+    pub code: ErrorCode,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+}
+
+/// Stable, machine-readable error classes. Messages may change between releases; the
+/// strings from [`ErrorCode::as_str`] do not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorCode {
+    InvalidQuery,
+    InvalidRegex,
+    InvalidGlob,
+    InvalidContentQuery,
+    NotFound,
+    PermissionDenied,
+    NotRegularFile,
+    DiskFull,
+    Io,
+    ContentTimeout,
+    Storage,
+    StorageBusy,
+    StorageReadOnly,
+    StorageCannotOpen,
+    Pool,
+    PoolTimeout,
+    IndexCorrupted,
+    IndexNotFound,
+    NotInitialized,
+    QuotaExceeded,
+    WriteLeaseHeld,
+    EntryNotFound,
+    SavedSearchNotFound,
+    UnsupportedMedia,
+    FeatureUnavailable,
+    UndoSnapshotTooLarge,
+    Configuration,
+    Watch,
+    Encoding,
+    Parse,
+    Cancelled,
+    /// A code this version does not know, read back from a newer peer.
+    Unknown,
 }
 
+impl ErrorCode {
+    const ALL: [ErrorCode; 32] = [
+        ErrorCode::InvalidQuery,
+        ErrorCode::InvalidRegex,
+        ErrorCode::InvalidGlob,
+        ErrorCode::InvalidContentQuery,
+        ErrorCode::NotFound,
+        ErrorCode::PermissionDenied,
+        ErrorCode::NotRegularFile,
+        ErrorCode::DiskFull,
+        ErrorCode::Io,
+        ErrorCode::ContentTimeout,
+        ErrorCode::Storage,
+        ErrorCode::StorageBusy,
+        ErrorCode::StorageReadOnly,
+        ErrorCode::StorageCannotOpen,
+        ErrorCode::Pool,
+        ErrorCode::PoolTimeout,
+        ErrorCode::IndexCorrupted,
+        ErrorCode::IndexNotFound,
+        ErrorCode::NotInitialized,
+        ErrorCode::QuotaExceeded,
+        ErrorCode::WriteLeaseHeld,
+        ErrorCode::EntryNotFound,
+        ErrorCode::SavedSearchNotFound,
+        ErrorCode::UnsupportedMedia,
+        ErrorCode::FeatureUnavailable,
+        ErrorCode::UndoSnapshotTooLarge,
+        ErrorCode::Configuration,
+        ErrorCode::Watch,
+        ErrorCode::Encoding,
+        ErrorCode::Parse,
+        ErrorCode::Cancelled,
+        ErrorCode::Unknown,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::InvalidQuery => "invalid_query",
+            ErrorCode::InvalidRegex => "invalid_query.regex",
+            ErrorCode::InvalidGlob => "invalid_query.glob",
+            ErrorCode::InvalidContentQuery => "invalid_query.content",
+            ErrorCode::NotFound => "io.not_found",
+            ErrorCode::PermissionDenied => "io.permission_denied",
+            ErrorCode::NotRegularFile => "io.not_regular_file",
+            ErrorCode::DiskFull => "io.disk_full",
+            ErrorCode::Io => "io.other",
+            ErrorCode::ContentTimeout => "content.timeout",
+            ErrorCode::Storage => "storage.error",
+            ErrorCode::StorageBusy => "storage.busy",
+            ErrorCode::StorageReadOnly => "storage.read_only",
+            ErrorCode::StorageCannotOpen => "storage.cannot_open",
+            ErrorCode::Pool => "storage.pool",
+            ErrorCode::PoolTimeout => "storage.pool_timeout",
+            ErrorCode::IndexCorrupted => "index.corrupted",
+            ErrorCode::IndexNotFound => "index.not_found",
+            ErrorCode::NotInitialized => "index.not_initialized",
+            ErrorCode::QuotaExceeded => "index.quota_exceeded",
+            ErrorCode::WriteLeaseHeld => "index.write_lease_held",
+            ErrorCode::EntryNotFound => "entry.not_found",
+            ErrorCode::SavedSearchNotFound => "saved_search.not_found",
+            ErrorCode::UnsupportedMedia => "media.unsupported",
+            ErrorCode::FeatureUnavailable => "feature.unavailable",
+            ErrorCode::UndoSnapshotTooLarge => "undo.snapshot_too_large",
+            ErrorCode::Configuration => "config.invalid",
+            ErrorCode::Watch => "watch.error",
+            ErrorCode::Encoding => "encoding.error",
+            ErrorCode::Parse => "parse.error",
+            ErrorCode::Cancelled => "cancelled",
+            ErrorCode::Unknown => "unknown",
+        }
+    }
+
+    /// Parses a code produced by [`as_str`](Self::as_str); anything else is `Unknown`.
+    pub fn parse(code: &str) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|known| known.as_str() == code)
+            .unwrap_or(ErrorCode::Unknown)
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for ErrorCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ErrorCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        Ok(ErrorCode::parse(&String::deserialize(deserializer)?))
+    }
+}
+
+fn io_code(err: &std::io::Error) -> ErrorCode {
+    // ENOSPC; `ErrorKind::StorageFull` is newer than the supported toolchain.
+    const NO_SPACE: i32 = 28;
+
+    match err.kind() {
+        ErrorKind::NotFound => ErrorCode::NotFound,
+        ErrorKind::PermissionDenied => ErrorCode::PermissionDenied,
+        _ if err.raw_os_error() == Some(NO_SPACE) => ErrorCode::DiskFull,
+        _ => ErrorCode::Io,
+    }
+}
+
+fn sqlite_code(err: &rusqlite::Error) -> ErrorCode {
+    use rusqlite::ErrorCode as Sqlite;
+
+    let rusqlite::Error::SqliteFailure(failure, _) = err else {
+        return ErrorCode::Storage;
+    };
+    match failure.code {
+        Sqlite::DatabaseCorrupt | Sqlite::NotADatabase => ErrorCode::IndexCorrupted,
+        Sqlite::DatabaseBusy | Sqlite::DatabaseLocked => ErrorCode::StorageBusy,
+        Sqlite::DiskFull => ErrorCode::DiskFull,
+        Sqlite::ReadOnly => ErrorCode::StorageReadOnly,
+        Sqlite::CannotOpen | Sqlite::PermissionDenied => ErrorCode::StorageCannotOpen,
+        _ => ErrorCode::Storage,
+    }
+}
+
+/// r2d2 only fails a checkout, or the initial connections of a new pool, by timing out.
 impl From<r2d2::Error> for SearchError {
     fn from(err: r2d2::Error) -> Self {
-        SearchError::Pool(err.to_string())
+        SearchError::PoolTimeout(err.to_string())
     }
 }
 
@@ -57,14 +394,83 @@ impl From<notify::Error> for SearchError {
 
 impl From<globset::Error> for SearchError {
     fn from(err: globset::Error) -> Self {
-        SearchError::Parse(err.to_string())
+        SearchError::InvalidGlob(err.to_string())
     }
 }
 
 impl From<regex::Error> for SearchError {
     fn from(err: regex::Error) -> Self {
-        SearchError::Parse(err.to_string())
+        SearchError::InvalidRegex(err.to_string())
     }
 }
 
 pub type Result<T> = std::result::Result<T, SearchError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_errors_keep_their_path_and_class() {
+        let path = Path::new("/srv/reports");
+        let denied = SearchError::io_at(path, ErrorKind::PermissionDenied.into());
+        assert!(matches!(denied, SearchError::PermissionDenied(_)));
+        assert_eq!(denied.code().as_str(), "io.permission_denied");
+        assert_eq!(denied.path(), Some(path));
+
+        let missing: Result<()> = Err(std::io::Error::from(ErrorKind::NotFound)).at_path(path);
+        assert_eq!(missing.unwrap_err().code(), ErrorCode::NotFound);
+
+        let full = SearchError::io_at(path, std::io::Error::from_raw_os_error(28));
+        assert_eq!(full.code().as_str(), "io.disk_full");
+        assert_eq!(full.path(), Some(path));
+
+        let unlocated = SearchError::Io(ErrorKind::Other.into());
+        assert_eq!(unlocated.path(), None);
+        assert_eq!(unlocated.at_path(path).path(), Some(path));
+        assert!(SearchError::Cancelled.at_path(path).path().is_none());
+    }
+
+    #[test]
+    fn test_sqlite_failures_are_classified() {
+        let failure = |code| {
+            SearchError::Database(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error {
+                    code,
+                    extended_code: 0,
+                },
+                None,
+            ))
+        };
+
+        let corrupt = failure(rusqlite::ErrorCode::DatabaseCorrupt);
+        assert_eq!(corrupt.code().as_str(), "index.corrupted");
+        assert_eq!(
+            failure(rusqlite::ErrorCode::DatabaseBusy).code(),
+            ErrorCode::StorageBusy
+        );
+        assert_eq!(
+            SearchError::Database(rusqlite::Error::InvalidQuery).code(),
+            ErrorCode::Storage
+        );
+    }
+
+    #[test]
+    fn test_reports_round_trip_their_code() {
+        for code in ErrorCode::ALL {
+            assert_eq!(ErrorCode::parse(code.as_str()), code);
+        }
+        assert_eq!(ErrorCode::parse("index.exploded"), ErrorCode::Unknown);
+
+        let err = SearchError::PathNotFound(PathBuf::from("/srv/reports/q2.xlsx"));
+        let value = serde_json::to_value(&err).unwrap();
+        assert_eq!(value["code"], "io.not_found");
+        assert_eq!(value["path"], "/srv/reports/q2.xlsx");
+
+        let report: ErrorReport = serde_json::from_value(value).unwrap();
+        assert_eq!(report, err.report());
+
+        let pathless = serde_json::to_value(SearchError::Cancelled).unwrap();
+        assert!(pathless.get("path").is_none());
+    }
+}
@@ -0,0 +1,436 @@
+use crate::core::config::SearchConfig;
+use crate::core::engine::SearchEngine;
+use crate::core::error::{Result, SearchError};
+use crate::core::types::MaintenanceTask;
+use chrono::{DateTime, Datelike, Local, TimeZone, Timelike, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// What one bounded step of a maintenance task left behind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MaintenanceStep {
+    /// The task finished and its last-run time was recorded.
+    Done,
+    /// The task has more work for later steps.
+    More,
+    /// Another process holds the write lease, so nothing was done.
+    LeaseHeld,
+}
+
+/// Why [`MaintenanceScheduler::run_slice`] returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SliceOutcome {
+    /// Nothing ran: the engine is in use or not idle long enough, the blackout window is on,
+    /// or nothing happened since the last pass.
+    NotDue,
+    /// A search, index run or other writer got in the way; the pass resumes once the engine
+    /// is idle again.
+    Preempted,
+    /// The slice used up its time with tasks left.
+    Yielded,
+    /// The last task of the pass completed.
+    Finished,
+}
+
+/// Runs maintenance passes while an engine is idle, a time-boxed slice at a time.
+///
+/// A pass starts once no search or index operation has run for `idle_threshold_secs`, outside
+/// `maintenance_window`, and runs each enabled task in turn. Every call to
+/// [`run_slice`](Self::run_slice) works for about `maintenance_slice_ms`, checking between
+/// steps whether an operation has started and stopping for it if so; the next call resumes
+/// where the last one stopped. After a pass completes, the next one waits until the engine
+/// has been used and gone idle again.
+pub struct MaintenanceScheduler {
+    idle_threshold: Duration,
+    slice: Duration,
+    window: Option<MaintenanceWindow>,
+    tasks: Vec<MaintenanceTask>,
+    pending: VecDeque<MaintenanceTask>,
+    /// Operations the engine had begun when the last pass finished.
+    finished_after: Option<u64>,
+}
+
+impl MaintenanceScheduler {
+    pub fn new(config: &SearchConfig) -> Self {
+        Self {
+            idle_threshold: Duration::from_secs(config.idle_threshold_secs),
+            slice: Duration::from_millis(config.maintenance_slice_ms),
+            window: config.maintenance_window.clone(),
+            tasks: MaintenanceTask::ALL
+                .into_iter()
+                .filter(|task| !config.disabled_maintenance_tasks.contains(task))
+                .collect(),
+            pending: VecDeque::new(),
+            finished_after: None,
+        }
+    }
+
+    /// How long callers should wait between slices while nothing is due.
+    pub fn poll_interval(&self) -> Duration {
+        self.idle_threshold
+            .clamp(Duration::from_millis(100), Duration::from_secs(5))
+    }
+
+    /// Runs maintenance for one slice if it is due. A slice always makes at least one step
+    /// of progress once it starts.
+    pub fn run_slice(&mut self, engine: &SearchEngine) -> Result<SliceOutcome> {
+        self.run_slice_at(engine, Local::now())
+    }
+
+    fn run_slice_at<Tz: TimeZone>(
+        &mut self,
+        engine: &SearchEngine,
+        now: DateTime<Tz>,
+    ) -> Result<SliceOutcome> {
+        let activity = engine.activity();
+        let interrupted = if self.pending.is_empty() {
+            SliceOutcome::NotDue
+        } else {
+            SliceOutcome::Preempted
+        };
+
+        if engine.capabilities().read_only
+            || self
+                .window
+                .as_ref()
+                .is_some_and(|window| window.contains(&now))
+        {
+            return Ok(SliceOutcome::NotDue);
+        }
+        if activity.is_busy() || activity.idle_for() < self.idle_threshold {
+            return Ok(interrupted);
+        }
+        if self.pending.is_empty() {
+            if self.finished_after == Some(activity.operations_begun()) || self.tasks.is_empty() {
+                return Ok(SliceOutcome::NotDue);
+            }
+            self.pending.extend(self.tasks.iter().copied());
+        }
+
+        let deadline = Instant::now() + self.slice;
+        while let Some(&task) = self.pending.front() {
+            match engine.maintenance_step(task)? {
+                MaintenanceStep::Done => {
+                    self.pending.pop_front();
+                }
+                MaintenanceStep::More => {}
+                MaintenanceStep::LeaseHeld => return Ok(SliceOutcome::Preempted),
+            }
+
+            if self.pending.is_empty() {
+                break;
+            }
+            if activity.is_busy() {
+                return Ok(SliceOutcome::Preempted);
+            }
+            if Instant::now() >= deadline {
+                return Ok(SliceOutcome::Yielded);
+            }
+        }
+
+        self.finished_after = Some(activity.operations_begun());
+        Ok(SliceOutcome::Finished)
+    }
+}
+
+/// Times of the week when automatic maintenance must not run, such as business hours.
+///
+/// Written as one or more `;`-separated spans of `[days] HH:MM-HH:MM` in local time, e.g.
+/// `Mon-Fri 09:00-18:00; Sat 10:00-14:00`. Days are `*` (the default), a day or a range of
+/// days, or a comma-separated list of them. A span whose end is not after its start runs
+/// past midnight into the next day, and `24:00` ends a span at midnight.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct MaintenanceWindow {
+    spans: Vec<BlackoutSpan>,
+    source: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BlackoutSpan {
+    /// Bit `n` set for the weekday `n` days from Monday.
+    days: u8,
+    start_minute: u32,
+    end_minute: u32,
+}
+
+const ALL_DAYS: u8 = 0b111_1111;
+
+impl MaintenanceWindow {
+    pub fn contains<Tz: TimeZone>(&self, at: &DateTime<Tz>) -> bool {
+        let minute = at.hour() * 60 + at.minute();
+        self.spans
+            .iter()
+            .any(|span| span.contains(at.weekday(), minute))
+    }
+}
+
+impl BlackoutSpan {
+    fn contains(&self, weekday: Weekday, minute: u32) -> bool {
+        let on = |day: Weekday| self.days & (1 << day.num_days_from_monday()) != 0;
+        if self.start_minute < self.end_minute {
+            on(weekday) && (self.start_minute..self.end_minute).contains(&minute)
+        } else {
+            (on(weekday) && minute >= self.start_minute)
+                || (on(weekday.pred()) && minute < self.end_minute)
+        }
+    }
+
+    fn parse(span: &str) -> std::result::Result<Self, String> {
+        let (days, times) = match span.rsplit_once(char::is_whitespace) {
+            Some((days, times)) => (parse_days(days.trim())?, times),
+            None => (ALL_DAYS, span),
+        };
+        let (start, end) = times
+            .split_once('-')
+            .ok_or_else(|| format!("expected HH:MM-HH:MM, got '{}'", times))?;
+
+        Ok(Self {
+            days,
+            start_minute: parse_minute(start)?,
+            end_minute: parse_minute(end)?,
+        })
+    }
+}
+
+fn parse_days(days: &str) -> std::result::Result<u8, String> {
+    if days == "*" {
+        return Ok(ALL_DAYS);
+    }
+
+    let weekday = |name: &str| {
+        Weekday::from_str(name.trim()).map_err(|_| format!("unknown day '{}'", name.trim()))
+    };
+    let mut mask = 0;
+    for item in days.split(',') {
+        let (first, last) = match item.split_once('-') {
+            Some((first, last)) => (weekday(first)?, weekday(last)?),
+            None => (weekday(item)?, weekday(item)?),
+        };
+        let mut day = first;
+        loop {
+            mask |= 1 << day.num_days_from_monday();
+            if day == last {
+                break;
+            }
+            day = day.succ();
+        }
+    }
+    Ok(mask)
+}
+
+fn parse_minute(time: &str) -> std::result::Result<u32, String> {
+    let invalid = || format!("invalid time '{}'", time);
+    let (hour, minute) = time.trim().split_once(':').ok_or_else(invalid)?;
+    let hour: u32 = hour.parse().map_err(|_| invalid())?;
+    let minute: u32 = minute.parse().map_err(|_| invalid())?;
+
+    match (hour, minute) {
+        (24, 0) => Ok(24 * 60),
+        (0..=23, 0..=59) => Ok(hour * 60 + minute),
+        _ => Err(invalid()),
+    }
+}
+
+impl FromStr for MaintenanceWindow {
+    type Err = SearchError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let spans = s
+            .split(';')
+            .map(str::trim)
+            .filter(|span| !span.is_empty())
+            .map(BlackoutSpan::parse)
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| {
+                SearchError::Configuration(format!("Invalid maintenance_window '{}': {}", s, e))
+            })?;
+        if spans.is_empty() {
+            return Err(SearchError::Configuration(
+                "maintenance_window is empty".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            spans,
+            source: s.trim().to_string(),
+        })
+    }
+}
+
+impl TryFrom<String> for MaintenanceWindow {
+    type Error = SearchError;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<MaintenanceWindow> for String {
+    fn from(window: MaintenanceWindow) -> Self {
+        window.source
+    }
+}
+
+impl fmt::Display for MaintenanceWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::config::SearchConfigBuilder;
+    use chrono::Utc;
+
+    fn at(weekday_offset: i64, hour: u32, minute: u32) -> DateTime<Utc> {
+        // 2024-01-01 was a Monday.
+        Utc.with_ymd_and_hms(2024, 1, 1, hour, minute, 0).unwrap()
+            + chrono::Duration::days(weekday_offset)
+    }
+
+    #[test]
+    fn test_parse_maintenance_windows() {
+        let business: MaintenanceWindow = "Mon-Fri 09:00-18:00".parse().unwrap();
+        assert!(business.contains(&at(0, 9, 0)));
+        assert!(business.contains(&at(4, 17, 59)));
+        assert!(!business.contains(&at(4, 18, 0)));
+        assert!(!business.contains(&at(5, 12, 0)));
+
+        let nightly: MaintenanceWindow = "Sat,Sun 22:00-06:00; 12:00-13:00".parse().unwrap();
+        assert!(nightly.contains(&at(5, 23, 30)));
+        assert!(
+            nightly.contains(&at(0, 5, 59)),
+            "Sunday's span runs into Monday"
+        );
+        assert!(!nightly.contains(&at(4, 23, 30)));
+        assert!(nightly.contains(&at(2, 12, 30)));
+        assert_eq!(nightly.to_string(), "Sat,Sun 22:00-06:00; 12:00-13:00");
+
+        let weekend: MaintenanceWindow = "Fri-Mon 00:00-24:00".parse().unwrap();
+        assert!(weekend.contains(&at(6, 23, 59)));
+        assert!(!weekend.contains(&at(1, 0, 0)));
+
+        for invalid in ["", "Mon-Fri", "Someday 09:00-10:00", "09:00-25:00", "9-17"] {
+            assert!(
+                matches!(
+                    invalid.parse::<MaintenanceWindow>(),
+                    Err(SearchError::Configuration(_))
+                ),
+                "{:?}",
+                invalid
+            );
+        }
+        assert!(toml::from_str::<SearchConfig>("maintenance_window = \"Mon 09:00\"").is_err());
+    }
+
+    fn idle_engine() -> SearchEngine {
+        let config = SearchConfigBuilder::new()
+            .idle_threshold_secs(0)
+            .maintenance_slice_ms(0)
+            .build();
+        SearchEngine::in_memory_with_config(config).unwrap()
+    }
+
+    #[test]
+    fn test_scheduler_runs_every_task_while_idle() {
+        let engine = idle_engine();
+        let mut scheduler = MaintenanceScheduler::new(engine.config());
+        let mut outcomes = Vec::new();
+        loop {
+            let outcome = scheduler.run_slice(&engine).unwrap();
+            outcomes.push(outcome);
+            if outcome != SliceOutcome::Yielded {
+                break;
+            }
+        }
+
+        assert_eq!(outcomes.last(), Some(&SliceOutcome::Finished));
+        assert!(outcomes.len() >= MaintenanceTask::ALL.len());
+        for status in engine.maintenance_status().unwrap() {
+            assert!(status.enabled);
+            let last_run = status.last_run.expect("every task records its run");
+            assert!((Utc::now() - last_run).num_seconds() < 5);
+        }
+
+        // Nothing happened since, so there is no reason for another pass.
+        assert_eq!(scheduler.run_slice(&engine).unwrap(), SliceOutcome::NotDue);
+        engine.search("anything").unwrap();
+        assert_eq!(scheduler.run_slice(&engine).unwrap(), SliceOutcome::Yielded);
+    }
+
+    #[test]
+    fn test_scheduler_yields_to_an_incoming_search() {
+        let engine = idle_engine();
+        let mut scheduler = MaintenanceScheduler::new(engine.config());
+        assert_eq!(scheduler.run_slice(&engine).unwrap(), SliceOutcome::Yielded);
+
+        let search = engine.activity().begin_operation();
+        assert_eq!(
+            scheduler.run_slice(&engine).unwrap(),
+            SliceOutcome::Preempted
+        );
+        let ran: Vec<_> = engine
+            .maintenance_status()
+            .unwrap()
+            .into_iter()
+            .filter(|status| status.last_run.is_some())
+            .map(|status| status.task)
+            .collect();
+        assert_eq!(ran, vec![MaintenanceTask::ALL[0]]);
+
+        drop(search);
+        while scheduler.run_slice(&engine).unwrap() == SliceOutcome::Yielded {}
+        assert!(engine
+            .maintenance_status()
+            .unwrap()
+            .iter()
+            .all(|status| status.last_run.is_some()));
+    }
+
+    #[test]
+    fn test_scheduler_waits_for_idle_and_respects_settings() {
+        let busy = SearchEngine::in_memory_with_config(
+            SearchConfigBuilder::new().idle_threshold_secs(3600).build(),
+        )
+        .unwrap();
+        let mut scheduler = MaintenanceScheduler::new(busy.config());
+        assert_eq!(scheduler.run_slice(&busy).unwrap(), SliceOutcome::NotDue);
+
+        let config = SearchConfigBuilder::new()
+            .idle_threshold_secs(0)
+            .maintenance_slice_ms(10_000)
+            .maintenance_window("Mon 09:00-10:00".parse().unwrap())
+            .disabled_maintenance_tasks(vec![MaintenanceTask::Analyze])
+            .build();
+        let engine = SearchEngine::in_memory_with_config(config).unwrap();
+        let mut scheduler = MaintenanceScheduler::new(engine.config());
+        assert_eq!(
+            scheduler.run_slice_at(&engine, at(0, 9, 30)).unwrap(),
+            SliceOutcome::NotDue
+        );
+        assert_eq!(
+            scheduler.run_slice_at(&engine, at(0, 10, 0)).unwrap(),
+            SliceOutcome::Finished
+        );
+
+        let status = engine.maintenance_status().unwrap();
+        let analyze = status
+            .iter()
+            .find(|status| status.task == MaintenanceTask::Analyze)
+            .unwrap();
+        assert!(!analyze.enabled);
+        assert_eq!(analyze.last_run, None);
+        assert_eq!(
+            status
+                .iter()
+                .filter(|status| status.last_run.is_some())
+                .count(),
+            MaintenanceTask::ALL.len() - 1
+        );
+    }
+}
@@ -0,0 +1,300 @@
+use crate::core::types::MatchMode;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Counters shared by the engine's components.
+///
+/// Every update is a relaxed atomic add, so recording is cheap enough for hot paths. The
+/// counters are independent; a snapshot may observe one operation half recorded.
+#[derive(Debug, Default)]
+pub struct EngineCounters {
+    searches: [AtomicU64; 5],
+    search_time_us: AtomicU64,
+    last_search_us: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    bloom_filter_hits: AtomicU64,
+    plan_cache_hits: AtomicU64,
+    plan_cache_misses: AtomicU64,
+    miss_cache_hits: AtomicU64,
+    miss_cache_misses: AtomicU64,
+    name_index_hits: AtomicU64,
+    name_index_misses: AtomicU64,
+    index_operations: AtomicU64,
+    last_index_us: AtomicU64,
+    watch_events: AtomicU64,
+    operations_begun: AtomicU64,
+    operations_running: AtomicU64,
+    /// Milliseconds since the Unix epoch when the last operation ended.
+    last_active_ms: AtomicU64,
+}
+
+impl EngineCounters {
+    pub fn new() -> Self {
+        let counters = Self::default();
+        counters.last_active_ms.store(now_ms(), Ordering::Relaxed);
+        counters
+    }
+
+    /// Marks a search, index run or watch event as running until the guard is dropped, so
+    /// background maintenance stays out of its way.
+    pub fn begin_operation(&self) -> ActiveOperation<'_> {
+        self.operations_begun.fetch_add(1, Ordering::Relaxed);
+        self.operations_running.fetch_add(1, Ordering::Relaxed);
+        ActiveOperation { counters: self }
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.operations_running.load(Ordering::Relaxed) > 0
+    }
+
+    /// Time since the last operation ended, or since the counters were created; zero while
+    /// one is running.
+    pub fn idle_for(&self) -> Duration {
+        if self.is_busy() {
+            return Duration::ZERO;
+        }
+        let since = self.last_active_ms.load(Ordering::Relaxed);
+        Duration::from_millis(now_ms().saturating_sub(since))
+    }
+
+    /// Operations begun so far, so callers can tell whether any happened in between.
+    pub fn operations_begun(&self) -> u64 {
+        self.operations_begun.load(Ordering::Relaxed)
+    }
+
+    pub fn record_search(&self, mode: MatchMode, duration: Duration) {
+        let micros = duration_micros(duration);
+        self.searches[mode_slot(mode)].fetch_add(1, Ordering::Relaxed);
+        self.search_time_us.fetch_add(micros, Ordering::Relaxed);
+        self.last_search_us.store(micros, Ordering::Relaxed);
+    }
+
+    pub fn record_cache_lookup(&self, hit: bool) {
+        let counter = if hit { &self.cache_hits } else { &self.cache_misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_plan_lookup(&self, hit: bool) {
+        let counter = if hit {
+            &self.plan_cache_hits
+        } else {
+            &self.plan_cache_misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_miss_cache_lookup(&self, hit: bool) {
+        let counter = if hit {
+            &self.miss_cache_hits
+        } else {
+            &self.miss_cache_misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_name_index_lookup(&self, hit: bool) {
+        let counter = if hit {
+            &self.name_index_hits
+        } else {
+            &self.name_index_misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_bloom_filter_hit(&self) {
+        self.bloom_filter_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_index(&self, duration: Duration) {
+        self.index_operations.fetch_add(1, Ordering::Relaxed);
+        self.last_index_us.store(duration_micros(duration), Ordering::Relaxed);
+    }
+
+    pub fn record_watch_event(&self) {
+        self.watch_events.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Reads every counter. Pool usage is not tracked here and is left at zero.
+    pub fn snapshot(&self) -> EngineMetrics {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let searches_by_mode = SearchModeCounts {
+            exact: load(&self.searches[mode_slot(MatchMode::Exact)]),
+            case_insensitive: load(&self.searches[mode_slot(MatchMode::CaseInsensitive)]),
+            fuzzy: load(&self.searches[mode_slot(MatchMode::Fuzzy)]),
+            regex: load(&self.searches[mode_slot(MatchMode::Regex)]),
+            glob: load(&self.searches[mode_slot(MatchMode::Glob)]),
+        };
+        let total_searches = searches_by_mode.total();
+        let avg_search_time_ms = if total_searches == 0 {
+            0.0
+        } else {
+            micros_to_ms(load(&self.search_time_us)) / total_searches as f64
+        };
+
+        EngineMetrics {
+            total_searches,
+            searches_by_mode,
+            avg_search_time_ms,
+            last_search_time_ms: micros_to_ms(load(&self.last_search_us)),
+            cache_hits: load(&self.cache_hits),
+            cache_misses: load(&self.cache_misses),
+            bloom_filter_hits: load(&self.bloom_filter_hits),
+            plan_cache_hits: load(&self.plan_cache_hits),
+            plan_cache_misses: load(&self.plan_cache_misses),
+            miss_cache_hits: load(&self.miss_cache_hits),
+            miss_cache_misses: load(&self.miss_cache_misses),
+            name_index_hits: load(&self.name_index_hits),
+            name_index_misses: load(&self.name_index_misses),
+            db_connections_in_use: 0,
+            db_connections_idle: 0,
+            index_operations: load(&self.index_operations),
+            last_index_time_ms: micros_to_ms(load(&self.last_index_us)),
+            watch_events_processed: load(&self.watch_events),
+        }
+    }
+}
+
+/// Ends an operation begun with [`EngineCounters::begin_operation`] when dropped.
+pub struct ActiveOperation<'a> {
+    counters: &'a EngineCounters,
+}
+
+impl Drop for ActiveOperation<'_> {
+    fn drop(&mut self) {
+        self.counters.last_active_ms.store(now_ms(), Ordering::Relaxed);
+        self.counters.operations_running.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis().min(u64::MAX as u128) as u64)
+}
+
+fn mode_slot(mode: MatchMode) -> usize {
+    match mode {
+        MatchMode::Exact => 0,
+        MatchMode::CaseInsensitive => 1,
+        MatchMode::Fuzzy => 2,
+        MatchMode::Regex => 3,
+        MatchMode::Glob => 4,
+    }
+}
+
+fn duration_micros(duration: Duration) -> u64 {
+    duration.as_micros().min(u64::MAX as u128) as u64
+}
+
+fn micros_to_ms(micros: u64) -> f64 {
+    micros as f64 / 1000.0
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct SearchModeCounts {
+    pub exact: u64,
+    pub case_insensitive: u64,
+    pub fuzzy: u64,
+    pub regex: u64,
+    pub glob: u64,
+}
+
+impl SearchModeCounts {
+    pub fn total(&self) -> u64 {
+        self.exact + self.case_insensitive + self.fuzzy + self.regex + self.glob
+    }
+}
+
+/// A point-in-time view of what the engine has done since it was created.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct EngineMetrics {
+    pub total_searches: u64,
+    pub searches_by_mode: SearchModeCounts,
+    pub avg_search_time_ms: f64,
+    /// Zero until the first search completes.
+    pub last_search_time_ms: f64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    pub bloom_filter_hits: u64,
+    /// Searches that reused a cached query plan, and those that had to build one.
+    pub plan_cache_hits: u64,
+    pub plan_cache_misses: u64,
+    /// Path lookups answered from the cache of recent misses without querying the index,
+    /// and those that had to query it.
+    pub miss_cache_hits: u64,
+    pub miss_cache_misses: u64,
+    /// Name and path lookups answered from the in-memory name index, and those left to the
+    /// index database because it was not current.
+    pub name_index_hits: u64,
+    pub name_index_misses: u64,
+    pub db_connections_in_use: usize,
+    pub db_connections_idle: usize,
+    /// Full and incremental index runs, counting each manifest root separately.
+    pub index_operations: u64,
+    /// Zero until the first index operation completes.
+    pub last_index_time_ms: f64,
+    pub watch_events_processed: u64,
+}
+
+impl EngineMetrics {
+    pub fn cache_hit_rate(&self) -> f32 {
+        let total = self.cache_hits + self.cache_misses;
+        if total == 0 {
+            return 0.0;
+        }
+        self.cache_hits as f32 / total as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_aggregates_searches_by_mode() {
+        let counters = EngineCounters::new();
+        assert_eq!(counters.snapshot(), EngineMetrics::default());
+
+        counters.record_search(MatchMode::Glob, Duration::from_millis(3));
+        counters.record_search(MatchMode::Glob, Duration::from_millis(1));
+        counters.record_search(MatchMode::Fuzzy, Duration::from_millis(2));
+        counters.record_cache_lookup(true);
+        counters.record_cache_lookup(false);
+        counters.record_cache_lookup(false);
+        counters.record_cache_lookup(false);
+
+        let metrics = counters.snapshot();
+        assert_eq!(metrics.total_searches, 3);
+        assert_eq!(metrics.searches_by_mode.glob, 2);
+        assert_eq!(metrics.searches_by_mode.fuzzy, 1);
+        assert_eq!(metrics.avg_search_time_ms, 2.0);
+        assert_eq!(metrics.last_search_time_ms, 2.0);
+        assert_eq!(metrics.cache_hit_rate(), 0.25);
+
+        let json = serde_json::to_value(&metrics).unwrap();
+        assert_eq!(json["searches_by_mode"]["glob"], 2);
+        assert_eq!(json["cache_misses"], 3);
+    }
+
+    #[test]
+    fn test_operations_mark_the_engine_busy() {
+        let counters = EngineCounters::new();
+        assert!(!counters.is_busy());
+
+        let search = counters.begin_operation();
+        let update = counters.begin_operation();
+        assert!(counters.is_busy());
+        assert_eq!(counters.idle_for(), Duration::ZERO);
+        drop(search);
+        assert!(counters.is_busy());
+        drop(update);
+
+        assert!(!counters.is_busy());
+        assert!(counters.idle_for() < Duration::from_secs(5));
+        assert_eq!(counters.operations_begun(), 2);
+    }
+}
@@ -1,9 +1,18 @@
 pub mod config;
 pub mod engine;
 pub mod error;
+pub mod maintenance;
+pub mod metrics;
+pub mod request;
 pub mod types;
 
-pub use config::{SearchConfig, SearchConfigBuilder};
-pub use engine::SearchEngine;
-pub use error::{Result, SearchError};
+pub use config::{
+    BatchErrorPolicy, CliTheme, ConfigError, ContentSampling, ContentSamplingRule, IndexManifest,
+    ManifestRoot, ProgressDelivery, QuotaEviction, SearchConfig, SearchConfigBuilder,
+};
+pub use engine::{SearchEngine, SearchEngineBuilder};
+pub use maintenance::{MaintenanceScheduler, MaintenanceWindow, SliceOutcome};
+pub use error::{ErrorCode, ErrorReport, IoResultExt, Result, SearchError};
+pub use metrics::{ActiveOperation, EngineCounters, EngineMetrics, SearchModeCounts};
+pub use request::RequestId;
 pub use types::*;
@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Longest id accepted from a client; anything longer is replaced with a generated one.
+const MAX_LEN: usize = 128;
+
+/// Correlates the log lines of one request across the server, the engine and the slow-query
+/// log.
+///
+/// Generated ids are UUIDs. Ids supplied by a client or proxy are kept as long as they are
+/// short and printable, so they can be written into logs as is.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RequestId(String);
+
+impl RequestId {
+    pub fn new() -> Self {
+        Self(uuid::Uuid::new_v4().to_string())
+    }
+
+    /// The id in `value` if it is fit for logs: 1 to 128 visible ASCII characters.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let valid = !value.is_empty()
+            && value.len() <= MAX_LEN
+            && value.chars().all(|c| c.is_ascii_graphic());
+        valid.then(|| Self(value.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for RequestId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_accepts_printable_ids_only() {
+        let generated = RequestId::new();
+        assert!(uuid::Uuid::parse_str(generated.as_str()).is_ok());
+        assert_ne!(generated, RequestId::new());
+
+        assert_eq!(
+            RequestId::parse(" req-42/edge ").map(|id| id.to_string()),
+            Some("req-42/edge".to_string())
+        );
+        for invalid in ["", "   ", "two words", "line\nbreak", "ünïcode"] {
+            assert_eq!(RequestId::parse(invalid), None, "{:?}", invalid);
+        }
+        assert!(RequestId::parse(&"x".repeat(MAX_LEN)).is_some());
+        assert!(RequestId::parse(&"x".repeat(MAX_LEN + 1)).is_none());
+    }
+}
@@ -1,6 +1,11 @@
+use crate::core::config::ManifestRoot;
+use crate::core::error::SearchError;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileEntry {
@@ -30,10 +35,11 @@ impl FileEntry {
             .unwrap_or("")
             .to_string();
 
+        // Lowercased, so `JPG` and `jpg` are one extension; the name keeps the casing.
         let extension = path
             .extension()
             .and_then(|e| e.to_str())
-            .map(|s| s.to_string());
+            .map(|s| s.to_ascii_lowercase());
 
         let parent_path = path.parent().map(|p| p.to_path_buf());
 
@@ -58,6 +64,64 @@ impl FileEntry {
             last_verified: now,
         }
     }
+
+    pub fn with_size(mut self, size: u64) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn with_modified_at(mut self, modified_at: DateTime<Utc>) -> Self {
+        self.modified_at = Some(modified_at);
+        self
+    }
+
+    pub fn with_kind(mut self, kind: EntryKind) -> Self {
+        self.is_directory = kind == EntryKind::Directory;
+        self.is_symlink = kind == EntryKind::Symlink;
+        self
+    }
+
+    pub fn with_mime_type(mut self, mime_type: impl Into<String>) -> Self {
+        self.mime_type = Some(mime_type.into());
+        self
+    }
+
+    /// The row id, once the entry has been indexed.
+    pub fn id(&self) -> Option<i64> {
+        self.id
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn extension(&self) -> Option<&str> {
+        self.extension.as_deref()
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn modified_at(&self) -> Option<DateTime<Utc>> {
+        self.modified_at
+    }
+
+    pub fn kind(&self) -> EntryKind {
+        EntryKind::of(self)
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.is_hidden
+    }
+
+    pub fn mime_type(&self) -> Option<&str> {
+        self.mime_type.as_deref()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -68,15 +132,123 @@ pub struct SearchResult {
     pub matches: Vec<MatchLocation>,
 }
 
+impl SearchResult {
+    pub fn new(file: FileEntry, score: f64) -> Self {
+        Self {
+            file,
+            score,
+            snippet: None,
+            matches: Vec::new(),
+        }
+    }
+
+    pub fn with_snippet(mut self, snippet: impl Into<String>) -> Self {
+        self.snippet = Some(snippet.into());
+        self
+    }
+
+    pub fn with_matches(mut self, matches: Vec<MatchLocation>) -> Self {
+        self.matches = matches;
+        self
+    }
+
+    pub fn file(&self) -> &FileEntry {
+        &self.file
+    }
+
+    pub fn score(&self) -> f64 {
+        self.score
+    }
+
+    pub fn snippet(&self) -> Option<&str> {
+        self.snippet.as_deref()
+    }
+
+    /// Lines matching a content query, when context lines were requested.
+    pub fn matches(&self) -> &[MatchLocation] {
+        &self.matches
+    }
+}
+
+/// One page of ranked results, with how many matched in all.
+#[derive(Debug, Clone, Default)]
+pub struct SearchOutcome {
+    pub results: Vec<SearchResult>,
+    /// Results found before `offset` and `max_results` were applied.
+    pub total_matched: usize,
+    /// Whether `total_matched` only counts the matches among the candidates a search
+    /// looked at, as there were more than it looks at and they can't be counted in SQL.
+    pub total_estimated: bool,
+}
+
+/// Where a match is. Line and column numbers start at 1, and columns and lengths count
+/// characters. A name or path match is on line 1 of its `context`, the name or path itself.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MatchLocation {
     pub line: usize,
     pub column: usize,
     pub length: usize,
+    /// The matching line with the lines around it, joined by `\n`.
     pub context: String,
+    /// The line number of the first line in `context`.
+    #[serde(default)]
+    pub context_start_line: usize,
+    #[serde(default)]
+    pub field: MatchField,
 }
 
-#[derive(Debug, Clone)]
+/// What a [`MatchLocation`] was found in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchField {
+    #[default]
+    Content,
+    Name,
+    Path,
+}
+
+impl MatchLocation {
+    /// A match of `length` characters at character `offset` of a name or path.
+    pub fn in_text(field: MatchField, text: &str, offset: usize, length: usize) -> Self {
+        Self {
+            line: 1,
+            column: offset + 1,
+            length,
+            context: text.to_string(),
+            context_start_line: 1,
+            field,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum ChangeRecord {
+    Upsert {
+        entry: FileEntry,
+    },
+    Delete {
+        path: PathBuf,
+        deleted_at: DateTime<Utc>,
+    },
+    Cursor {
+        cursor: DateTime<Utc>,
+        /// Sensitive entries and deletions left out of the export.
+        #[serde(default)]
+        omitted_sensitive: usize,
+    },
+}
+
+/// How a change export ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChangeExport {
+    /// Where the next export picks up.
+    pub cursor: DateTime<Utc>,
+    /// Sensitive entries and deletions left out.
+    pub omitted_sensitive: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct Progress {
     pub current: usize,
     pub total: usize,
@@ -86,8 +258,9 @@ pub struct Progress {
 
 impl Progress {
     pub fn new(current: usize, total: usize, message: String) -> Self {
+        // total = 0 means the total is unknown (e.g. a streaming walk), not that nothing is left.
         let percentage = if total > 0 {
-            (current as f64 / total as f64) * 100.0
+            (current as f64 / total as f64 * 100.0).min(100.0)
         } else {
             0.0
         };
@@ -99,9 +272,24 @@ impl Progress {
             percentage,
         }
     }
+
+    /// The last update of a finished run: `count` of `count`, at 100%.
+    pub fn complete(count: usize, message: String) -> Self {
+        Self {
+            current: count,
+            total: count,
+            message,
+            percentage: 100.0,
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.total > 0 && self.current >= self.total
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum MatchMode {
     Exact,
     CaseInsensitive,
@@ -110,7 +298,8 @@ pub enum MatchMode {
     Glob,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SearchScope {
     Name,
     Path,
@@ -118,30 +307,558 @@ pub enum SearchScope {
     All,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    File,
+    Directory,
+    Symlink,
+}
+
+impl EntryKind {
+    pub fn of(entry: &FileEntry) -> Self {
+        if entry.is_symlink {
+            EntryKind::Symlink
+        } else if entry.is_directory {
+            EntryKind::Directory
+        } else {
+            EntryKind::File
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEventType {
+    Created,
+    Modified,
+    Deleted,
+    Renamed,
+}
+
+impl AuditEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEventType::Created => "created",
+            AuditEventType::Modified => "modified",
+            AuditEventType::Deleted => "deleted",
+            AuditEventType::Renamed => "renamed",
+        }
+    }
+
+    pub fn parse(input: &str) -> Option<Self> {
+        match input.trim().to_lowercase().as_str() {
+            "created" | "create" => Some(AuditEventType::Created),
+            "modified" | "modify" => Some(AuditEventType::Modified),
+            "deleted" | "delete" | "removed" => Some(AuditEventType::Deleted),
+            "renamed" | "rename" | "moved" => Some(AuditEventType::Renamed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub id: Option<i64>,
+    pub path: PathBuf,
+    pub event_type: AuditEventType,
+    pub occurred_at: DateTime<Utc>,
+    pub size: Option<u64>,
+    pub file_hash: Option<String>,
+    pub watch_id: Option<String>,
+}
+
+/// Ordering applied to listing-style output such as smart folders.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ListingSort {
+    #[default]
+    ModifiedDesc,
+    ModifiedAsc,
+    NameAsc,
+    SizeDesc,
+}
+
+impl ListingSort {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ListingSort::ModifiedDesc => "modified_desc",
+            ListingSort::ModifiedAsc => "modified_asc",
+            ListingSort::NameAsc => "name_asc",
+            ListingSort::SizeDesc => "size_desc",
+        }
+    }
+
+    pub fn parse(input: &str) -> Option<Self> {
+        match input.trim().to_lowercase().as_str() {
+            "modified_desc" | "modified" | "newest" => Some(ListingSort::ModifiedDesc),
+            "modified_asc" | "oldest" => Some(ListingSort::ModifiedAsc),
+            "name_asc" | "name" => Some(ListingSort::NameAsc),
+            "size_desc" | "size" | "largest" => Some(ListingSort::SizeDesc),
+            _ => None,
+        }
+    }
+
+    // Ties fall back to the path so repeated runs list entries in the same order.
+    pub fn sort(&self, entries: &mut [FileEntry]) {
+        entries.sort_by(|a, b| {
+            let order = match self {
+                ListingSort::ModifiedDesc => b.modified_at.cmp(&a.modified_at),
+                ListingSort::ModifiedAsc => a.modified_at.cmp(&b.modified_at),
+                ListingSort::NameAsc => a.name.cmp(&b.name),
+                ListingSort::SizeDesc => b.size.cmp(&a.size),
+            };
+            order.then_with(|| a.path.cmp(&b.path))
+        });
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedSearch {
+    pub name: String,
+    pub query: String,
+    /// Listing order; `None` uses the listing default of newest first.
+    pub sort: Option<ListingSort>,
+    pub created_at: DateTime<Utc>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_result_count: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Pagination {
+    pub limit: Option<usize>,
+    pub offset: usize,
+}
+
+/// One page of a saved search, run as if it were a directory.
+#[derive(Debug, Clone, Serialize)]
+pub struct Listing {
+    pub name: String,
+    pub entries: Vec<FileEntry>,
+    pub total: usize,
+    pub offset: usize,
+    pub sort: ListingSort,
+    /// Changes whenever the page contents change; stable otherwise.
+    pub etag: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SizeFilter {
     Exact(u64),
     Range(u64, u64),
     GreaterThan(u64),
     LessThan(u64),
+    AtLeast(u64),
+    AtMost(u64),
+}
+
+/// A bound on an image's pixel width or height, from a `width:` or `height:` token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DimensionBound {
+    Exact(u32),
+    Range(u32, u32),
+    GreaterThan(u32),
+    LessThan(u32),
 }
 
+impl DimensionBound {
+    pub fn contains(&self, value: u32) -> bool {
+        match *self {
+            DimensionBound::Exact(exact) => value == exact,
+            DimensionBound::Range(min, max) => (min..=max).contains(&value),
+            DimensionBound::GreaterThan(min) => value > min,
+            DimensionBound::LessThan(max) => value < max,
+        }
+    }
+}
+
+/// A bound on how many directories deep an entry sits, from a `depth:` token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DepthFilter {
+    Exact(usize),
+    GreaterThan(usize),
+    LessThan(usize),
+}
+
+impl DepthFilter {
+    pub fn contains(&self, depth: usize) -> bool {
+        match *self {
+            DepthFilter::Exact(exact) => depth == exact,
+            DepthFilter::GreaterThan(min) => depth > min,
+            DepthFilter::LessThan(max) => depth < max,
+        }
+    }
+}
+
+/// Pixel bounds an image must satisfy. Entries without stored dimensions never match a
+/// non-empty filter.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DimensionFilter {
+    pub width: Option<DimensionBound>,
+    pub height: Option<DimensionBound>,
+}
+
+impl DimensionFilter {
+    pub fn is_empty(&self) -> bool {
+        self.width.is_none() && self.height.is_none()
+    }
+
+    pub fn matches(&self, dimensions: ImageDimensions) -> bool {
+        self.width.map_or(true, |bound| bound.contains(dimensions.width))
+            && self.height.map_or(true, |bound| bound.contains(dimensions.height))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageDimensions {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A structured field read from an email's headers or an HTML page's head.
+///
+/// Emails give `from`, `to`, `subject` and `date`, with `date` in RFC 3339 UTC; HTML pages
+/// give `title` and `description`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocumentField {
+    pub name: String,
+    pub value: String,
+}
+
+impl DocumentField {
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+}
+
+/// A `subject:`, `from:`, `to:` or `title:` filter; the field must contain `value`,
+/// ignoring case.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FieldFilter {
+    pub name: String,
+    pub value: String,
+}
+
+/// What the `#!` line of an extension-less file says about it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScriptInfo {
+    /// Interpreter basename, e.g. `python3`.
+    pub interpreter: String,
+    /// `bash`, `python`, `perl`, `node` and so on; interpreters outside the mapping table
+    /// use their own name.
+    pub language: String,
+    /// Any execute permission bit is set. Always `false` off unix.
+    pub is_executable: bool,
+}
+
+/// Owner, group and permission bits of a file or directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ownership {
+    pub uid: u32,
+    pub gid: u32,
+    /// The low 12 bits of `st_mode`.
+    pub mode: u32,
+}
+
+impl Ownership {
+    pub const READ: u32 = 0o4;
+    pub const EXECUTE: u32 = 0o1;
+
+    /// Whether the user `uid` in groups `gids` has all of `access` (`READ`, `EXECUTE`), as the
+    /// kernel decides: root always, else only the owner, group or other bits, whichever
+    /// class matches first.
+    pub fn allows(&self, uid: u32, gids: &[u32], access: u32) -> bool {
+        let bits = if uid == 0 {
+            return true;
+        } else if uid == self.uid {
+            self.mode >> 6
+        } else if gids.contains(&self.gid) {
+            self.mode >> 3
+        } else {
+            self.mode
+        };
+        bits & access == access
+    }
+}
+
+/// Ownership recorded for an indexed entry and the directory holding it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilePermissions {
+    pub entry: Ownership,
+    /// `None` when the directory could not be read, e.g. for an indexed root.
+    pub parent: Option<Ownership>,
+}
+
+impl FilePermissions {
+    /// Whether the user can see the entry's name, i.e. list and enter its directory.
+    pub fn visible_to(&self, uid: u32, gids: &[u32]) -> bool {
+        self.parent.map_or(true, |parent| {
+            parent.allows(uid, gids, Ownership::READ | Ownership::EXECUTE)
+        })
+    }
+
+    /// Whether the user can also read the entry itself.
+    pub fn readable_by(&self, uid: u32, gids: &[u32]) -> bool {
+        self.visible_to(uid, gids) && self.entry.allows(uid, gids, Ownership::READ)
+    }
+}
+
+/// A JPEG thumbnail of an indexed image.
 #[derive(Debug, Clone)]
+pub struct Thumbnail {
+    pub bytes: Vec<u8>,
+    /// Changes whenever the source file or the requested size does; usable as an ETag.
+    pub key: String,
+    /// Whether the thumbnail came from the cache rather than being generated.
+    pub cached: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum DateFilter {
     After(DateTime<Utc>),
     Before(DateTime<Utc>),
     Between(DateTime<Utc>, DateTime<Utc>),
     On(DateTime<Utc>),
+    OnOrAfter(DateTime<Utc>),
+    OnOrBefore(DateTime<Utc>),
+    /// Modified further in the future than clock skew explains.
+    Future,
+    /// Modified before 1980, which usually means the real time was lost.
+    Ancient,
 }
 
-#[derive(Debug, Clone)]
+/// The timestamp a `DateFilter` compares against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DateField {
+    #[default]
+    Modified,
+    Created,
+    Accessed,
+}
+
+impl DateField {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DateField::Modified => "modified",
+            DateField::Created => "created",
+            DateField::Accessed => "accessed",
+        }
+    }
+
+    /// The entry's timestamp for this field. Filesystems that record no birth or access time
+    /// leave those unset, so they fall back to the modification time.
+    pub fn timestamp(&self, entry: &FileEntry) -> Option<DateTime<Utc>> {
+        match self {
+            DateField::Modified => entry.modified_at,
+            DateField::Created => entry.created_at.or(entry.modified_at),
+            DateField::Accessed => entry.accessed_at.or(entry.modified_at),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
 pub struct IndexStats {
     pub total_files: usize,
     pub total_directories: usize,
     pub total_size: u64,
     pub indexed_files: usize,
+    /// Entries indexed as links into a package-manager store rather than as files of their own.
+    pub store_aliases: usize,
     pub last_update: DateTime<Utc>,
     pub index_size: u64,
+    /// Set when the engine enforces `max_index_size_bytes`.
+    pub quota: Option<QuotaUsage>,
+    /// Quick-indexed roots whose content is still being filled in, set by the engine.
+    pub enrichment: Vec<EnrichmentProgress>,
+    /// The writer currently holding the index's write lease, set by the engine.
+    pub write_lease: Option<WriteLease>,
+    /// When each maintenance task last completed, set by the engine.
+    pub maintenance: Vec<MaintenanceStatus>,
+    /// The in-memory name index, set by the engine when `fast_name_index` is on.
+    pub name_index: Option<NameIndexUsage>,
+}
+
+/// The running totals behind [`IndexStats`]. They are kept in the index's settings and
+/// adjusted by every write, so reading them costs no table scan.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct StatsCounters {
+    pub files: i64,
+    pub directories: i64,
+    /// Bytes in files, leaving out store aliases.
+    pub total_size: i64,
+    /// Files with indexed content.
+    pub content_indexed: i64,
+}
+
+/// Stored counters that disagree with a recount of the index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct StatsDrift {
+    pub stored: StatsCounters,
+    pub counted: StatsCounters,
+}
+
+/// The advisory lease a process holds while it writes to an index, so writers in other
+/// processes take turns instead of interleaving batches.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WriteLease {
+    /// Unique per engine, so two engines in one process are told apart.
+    pub holder_id: String,
+    pub pid: u32,
+    pub hostname: String,
+    pub acquired_at: DateTime<Utc>,
+    pub heartbeat_at: DateTime<Utc>,
+}
+
+impl WriteLease {
+    /// Whether the holder has stopped heartbeating, e.g. because it crashed.
+    pub fn is_stale(&self, stale_after: chrono::Duration, now: DateTime<Utc>) -> bool {
+        now - self.heartbeat_at > stale_after
+    }
+}
+
+/// Upkeep run on an index by automatic maintenance and `filesearch maintain`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaintenanceTask {
+    /// Merges full-text index segments a few pages at a time.
+    FtsMerge,
+    /// Refreshes the statistics the query planner picks indexes by.
+    Analyze,
+    /// Copies the write-ahead log into the database file without waiting for readers.
+    Checkpoint,
+    /// Deletes stored content that no indexed file refers to any more.
+    PruneOrphans,
+    /// Deletes access-log entries older than `access_log_retention_days`.
+    AccessLogRetention,
+}
+
+impl MaintenanceTask {
+    /// Every task, in the order a maintenance pass runs them.
+    pub const ALL: [MaintenanceTask; 5] = [
+        MaintenanceTask::PruneOrphans,
+        MaintenanceTask::AccessLogRetention,
+        MaintenanceTask::FtsMerge,
+        MaintenanceTask::Analyze,
+        MaintenanceTask::Checkpoint,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MaintenanceTask::FtsMerge => "fts_merge",
+            MaintenanceTask::Analyze => "analyze",
+            MaintenanceTask::Checkpoint => "checkpoint",
+            MaintenanceTask::PruneOrphans => "prune_orphans",
+            MaintenanceTask::AccessLogRetention => "access_log_retention",
+        }
+    }
+}
+
+/// When a maintenance task last completed; `None` if it never has on this index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MaintenanceStatus {
+    pub task: MaintenanceTask,
+    /// False when the task is listed in `disabled_maintenance_tasks`.
+    pub enabled: bool,
+    pub last_run: Option<DateTime<Utc>>,
+}
+
+/// What to do when another process holds the write lease.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaseWait {
+    /// Wait for it to be released or go stale, failing after the timeout.
+    Wait(std::time::Duration),
+    /// Fail with `SearchError::WriteLeaseHeld` right away.
+    NoWait,
+    /// Take it over even from a live holder. Only for holders known to be stuck.
+    Steal,
+}
+
+/// How far enrichment has got through the files a quick build queued under `root`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EnrichmentProgress {
+    pub root: PathBuf,
+    /// Files queued by quick builds of the root.
+    pub total: usize,
+    /// Queued files whose content has not been read yet.
+    pub pending: usize,
+}
+
+impl EnrichmentProgress {
+    pub fn enriched(&self) -> usize {
+        self.total.saturating_sub(self.pending)
+    }
+
+    pub fn percent(&self) -> f64 {
+        if self.total == 0 {
+            100.0
+        } else {
+            self.enriched() as f64 / self.total as f64 * 100.0
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct EnrichmentStats {
+    /// Files whose content was read in this run.
+    pub enriched: usize,
+    /// Files still pending afterwards, within the requested root.
+    pub remaining: usize,
+    /// The run was cancelled before the queue was empty.
+    pub cancelled: bool,
+}
+
+/// What the in-memory name index holds and costs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct NameIndexUsage {
+    /// Entries loaded; zero until the first load or build finishes.
+    pub entries: usize,
+    pub memory_bytes: u64,
+    /// Whether it matches the index, so searches are answered from it.
+    pub current: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct QuotaUsage {
+    pub limit_bytes: u64,
+    /// The database file plus its write-ahead log.
+    pub used_bytes: u64,
+}
+
+impl QuotaUsage {
+    pub fn usage_percent(&self) -> f64 {
+        if self.limit_bytes == 0 {
+            return 100.0;
+        }
+        self.used_bytes as f64 / self.limit_bytes as f64 * 100.0
+    }
+}
+
+/// Features the engine can actually serve, derived from its config and probes of the index.
+///
+/// Clients should treat unknown fields as absent; new capabilities are added over time.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct Capabilities {
+    /// Content indexing is enabled and the full-text index exists.
+    pub content_search: bool,
+    pub fuzzy_search: bool,
+    pub access_tracking: bool,
+    /// Whether indexed entries carry a content hash.
+    pub file_hashes: bool,
+    /// The platform backend used by `watch`, e.g. `inotify` or `poll`.
+    pub watch_backend: String,
+    pub read_only: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -149,6 +866,8 @@ pub enum ExclusionRuleType {
     Glob,
     Regex,
     Path,
+    /// A glob files must match to be indexed, once any inclusion rule exists.
+    Include,
 }
 
 #[derive(Debug, Clone)]
@@ -163,6 +882,213 @@ pub struct ContentPreview {
     pub word_count: usize,
     pub line_count: usize,
     pub encoding: String,
+    pub content_hash: String,
+    /// Whether only part of the file was analyzed, in which case the counts cover the
+    /// sample rather than the whole file.
+    pub sampled: bool,
+}
+
+/// A chunk of a file's text that matched a full-text query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkHit {
+    pub chunk_index: usize,
+    /// Where the chunk starts in the file's text, in bytes.
+    pub byte_offset: usize,
+    /// The chunk's length in bytes.
+    pub length: usize,
+    /// FTS5's bm25 rank of the chunk; lower is better.
+    pub rank: f64,
+}
+
+/// Offset applied to raw timestamps under a root whose filesystem stores local time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TimestampAdjustment {
+    pub root: PathBuf,
+    /// The filesystem that triggered the adjustment; `None` when the root was matched by
+    /// `assume_local_time_paths`.
+    pub filesystem: Option<String>,
+    /// Local time minus UTC on the host that indexed the root. Later runs reuse it, so
+    /// re-indexing from another timezone produces the same stored timestamps.
+    pub utc_offset_secs: i32,
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// Index entries saved before a destructive operation, restorable with `undo_last`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UndoSnapshot {
+    /// The operation that was about to run, e.g. `clear_index`.
+    pub operation: String,
+    pub created_at: DateTime<Utc>,
+    pub path: PathBuf,
+    /// Compressed size on disk.
+    pub size_bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct UndoReport {
+    pub snapshot: UndoSnapshot,
+    /// Entries written back to the index.
+    pub restored: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedRoot {
+    pub path: PathBuf,
+    pub options: ManifestRoot,
+    pub file_count: usize,
+    pub indexed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedEntry {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+/// Why a file was indexed without its content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentSkipReason {
+    /// Analysis ran past `content_analysis_timeout_ms`.
+    TimedOut,
+    /// The path is a FIFO, socket or device.
+    NotRegularFile,
+}
+
+impl ContentSkipReason {
+    /// The reason behind a content analysis error, if it is one that gets reported.
+    pub fn of(error: &SearchError) -> Option<Self> {
+        match error {
+            SearchError::ContentTimeout { .. } => Some(ContentSkipReason::TimedOut),
+            SearchError::NotRegularFile(_) => Some(ContentSkipReason::NotRegularFile),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentSkipReason::TimedOut => "timed out",
+            ContentSkipReason::NotRegularFile => "not a regular file",
+        }
+    }
+}
+
+/// A file whose metadata was indexed but whose content was not analyzed.
+#[derive(Debug, Clone, Serialize)]
+pub struct ContentSkip {
+    pub path: PathBuf,
+    pub reason: ContentSkipReason,
+}
+
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct IndexReport {
+    pub indexed: usize,
+    pub skipped: Vec<SkippedEntry>,
+    pub failed_batches: usize,
+    /// Indexed files left out of content search, with the reason.
+    pub content_skipped: Vec<ContentSkip>,
+    /// Files that changed while their content was read, so their stored size, times and
+    /// content may not belong together. The next update reads them again.
+    pub possibly_inconsistent: Vec<PathBuf>,
+    /// How the run's batches were sized.
+    pub batching: BatchStats,
+}
+
+impl IndexReport {
+    pub fn is_complete(&self) -> bool {
+        self.skipped.is_empty()
+    }
+}
+
+/// Batches written by an index run and how their size was adapted to commit latency.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct BatchStats {
+    pub batches: usize,
+    pub entries: usize,
+    /// Times the batch size was grown or shrunk.
+    pub adjustments: usize,
+    /// The size the next batch would have had.
+    pub last_size: usize,
+}
+
+impl BatchStats {
+    pub fn avg_batch_size(&self) -> f64 {
+        if self.batches == 0 {
+            0.0
+        } else {
+            self.entries as f64 / self.batches as f64
+        }
+    }
+}
+
+/// Outcome of rebuilding the full-text index from stored content.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct FtsRebuildReport {
+    /// Distinct content blobs written to the new full-text table.
+    pub blobs: usize,
+    /// Files whose content is searchable again.
+    pub files: usize,
+    /// Files with a content record but no stored text to index.
+    pub skipped: usize,
+}
+
+/// A finished copy of the index taken with SQLite's online backup.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct BackupReport {
+    pub path: PathBuf,
+    /// Database pages copied.
+    pub pages: usize,
+    pub size_bytes: u64,
+}
+
+/// Outcome of replacing an index's contents with a backup.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct RestoreReport {
+    /// Database pages copied.
+    pub pages: usize,
+    /// Schema version of the backup; older ones are migrated when the index is next opened.
+    pub schema_version: i32,
+}
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct RootIndexReport {
+    pub path: PathBuf,
+    pub indexed: usize,
+    pub skipped: usize,
+    pub duration: Duration,
+    pub error: Option<String>,
+}
+
+impl RootIndexReport {
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
 }
 
 pub type ProgressCallback = Box<dyn Fn(Progress) + Send + Sync>;
+
+/// Asks a long-running operation to stop at its next checkpoint. Clones share the flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+pub type RootProgressCallback = Arc<dyn Fn(&Path, Progress) + Send + Sync>;
@@ -1,13 +1,66 @@
-use crate::core::types::{DateFilter, FileEntry};
-use chrono::{DateTime, Duration, Utc};
+use crate::core::types::{DateField, DateFilter, FileEntry};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
 
-pub fn apply_date_filter(entry: &FileEntry, filter: &DateFilter) -> bool {
-    let modified = entry.modified_at.unwrap_or_else(Utc::now);
+/// How far ahead of the local clock a timestamp may be before it counts as skewed.
+pub const CLOCK_SKEW_TOLERANCE_SECS: i64 = 300;
+
+/// Timestamps before this year predate practically every real file and usually come from a
+/// reset clock or an archiver that dropped the original time.
+pub const ANCIENT_BEFORE_YEAR: i32 = 1980;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampSanity {
+    Plausible,
+    Future,
+    Ancient,
+}
+
+impl TimestampSanity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimestampSanity::Plausible => "plausible",
+            TimestampSanity::Future => "future",
+            TimestampSanity::Ancient => "ancient",
+        }
+    }
+}
+
+pub fn ancient_cutoff() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(ANCIENT_BEFORE_YEAR, 1, 1, 0, 0, 0).unwrap()
+}
+
+pub fn timestamp_sanity(date: DateTime<Utc>, now: DateTime<Utc>) -> TimestampSanity {
+    if date > now + Duration::seconds(CLOCK_SKEW_TOLERANCE_SECS) {
+        TimestampSanity::Future
+    } else if date < ancient_cutoff() {
+        TimestampSanity::Ancient
+    } else {
+        TimestampSanity::Plausible
+    }
+}
+
+/// Filters on the stored timestamp named by `field`.
+///
+/// Comparisons use the raw value, so a file with a future mtime matches every `After` filter
+/// and only matches `On` for the day its clock claims. Use `Future` and `Ancient` to find
+/// entries whose timestamps cannot be trusted; entries without one never match those.
+/// Missing creation and access times fall back to the modification time, as in
+/// [`DateField::timestamp`].
+pub fn apply_date_filter(entry: &FileEntry, filter: &DateFilter, field: DateField) -> bool {
+    let timestamp = field.timestamp(entry);
+    let is = |expected| {
+        timestamp.is_some_and(|date| timestamp_sanity(date, Utc::now()) == expected)
+    };
+    let value = timestamp.unwrap_or_else(Utc::now);
 
     match filter {
-        DateFilter::After(date) => modified > *date,
-        DateFilter::Before(date) => modified < *date,
-        DateFilter::Between(start, end) => modified >= *start && modified <= *end,
+        DateFilter::After(date) => value > *date,
+        DateFilter::Before(date) => value < *date,
+        DateFilter::OnOrAfter(date) => value >= *date,
+        DateFilter::OnOrBefore(date) => value <= *date,
+        DateFilter::Between(start, end) => value >= *start && value <= *end,
         DateFilter::On(date) => {
             let start_of_day = date.date_naive().and_hms_opt(0, 0, 0).unwrap();
             let end_of_day = date.date_naive().and_hms_opt(23, 59, 59).unwrap();
@@ -15,11 +68,41 @@ pub fn apply_date_filter(entry: &FileEntry, filter: &DateFilter) -> bool {
             let start = DateTime::<Utc>::from_naive_utc_and_offset(start_of_day, Utc);
             let end = DateTime::<Utc>::from_naive_utc_and_offset(end_of_day, Utc);
 
-            modified >= start && modified <= end
+            value >= start && value <= end
         }
+        DateFilter::Future => is(TimestampSanity::Future),
+        DateFilter::Ancient => is(TimestampSanity::Ancient),
     }
 }
 
+/// Parses an ISO date (`2024-06-01`) or datetime (`2024-06-01T14:30`) in UTC, falling back
+/// to the relative forms of [`parse_relative_date`]. A bare date means its first second.
+pub fn parse_date(input: &str) -> Option<DateTime<Utc>> {
+    parse_absolute_date(input, false).or_else(|| parse_relative_date(input))
+}
+
+/// Like [`parse_date`], but a bare date means its last second, for the end of a range.
+pub fn parse_end_date(input: &str) -> Option<DateTime<Utc>> {
+    parse_absolute_date(input, true).or_else(|| parse_relative_date(input))
+}
+
+fn parse_absolute_date(input: &str, end_of_day: bool) -> Option<DateTime<Utc>> {
+    let input = input.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        let time = if end_of_day {
+            date.and_hms_opt(23, 59, 59)
+        } else {
+            date.and_hms_opt(0, 0, 0)
+        };
+        return time.map(|time| Utc.from_utc_datetime(&time));
+    }
+
+    ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(input, format).ok())
+        .map(|time| Utc.from_utc_datetime(&time))
+}
+
 pub fn parse_relative_date(input: &str) -> Option<DateTime<Utc>> {
     let input = input.trim().to_lowercase();
     let now = Utc::now();
@@ -28,11 +111,11 @@ pub fn parse_relative_date(input: &str) -> Option<DateTime<Utc>> {
         Some(now - Duration::days(0))
     } else if input == "yesterday" {
         Some(now - Duration::days(1))
-    } else if input == "week" || input == "this week" {
+    } else if matches!(input.as_str(), "week" | "this week" | "last week") {
         Some(now - Duration::weeks(1))
-    } else if input == "month" || input == "this month" {
+    } else if matches!(input.as_str(), "month" | "this month" | "last month") {
         Some(now - Duration::days(30))
-    } else if input == "year" || input == "this year" {
+    } else if matches!(input.as_str(), "year" | "this year" | "last year") {
         Some(now - Duration::days(365))
     } else if input.ends_with("days") || input.ends_with('d') {
         let num_str = input.trim_end_matches("days").trim_end_matches('d').trim();
@@ -55,43 +138,108 @@ pub fn format_date(date: DateTime<Utc>) -> String {
     date.format("%Y-%m-%d %H:%M:%S UTC").to_string()
 }
 
+/// How dates are shown: `relative` ("3 days ago"), `iso` (as [`format_date`]) or a locale's
+/// strftime pattern such as `%d.%m.%Y %H:%M`, rendered in UTC.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum DateStyle {
+    #[default]
+    Relative,
+    Iso,
+    Locale(String),
+}
+
+impl DateStyle {
+    /// `None` for a pattern chrono cannot render.
+    pub fn parse(input: &str) -> Option<Self> {
+        match input.to_lowercase().as_str() {
+            "relative" => Some(Self::Relative),
+            "iso" => Some(Self::Iso),
+            _ if input.contains('%')
+                && !StrftimeItems::new(input).any(|item| item == Item::Error) =>
+            {
+                Some(Self::Locale(input.to_string()))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<String> for DateStyle {
+    type Error = String;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        Self::parse(&input).ok_or_else(|| {
+            format!("invalid date format `{}`: use relative, iso or a strftime pattern", input)
+        })
+    }
+}
+
+impl From<DateStyle> for String {
+    fn from(style: DateStyle) -> Self {
+        match style {
+            DateStyle::Relative => "relative".to_string(),
+            DateStyle::Iso => "iso".to_string(),
+            DateStyle::Locale(pattern) => pattern,
+        }
+    }
+}
+
+pub fn format_date_with(date: DateTime<Utc>, style: &DateStyle) -> String {
+    match style {
+        DateStyle::Relative => format_relative_date(date),
+        DateStyle::Iso => format_date(date),
+        DateStyle::Locale(pattern) => date.format(pattern).to_string(),
+    }
+}
+
+/// Describes how long ago `date` was.
+///
+/// Dates slightly ahead of the clock count as "just now". Dates further ahead are described
+/// as upcoming and marked `(future)`, and dates before 1980 are printed in full and marked
+/// `(ancient)`.
 pub fn format_relative_date(date: DateTime<Utc>) -> String {
     let now = Utc::now();
-    let duration = now.signed_duration_since(date);
-
-    if duration.num_seconds() < 60 {
-        "just now".to_string()
-    } else if duration.num_minutes() < 60 {
-        let mins = duration.num_minutes();
-        if mins == 1 {
-            "1 minute ago".to_string()
-        } else {
-            format!("{} minutes ago", mins)
+
+    match timestamp_sanity(date, now) {
+        TimestampSanity::Plausible => {
+            let elapsed = now.signed_duration_since(date).max(Duration::zero());
+            if elapsed.num_seconds() < 60 {
+                "just now".to_string()
+            } else {
+                match format_span(elapsed) {
+                    Some(span) => format!("{} ago", span),
+                    None => format_date(date),
+                }
+            }
         }
+        TimestampSanity::Future => match format_span(date.signed_duration_since(now)) {
+            Some(span) => format!("in {} (future)", span),
+            None => format!("{} (future)", format_date(date)),
+        },
+        TimestampSanity::Ancient => format!("{} (ancient)", format_date(date)),
+    }
+}
+
+/// Spells out spans shorter than four weeks in their largest whole unit.
+fn format_span(duration: Duration) -> Option<String> {
+    let (count, unit) = if duration.num_minutes() < 60 {
+        (duration.num_minutes(), "minute")
     } else if duration.num_hours() < 24 {
-        let hours = duration.num_hours();
-        if hours == 1 {
-            "1 hour ago".to_string()
-        } else {
-            format!("{} hours ago", hours)
-        }
+        (duration.num_hours(), "hour")
     } else if duration.num_days() < 7 {
-        let days = duration.num_days();
-        if days == 1 {
-            "1 day ago".to_string()
-        } else {
-            format!("{} days ago", days)
-        }
+        (duration.num_days(), "day")
     } else if duration.num_weeks() < 4 {
-        let weeks = duration.num_weeks();
-        if weeks == 1 {
-            "1 week ago".to_string()
-        } else {
-            format!("{} weeks ago", weeks)
-        }
+        (duration.num_weeks(), "week")
     } else {
-        format_date(date)
-    }
+        return None;
+    };
+
+    Some(if count == 1 {
+        format!("1 {}", unit)
+    } else {
+        format!("{} {}s", count, unit)
+    })
 }
 
 #[cfg(test)]
@@ -105,6 +253,8 @@ mod tests {
         assert!(parse_relative_date("week").is_some());
         assert!(parse_relative_date("7days").is_some());
         assert!(parse_relative_date("2weeks").is_some());
+        assert!(parse_relative_date("last week").is_some());
+        assert!(parse_relative_date("3 months").is_some());
     }
 
     #[test]
@@ -114,4 +264,130 @@ mod tests {
         let formatted = format_relative_date(one_hour_ago);
         assert!(formatted.contains("hour"));
     }
+
+    fn entry_modified(modified: Option<DateTime<Utc>>) -> FileEntry {
+        let mut entry = FileEntry::new(std::path::PathBuf::from("/data/file.txt"));
+        entry.modified_at = modified;
+        entry
+    }
+
+    fn by_mtime(entry: &FileEntry, filter: &DateFilter) -> bool {
+        apply_date_filter(entry, filter, DateField::Modified)
+    }
+
+    #[test]
+    fn test_format_relative_date_future_and_ancient() {
+        let now = Utc::now();
+        assert_eq!(format_relative_date(now + Duration::seconds(30)), "just now");
+        assert_eq!(
+            format_relative_date(now + Duration::days(3) + Duration::minutes(1)),
+            "in 3 days (future)"
+        );
+        assert_eq!(
+            format_relative_date(now + Duration::hours(1) + Duration::minutes(1)),
+            "in 1 hour (future)"
+        );
+
+        let far = Utc.with_ymd_and_hms(2300, 1, 2, 3, 4, 5).unwrap();
+        assert_eq!(format_relative_date(far), "2300-01-02 03:04:05 UTC (future)");
+
+        let epoch = DateTime::<Utc>::UNIX_EPOCH;
+        assert_eq!(format_relative_date(epoch), "1970-01-01 00:00:00 UTC (ancient)");
+        let just_after = Utc.with_ymd_and_hms(1980, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(format_relative_date(just_after), "1980-01-01 00:00:00 UTC");
+    }
+
+    #[test]
+    fn test_timestamp_sanity_boundaries() {
+        let now = Utc::now();
+        let tolerance = Duration::seconds(CLOCK_SKEW_TOLERANCE_SECS);
+
+        assert_eq!(timestamp_sanity(now + tolerance, now), TimestampSanity::Plausible);
+        assert_eq!(
+            timestamp_sanity(now + tolerance + Duration::seconds(1), now),
+            TimestampSanity::Future
+        );
+        assert_eq!(timestamp_sanity(ancient_cutoff(), now), TimestampSanity::Plausible);
+        assert_eq!(
+            timestamp_sanity(ancient_cutoff() - Duration::seconds(1), now),
+            TimestampSanity::Ancient
+        );
+    }
+
+    #[test]
+    fn test_date_filters_against_skewed_mtimes() {
+        let now = Utc::now();
+        let future = entry_modified(Some(now + Duration::days(5 * 365)));
+        let ancient = entry_modified(Some(DateTime::<Utc>::UNIX_EPOCH));
+        let recent = entry_modified(Some(now - Duration::hours(1)));
+        let unknown = entry_modified(None);
+
+        // After compares the raw value, so a future mtime passes any lower bound.
+        assert!(by_mtime(&future, &DateFilter::After(now - Duration::days(7))));
+        assert!(!by_mtime(&ancient, &DateFilter::After(now - Duration::days(7))));
+        let hour_ago = now - Duration::hours(1);
+        assert!(!by_mtime(&recent, &DateFilter::After(hour_ago)));
+        assert!(by_mtime(&recent, &DateFilter::OnOrAfter(hour_ago)));
+        assert!(by_mtime(&recent, &DateFilter::OnOrBefore(hour_ago)));
+        // On only matches the day the skewed clock claims, never today.
+        assert!(!by_mtime(&future, &DateFilter::On(now)));
+        assert!(by_mtime(&future, &DateFilter::On(now + Duration::days(5 * 365))));
+        assert!(by_mtime(&recent, &DateFilter::On(now - Duration::hours(1))));
+
+        for (entry, future_match, ancient_match) in [
+            (&future, true, false),
+            (&ancient, false, true),
+            (&recent, false, false),
+            (&unknown, false, false),
+        ] {
+            assert_eq!(by_mtime(entry, &DateFilter::Future), future_match);
+            assert_eq!(by_mtime(entry, &DateFilter::Ancient), ancient_match);
+        }
+    }
+
+    #[test]
+    fn test_date_filters_on_created_and_accessed_times() {
+        let now = Utc::now();
+        let mut entry = entry_modified(Some(now - Duration::days(1)));
+        entry.created_at = Some(now - Duration::days(400));
+        let last_month = DateFilter::After(now - Duration::days(30));
+
+        assert!(apply_date_filter(&entry, &last_month, DateField::Modified));
+        assert!(!apply_date_filter(&entry, &last_month, DateField::Created));
+        // No recorded access time: the modification time stands in for it.
+        assert!(apply_date_filter(&entry, &last_month, DateField::Accessed));
+
+        entry.accessed_at = Some(now - Duration::days(90));
+        assert!(!apply_date_filter(&entry, &last_month, DateField::Accessed));
+    }
+
+    #[test]
+    fn test_parse_absolute_dates() {
+        let start = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        assert_eq!(parse_date("2024-06-01"), Some(start));
+        assert_eq!(parse_end_date("2024-06-01"), Some(start + Duration::seconds(86_399)));
+        assert_eq!(
+            parse_date("2024-06-01T14:30"),
+            Some(Utc.with_ymd_and_hms(2024, 6, 1, 14, 30, 0).unwrap())
+        );
+        assert_eq!(parse_end_date("2024-06-01T14:30:15"), parse_date("2024-06-01T14:30:15"));
+        assert!(parse_date("7d").is_some());
+
+        assert_eq!(parse_date("2024-13-45"), None);
+        assert_eq!(parse_date("2024-02-30"), None);
+        assert_eq!(parse_date("2024-06-01T25:00"), None);
+    }
+
+    #[test]
+    fn test_date_styles() {
+        let date = Utc.with_ymd_and_hms(2024, 12, 24, 14, 30, 0).unwrap();
+        let pattern = DateStyle::parse("%d.%m.%Y %H:%M").unwrap();
+        assert_eq!(format_date_with(date, &pattern), "24.12.2024 14:30");
+        assert_eq!(format_date_with(date, &DateStyle::Iso), "2024-12-24 14:30:00 UTC");
+        assert_eq!(DateStyle::parse("ISO"), Some(DateStyle::Iso));
+
+        assert_eq!(DateStyle::parse("%Q"), None);
+        assert_eq!(DateStyle::parse("dd.mm.yyyy"), None);
+        assert_eq!(String::from(pattern), "%d.%m.%Y %H:%M");
+    }
 }
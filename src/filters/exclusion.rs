@@ -1,13 +1,90 @@
 use crate::core::error::Result;
 use crate::core::types::{ExclusionRule, ExclusionRuleType};
 use globset::{Glob, GlobSet, GlobSetBuilder};
+use once_cell::sync::Lazy;
 use regex::RegexSet;
+use std::fmt;
 use std::path::Path;
 
+pub const TRANSIENT_ARTIFACTS_SET: &str = "transient-artifacts";
+
+// Matched against the file name only: editor swap/backup/lock files and tmp suffixes.
+pub const TRANSIENT_ARTIFACT_PATTERNS: &[&str] = &[
+    "*.swp",
+    "*.swo",
+    "*.swx",
+    "*~",
+    "4913",
+    ".#*",
+    "#*#",
+    "*___jb_tmp___",
+    "*___jb_old___",
+    "~$*",
+    ".~lock.*#",
+    "*.tmp",
+    "*.temp",
+    "*.crdownload",
+    ".DS_Store",
+];
+
+static TRANSIENT_ARTIFACTS: Lazy<GlobSet> = Lazy::new(|| {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in TRANSIENT_ARTIFACT_PATTERNS {
+        builder.add(Glob::new(pattern).expect("built-in transient pattern is valid"));
+    }
+    builder.build().expect("built-in transient pattern set is valid")
+});
+
+pub fn is_transient_artifact<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref()
+        .file_name()
+        .map(|name| TRANSIENT_ARTIFACTS.is_match(Path::new(name)))
+        .unwrap_or(false)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExclusionSource {
+    BuiltIn(&'static str),
+    Rule,
+}
+
+/// Whether a file would be indexed, and if not, why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterDecision {
+    Included,
+    Excluded(ExclusionSource),
+    /// Inclusion patterns are configured and the file matched none of them.
+    NotIncluded,
+}
+
+impl FilterDecision {
+    pub fn is_included(&self) -> bool {
+        *self == FilterDecision::Included
+    }
+}
+
+impl fmt::Display for FilterDecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterDecision::Included => write!(f, "included"),
+            FilterDecision::Excluded(ExclusionSource::BuiltIn(set)) => {
+                write!(f, "excluded by the built-in {} set", set)
+            }
+            FilterDecision::Excluded(ExclusionSource::Rule) => {
+                write!(f, "excluded by an exclusion rule")
+            }
+            FilterDecision::NotIncluded => write!(f, "did not match any inclusion pattern"),
+        }
+    }
+}
+
 pub struct ExclusionFilter {
     glob_set: Option<GlobSet>,
     regex_set: Option<RegexSet>,
     path_patterns: Vec<String>,
+    inclusions: Vec<Glob>,
+    inclusion_set: Option<GlobSet>,
+    exclude_transient: bool,
 }
 
 impl ExclusionFilter {
@@ -15,6 +92,7 @@ impl ExclusionFilter {
         let mut glob_builder = GlobSetBuilder::new();
         let mut regex_patterns = Vec::new();
         let mut path_patterns = Vec::new();
+        let mut inclusions = Vec::new();
 
         for rule in rules {
             match rule.rule_type {
@@ -28,6 +106,9 @@ impl ExclusionFilter {
                 ExclusionRuleType::Path => {
                     path_patterns.push(rule.pattern);
                 }
+                ExclusionRuleType::Include => {
+                    inclusions.push(Glob::new(&rule.pattern)?);
+                }
             }
         }
 
@@ -43,11 +124,28 @@ impl ExclusionFilter {
             None
         };
 
-        Ok(Self {
+        Self {
             glob_set,
             regex_set,
             path_patterns,
-        })
+            inclusions: Vec::new(),
+            inclusion_set: None,
+            exclude_transient: false,
+        }
+        .with_inclusions(inclusions)
+    }
+
+    pub fn with_transient_artifacts(mut self, enabled: bool) -> Self {
+        self.exclude_transient = enabled;
+        self
+    }
+
+    pub fn excludes_transient_artifacts(&self) -> bool {
+        self.exclude_transient
+    }
+
+    pub fn is_transient<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.exclude_transient && is_transient_artifact(path)
     }
 
     pub fn from_patterns(patterns: &[String]) -> Result<Self> {
@@ -62,8 +160,61 @@ impl ExclusionFilter {
         Self::new(rules)
     }
 
+    /// Adds inclusion globs: once any are present, only files matching one of them are
+    /// indexed. Directories are still traversed whether or not they match.
+    pub fn with_inclusion_patterns(self, patterns: &[String]) -> Result<Self> {
+        let globs = patterns
+            .iter()
+            .map(|pattern| Glob::new(pattern))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        self.with_inclusions(globs)
+    }
+
+    fn with_inclusions(mut self, globs: Vec<Glob>) -> Result<Self> {
+        if globs.is_empty() {
+            return Ok(self);
+        }
+
+        self.inclusions.extend(globs);
+        let mut builder = GlobSetBuilder::new();
+        for glob in &self.inclusions {
+            builder.add(glob.clone());
+        }
+        self.inclusion_set = Some(builder.build()?);
+        Ok(self)
+    }
+
+    pub fn has_inclusions(&self) -> bool {
+        self.inclusion_set.is_some()
+    }
+
+    /// Whether `path` matches an inclusion pattern; always true when none are configured.
+    pub fn is_included<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.inclusion_set
+            .as_ref()
+            .map_or(true, |set| set.is_match(path.as_ref()))
+    }
+
+    /// Exclusions only; this is what decides whether a directory is descended into.
     pub fn is_excluded<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.exclusion_source(path).is_some()
+    }
+
+    pub fn exclusion_source<P: AsRef<Path>>(&self, path: P) -> Option<ExclusionSource> {
         let path = path.as_ref();
+
+        if self.is_transient(path) {
+            return Some(ExclusionSource::BuiltIn(TRANSIENT_ARTIFACTS_SET));
+        }
+
+        if self.matches_rules(path) {
+            return Some(ExclusionSource::Rule);
+        }
+
+        None
+    }
+
+    fn matches_rules(&self, path: &Path) -> bool {
         let path_str = path.to_string_lossy();
 
         if let Some(ref glob_set) = self.glob_set {
@@ -87,8 +238,24 @@ impl ExclusionFilter {
         false
     }
 
+    /// The decision for a file: it must match an inclusion, when any are configured, and no
+    /// exclusion. Exclusions are reported first.
+    pub fn decision<P: AsRef<Path>>(&self, path: P) -> FilterDecision {
+        let path = path.as_ref();
+
+        if let Some(source) = self.exclusion_source(path) {
+            return FilterDecision::Excluded(source);
+        }
+
+        if !self.is_included(path) {
+            return FilterDecision::NotIncluded;
+        }
+
+        FilterDecision::Included
+    }
+
     pub fn should_index<P: AsRef<Path>>(&self, path: P) -> bool {
-        !self.is_excluded(path)
+        self.decision(path).is_included()
     }
 }
 
@@ -101,6 +268,7 @@ impl Default for ExclusionFilter {
             ".DS_Store".to_string(),
         ])
         .unwrap()
+        .with_transient_artifacts(true)
     }
 }
 
@@ -152,4 +320,79 @@ mod tests {
         assert!(filter.is_excluded(PathBuf::from("node_modules")));
         assert!(filter.is_excluded(PathBuf::from("target")));
     }
+
+    #[test]
+    fn test_transient_artifacts_excluded_by_default() {
+        let filter = ExclusionFilter::default();
+        for name in [
+            "/src/foo.txt.swp",
+            "/src/.main.rs.swo",
+            "/src/notes.md~",
+            "/src/4913",
+            "/src/.#lockfile",
+            "/src/#autosave#",
+            "/src/Main.java___jb_tmp___",
+            "/docs/~$report.docx",
+            "/docs/.~lock.sheet.ods#",
+            "/tmp/download.tmp",
+            "/photos/.DS_Store",
+        ] {
+            assert_eq!(
+                filter.exclusion_source(PathBuf::from(name)),
+                Some(ExclusionSource::BuiltIn(TRANSIENT_ARTIFACTS_SET)),
+                "{} should be excluded",
+                name
+            );
+        }
+        assert!(!filter.is_excluded(PathBuf::from("/src/main.rs")));
+        assert!(!filter.is_excluded(PathBuf::from("/src/49130")));
+    }
+
+    #[test]
+    fn test_inclusions_and_exclusions_combine() {
+        let rules = vec![
+            ExclusionRule {
+                pattern: "*.rs".to_string(),
+                rule_type: ExclusionRuleType::Include,
+            },
+            ExclusionRule {
+                pattern: "**/generated/**".to_string(),
+                rule_type: ExclusionRuleType::Glob,
+            },
+        ];
+        let filter = ExclusionFilter::new(rules)
+            .unwrap()
+            .with_inclusion_patterns(&["*.toml".to_string()])
+            .unwrap();
+
+        assert_eq!(filter.decision("/repo/src/main.rs"), FilterDecision::Included);
+        assert_eq!(filter.decision("/repo/Cargo.toml"), FilterDecision::Included);
+        assert_eq!(filter.decision("/repo/README.md"), FilterDecision::NotIncluded);
+        // An exclusion wins over a matching inclusion.
+        assert_eq!(
+            filter.decision("/repo/src/generated/api.rs"),
+            FilterDecision::Excluded(ExclusionSource::Rule)
+        );
+        // Directories are never excluded for missing an inclusion.
+        assert!(!filter.is_excluded("/repo/src"));
+        assert!(!filter.should_index("/repo/src"));
+        assert_eq!(
+            FilterDecision::NotIncluded.to_string(),
+            "did not match any inclusion pattern"
+        );
+    }
+
+    #[test]
+    fn test_transient_artifacts_can_be_disabled() {
+        let filter = ExclusionFilter::from_patterns(&["*.log".to_string()])
+            .unwrap()
+            .with_transient_artifacts(false);
+
+        assert!(!filter.is_excluded(PathBuf::from("/src/foo.txt.swp")));
+        assert!(!filter.is_excluded(PathBuf::from("/src/notes.md~")));
+        assert_eq!(
+            filter.exclusion_source(PathBuf::from("/var/app.log")),
+            Some(ExclusionSource::Rule)
+        );
+    }
 }
@@ -1,17 +1,28 @@
 use crate::core::types::FileEntry;
+use serde::{Deserialize, Serialize};
 
+/// Extensions may also name a category, as in `ext:code`, matching every extension in it.
 pub fn apply_extension_filter(entry: &FileEntry, extensions: &[String]) -> bool {
     if extensions.is_empty() {
         return true;
     }
 
     if let Some(ref ext) = entry.extension {
-        extensions.iter().any(|e| e.eq_ignore_ascii_case(ext))
+        extensions.iter().any(|e| {
+            e.eq_ignore_ascii_case(ext)
+                || ExtensionCategory::parse(e).is_some_and(|c| get_extension_category(ext) == c)
+        })
     } else {
         false
     }
 }
 
+/// Whether `entry`'s extension falls in `category`; entries without one are `Other`.
+pub fn apply_category_filter(entry: &FileEntry, category: ExtensionCategory) -> bool {
+    let ext = entry.extension.as_deref().unwrap_or_default();
+    get_extension_category(ext) == category
+}
+
 pub fn normalize_extension(ext: &str) -> String {
     ext.trim_start_matches('.').to_lowercase()
 }
@@ -89,8 +100,10 @@ pub fn get_extension_category(ext: &str) -> ExtensionCategory {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ExtensionCategory {
+    #[serde(rename = "source")]
     SourceCode,
     Document,
     Image,
@@ -100,6 +113,35 @@ pub enum ExtensionCategory {
     Other,
 }
 
+impl ExtensionCategory {
+    /// The category an `ext:` value names, such as `ext:code` or `ext:image`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "code" => Some(ExtensionCategory::SourceCode),
+            "document" => Some(ExtensionCategory::Document),
+            "image" => Some(ExtensionCategory::Image),
+            "video" => Some(ExtensionCategory::Video),
+            "audio" => Some(ExtensionCategory::Audio),
+            "archive" => Some(ExtensionCategory::Archive),
+            _ => None,
+        }
+    }
+
+    /// Names accepted by `category:`, in the order they are listed in errors.
+    pub const NAMES: [&'static str; 7] =
+        ["source", "document", "image", "video", "audio", "archive", "other"];
+
+    /// The category a `category:` value names. Unlike [`parse`](Self::parse) this takes
+    /// `other`, and `source` as well as `code`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "source" => Some(ExtensionCategory::SourceCode),
+            "other" => Some(ExtensionCategory::Other),
+            name => Self::parse(name),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -133,4 +175,40 @@ mod tests {
         assert_eq!(get_extension_category("mp3"), ExtensionCategory::Audio);
         assert_eq!(get_extension_category("zip"), ExtensionCategory::Archive);
     }
+
+    #[test]
+    fn test_extension_filter_expands_categories() {
+        let entry = FileEntry::new(std::path::PathBuf::from("/src/main.rs"));
+        assert!(apply_extension_filter(&entry, &["code".to_string()]));
+        assert!(apply_extension_filter(&entry, &["md".to_string(), "RS".to_string()]));
+        assert!(!apply_extension_filter(&entry, &["image".to_string()]));
+
+        let script = FileEntry::new(std::path::PathBuf::from("/bin/deploy"));
+        assert!(!apply_extension_filter(&script, &["code".to_string()]));
+    }
+
+    #[test]
+    fn test_parse_category_names() {
+        assert_eq!(ExtensionCategory::parse("code"), Some(ExtensionCategory::SourceCode));
+        assert_eq!(ExtensionCategory::parse("Image"), Some(ExtensionCategory::Image));
+        assert_eq!(ExtensionCategory::parse("rs"), None);
+        assert_eq!(ExtensionCategory::parse("other"), None);
+    }
+
+    #[test]
+    fn test_category_filter_puts_extensionless_files_in_other() {
+        for name in ExtensionCategory::NAMES {
+            assert!(ExtensionCategory::from_name(name).is_some(), "{}", name);
+        }
+        assert_eq!(ExtensionCategory::from_name("Code"), Some(ExtensionCategory::SourceCode));
+        assert_eq!(ExtensionCategory::from_name("pictures"), None);
+
+        let photo = FileEntry::new(std::path::PathBuf::from("/photos/cat.JPG"));
+        assert!(apply_category_filter(&photo, ExtensionCategory::Image));
+        assert!(!apply_category_filter(&photo, ExtensionCategory::Other));
+
+        let readme = FileEntry::new(std::path::PathBuf::from("/src/README"));
+        assert!(apply_category_filter(&readme, ExtensionCategory::Other));
+        assert!(!apply_category_filter(&readme, ExtensionCategory::Document));
+    }
 }
@@ -0,0 +1,48 @@
+use crate::core::types::{EntryKind, FileEntry};
+
+pub fn apply_kind_filter(entry: &FileEntry, kinds: &[EntryKind]) -> bool {
+    kinds.is_empty() || kinds.contains(&EntryKind::of(entry))
+}
+
+pub fn parse_entry_kinds(input: &str) -> Option<Vec<EntryKind>> {
+    input
+        .split(',')
+        .map(|s| match s.trim().to_lowercase().as_str() {
+            "file" | "files" => Some(EntryKind::File),
+            "dir" | "dirs" | "directory" | "folder" => Some(EntryKind::Directory),
+            "symlink" | "link" => Some(EntryKind::Symlink),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_parse_entry_kinds() {
+        assert_eq!(
+            parse_entry_kinds("dir,symlink"),
+            Some(vec![EntryKind::Directory, EntryKind::Symlink])
+        );
+        assert_eq!(parse_entry_kinds("file"), Some(vec![EntryKind::File]));
+        assert_eq!(parse_entry_kinds("dir,socket"), None);
+    }
+
+    #[test]
+    fn test_apply_kind_filter() {
+        let file = FileEntry::new(PathBuf::from("/a/file.txt"));
+        let mut dir = FileEntry::new(PathBuf::from("/a/dir"));
+        dir.is_directory = true;
+        let mut link = FileEntry::new(PathBuf::from("/a/link"));
+        link.is_symlink = true;
+
+        assert!(apply_kind_filter(&file, &[]));
+        assert!(apply_kind_filter(&file, &[EntryKind::File]));
+        assert!(!apply_kind_filter(&dir, &[EntryKind::File]));
+        assert!(apply_kind_filter(&dir, &[EntryKind::Directory, EntryKind::Symlink]));
+        assert!(apply_kind_filter(&link, &[EntryKind::Symlink]));
+    }
+}
@@ -0,0 +1,41 @@
+use crate::core::types::FileEntry;
+
+/// Whether `entry`'s MIME type matches `filter`: an exact type such as `application/pdf`, or
+/// a prefix ending in `*` such as `image/*`. Case is ignored, and entries without a MIME
+/// type never match.
+pub fn apply_mime_filter(entry: &FileEntry, filter: &str) -> bool {
+    let Some(mime) = entry.mime_type.as_deref() else {
+        return false;
+    };
+
+    match filter.strip_suffix('*') {
+        Some(prefix) => mime
+            .get(..prefix.len())
+            .is_some_and(|head| head.eq_ignore_ascii_case(prefix)),
+        None => mime.eq_ignore_ascii_case(filter),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_apply_mime_filter() {
+        let mut pdf = FileEntry::new(PathBuf::from("/docs/report.pdf"));
+        pdf.mime_type = Some("application/pdf".to_string());
+        let mut png = FileEntry::new(PathBuf::from("/img/logo.png"));
+        png.mime_type = Some("image/png".to_string());
+        let unknown = FileEntry::new(PathBuf::from("/data/blob"));
+
+        assert!(apply_mime_filter(&pdf, "application/pdf"));
+        assert!(apply_mime_filter(&pdf, "Application/PDF"));
+        assert!(!apply_mime_filter(&pdf, "application/pd"));
+        assert!(apply_mime_filter(&png, "image/*"));
+        assert!(!apply_mime_filter(&pdf, "image/*"));
+        assert!(apply_mime_filter(&png, "*"));
+        assert!(!apply_mime_filter(&unknown, "*"));
+        assert!(!apply_mime_filter(&unknown, "application/octet-stream"));
+    }
+}
@@ -1,13 +1,31 @@
 pub mod date;
 pub mod exclusion;
 pub mod extension;
+pub mod kind;
+pub mod mime;
+pub mod sensitive;
+pub mod shebang;
 pub mod size;
 
-pub use date::{apply_date_filter, format_date, format_relative_date, parse_relative_date};
-pub use exclusion::{build_gitignore_filter, ExclusionFilter};
+pub use date::{
+    ancient_cutoff, apply_date_filter, format_date, format_date_with, format_relative_date,
+    parse_date, parse_end_date, parse_relative_date, timestamp_sanity, DateStyle,
+    TimestampSanity, ANCIENT_BEFORE_YEAR, CLOCK_SKEW_TOLERANCE_SECS,
+};
+pub use exclusion::{
+    build_gitignore_filter, is_transient_artifact, ExclusionFilter, ExclusionSource,
+    FilterDecision, TRANSIENT_ARTIFACTS_SET, TRANSIENT_ARTIFACT_PATTERNS,
+};
 pub use extension::{
-    apply_extension_filter, get_extension_category, is_archive_extension, is_audio_extension,
+    apply_category_filter, apply_extension_filter, get_extension_category, is_archive_extension, is_audio_extension,
     is_document_extension, is_image_extension, is_source_code_extension, is_video_extension,
     normalize_extension, parse_extensions, ExtensionCategory,
 };
-pub use size::{apply_size_filter, format_size, parse_size};
+pub use kind::{apply_kind_filter, parse_entry_kinds};
+pub use mime::apply_mime_filter;
+pub use sensitive::{SensitiveFilter, DEFAULT_SENSITIVE_PATTERNS, REDACTED};
+pub use shebang::{
+    apply_language_filter, extension_language, interpreter_language, parse_shebang,
+    SHEBANG_PROBE_BYTES,
+};
+pub use size::{apply_size_filter, format_size, format_size_with, parse_size, SizeUnits};
@@ -0,0 +1,142 @@
+use crate::core::config::SearchConfig;
+use crate::core::error::{Result, SearchError};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::path::Path;
+
+/// Files and directories that usually hold keys, tokens or passwords.
+pub const DEFAULT_SENSITIVE_PATTERNS: &[&str] = &[
+    ".ssh",
+    ".gnupg",
+    ".aws",
+    ".env",
+    ".env.*",
+    ".netrc",
+    ".pgpass",
+    "*.pem",
+    "*.key",
+    "*.p12",
+    "*.pfx",
+    "id_rsa*",
+    "id_ecdsa*",
+    "id_ed25519*",
+];
+
+/// What a sensitive file name is replaced with in logs.
+pub const REDACTED: &str = "[redacted]";
+
+/// Paths matching `sensitive_patterns`: their content is never indexed, their names are
+/// masked in logs and exports leave them out unless asked not to.
+#[derive(Debug, Clone, Default)]
+pub struct SensitiveFilter {
+    set: Option<GlobSet>,
+}
+
+impl SensitiveFilter {
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        if patterns.is_empty() {
+            return Ok(Self::default());
+        }
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            builder.add(Glob::new(pattern)?);
+        }
+        Ok(Self {
+            set: Some(builder.build()?),
+        })
+    }
+
+    /// Patterns that are not valid globs are left out; `SearchConfig::validate` reports them.
+    pub fn from_config(config: &SearchConfig) -> Self {
+        let valid: Vec<String> = config
+            .sensitive_patterns
+            .iter()
+            .filter(|pattern| Glob::new(pattern).is_ok())
+            .cloned()
+            .collect();
+        Self::new(&valid).unwrap_or_default()
+    }
+
+    /// Whether the whole path, its file name or any directory above it matches a pattern,
+    /// so `.ssh` covers everything in it.
+    pub fn is_sensitive<P: AsRef<Path>>(&self, path: P) -> bool {
+        let Some(set) = &self.set else {
+            return false;
+        };
+        let path = path.as_ref();
+        set.is_match(path)
+            || path
+                .components()
+                .any(|component| set.is_match(Path::new(component.as_os_str())))
+    }
+
+    /// `path` for a log line, with the file name masked when the path is sensitive.
+    pub fn redact_path<P: AsRef<Path>>(&self, path: P) -> String {
+        let path = path.as_ref();
+        if !self.is_sensitive(path) {
+            return path.display().to_string();
+        }
+        match path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            Some(parent) => parent.join(REDACTED).display().to_string(),
+            None => REDACTED.to_string(),
+        }
+    }
+
+    /// The error's message, with the path it concerns masked like
+    /// [`redact_path`](Self::redact_path) does.
+    pub fn redact_error(&self, err: &SearchError) -> String {
+        let message = err.to_string();
+        match err.path().filter(|path| self.is_sensitive(path)) {
+            Some(path) => message.replace(&path.display().to_string(), &self.redact_path(path)),
+            None => message,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn defaults() -> SensitiveFilter {
+        SensitiveFilter::from_config(&SearchConfig::default())
+    }
+
+    #[test]
+    fn test_default_patterns_cover_key_material_and_its_directories() {
+        let filter = defaults();
+        for path in [
+            "/home/ana/.ssh/config",
+            "/home/ana/.ssh/known_hosts",
+            "/home/ana/.aws/credentials",
+            "/srv/app/.env",
+            "/srv/app/.env.production",
+            "/etc/tls/server.pem",
+            "/home/ana/backup/id_ed25519.pub",
+        ] {
+            assert!(filter.is_sensitive(path), "{}", path);
+        }
+        for path in ["/srv/app/env.rs", "/home/ana/notes/ssh.md", "/srv/app/keyboard.rs"] {
+            assert!(!filter.is_sensitive(path), "{}", path);
+        }
+        assert!(!SensitiveFilter::default().is_sensitive("/home/ana/.ssh/id_rsa"));
+    }
+
+    #[test]
+    fn test_redaction_masks_only_sensitive_file_names() {
+        let filter = defaults();
+        assert_eq!(
+            filter.redact_path("/home/ana/.ssh/id_rsa"),
+            format!("/home/ana/.ssh/{}", REDACTED)
+        );
+        assert_eq!(filter.redact_path("server.pem"), REDACTED);
+        assert_eq!(filter.redact_path("/srv/app/main.rs"), "/srv/app/main.rs");
+
+        let err = SearchError::PermissionDenied(PathBuf::from("/home/ana/.aws/credentials"));
+        assert_eq!(
+            filter.redact_error(&err),
+            format!("Permission denied: /home/ana/.aws/{}", REDACTED)
+        );
+        let err = SearchError::PathNotFound(PathBuf::from("/srv/app/main.rs"));
+        assert_eq!(filter.redact_error(&err), "Path not found: /srv/app/main.rs");
+    }
+}
@@ -0,0 +1,124 @@
+use crate::core::types::FileEntry;
+
+/// Bytes read from the start of an extension-less file to look for a `#!` line.
+pub const SHEBANG_PROBE_BYTES: usize = 128;
+
+/// The interpreter named by a `#!` line at the start of `head`, as a basename:
+/// `#!/usr/bin/env python3` and `#!/usr/local/bin/python3 -u` both give `python3`.
+/// `env` options such as `-S` are skipped. `None` without a shebang or a readable name.
+pub fn parse_shebang(head: &[u8]) -> Option<String> {
+    let rest = head.strip_prefix(b"#!")?;
+    let line = rest.split(|&b| b == b'\n').next()?;
+    let line = std::str::from_utf8(line).ok()?.trim_end_matches('\r');
+
+    let mut words = line.split_whitespace();
+    let mut program = basename(words.next()?);
+    if program == "env" {
+        program = words.find(|word| !word.starts_with('-') && !word.contains('='))?;
+        program = basename(program);
+    }
+
+    (!program.is_empty()).then(|| program.to_string())
+}
+
+fn basename(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// The language an interpreter runs, ignoring version suffixes such as `python3.11` or
+/// `perl5`. `None` for interpreters outside the table.
+pub fn interpreter_language(interpreter: &str) -> Option<&'static str> {
+    let name = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    let language = match name.to_ascii_lowercase().as_str() {
+        "bash" => "bash",
+        "sh" | "dash" | "ash" | "ksh" | "mksh" => "sh",
+        "zsh" => "zsh",
+        "fish" => "fish",
+        "python" | "pypy" => "python",
+        "perl" => "perl",
+        "node" | "nodejs" => "node",
+        "deno" => "deno",
+        "ruby" => "ruby",
+        "php" => "php",
+        "lua" | "luajit" => "lua",
+        "tclsh" | "wish" => "tcl",
+        "awk" | "gawk" | "mawk" => "awk",
+        "rscript" => "r",
+        "pwsh" => "powershell",
+        _ => return None,
+    };
+    Some(language)
+}
+
+/// The script language a file extension implies, aligned with [`interpreter_language`] so
+/// `lang:python` finds `tool.py` as well as an extension-less file run by `python3`.
+pub fn extension_language(ext: &str) -> Option<&'static str> {
+    let language = match ext.to_ascii_lowercase().as_str() {
+        "bash" => "bash",
+        "sh" => "sh",
+        "zsh" => "zsh",
+        "fish" => "fish",
+        "py" => "python",
+        "pl" | "pm" => "perl",
+        "js" | "mjs" | "cjs" => "node",
+        "rb" => "ruby",
+        "php" => "php",
+        "lua" => "lua",
+        "tcl" => "tcl",
+        "awk" => "awk",
+        "r" => "r",
+        "ps1" | "psm1" => "powershell",
+        _ => return None,
+    };
+    Some(language)
+}
+
+/// Whether `entry`'s extension implies one of `languages`. Extension-less scripts are
+/// matched by the executor from their stored shebang instead.
+pub fn apply_language_filter(entry: &FileEntry, languages: &[String]) -> bool {
+    entry
+        .extension
+        .as_deref()
+        .and_then(extension_language)
+        .is_some_and(|language| languages.iter().any(|l| l == language))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_shebang() {
+        assert_eq!(parse_shebang(b"#!/bin/bash\necho hi\n"), Some("bash".to_string()));
+        assert_eq!(parse_shebang(b"#!/usr/bin/env python3\n"), Some("python3".to_string()));
+        assert_eq!(parse_shebang(b"#! /usr/bin/perl -w\r\n"), Some("perl".to_string()));
+        assert_eq!(
+            parse_shebang(b"#!/usr/bin/env -S LC_ALL=C node --harmony\n"),
+            Some("node".to_string())
+        );
+        assert_eq!(parse_shebang(b"#!/usr/bin/env"), None);
+        assert_eq!(parse_shebang(b"#!\n"), None);
+        assert_eq!(parse_shebang(b"echo #!/bin/sh\n"), None);
+        assert_eq!(parse_shebang(b"\x7fELF\x02\x01"), None);
+    }
+
+    #[test]
+    fn test_interpreter_language() {
+        assert_eq!(interpreter_language("bash"), Some("bash"));
+        assert_eq!(interpreter_language("dash"), Some("sh"));
+        assert_eq!(interpreter_language("python3.11"), Some("python"));
+        assert_eq!(interpreter_language("perl5"), Some("perl"));
+        assert_eq!(interpreter_language("nodejs"), Some("node"));
+        assert_eq!(interpreter_language("Rscript"), Some("r"));
+        assert_eq!(interpreter_language("make"), None);
+    }
+
+    #[test]
+    fn test_extension_language_matches_interpreters() {
+        for (ext, interpreter) in [("py", "python3"), ("pl", "perl"), ("js", "node")] {
+            assert_eq!(extension_language(ext), interpreter_language(interpreter));
+        }
+        assert_eq!(extension_language("SH"), Some("sh"));
+        assert_eq!(extension_language("rs"), None);
+    }
+}
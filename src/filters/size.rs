@@ -1,4 +1,15 @@
 use crate::core::types::{FileEntry, SizeFilter};
+use serde::{Deserialize, Serialize};
+
+/// The base sizes are shown in. `Binary` steps by 1024, `Decimal` by 1000; both use the same
+/// `KB`, `MB`... labels, like `ls -h` and `ls --si`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SizeUnits {
+    #[default]
+    Binary,
+    Decimal,
+}
 
 pub fn apply_size_filter(entry: &FileEntry, filter: &SizeFilter) -> bool {
     match filter {
@@ -6,9 +17,13 @@ pub fn apply_size_filter(entry: &FileEntry, filter: &SizeFilter) -> bool {
         SizeFilter::Range(min, max) => entry.size >= *min && entry.size <= *max,
         SizeFilter::GreaterThan(size) => entry.size > *size,
         SizeFilter::LessThan(size) => entry.size < *size,
+        SizeFilter::AtLeast(size) => entry.size >= *size,
+        SizeFilter::AtMost(size) => entry.size <= *size,
     }
 }
 
+/// Parses a size such as `100`, `1KB` or `1.5 GB`, rounding fractions to the nearest byte.
+/// Units step by 1024. Negative and non-numeric values give `None`.
 pub fn parse_size(input: &str) -> Option<u64> {
     let input = input.trim().to_lowercase();
 
@@ -26,25 +41,42 @@ pub fn parse_size(input: &str) -> Option<u64> {
         (input.as_str(), 1u64)
     };
 
-    number_str.trim().parse::<u64>().ok().map(|n| n * multiplier)
+    let number = number_str.trim();
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    let digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    if whole.is_empty() || !digits(whole) || !digits(fraction) {
+        return None;
+    }
+    if !number.contains('.') {
+        return whole.parse::<u64>().ok()?.checked_mul(multiplier);
+    }
+    if fraction.is_empty() {
+        return None;
+    }
+
+    let bytes = (number.parse::<f64>().ok()? * multiplier as f64).round();
+    (bytes < u64::MAX as f64).then_some(bytes as u64)
 }
 
 pub fn format_size(size: u64) -> String {
-    const KB: u64 = 1024;
-    const MB: u64 = KB * 1024;
-    const GB: u64 = MB * 1024;
-    const TB: u64 = GB * 1024;
-
-    if size >= TB {
-        format!("{:.2} TB", size as f64 / TB as f64)
-    } else if size >= GB {
-        format!("{:.2} GB", size as f64 / GB as f64)
-    } else if size >= MB {
-        format!("{:.2} MB", size as f64 / MB as f64)
-    } else if size >= KB {
-        format!("{:.2} KB", size as f64 / KB as f64)
-    } else {
+    format_size_with(size, SizeUnits::Binary, '.')
+}
+
+pub fn format_size_with(size: u64, units: SizeUnits, decimal_separator: char) -> String {
+    let kb: u64 = match units {
+        SizeUnits::Binary => 1024,
+        SizeUnits::Decimal => 1000,
+    };
+    let (unit, label) = [(kb.pow(4), "TB"), (kb.pow(3), "GB"), (kb.pow(2), "MB"), (kb, "KB")]
+        .into_iter()
+        .find(|(unit, _)| size >= *unit)
+        .unwrap_or((1, "B"));
+
+    if unit == 1 {
         format!("{} B", size)
+    } else {
+        let value = format!("{:.2}", size as f64 / unit as f64);
+        format!("{} {}", value.replace('.', &decimal_separator.to_string()), label)
     }
 }
 
@@ -59,7 +91,20 @@ mod tests {
         assert_eq!(parse_size("1KB"), Some(1024));
         assert_eq!(parse_size("1MB"), Some(1024 * 1024));
         assert_eq!(parse_size("1GB"), Some(1024 * 1024 * 1024));
-        assert_eq!(parse_size("1.5MB"), None);
+    }
+
+    #[test]
+    fn test_parse_fractional_sizes() {
+        assert_eq!(parse_size("1.5MB"), Some(1_572_864));
+        assert_eq!(parse_size("1.5 MB"), Some(1_572_864));
+        assert_eq!(parse_size("0.1kb"), Some(102));
+        assert_eq!(parse_size("2.5"), Some(3));
+        assert_eq!(parse_size("1.5GB"), Some(1_610_612_736));
+
+        for invalid in ["-5MB", "-1", "1.MB", ".5MB", "1.2.3KB", "1e3", "inf", "NaN", "MB", ""] {
+            assert_eq!(parse_size(invalid), None, "{}", invalid);
+        }
+        assert_eq!(parse_size("99999999999TB"), None);
     }
 
     #[test]
@@ -70,6 +115,16 @@ mod tests {
         assert_eq!(format_size(1024 * 1024 * 1024), "1.00 GB");
     }
 
+    #[test]
+    fn test_format_size_with_units_and_separator() {
+        let size = 1_572_864;
+        assert_eq!(format_size_with(size, SizeUnits::Binary, '.'), format_size(size));
+        assert_eq!(format_size_with(size, SizeUnits::Binary, ','), "1,50 MB");
+        assert_eq!(format_size_with(size, SizeUnits::Decimal, '.'), "1.57 MB");
+        assert_eq!(format_size_with(999, SizeUnits::Decimal, ','), "999 B");
+        assert_eq!(format_size_with(1000, SizeUnits::Decimal, '.'), "1.00 KB");
+    }
+
     #[test]
     fn test_apply_size_filter() {
         let entry = FileEntry {
@@ -95,5 +150,11 @@ mod tests {
         assert!(apply_size_filter(&entry, &SizeFilter::GreaterThan(1000)));
         assert!(apply_size_filter(&entry, &SizeFilter::LessThan(2000)));
         assert!(apply_size_filter(&entry, &SizeFilter::Range(1000, 2000)));
+        assert!(!apply_size_filter(&entry, &SizeFilter::GreaterThan(1024)));
+        assert!(apply_size_filter(&entry, &SizeFilter::AtLeast(1024)));
+        assert!(!apply_size_filter(&entry, &SizeFilter::AtLeast(1025)));
+        assert!(!apply_size_filter(&entry, &SizeFilter::LessThan(1024)));
+        assert!(apply_size_filter(&entry, &SizeFilter::AtMost(1024)));
+        assert!(!apply_size_filter(&entry, &SizeFilter::AtMost(1023)));
     }
 }
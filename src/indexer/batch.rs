@@ -0,0 +1,410 @@
+use crate::core::config::{BatchErrorPolicy, SearchConfig};
+use crate::core::error::Result;
+use crate::core::types::{BatchStats, FileEntry, SkippedEntry};
+use crate::filters::SensitiveFilter;
+use crate::indexer::quota::QuotaGuard;
+use crate::storage::{Database, FileStore};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Default)]
+pub struct BatchOutcome {
+    pub written: usize,
+    pub skipped: Vec<SkippedEntry>,
+    /// Whether the batch as a whole could not be written, even if some entries were
+    /// recovered one by one.
+    pub failed: bool,
+    /// Time spent writing the batch, retries and their backoff included.
+    pub commit_time: Duration,
+}
+
+/// Picks the size of the next batch from how long the last one took to commit, so commits
+/// converge toward `target_batch_ms`: the size doubles while full batches commit in under
+/// half the target and halves whenever one takes longer than it.
+#[derive(Debug, Clone)]
+pub struct BatchSizer {
+    size: usize,
+    min: usize,
+    max: usize,
+    target: Duration,
+    stats: BatchStats,
+}
+
+impl BatchSizer {
+    /// Starts at `initial`, kept within `min..=max`.
+    pub fn new(initial: usize, min: usize, max: usize, target: Duration) -> Self {
+        let min = min.max(1);
+        let max = max.max(min);
+        let size = initial.clamp(min, max);
+        Self {
+            size,
+            min,
+            max,
+            target,
+            stats: BatchStats {
+                last_size: size,
+                ..BatchStats::default()
+            },
+        }
+    }
+
+    pub fn from_config(config: &SearchConfig) -> Self {
+        Self::new(
+            config.batch_size,
+            config.min_batch_size,
+            config.max_batch_size,
+            Duration::from_millis(config.target_batch_ms),
+        )
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn stats(&self) -> BatchStats {
+        self.stats
+    }
+
+    /// Records a batch of `entries` that took `elapsed` to commit and returns the size of
+    /// the next one. A batch cut short, such as a walk's last, is too small to show that
+    /// larger ones would be fast, so only full batches grow the size.
+    pub fn observe(&mut self, entries: usize, elapsed: Duration) -> usize {
+        self.stats.batches += 1;
+        self.stats.entries += entries;
+
+        let next = if elapsed > self.target {
+            (self.size / 2).max(self.min)
+        } else if elapsed < self.target / 2 && entries >= self.size {
+            self.size.saturating_mul(2).min(self.max)
+        } else {
+            self.size
+        };
+        if next != self.size {
+            log::debug!(
+                "Batch of {} entries committed in {} ms (target {} ms), next batch {}",
+                entries,
+                elapsed.as_millis(),
+                self.target.as_millis(),
+                next
+            );
+            self.stats.adjustments += 1;
+            self.size = next;
+        }
+        self.stats.last_size = self.size;
+        self.size
+    }
+}
+
+/// Writes batches of file entries, applying the configured `BatchErrorPolicy` on failure.
+pub struct BatchWriter {
+    store: Arc<dyn FileStore>,
+    policy: BatchErrorPolicy,
+    quota: Option<QuotaGuard>,
+    sensitive: SensitiveFilter,
+}
+
+impl BatchWriter {
+    pub fn new(store: Arc<dyn FileStore>, policy: BatchErrorPolicy) -> Self {
+        Self {
+            store,
+            policy,
+            quota: None,
+            sensitive: SensitiveFilter::default(),
+        }
+    }
+
+    /// A writer applying `config`'s batch error policy and keeping `database` within its
+    /// index quota.
+    pub fn from_config(
+        store: Arc<dyn FileStore>,
+        database: Arc<Database>,
+        config: &SearchConfig,
+    ) -> Self {
+        Self::new(store, config.on_batch_error)
+            .with_quota(QuotaGuard::new(database, config))
+            .with_sensitive(SensitiveFilter::from_config(config))
+    }
+
+    /// Checks every batch against `quota` before writing it. A batch that does not fit fails
+    /// with `QuotaExceeded` whatever the error policy, since retrying cannot make room.
+    pub fn with_quota(mut self, quota: QuotaGuard) -> Self {
+        self.quota = Some(quota);
+        self
+    }
+
+    /// Masks the names of `sensitive` files in the warnings logged for entries that fail.
+    pub fn with_sensitive(mut self, sensitive: SensitiveFilter) -> Self {
+        self.sensitive = sensitive;
+        self
+    }
+
+    /// Fails with `QuotaExceeded` unless `entries` more entries fit in the index quota.
+    pub fn admit(&self, entries: usize) -> Result<()> {
+        match &self.quota {
+            Some(quota) => quota.admit(entries),
+            None => Ok(()),
+        }
+    }
+
+    pub fn write(&self, entries: &[FileEntry]) -> Result<BatchOutcome> {
+        if entries.is_empty() {
+            return Ok(BatchOutcome::default());
+        }
+
+        self.admit(entries.len())?;
+
+        let started = Instant::now();
+        let mut outcome = self.write_admitted(entries)?;
+        outcome.commit_time = started.elapsed();
+        Ok(outcome)
+    }
+
+    fn write_admitted(&self, entries: &[FileEntry]) -> Result<BatchOutcome> {
+        let error = match self.store.insert_files_batch(entries) {
+            Ok(()) => {
+                return Ok(BatchOutcome {
+                    written: entries.len(),
+                    ..Default::default()
+                })
+            }
+            Err(e) => e,
+        };
+
+        match self.policy {
+            BatchErrorPolicy::Abort => Err(error),
+            BatchErrorPolicy::Skip => {
+                log::warn!("Skipping batch of {} entries: {}", entries.len(), error);
+                let error = error.to_string();
+                Ok(BatchOutcome {
+                    written: 0,
+                    skipped: entries
+                        .iter()
+                        .map(|entry| SkippedEntry {
+                            path: entry.path.clone(),
+                            error: error.clone(),
+                        })
+                        .collect(),
+                    failed: true,
+                    ..Default::default()
+                })
+            }
+            BatchErrorPolicy::Retry {
+                attempts,
+                backoff_ms,
+            } => {
+                log::warn!("Batch of {} entries failed, retrying: {}", entries.len(), error);
+                self.retry(entries, attempts.max(1), backoff_ms)
+            }
+        }
+    }
+
+    fn retry(&self, entries: &[FileEntry], attempts: u32, backoff_ms: u64) -> Result<BatchOutcome> {
+        for attempt in 1..attempts {
+            thread::sleep(backoff(backoff_ms, attempt));
+            match self.store.insert_files_batch(entries) {
+                Ok(()) => {
+                    return Ok(BatchOutcome {
+                        written: entries.len(),
+                        ..Default::default()
+                    })
+                }
+                Err(e) => log::warn!("Batch retry {} of {} failed: {}", attempt, attempts, e),
+            }
+        }
+
+        thread::sleep(backoff(backoff_ms, attempts));
+        Ok(self.write_individually(entries))
+    }
+
+    fn write_individually(&self, entries: &[FileEntry]) -> BatchOutcome {
+        let mut outcome = BatchOutcome {
+            failed: true,
+            ..Default::default()
+        };
+
+        for entry in entries {
+            match self.store.insert_file(entry) {
+                Ok(_) => outcome.written += 1,
+                Err(e) => {
+                    log::warn!(
+                        "Failed to index {}: {}",
+                        self.sensitive.redact_path(&entry.path),
+                        self.sensitive.redact_error(&e)
+                    );
+                    outcome.skipped.push(SkippedEntry {
+                        path: entry.path.clone(),
+                        error: e.to_string(),
+                    });
+                }
+            }
+        }
+
+        outcome
+    }
+}
+
+fn backoff(base_ms: u64, attempt: u32) -> Duration {
+    Duration::from_millis(base_ms.saturating_mul(1 << (attempt - 1).min(10)))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::core::error::SearchError;
+    use crate::storage::Database;
+    use parking_lot::Mutex;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Delegates to a real database, failing the `fail_on`-th batch call (1-based) and any
+    /// write that touches the poison path. Batch calls take `delay_per_entry` for each entry
+    /// and record their size in `batch_sizes`.
+    pub(crate) struct FailingStore {
+        pub inner: Arc<Database>,
+        pub fail_on: Option<usize>,
+        pub poison: Option<PathBuf>,
+        pub delay_per_entry: Duration,
+        pub batch_calls: AtomicUsize,
+        pub batch_sizes: Mutex<Vec<usize>>,
+    }
+
+    impl FailingStore {
+        pub fn new(inner: Arc<Database>) -> Self {
+            Self {
+                inner,
+                fail_on: None,
+                poison: None,
+                delay_per_entry: Duration::ZERO,
+                batch_calls: AtomicUsize::new(0),
+                batch_sizes: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn is_poison(&self, path: &Path) -> bool {
+            self.poison.as_deref() == Some(path)
+        }
+    }
+
+    fn injected() -> SearchError {
+        SearchError::Io(std::io::Error::new(std::io::ErrorKind::Other, "disk full"))
+    }
+
+    impl FileStore for FailingStore {
+        fn insert_files_batch(&self, files: &[FileEntry]) -> Result<()> {
+            let call = self.batch_calls.fetch_add(1, Ordering::SeqCst) + 1;
+            self.batch_sizes.lock().push(files.len());
+            thread::sleep(self.delay_per_entry * files.len() as u32);
+            if Some(call) == self.fail_on || files.iter().any(|f| self.is_poison(&f.path)) {
+                return Err(injected());
+            }
+            self.inner.insert_files_batch(files)
+        }
+
+        fn insert_file(&self, file: &FileEntry) -> Result<i64> {
+            if self.is_poison(&file.path) {
+                return Err(injected());
+            }
+            self.inner.insert_file(file)
+        }
+    }
+
+    fn entries(count: usize) -> Vec<FileEntry> {
+        (0..count)
+            .map(|i| FileEntry::new(PathBuf::from(format!("/data/file{}.txt", i))))
+            .collect()
+    }
+
+    #[test]
+    fn test_abort_returns_error() {
+        let db = Arc::new(Database::in_memory(1).unwrap());
+        let mut store = FailingStore::new(Arc::clone(&db));
+        store.fail_on = Some(1);
+
+        let writer = BatchWriter::new(Arc::new(store), BatchErrorPolicy::Abort);
+        assert!(writer.write(&entries(3)).is_err());
+        assert_eq!(db.get_stats().unwrap().total_files, 0);
+    }
+
+    #[test]
+    fn test_retry_recovers_transient_failure() {
+        let db = Arc::new(Database::in_memory(1).unwrap());
+        let mut store = FailingStore::new(Arc::clone(&db));
+        store.fail_on = Some(1);
+        let store = Arc::new(store);
+
+        let policy = BatchErrorPolicy::Retry {
+            attempts: 3,
+            backoff_ms: 1,
+        };
+        let outcome = BatchWriter::new(store.clone(), policy).write(&entries(3)).unwrap();
+
+        assert_eq!(outcome.written, 3);
+        assert!(outcome.skipped.is_empty());
+        assert!(!outcome.failed);
+        assert_eq!(store.batch_calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_retry_isolates_poison_row() {
+        let db = Arc::new(Database::in_memory(1).unwrap());
+        let batch = entries(5);
+        let mut store = FailingStore::new(Arc::clone(&db));
+        store.poison = Some(batch[2].path.clone());
+        let store = Arc::new(store);
+
+        let policy = BatchErrorPolicy::Retry {
+            attempts: 2,
+            backoff_ms: 1,
+        };
+        let outcome = BatchWriter::new(store.clone(), policy).write(&batch).unwrap();
+
+        assert_eq!(outcome.written, 4);
+        assert!(outcome.failed);
+        assert_eq!(outcome.skipped.len(), 1);
+        assert_eq!(outcome.skipped[0].path, batch[2].path);
+        assert!(outcome.skipped[0].error.contains("disk full"));
+        // One original attempt and one retry as a batch; the final attempt goes per file.
+        assert_eq!(store.batch_calls.load(Ordering::SeqCst), 2);
+        assert_eq!(db.get_stats().unwrap().total_files, 4);
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_sizer_grows_under_fast_commits_and_shrinks_under_slow_ones() {
+        let mut sizer = BatchSizer::new(100, 10, 1000, ms(100));
+        assert_eq!(sizer.observe(100, ms(10)), 200);
+        assert_eq!(sizer.observe(200, ms(20)), 400);
+        // Within the target but over half of it: doubling would overshoot.
+        assert_eq!(sizer.observe(400, ms(60)), 400);
+        // A short batch says nothing about larger ones.
+        assert_eq!(sizer.observe(30, ms(1)), 400);
+        assert_eq!(sizer.observe(400, ms(250)), 200);
+
+        let stats = sizer.stats();
+        assert_eq!((stats.batches, stats.entries), (5, 1130));
+        assert_eq!((stats.adjustments, stats.last_size), (3, 200));
+        assert_eq!(stats.avg_batch_size(), 226.0);
+    }
+
+    #[test]
+    fn test_sizer_stays_within_its_bounds() {
+        let mut sizer = BatchSizer::new(5000, 10, 1000, ms(100));
+        assert_eq!(sizer.size(), 1000);
+        assert_eq!(sizer.observe(1000, ms(1)), 1000);
+        for _ in 0..10 {
+            sizer.observe(sizer.size(), ms(500));
+        }
+        assert_eq!(sizer.size(), 10);
+        assert_eq!(sizer.stats().adjustments, 7);
+
+        // Equal bounds pin the size whatever commits take.
+        let mut fixed = BatchSizer::new(1000, 64, 64, ms(100));
+        assert_eq!(fixed.observe(64, ms(1)), 64);
+        assert_eq!(fixed.observe(64, ms(900)), 64);
+        assert_eq!(fixed.stats().adjustments, 0);
+    }
+}
@@ -1,21 +1,39 @@
 use crate::core::config::SearchConfig;
-use crate::core::error::Result;
-use crate::core::types::{FileEntry, Progress, ProgressCallback};
-use crate::filters::ExclusionFilter;
+use crate::core::error::{IoResultExt, Result};
+use crate::core::metrics::EngineCounters;
+use crate::core::types::{
+    CancellationToken, ContentPreview, ContentSkip, ContentSkipReason, DocumentField,
+    EnrichmentStats, FileEntry, FilePermissions, IndexReport, Ownership, Progress,
+    ProgressCallback,
+};
+use crate::filters::{ExclusionFilter, SensitiveFilter};
+use crate::indexer::batch::{BatchSizer, BatchWriter};
+use crate::indexer::clock::TimestampClock;
 use crate::indexer::content::ContentAnalyzer;
 use crate::indexer::metadata::MetadataExtractor;
-use crate::indexer::walker::DirectoryWalker;
-use crate::storage::Database;
-use std::path::Path;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use crate::indexer::progress::ProgressDispatcher;
+use crate::indexer::quota;
+use crate::indexer::stores::StoreRoots;
+use crate::utils::hash::hash_string;
+use crate::indexer::walker::{DirectoryWalker, WalkedEntry};
+use crate::storage::{Database, FileStore};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 pub struct IndexBuilder {
     database: Arc<Database>,
     config: Arc<SearchConfig>,
     exclusion_filter: Arc<ExclusionFilter>,
     content_analyzer: Arc<ContentAnalyzer>,
+    sensitive: SensitiveFilter,
+    writer: BatchWriter,
+    clock: TimestampClock,
+    stores: StoreRoots,
     cancelled: Arc<AtomicBool>,
+    metrics: Arc<EngineCounters>,
 }
 
 impl IndexBuilder {
@@ -24,76 +42,295 @@ impl IndexBuilder {
         config: Arc<SearchConfig>,
         exclusion_filter: Arc<ExclusionFilter>,
     ) -> Self {
-        let content_analyzer = Arc::new(ContentAnalyzer::new(config.max_file_size_for_content));
+        let content_analyzer = Arc::new(ContentAnalyzer::from_config(&config));
+        let store: Arc<dyn FileStore> = database.clone();
+        let writer = BatchWriter::from_config(store, Arc::clone(&database), &config);
+        let clock = TimestampClock::new(Arc::clone(&database), Arc::clone(&config));
+        let stores = StoreRoots::from_config(&config);
+        let sensitive = SensitiveFilter::from_config(&config);
 
         Self {
             database,
             config,
             exclusion_filter,
             content_analyzer,
+            sensitive,
+            writer,
+            clock,
+            stores,
             cancelled: Arc::new(AtomicBool::new(false)),
+            metrics: Arc::new(EngineCounters::new()),
         }
     }
 
+    /// Routes file row writes through `store` instead of the database.
+    pub fn with_store(mut self, store: Arc<dyn FileStore>) -> Self {
+        self.writer = BatchWriter::from_config(store, Arc::clone(&self.database), &self.config);
+        self
+    }
+
+    /// Decides timestamp offsets with `clock` instead of one using the host's settings.
+    pub fn with_clock(mut self, clock: TimestampClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Records completed builds into `metrics` instead of a private set of counters.
+    pub fn with_metrics(mut self, metrics: Arc<EngineCounters>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
     pub fn build<P: AsRef<Path>>(
         &self,
         root: P,
         progress_callback: Option<ProgressCallback>,
     ) -> Result<usize> {
+        Ok(self.build_with_report(root, progress_callback)?.indexed)
+    }
+
+    pub fn build_with_report<P: AsRef<Path>>(
+        &self,
+        root: P,
+        progress_callback: Option<ProgressCallback>,
+    ) -> Result<IndexReport> {
+        let _active = self.metrics.begin_operation();
+        let started = Instant::now();
+        let report = self.walk_and_index(root.as_ref(), progress_callback, false)?;
+        self.metrics.record_index(started.elapsed());
+        Ok(report)
+    }
+
+    /// Writes entries' metadata only, queueing their content and image details for
+    /// [`enrich`](Self::enrich), so name and path searches work as soon as the walk is done.
+    pub fn build_quick_with_report<P: AsRef<Path>>(
+        &self,
+        root: P,
+        progress_callback: Option<ProgressCallback>,
+    ) -> Result<IndexReport> {
+        let _active = self.metrics.begin_operation();
+        let started = Instant::now();
+        let report = self.walk_and_index(root.as_ref(), progress_callback, true)?;
+        self.metrics.record_index(started.elapsed());
+        Ok(report)
+    }
+
+    /// Reads the content and image details that quick builds deferred, under `root` if given,
+    /// most recently modified files first. Each batch is committed before the next starts, so
+    /// a cancelled or interrupted run resumes where it stopped.
+    pub fn enrich(
+        &self,
+        root: Option<&Path>,
+        progress_callback: Option<ProgressCallback>,
+        cancel: &CancellationToken,
+    ) -> Result<EnrichmentStats> {
+        let progress = ProgressDispatcher::new(progress_callback, &self.config);
+        let total = self.database.pending_content_count(root)?;
+        let batch_size = self.config.batch_size.max(1);
+        let mut stats = EnrichmentStats::default();
+
+        loop {
+            if cancel.is_cancelled() {
+                stats.cancelled = true;
+                break;
+            }
+
+            let batch = self.database.pending_content_batch(root, batch_size)?;
+            if batch.is_empty() {
+                break;
+            }
+
+            // Files gone from disk since the quick build have nothing to read and simply
+            // leave the queue.
+            self.index_file_details(&batch)?;
+            let ids: Vec<i64> = batch.iter().filter_map(|entry| entry.id).collect();
+            self.database.complete_pending_content(&ids)?;
+
+            stats.enriched += batch.len();
+            progress.report(Progress::new(
+                stats.enriched,
+                total,
+                format!("Enriched {} files", stats.enriched),
+            ));
+        }
+
+        stats.remaining = self.database.pending_content_count(root)?;
+        if !stats.cancelled {
+            progress.finish(Progress::complete(
+                stats.enriched,
+                format!("Enriched {} files", stats.enriched),
+            ));
+        }
+        Ok(stats)
+    }
+
+    fn walk_and_index(
+        &self,
+        root: &Path,
+        progress_callback: Option<ProgressCallback>,
+        quick: bool,
+    ) -> Result<IndexReport> {
         let walker = DirectoryWalker::new(
             Arc::clone(&self.config),
             Arc::clone(&self.exclusion_filter),
         );
 
-        let paths = walker.walk_parallel(root)?;
-        let total_paths = paths.len();
+        // The total is unknown while the walk is still running, so progress reports
+        // a running count with total = 0 until the final update.
+        let progress = ProgressDispatcher::new(progress_callback, &self.config);
+        progress.report(Progress::new(0, 0, "Starting indexing...".to_string()));
 
-        if let Some(ref callback) = progress_callback {
-            callback(Progress::new(
-                0,
-                total_paths,
-                "Starting indexing...".to_string(),
-            ));
+        // A missing or unreadable root is an error, not an empty index.
+        if std::fs::metadata(root).at_path(root)?.is_dir() {
+            std::fs::read_dir(root).at_path(root)?;
         }
 
-        let processed = Arc::new(AtomicUsize::new(0));
-        let batch_size = self.config.batch_size;
-        let mut indexed_count = 0;
+        let utc_offset_secs = self.clock.prepare_root(root)?;
+        let mut sizer = BatchSizer::from_config(&self.config);
+        let mut batch: Vec<WalkedEntry> = Vec::with_capacity(sizer.size());
+        let mut report = IndexReport::default();
 
-        for chunk in paths.chunks(batch_size) {
+        // Walk order depends on the filesystem, so deterministic builds sort the whole walk
+        // first; batches then cover the same paths and rows are inserted in path order.
+        let walked: Box<dyn Iterator<Item = WalkedEntry>> = if self.config.deterministic {
+            let mut entries: Vec<WalkedEntry> = walker.walk_streaming(root)?.collect();
+            entries.sort_by(|a, b| a.path.cmp(&b.path));
+            Box::new(entries.into_iter())
+        } else {
+            Box::new(walker.walk_streaming(root)?)
+        };
+
+        for entry in walked {
             if self.cancelled.load(Ordering::Relaxed) {
-                break;
+                return Ok(report);
             }
 
-            let entries = self.process_batch(chunk)?;
-            self.database.insert_files_batch(&entries)?;
-
-            if self.config.enable_content_search {
-                self.index_content_batch(&entries)?;
+            batch.push(entry);
+            if batch.len() >= sizer.size() {
+                let commit_time = self.index_batch(
+                    root,
+                    &batch,
+                    utc_offset_secs,
+                    quick,
+                    &mut report,
+                    &progress,
+                )?;
+                sizer.observe(batch.len(), commit_time);
+                report.batching = sizer.stats();
+                batch.clear();
             }
+        }
+
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Ok(report);
+        }
+        if !batch.is_empty() {
+            let commit_time = self.index_batch(
+                root,
+                &batch,
+                utc_offset_secs,
+                quick,
+                &mut report,
+                &progress,
+            )?;
+            sizer.observe(batch.len(), commit_time);
+            report.batching = sizer.stats();
+        }
+        log::debug!(
+            "Indexed {} in {} batches averaging {:.0} entries, {} size adjustments",
+            root.display(),
+            report.batching.batches,
+            report.batching.avg_batch_size(),
+            report.batching.adjustments
+        );
+        if !quick && self.has_file_details() {
+            // A full build read everything an earlier quick build of this root deferred.
+            self.database.clear_pending_content(root)?;
+        }
+
+        progress.finish(Progress::complete(
+            report.indexed,
+            format!("Indexed {} files", report.indexed),
+        ));
+        Ok(report)
+    }
+
+    /// Writes one batch and everything recorded beside it, returning how long the batch
+    /// took to commit.
+    fn index_batch(
+        &self,
+        root: &Path,
+        walked: &[WalkedEntry],
+        utc_offset_secs: i32,
+        quick: bool,
+        report: &mut IndexReport,
+        progress: &ProgressDispatcher,
+    ) -> Result<Duration> {
+        let paths: Vec<&Path> = walked.iter().map(|entry| entry.path.as_path()).collect();
+        let targets: HashMap<PathBuf, PathBuf> = walked
+            .iter()
+            .filter_map(|entry| Some((entry.path.clone(), entry.link_target.clone()?)))
+            .collect();
+        let mut entries = self.process_batch(&paths, utc_offset_secs)?;
+        Self::mark_store_aliases(&mut entries, &targets);
+        let outcome = self
+            .writer
+            .write(&entries)
+            .map_err(|e| quota::with_progress(e, report.indexed))?;
 
-            indexed_count += entries.len();
-            processed.fetch_add(entries.len(), Ordering::Relaxed);
+        report.indexed += outcome.written;
+        if outcome.failed {
+            report.failed_batches += 1;
+        }
 
-            if let Some(ref callback) = progress_callback {
-                callback(Progress::new(
-                    processed.load(Ordering::Relaxed),
-                    total_paths,
-                    format!("Indexed {} files", processed.load(Ordering::Relaxed)),
-                ));
+        entries.retain(|entry| !outcome.skipped.iter().any(|s| s.path == entry.path));
+        self.record_permissions(&entries)?;
+        let entries = self.record_store_aliases(entries, &targets)?;
+        if !quick {
+            let details = self.index_file_details(&entries)?;
+            report.content_skipped.extend(details.content_skipped);
+            report.possibly_inconsistent.extend(details.possibly_inconsistent);
+        } else {
+            // Shebangs and document fields are a short read of extension-less files, emails
+            // and web pages only, so even quick builds take them rather than queue every file
+            // for enrichment.
+            if self.config.detect_shebangs {
+                self.index_script_batch(&entries)?;
+            }
+            if self.config.extract_document_fields {
+                self.index_document_batch(&entries)?;
+            }
+            if self.has_file_details() {
+                self.queue_file_details(root, &entries)?;
             }
         }
+        report.skipped.extend(outcome.skipped);
+
+        progress.report(Progress::new(
+            report.indexed,
+            0,
+            format!("Indexed {} files", report.indexed),
+        ));
 
-        Ok(indexed_count)
+        Ok(outcome.commit_time)
     }
 
-    fn process_batch(&self, paths: &[impl AsRef<Path> + Sync]) -> Result<Vec<FileEntry>> {
-        let results = MetadataExtractor::extract_batch(paths);
+    fn process_batch(
+        &self,
+        paths: &[impl AsRef<Path> + Sync],
+        utc_offset_secs: i32,
+    ) -> Result<Vec<FileEntry>> {
+        let results = MetadataExtractor::extract_batch_with_offset(paths, utc_offset_secs);
 
+        let now = self.clock.now();
         let entries: Vec<FileEntry> = results
             .into_iter()
             .filter_map(|result| match result {
-                Ok(entry) => Some(entry),
+                Ok(mut entry) => {
+                    entry.indexed_at = now;
+                    entry.last_verified = now;
+                    Some(entry)
+                }
                 Err(e) => {
                     log::warn!("Failed to extract metadata: {}", e);
                     None
@@ -104,39 +341,317 @@ impl IndexBuilder {
         Ok(entries)
     }
 
-    fn index_content_batch(&self, entries: &[FileEntry]) -> Result<()> {
-        let text_files: Vec<_> = entries
+    /// Flags the entries reached through a link into a package store, found in `targets`, as
+    /// links even when the link is one of their parent directories.
+    pub(crate) fn mark_store_aliases(
+        entries: &mut [FileEntry],
+        targets: &HashMap<PathBuf, PathBuf>,
+    ) {
+        for entry in entries {
+            if targets.contains_key(&entry.path) {
+                entry.is_symlink = true;
+            }
+        }
+    }
+
+    /// Records where the store aliases among the written `entries` point and returns the
+    /// other entries, whose content and details are read as usual. Aliases are never read:
+    /// their content is indexed once, where the store itself is.
+    pub(crate) fn record_store_aliases(
+        &self,
+        entries: Vec<FileEntry>,
+        targets: &HashMap<PathBuf, PathBuf>,
+    ) -> Result<Vec<FileEntry>> {
+        // Without stores nothing is an alias, nor can an entry have stopped being one.
+        if self.stores.is_empty() || entries.is_empty() {
+            return Ok(entries);
+        }
+
+        let paths: Vec<PathBuf> = entries.iter().map(|entry| entry.path.clone()).collect();
+        let ids = self.ids_by_path(&paths)?;
+        let links: Vec<_> = entries
             .iter()
-            .filter(|e| !e.is_directory)
+            .filter_map(|entry| Some((*ids.get(&entry.path)?, targets.get(&entry.path).cloned())))
             .collect();
+        self.database.set_link_targets(&links)?;
 
-        if text_files.is_empty() {
+        Ok(entries
+            .into_iter()
+            .filter(|entry| !targets.contains_key(&entry.path))
+            .collect())
+    }
+
+    pub(crate) fn store_roots(&self) -> &StoreRoots {
+        &self.stores
+    }
+
+    fn has_file_details(&self) -> bool {
+        self.config.enable_content_search || self.config.extract_image_metadata
+    }
+
+    fn queue_file_details(&self, root: &Path, entries: &[FileEntry]) -> Result<()> {
+        let paths: Vec<PathBuf> = entries
+            .iter()
+            .filter(|entry| !entry.is_directory)
+            .map(|entry| entry.path.clone())
+            .collect();
+        let ids = self.ids_by_path(&paths)?;
+        let ids: Vec<i64> = paths.iter().filter_map(|path| ids.get(path).copied()).collect();
+
+        self.database.queue_pending_content(root, &ids)?;
+        Ok(())
+    }
+
+    /// Stores what the configuration derives from written entries' contents: email and HTML
+    /// fields, text previews for content search, image dimensions and script interpreters.
+    pub(crate) fn index_file_details(&self, entries: &[FileEntry]) -> Result<FileDetails> {
+        let mut fields = HashMap::new();
+        if self.config.extract_document_fields {
+            fields = self.index_document_batch(entries)?;
+        }
+        let mut details = FileDetails::default();
+        if self.config.enable_content_search {
+            details = self.index_content_batch(entries, &fields)?;
+        }
+        if self.config.extract_image_metadata {
+            self.index_image_batch(entries)?;
+        }
+        if self.config.detect_shebangs {
+            self.index_script_batch(entries)?;
+        }
+        Ok(details)
+    }
+
+    /// Stores the fields of the emails and web pages among `entries` and returns them by
+    /// path, for the content index to take as well.
+    fn index_document_batch(
+        &self,
+        entries: &[FileEntry],
+    ) -> Result<HashMap<PathBuf, Vec<DocumentField>>> {
+        use rayon::prelude::*;
+
+        let read: HashMap<PathBuf, Vec<DocumentField>> = entries
+            .par_iter()
+            .filter(|entry| !entry.is_directory && entry.extension.is_some())
+            .filter_map(|entry| {
+                MetadataExtractor::document_fields(&entry.path)
+                    .map(|fields| (entry.path.clone(), fields))
+            })
+            .collect();
+        if read.is_empty() {
+            return Ok(read);
+        }
+
+        let paths: Vec<PathBuf> = read.keys().cloned().collect();
+        let fields: Vec<_> = self
+            .ids_by_path(&paths)?
+            .into_iter()
+            .filter_map(|(path, id)| Some((id, read.get(&path)?.clone())))
+            .collect();
+
+        if let Err(e) = self.database.set_document_fields(&fields) {
+            log::warn!("Failed to store document fields: {}", e);
+        }
+        Ok(read)
+    }
+
+    /// Records the ownership of the written `entries` and their directories when
+    /// `record_ownership` is on. Each directory is read once per batch.
+    pub(crate) fn record_permissions(&self, entries: &[FileEntry]) -> Result<()> {
+        use rayon::prelude::*;
+
+        if !self.config.record_ownership || entries.is_empty() {
+            return Ok(());
+        }
+
+        let parents: HashMap<&Path, Option<Ownership>> = entries
+            .iter()
+            .filter_map(|entry| entry.path.parent())
+            .collect::<HashSet<_>>()
+            .into_par_iter()
+            .map(|parent| (parent, MetadataExtractor::ownership(parent)))
+            .collect();
+        let read: Vec<_> = entries
+            .par_iter()
+            .map(|entry| {
+                let permissions = MetadataExtractor::ownership(&entry.path).map(|owner| {
+                    FilePermissions {
+                        entry: owner,
+                        parent: entry.path.parent().and_then(|parent| parents[parent]),
+                    }
+                });
+                (&entry.path, permissions)
+            })
+            .collect();
+
+        let paths: Vec<PathBuf> = read.iter().map(|(path, _)| (*path).clone()).collect();
+        let ids = self.ids_by_path(&paths)?;
+        let permissions: Vec<_> = read
+            .into_iter()
+            .filter_map(|(path, permissions)| Some((*ids.get(path)?, permissions)))
+            .collect();
+
+        if let Err(e) = self.database.set_permissions(&permissions) {
+            log::warn!("Failed to store file ownership: {}", e);
+        }
+        Ok(())
+    }
+
+    fn index_script_batch(&self, entries: &[FileEntry]) -> Result<()> {
+        use rayon::prelude::*;
+
+        let sniffed: Vec<_> = entries
+            .par_iter()
+            .filter(|entry| !entry.is_directory && entry.extension.is_none())
+            .map(|entry| (&entry.path, MetadataExtractor::script_info(&entry.path)))
+            .collect();
+        if sniffed.is_empty() {
             return Ok(());
         }
 
+        let paths: Vec<PathBuf> = sniffed.iter().map(|(path, _)| (*path).clone()).collect();
+        let ids = self.ids_by_path(&paths)?;
+        let scripts: Vec<_> = sniffed
+            .into_iter()
+            .filter_map(|(path, script)| Some((*ids.get(path)?, script)))
+            .collect();
+
+        if let Err(e) = self.database.set_scripts(&scripts) {
+            log::warn!("Failed to store script interpreters: {}", e);
+        }
+        Ok(())
+    }
+
+    /// Row ids of the entries at `paths`, which batch inserts don't report.
+    fn ids_by_path(&self, paths: &[PathBuf]) -> Result<HashMap<PathBuf, i64>> {
+        Ok(self
+            .database
+            .find_by_paths(paths)?
+            .into_iter()
+            .filter_map(|entry| Some((entry.path, entry.id?)))
+            .collect())
+    }
+
+    fn index_image_batch(&self, entries: &[FileEntry]) -> Result<()> {
+        use rayon::prelude::*;
+
+        let measured: Vec<_> = entries
+            .par_iter()
+            .filter(|entry| !entry.is_directory)
+            .filter_map(|entry| {
+                MetadataExtractor::image_dimensions(&entry.path).map(|size| (&entry.path, size))
+            })
+            .collect();
+        if measured.is_empty() {
+            return Ok(());
+        }
+
+        let paths: Vec<PathBuf> = measured.iter().map(|(path, _)| (*path).clone()).collect();
+        let ids = self.ids_by_path(&paths)?;
+        let dimensions: Vec<_> = measured
+            .into_iter()
+            .filter_map(|(path, size)| Some((*ids.get(path)?, size)))
+            .collect();
+
+        if let Err(e) = self.database.set_image_dimensions(&dimensions) {
+            log::warn!("Failed to store image dimensions: {}", e);
+        }
+        Ok(())
+    }
+
+    /// `fields` are prepended to the indexed text of the files they belong to, one
+    /// `name: value` line each, so `subject:` words are found by content search too.
+    ///
+    /// A file whose size or modification time differs after its content is read from what
+    /// was stored for it is flagged, so the next update reads it again.
+    pub(crate) fn index_content_batch(
+        &self,
+        entries: &[FileEntry],
+        fields: &HashMap<PathBuf, Vec<DocumentField>>,
+    ) -> Result<FileDetails> {
+        let unresolved: Vec<PathBuf> = entries
+            .iter()
+            .filter(|e| !e.is_directory && e.id.is_none())
+            .map(|e| e.path.clone())
+            .collect();
+        let ids = self.ids_by_path(&unresolved)?;
+        let file_id = |entry: &FileEntry| entry.id.or_else(|| ids.get(&entry.path).copied());
+
+        // Sensitive files are never read, and lose any content indexed before they matched.
+        let (sensitive, text_files): (Vec<_>, Vec<_>) = entries
+            .iter()
+            .filter(|e| !e.is_directory)
+            .partition(|e| self.sensitive.is_sensitive(&e.path));
+        let sensitive_ids: Vec<i64> = sensitive.into_iter().filter_map(file_id).collect();
+        self.database.drop_content(&sensitive_ids)?;
+
+        if text_files.is_empty() {
+            return Ok(FileDetails::default());
+        }
+
         let paths: Vec<_> = text_files.iter().map(|e| &e.path).collect();
         let results = self.content_analyzer.analyze_batch(&paths);
+        let mut details = FileDetails::default();
+        let (mut changed, mut consistent) = (Vec::new(), Vec::new());
 
         for (idx, result) in results {
-            if let Ok(Some(preview)) = result {
-                if let Some(file_id) = text_files[idx].id {
-                    if let Err(e) = self.database.insert_content(file_id, &preview) {
-                        log::warn!("Failed to insert content: {}", e);
+            let entry = text_files[idx];
+            let analyzed = match result {
+                Ok(analyzed) => analyzed,
+                // Other failures leave the file out of content search as before.
+                Err(e) => {
+                    if let Some(reason) = ContentSkipReason::of(&e) {
+                        log::warn!(
+                            "Indexing {} without content: {}",
+                            self.sensitive.redact_path(&entry.path),
+                            self.sensitive.redact_error(&e)
+                        );
+                        details.content_skipped.push(ContentSkip {
+                            path: entry.path.clone(),
+                            reason,
+                        });
                     }
+                    continue;
+                }
+            };
+            let Some(preview) = analyzed.preview else {
+                continue;
+            };
+            let preview = match fields.get(&entry.path) {
+                Some(fields) => with_field_text(preview, fields),
+                None => preview,
+            };
 
-                    if let Err(e) = self.database.insert_fts_entry(
-                        file_id,
-                        &text_files[idx].name,
-                        &text_files[idx].path.to_string_lossy(),
-                        &preview.preview,
-                    ) {
-                        log::warn!("Failed to insert FTS entry: {}", e);
-                    }
+            if let Some(file_id) = file_id(entry) {
+                if let Err(e) = self.database.insert_content(file_id, &preview) {
+                    log::warn!("Failed to insert content: {}", e);
+                }
+                // The entry's size was taken before the read, so a file written to in between
+                // shows up here even if the read itself saw no change.
+                if analyzed.changed || analyzed.len != entry.size {
+                    log::debug!(
+                        "{} changed while it was read",
+                        self.sensitive.redact_path(&entry.path)
+                    );
+                    changed.push(file_id);
+                    details.possibly_inconsistent.push(entry.path.clone());
+                } else {
+                    consistent.push(file_id);
                 }
             }
         }
+        self.database.mark_inconsistent(&changed, &consistent)?;
 
-        Ok(())
+        Ok(details)
+    }
+
+    pub(crate) fn sensitive(&self) -> &SensitiveFilter {
+        &self.sensitive
+    }
+
+    #[cfg(test)]
+    pub(crate) fn content_analyzer(&self) -> &ContentAnalyzer {
+        &self.content_analyzer
     }
 
     pub fn cancel(&self) {
@@ -152,10 +667,38 @@ impl IndexBuilder {
     }
 }
 
+/// What reading written entries' contents turned up.
+#[derive(Debug, Default)]
+pub(crate) struct FileDetails {
+    /// Files left out of content search, with the reason.
+    pub content_skipped: Vec<ContentSkip>,
+    /// Files that changed while their content was read.
+    pub possibly_inconsistent: Vec<PathBuf>,
+}
+
+/// `preview` led by one `name: value` line per field. The date is left out, as its RFC 3339
+/// form is no help to word search.
+fn with_field_text(mut preview: ContentPreview, fields: &[DocumentField]) -> ContentPreview {
+    let text: String = fields
+        .iter()
+        .filter(|field| field.name != "date")
+        .map(|field| format!("{}: {}\n", field.name, field.value))
+        .collect();
+    if text.is_empty() {
+        return preview;
+    }
+
+    // Files whose fields differ keep distinct blobs even when their bodies match.
+    preview.content_hash = hash_string(&format!("{}{}", text, preview.content_hash));
+    preview.preview.insert_str(0, &text);
+    preview
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::config::SearchConfig;
+    use crate::core::config::{BatchErrorPolicy, ContentSampling, ProgressDelivery, SearchConfig};
+    use crate::indexer::batch::tests::FailingStore;
     use std::fs;
     use tempfile::TempDir;
 
@@ -203,4 +746,479 @@ mod tests {
         let count = builder.build(root, None).unwrap();
         assert_eq!(count, 0);
     }
+
+    #[test]
+    fn test_streaming_build_matches_walk() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for d in 0..5 {
+            fs::create_dir(root.join(format!("dir{}", d))).unwrap();
+            for f in 0..7 {
+                fs::write(root.join(format!("dir{}/file{}.txt", d, f)), "content").unwrap();
+            }
+        }
+
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let config = Arc::new(SearchConfig {
+            index_hidden_files: true,
+            batch_size: 4,
+            ..Default::default()
+        });
+        let filter = Arc::new(ExclusionFilter::from_patterns(&[]).unwrap());
+
+        let reports = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&reports);
+        let callback: ProgressCallback =
+            Box::new(move |progress| sink.lock().unwrap().push(progress));
+
+        let builder = IndexBuilder::new(db.clone(), config.clone(), filter.clone());
+        let count = builder.build(root, Some(callback)).unwrap();
+
+        let mut expected = DirectoryWalker::new(config, filter).walk(root).unwrap();
+        expected.sort();
+        let mut indexed: Vec<_> = db
+            .get_all_files(1000, 0)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect();
+        indexed.sort();
+
+        assert_eq!(count, 35);
+        assert_eq!(indexed, expected);
+
+        let reports = reports.lock().unwrap();
+        let (last, running) = reports.split_last().unwrap();
+        assert!(running.iter().all(|p| p.total == 0 && p.percentage == 0.0));
+        assert!(last.is_complete());
+        assert_eq!((last.current, last.percentage), (35, 100.0));
+    }
+
+    #[test]
+    fn test_cancellation_between_batches() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for i in 0..50 {
+            fs::write(root.join(format!("file{}.txt", i)), "content").unwrap();
+        }
+
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let config = Arc::new(SearchConfig {
+            index_hidden_files: true,
+            batch_size: 10,
+            min_batch_size: 10,
+            max_batch_size: 10,
+            progress_delivery: ProgressDelivery::Inline,
+            ..Default::default()
+        });
+        let filter = Arc::new(ExclusionFilter::from_patterns(&[]).unwrap());
+
+        let builder = IndexBuilder::new(db, config, filter);
+        let cancel = Arc::clone(&builder.cancelled);
+        let callback: ProgressCallback = Box::new(move |progress| {
+            if progress.current >= 20 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+        });
+
+        assert_eq!(builder.build(root, Some(callback)).unwrap(), 20);
+    }
+
+    #[test]
+    fn test_progress_callback_cannot_stall_or_fail_a_build() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for i in 0..40 {
+            fs::write(root.join(format!("file{}.txt", i)), "content").unwrap();
+        }
+
+        let build = |delivery, callback: ProgressCallback| {
+            let db = Arc::new(Database::in_memory(10).unwrap());
+            let config = Arc::new(SearchConfig {
+                index_hidden_files: true,
+                batch_size: 2,
+                progress_delivery: delivery,
+                progress_interval_ms: 0,
+                ..Default::default()
+            });
+            let filter = Arc::new(ExclusionFilter::from_patterns(&[]).unwrap());
+            IndexBuilder::new(db, config, filter).build(root, Some(callback))
+        };
+
+        let reports = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&reports);
+        let slow: ProgressCallback = Box::new(move |progress| {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            sink.lock().unwrap().push(progress);
+        });
+        let started = std::time::Instant::now();
+        assert_eq!(build(ProgressDelivery::Async, slow).unwrap(), 40);
+        // Inline delivery would sleep through 22 updates; the queue holds at most 8.
+        assert!(started.elapsed() < std::time::Duration::from_millis(1000));
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.iter().filter(|p| p.is_complete()).count(), 1);
+        assert!(reports.last().unwrap().is_complete());
+
+        for delivery in [ProgressDelivery::Inline, ProgressDelivery::Async] {
+            let panicking: ProgressCallback = Box::new(|_| panic!("callback bug"));
+            assert_eq!(build(delivery, panicking).unwrap(), 40);
+        }
+    }
+
+    fn failing_builder(
+        root: &Path,
+        policy: BatchErrorPolicy,
+        configure: impl FnOnce(&mut FailingStore),
+    ) -> (Arc<Database>, IndexBuilder) {
+        for i in 0..12 {
+            fs::write(root.join(format!("file{:02}.txt", i)), "content").unwrap();
+        }
+
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let config = Arc::new(SearchConfig {
+            index_hidden_files: true,
+            batch_size: 4,
+            min_batch_size: 4,
+            max_batch_size: 4,
+            on_batch_error: policy,
+            ..Default::default()
+        });
+        let filter = Arc::new(ExclusionFilter::from_patterns(&[]).unwrap());
+
+        let mut store = FailingStore::new(Arc::clone(&db));
+        configure(&mut store);
+        let builder = IndexBuilder::new(db.clone(), config, filter).with_store(Arc::new(store));
+        (db, builder)
+    }
+
+    #[test]
+    fn test_abort_keeps_earlier_batches() {
+        let temp_dir = TempDir::new().unwrap();
+        let (db, builder) = failing_builder(temp_dir.path(), BatchErrorPolicy::Abort, |store| {
+            store.fail_on = Some(2)
+        });
+
+        assert!(builder.build_with_report(temp_dir.path(), None).is_err());
+        assert_eq!(db.get_stats().unwrap().total_files, 4);
+    }
+
+    #[test]
+    fn test_skip_reports_failed_batch() {
+        let temp_dir = TempDir::new().unwrap();
+        let (db, builder) = failing_builder(temp_dir.path(), BatchErrorPolicy::Skip, |store| {
+            store.fail_on = Some(2)
+        });
+
+        let report = builder.build_with_report(temp_dir.path(), None).unwrap();
+
+        assert!(!report.is_complete());
+        assert_eq!(report.indexed, 8);
+        assert_eq!(report.skipped.len(), 4);
+        assert_eq!(report.failed_batches, 1);
+        assert_eq!(db.get_stats().unwrap().total_files, 8);
+        for skipped in &report.skipped {
+            assert!(db.find_by_path(&skipped.path).unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn test_retry_skips_only_poison_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let poison = temp_dir.path().join("file05.txt");
+        let policy = BatchErrorPolicy::Retry {
+            attempts: 2,
+            backoff_ms: 1,
+        };
+        let (db, builder) = failing_builder(temp_dir.path(), policy, |store| {
+            store.poison = Some(poison.clone())
+        });
+
+        let report = builder.build_with_report(temp_dir.path(), None).unwrap();
+
+        assert_eq!(report.indexed, 11);
+        assert_eq!(report.failed_batches, 1);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].path, poison);
+        assert_eq!(db.get_stats().unwrap().total_files, 11);
+    }
+
+    /// Indexes 800 files into a store taking 1 ms per entry, with commits aimed at 100 ms,
+    /// cancelling once the sixth batch starts. Returns the batch sizes written and how long
+    /// the build ran on after the cancel.
+    fn build_against_slow_store(batch_size: usize) -> (Vec<usize>, IndexReport, Duration) {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for i in 0..800 {
+            fs::write(root.join(format!("file{:03}.txt", i)), "content").unwrap();
+        }
+
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let config = Arc::new(SearchConfig {
+            index_hidden_files: true,
+            batch_size,
+            min_batch_size: 4,
+            max_batch_size: 512,
+            target_batch_ms: 100,
+            ..Default::default()
+        });
+        let filter = Arc::new(ExclusionFilter::from_patterns(&[]).unwrap());
+        let mut store = FailingStore::new(Arc::clone(&db));
+        store.delay_per_entry = Duration::from_millis(1);
+        let store = Arc::new(store);
+        let builder = IndexBuilder::new(db, config, filter).with_store(store.clone());
+
+        let cancel = Arc::clone(&builder.cancelled);
+        let watched = Arc::clone(&store);
+        let canceller = std::thread::spawn(move || {
+            while watched.batch_calls.load(Ordering::SeqCst) < 6 {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            cancel.store(true, Ordering::Relaxed);
+            Instant::now()
+        });
+        let report = builder.build_with_report(root, None).unwrap();
+        let returned = Instant::now();
+        let cancelled_at = canceller.join().unwrap();
+
+        let sizes = store.batch_sizes.lock().clone();
+        (sizes, report, returned.duration_since(cancelled_at))
+    }
+
+    #[test]
+    fn test_batch_size_converges_to_target_commit_latency() {
+        // 64 entries commit in about 64 ms, within the 100 ms target but over half of it.
+        let (sizes, report, _) = build_against_slow_store(8);
+        assert_eq!(&sizes[..4], &[8, 16, 32, 64]);
+        assert!(sizes[4..].iter().all(|size| (32..=64).contains(size)), "{:?}", sizes);
+        assert!(report.batching.adjustments >= 3);
+        assert_eq!(report.batching.batches, sizes.len());
+
+        let (sizes, report, cancel_latency) = build_against_slow_store(256);
+        assert_eq!(&sizes[..3], &[256, 128, 64]);
+        assert!(sizes[3..].iter().all(|size| (32..=64).contains(size)), "{:?}", sizes);
+        assert_eq!(report.indexed, sizes.iter().sum::<usize>());
+        // Only the batch in flight is finished after a cancel, and it is sized to the target.
+        assert!(cancel_latency < Duration::from_millis(100), "{:?}", cancel_latency);
+    }
+
+    #[test]
+    fn test_tail_only_content_is_searchable_when_sampled() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let mut log = "INFO service started\n".repeat(200);
+        log.push_str("ERROR zebracorn exhausted\n");
+        fs::write(root.join("service.out"), log).unwrap();
+
+        for (sampling, searchable) in [
+            (ContentSampling::Head, false),
+            (ContentSampling::Tail { tail_bytes: 256 }, true),
+            (
+                ContentSampling::HeadAndTail {
+                    head_bytes: 256,
+                    tail_bytes: 256,
+                },
+                true,
+            ),
+        ] {
+            let db = Arc::new(Database::in_memory(4).unwrap());
+            let config = Arc::new(SearchConfig {
+                index_hidden_files: true,
+                enable_content_search: true,
+                content_sampling: sampling,
+                ..Default::default()
+            });
+            let filter = Arc::new(ExclusionFilter::from_patterns(&[]).unwrap());
+
+            IndexBuilder::new(db.clone(), config, filter)
+                .build(root, None)
+                .unwrap();
+
+            let found = db.search_content_files("zebracorn", 10).unwrap();
+            assert_eq!(found.len() == 1, searchable, "{:?}", sampling);
+        }
+    }
+
+    #[test]
+    fn test_sensitive_files_are_indexed_without_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join(".ssh")).unwrap();
+        for name in ["notes.txt", "server.pem", ".ssh/config"] {
+            fs::write(root.join(name), "zebracorn\n").unwrap();
+        }
+
+        let db = Arc::new(Database::in_memory(4).unwrap());
+        let build = |sensitive_patterns: Vec<String>| {
+            let config = Arc::new(SearchConfig {
+                index_hidden_files: true,
+                enable_content_search: true,
+                sensitive_patterns,
+                ..Default::default()
+            });
+            let filter = Arc::new(ExclusionFilter::from_patterns(&[]).unwrap());
+            IndexBuilder::new(db.clone(), config, filter).build(root, None).unwrap();
+        };
+
+        build(Vec::new());
+        assert_eq!(db.search_content_files("zebracorn", 10).unwrap().len(), 3);
+
+        // Files that become sensitive lose the content indexed before.
+        build(SearchConfig::default().sensitive_patterns);
+        let found = db.search_content_files("zebracorn", 10).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].path, root.join("notes.txt"));
+        assert_eq!(db.get_stats().unwrap().indexed_files, 1);
+        for name in ["server.pem", ".ssh/config"] {
+            assert!(db.find_by_path(&root.join(name)).unwrap().is_some(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_file_written_during_read_is_flagged() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let growing = root.join("growing.log");
+        fs::write(&growing, "first entry\n").unwrap();
+        fs::write(root.join("steady.txt"), "steady\n").unwrap();
+
+        let db = Arc::new(Database::in_memory(4).unwrap());
+        let config = Arc::new(SearchConfig {
+            index_hidden_files: true,
+            enable_content_search: true,
+            ..Default::default()
+        });
+        let filter = Arc::new(ExclusionFilter::from_patterns(&[]).unwrap());
+        let builder = IndexBuilder::new(db.clone(), config, filter);
+        builder
+            .content_analyzer()
+            .set_read_hook(Some(Arc::new(|path: &Path| {
+                if path.ends_with("growing.log") {
+                    let mut file = fs::OpenOptions::new().append(true).open(path).unwrap();
+                    std::io::Write::write_all(&mut file, b"second entry\n").unwrap();
+                }
+            })));
+
+        let report = builder.build_with_report(root, None).unwrap();
+
+        assert_eq!(report.indexed, 2);
+        assert_eq!(report.possibly_inconsistent, vec![growing.clone()]);
+        let flagged: Vec<PathBuf> =
+            db.inconsistent_files().unwrap().into_iter().map(|entry| entry.path).collect();
+        assert_eq!(flagged, vec![growing]);
+        assert_eq!(db.search_content_files("first", 10).unwrap().len(), 1);
+        assert!(db.search_content_files("second", 10).unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_fifo_is_indexed_without_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let fifo = root.join("events.pipe");
+        let status = std::process::Command::new("mkfifo").arg(&fifo).status().unwrap();
+        assert!(status.success());
+        fs::write(root.join("notes.txt"), "zebracorn sighting").unwrap();
+
+        let db = Arc::new(Database::in_memory(4).unwrap());
+        let config = Arc::new(SearchConfig {
+            index_hidden_files: true,
+            enable_content_search: true,
+            ..Default::default()
+        });
+        let filter = Arc::new(ExclusionFilter::from_patterns(&[]).unwrap());
+
+        let report = IndexBuilder::new(db.clone(), config, filter)
+            .build_with_report(root, None)
+            .unwrap();
+
+        assert!(report.is_complete());
+        assert_eq!(db.search_by_name("events", 10).unwrap().len(), 1);
+        assert_eq!(db.search_content_files("zebracorn", 10).unwrap().len(), 1);
+        assert_eq!(report.content_skipped.len(), 1);
+        assert_eq!(report.content_skipped[0].path, fifo);
+        assert_eq!(report.content_skipped[0].reason, ContentSkipReason::NotRegularFile);
+    }
+
+    #[test]
+    fn test_quick_build_defers_content_until_enriched() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join("docs")).unwrap();
+        for i in 0..25 {
+            fs::write(root.join(format!("docs/note{:02}.txt", i)), "zebracorn sighting").unwrap();
+        }
+
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let config = Arc::new(SearchConfig {
+            index_hidden_files: true,
+            enable_content_search: true,
+            batch_size: 10,
+            ..Default::default()
+        });
+        let filter = Arc::new(ExclusionFilter::from_patterns(&[]).unwrap());
+        let builder = IndexBuilder::new(db.clone(), config, filter);
+
+        let report = builder.build_quick_with_report(root, None).unwrap();
+        assert_eq!(report.indexed, 25);
+        assert_eq!(db.search_by_name("note07", 10).unwrap().len(), 1);
+        assert_eq!(db.get_stats().unwrap().indexed_files, 0);
+        assert!(db.search_content_files("zebracorn", 100).unwrap().is_empty());
+        assert_eq!(db.pending_content_count(Some(root)).unwrap(), 25);
+
+        let cancel = CancellationToken::new();
+        let stop = cancel.clone();
+        let callback: ProgressCallback = Box::new(move |progress| {
+            assert_eq!(progress.total, 25);
+            stop.cancel();
+        });
+        let stats = builder.enrich(Some(root), Some(callback), &cancel).unwrap();
+        assert_eq!(
+            stats,
+            EnrichmentStats { enriched: 10, remaining: 15, cancelled: true }
+        );
+        assert_eq!(db.search_content_files("zebracorn", 100).unwrap().len(), 10);
+        let progress = db.enrichment_progress().unwrap();
+        assert_eq!(progress.len(), 1);
+        assert_eq!((progress[0].total, progress[0].pending), (25, 15));
+
+        let stats = builder.enrich(None, None, &CancellationToken::new()).unwrap();
+        assert_eq!(stats, EnrichmentStats { enriched: 15, remaining: 0, cancelled: false });
+        assert_eq!(db.search_content_files("zebracorn", 100).unwrap().len(), 25);
+        assert!(db.enrichment_progress().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_full_build_clears_pending_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for i in 0..5 {
+            fs::write(root.join(format!("note{}.txt", i)), "zebracorn sighting").unwrap();
+        }
+
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let filter = Arc::new(ExclusionFilter::from_patterns(&[]).unwrap());
+        let metadata_only = Arc::new(SearchConfig {
+            index_hidden_files: true,
+            ..Default::default()
+        });
+        IndexBuilder::new(db.clone(), metadata_only, filter.clone())
+            .build_quick_with_report(root, None)
+            .unwrap();
+        assert_eq!(db.pending_content_count(None).unwrap(), 0);
+
+        let config = Arc::new(SearchConfig {
+            index_hidden_files: true,
+            enable_content_search: true,
+            ..Default::default()
+        });
+        let builder = IndexBuilder::new(db.clone(), config, filter);
+        builder.build_quick_with_report(root, None).unwrap();
+        assert_eq!(db.pending_content_count(None).unwrap(), 5);
+
+        builder.build(root, None).unwrap();
+        assert_eq!(db.pending_content_count(None).unwrap(), 0);
+        assert!(db.enrichment_progress().unwrap().is_empty());
+        assert_eq!(db.search_content_files("zebracorn", 100).unwrap().len(), 5);
+    }
 }
@@ -0,0 +1,167 @@
+use crate::core::config::SearchConfig;
+use crate::core::error::Result;
+use crate::core::types::TimestampAdjustment;
+use crate::storage::Database;
+use crate::utils::filesystem::{filesystem_type, is_local_time_filesystem};
+use chrono::{DateTime, Local, Utc};
+use std::path::Path;
+use std::sync::Arc;
+
+type FilesystemProbe = fn(&Path) -> Option<String>;
+
+/// Reads the current time, which indexing stamps entries' `indexed_at` and `last_verified`
+/// with.
+pub type WallClock = fn() -> DateTime<Utc>;
+
+/// The time `deterministic` builds stamp entries with unless given a clock: the Unix epoch.
+pub fn frozen_time() -> DateTime<Utc> {
+    DateTime::UNIX_EPOCH
+}
+
+/// Decides which UTC offset applies to raw timestamps under an indexed root.
+///
+/// Local-time filesystems such as FAT get the host's current offset the first time their
+/// root is indexed. The offset is recorded with the root and reused afterwards, so updates
+/// and verification from a host in another timezone compare like with like.
+#[derive(Clone)]
+pub struct TimestampClock {
+    database: Arc<Database>,
+    config: Arc<SearchConfig>,
+    host_offset_secs: i32,
+    probe: FilesystemProbe,
+    now: WallClock,
+}
+
+impl TimestampClock {
+    pub fn new(database: Arc<Database>, config: Arc<SearchConfig>) -> Self {
+        let now: WallClock = if config.deterministic {
+            frozen_time
+        } else {
+            Utc::now
+        };
+        Self {
+            database,
+            config,
+            host_offset_secs: Local::now().offset().local_minus_utc(),
+            probe: |path| filesystem_type(path),
+            now,
+        }
+    }
+
+    /// Uses `offset_secs` as the host's local time minus UTC instead of the system setting.
+    pub fn with_host_offset(mut self, offset_secs: i32) -> Self {
+        self.host_offset_secs = offset_secs;
+        self
+    }
+
+    /// Replaces filesystem type detection, e.g. to simulate a FAT volume.
+    pub fn with_probe(mut self, probe: FilesystemProbe) -> Self {
+        self.probe = probe;
+        self
+    }
+
+    /// Reads the current time from `now` instead of the system clock.
+    pub fn with_wall_clock(mut self, now: WallClock) -> Self {
+        self.now = now;
+        self
+    }
+
+    /// The time to stamp indexed entries with.
+    pub fn now(&self) -> DateTime<Utc> {
+        (self.now)()
+    }
+
+    /// The adjustment a fresh index of `root` needs, or `None` when its timestamps are UTC.
+    pub fn detect(&self, root: &Path) -> Option<TimestampAdjustment> {
+        let assumed = self.config.assume_local_time_paths.iter().any(|p| root.starts_with(p));
+        let filesystem = if assumed {
+            None
+        } else {
+            Some((self.probe)(root).filter(|fs| is_local_time_filesystem(fs))?)
+        };
+
+        Some(TimestampAdjustment {
+            root: root.to_path_buf(),
+            filesystem,
+            utc_offset_secs: self.host_offset_secs,
+            recorded_at: self.now(),
+        })
+    }
+
+    /// Detects the adjustment for a full index of `root` and records it, replacing whatever
+    /// an earlier run recorded. Returns the offset to apply.
+    pub fn prepare_root(&self, root: &Path) -> Result<i32> {
+        match self.detect(root) {
+            Some(adjustment) => {
+                log::info!(
+                    "{} stores local time; shifting its timestamps by {}s",
+                    root.display(),
+                    -adjustment.utc_offset_secs
+                );
+                self.database.record_timestamp_adjustment(&adjustment)?;
+                Ok(adjustment.utc_offset_secs)
+            }
+            None => {
+                self.database.clear_timestamp_adjustment(root)?;
+                Ok(0)
+            }
+        }
+    }
+
+    /// The offset for `path`: the one recorded for the closest indexed root containing it,
+    /// otherwise whatever detection says now.
+    pub fn offset_for(&self, path: &Path) -> Result<i32> {
+        let recorded = self
+            .database
+            .get_timestamp_adjustments()?
+            .into_iter()
+            .filter(|adjustment| path.starts_with(&adjustment.root))
+            .max_by_key(|adjustment| adjustment.root.components().count());
+
+        Ok(match recorded {
+            Some(adjustment) => adjustment.utc_offset_secs,
+            None => self.detect(path).map_or(0, |adjustment| adjustment.utc_offset_secs),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_recorded_offset_outlives_host_timezone() {
+        let db = Arc::new(Database::in_memory(2).unwrap());
+        let config = Arc::new(SearchConfig::default());
+        let camera = Path::new("/media/camera");
+
+        let indexing_host = TimestampClock::new(Arc::clone(&db), Arc::clone(&config))
+            .with_host_offset(7200)
+            .with_probe(|path| {
+                Some(if path.starts_with("/media") { "vfat" } else { "ext4" }.to_string())
+            });
+        assert_eq!(indexing_host.prepare_root(camera).unwrap(), 7200);
+        assert_eq!(indexing_host.prepare_root(Path::new("/home/me")).unwrap(), 0);
+
+        let adjustments = db.get_timestamp_adjustments().unwrap();
+        assert_eq!(adjustments.len(), 1);
+        assert_eq!(adjustments[0].filesystem.as_deref(), Some("vfat"));
+
+        let other_host = indexing_host.clone().with_host_offset(-18000);
+        assert_eq!(other_host.offset_for(&camera.join("DCIM/img.jpg")).unwrap(), 7200);
+        assert_eq!(other_host.offset_for(Path::new("/media/other/a.txt")).unwrap(), -18000);
+        assert_eq!(other_host.offset_for(Path::new("/home/me/a.txt")).unwrap(), 0);
+
+        let share = SearchConfig {
+            assume_local_time_paths: vec![PathBuf::from("/mnt/share")],
+            ..SearchConfig::default()
+        };
+        let clock = TimestampClock::new(db, Arc::new(share))
+            .with_host_offset(3600)
+            .with_probe(|_| None);
+        let adjustment = clock.detect(Path::new("/mnt/share/photos")).unwrap();
+        assert_eq!((adjustment.filesystem, adjustment.utc_offset_secs), (None, 3600));
+        assert!(clock.detect(Path::new("/mnt/other")).is_none());
+    }
+}
@@ -1,13 +1,49 @@
-use crate::core::error::Result;
-use crate::core::types::ContentPreview;
-use crate::utils::encoding::{detect_encoding, is_likely_text, read_file_with_encoding};
-use std::fs::File;
-use std::io::Read;
+use crate::core::config::{ContentSampling, ContentSamplingRule, SearchConfig};
+use crate::core::error::{IoResultExt, Result, SearchError};
+use crate::core::types::{ChunkHit, ContentPreview, MatchField, MatchLocation};
+use crate::utils::encoding::{decode_text, detect_encoding, is_likely_text, read_file_with_encoding};
+use crate::utils::hash::hash_string;
+use std::fs::{File, Metadata};
+use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Marks the gap between the two halves of a head-and-tail sample.
+pub const SAMPLE_SEPARATOR: &str = "\n[...]\n";
+
+/// Bytes read between checks of the time budget.
+const READ_CHUNK: usize = 64 * 1024;
+/// Bytes inspected to tell text from binary and to detect the encoding.
+const PROBE_BYTES: u64 = 8192;
+/// Most context text [`ContentAnalyzer::get_line_context`] returns for one file.
+pub const MAX_CONTEXT_BYTES: usize = 16 * 1024;
+/// Wrapped around the match in snippets, like Markdown's bold.
+pub const HIGHLIGHT_START: &str = "**";
+pub const HIGHLIGHT_END: &str = "**";
+
+/// Called between the reads of a file, so tests can change it mid-analysis.
+#[cfg(test)]
+pub(crate) type ReadHook = std::sync::Arc<dyn Fn(&Path) + Send + Sync>;
+
+/// A file's content as [`ContentAnalyzer::analyze_tracked`] read it.
+#[derive(Debug, Clone)]
+pub struct AnalyzedContent {
+    pub preview: Option<ContentPreview>,
+    /// The file's size when the read started.
+    pub len: u64,
+    /// The file's size or modification time was different once the content was read, so the
+    /// preview may mix two versions of it. Always `false` without a preview.
+    pub changed: bool,
+}
 
 pub struct ContentAnalyzer {
     max_file_size: u64,
     preview_length: usize,
+    sampling: ContentSampling,
+    sampling_rules: Vec<ContentSamplingRule>,
+    timeout: Option<Duration>,
+    #[cfg(test)]
+    read_hook: std::sync::Mutex<Option<ReadHook>>,
 }
 
 impl ContentAnalyzer {
@@ -15,22 +51,117 @@ impl ContentAnalyzer {
         Self {
             max_file_size,
             preview_length: 1000,
+            sampling: ContentSampling::Head,
+            sampling_rules: Vec::new(),
+            timeout: None,
+            #[cfg(test)]
+            read_hook: std::sync::Mutex::new(None),
         }
     }
 
+    pub fn from_config(config: &SearchConfig) -> Self {
+        let timeout = (config.content_analysis_timeout_ms > 0)
+            .then(|| Duration::from_millis(config.content_analysis_timeout_ms));
+        Self::new(config.max_file_size_for_content)
+            .with_sampling(config.content_sampling, config.content_sampling_rules.clone())
+            .with_timeout(timeout)
+    }
+
+    /// Gives up on a file with `SearchError::ContentTimeout` once analyzing it has taken
+    /// longer than `timeout`. The budget is checked between chunks, so no thread is spawned.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_sampling(mut self, sampling: ContentSampling, rules: Vec<ContentSamplingRule>) -> Self {
+        self.sampling = sampling;
+        self.sampling_rules = rules;
+        self
+    }
+
+    pub fn sampling_for(&self, path: &Path) -> ContentSampling {
+        self.sampling_rules
+            .iter()
+            .find(|rule| rule.matches(path))
+            .map_or(self.sampling, |rule| rule.sampling)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn set_read_hook(&self, hook: Option<ReadHook>) {
+        *self.read_hook.lock().unwrap() = hook;
+    }
+
+    fn between_reads(&self, _path: &Path) {
+        #[cfg(test)]
+        if let Some(hook) = self.read_hook.lock().unwrap().clone() {
+            hook(_path);
+        }
+    }
+
+    /// Reads a preview of a regular file's text. Binary and oversized files give `None`;
+    /// FIFOs, sockets and devices fail with `SearchError::NotRegularFile` without being opened.
     pub fn analyze<P: AsRef<Path>>(&self, path: P) -> Result<Option<ContentPreview>> {
+        Ok(self.analyze_tracked(path)?.preview)
+    }
+
+    /// Like [`analyze`](Self::analyze), telling as well whether the file changed while it was
+    /// read. That costs one more stat of each file a preview is read from.
+    pub fn analyze_tracked<P: AsRef<Path>>(&self, path: P) -> Result<AnalyzedContent> {
         let path = path.as_ref();
-        let metadata = std::fs::metadata(path)?;
 
-        if metadata.len() > self.max_file_size {
+        let metadata = std::fs::metadata(path).at_path(path)?;
+        if !metadata.is_file() {
+            return Err(SearchError::NotRegularFile(path.to_path_buf()));
+        }
+        let before = FileState::of(&metadata);
+
+        // A file growing while it is read stops at the length it had when analysis started.
+        let len = metadata.len();
+        let budget = Budget::start(path, self.timeout);
+        let preview = match self.sampling_for(path) {
+            ContentSampling::Head => self.analyze_head(path, len, &budget),
+            ContentSampling::Tail { tail_bytes } => {
+                self.analyze_sample(path, len, 0, tail_bytes, &budget)
+            }
+            ContentSampling::HeadAndTail {
+                head_bytes,
+                tail_bytes,
+            } => self.analyze_sample(path, len, head_bytes, tail_bytes, &budget),
+        }
+        .map_err(|e| e.at_path(path))?;
+
+        // A file that is gone by now changed as much as one that was written to.
+        let changed = preview.is_some()
+            && std::fs::metadata(path).map_or(true, |after| FileState::of(&after) != before);
+        Ok(AnalyzedContent {
+            preview,
+            len,
+            changed,
+        })
+    }
+
+    fn analyze_head(
+        &self,
+        path: &Path,
+        len: u64,
+        budget: &Budget,
+    ) -> Result<Option<ContentPreview>> {
+        if len > self.max_file_size {
             return Ok(None);
         }
 
-        if !self.is_text_file(path)? {
+        let mut file = File::open(path)?;
+        let mut bytes = Vec::with_capacity(len as usize);
+        budget.read(&mut file, &mut bytes, PROBE_BYTES.min(len))?;
+        if !is_likely_text(&bytes) {
             return Ok(None);
         }
+        let encoding = detect_encoding(&bytes);
 
-        let content = read_file_with_encoding(path, self.max_file_size)?;
+        self.between_reads(path);
+        budget.read(&mut file, &mut bytes, len)?;
+        let content = decode_text(&bytes);
 
         let preview = if content.len() > self.preview_length {
             content.chars().take(self.preview_length).collect()
@@ -38,48 +169,90 @@ impl ContentAnalyzer {
             content.clone()
         };
 
-        let word_count = content.split_whitespace().count();
-        let line_count = content.lines().count();
+        let (word_count, line_count) = budget.count_words_and_lines(&content)?;
+
+        Ok(Some(ContentPreview {
+            preview,
+            word_count,
+            line_count,
+            encoding: encoding.name().to_string(),
+            content_hash: hash_string(&content),
+            sampled: false,
+        }))
+    }
 
+    /// Reads up to `head_bytes` from the start and `tail_bytes` from the end, cutting each
+    /// at a line boundary. The whole sample becomes the preview, so it is fully searchable.
+    fn analyze_sample(
+        &self,
+        path: &Path,
+        len: u64,
+        head_bytes: u64,
+        tail_bytes: u64,
+        budget: &Budget,
+    ) -> Result<Option<ContentPreview>> {
         let mut file = File::open(path)?;
-        let mut buffer = vec![0u8; 8192.min(metadata.len() as usize)];
-        file.read_exact(&mut buffer)?;
 
-        let encoding = detect_encoding(&buffer);
+        let sampled = len > head_bytes.saturating_add(tail_bytes);
+        let (head, tail) = if sampled {
+            let mut head = Vec::with_capacity(head_bytes as usize);
+            budget.read(&mut file, &mut head, head_bytes)?;
+            if let Some(end) = head.iter().rposition(|&b| b == b'\n') {
+                head.truncate(end + 1);
+            }
+
+            self.between_reads(path);
+            let mut tail = Vec::with_capacity(tail_bytes as usize);
+            file.seek(SeekFrom::Start(len - tail_bytes))?;
+            budget.read(&mut file, &mut tail, tail_bytes)?;
+            // The sample almost always starts mid-line; drop the partial line.
+            if let Some(start) = tail.iter().position(|&b| b == b'\n') {
+                tail.drain(..=start);
+            }
+            (head, tail)
+        } else {
+            let mut content = Vec::with_capacity(len as usize);
+            budget.read(&mut file, &mut content, len)?;
+            self.between_reads(path);
+            (Vec::new(), content)
+        };
+
+        if !is_likely_text(&head) || !is_likely_text(&tail) {
+            return Ok(None);
+        }
+
+        let content = match (head.is_empty(), decode_text(&tail)) {
+            (true, tail) => tail,
+            (false, tail) => format!("{}{}{}", decode_text(&head), SAMPLE_SEPARATOR, tail),
+        };
+        let encoding = detect_encoding(if head.is_empty() { &tail } else { &head });
+        let (word_count, line_count) = budget.count_words_and_lines(&content)?;
 
         Ok(Some(ContentPreview {
-            preview,
             word_count,
             line_count,
             encoding: encoding.name().to_string(),
+            content_hash: hash_string(&content),
+            preview: content,
+            sampled,
         }))
     }
 
     pub fn analyze_batch<P: AsRef<Path> + Sync>(
         &self,
         paths: &[P],
-    ) -> Vec<(usize, Result<Option<ContentPreview>>)> {
+    ) -> Vec<(usize, Result<AnalyzedContent>)> {
         use rayon::prelude::*;
 
         paths
             .par_iter()
             .enumerate()
-            .map(|(idx, path)| (idx, self.analyze(path.as_ref())))
+            .map(|(idx, path)| (idx, self.analyze_tracked(path.as_ref())))
             .collect()
     }
 
-    fn is_text_file<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
-        let mut file = File::open(path)?;
-        let mut buffer = vec![0u8; 8192];
-
-        let bytes_read = file.read(&mut buffer)?;
-        buffer.truncate(bytes_read);
-
-        Ok(is_likely_text(&buffer))
-    }
-
     pub fn extract_text<P: AsRef<Path>>(&self, path: P, max_length: usize) -> Result<String> {
-        let content = read_file_with_encoding(path, self.max_file_size)?;
+        let content = read_file_with_encoding(&path, self.max_file_size).at_path(&path)?;
 
         if content.len() > max_length {
             Ok(content.chars().take(max_length).collect())
@@ -88,24 +261,216 @@ impl ContentAnalyzer {
         }
     }
 
+    /// The first occurrence of any whitespace-separated term of `query`, ignoring case, with
+    /// up to `context_chars` characters either side of it, as one line: the occurrence is
+    /// wrapped in [`HIGHLIGHT_START`] and [`HIGHLIGHT_END`], whitespace runs become single
+    /// spaces and `…` marks text cut off at either end. Binary files, files over the size
+    /// limit and files without a match give `None`.
     pub fn get_snippet<P: AsRef<Path>>(
         &self,
         path: P,
         query: &str,
         context_chars: usize,
     ) -> Result<Option<String>> {
-        let content = read_file_with_encoding(path, self.max_file_size)?;
+        let path = path.as_ref();
+        let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        let len = std::fs::metadata(path).at_path(path)?.len();
+        if terms.is_empty() || len > self.max_file_size {
+            return Ok(None);
+        }
+
+        let bytes = std::fs::read(path).at_path(path)?;
+        if !is_likely_text(&bytes[..bytes.len().min(PROBE_BYTES as usize)]) {
+            return Ok(None);
+        }
+        let content = decode_text(&bytes);
+        let Some((start, end)) = terms
+            .iter()
+            .filter_map(|term| find_ignoring_case(&content, term))
+            .min()
+        else {
+            return Ok(None);
+        };
 
-        if let Some(pos) = content.to_lowercase().find(&query.to_lowercase()) {
-            let start = pos.saturating_sub(context_chars);
-            let end = (pos + query.len() + context_chars).min(content.len());
+        let lead = content[..start]
+            .char_indices()
+            .rev()
+            .take(context_chars)
+            .last()
+            .map_or(start, |(index, _)| index);
+        let trail = content[end..]
+            .char_indices()
+            .nth(context_chars)
+            .map_or(content.len(), |(index, _)| end + index);
+        Ok(Some(format!(
+            "{}{}{}{}{}{}{}",
+            if lead > 0 { "…" } else { "" },
+            one_line(&content[lead..start]).trim_start(),
+            HIGHLIGHT_START,
+            one_line(&content[start..end]),
+            HIGHLIGHT_END,
+            one_line(&content[end..trail]).trim_end(),
+            if trail < content.len() { "…" } else { "" },
+        )))
+    }
 
-            let snippet: String = content.chars().skip(start).take(end - start).collect();
-            Ok(Some(snippet))
-        } else {
-            Ok(None)
+    /// Lines containing any whitespace-separated term of `query`, ignoring case, each with up
+    /// to `context_lines` lines around it, like `grep -C`.
+    ///
+    /// Line and column numbers start at 1, and columns and lengths count characters. Blocks
+    /// never overlap: a block's trailing context stops before the next match, whose leading
+    /// context starts after it. At most `max_matches` blocks and [`MAX_CONTEXT_BYTES`] of
+    /// context are returned, the last block cut short at a character boundary if needed.
+    /// Binary files and files over the size limit give no matches.
+    pub fn get_line_context<P: AsRef<Path>>(
+        &self,
+        path: P,
+        query: &str,
+        context_lines: usize,
+        max_matches: usize,
+    ) -> Result<Vec<MatchLocation>> {
+        self.line_context(path.as_ref(), query, context_lines, max_matches, &[])
+    }
+
+    /// Like [`get_line_context`](Self::get_line_context), but only matching lines that
+    /// overlap one of `chunks` count. Chunk offsets are into the file's decoded text, so line
+    /// numbers stay those of the whole file.
+    pub fn get_line_context_in_chunks<P: AsRef<Path>>(
+        &self,
+        path: P,
+        query: &str,
+        context_lines: usize,
+        max_matches: usize,
+        chunks: &[ChunkHit],
+    ) -> Result<Vec<MatchLocation>> {
+        self.line_context(path.as_ref(), query, context_lines, max_matches, chunks)
+    }
+
+    // An empty `chunks` means the whole file.
+    fn line_context(
+        &self,
+        path: &Path,
+        query: &str,
+        context_lines: usize,
+        max_matches: usize,
+        chunks: &[ChunkHit],
+    ) -> Result<Vec<MatchLocation>> {
+        let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        let len = std::fs::metadata(path).at_path(path)?.len();
+        if terms.is_empty() || max_matches == 0 || len > self.max_file_size {
+            return Ok(Vec::new());
+        }
+
+        let bytes = std::fs::read(path).at_path(path)?;
+        if !is_likely_text(&bytes[..bytes.len().min(PROBE_BYTES as usize)]) {
+            return Ok(Vec::new());
+        }
+        let content = decode_text(&bytes);
+        let mut offset = 0;
+        let (starts, lines): (Vec<usize>, Vec<&str>) = content
+            .split_inclusive('\n')
+            .map(|raw| {
+                let start = offset;
+                offset += raw.len();
+                let line = raw
+                    .strip_suffix('\n')
+                    .map_or(raw, |line| line.strip_suffix('\r').unwrap_or(line));
+                (start, line)
+            })
+            .unzip();
+        let in_chunks = |index: usize| {
+            let (start, end) = (starts[index], starts.get(index + 1).copied().unwrap_or(offset));
+            chunks.is_empty()
+                || chunks.iter().any(|chunk| {
+                    start < chunk.byte_offset + chunk.length && end > chunk.byte_offset
+                })
+        };
+
+        let found: Vec<(usize, usize, usize)> = lines
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| in_chunks(*index))
+            .filter_map(|(index, line)| {
+                let (start, end) = terms
+                    .iter()
+                    .filter_map(|term| find_ignoring_case(line, term))
+                    .min()?;
+                Some((index, line[..start].chars().count(), line[start..end].chars().count()))
+            })
+            .take(max_matches)
+            .collect();
+
+        let mut locations = Vec::with_capacity(found.len());
+        let mut budget = MAX_CONTEXT_BYTES;
+        let mut previous_end = None;
+        for (i, &(index, column, length)) in found.iter().enumerate() {
+            let first = index
+                .saturating_sub(context_lines)
+                .max(previous_end.map_or(0, |end| end + 1));
+            let next_match = found.get(i + 1).map_or(lines.len(), |next| next.0);
+            let last = (index + context_lines).min(next_match - 1);
+            previous_end = Some(last);
+
+            let mut context = lines[first..=last].join("\n");
+            let truncated = context.len() > budget;
+            if truncated {
+                let mut end = budget;
+                while !context.is_char_boundary(end) {
+                    end -= 1;
+                }
+                context.truncate(end);
+            }
+            budget -= context.len();
+
+            locations.push(MatchLocation {
+                line: index + 1,
+                column: column + 1,
+                length,
+                context,
+                context_start_line: first + 1,
+                field: MatchField::Content,
+            });
+            if truncated || budget == 0 {
+                break;
+            }
+        }
+
+        Ok(locations)
+    }
+}
+
+/// The byte range of the first occurrence of the lowercase `needle` in `haystack`, ignoring
+/// case.
+fn find_ignoring_case(haystack: &str, needle: &str) -> Option<(usize, usize)> {
+    haystack.char_indices().find_map(|(start, _)| {
+        let mut expected = needle.chars().peekable();
+        for (offset, c) in haystack[start..].char_indices() {
+            for lower in c.to_lowercase() {
+                if expected.next() != Some(lower) {
+                    return None;
+                }
+            }
+            if expected.peek().is_none() {
+                return Some((start, start + offset + c.len_utf8()));
+            }
+        }
+        None
+    })
+}
+
+/// `text` with every run of whitespace, line breaks included, replaced by one space.
+fn one_line(text: &str) -> String {
+    let mut line = String::with_capacity(text.len());
+    let mut in_space = false;
+    for c in text.chars() {
+        if !c.is_whitespace() {
+            line.push(c);
+        } else if !in_space {
+            line.push(' ');
         }
+        in_space = c.is_whitespace();
     }
+    line
 }
 
 impl Default for ContentAnalyzer {
@@ -114,10 +479,92 @@ impl Default for ContentAnalyzer {
     }
 }
 
+/// What is compared before and after a file's content is read to tell whether it changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileState {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl FileState {
+    fn of(metadata: &Metadata) -> Self {
+        Self {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        }
+    }
+}
+
+/// The wall-clock time one file's analysis may take, checked at chunk boundaries.
+struct Budget<'a> {
+    path: &'a Path,
+    started: Instant,
+    timeout: Option<Duration>,
+}
+
+impl<'a> Budget<'a> {
+    fn start(path: &'a Path, timeout: Option<Duration>) -> Self {
+        Self {
+            path,
+            started: Instant::now(),
+            timeout,
+        }
+    }
+
+    fn check(&self) -> Result<()> {
+        match self.timeout {
+            Some(timeout) if self.started.elapsed() > timeout => Err(SearchError::ContentTimeout {
+                path: self.path.to_path_buf(),
+                budget_ms: timeout.as_millis() as u64,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Appends to `buf` until it holds `limit` bytes or the file ends.
+    fn read(&self, file: &mut File, buf: &mut Vec<u8>, limit: u64) -> Result<()> {
+        let mut chunk = vec![0u8; READ_CHUNK];
+        while (buf.len() as u64) < limit {
+            self.check()?;
+            let wanted = (limit - buf.len() as u64).min(READ_CHUNK as u64) as usize;
+            let read = file.read(&mut chunk[..wanted])?;
+            if read == 0 {
+                break;
+            }
+            buf.extend_from_slice(&chunk[..read]);
+        }
+        Ok(())
+    }
+
+    /// Counts like `split_whitespace` and `lines`, in a single pass over the text that was
+    /// read, checking the budget as it goes.
+    fn count_words_and_lines(&self, content: &str) -> Result<(usize, usize)> {
+        let (mut words, mut newlines, mut in_word) = (0, 0, false);
+        for (i, c) in content.chars().enumerate() {
+            if i % READ_CHUNK == 0 {
+                self.check()?;
+            }
+            if c == '\n' {
+                newlines += 1;
+            }
+            if c.is_whitespace() {
+                in_word = false;
+            } else if !in_word {
+                in_word = true;
+                words += 1;
+            }
+        }
+
+        let unterminated = !content.is_empty() && !content.ends_with('\n');
+        Ok((words, newlines + usize::from(unterminated)))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs;
+    use std::path::PathBuf;
     use tempfile::TempDir;
 
     #[test]
@@ -148,6 +595,154 @@ mod tests {
         assert!(preview.is_none());
     }
 
+    fn write_log(path: &Path) {
+        let mut log = String::new();
+        for i in 0..2000 {
+            log.push_str(&format!("2024-05-01 INFO request {} served\n", i));
+        }
+        log.push_str("2024-05-02 ERROR zebracorn exhausted\n");
+        fs::write(path, log).unwrap();
+    }
+
+    #[test]
+    fn test_tail_sampling_starts_at_line_boundary() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("app.log");
+        write_log(&file_path);
+
+        let analyzer = ContentAnalyzer::default()
+            .with_sampling(ContentSampling::Tail { tail_bytes: 200 }, Vec::new());
+        let preview = analyzer.analyze(&file_path).unwrap().unwrap();
+
+        assert!(preview.sampled);
+        assert!(preview.preview.starts_with("2024-05-01 INFO"));
+        assert!(preview.preview.ends_with("zebracorn exhausted\n"));
+        assert!(preview.line_count < 10);
+
+        let head = ContentAnalyzer::default().analyze(&file_path).unwrap().unwrap();
+        assert!(!head.sampled);
+        assert!(!head.preview.contains("zebracorn"));
+    }
+
+    #[test]
+    fn test_head_and_tail_sampling_joins_both_ends() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("app.log");
+        write_log(&file_path);
+
+        let sampling = ContentSampling::HeadAndTail {
+            head_bytes: 100,
+            tail_bytes: 100,
+        };
+        let analyzer = ContentAnalyzer::default().with_sampling(sampling, Vec::new());
+        let preview = analyzer.analyze(&file_path).unwrap().unwrap();
+
+        assert!(preview.sampled);
+        assert!(preview.preview.starts_with("2024-05-01 INFO request 0 served\n"));
+        assert!(preview.preview.contains(SAMPLE_SEPARATOR));
+        assert!(preview.preview.contains("zebracorn"));
+    }
+
+    #[test]
+    fn test_small_files_are_not_marked_sampled() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("short.log");
+        fs::write(&file_path, "one\ntwo\n").unwrap();
+
+        let analyzer = ContentAnalyzer::default()
+            .with_sampling(ContentSampling::Tail { tail_bytes: 1024 }, Vec::new());
+        let preview = analyzer.analyze(&file_path).unwrap().unwrap();
+
+        assert!(!preview.sampled);
+        assert_eq!(preview.preview, "one\ntwo\n");
+        assert_eq!(preview.line_count, 2);
+    }
+
+    #[test]
+    fn test_sampling_rules_match_extension_and_prefix() {
+        let rule = ContentSamplingRule {
+            extensions: vec!["log".to_string(), "txt".to_string()],
+            path_prefix: Some(PathBuf::from("/var/log")),
+            sampling: ContentSampling::Tail { tail_bytes: 4096 },
+        };
+        let analyzer = ContentAnalyzer::default().with_sampling(ContentSampling::Head, vec![rule]);
+
+        assert_eq!(
+            analyzer.sampling_for(Path::new("/var/log/syslog.TXT")),
+            ContentSampling::Tail { tail_bytes: 4096 }
+        );
+        assert_eq!(
+            analyzer.sampling_for(Path::new("/home/me/notes.txt")),
+            ContentSampling::Head
+        );
+        assert_eq!(
+            analyzer.sampling_for(Path::new("/var/log/app.gz")),
+            ContentSampling::Head
+        );
+    }
+
+    #[test]
+    fn test_huge_single_line_file_stays_within_budget() {
+        use std::io::Write;
+
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("dump.json");
+        let mut file = std::io::BufWriter::new(File::create(&file_path).unwrap());
+        let record = "{\"id\":12345,\"tags\":[\"a\",\"b\"]},".repeat(1024);
+        while file.get_ref().metadata().unwrap().len() < 100 * 1024 * 1024 {
+            file.write_all(record.as_bytes()).unwrap();
+            file.flush().unwrap();
+        }
+        drop(file);
+
+        let analyzer = ContentAnalyzer::new(200 * 1024 * 1024)
+            .with_timeout(Some(Duration::from_millis(50)));
+        let started = Instant::now();
+        match analyzer.analyze(&file_path) {
+            Ok(preview) => assert_eq!(preview.unwrap().line_count, 1),
+            Err(SearchError::ContentTimeout { path, budget_ms }) => {
+                assert_eq!(path, file_path);
+                assert_eq!(budget_ms, 50);
+            }
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_fifo_is_skipped_without_blocking_its_batch() {
+        let temp_dir = TempDir::new().unwrap();
+        let fifo = temp_dir.path().join("pipe");
+        let status = std::process::Command::new("mkfifo").arg(&fifo).status().unwrap();
+        assert!(status.success());
+        let notes = temp_dir.path().join("notes.txt");
+        fs::write(&notes, "plain text").unwrap();
+
+        let analyzer = ContentAnalyzer::default();
+        let mut results = analyzer.analyze_batch(&[&fifo, &notes]);
+        results.sort_by_key(|(idx, _)| *idx);
+
+        assert!(matches!(
+            results[0].1,
+            Err(SearchError::NotRegularFile(ref path)) if *path == fifo
+        ));
+        assert_eq!(results[1].1.as_ref().unwrap().preview.as_ref().unwrap().word_count, 2);
+    }
+
+    #[test]
+    fn test_counts_match_split_whitespace_and_lines() {
+        let budget = Budget::start(Path::new("counts.txt"), None);
+        for text in ["", "\n", "one", "one two\n", " a\tb \r\n\nc", "ü ß\u{3000}x\n\n"] {
+            assert_eq!(
+                budget.count_words_and_lines(text).unwrap(),
+                (text.split_whitespace().count(), text.lines().count()),
+                "{:?}",
+                text
+            );
+        }
+    }
+
     #[test]
     fn test_get_snippet() {
         let temp_dir = TempDir::new().unwrap();
@@ -156,9 +751,75 @@ mod tests {
 
         let analyzer = ContentAnalyzer::default();
         let snippet = analyzer.get_snippet(&file_path, "brown", 10).unwrap();
+        assert_eq!(snippet.as_deref(), Some("The quick **brown** fox jumps…"));
+
+        let snippet = analyzer.get_snippet(&file_path, "cat DOG", 9).unwrap();
+        assert_eq!(snippet.as_deref(), Some("…the lazy **dog**"));
+        assert_eq!(analyzer.get_snippet(&file_path, "cat", 10).unwrap(), None);
 
-        assert!(snippet.is_some());
-        let snippet = snippet.unwrap();
-        assert!(snippet.contains("brown"));
+        // Case folding may change byte lengths, and line breaks are flattened.
+        fs::write(&file_path, "Grüße\n\n   aus KÖLN, am Rhein").unwrap();
+        let snippet = analyzer.get_snippet(&file_path, "köln", 100).unwrap();
+        assert_eq!(snippet.as_deref(), Some("Grüße aus **KÖLN**, am Rhein"));
+        assert!(analyzer.get_snippet(temp_dir.path().join("gone.txt"), "köln", 10).is_err());
+    }
+
+    #[test]
+    fn test_get_line_context() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("notes.txt");
+        let lines = [
+            "TODO: first", "two", "three", "four", "five", "Grüße, todo here", "seven todo",
+            "eight", "nine", "ten", "eleven", "last todo",
+        ];
+        fs::write(&file_path, lines.join("\n")).unwrap();
+        let analyzer = ContentAnalyzer::default();
+
+        let locations = analyzer.get_line_context(&file_path, "todo", 2, 10).unwrap();
+        let blocks: Vec<_> = locations
+            .iter()
+            .map(|location| (location.line, location.context_start_line, location.context.as_str()))
+            .collect();
+        assert_eq!(
+            blocks,
+            vec![
+                // Nothing before the first line.
+                (1, 1, "TODO: first\ntwo\nthree"),
+                (6, 4, "four\nfive\nGrüße, todo here"),
+                // Leading context starts after the previous block.
+                (7, 7, "seven todo\neight\nnine"),
+                // Nothing after the last line.
+                (12, 10, "ten\neleven\nlast todo"),
+            ]
+        );
+        assert_eq!((locations[0].column, locations[0].length), (1, 4));
+        assert_eq!((locations[1].column, locations[1].length), (8, 4));
+
+        let locations = analyzer.get_line_context(&file_path, "nine ELEVEN", 0, 10).unwrap();
+        let found: Vec<_> = locations.iter().map(|l| (l.line, l.context.as_str())).collect();
+        assert_eq!(found, vec![(9, "nine"), (11, "eleven")]);
+        assert_eq!(analyzer.get_line_context(&file_path, "todo", 1, 2).unwrap().len(), 2);
+        assert!(analyzer.get_line_context(&file_path, "missing", 2, 10).unwrap().is_empty());
+        assert!(ContentAnalyzer::new(8)
+            .get_line_context(&file_path, "todo", 2, 10)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_line_context_is_capped_per_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("long.txt");
+        let line = format!("{} match", "ü".repeat(999));
+        fs::write(&file_path, vec![line.as_str(); 100].join("\n")).unwrap();
+
+        let locations = ContentAnalyzer::default()
+            .get_line_context(&file_path, "match", 1, 100)
+            .unwrap();
+        let total: usize = locations.iter().map(|l| l.context.len()).sum();
+        assert!(total <= MAX_CONTEXT_BYTES);
+        assert!(locations.len() < 100);
+        assert_eq!(locations[1].column, 1001);
+        assert!(locations.last().unwrap().context.len() < line.len());
     }
 }
@@ -0,0 +1,286 @@
+use crate::core::types::DocumentField;
+use chrono::{TimeZone, Utc};
+use mailparse::MailHeaderMap;
+
+/// Bytes read from the start of an email or web page to find its fields.
+pub const DOCUMENT_PROBE_BYTES: usize = 64 * 1024;
+
+/// Extensions whose files carry fields, and which reader takes them.
+pub fn document_kind(extension: &str) -> Option<DocumentKind> {
+    match extension.to_ascii_lowercase().as_str() {
+        "eml" => Some(DocumentKind::Email),
+        "html" | "htm" | "xhtml" => Some(DocumentKind::Html),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentKind {
+    Email,
+    Html,
+}
+
+impl DocumentKind {
+    /// Fields found in the start of a document; one that doesn't parse has none.
+    pub fn fields(self, head: &[u8]) -> Vec<DocumentField> {
+        match self {
+            Self::Email => email_fields(head).unwrap_or_default(),
+            Self::Html => html_fields(&String::from_utf8_lossy(head)),
+        }
+    }
+}
+
+/// `From`, `To`, `Subject` and `Date` of an RFC 822 message, with encoded words decoded.
+/// `None` when the headers don't parse.
+pub fn email_fields(head: &[u8]) -> Option<Vec<DocumentField>> {
+    let (headers, _) = mailparse::parse_headers(head).ok()?;
+
+    let mut fields = Vec::new();
+    for (header, name) in [("From", "from"), ("To", "to"), ("Subject", "subject")] {
+        let values = headers.get_all_values(header);
+        let value = collapse_whitespace(&values.join(", "));
+        if !value.is_empty() {
+            fields.push(DocumentField::new(name, value));
+        }
+    }
+
+    // An unparseable date is left out rather than failing the other fields.
+    let date = headers
+        .get_first_value("Date")
+        .and_then(|date| mailparse::dateparse(&date).ok())
+        .and_then(|secs| Utc.timestamp_opt(secs, 0).single());
+    if let Some(date) = date {
+        fields.push(DocumentField::new("date", date.to_rfc3339()));
+    }
+
+    Some(fields)
+}
+
+/// The `<title>` and `<meta name="description">` of an HTML page, with entities decoded.
+pub fn html_fields(html: &str) -> Vec<DocumentField> {
+    let mut fields = Vec::new();
+
+    if let Some(title) = element_text(html, "title") {
+        let title = collapse_whitespace(&decode_entities(title));
+        if !title.is_empty() {
+            fields.push(DocumentField::new("title", title));
+        }
+    }
+
+    let description = tags(html, "meta")
+        .find(|tag| {
+            attribute(tag, "name").is_some_and(|name| name.eq_ignore_ascii_case("description"))
+        })
+        .and_then(|tag| attribute(tag, "content"));
+    if let Some(description) = description {
+        let description = collapse_whitespace(&decode_entities(description));
+        if !description.is_empty() {
+            fields.push(DocumentField::new("description", description));
+        }
+    }
+
+    fields
+}
+
+/// Text between `<name ...>` and `</name>`, matched without regard to case.
+fn element_text<'a>(html: &'a str, name: &str) -> Option<&'a str> {
+    let lower = html.to_ascii_lowercase();
+    let open = tag_start(&lower, name, 0)?;
+    let content_start = open + lower[open..].find('>')? + 1;
+    let content_end = content_start + lower[content_start..].find(&format!("</{}", name))?;
+    Some(&html[content_start..content_end])
+}
+
+/// The `<name ...>` tags of `html`, each without its angle brackets.
+fn tags<'a>(html: &'a str, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    let lower = html.to_ascii_lowercase();
+    let mut from = 0;
+    std::iter::from_fn(move || {
+        let start = tag_start(&lower, name, from)?;
+        let end = start + lower[start..].find('>')?;
+        from = end;
+        Some(&html[start + 1..end])
+    })
+}
+
+/// Where the next `<name` tag starts at or after `from`, skipping longer names such as
+/// `<titles` for `title`. `lower` is the lowercased document.
+fn tag_start(lower: &str, name: &str, mut from: usize) -> Option<usize> {
+    let needle = format!("<{}", name);
+    loop {
+        let start = from + lower[from..].find(&needle)?;
+        let after = lower[start + needle.len()..].chars().next();
+        if after.map_or(true, |c| c == '>' || c == '/' || c.is_ascii_whitespace()) {
+            return Some(start);
+        }
+        from = start + needle.len();
+    }
+}
+
+/// The value of attribute `name` in the inside of a tag, quoted or not.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let lower = tag.to_ascii_lowercase();
+    let mut from = 0;
+    while let Some(found) = lower[from..].find(name) {
+        let start = from + found;
+        from = start + name.len();
+        let preceded = lower[..start]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_ascii_whitespace());
+        let rest = lower[from..].trim_start();
+        if !preceded || !rest.starts_with('=') {
+            continue;
+        }
+
+        let value_start = tag.len() - rest[1..].trim_start().len();
+        let value = &tag[value_start..];
+        return Some(match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let value = &value[1..];
+                &value[..value.find(quote).unwrap_or(value.len())]
+            }
+            _ => {
+                let end = value
+                    .find(|c: char| c.is_ascii_whitespace() || c == '/')
+                    .unwrap_or(value.len());
+                &value[..end]
+            }
+        });
+    }
+    None
+}
+
+/// Decodes numeric character references and the named entities common in titles.
+/// Unknown entities are kept as written.
+pub fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+
+        let entity = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| Some((decode_entity(&rest[1..=end])?, end + 2)));
+        match entity {
+            Some((c, len)) => {
+                decoded.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+    if let Some(number) = name.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "ndash" => '–',
+        "mdash" => '—',
+        "hellip" => '…',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "laquo" => '«',
+        "raquo" => '»',
+        "euro" => '€',
+        "pound" => '£',
+        "middot" => '·',
+        "eacute" => 'é',
+        "egrave" => 'è',
+        "agrave" => 'à',
+        "ccedil" => 'ç',
+        "auml" => 'ä',
+        "ouml" => 'ö',
+        "uuml" => 'ü',
+        "szlig" => 'ß',
+        _ => return None,
+    })
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field<'a>(fields: &'a [DocumentField], name: &str) -> Option<&'a str> {
+        fields
+            .iter()
+            .find(|field| field.name == name)
+            .map(|field| field.value.as_str())
+    }
+
+    #[test]
+    fn test_email_fields_decode_unicode_headers() {
+        let message = concat!(
+            "From: =?UTF-8?Q?J=C3=BCrgen_M=C3=BCller?= <juergen@acme.com>\r\n",
+            "To: billing@example.org,\r\n",
+            "  \"Zoë\" <zoe@example.org>\r\n",
+            "Subject: =?UTF-8?B?UmVjaG51bmcgw5xiZXJzaWNodA==?=\r\n",
+            "Date: Tue, 14 Mar 2023 09:30:00 +0100\r\n",
+            "\r\n",
+            "Body text\r\n",
+        );
+        let fields = email_fields(message.as_bytes()).unwrap();
+
+        assert_eq!(
+            field(&fields, "from"),
+            Some("Jürgen Müller <juergen@acme.com>")
+        );
+        assert_eq!(
+            field(&fields, "to"),
+            Some("billing@example.org, \"Zoë\" <zoe@example.org>")
+        );
+        assert_eq!(field(&fields, "subject"), Some("Rechnung Übersicht"));
+        assert_eq!(field(&fields, "date"), Some("2023-03-14T08:30:00+00:00"));
+        assert_eq!(email_fields(b"\rSubject: stray carriage return"), None);
+    }
+
+    #[test]
+    fn test_html_fields_decode_entities() {
+        let html = r#"<!DOCTYPE html><HTML><head>
+            <meta charset="utf-8">
+            <Title>
+              Q3 Results &amp; Outlook &#8212; Caf&eacute; &#x2603; &bogus;
+            </Title>
+            <meta content='Quarterly &quot;numbers&quot;' name=description>
+            </head><body><h1>Ignored</h1></body></HTML>"#;
+        let fields = html_fields(html);
+
+        assert_eq!(
+            field(&fields, "title"),
+            Some("Q3 Results & Outlook — Café ☃ &bogus;")
+        );
+        assert_eq!(field(&fields, "description"), Some("Quarterly \"numbers\""));
+        assert!(html_fields("<p>no head</p>").is_empty());
+        assert_eq!(document_kind("HTM"), Some(DocumentKind::Html));
+        assert_eq!(document_kind("txt"), None);
+    }
+}
@@ -1,18 +1,30 @@
 use crate::core::config::SearchConfig;
-use crate::core::error::Result;
-use crate::core::types::ProgressCallback;
+use crate::core::error::{IoResultExt, Result};
+use crate::core::metrics::EngineCounters;
+use crate::core::types::{CancellationToken, FileEntry, Progress, ProgressCallback, SkippedEntry};
 use crate::filters::ExclusionFilter;
+use crate::indexer::batch::{BatchSizer, BatchWriter};
 use crate::indexer::builder::IndexBuilder;
+use crate::indexer::clock::TimestampClock;
 use crate::indexer::metadata::MetadataExtractor;
-use crate::storage::Database;
-use std::collections::HashSet;
+use crate::indexer::progress::ProgressDispatcher;
+use crate::indexer::quota;
+use crate::storage::{Database, FileStore};
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Instant;
 
 pub struct IncrementalIndexer {
     database: Arc<Database>,
     config: Arc<SearchConfig>,
-    _builder: Arc<IndexBuilder>,
+    builder: Arc<IndexBuilder>,
+    writer: BatchWriter,
+    /// Sizes the batches of `update_files`, which applies watched file events.
+    sizer: Mutex<BatchSizer>,
+    clock: TimestampClock,
+    metrics: Arc<EngineCounters>,
 }
 
 impl IncrementalIndexer {
@@ -27,57 +39,158 @@ impl IncrementalIndexer {
             exclusion_filter,
         ));
 
+        let store: Arc<dyn FileStore> = database.clone();
+        let writer = BatchWriter::from_config(store, Arc::clone(&database), &config);
+        let clock = TimestampClock::new(Arc::clone(&database), Arc::clone(&config));
+        let sizer = Mutex::new(BatchSizer::from_config(&config));
+
         Self {
             database,
             config,
-            _builder: builder,
+            builder,
+            writer,
+            sizer,
+            clock,
+            metrics: Arc::new(EngineCounters::new()),
         }
     }
 
+    /// Routes file row writes through `store` instead of the database.
+    pub fn with_store(mut self, store: Arc<dyn FileStore>) -> Self {
+        self.writer = BatchWriter::from_config(store, Arc::clone(&self.database), &self.config);
+        self
+    }
+
+    /// Decides timestamp offsets with `clock` instead of one using the host's settings.
+    pub fn with_clock(mut self, clock: TimestampClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Records completed updates into `metrics` instead of a private set of counters.
+    pub fn with_metrics(mut self, metrics: Arc<EngineCounters>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
     pub fn update<P: AsRef<Path>>(
         &self,
         root: P,
         progress_callback: Option<ProgressCallback>,
     ) -> Result<UpdateStats> {
+        let _active = self.metrics.begin_operation();
+        let started = Instant::now();
         let root = root.as_ref();
 
         let existing_files = self.get_indexed_files(root)?;
         let current_files = self.scan_current_files(root)?;
-
+        let utc_offset_secs = self.clock.offset_for(root)?;
+
+        // Files that changed while they were last read go first, and are read again whatever
+        // their metadata says.
+        let inconsistent: HashSet<PathBuf> = self
+            .database
+            .inconsistent_files()?
+            .into_iter()
+            .map(|entry| entry.path)
+            .filter(|path| path.starts_with(root))
+            .collect();
+        let mut scan_order: Vec<_> = current_files.iter().collect();
+        scan_order.sort_by_key(|(path, _)| !inconsistent.contains(*path));
+
+        let progress = ProgressDispatcher::new(progress_callback, &self.config);
         let mut stats = UpdateStats::default();
+        let mut changed = Vec::new();
+        let mut pending = Vec::new();
+        let batch_size = self.config.batch_size.max(1);
+
+        for (scanned, (path, link_target)) in scan_order.into_iter().enumerate() {
+            if scanned > 0 && scanned % batch_size == 0 {
+                progress.report(Progress::new(
+                    scanned,
+                    current_files.len(),
+                    format!("Checked {} of {} files", scanned, current_files.len()),
+                ));
+            }
 
-        for path in &current_files {
-            if !existing_files.contains(path) {
-                if let Ok(entry) = MetadataExtractor::extract(path) {
-                    self.database.insert_file(&entry)?;
-                    stats.added += 1;
-                }
-            } else if self.needs_update(path)? {
-                if let Ok(entry) = MetadataExtractor::extract(path) {
-                    self.database.insert_file(&entry)?;
-                    stats.updated += 1;
-                }
+            let is_new = !existing_files.contains(path);
+            if !is_new && !inconsistent.contains(path) && !self.needs_update(path, utc_offset_secs)?
+            {
+                continue;
+            }
+
+            if let Ok(mut entry) = self.extract(path, utc_offset_secs) {
+                entry.is_symlink |= link_target.is_some();
+                pending.push((entry, is_new));
+            }
+
+            if pending.len() >= batch_size {
+                self.write_pending(&mut pending, &mut stats, &mut changed)?;
             }
         }
+        self.write_pending(&mut pending, &mut stats, &mut changed)?;
+
+        let targets: HashMap<PathBuf, PathBuf> = current_files
+            .iter()
+            .filter_map(|(path, target)| Some((path.clone(), target.clone()?)))
+            .collect();
+        self.builder.record_permissions(&changed)?;
+        let changed = self.builder.record_store_aliases(changed, &targets)?;
+        let details = self.builder.index_file_details(&changed)?;
+        stats.possibly_inconsistent = details.possibly_inconsistent;
 
         for path in &existing_files {
-            if !current_files.contains(path) {
+            if !current_files.contains_key(path) {
                 self.database.delete_by_path(path)?;
                 stats.removed += 1;
             }
         }
 
-        if let Some(callback) = progress_callback {
-            callback(crate::core::types::Progress::new(
-                stats.total(),
-                stats.total(),
-                format!("Update complete: {} changes", stats.total()),
-            ));
-        }
+        progress.finish(Progress::complete(
+            stats.total(),
+            format!("Update complete: {} changes", stats.total()),
+        ));
 
+        self.metrics.record_index(started.elapsed());
         Ok(stats)
     }
 
+    /// The entry for `path`, stamped with the clock's time.
+    fn extract(&self, path: &Path, utc_offset_secs: i32) -> Result<FileEntry> {
+        let mut entry = MetadataExtractor::extract_with_offset(path, utc_offset_secs)?;
+        entry.indexed_at = self.clock.now();
+        entry.last_verified = entry.indexed_at;
+        Ok(entry)
+    }
+
+    fn write_pending(
+        &self,
+        pending: &mut Vec<(FileEntry, bool)>,
+        stats: &mut UpdateStats,
+        changed: &mut Vec<FileEntry>,
+    ) -> Result<()> {
+        let entries: Vec<FileEntry> = pending.iter().map(|(entry, _)| entry.clone()).collect();
+        let outcome = self
+            .writer
+            .write(&entries)
+            .map_err(|e| quota::with_progress(e, stats.added + stats.updated))?;
+
+        for (entry, is_new) in pending.drain(..) {
+            if outcome.skipped.iter().any(|skipped| skipped.path == entry.path) {
+                continue;
+            }
+            if is_new {
+                stats.added += 1;
+            } else {
+                stats.updated += 1;
+            }
+            changed.push(entry);
+        }
+        stats.skipped.extend(outcome.skipped);
+
+        Ok(())
+    }
+
     pub fn update_file<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
         let path = path.as_ref();
 
@@ -86,26 +199,82 @@ impl IncrementalIndexer {
             return Ok(true);
         }
 
-        let entry = MetadataExtractor::extract(path)?;
+        let mut entry = self.extract(path, self.clock.offset_for(path)?)?;
+        let targets = self.store_targets(std::slice::from_mut(&mut entry));
+        self.writer.admit(1)?;
         self.database.insert_file(&entry)?;
 
+        self.builder.record_permissions(std::slice::from_ref(&entry))?;
+        let entries = self.builder.record_store_aliases(vec![entry], &targets)?;
+        // Watched files are the likeliest to be read mid-write; the flag has the next event
+        // or update read them again.
+        for path in self.builder.index_file_details(&entries)?.possibly_inconsistent {
+            log::debug!(
+                "{} changed while it was read; it is read again on update",
+                self.builder.sensitive().redact_path(&path)
+            );
+        }
+
         Ok(true)
     }
 
+    /// Brings the entries of `paths` up to date, writing them in batches sized by how long
+    /// earlier ones took to commit.
     pub fn update_files(&self, paths: &[PathBuf]) -> Result<usize> {
         let mut updated = 0;
+        let mut entries = Vec::new();
 
         for path in paths {
-            if self.update_file(path).is_ok() {
-                updated += 1;
+            if !path.exists() {
+                if self.database.delete_by_path(path).is_ok() {
+                    updated += 1;
+                }
+            } else if let Ok(entry) = self.extract(path, self.clock.offset_for(path)?) {
+                entries.push(entry);
+            }
+        }
+        let targets = self.store_targets(&mut entries);
+
+        let mut sizer = self.sizer.lock();
+        let mut rest = entries.as_slice();
+        while !rest.is_empty() {
+            let (batch, tail) = rest.split_at(sizer.size().min(rest.len()));
+            rest = tail;
+            let outcome = self
+                .writer
+                .write(batch)
+                .map_err(|e| quota::with_progress(e, updated))?;
+            sizer.observe(batch.len(), outcome.commit_time);
+            updated += outcome.written;
+
+            let written: Vec<FileEntry> = batch
+                .iter()
+                .filter(|entry| !outcome.skipped.iter().any(|s| s.path == entry.path))
+                .cloned()
+                .collect();
+            self.builder.record_permissions(&written)?;
+            let written = self.builder.record_store_aliases(written, &targets)?;
+            for path in self.builder.index_file_details(&written)?.possibly_inconsistent {
+                log::debug!(
+                    "{} changed while it was read; it is read again on update",
+                    self.builder.sensitive().redact_path(&path)
+                );
             }
         }
 
         Ok(updated)
     }
 
+    /// Indexed entries under `root` that a walk of it would visit. Entries below
+    /// `max_depth` are out of the update's reach, so they are neither checked nor removed.
     fn get_indexed_files<P: AsRef<Path>>(&self, root: P) -> Result<HashSet<PathBuf>> {
         let root = root.as_ref();
+        let within_depth = |path: &Path| {
+            self.config.max_depth.map_or(true, |depth| {
+                path.strip_prefix(root)
+                    .is_ok_and(|relative| relative.components().count() <= depth)
+            })
+        };
         let mut files = HashSet::new();
         let mut offset = 0;
         let limit = 1000;
@@ -117,7 +286,7 @@ impl IncrementalIndexer {
             }
 
             for entry in batch {
-                if entry.path.starts_with(root) {
+                if entry.path.starts_with(root) && within_depth(&entry.path) {
                     files.insert(entry.path);
                 }
             }
@@ -128,7 +297,25 @@ impl IncrementalIndexer {
         Ok(files)
     }
 
-    fn scan_current_files<P: AsRef<Path>>(&self, root: P) -> Result<HashSet<PathBuf>> {
+    /// Where the links into a package store among `entries` point, flagging them as links.
+    fn store_targets(&self, entries: &mut [FileEntry]) -> HashMap<PathBuf, PathBuf> {
+        let targets: HashMap<PathBuf, PathBuf> = entries
+            .iter()
+            .filter_map(|entry| {
+                let target = self.builder.store_roots().resolve(&entry.path)?;
+                Some((entry.path.clone(), target))
+            })
+            .collect();
+        IndexBuilder::mark_store_aliases(entries, &targets);
+        targets
+    }
+
+    /// Files a walk of `root` finds, with the store target of those reached through a link
+    /// into a package store.
+    fn scan_current_files<P: AsRef<Path>>(
+        &self,
+        root: P,
+    ) -> Result<HashMap<PathBuf, Option<PathBuf>>> {
         use crate::indexer::walker::DirectoryWalker;
 
         let walker = DirectoryWalker::new(
@@ -136,76 +323,231 @@ impl IncrementalIndexer {
             Arc::new(ExclusionFilter::default()),
         );
 
-        let paths = walker.walk_parallel(root)?;
-        Ok(paths.into_iter().collect())
+        let files = walker
+            .walk_streaming(root)?
+            .map(|entry| (entry.path, entry.link_target))
+            .collect();
+        Ok(files)
     }
 
-    fn needs_update<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+    /// A size change always counts; otherwise the file's current mtime is compared with the
+    /// indexed one, allowing for `mtime_tolerance_secs` of drift either way. Same-size edits
+    /// inside that window are missed until the file changes again.
+    fn needs_update<P: AsRef<Path>>(&self, path: P, utc_offset_secs: i32) -> Result<bool> {
         let path = path.as_ref();
+        let existing = self.database.find_by_path(path)?;
+        Self::is_outdated(
+            path,
+            existing.as_ref(),
+            utc_offset_secs,
+            self.config.mtime_tolerance_secs,
+        )
+    }
 
-        if let Some(existing) = self.database.find_by_path(path)? {
-            if let Some(last_modified) = existing.modified_at {
-                return MetadataExtractor::is_modified_since(path, last_modified);
-            }
-        }
+    fn is_outdated(
+        path: &Path,
+        existing: Option<&FileEntry>,
+        utc_offset_secs: i32,
+        mtime_tolerance_secs: u64,
+    ) -> Result<bool> {
+        let Some(existing) = existing else {
+            return Ok(true);
+        };
+        let Some(last_modified) = existing.modified_at else {
+            return Ok(true);
+        };
 
-        Ok(true)
+        let metadata = std::fs::metadata(path).at_path(path)?;
+        if metadata.len() != existing.size {
+            return Ok(true);
+        }
+        let Some(modified) = metadata.modified().ok().and_then(|modified| {
+            MetadataExtractor::system_time_to_datetime(modified, utc_offset_secs)
+        }) else {
+            return Ok(false);
+        };
+        // The index keeps whole seconds, so compare at that precision.
+        let drift = (modified.timestamp() - last_modified.timestamp()).unsigned_abs();
+        Ok(drift > mtime_tolerance_secs)
     }
 
-    pub fn verify_index<P: AsRef<Path>>(&self, root: P) -> Result<VerificationStats> {
-        let root = root.as_ref();
-        let indexed_files = self.get_indexed_files(root)?;
+    /// Checks every indexed entry under `root` against the filesystem.
+    ///
+    /// Entries are checked in path order, `batch_size` at a time: one query fetches a batch's
+    /// rows and the filesystem checks run in parallel. Progress is reported and `cancel` is
+    /// honored between batches; a cancelled run returns the stats of the batches it finished.
+    pub fn verify_index<P: AsRef<Path>>(
+        &self,
+        root: P,
+        progress_callback: Option<ProgressCallback>,
+        cancel: &CancellationToken,
+    ) -> Result<VerificationStats> {
+        use rayon::prelude::*;
 
-        let mut stats = VerificationStats::default();
-        stats.total_indexed = indexed_files.len();
+        let root = root.as_ref();
+        let mut indexed_files: Vec<PathBuf> = self.get_indexed_files(root)?.into_iter().collect();
+        indexed_files.sort();
+
+        let utc_offset_secs = self.clock.offset_for(root)?;
+        let tolerance = self.config.mtime_tolerance_secs;
+        let total = indexed_files.len();
+        let mut stats = VerificationStats {
+            total_indexed: total,
+            ..Default::default()
+        };
+        let progress = ProgressDispatcher::new(progress_callback, &self.config);
+
+        for batch in indexed_files.chunks(self.config.batch_size.max(1)) {
+            if cancel.is_cancelled() {
+                stats.cancelled = true;
+                break;
+            }
 
-        for path in indexed_files {
-            if !path.exists() {
-                stats.missing += 1;
-            } else if self.needs_update(&path)? {
-                stats.outdated += 1;
-            } else {
-                stats.valid += 1;
+            let entries: HashMap<PathBuf, FileEntry> = self
+                .database
+                .find_by_paths(batch)?
+                .into_iter()
+                .map(|entry| (entry.path.clone(), entry))
+                .collect();
+            let problems = batch
+                .par_iter()
+                .map(|path| {
+                    if !path.exists() {
+                        return Ok(Some(ProblemKind::Missing));
+                    }
+                    let outdated =
+                        Self::is_outdated(path, entries.get(path), utc_offset_secs, tolerance)?;
+                    Ok(outdated.then_some(ProblemKind::Outdated))
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            for (path, problem) in batch.iter().zip(problems) {
+                stats.record(path, problem);
             }
+
+            progress.report(Progress::new(
+                stats.checked(),
+                total,
+                format!("Verified {} of {} entries", stats.checked(), total),
+            ));
         }
 
+        if !stats.cancelled {
+            progress.finish(Progress::complete(
+                total,
+                format!("Verified {} of {} entries", total, total),
+            ));
+        }
         Ok(stats)
     }
 }
 
 #[derive(Debug, Default, Clone)]
+#[non_exhaustive]
 pub struct UpdateStats {
     pub added: usize,
     pub updated: usize,
     pub removed: usize,
+    pub skipped: Vec<SkippedEntry>,
+    /// Files that changed while their content was read; the next update reads them again.
+    pub possibly_inconsistent: Vec<PathBuf>,
 }
 
 impl UpdateStats {
     pub fn total(&self) -> usize {
         self.added + self.updated + self.removed
     }
+
+    /// Adds another run's changes to these, e.g. to total several roots.
+    pub fn merge(&mut self, other: &UpdateStats) {
+        self.added += other.added;
+        self.updated += other.updated;
+        self.removed += other.removed;
+        self.skipped.extend(other.skipped.iter().cloned());
+        self.possibly_inconsistent.extend(other.possibly_inconsistent.iter().cloned());
+    }
 }
 
+/// Problems listed in [`VerificationStats::problems`]; the counts cover every entry.
+pub const MAX_REPORTED_PROBLEMS: usize = 10_000;
+
 #[derive(Debug, Default, Clone)]
+#[non_exhaustive]
 pub struct VerificationStats {
     pub total_indexed: usize,
     pub valid: usize,
     pub outdated: usize,
     pub missing: usize,
+    /// The first [`MAX_REPORTED_PROBLEMS`] missing or outdated entries, in path order.
+    pub problems: Vec<VerificationProblem>,
+    /// The run was cancelled before every entry was checked.
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationProblem {
+    pub path: PathBuf,
+    pub kind: ProblemKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProblemKind {
+    /// Indexed, but gone from disk.
+    Missing,
+    /// Changed on disk since it was indexed.
+    Outdated,
+}
+
+impl ProblemKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProblemKind::Missing => "missing",
+            ProblemKind::Outdated => "outdated",
+        }
+    }
 }
 
 impl VerificationStats {
+    /// Entries checked so far; less than `total_indexed` only when cancelled.
+    pub fn checked(&self) -> usize {
+        self.valid + self.outdated + self.missing
+    }
+
+    /// Share of the checked entries that are up to date.
     pub fn health_percentage(&self) -> f64 {
-        if self.total_indexed == 0 {
+        if self.checked() == 0 {
             return 100.0;
         }
-        (self.valid as f64 / self.total_indexed as f64) * 100.0
+        (self.valid as f64 / self.checked() as f64) * 100.0
+    }
+
+    /// Missing and outdated entries left out of `problems` by the cap.
+    pub fn unlisted_problems(&self) -> usize {
+        self.missing + self.outdated - self.problems.len()
+    }
+
+    fn record(&mut self, path: &Path, problem: Option<ProblemKind>) {
+        match problem {
+            None => self.valid += 1,
+            Some(ProblemKind::Missing) => self.missing += 1,
+            Some(ProblemKind::Outdated) => self.outdated += 1,
+        }
+        if let Some(kind) = problem {
+            if self.problems.len() < MAX_REPORTED_PROBLEMS {
+                self.problems.push(VerificationProblem {
+                    path: path.to_path_buf(),
+                    kind,
+                });
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::config::ProgressDelivery;
+    use parking_lot::Mutex;
     use std::fs;
     use tempfile::TempDir;
 
@@ -261,4 +603,207 @@ mod tests {
         let stats = indexer.update(root, None).unwrap();
         assert!(stats.removed > 0, "Expected at least one file to be removed");
     }
+
+    #[test]
+    fn test_shallow_update_leaves_deeper_entries() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("nested")).unwrap();
+        fs::write(root.join("top.txt"), "top").unwrap();
+        fs::write(root.join("nested/deep.txt"), "deep").unwrap();
+
+        let db = Arc::new(Database::in_memory(4).unwrap());
+        let mut config = SearchConfig::default();
+        config.index_hidden_files = true;
+        let filter = Arc::new(ExclusionFilter::from_patterns(&[]).unwrap());
+        let full = IncrementalIndexer::new(db.clone(), Arc::new(config.clone()), filter.clone());
+        full.update(root, None).unwrap();
+
+        let shallow_config = SearchConfig {
+            max_depth: Some(1),
+            ..config
+        };
+        let shallow = IncrementalIndexer::new(db.clone(), Arc::new(shallow_config), filter);
+        fs::remove_file(root.join("top.txt")).unwrap();
+        fs::write(root.join("nested/deeper.txt"), "deeper").unwrap();
+
+        let stats = shallow.update(root, None).unwrap();
+        assert_eq!((stats.added, stats.removed), (0, 1));
+        assert!(db.find_by_path(&root.join("nested/deep.txt")).unwrap().is_some());
+        assert!(db.find_by_path(&root.join("nested/deeper.txt")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_file_changed_during_read_is_read_again() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::time::{Duration, SystemTime};
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().to_path_buf();
+        let draft = root.join("draft.txt");
+        let set_mtime = |path: &Path, mtime: SystemTime| {
+            fs::File::options().write(true).open(path).unwrap().set_modified(mtime).unwrap();
+        };
+        // A rewrite of the same length within the same second looks unchanged to an update.
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        fs::write(&draft, "old text").unwrap();
+        set_mtime(&draft, mtime);
+
+        let db = Arc::new(Database::in_memory(4).unwrap());
+        let config = Arc::new(SearchConfig {
+            index_hidden_files: true,
+            enable_content_search: true,
+            ..Default::default()
+        });
+        let filter = Arc::new(ExclusionFilter::from_patterns(&[]).unwrap());
+        let indexer = IncrementalIndexer::new(Arc::clone(&db), config, filter);
+        let rewritten = Arc::new(AtomicBool::new(false));
+        let hook_rewritten = Arc::clone(&rewritten);
+        indexer.builder.content_analyzer().set_read_hook(Some(Arc::new(move |path: &Path| {
+            if !hook_rewritten.swap(true, Ordering::SeqCst) {
+                fs::write(path, "new text").unwrap();
+                set_mtime(path, mtime + Duration::from_millis(500));
+            }
+        })));
+
+        let stats = indexer.update(&root, None).unwrap();
+        assert_eq!(stats.added, 1);
+        assert_eq!(stats.possibly_inconsistent, vec![draft.clone()]);
+        assert!(rewritten.load(Ordering::SeqCst));
+        assert!(db.search_content_files("new", 10).unwrap().is_empty());
+
+        let stats = indexer.update(&root, None).unwrap();
+        assert_eq!(stats.updated, 1);
+        assert!(stats.possibly_inconsistent.is_empty());
+        assert_eq!(db.search_content_files("new", 10).unwrap().len(), 1);
+        assert!(db.inconsistent_files().unwrap().is_empty());
+
+        assert_eq!(indexer.update(&root, None).unwrap().updated, 0);
+    }
+
+    #[test]
+    fn test_mtime_tolerance_absorbs_fat_granularity() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("photo.jpg");
+        fs::write(&file_path, "jpeg").unwrap();
+
+        let db = Arc::new(Database::in_memory(2).unwrap());
+        let verify_with_drift = |tolerance: u64, drift_secs: i64| {
+            let config = Arc::new(SearchConfig {
+                index_hidden_files: true,
+                mtime_tolerance_secs: tolerance,
+                ..Default::default()
+            });
+            let filter = Arc::new(ExclusionFilter::from_patterns(&[]).unwrap());
+            let indexer = IncrementalIndexer::new(Arc::clone(&db), config, filter);
+
+            // FAT stores mtimes rounded to two seconds, so the indexed value can be off by one.
+            let mut entry = MetadataExtractor::extract(&file_path).unwrap();
+            entry.modified_at =
+                entry.modified_at.map(|t| t + chrono::Duration::seconds(drift_secs));
+            db.insert_file(&entry).unwrap();
+            indexer.verify_index(temp_dir.path(), None, &CancellationToken::new()).unwrap()
+        };
+
+        assert_eq!(verify_with_drift(2, 1).valid, 1);
+        assert_eq!(verify_with_drift(2, -2).valid, 1);
+        assert_eq!(verify_with_drift(2, 3).outdated, 1);
+        assert_eq!(verify_with_drift(0, 1).outdated, 1);
+    }
+
+    #[test]
+    fn test_local_time_root_verifies_from_another_timezone() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("card");
+        fs::create_dir(&root).unwrap();
+        let file_path = root.join("IMG_0001.JPG");
+        fs::write(&file_path, "jpeg").unwrap();
+
+        let db = Arc::new(Database::in_memory(2).unwrap());
+        let config = Arc::new(SearchConfig {
+            index_hidden_files: true,
+            assume_local_time_paths: vec![root.clone()],
+            ..Default::default()
+        });
+        let filter = Arc::new(ExclusionFilter::from_patterns(&[]).unwrap());
+        let clock = |offset| TimestampClock::new(Arc::clone(&db), Arc::clone(&config))
+            .with_host_offset(offset);
+
+        IndexBuilder::new(Arc::clone(&db), Arc::clone(&config), Arc::clone(&filter))
+            .with_clock(clock(3600))
+            .build(&root, None)
+            .unwrap();
+
+        let raw = MetadataExtractor::modified_at(&file_path, 0).unwrap().unwrap();
+        let stored = db.find_by_path(&file_path).unwrap().unwrap().modified_at.unwrap();
+        assert_eq!((raw - stored).num_seconds(), 3600);
+        assert_eq!(db.get_timestamp_adjustments().unwrap()[0].utc_offset_secs, 3600);
+
+        let elsewhere = IncrementalIndexer::new(Arc::clone(&db), Arc::clone(&config), filter)
+            .with_clock(clock(-18000));
+        let verify = || elsewhere.verify_index(&root, None, &CancellationToken::new()).unwrap();
+        assert_eq!(verify().valid, 1);
+        assert_eq!(elsewhere.update(&root, None).unwrap().updated, 0);
+
+        db.clear_timestamp_adjustment(&root).unwrap();
+        assert_eq!(verify().outdated, 1);
+    }
+
+    #[test]
+    fn test_verify_index_reports_progress_problems_and_cancellation() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for i in 0..5 {
+            fs::write(root.join(format!("file{}.txt", i)), "content").unwrap();
+        }
+
+        let db = Arc::new(Database::in_memory(2).unwrap());
+        let config = Arc::new(SearchConfig {
+            index_hidden_files: true,
+            batch_size: 2,
+            progress_delivery: ProgressDelivery::Inline,
+            ..Default::default()
+        });
+        let filter = Arc::new(ExclusionFilter::from_patterns(&[]).unwrap());
+        let indexer = IncrementalIndexer::new(db, config, filter);
+        indexer.update(root, None).unwrap();
+
+        fs::remove_file(root.join("file1.txt")).unwrap();
+        fs::write(root.join("file3.txt"), "changed content").unwrap();
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&calls);
+        let callback: ProgressCallback =
+            Box::new(move |progress| seen.lock().push((progress.current, progress.total)));
+        let stats = indexer
+            .verify_index(root, Some(callback), &CancellationToken::new())
+            .unwrap();
+
+        assert!(!stats.cancelled);
+        assert_eq!((stats.valid, stats.missing, stats.outdated), (3, 1, 1));
+        assert_eq!(*calls.lock(), vec![(2, 5), (4, 5), (5, 5)]);
+        assert_eq!(
+            stats.problems,
+            vec![
+                VerificationProblem {
+                    path: root.join("file1.txt"),
+                    kind: ProblemKind::Missing,
+                },
+                VerificationProblem {
+                    path: root.join("file3.txt"),
+                    kind: ProblemKind::Outdated,
+                },
+            ]
+        );
+
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        let callback: ProgressCallback = Box::new(move |_| trigger.cancel());
+        let stats = indexer.verify_index(root, Some(callback), &cancel).unwrap();
+
+        assert!(stats.cancelled);
+        assert_eq!(stats.total_indexed, 5);
+        assert_eq!(stats.checked(), 2);
+        assert_eq!((stats.valid, stats.missing), (1, 1));
+    }
 }
@@ -1,17 +1,28 @@
-use crate::core::error::Result;
-use crate::core::types::FileEntry;
+use crate::core::error::{IoResultExt, Result};
+use crate::core::types::{DocumentField, FileEntry, ImageDimensions, Ownership, ScriptInfo};
+use crate::filters::{
+    get_extension_category, interpreter_language, parse_shebang, ExtensionCategory,
+    SHEBANG_PROBE_BYTES,
+};
 use crate::utils::mime::detect_mime_type;
 use crate::utils::path::is_hidden;
 use chrono::{DateTime, TimeZone, Utc};
 use std::fs;
+use std::io::Read;
 use std::path::Path;
 
 pub struct MetadataExtractor;
 
 impl MetadataExtractor {
     pub fn extract<P: AsRef<Path>>(path: P) -> Result<FileEntry> {
+        Self::extract_with_offset(path, 0)
+    }
+
+    /// Like `extract`, for a file whose timestamps are local time `utc_offset_secs` ahead of
+    /// UTC; they are shifted back so the entry holds real UTC.
+    pub fn extract_with_offset<P: AsRef<Path>>(path: P, utc_offset_secs: i32) -> Result<FileEntry> {
         let path = path.as_ref();
-        let metadata = fs::metadata(path)?;
+        let metadata = fs::metadata(path).at_path(path)?;
 
         let mut entry = FileEntry::new(path.to_path_buf());
 
@@ -19,26 +30,21 @@ impl MetadataExtractor {
         entry.is_directory = metadata.is_dir();
         entry.is_hidden = is_hidden(path);
 
-        #[cfg(unix)]
-        {
-            entry.is_symlink = metadata.file_type().is_symlink();
-        }
-
-        #[cfg(windows)]
-        {
-            entry.is_symlink = metadata.file_type().is_symlink();
-        }
+        // `metadata` follows links, so only the link's own metadata tells.
+        entry.is_symlink = fs::symlink_metadata(path)
+            .map(|link| link.file_type().is_symlink())
+            .unwrap_or(false);
 
         if let Ok(created) = metadata.created() {
-            entry.created_at = Self::system_time_to_datetime(created);
+            entry.created_at = Self::system_time_to_datetime(created, utc_offset_secs);
         }
 
         if let Ok(modified) = metadata.modified() {
-            entry.modified_at = Self::system_time_to_datetime(modified);
+            entry.modified_at = Self::system_time_to_datetime(modified, utc_offset_secs);
         }
 
         if let Ok(accessed) = metadata.accessed() {
-            entry.accessed_at = Self::system_time_to_datetime(accessed);
+            entry.accessed_at = Self::system_time_to_datetime(accessed, utc_offset_secs);
         }
 
         if !entry.is_directory {
@@ -53,44 +59,164 @@ impl MetadataExtractor {
     }
 
     pub fn extract_batch<P: AsRef<Path> + Sync>(paths: &[P]) -> Vec<Result<FileEntry>> {
+        Self::extract_batch_with_offset(paths, 0)
+    }
+
+    pub fn extract_batch_with_offset<P: AsRef<Path> + Sync>(
+        paths: &[P],
+        utc_offset_secs: i32,
+    ) -> Vec<Result<FileEntry>> {
         use rayon::prelude::*;
 
         paths
             .par_iter()
-            .map(|path| Self::extract(path.as_ref()))
+            .map(|path| Self::extract_with_offset(path.as_ref(), utc_offset_secs))
             .collect()
     }
 
-    fn system_time_to_datetime(time: std::time::SystemTime) -> Option<DateTime<Utc>> {
+    /// Converts a raw filesystem timestamp, treating it as local time `utc_offset_secs`
+    /// ahead of UTC. Filesystems that store UTC use an offset of zero.
+    pub fn system_time_to_datetime(
+        time: std::time::SystemTime,
+        utc_offset_secs: i32,
+    ) -> Option<DateTime<Utc>> {
         time.duration_since(std::time::UNIX_EPOCH)
             .ok()
             .and_then(|duration| {
-                Utc.timestamp_opt(duration.as_secs() as i64, duration.subsec_nanos())
-                    .single()
+                let secs = duration.as_secs() as i64 - i64::from(utc_offset_secs);
+                Utc.timestamp_opt(secs, duration.subsec_nanos()).single()
             })
     }
 
+    pub fn modified_at<P: AsRef<Path>>(
+        path: P,
+        utc_offset_secs: i32,
+    ) -> Result<Option<DateTime<Utc>>> {
+        let metadata = fs::metadata(&path).at_path(&path)?;
+        Ok(metadata
+            .modified()
+            .ok()
+            .and_then(|modified| Self::system_time_to_datetime(modified, utc_offset_secs)))
+    }
+
     pub fn is_modified_since<P: AsRef<Path>>(
         path: P,
         since: DateTime<Utc>,
     ) -> Result<bool> {
-        let metadata = fs::metadata(path)?;
-        if let Ok(modified) = metadata.modified() {
-            if let Some(modified_dt) = Self::system_time_to_datetime(modified) {
-                return Ok(modified_dt > since);
-            }
+        Ok(Self::modified_at(path, 0)?.is_some_and(|modified| modified > since))
+    }
+
+    /// The pixel size recorded in an image file's header, without decoding the image.
+    /// `None` for files that are not images by extension or whose header cannot be read.
+    pub fn image_dimensions<P: AsRef<Path>>(path: P) -> Option<ImageDimensions> {
+        let path = path.as_ref();
+        let extension = path.extension()?.to_str()?;
+        if get_extension_category(extension) != ExtensionCategory::Image {
+            return None;
+        }
+
+        let size = imagesize::size(path).ok()?;
+        Some(ImageDimensions {
+            width: u32::try_from(size.width).ok()?,
+            height: u32::try_from(size.height).ok()?,
+        })
+    }
+
+    /// The interpreter named by the `#!` line of an extension-less regular file, read from
+    /// its first `SHEBANG_PROBE_BYTES`. `None` for any other file.
+    pub fn script_info<P: AsRef<Path>>(path: P) -> Option<ScriptInfo> {
+        let path = path.as_ref();
+        if path.extension().is_some() {
+            return None;
+        }
+        let metadata = fs::metadata(path).ok()?;
+        if !metadata.is_file() {
+            return None;
+        }
+
+        let mut head = Vec::with_capacity(SHEBANG_PROBE_BYTES);
+        fs::File::open(path)
+            .ok()?
+            .take(SHEBANG_PROBE_BYTES as u64)
+            .read_to_end(&mut head)
+            .ok()?;
+        let interpreter = parse_shebang(&head)?;
+        let language = interpreter_language(&interpreter)
+            .map_or_else(|| interpreter.to_ascii_lowercase(), str::to_string);
+
+        Some(ScriptInfo {
+            interpreter,
+            language,
+            is_executable: is_executable(&metadata),
+        })
+    }
+
+    /// Header fields of an `.eml` message or head fields of an `.html` page, read from its
+    /// first `DOCUMENT_PROBE_BYTES`. `None` for any other file. Documents that don't parse
+    /// give no fields and are indexed by their plain content.
+    #[cfg(feature = "document-fields")]
+    pub fn document_fields<P: AsRef<Path>>(path: P) -> Option<Vec<DocumentField>> {
+        use crate::indexer::document::{document_kind, DOCUMENT_PROBE_BYTES};
+
+        let path = path.as_ref();
+        let kind = document_kind(path.extension()?.to_str()?)?;
+        if !fs::metadata(path).ok()?.is_file() {
+            return None;
         }
-        Ok(false)
+
+        let mut head = Vec::with_capacity(DOCUMENT_PROBE_BYTES);
+        fs::File::open(path)
+            .ok()?
+            .take(DOCUMENT_PROBE_BYTES as u64)
+            .read_to_end(&mut head)
+            .ok()?;
+        Some(kind.fields(&head))
+    }
+
+    #[cfg(not(feature = "document-fields"))]
+    pub fn document_fields<P: AsRef<Path>>(_path: P) -> Option<Vec<DocumentField>> {
+        None
     }
 
     pub fn get_file_size<P: AsRef<Path>>(path: P) -> Result<u64> {
-        let metadata = fs::metadata(path)?;
+        let metadata = fs::metadata(&path).at_path(&path)?;
         Ok(metadata.len())
     }
 
     pub fn is_readable<P: AsRef<Path>>(path: P) -> bool {
         fs::metadata(path).is_ok()
     }
+
+    /// Owner, group and permission bits of `path`, following links. `None` off unix.
+    pub fn ownership<P: AsRef<Path>>(path: P) -> Option<Ownership> {
+        fs::metadata(path).ok().and_then(|metadata| ownership(&metadata))
+    }
+}
+
+#[cfg(unix)]
+fn ownership(metadata: &fs::Metadata) -> Option<Ownership> {
+    use std::os::unix::fs::MetadataExt;
+    Some(Ownership {
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+        mode: metadata.mode() & 0o7777,
+    })
+}
+
+#[cfg(not(unix))]
+fn ownership(_metadata: &fs::Metadata) -> Option<Ownership> {
+    None
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &fs::Metadata) -> bool {
+    false
 }
 
 #[cfg(test)]
@@ -111,6 +237,37 @@ mod tests {
         assert_eq!(entry.extension, Some("txt".to_string()));
         assert_eq!(entry.size, 13);
         assert!(!entry.is_directory);
+
+        let photo = temp_dir.path().join("IMG_0001.JPG");
+        fs::write(&photo, "").unwrap();
+        let entry = MetadataExtractor::extract(&photo).unwrap();
+        assert_eq!(entry.name, "IMG_0001.JPG");
+        assert_eq!(entry.extension, Some("jpg".to_string()));
+    }
+
+    #[test]
+    fn test_script_info_reads_extensionless_shebangs() {
+        let temp_dir = TempDir::new().unwrap();
+        let deploy = temp_dir.path().join("deploy");
+        fs::write(&deploy, "#!/usr/bin/env bash\nset -e\n").unwrap();
+        let renamed = temp_dir.path().join("deploy.txt");
+        fs::write(&renamed, "#!/usr/bin/env bash\n").unwrap();
+        let notes = temp_dir.path().join("NOTES");
+        fs::write(&notes, "no shebang here").unwrap();
+
+        let script = MetadataExtractor::script_info(&deploy).unwrap();
+        assert_eq!((script.interpreter.as_str(), script.language.as_str()), ("bash", "bash"));
+        assert!(!script.is_executable);
+        assert_eq!(MetadataExtractor::script_info(&renamed), None);
+        assert_eq!(MetadataExtractor::script_info(&notes), None);
+        assert_eq!(MetadataExtractor::script_info(temp_dir.path()), None);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&deploy, fs::Permissions::from_mode(0o755)).unwrap();
+            assert!(MetadataExtractor::script_info(&deploy).unwrap().is_executable);
+        }
     }
 
     #[test]
@@ -125,6 +282,39 @@ mod tests {
         assert!(entry.is_directory);
     }
 
+    #[test]
+    fn test_image_dimensions_from_headers() {
+        let temp_dir = TempDir::new().unwrap();
+        let png = temp_dir.path().join("wide.png");
+        let jpeg = temp_dir.path().join("tall.JPG");
+        image::RgbImage::new(64, 16).save(&png).unwrap();
+        image::RgbImage::new(9, 31)
+            .save_with_format(&jpeg, image::ImageFormat::Jpeg)
+            .unwrap();
+
+        assert_eq!(
+            MetadataExtractor::image_dimensions(&png),
+            Some(ImageDimensions {
+                width: 64,
+                height: 16
+            })
+        );
+        assert_eq!(
+            MetadataExtractor::image_dimensions(&jpeg),
+            Some(ImageDimensions {
+                width: 9,
+                height: 31
+            })
+        );
+
+        let renamed = temp_dir.path().join("wide.txt");
+        fs::copy(&png, &renamed).unwrap();
+        assert_eq!(MetadataExtractor::image_dimensions(&renamed), None);
+        let broken = temp_dir.path().join("broken.png");
+        fs::write(&broken, "not an image").unwrap();
+        assert_eq!(MetadataExtractor::image_dimensions(&broken), None);
+    }
+
     #[test]
     fn test_extract_batch() {
         let temp_dir = TempDir::new().unwrap();
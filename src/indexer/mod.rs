@@ -1,11 +1,25 @@
+pub mod batch;
 pub mod builder;
+pub mod clock;
 pub mod content;
+#[cfg(feature = "document-fields")]
+pub mod document;
 pub mod incremental;
 pub mod metadata;
-pub mod walker;
+pub mod progress;
+pub mod quota;
+pub mod stores;
+pub(crate) mod walker;
 
+pub use batch::{BatchOutcome, BatchWriter};
 pub use builder::IndexBuilder;
-pub use content::ContentAnalyzer;
-pub use incremental::{IncrementalIndexer, UpdateStats, VerificationStats};
+pub use clock::{frozen_time, TimestampClock, WallClock};
+pub use content::{AnalyzedContent, ContentAnalyzer};
+pub use incremental::{
+    IncrementalIndexer, ProblemKind, UpdateStats, VerificationProblem, VerificationStats,
+    MAX_REPORTED_PROBLEMS,
+};
 pub use metadata::MetadataExtractor;
-pub use walker::DirectoryWalker;
+pub use progress::{ProgressDispatcher, PROGRESS_QUEUE_CAPACITY};
+pub use quota::QuotaGuard;
+pub use stores::StoreRoots;
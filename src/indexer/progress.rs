@@ -0,0 +1,337 @@
+use crate::core::config::{ProgressDelivery, SearchConfig};
+use crate::core::types::{Progress, ProgressCallback};
+use parking_lot::{Condvar, Mutex};
+use std::collections::VecDeque;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Updates waiting for an async callback; the oldest is dropped to make room for a new one.
+pub const PROGRESS_QUEUE_CAPACITY: usize = 8;
+
+/// Calls a run's progress callback the way `ProgressDelivery` says, so a slow callback
+/// doesn't hold up indexing and a panicking one doesn't end it.
+///
+/// Updates that say the work is complete are held back: only [`finish`](Self::finish)
+/// delivers the final update, so a finished run reports 100% exactly once. Dropping a
+/// dispatcher without finishing, as a cancelled or failed run does, delivers the latest
+/// update still held back by throttling.
+pub struct ProgressDispatcher {
+    target: Option<Target>,
+    interval: Duration,
+    state: Mutex<Throttle>,
+}
+
+enum Target {
+    Inline(Arc<GuardedCallback>),
+    Async {
+        queue: Arc<UpdateQueue>,
+        worker: Mutex<Option<JoinHandle<()>>>,
+    },
+}
+
+#[derive(Default)]
+struct Throttle {
+    last_sent: Option<Instant>,
+    pending: Option<Progress>,
+    finished: bool,
+}
+
+impl ProgressDispatcher {
+    pub fn new(callback: Option<ProgressCallback>, config: &SearchConfig) -> Self {
+        Self::with_delivery(
+            callback,
+            config.progress_delivery,
+            Duration::from_millis(config.progress_interval_ms),
+        )
+    }
+
+    pub fn with_delivery(
+        callback: Option<ProgressCallback>,
+        delivery: ProgressDelivery,
+        interval: Duration,
+    ) -> Self {
+        let target = callback.map(|callback| {
+            let callback = Arc::new(GuardedCallback::new(callback));
+            match delivery {
+                ProgressDelivery::Async => Target::spawn(callback),
+                ProgressDelivery::Inline | ProgressDelivery::Throttled => Target::Inline(callback),
+            }
+        });
+        let interval = match delivery {
+            ProgressDelivery::Inline => Duration::ZERO,
+            ProgressDelivery::Throttled | ProgressDelivery::Async => interval,
+        };
+
+        Self {
+            target,
+            interval,
+            state: Mutex::new(Throttle::default()),
+        }
+    }
+
+    /// Reports an intermediate update, unless one was delivered less than the interval ago;
+    /// then it replaces whatever update was waiting.
+    pub fn report(&self, progress: Progress) {
+        let Some(ref target) = self.target else {
+            return;
+        };
+
+        let mut state = self.state.lock();
+        if state.finished {
+            return;
+        }
+        let now = Instant::now();
+        let due = state
+            .last_sent
+            .map_or(true, |sent| now.duration_since(sent) >= self.interval);
+        if progress.is_complete() || !due {
+            state.pending = Some(progress);
+            return;
+        }
+
+        state.last_sent = Some(now);
+        state.pending = None;
+        drop(state);
+        target.deliver(progress, false);
+    }
+
+    /// Delivers the final update, dropping any still waiting, and waits for an async callback
+    /// to return. Later reports are ignored.
+    pub fn finish(&self, progress: Progress) {
+        let Some(ref target) = self.target else {
+            return;
+        };
+
+        {
+            let mut state = self.state.lock();
+            if state.finished {
+                return;
+            }
+            state.finished = true;
+            state.pending = None;
+        }
+        target.deliver(progress, true);
+        target.shut_down();
+    }
+}
+
+impl Drop for ProgressDispatcher {
+    fn drop(&mut self) {
+        let Some(ref target) = self.target else {
+            return;
+        };
+
+        let pending = {
+            let mut state = self.state.lock();
+            state.finished = true;
+            state.pending.take()
+        };
+        if let Some(progress) = pending {
+            target.deliver(progress, true);
+        }
+        target.shut_down();
+    }
+}
+
+impl Target {
+    fn spawn(callback: Arc<GuardedCallback>) -> Self {
+        let queue = Arc::new(UpdateQueue::default());
+        let worker_queue = Arc::clone(&queue);
+        let worker_callback = Arc::clone(&callback);
+        let spawned = std::thread::Builder::new()
+            .name("progress-callback".to_string())
+            .spawn(move || {
+                while let Some(progress) = worker_queue.pop() {
+                    worker_callback.call(progress);
+                }
+            });
+
+        match spawned {
+            Ok(worker) => Target::Async {
+                queue,
+                worker: Mutex::new(Some(worker)),
+            },
+            Err(e) => {
+                log::warn!("Cannot start the progress thread, reporting inline: {}", e);
+                Target::Inline(callback)
+            }
+        }
+    }
+
+    /// `last` updates clear the async queue, so the callback sees them next.
+    fn deliver(&self, progress: Progress, last: bool) {
+        match self {
+            Target::Inline(callback) => callback.call(progress),
+            Target::Async { queue, .. } => queue.push(progress, last),
+        }
+    }
+
+    fn shut_down(&self) {
+        if let Target::Async { queue, worker } = self {
+            queue.close();
+            if let Some(worker) = worker.lock().take() {
+                let _ = worker.join();
+            }
+        }
+    }
+}
+
+struct GuardedCallback {
+    callback: ProgressCallback,
+    disabled: AtomicBool,
+}
+
+impl GuardedCallback {
+    fn new(callback: ProgressCallback) -> Self {
+        Self {
+            callback,
+            disabled: AtomicBool::new(false),
+        }
+    }
+
+    fn call(&self, progress: Progress) {
+        if self.disabled.load(Ordering::Relaxed) {
+            return;
+        }
+        if catch_unwind(AssertUnwindSafe(|| (self.callback)(progress))).is_err() {
+            self.disabled.store(true, Ordering::Relaxed);
+            log::warn!("Progress callback panicked; no more progress is reported for this run");
+        }
+    }
+}
+
+#[derive(Default)]
+struct UpdateQueue {
+    state: Mutex<QueueState>,
+    ready: Condvar,
+}
+
+#[derive(Default)]
+struct QueueState {
+    updates: VecDeque<Progress>,
+    closed: bool,
+}
+
+impl UpdateQueue {
+    fn push(&self, progress: Progress, last: bool) {
+        let mut state = self.state.lock();
+        if last {
+            state.updates.clear();
+        } else if state.updates.len() >= PROGRESS_QUEUE_CAPACITY {
+            state.updates.pop_front();
+        }
+        state.updates.push_back(progress);
+        self.ready.notify_one();
+    }
+
+    /// The next update, or `None` once the queue is closed and drained.
+    fn pop(&self) -> Option<Progress> {
+        let mut state = self.state.lock();
+        loop {
+            if let Some(progress) = state.updates.pop_front() {
+                return Some(progress);
+            }
+            if state.closed {
+                return None;
+            }
+            self.ready.wait(&mut state);
+        }
+    }
+
+    fn close(&self) {
+        self.state.lock().closed = true;
+        self.ready.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recorder() -> (ProgressCallback, Arc<Mutex<Vec<usize>>>) {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        let callback: ProgressCallback =
+            Box::new(move |progress| sink.lock().push(progress.current));
+        (callback, seen)
+    }
+
+    fn update(current: usize) -> Progress {
+        Progress::new(current, 10, String::new())
+    }
+
+    #[test]
+    fn test_throttled_updates_coalesce_to_the_latest() {
+        let (callback, seen) = recorder();
+        let dispatcher = ProgressDispatcher::with_delivery(
+            Some(callback),
+            ProgressDelivery::Throttled,
+            Duration::from_secs(60),
+        );
+        for current in 1..=9 {
+            dispatcher.report(update(current));
+        }
+        drop(dispatcher);
+        assert_eq!(*seen.lock(), vec![1, 9]);
+
+        let (callback, seen) = recorder();
+        let dispatcher = ProgressDispatcher::with_delivery(
+            Some(callback),
+            ProgressDelivery::Inline,
+            Duration::from_secs(60),
+        );
+        for current in 1..=10 {
+            dispatcher.report(update(current));
+        }
+        dispatcher.finish(Progress::complete(10, String::new()));
+        dispatcher.report(update(3));
+        drop(dispatcher);
+        assert_eq!(*seen.lock(), (1..=10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_async_finish_waits_for_the_final_update() {
+        let (callback, seen) = recorder();
+        let slow: ProgressCallback = Box::new(move |progress| {
+            std::thread::sleep(Duration::from_millis(20));
+            callback(progress);
+        });
+        let dispatcher =
+            ProgressDispatcher::with_delivery(Some(slow), ProgressDelivery::Async, Duration::ZERO);
+
+        let started = Instant::now();
+        for current in 1..=50 {
+            dispatcher.report(Progress::new(current, 100, String::new()));
+        }
+        assert!(started.elapsed() < Duration::from_millis(200));
+        dispatcher.finish(Progress::complete(100, String::new()));
+
+        let seen = seen.lock();
+        assert_eq!(seen.last(), Some(&100));
+        assert!(seen.len() <= 2 + PROGRESS_QUEUE_CAPACITY);
+    }
+
+    #[test]
+    fn test_panicking_callback_is_disabled() {
+        let calls = Arc::new(Mutex::new(0));
+        for delivery in [ProgressDelivery::Inline, ProgressDelivery::Async] {
+            *calls.lock() = 0;
+            let callback: ProgressCallback = {
+                let calls = Arc::clone(&calls);
+                Box::new(move |_| {
+                    *calls.lock() += 1;
+                    panic!("callback bug");
+                })
+            };
+            let dispatcher =
+                ProgressDispatcher::with_delivery(Some(callback), delivery, Duration::ZERO);
+            dispatcher.report(update(1));
+            dispatcher.report(update(2));
+            dispatcher.finish(Progress::complete(10, String::new()));
+            assert_eq!(*calls.lock(), 1);
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use crate::core::config::{QuotaEviction, SearchConfig};
+use crate::core::error::{Result, SearchError};
+use crate::storage::Database;
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// Assumed cost of one entry until batches have been measured: the row, its index entries
+/// and the log pages written with it.
+const DEFAULT_ENTRY_BYTES: u64 = 1024;
+
+/// Files whose content the first eviction round drops before space is reclaimed and measured.
+/// Each further round doubles it, so a large shortfall needs few rebuilds of the file.
+const FIRST_EVICTION_ROUND: usize = 16;
+
+/// Keeps the index within `max_index_size_bytes` by checking each batch before it is written.
+///
+/// The write-ahead log is checkpointed before measuring, so between checks it holds at most
+/// the last batch. A batch is admitted when the current size plus the per-entry cost seen
+/// in earlier batches, and never less than [`DEFAULT_ENTRY_BYTES`], fits the budget.
+pub struct QuotaGuard {
+    database: Arc<Database>,
+    limit: Option<u64>,
+    eviction: QuotaEviction,
+    // Size after the last admitted batch and how many entries it had.
+    last_admitted: Mutex<Option<(u64, usize)>>,
+}
+
+impl QuotaGuard {
+    pub fn new(database: Arc<Database>, config: &SearchConfig) -> Self {
+        Self {
+            database,
+            limit: config.max_index_size_bytes,
+            eviction: config.quota_eviction,
+            last_admitted: Mutex::new(None),
+        }
+    }
+
+    /// Makes sure `incoming` more entries fit, evicting content first when configured.
+    ///
+    /// Fails with `QuotaExceeded`, reporting 0 entries indexed, when they do not fit; callers
+    /// fill in their progress with [`with_progress`].
+    pub fn admit(&self, incoming: usize) -> Result<()> {
+        let Some(limit) = self.limit else {
+            return Ok(());
+        };
+
+        self.database.checkpoint()?;
+        let mut used = self.database.storage_size()?;
+        let needed = incoming as u64 * self.entry_bytes(used);
+
+        if self.eviction == QuotaEviction::EvictLeastRecentlyAccessed {
+            let mut round = FIRST_EVICTION_ROUND;
+            while used + needed > limit
+                && self.database.evict_least_recently_accessed_content(round)? > 0
+            {
+                // Deleted rows only free pages inside the file; rebuilding it gives them back.
+                self.database.vacuum()?;
+                self.database.checkpoint()?;
+                used = self.database.storage_size()?;
+                round = round.saturating_mul(2);
+            }
+        }
+
+        if used + needed > limit {
+            return Err(SearchError::QuotaExceeded {
+                limit,
+                used,
+                indexed: 0,
+            });
+        }
+
+        *self.last_admitted.lock() = Some((used, incoming));
+        Ok(())
+    }
+
+    fn entry_bytes(&self, used: u64) -> u64 {
+        let observed = match *self.last_admitted.lock() {
+            Some((before, entries)) if entries > 0 => used.saturating_sub(before) / entries as u64,
+            _ => 0,
+        };
+        observed.max(DEFAULT_ENTRY_BYTES)
+    }
+}
+
+/// Attaches the number of entries already indexed to a `QuotaExceeded` error.
+pub fn with_progress(error: SearchError, indexed: usize) -> SearchError {
+    match error {
+        SearchError::QuotaExceeded { limit, used, .. } => SearchError::QuotaExceeded {
+            limit,
+            used,
+            indexed,
+        },
+        error => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::ExclusionFilter;
+    use crate::indexer::IndexBuilder;
+    use std::fs;
+    use crate::core::types::FileEntry;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn write_files(dir: &Path, prefix: &str, count: usize) {
+        fs::create_dir_all(dir).unwrap();
+        for i in 0..count {
+            let text = format!("{}word{} ", prefix, i).repeat(300);
+            fs::write(dir.join(format!("{}{}.txt", prefix, i)), text).unwrap();
+        }
+    }
+
+    fn builder(db: &Arc<Database>, quota: Option<(u64, QuotaEviction)>) -> IndexBuilder {
+        let config = SearchConfig {
+            batch_size: 5,
+            enable_content_search: true,
+            index_hidden_files: true,
+            max_index_size_bytes: quota.map(|(limit, _)| limit),
+            quota_eviction: quota.map_or(QuotaEviction::RejectNew, |(_, eviction)| eviction),
+            ..SearchConfig::default()
+        };
+        IndexBuilder::new(
+            Arc::clone(db),
+            Arc::new(config),
+            Arc::new(ExclusionFilter::from_patterns(&[]).unwrap()),
+        )
+    }
+
+    fn open(temp_dir: &TempDir) -> Arc<Database> {
+        let db = Arc::new(Database::new(temp_dir.path().join("index.db"), 2).unwrap());
+        db.checkpoint().unwrap();
+        db
+    }
+
+    fn has_content(db: &Database, token: &str) -> bool {
+        !db.search_content_files(token, 10).unwrap().is_empty()
+    }
+
+    #[test]
+    fn test_reject_stops_with_partial_progress() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("data");
+        write_files(&root, "a", 40);
+        let db = open(&temp_dir);
+        let limit = db.storage_size().unwrap() + 64 * 1024;
+
+        let err = builder(&db, Some((limit, QuotaEviction::RejectNew)))
+            .build(&root, None)
+            .unwrap_err();
+        let SearchError::QuotaExceeded {
+            limit: reported,
+            used,
+            indexed,
+        } = err
+        else {
+            panic!("expected QuotaExceeded, got {:?}", err);
+        };
+
+        assert_eq!(reported, limit);
+        assert!(indexed > 0 && indexed < 40, "indexed {}", indexed);
+        assert_eq!(indexed % 5, 0);
+        let stats = db.get_stats().unwrap();
+        assert_eq!(stats.total_files + stats.total_directories, indexed);
+        db.checkpoint().unwrap();
+        assert!(used <= limit && db.storage_size().unwrap() <= limit);
+    }
+
+    #[test]
+    fn test_eviction_drops_least_recently_accessed_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let (first, second) = (temp_dir.path().join("first"), temp_dir.path().join("second"));
+        write_files(&first, "a", 40);
+        write_files(&second, "b", 10);
+        let db = open(&temp_dir);
+        builder(&db, None).build(&first, None).unwrap();
+
+        // Without the access, the first file indexed would be the first to lose its content.
+        let mut indexed = db.get_all_files(100, 0).unwrap();
+        indexed.sort_by_key(|entry| entry.id);
+        let recent = indexed.remove(0);
+        db.log_access(recent.id.unwrap()).unwrap();
+        db.checkpoint().unwrap();
+        let limit = db.storage_size().unwrap() + 2 * 1024;
+
+        let rejected = builder(&db, Some((limit, QuotaEviction::RejectNew))).build(&second, None);
+        assert!(matches!(rejected, Err(SearchError::QuotaExceeded { indexed: 0, .. })));
+
+        let evicting = builder(&db, Some((limit, QuotaEviction::EvictLeastRecentlyAccessed)));
+        assert_eq!(evicting.build(&second, None).unwrap(), 10);
+
+        let stats = db.get_stats().unwrap();
+        assert_eq!(stats.total_files, 50);
+        assert!(stats.indexed_files < 50);
+        let token = |entry: &FileEntry| entry.name.trim_end_matches(".txt").replace('a', "aword");
+        assert!(has_content(&db, &token(&recent)));
+        assert!(!has_content(&db, &token(&indexed[0])));
+        assert!(db.find_by_path(&indexed[0].path).unwrap().is_some());
+        db.checkpoint().unwrap();
+        assert!(db.storage_size().unwrap() <= limit);
+    }
+}
@@ -0,0 +1,87 @@
+use crate::core::config::SearchConfig;
+use std::path::{Path, PathBuf};
+
+/// The package-manager stores from `symlink_store_roots` that exist on this machine.
+///
+/// A link that resolves into one of them is a store alias: pnpm's `node_modules` and Nix
+/// profiles hold thousands of them pointing at the same files, so they are indexed by their
+/// own path and the store is read only where it is indexed itself.
+#[derive(Debug, Clone, Default)]
+pub struct StoreRoots {
+    roots: Vec<PathBuf>,
+}
+
+impl StoreRoots {
+    pub fn from_config(config: &SearchConfig) -> Self {
+        Self::new(&config.symlink_store_roots)
+    }
+
+    /// Missing roots are dropped; the rest are canonicalized, so links are matched against
+    /// where the store really is.
+    pub fn new(roots: &[PathBuf]) -> Self {
+        Self {
+            roots: roots
+                .iter()
+                .filter_map(|root| dunce::canonicalize(root).ok())
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// Whether the canonical `path` lies in a store.
+    pub fn contains(&self, path: &Path) -> bool {
+        self.roots.iter().any(|root| path.starts_with(root))
+    }
+
+    /// Where `path` points if it is a link into a store.
+    pub fn resolve(&self, path: &Path) -> Option<PathBuf> {
+        if self.roots.is_empty() || !path.symlink_metadata().ok()?.file_type().is_symlink() {
+            return None;
+        }
+
+        let target = dunce::canonicalize(path).ok()?;
+        self.contains(&target).then_some(target)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_only_links_into_a_store_resolve() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("store/pkg")).unwrap();
+        fs::create_dir(root.join("elsewhere")).unwrap();
+        fs::write(root.join("store/pkg/index.js"), "").unwrap();
+        fs::write(root.join("elsewhere/other.js"), "").unwrap();
+        symlink(root.join("store/pkg"), root.join("pkg")).unwrap();
+        symlink(root.join("store/pkg/index.js"), root.join("index.js")).unwrap();
+        symlink(root.join("elsewhere/other.js"), root.join("other.js")).unwrap();
+
+        let stores = StoreRoots::new(&[root.join("store"), root.join("missing")]);
+        assert_eq!(stores.roots().len(), 1);
+
+        let store = dunce::canonicalize(root.join("store")).unwrap();
+        assert_eq!(stores.resolve(&root.join("pkg")), Some(store.join("pkg")));
+        assert_eq!(
+            stores.resolve(&root.join("index.js")),
+            Some(store.join("pkg/index.js"))
+        );
+        assert_eq!(stores.resolve(&root.join("other.js")), None);
+        // The store's own files are not links.
+        assert_eq!(stores.resolve(&root.join("store/pkg/index.js")), None);
+        assert!(StoreRoots::new(&[]).resolve(&root.join("pkg")).is_none());
+    }
+}
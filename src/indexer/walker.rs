@@ -1,103 +1,222 @@
 use crate::core::config::SearchConfig;
 use crate::core::error::Result;
 use crate::filters::ExclusionFilter;
+use crate::indexer::stores::StoreRoots;
 use crate::utils::path::is_hidden;
 use dashmap::DashSet;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, IntoIter, SyncSender};
 use std::sync::Arc;
+use std::thread;
 use walkdir::{DirEntry, WalkDir};
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalkedEntry {
+    pub path: PathBuf,
+    pub depth: usize,
+    /// Where the file really is when `path` reaches it through a link into a package store.
+    pub link_target: Option<PathBuf>,
+}
+
+#[derive(Clone)]
 pub struct DirectoryWalker {
     config: Arc<SearchConfig>,
     exclusion_filter: Arc<ExclusionFilter>,
+    stores: Arc<StoreRoots>,
     visited: Arc<DashSet<PathBuf>>,
+    in_flight: Arc<AtomicUsize>,
+    queue_high_water: Arc<AtomicUsize>,
 }
 
 impl DirectoryWalker {
     pub fn new(config: Arc<SearchConfig>, exclusion_filter: Arc<ExclusionFilter>) -> Self {
         Self {
+            stores: Arc::new(StoreRoots::from_config(&config)),
             config,
             exclusion_filter,
             visited: Arc::new(DashSet::new()),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            queue_high_water: Arc::new(AtomicUsize::new(0)),
         }
     }
 
+    /// Walks `root` on a producer thread and yields files as they are found.
+    /// At most `queue_capacity()` entries are buffered; dropping the stream stops the walk.
+    pub fn walk_streaming<P: AsRef<Path>>(&self, root: P) -> Result<WalkStream> {
+        let (sender, receiver) = sync_channel(self.queue_capacity());
+        let producer = self.clone();
+        let root = root.as_ref().to_path_buf();
+
+        thread::Builder::new()
+            .name("filesearch-walker".to_string())
+            .spawn(move || producer.produce(&root, &sender))?;
+
+        Ok(WalkStream {
+            receiver: receiver.into_iter(),
+            in_flight: Arc::clone(&self.in_flight),
+        })
+    }
+
+    #[cfg(test)]
     pub fn walk<P: AsRef<Path>>(&self, root: P) -> Result<Vec<PathBuf>> {
-        let root = root.as_ref();
-        let mut paths = Vec::new();
+        Ok(self.walk_streaming(root)?.map(|entry| entry.path).collect())
+    }
+
+    // The walk itself is sequential; parallelism now happens per batch in the indexer.
+    #[cfg(test)]
+    pub fn walk_parallel<P: AsRef<Path>>(&self, root: P) -> Result<Vec<PathBuf>> {
+        self.walk(root)
+    }
 
-        for entry in WalkDir::new(root)
-            .follow_links(self.config.follow_symlinks)
+    pub fn queue_capacity(&self) -> usize {
+        self.config.batch_size.max(1) * self.config.thread_count.max(1)
+    }
+
+    /// The most entries that were ever buffered between the walker and its consumer.
+    #[cfg(test)]
+    pub fn queue_high_water(&self) -> usize {
+        self.queue_high_water.load(Ordering::Relaxed)
+    }
+
+    #[cfg(test)]
+    pub fn visited_count(&self) -> usize {
+        self.visited.len()
+    }
+
+    fn produce(&self, root: &Path, sender: &SyncSender<WalkedEntry>) {
+        let mut walker = self
+            .walk_dir(root)
             .into_iter()
-            .filter_entry(|e| self.should_visit(e))
-        {
-            match entry {
-                Ok(entry) => {
-                    let path = entry.path();
-
-                    if !self.should_index(path) {
-                        continue;
-                    }
-
-                    if self.is_cyclic(path) {
-                        continue;
-                    }
-
-                    // Insert canonical path to match is_cyclic check
-                    if let Ok(canonical) = dunce::canonicalize(path) {
-                        self.visited.insert(canonical);
-                    } else {
-                        self.visited.insert(path.to_path_buf());
-                    }
-                    paths.push(path.to_path_buf());
-                }
+            .filter_entry(|e| self.should_visit(e));
+
+        while let Some(entry) = walker.next() {
+            let entry = match entry {
+                Ok(entry) => entry,
                 Err(e) => {
                     log::warn!("Error walking directory: {}", e);
+                    continue;
+                }
+            };
+
+            if let Some(target) = self.store_target(&entry) {
+                if entry.file_type().is_dir() {
+                    // Followed links are not descended into; their files are sent as aliases.
+                    walker.skip_current_dir();
+                }
+                if !self.send_aliases(&entry, target, sender) {
+                    break;
                 }
+                continue;
+            }
+
+            if !self.should_index(&entry) {
+                continue;
             }
-        }
 
-        Ok(paths)
+            let walked = WalkedEntry {
+                depth: entry.depth(),
+                path: entry.into_path(),
+                link_target: None,
+            };
+            if !self.send(walked, sender) {
+                break;
+            }
+        }
     }
 
-    pub fn walk_parallel<P: AsRef<Path>>(
-        &self,
-        root: P,
-    ) -> Result<Vec<PathBuf>> {
-        use rayon::prelude::*;
+    /// Returns false once the consumer went away (cancelled or finished early).
+    fn send(&self, walked: WalkedEntry, sender: &SyncSender<WalkedEntry>) -> bool {
+        let in_flight = self.in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+        self.queue_high_water.fetch_max(in_flight, Ordering::Relaxed);
 
-        let root = root.as_ref();
-        let entries: Vec<_> = WalkDir::new(root)
-            .follow_links(self.config.follow_symlinks)
-            .into_iter()
-            .filter_entry(|e| self.should_visit(e))
-            .filter_map(|e| e.ok())
-            .collect();
+        if sender.send(walked).is_err() {
+            self.in_flight.fetch_sub(1, Ordering::Relaxed);
+            return false;
+        }
+        true
+    }
 
-        let paths: Vec<PathBuf> = entries
-            .par_iter()
-            .filter_map(|entry| {
-                let path = entry.path();
+    fn store_target(&self, entry: &DirEntry) -> Option<PathBuf> {
+        if !entry.path_is_symlink() {
+            return None;
+        }
+        self.stores.resolve(entry.path())
+    }
 
-                if !self.should_index(path) {
-                    return None;
-                }
+    /// Sends a link into a package store as an alias of `target`. A linked directory's files
+    /// are sent under the link's path, so the logical layout stays searchable while the
+    /// store's content is only read where the store itself is walked.
+    fn send_aliases(
+        &self,
+        link: &DirEntry,
+        target: PathBuf,
+        sender: &SyncSender<WalkedEntry>,
+    ) -> bool {
+        if !target.is_dir() {
+            if !self.exclusion_filter.is_included(link.path()) {
+                return true;
+            }
+            return self.send(
+                WalkedEntry {
+                    path: link.path().to_path_buf(),
+                    depth: link.depth(),
+                    link_target: Some(target),
+                },
+                sender,
+            );
+        }
 
-                if self.is_cyclic(path) {
-                    return None;
+        let mut walk_dir = WalkDir::new(&target).min_depth(1);
+        if let Some(depth) = self.config.max_depth {
+            if link.depth() >= depth {
+                return true;
+            }
+            walk_dir = walk_dir.max_depth(depth - link.depth());
+        }
+        let logical = |path: &Path| link.path().join(path.strip_prefix(&target).unwrap_or(path));
+        let entries = walk_dir.into_iter().filter_entry(|e| {
+            let path = logical(e.path());
+            !self.exclusion_filter.is_excluded(&path)
+                && (self.config.index_hidden_files || !is_hidden(&path))
+        });
+
+        for entry in entries.flatten() {
+            if entry.file_type().is_dir() {
+                continue;
+            }
+            // Links inside the store point at siblings in it, e.g. pnpm's dependency links.
+            let physical = if entry.path_is_symlink() {
+                match dunce::canonicalize(entry.path()) {
+                    Ok(physical) if !physical.is_dir() => physical,
+                    _ => continue,
                 }
+            } else {
+                entry.path().to_path_buf()
+            };
 
-                // Insert canonical path to match is_cyclic check
-                if let Ok(canonical) = dunce::canonicalize(path) {
-                    self.visited.insert(canonical);
-                } else {
-                    self.visited.insert(path.to_path_buf());
-                }
-                Some(path.to_path_buf())
-            })
-            .collect();
+            let path = logical(entry.path());
+            if !self.exclusion_filter.is_included(&path) {
+                continue;
+            }
+            let walked = WalkedEntry {
+                path,
+                depth: link.depth() + entry.depth(),
+                link_target: Some(physical),
+            };
+            if !self.send(walked, sender) {
+                return false;
+            }
+        }
+        true
+    }
 
-        Ok(paths)
+    fn walk_dir(&self, root: &Path) -> WalkDir {
+        let walk_dir = WalkDir::new(root).follow_links(self.config.follow_symlinks);
+        match self.config.max_depth {
+            Some(depth) => walk_dir.max_depth(depth),
+            None => walk_dir,
+        }
     }
 
     fn should_visit(&self, entry: &DirEntry) -> bool {
@@ -111,42 +230,66 @@ impl DirectoryWalker {
             return false;
         }
 
-        true
-    }
-
-    fn should_index(&self, path: &Path) -> bool {
-        // Only index files, not directories
-        if path.is_dir() {
+        // A directory reachable through several symlinks is only descended into once. Store
+        // links are never descended into, so they leave the store to be walked where it is.
+        if entry.file_type().is_dir()
+            && self.store_target(entry).is_none()
+            && self.is_cyclic(entry)
+        {
             return false;
         }
 
-        if self.exclusion_filter.is_excluded(path) {
-            return false;
+        true
+    }
+
+    fn should_index(&self, entry: &DirEntry) -> bool {
+        // Directories only when asked for, and never the root the walk starts from.
+        if entry.file_type().is_dir() {
+            return self.config.index_directories && entry.depth() > 0;
         }
 
-        if !self.config.index_hidden_files && is_hidden(path) {
+        if entry.path_is_symlink() && (entry.path().is_dir() || self.is_cyclic(entry)) {
             return false;
         }
 
-        true
+        // Inclusions narrow which files are indexed, never which directories are walked.
+        self.exclusion_filter.is_included(entry.path())
     }
 
-    fn is_cyclic(&self, path: &Path) -> bool {
-        if let Ok(canonical) = dunce::canonicalize(path) {
-            // Only check if we've already visited this exact path
-            if self.visited.contains(&canonical) {
-                return true;
-            }
+    // Without symlink following every path is reached once, so nothing is tracked; with it,
+    // only directories and symlink targets are, keeping the set proportional to the
+    // directory count rather than the file count.
+    fn is_cyclic(&self, entry: &DirEntry) -> bool {
+        if !self.config.follow_symlinks {
+            return false;
         }
 
-        false
+        let canonical =
+            dunce::canonicalize(entry.path()).unwrap_or_else(|_| entry.path().to_path_buf());
+        !self.visited.insert(canonical)
     }
 
+    #[cfg(test)]
     pub fn clear_visited(&self) {
         self.visited.clear();
     }
 }
 
+pub struct WalkStream {
+    receiver: IntoIter<WalkedEntry>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl Iterator for WalkStream {
+    type Item = WalkedEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.receiver.next()?;
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+        Some(entry)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -209,4 +352,205 @@ mod tests {
         assert_eq!(paths.len(), 1, "Expected only visible file");
         assert!(paths.iter().all(|p| !is_hidden(p)), "Should not have hidden files");
     }
+
+    #[test]
+    fn test_inclusions_select_files_but_not_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("src/nested/deep")).unwrap();
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(root.join("Cargo.toml"), "[package]").unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(root.join("src/nested/deep/lib.rs"), "").unwrap();
+        fs::write(root.join("src/nested/notes.txt"), "").unwrap();
+        fs::write(root.join("docs/guide.md"), "").unwrap();
+
+        let config = Arc::new(SearchConfig {
+            index_hidden_files: true,
+            ..Default::default()
+        });
+        let filter = ExclusionFilter::from_patterns(&[])
+            .unwrap()
+            .with_inclusion_patterns(&["*.rs".to_string(), "*.toml".to_string()])
+            .unwrap();
+        let walker = DirectoryWalker::new(config, Arc::new(filter));
+
+        // None of the directories match an inclusion, yet their files are still reached.
+        let mut paths = walker.walk(root).unwrap();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                root.join("Cargo.toml"),
+                root.join("src/main.rs"),
+                root.join("src/nested/deep/lib.rs"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_directories_are_walked_only_when_enabled() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::create_dir_all(root.join("target/debug")).unwrap();
+        fs::write(root.join("src/nested/lib.rs"), "").unwrap();
+
+        let walk = |index_directories| {
+            let config = Arc::new(SearchConfig {
+                index_hidden_files: true,
+                index_directories,
+                ..Default::default()
+            });
+            let filter = ExclusionFilter::from_patterns(&["**/target".to_string()]).unwrap();
+            let mut paths = DirectoryWalker::new(config, Arc::new(filter))
+                .walk(root)
+                .unwrap();
+            paths.sort();
+            paths
+        };
+
+        assert_eq!(walk(false), vec![root.join("src/nested/lib.rs")]);
+        // Neither the root nor excluded directories are entries.
+        assert_eq!(
+            walk(true),
+            vec![
+                root.join("src"),
+                root.join("src/nested"),
+                root.join("src/nested/lib.rs"),
+            ]
+        );
+    }
+
+    fn streaming_config(follow_symlinks: bool) -> Arc<SearchConfig> {
+        Arc::new(SearchConfig {
+            index_hidden_files: true,
+            follow_symlinks,
+            batch_size: 64,
+            thread_count: 2,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_streaming_and_legacy_walks_agree() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for dir in ["a", "a/b", "c"] {
+            fs::create_dir(root.join(dir)).unwrap();
+        }
+        for file in ["top.txt", "a/one.rs", "a/b/two.md", "c/three.toml"] {
+            fs::write(root.join(file), "content").unwrap();
+        }
+
+        let filter = Arc::new(ExclusionFilter::from_patterns(&[]).unwrap());
+        let walker = DirectoryWalker::new(streaming_config(false), filter);
+
+        let mut streamed: Vec<_> = walker.walk_streaming(root).unwrap().collect();
+        streamed.sort_by(|a, b| a.path.cmp(&b.path));
+        let mut legacy = walker.walk(root).unwrap();
+        legacy.sort();
+        let mut parallel = walker.walk_parallel(root).unwrap();
+        parallel.sort();
+
+        let streamed_paths: Vec<_> = streamed.iter().map(|e| e.path.clone()).collect();
+        assert_eq!(streamed_paths, legacy);
+        assert_eq!(legacy, parallel);
+        assert_eq!(legacy.len(), 4);
+        assert_eq!(
+            streamed.iter().find(|e| e.path.ends_with("a/b/two.md")).unwrap().depth,
+            3
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_streaming_walk_descends_symlinked_directory_once() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join("real")).unwrap();
+        fs::write(root.join("real/file.txt"), "content").unwrap();
+        std::os::unix::fs::symlink(root.join("real"), root.join("alias")).unwrap();
+
+        let filter = Arc::new(ExclusionFilter::from_patterns(&[]).unwrap());
+        let walker = DirectoryWalker::new(streaming_config(true), filter);
+
+        assert_eq!(walker.walk_streaming(root).unwrap().count(), 1);
+        assert_eq!(walker.visited_count(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_store_links_become_aliases_whether_followed_or_not() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        // However the directory is ordered, the store is walked once, where it is.
+        fs::create_dir_all(root.join("z-store/pkg")).unwrap();
+        fs::write(root.join("z-store/pkg/index.js"), "").unwrap();
+        fs::create_dir(root.join("app")).unwrap();
+        std::os::unix::fs::symlink(root.join("z-store/pkg"), root.join("app/pkg")).unwrap();
+        std::os::unix::fs::symlink(root.join("z-store/pkg"), root.join("app/again")).unwrap();
+
+        let filter = Arc::new(ExclusionFilter::from_patterns(&[]).unwrap());
+        let store = dunce::canonicalize(root.join("z-store")).unwrap();
+        for follow_symlinks in [false, true] {
+            let config = SearchConfig {
+                symlink_store_roots: vec![root.join("z-store")],
+                ..(*streaming_config(follow_symlinks)).clone()
+            };
+            let walker = DirectoryWalker::new(Arc::new(config), Arc::clone(&filter));
+
+            let mut walked: Vec<_> = walker.walk_streaming(root).unwrap().collect();
+            walked.sort_by(|a, b| a.path.cmp(&b.path));
+            let found: Vec<_> = walked
+                .iter()
+                .map(|e| (e.path.clone(), e.link_target.clone(), e.depth))
+                .collect();
+            let target = Some(store.join("pkg/index.js"));
+            assert_eq!(
+                found,
+                vec![
+                    (root.join("app/again/index.js"), target.clone(), 3),
+                    (root.join("app/pkg/index.js"), target, 3),
+                    (root.join("z-store/pkg/index.js"), None, 3),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_streaming_walk_memory_stays_bounded() {
+        const DIRS: usize = 200;
+        const FILES_PER_DIR: usize = 1000;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for d in 0..DIRS {
+            let dir = root.join(format!("d{}", d));
+            fs::create_dir(&dir).unwrap();
+            for f in 0..FILES_PER_DIR {
+                fs::File::create(dir.join(format!("f{}", f))).unwrap();
+            }
+        }
+
+        let filter = Arc::new(ExclusionFilter::from_patterns(&[]).unwrap());
+        let walker = DirectoryWalker::new(streaming_config(true), filter);
+
+        let mut count = 0;
+        for _ in walker.walk_streaming(root).unwrap() {
+            count += 1;
+            if count % 10_000 == 0 {
+                // Let the producer run ahead and fill the queue.
+                thread::sleep(std::time::Duration::from_millis(5));
+            }
+        }
+
+        assert_eq!(count, DIRS * FILES_PER_DIR);
+        assert!(walker.queue_high_water() > 0);
+        // Besides the buffered entries, one send can be blocked and one received entry can
+        // still be counted before the consumer decrements.
+        assert!(walker.queue_high_water() <= walker.queue_capacity() + 2);
+        // Only the root and its directories are tracked, never individual files.
+        assert_eq!(walker.visited_count(), DIRS + 1);
+    }
 }
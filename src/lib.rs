@@ -1,26 +1,73 @@
+//! The supported API is what this file re-exports, together with [`prelude`]; it follows
+//! semantic versioning. The modules below are public for the bundled `filesearch` and
+//! `filesearch-server` binaries and may change in any release.
+
+// Test setups tweak one field of a default config at a time.
+#![cfg_attr(test, allow(clippy::field_reassign_with_default))]
+
+#[doc(hidden)]
 pub mod core;
+#[doc(hidden)]
 pub mod filters;
+#[doc(hidden)]
 pub mod indexer;
+#[doc(hidden)]
 pub mod search;
+#[doc(hidden)]
 pub mod storage;
+#[doc(hidden)]
 pub mod utils;
+#[doc(hidden)]
 pub mod watcher;
+#[doc(hidden)]
 pub mod server;
 
 pub use core::{
-    DateFilter, ExclusionRule, ExclusionRuleType, FileEntry, IndexStats, MatchLocation, MatchMode,
-    Progress, Result, SearchConfig, SearchConfigBuilder, SearchEngine, SearchError, SearchResult,
-    SearchScope, SizeFilter,
+    AuditEventType, AuditRecord, BackupReport, BatchErrorPolicy, BatchStats, CancellationToken,
+    Capabilities,
+    ChangeExport, ChangeRecord, CliTheme, ConfigError, ContentSampling, ContentSamplingRule,
+    ContentSkip, ContentSkipReason, DateField, DateFilter, DimensionBound, DimensionFilter,
+    DocumentField,
+    EngineCounters, EngineMetrics, EnrichmentProgress, EnrichmentStats, EntryKind, ErrorCode,
+    ErrorReport, ExclusionRule, ExclusionRuleType, FieldFilter, FileEntry, FilePermissions,
+    FtsRebuildReport, ImageDimensions, IndexManifest, IndexReport, IndexStats, IndexedRoot,
+    LeaseWait, Listing, ListingSort, MaintenanceScheduler, MaintenanceStatus, MaintenanceTask,
+    MaintenanceWindow, ManifestRoot, MatchField, MatchLocation, MatchMode, NameIndexUsage,
+    Ownership, Pagination, Progress, ProgressDelivery, QuotaEviction,
+    QuotaUsage, RequestId, RestoreReport, Result, RootIndexReport, RootProgressCallback,
+    SavedSearch, ScriptInfo, SearchConfig, SearchConfigBuilder, SearchEngine, SearchEngineBuilder,
+    SearchError, SearchModeCounts, SearchOutcome, SearchResult, SearchScope, SizeFilter,
+    SkippedEntry, SliceOutcome, StatsCounters, StatsDrift, Thumbnail, TimestampAdjustment,
+    UndoReport, UndoSnapshot, WriteLease,
+};
+
+pub use search::{
+    EffectiveQuery, HiddenMode, Query, QueryDiagnostic, QueryParser, ResultLimit, SortField,
+    SortKey, WeightedTerm,
+};
+
+pub use indexer::{
+    ProblemKind, TimestampClock, UpdateStats, VerificationProblem, VerificationStats, WallClock,
+};
+
+pub use filters::{
+    format_date, format_date_with, format_relative_date, format_size, format_size_with,
+    parse_date, parse_extensions, parse_relative_date, parse_size, timestamp_sanity, DateStyle,
+    ExclusionFilter, ExtensionCategory, FilterDecision, SensitiveFilter, SizeUnits,
+    TimestampSanity,
 };
 
-pub use search::{Query, QueryParser};
+pub use storage::{PathLayout, PoolSizes, Setting, WriteLeaseGuard};
 
-pub use indexer::{UpdateStats, VerificationStats};
+pub use utils::{TextNormalizer, UnicodeNormalizer};
 
-pub use filters::ExclusionFilter;
+pub use watcher::{InitialScanState, WatchEvent, WatchOptions, WatchState, WatchStatus};
 
 pub mod prelude {
-    pub use crate::core::{Result, SearchConfig, SearchEngine};
+    pub use crate::core::{
+        FileEntry, Result, SearchConfig, SearchConfigBuilder, SearchEngine, SearchEngineBuilder,
+        SearchError, SearchResult,
+    };
     pub use crate::search::{Query, QueryParser};
     pub use std::path::PathBuf;
 }
@@ -1,13 +1,103 @@
 use crate::core::config::SearchConfig;
 use crate::core::error::Result;
-use crate::core::types::{FileEntry, MatchMode, SearchResult, SearchScope};
-use crate::filters::{apply_date_filter, apply_extension_filter, apply_size_filter};
+use crate::core::metrics::EngineCounters;
+use crate::core::request::RequestId;
+use crate::core::types::{
+    FileEntry, MatchField, MatchLocation, MatchMode, SearchOutcome, SearchResult, SearchScope,
+};
+use crate::filters::{
+    apply_category_filter, apply_date_filter, apply_extension_filter, apply_kind_filter,
+    apply_language_filter, apply_mime_filter, apply_size_filter, SensitiveFilter,
+};
+use crate::indexer::ContentAnalyzer;
 use crate::search::fuzzy::FuzzyMatcher;
-use crate::search::matcher::create_matcher;
+use crate::search::matcher::{create_normalized_matcher, CompositeMatcher, Matcher};
+use crate::search::plan::{CandidateSource, PlanCache, PlanKey, QueryPlan};
 use crate::search::query::Query;
 use crate::search::ranker::ResultRanker;
-use crate::storage::{Database, FileBloomFilter, LruCache};
+use crate::storage::{Database, FileBloomFilter, LruCache, NameField, NameIndex};
+use crate::utils::normalizer::TextNormalizer;
+use crate::utils::path::{depth_below, normalize_separators, path_match_form};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Instant;
+
+/// Tracing target of the events logged for searches slower than `slow_query_ms`.
+pub const SLOW_QUERY_TARGET: &str = "rusty_files::slow_query";
+
+/// Most matching lines a `context:N` query shows per result.
+const MAX_CONTEXT_MATCHES: usize = 10;
+
+/// Fewest candidates a paged search ranks, so that its pages agree with each other as long as
+/// they fall within them.
+const PAGED_CANDIDATES: usize = 1000;
+
+/// Most entries a fuzzy search scores.
+const FUZZY_CANDIDATES: usize = 10000;
+
+/// Best full-text rank of each file matching a query's `content:` terms, by file id.
+type ContentRanks = HashMap<i64, f64>;
+
+/// Keeps the matches a search may return, given all of them at once so that what decides
+/// it can be looked up together. It runs before results are paged and counted.
+pub type Visibility<'a> = &'a dyn Fn(Vec<FileEntry>) -> Result<Vec<FileEntry>>;
+
+/// Whether a ranking pass reached its candidate limit, and how many candidates matched.
+struct RankedCounts {
+    full: bool,
+    matched: usize,
+}
+
+/// How well content matched, from 0 to 1, given FTS5's bm25 rank: negative, and lower for
+/// better matches.
+fn content_match_score(rank: f64) -> f64 {
+    let strength = (-rank).max(0.0);
+    strength / (1.0 + strength)
+}
+
+/// The runs of characters of `text` that the closest of the query's patterns matched
+/// fuzzily, as character offsets and lengths.
+fn fuzzy_spans(fuzzy: &FuzzyMatcher, text: &str, query: &Query) -> Vec<(usize, usize)> {
+    let Some((_, indices)) = query
+        .patterns()
+        .into_iter()
+        .filter_map(|pattern| fuzzy.fuzzy_indices(text, pattern))
+        .max_by_key(|(score, _)| *score)
+    else {
+        return Vec::new();
+    };
+    let mut spans: Vec<(usize, usize)> = Vec::new();
+    for index in indices {
+        match spans.last_mut() {
+            Some((start, length)) if *start + *length == index => *length += 1,
+            _ => spans.push((index, 1)),
+        }
+    }
+    spans
+}
+
+/// A full-text query for content containing the query's text: any of its patterns, or
+/// every boosted term, each as a phrase so that punctuation in them is not query syntax.
+fn content_phrases(query: &Query) -> String {
+    let phrase = |text: &str| format!("\"{}\"", text.replace('"', "\"\""));
+    if query.alternatives.is_empty() && !query.terms.is_empty() {
+        let terms: Vec<String> = query.terms.iter().map(|term| phrase(&term.text)).collect();
+        terms.join(" AND ")
+    } else {
+        let patterns: Vec<String> = query.patterns().into_iter().map(phrase).collect();
+        patterns.join(" OR ")
+    }
+}
+
+fn longest_glob_literal(pattern: &str) -> Option<String> {
+    normalize_separators(pattern)
+        .split(['*', '?', '[', ']', '{', '}'])
+        .max_by_key(|literal| literal.len())
+        .filter(|literal| !literal.is_empty())
+        .map(str::to_string)
+}
 
 pub struct SearchExecutor {
     database: Arc<Database>,
@@ -15,6 +105,12 @@ pub struct SearchExecutor {
     _cache: Arc<LruCache>,
     _bloom_filter: Arc<FileBloomFilter>,
     ranker: ResultRanker,
+    normalizer: Arc<dyn TextNormalizer>,
+    plans: PlanCache,
+    metrics: Arc<EngineCounters>,
+    content: ContentAnalyzer,
+    sensitive: SensitiveFilter,
+    names: Option<Arc<NameIndex>>,
 }
 
 impl SearchExecutor {
@@ -24,7 +120,13 @@ impl SearchExecutor {
         cache: Arc<LruCache>,
         bloom_filter: Arc<FileBloomFilter>,
     ) -> Self {
-        let ranker = ResultRanker::new(config.fuzzy_threshold);
+        let normalizer = database.normalizer();
+        let ranker =
+            ResultRanker::new(config.fuzzy_threshold).with_normalizer(Arc::clone(&normalizer));
+        let metrics = Arc::new(EngineCounters::new());
+        let plans = PlanCache::new(config.query_plan_cache_size).with_metrics(Arc::clone(&metrics));
+        let content = ContentAnalyzer::from_config(&config);
+        let sensitive = SensitiveFilter::from_config(&config);
 
         Self {
             database,
@@ -32,90 +134,803 @@ impl SearchExecutor {
             _cache: cache,
             _bloom_filter: bloom_filter,
             ranker,
+            normalizer,
+            plans,
+            metrics,
+            content,
+            sensitive,
+            names: None,
         }
     }
 
+    /// Records completed searches into `metrics` instead of a private set of counters.
+    pub fn with_metrics(mut self, metrics: Arc<EngineCounters>) -> Self {
+        self.plans = PlanCache::new(self.config.query_plan_cache_size)
+            .with_metrics(Arc::clone(&metrics));
+        self.metrics = metrics;
+        self
+    }
+
+    /// Answers plain name and path lookups from `names` while it is current.
+    pub fn with_name_index(mut self, names: Arc<NameIndex>) -> Self {
+        self.names = Some(names);
+        self
+    }
+
     pub fn execute(&self, query: &Query) -> Result<Vec<SearchResult>> {
-        if self.config.enable_fuzzy_search && query.match_mode == MatchMode::Fuzzy {
-            return self.execute_fuzzy_search(query);
+        self.execute_traced(query, None)
+    }
+
+    /// Runs `query` in a `search` span carrying `request_id`. The timing, failure and
+    /// slow-query events of the search record the id as a field as well, so they can be
+    /// matched to the request without the span.
+    pub fn execute_traced(
+        &self,
+        query: &Query,
+        request_id: Option<&RequestId>,
+    ) -> Result<Vec<SearchResult>> {
+        self.execute_counted(query, request_id, false, None)
+            .map(|outcome| outcome.results)
+    }
+
+    /// Like [`execute_traced`](Self::execute_traced), also counting every match, including
+    /// those outside the page that `offset` and `max_results` select.
+    pub fn execute_paged(
+        &self,
+        query: &Query,
+        request_id: Option<&RequestId>,
+    ) -> Result<SearchOutcome> {
+        self.execute_counted(query, request_id, true, None)
+    }
+
+    /// Like [`execute_paged`](Self::execute_paged), for a caller shown only the matches
+    /// `visible` keeps, which are the only ones paged and counted.
+    pub fn execute_paged_visible(
+        &self,
+        query: &Query,
+        request_id: Option<&RequestId>,
+        visible: Visibility,
+    ) -> Result<SearchOutcome> {
+        self.execute_counted(query, request_id, true, Some(visible))
+    }
+
+    fn execute_counted(
+        &self,
+        query: &Query,
+        request_id: Option<&RequestId>,
+        count_all: bool,
+        visible: Option<Visibility>,
+    ) -> Result<SearchOutcome> {
+        let request_id = request_id.map(RequestId::as_str);
+        let span = tracing::debug_span!("search", request_id);
+        let _entered = span.enter();
+
+        let _active = self.metrics.begin_operation();
+        let started = Instant::now();
+        let plan = self.cached_plan(query);
+        let outcome = self.run(query, &plan, count_all, visible).map(|mut outcome| {
+            self.attach_context(&mut outcome.results, query);
+            self.attach_name_locations(&mut outcome.results, query, &plan);
+            self.attach_snippets(&mut outcome.results, query);
+            outcome
+        });
+        let elapsed = started.elapsed();
+        let took_ms = elapsed.as_millis() as u64;
+
+        match &outcome {
+            Ok(outcome) => {
+                self.metrics.record_search(query.match_mode, elapsed);
+                let results = outcome.results.len();
+                tracing::debug!(request_id, took_ms, results, "Search finished");
+            }
+            Err(e) => {
+                let error = self.sensitive.redact_error(e);
+                tracing::debug!(request_id, took_ms, error, "Search failed")
+            }
+        }
+        if took_ms >= self.config.slow_query_ms {
+            tracing::warn!(
+                target: SLOW_QUERY_TARGET,
+                request_id,
+                took_ms,
+                mode = ?query.match_mode,
+                pattern = %self.sensitive.redact_path(&query.pattern),
+                "Slow query"
+            );
+        }
+
+        outcome
+    }
+
+    /// Fills in `matches` with the matching lines of each file for queries that search
+    /// content: up to `max_match_locations` of them, or for `context:N` queries up to
+    /// [`MAX_CONTEXT_MATCHES`] with the lines around them. Lines are looked for in the
+    /// file's best matching chunks when the full-text index has them, and in the whole file
+    /// otherwise. A file that can't be read, or is sensitive, keeps no matches.
+    fn attach_context(&self, results: &mut [SearchResult], query: &Query) {
+        let max_matches = match query.context_lines {
+            0 => self.config.max_match_locations,
+            _ => MAX_CONTEXT_MATCHES,
+        };
+        let Some(terms) = self.content_terms(query).filter(|_| max_matches > 0) else {
+            return;
+        };
+        let full_text = match &query.content_pattern {
+            Some(content) => content.clone(),
+            None => query.patterns().join(" OR "),
+        };
+        for result in results.iter_mut().filter(|result| self.readable(result)) {
+            let chunks = result
+                .file
+                .id
+                .and_then(|id| {
+                    self.database
+                        .content_chunk_hits(id, &full_text, self.config.fts_max_chunk_hits)
+                        .ok()
+                })
+                .unwrap_or_default();
+            match self.content.get_line_context_in_chunks(
+                &result.file.path,
+                &terms,
+                query.context_lines,
+                max_matches,
+                &chunks,
+            ) {
+                Ok(matches) => result.matches = matches,
+                Err(e) => tracing::debug!(error = %e, "No match context"),
+            }
+        }
+    }
+
+    /// Puts where the pattern occurs in each result's name, or its path for `scope:path`, in
+    /// front of its `matches`, up to `max_match_locations` occurrences. Searches of content
+    /// alone, and those without a pattern, locate none.
+    fn attach_name_locations(&self, results: &mut [SearchResult], query: &Query, plan: &QueryPlan) {
+        let max = self.config.max_match_locations;
+        if max == 0 || query.pattern.is_empty() || query.scope == SearchScope::Content {
+            return;
+        }
+        // Fuzzy searches match names, whatever the scope. `scope:all` results are located in
+        // their path when their name has no match.
+        let fields: &[MatchField] = match query.scope {
+            _ if plan.fuzzy().is_some() => &[MatchField::Name],
+            SearchScope::Path => &[MatchField::Path],
+            SearchScope::All => &[MatchField::Name, MatchField::Path],
+            _ => &[MatchField::Name],
+        };
+        let matchers = match plan.fuzzy() {
+            Some(_) => Vec::new(),
+            None => match self.pattern_matchers(query, plan) {
+                Ok(matchers) => matchers,
+                Err(e) => {
+                    tracing::debug!(error = %e, "No match locations");
+                    return;
+                }
+            },
+        };
+
+        for result in results.iter_mut() {
+            for &field in fields {
+                let text = match field {
+                    MatchField::Path => path_match_form(&result.file.path),
+                    _ => result.file.name.clone(),
+                };
+                let spans = match plan.fuzzy() {
+                    Some(fuzzy) => fuzzy_spans(fuzzy, &text, query),
+                    None => {
+                        let mut spans: Vec<(usize, usize)> = matchers
+                            .iter()
+                            .flat_map(|matcher| matcher.find_matches(&text))
+                            .filter_map(|(start, len)| {
+                                let matched = text.get(start..start + len)?;
+                                Some((text[..start].chars().count(), matched.chars().count()))
+                            })
+                            .collect();
+                        spans.sort_unstable();
+                        spans.dedup();
+                        spans
+                    }
+                };
+                if spans.is_empty() {
+                    continue;
+                }
+                let locations = spans
+                    .into_iter()
+                    .take(max)
+                    .map(|(offset, length)| MatchLocation::in_text(field, &text, offset, length));
+                result.matches.splice(0..0, locations);
+                break;
+            }
+        }
+    }
+
+    /// Gives each result of a content search a snippet: its first match with
+    /// `snippet_context_chars` characters either side. Files deleted or unreadable since
+    /// they were indexed, and sensitive ones, keep none.
+    fn attach_snippets(&self, results: &mut [SearchResult], query: &Query) {
+        let context_chars = self.config.snippet_context_chars;
+        let Some(terms) = self.content_terms(query).filter(|_| context_chars > 0) else {
+            return;
+        };
+        for result in results.iter_mut().filter(|result| self.readable(result)) {
+            match self.content.get_snippet(&result.file.path, &terms, context_chars) {
+                Ok(snippet) => result.snippet = snippet,
+                Err(e) => tracing::debug!(error = %e, "No snippet"),
+            }
+        }
+    }
+
+    /// The terms a content search looks for in text, or `None` when `query` searches no
+    /// content.
+    fn content_terms(&self, query: &Query) -> Option<String> {
+        let searches_content = query.content_pattern.is_some()
+            || matches!(query.scope, SearchScope::Content | SearchScope::All);
+        if !self.config.enable_content_search || !searches_content {
+            return None;
+        }
+        Some(match &query.content_pattern {
+            Some(content) => content.clone(),
+            None => query.patterns().join(" "),
+        })
+    }
+
+    /// Whether a result's text may be read for context and snippets.
+    fn readable(&self, result: &SearchResult) -> bool {
+        !result.file.is_directory && !self.sensitive.is_sensitive(&result.file.path)
+    }
+
+    /// Builds the pattern-independent part of executing `query` without consulting the cache.
+    pub fn plan(&self, query: &Query) -> QueryPlan {
+        self.build_plan(PlanKey::of(query))
+    }
+
+    /// The plan for `query`, reused from an earlier query with the same structure if cached.
+    pub fn cached_plan(&self, query: &Query) -> Arc<QueryPlan> {
+        self.plans.get_or_build(PlanKey::of(query), |key| self.build_plan(key))
+    }
+
+    fn build_plan(&self, key: PlanKey) -> QueryPlan {
+        let fuzzy = (self.config.enable_fuzzy_search && key.match_mode == MatchMode::Fuzzy)
+            .then(|| {
+                FuzzyMatcher::new(self.config.fuzzy_threshold)
+                    .with_normalizer(Arc::clone(&self.normalizer))
+            });
+        QueryPlan::build(key, self.config.enable_content_search, fuzzy)
+    }
+
+    fn run(
+        &self,
+        query: &Query,
+        plan: &QueryPlan,
+        count_all: bool,
+        visible: Option<Visibility>,
+    ) -> Result<SearchOutcome> {
+        if let Some(fuzzy_matcher) = plan.fuzzy() {
+            if !query.pattern.is_empty() {
+                return self.execute_fuzzy_search(query, plan, fuzzy_matcher, visible);
+            }
         }
 
-        let candidates = self.get_candidates(query)?;
-        let filtered = self.apply_filters(candidates, query)?;
-        let matched = self.apply_matchers(filtered, query)?;
+        let max_results = query.result_limit(self.config.max_search_results)?.effective_limit;
+        // Over-fetch so filters and matchers that reject candidates still leave enough to rank
+        // everything up to the end of the page.
+        let needed = query.offset.saturating_add(max_results);
+        let mut limit = needed.saturating_mul(self.config.candidate_multiplier.max(1));
+        if count_all {
+            limit = limit.max(PAGED_CANDIDATES);
+        }
+
+        let (ranked, counts) = self.ranked(query, plan, limit, needed, visible)?;
+        let mut outcome = Self::page(ranked, counts.matched, query, max_results);
+        // A full window may have left matches unfetched. They are counted in SQL where the
+        // lookup alone decides what matches; otherwise the total is what the window held.
+        if count_all && counts.full {
+            match self.counted_in_sql(query, plan, visible)? {
+                Some(total) => outcome.total_matched = total.max(outcome.total_matched),
+                None => outcome.total_estimated = true,
+            }
+        }
+        Ok(outcome)
+    }
+
+    /// Every match of `query`, counted in SQL, for a plain name search whose `LIKE` lookup
+    /// finds exactly its matches: no filter or matcher rejects any candidate, and the
+    /// pattern holds no `LIKE` wildcard. `None` for any other query.
+    fn counted_in_sql(
+        &self,
+        query: &Query,
+        plan: &QueryPlan,
+        visible: Option<Visibility>,
+    ) -> Result<Option<usize>> {
+        let key = plan.key();
+        let plain = visible.is_none()
+            && *plan.source() == CandidateSource::Name
+            && query.match_mode == MatchMode::CaseInsensitive
+            && query.case_sensitive != Some(true)
+            && query.terms.is_empty()
+            && query.alternatives.is_empty()
+            && query.excluded_terms.is_empty()
+            && query.content_pattern.is_none()
+            && !query.pattern.is_empty()
+            && !query.pattern.contains(['%', '_', ' '])
+            && plan.extensions().is_empty()
+            && plan.excluded_extensions().is_empty()
+            && key.size_filters.is_empty()
+            && key.date_filters.is_empty()
+            && key.languages.is_empty()
+            && key.excluded_kinds.is_empty()
+            && key.mime_filter.is_none()
+            && key.category.is_none()
+            && key.hidden.is_none()
+            && key.depth_filter.is_none()
+            && key.kinds.is_empty()
+            && key.dimensions.is_empty()
+            && key.fields.is_empty()
+            && key.under.is_none();
+        if !plain {
+            return Ok(None);
+        }
+        self.database.count_by_name(&query.pattern).map(Some)
+    }
+
+    /// Ranks the matches among up to `limit` candidates that `visible` keeps, keeping the
+    /// first `keep`, and returns how many candidates were fetched and matched too.
+    fn ranked(
+        &self,
+        query: &Query,
+        plan: &QueryPlan,
+        limit: usize,
+        keep: usize,
+        visible: Option<Visibility>,
+    ) -> Result<(Vec<SearchResult>, RankedCounts)> {
+        let mut full = false;
+        let (candidates, content_ranks) = self.scoped_candidates(query, plan, limit, &mut full)?;
+        let filtered = self.apply_filters(candidates, plan)?;
+        // Without `content:` terms, full-text ranks are those of the pattern itself.
+        let content_hits = content_ranks.as_ref().filter(|_| query.content_pattern.is_none());
+        let matched = self.apply_matchers(filtered, query, plan, content_hits)?;
+
+        let by_field = query.scope == SearchScope::All
+            && query.content_pattern.is_none()
+            && !query.pattern.is_empty();
+        let fields: HashMap<PathBuf, MatchField> = if by_field {
+            matched
+                .iter()
+                .map(|(entry, field)| (entry.path.clone(), *field))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        let mut matched: Vec<FileEntry> = matched.into_iter().map(|(entry, _)| entry).collect();
+        if let Some(visible) = visible {
+            matched = visible(matched)?;
+        }
+        let counts = RankedCounts {
+            full,
+            matched: matched.len(),
+        };
         let results = self.create_search_results(matched, query);
 
-        let ranked = self.ranker.rank(results, &query.pattern);
+        let content_score = |ranks: &ContentRanks, file: &FileEntry| {
+            file.id
+                .and_then(|id| ranks.get(&id))
+                .map_or(0.0, |rank| content_match_score(*rank))
+        };
+        let ranked = match content_ranks {
+            _ if by_field => {
+                let ranks = content_ranks.unwrap_or_default();
+                self.ranker.rank_query_by_field(
+                    results,
+                    query,
+                    keep,
+                    |file| fields.get(&file.path).copied().unwrap_or(MatchField::Name),
+                    |file| content_score(&ranks, file),
+                )
+            }
+            Some(ranks) => self.ranker.rank_query_with_content(results, query, keep, |file| {
+                content_score(&ranks, file)
+            }),
+            None => self.ranker.rank_query(results, query, keep),
+        };
+        Ok((ranked, counts))
+    }
+
+    /// Candidates for the query's pattern. With `content:` terms, only files whose content
+    /// matches them are kept, and their best content match rank is returned by file id, as
+    /// it is for a plain content search.
+    ///
+    /// A plain name search is joined to the full-text matches in SQL; other sources are
+    /// intersected with them by file id.
+    fn scoped_candidates(
+        &self,
+        query: &Query,
+        plan: &QueryPlan,
+        limit: usize,
+        full: &mut bool,
+    ) -> Result<(Vec<FileEntry>, Option<ContentRanks>)> {
+        let Some(content) = &query.content_pattern else {
+            if query.alternatives.is_empty() && *plan.source() == CandidateSource::Content {
+                let matched = self.database.search_content_ranked(&query.pattern, limit)?;
+                *full |= matched.len() >= limit;
+                return Ok(Self::with_ranks(matched));
+            }
+            if *plan.source() == CandidateSource::All && !query.pattern.is_empty() {
+                return self.any_field_candidates(query, limit, full);
+            }
+            return Ok((self.pattern_candidates(query, plan, limit, full)?, None));
+        };
+        if !self.config.enable_content_search {
+            return Ok((Vec::new(), None));
+        }
 
-        let max_results = query
-            .max_results
-            .unwrap_or(self.config.max_search_results);
+        if query.alternatives.is_empty() && *plan.source() == CandidateSource::Name {
+            let matched =
+                self.database
+                    .search_by_name_with_content(query.required_text(), content, limit)?;
+            *full |= matched.len() >= limit;
+            return Ok(Self::with_ranks(matched));
+        }
 
-        Ok(ranked.into_iter().take(max_results).collect())
+        let ranks = self.database.content_match_ranks(content)?;
+        let candidates = self
+            .pattern_candidates(query, plan, limit, full)?
+            .into_iter()
+            .filter(|entry| entry.id.is_some_and(|id| ranks.contains_key(&id)))
+            .collect();
+        Ok((candidates, Some(ranks)))
+    }
+
+    fn with_ranks(matched: Vec<(FileEntry, f64)>) -> (Vec<FileEntry>, Option<ContentRanks>) {
+        let ranks = matched
+            .iter()
+            .filter_map(|(entry, rank)| entry.id.map(|id| (id, *rank)))
+            .collect();
+        let candidates = matched.into_iter().map(|(entry, _)| entry).collect();
+        (candidates, Some(ranks))
     }
 
-    fn get_candidates(&self, query: &Query) -> Result<Vec<FileEntry>> {
-        match query.scope {
-            SearchScope::Name => {
-                if !query.extensions.is_empty() && query.extensions.len() == 1 {
-                    self.database.search_by_extension(
-                        &query.extensions[0],
-                        self.config.max_search_results * 2,
-                    )
-                } else {
-                    self.database.search_by_name(
-                        &query.pattern,
-                        self.config.max_search_results * 2,
-                    )
+    /// Candidates of a `scope:all` search: entries whose name, path or content contains the
+    /// pattern, without duplicates, and the rank of each content match by file id. Glob and
+    /// regex patterns are not text to look for in names or content, so only paths are
+    /// looked up for them.
+    fn any_field_candidates(
+        &self,
+        query: &Query,
+        limit: usize,
+        full: &mut bool,
+    ) -> Result<(Vec<FileEntry>, Option<ContentRanks>)> {
+        let text = !matches!(query.match_mode, MatchMode::Glob | MatchMode::Regex);
+        let mut seen = HashSet::new();
+        let mut candidates = Vec::new();
+        let mut add = |entries: Vec<FileEntry>| {
+            for entry in entries {
+                if seen.insert(entry.path.clone()) {
+                    candidates.push(entry);
                 }
             }
-            SearchScope::Path => self.database.search_by_name(
-                &query.pattern,
-                self.config.max_search_results * 2,
-            ),
-            SearchScope::Content => {
-                if self.config.enable_content_search {
-                    let file_ids = self.database.search_content(
-                        &query.pattern,
-                        self.config.max_search_results * 2,
-                    )?;
-
-                    let mut files = Vec::new();
-                    for id in file_ids {
-                        if let Ok(Some(file)) = self.database.find_by_id(id) {
-                            files.push(file);
-                        }
-                    }
-                    Ok(files)
-                } else {
-                    Ok(Vec::new())
+        };
+
+        if text {
+            add(self.source_candidates(&CandidateSource::Name, query, limit, full)?);
+        }
+        add(self.source_candidates(&CandidateSource::Path, query, limit, full)?);
+        let mut ranks = ContentRanks::new();
+        if text && self.config.enable_content_search {
+            let matched = self.database.search_content_ranked(&content_phrases(query), limit)?;
+            *full |= matched.len() >= limit;
+            let (entries, matched_ranks) = Self::with_ranks(matched);
+            ranks = matched_ranks.unwrap_or_default();
+            add(entries);
+        }
+        Ok((candidates, Some(ranks)))
+    }
+
+    fn pattern_candidates(
+        &self,
+        query: &Query,
+        plan: &QueryPlan,
+        limit: usize,
+        full: &mut bool,
+    ) -> Result<Vec<FileEntry>> {
+        self.source_candidates(plan.source(), query, limit, full)
+    }
+
+    fn source_candidates(
+        &self,
+        source: &CandidateSource,
+        query: &Query,
+        limit: usize,
+        full: &mut bool,
+    ) -> Result<Vec<FileEntry>> {
+        if query.alternatives.is_empty() {
+            self.get_candidates(source, query, limit, full)
+        } else {
+            self.get_alternative_candidates(source, query, limit, full)
+        }
+    }
+
+    /// The page of `results`, already in order, that `query` asks for.
+    fn page(
+        results: Vec<SearchResult>,
+        total_matched: usize,
+        query: &Query,
+        max_results: usize,
+    ) -> SearchOutcome {
+        let results = results.into_iter().skip(query.offset).take(max_results).collect();
+        SearchOutcome {
+            results,
+            total_matched,
+            total_estimated: false,
+        }
+    }
+
+    /// Candidates from `source`, setting `full` if any lookup was cut short by `limit`, which
+    /// may have left matches out even when narrowing leaves fewer than that.
+    fn get_candidates(
+        &self,
+        source: &CandidateSource,
+        query: &Query,
+        limit: usize,
+        full: &mut bool,
+    ) -> Result<Vec<FileEntry>> {
+        let candidates = match source {
+            CandidateSource::Tagged { narrowing: None } => {
+                self.database.find_by_tags(&query.tags, limit)
+            }
+            CandidateSource::Tagged {
+                narrowing: Some(source),
+            } => {
+                let tagged: HashSet<i64> = self
+                    .database
+                    .find_by_tags(&query.tags, i64::MAX as usize)?
+                    .into_iter()
+                    .filter_map(|entry| entry.id)
+                    .collect();
+
+                let mut candidates = self.get_candidates(source, query, limit, full)?;
+                candidates.retain(|entry| entry.id.is_some_and(|id| tagged.contains(&id)));
+                Ok(candidates)
+            }
+            CandidateSource::Dimensioned { narrowing: None } => {
+                self.database.find_by_dimensions(&query.dimensions, limit)
+            }
+            CandidateSource::Dimensioned {
+                narrowing: Some(source),
+            } => {
+                let dimensioned = self.database.image_ids_matching(&query.dimensions)?;
+                let mut candidates = self.get_candidates(source, query, limit, full)?;
+                candidates.retain(|entry| entry.id.is_some_and(|id| dimensioned.contains(&id)));
+                Ok(candidates)
+            }
+            CandidateSource::Fielded { narrowing: None } => {
+                self.database.find_by_document_fields(&query.fields, limit)
+            }
+            CandidateSource::Fielded {
+                narrowing: Some(source),
+            } => {
+                let fielded = self.database.document_field_ids(&query.fields)?;
+                let mut candidates = self.get_candidates(source, query, limit, full)?;
+                candidates.retain(|entry| entry.id.is_some_and(|id| fielded.contains(&id)));
+                Ok(candidates)
+            }
+            CandidateSource::KindsByName => {
+                self.database.search_by_kind(query.required_text(), &query.kinds, limit)
+            }
+            CandidateSource::Kinds => self.database.search_by_kind("", &query.kinds, limit),
+            CandidateSource::ContentKinds { files, contentless } => {
+                let mut candidates = match files {
+                    Some(source) => self.get_candidates(source, query, limit, full)?,
+                    None => Vec::new(),
+                };
+                if !contentless.is_empty() {
+                    candidates.extend(
+                        self.database.search_by_kind(query.required_text(), contentless, limit)?,
+                    );
                 }
+                Ok(candidates)
             }
-            SearchScope::All => self.database.get_all_files(
-                self.config.max_search_results * 2,
-                0,
-            ),
+            CandidateSource::Extension(extension) => {
+                self.database.search_by_extension(extension, limit)
+            }
+            CandidateSource::Mime(filter) => {
+                self.database.search_by_mime(filter, query.required_text(), limit)
+            }
+            CandidateSource::Name => {
+                let text = query.required_text();
+                match self.indexed_candidates(query, NameField::Name, text, limit)? {
+                    Some(candidates) => Ok(candidates),
+                    None => self.database.search_by_name(text, limit),
+                }
+            }
+            CandidateSource::NameUnder => match &query.under {
+                Some(dir) => self.database.search_by_name_under(query.required_text(), dir, limit),
+                None => self.database.search_by_name(query.required_text(), limit),
+            },
+            CandidateSource::Path => self.get_path_candidates(query, limit),
+            // A blob hit expands to every file sharing that content.
+            CandidateSource::Content => self.database.search_content_files(&query.pattern, limit),
+            CandidateSource::All => self.database.get_all_files(limit, 0),
+            CandidateSource::Empty => Ok(Vec::new()),
+        }?;
+        *full |= candidates.len() >= limit;
+        Ok(candidates)
+    }
+
+    /// Candidates for each `OR` alternative, without duplicates.
+    fn get_alternative_candidates(
+        &self,
+        source: &CandidateSource,
+        query: &Query,
+        limit: usize,
+        full: &mut bool,
+    ) -> Result<Vec<FileEntry>> {
+        let mut seen = HashSet::new();
+        let mut candidates = Vec::new();
+        for pattern in &query.alternatives {
+            for entry in self.get_candidates(source, &query.alternative(pattern), limit, full)? {
+                if seen.insert(entry.path.clone()) {
+                    candidates.push(entry);
+                }
+            }
+        }
+        Ok(candidates)
+    }
+
+    // Glob and regex patterns are not substrings; narrow by their longest literal run if any.
+    fn get_path_candidates(&self, query: &Query, limit: usize) -> Result<Vec<FileEntry>> {
+        let literal = match query.match_mode {
+            MatchMode::Regex => None,
+            MatchMode::Glob => longest_glob_literal(&query.pattern),
+            _ => Some(query.required_text().to_string()),
+        };
+
+        let Some(literal) = literal else {
+            return self.database.get_all_files(limit, 0);
+        };
+        match self.indexed_candidates(query, NameField::Path, &literal, limit)? {
+            Some(candidates) => Ok(candidates),
+            None => self.database.search_by_path_contains(&literal, limit),
         }
     }
 
-    fn apply_filters(&self, candidates: Vec<FileEntry>, query: &Query) -> Result<Vec<FileEntry>> {
+    /// The same candidates as the SQL name or path lookup, from the in-memory name index when
+    /// it is current. Queries with content, size or date filters are left to SQLite.
+    fn indexed_candidates(
+        &self,
+        query: &Query,
+        field: NameField,
+        pattern: &str,
+        limit: usize,
+    ) -> Result<Option<Vec<FileEntry>>> {
+        let Some(names) = &self.names else {
+            return Ok(None);
+        };
+        if query.content_pattern.is_some()
+            || !query.size_filters.is_empty()
+            || !query.date_filters.is_empty()
+        {
+            return Ok(None);
+        }
+
+        let ids = names.lookup(field, pattern, limit)?;
+        self.metrics.record_name_index_lookup(ids.is_some());
+        match ids {
+            Some(ids) => Ok(Some(self.database.find_by_ids(&ids)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn apply_filters(
+        &self,
+        candidates: Vec<FileEntry>,
+        plan: &QueryPlan,
+    ) -> Result<Vec<FileEntry>> {
+        let key = plan.key();
+        // A dimensioned source already applied the bounds; other sources are checked here.
+        let dimensioned = match plan.source() {
+            _ if key.dimensions.is_empty() => None,
+            CandidateSource::Dimensioned { .. } => None,
+            _ => Some(self.database.image_ids_matching(&key.dimensions)?),
+        };
+        let fielded = match plan.source() {
+            _ if key.fields.is_empty() => None,
+            CandidateSource::Fielded { .. } => None,
+            _ => Some(self.database.document_field_ids(&key.fields)?),
+        };
+        // Extension-less scripts have only their shebang to say what they are.
+        let scripts = (plan.includes_scripts() || plan.excludes_scripts())
+            .then(|| self.database.script_ids(&[]))
+            .transpose()?;
+        let languages = (!key.languages.is_empty())
+            .then(|| self.database.script_ids(&key.languages))
+            .transpose()?;
+        let stored = |ids: &Option<HashSet<i64>>, entry: &FileEntry| {
+            ids.as_ref()
+                .is_some_and(|ids| entry.id.is_some_and(|id| ids.contains(&id)))
+        };
+
         let filtered = candidates
             .into_iter()
             .filter(|entry| {
-                if !query.extensions.is_empty()
-                    && !apply_extension_filter(entry, &query.extensions)
+                if let Some(ref ids) = dimensioned {
+                    if !entry.id.is_some_and(|id| ids.contains(&id)) {
+                        return false;
+                    }
+                }
+
+                if let Some(ref ids) = fielded {
+                    if !entry.id.is_some_and(|id| ids.contains(&id)) {
+                        return false;
+                    }
+                }
+
+                if !plan.extensions().is_empty()
+                    && !apply_extension_filter(entry, plan.extensions())
+                    && !stored(&scripts, entry)
+                {
+                    return false;
+                }
+
+                if !plan.excluded_extensions().is_empty()
+                    && (apply_extension_filter(entry, plan.excluded_extensions())
+                        || (plan.excludes_scripts() && stored(&scripts, entry)))
+                {
+                    return false;
+                }
+
+                if languages.is_some()
+                    && !apply_language_filter(entry, &key.languages)
+                    && !stored(&languages, entry)
                 {
                     return false;
                 }
 
-                if let Some(ref size_filter) = query.size_filter {
-                    if !apply_size_filter(entry, size_filter) {
+                if !apply_kind_filter(entry, &key.kinds) {
+                    return false;
+                }
+
+                if !key.excluded_kinds.is_empty() && apply_kind_filter(entry, &key.excluded_kinds) {
+                    return false;
+                }
+
+                if !key.size_filters.iter().all(|filter| apply_size_filter(entry, filter)) {
+                    return false;
+                }
+
+                if !key
+                    .date_filters
+                    .iter()
+                    .all(|(field, filter)| apply_date_filter(entry, filter, *field))
+                {
+                    return false;
+                }
+
+                if let Some(ref under) = key.under {
+                    if !entry.path.starts_with(under) {
+                        return false;
+                    }
+                }
+
+                if let Some(ref mime) = key.mime_filter {
+                    if !apply_mime_filter(entry, mime) {
+                        return false;
+                    }
+                }
+
+                if let Some(category) = key.category {
+                    if !apply_category_filter(entry, category) {
+                        return false;
+                    }
+                }
+
+                if let Some(hidden) = key.hidden {
+                    if !hidden.admits(entry.is_hidden) {
                         return false;
                     }
                 }
 
-                if let Some(ref date_filter) = query.date_filter {
-                    if !apply_date_filter(entry, date_filter) {
+                if let Some(depth) = key.depth_filter {
+                    if !depth.contains(depth_below(&entry.path, key.under.as_deref())) {
                         return false;
                     }
                 }
@@ -127,58 +942,177 @@ impl SearchExecutor {
         Ok(filtered)
     }
 
-    fn apply_matchers(&self, candidates: Vec<FileEntry>, query: &Query) -> Result<Vec<FileEntry>> {
-        let matcher = create_matcher(&query.pattern, query.match_mode)?;
+    /// The candidates the pattern matches, each with the field it matched in: the scope's
+    /// own, or for `scope:all` the first of the name, the path and, for `content_hits`, the
+    /// content.
+    fn apply_matchers(
+        &self,
+        candidates: Vec<FileEntry>,
+        query: &Query,
+        plan: &QueryPlan,
+        content_hits: Option<&ContentRanks>,
+    ) -> Result<Vec<(FileEntry, MatchField)>> {
+        let scope_field = match query.scope {
+            SearchScope::Path => MatchField::Path,
+            SearchScope::Content => MatchField::Content,
+            SearchScope::Name | SearchScope::All => MatchField::Name,
+        };
+        if query.pattern.is_empty() && query.excluded_terms.is_empty() {
+            return Ok(candidates.into_iter().map(|entry| (entry, scope_field)).collect());
+        }
+
+        let matchers = self.pattern_matchers(query, plan)?;
+        let prepare = |pattern: &str| {
+            if plan.normalizes_separators() {
+                normalize_separators(pattern)
+            } else {
+                pattern.to_string()
+            }
+        };
+        let excluded = self.exclusion_matchers(query, prepare)?;
+
+        let matches = |text: &str| matchers.iter().all(|matcher| matcher.is_match(text));
+        let content_hit = |entry: &FileEntry| {
+            content_hits.is_some_and(|hits| entry.id.is_some_and(|id| hits.contains_key(&id)))
+        };
 
         let matched = candidates
             .into_iter()
-            .filter(|entry| {
-                match query.scope {
-                    SearchScope::Name => matcher.is_match(&entry.name),
-                    SearchScope::Path => matcher.is_match(&entry.path.to_string_lossy()),
-                    SearchScope::Content => true,
-                    SearchScope::All => matcher.is_match(&entry.name),
+            .filter_map(|entry| {
+                let text = match query.scope {
+                    SearchScope::Path => Cow::Owned(path_match_form(&entry.path)),
+                    _ => Cow::Borrowed(entry.name.as_str()),
+                };
+                if excluded.iter().any(|matcher| matcher.is_match(&text)) {
+                    return None;
                 }
+                let field = match query.scope {
+                    // Content matches come from the full-text index; only exclusions apply here.
+                    SearchScope::Content => Some(scope_field),
+                    SearchScope::All if matches(&text) => Some(MatchField::Name),
+                    SearchScope::All if matches(&path_match_form(&entry.path)) => {
+                        Some(MatchField::Path)
+                    }
+                    SearchScope::All => content_hit(&entry).then_some(MatchField::Content),
+                    _ => matches(&text).then_some(scope_field),
+                };
+                field.map(|field| (entry, field))
             })
             .collect();
 
         Ok(matched)
     }
 
-    fn execute_fuzzy_search(&self, query: &Query) -> Result<Vec<SearchResult>> {
-        let fuzzy_matcher = FuzzyMatcher::new(self.config.fuzzy_threshold);
-        let mut all_files = self.database.get_all_files(10000, 0)?;
+    /// Matchers that a name, or a path for `scope:path`, has to satisfy every one of.
+    fn pattern_matchers(&self, query: &Query, plan: &QueryPlan) -> Result<Vec<Arc<dyn Matcher>>> {
+        let prepare = |pattern: &str| {
+            if plan.normalizes_separators() {
+                normalize_separators(pattern)
+            } else {
+                pattern.to_string()
+            }
+        };
+        let create = |pattern: &str| {
+            create_normalized_matcher(
+                &prepare(pattern),
+                query.match_mode,
+                query.case_sensitive,
+                Arc::clone(&self.normalizer),
+            )
+        };
+        // Boosted terms each have to match, wherever they occur; of `OR` alternatives, any one.
+        let matchers = if query.pattern.is_empty() {
+            Vec::new()
+        } else if !query.alternatives.is_empty() {
+            let alternatives = query
+                .alternatives
+                .iter()
+                .map(|pattern| create(pattern))
+                .collect::<Result<Vec<_>>>()?;
+            vec![Arc::new(CompositeMatcher::or(alternatives)) as Arc<dyn Matcher>]
+        } else if query.terms.is_empty() {
+            vec![create(&query.pattern)?]
+        } else {
+            query
+                .terms
+                .iter()
+                .map(|term| create(&term.text))
+                .collect::<Result<Vec<_>>>()?
+        };
+        Ok(matchers)
+    }
 
-        if !query.extensions.is_empty() {
-            all_files.retain(|f| apply_extension_filter(f, &query.extensions));
-        }
+    /// Case-insensitive substring matchers for the query's `-term` exclusions, whatever its
+    /// match mode.
+    fn exclusion_matchers(
+        &self,
+        query: &Query,
+        prepare: impl Fn(&str) -> String,
+    ) -> Result<Vec<Arc<dyn Matcher>>> {
+        query
+            .excluded_terms
+            .iter()
+            .map(|term| {
+                create_normalized_matcher(
+                    &prepare(term),
+                    MatchMode::CaseInsensitive,
+                    None,
+                    Arc::clone(&self.normalizer),
+                )
+            })
+            .collect()
+    }
 
-        if let Some(ref size_filter) = query.size_filter {
-            all_files.retain(|f| apply_size_filter(f, size_filter));
+    fn execute_fuzzy_search(
+        &self,
+        query: &Query,
+        plan: &QueryPlan,
+        fuzzy_matcher: &FuzzyMatcher,
+        visible: Option<Visibility>,
+    ) -> Result<SearchOutcome> {
+        let all_files = if query.tags.is_empty() {
+            self.database.get_all_files(FUZZY_CANDIDATES, 0)?
+        } else {
+            self.database.find_by_tags(&query.tags, FUZZY_CANDIDATES)?
+        };
+        let full = all_files.len() >= FUZZY_CANDIDATES;
+        let mut all_files = self.apply_filters(all_files, plan)?;
+        if let Some(content) = &query.content_pattern {
+            let ranks = if self.config.enable_content_search {
+                self.database.content_match_ranks(content)?
+            } else {
+                HashMap::new()
+            };
+            all_files.retain(|entry| entry.id.is_some_and(|id| ranks.contains_key(&id)));
         }
-
-        if let Some(ref date_filter) = query.date_filter {
-            all_files.retain(|f| apply_date_filter(f, date_filter));
+        if let Some(visible) = visible {
+            all_files = visible(all_files)?;
         }
+        let excluded = self.exclusion_matchers(query, str::to_string)?;
 
         let mut scored_results: Vec<(FileEntry, i64)> = all_files
             .into_iter()
+            .filter(|entry| !excluded.iter().any(|matcher| matcher.is_match(&entry.name)))
             .filter_map(|entry| {
-                fuzzy_matcher
-                    .fuzzy_match_with_threshold(&entry.name, &query.pattern)
+                // Of `OR` alternatives, the closest one counts.
+                query
+                    .patterns()
+                    .into_iter()
+                    .filter_map(|pattern| {
+                        fuzzy_matcher.fuzzy_match_with_threshold(&entry.name, pattern)
+                    })
+                    .max()
                     .map(|score| (entry, score))
             })
             .collect();
 
-        scored_results.sort_by(|a, b| b.1.cmp(&a.1));
+        scored_results.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
 
-        let max_results = query
-            .max_results
-            .unwrap_or(self.config.max_search_results);
+        let max_results = query.result_limit(self.config.max_search_results)?.effective_limit;
 
-        let results: Vec<SearchResult> = scored_results
+        let total = scored_results.len();
+        let mut results: Vec<SearchResult> = scored_results
             .into_iter()
-            .take(max_results)
             .map(|(file, score)| SearchResult {
                 file,
                 score: score as f64 / 100.0,
@@ -186,8 +1120,13 @@ impl SearchExecutor {
                 matches: vec![],
             })
             .collect();
+        if let Some(sort) = query.sort_by {
+            sort.sort(&mut results);
+        }
 
-        Ok(results)
+        let mut outcome = Self::page(results, total, query, max_results);
+        outcome.total_estimated = full;
+        Ok(outcome)
     }
 
     fn create_search_results(&self, files: Vec<FileEntry>, _query: &Query) -> Vec<SearchResult> {
@@ -211,9 +1150,11 @@ impl SearchExecutor {
 mod tests {
     use super::*;
     use crate::core::config::SearchConfig;
+    use crate::core::types::{EntryKind, ImageDimensions, SizeFilter};
     use crate::filters::ExclusionFilter;
     use crate::indexer::builder::IndexBuilder;
     use std::fs;
+    use std::path::{Path, PathBuf};
     use tempfile::TempDir;
 
     #[test]
@@ -277,4 +1218,930 @@ mod tests {
         assert_eq!(results.len(), 1, "Expected exactly one search result");
         assert_eq!(results[0].file.name, "file2.rs");
     }
+
+    fn seed_kinds() -> SearchExecutor {
+        let db = Arc::new(Database::in_memory(10).unwrap());
+
+        let mut backup_dir = FileEntry::new(PathBuf::from("/data/backup"));
+        backup_dir.is_directory = true;
+        let backup_file = FileEntry::new(PathBuf::from("/data/backup.tar"));
+        let mut backup_link = FileEntry::new(PathBuf::from("/data/backup-latest"));
+        backup_link.is_symlink = true;
+        let mut etc_link = FileEntry::new(PathBuf::from("/etc/localtime"));
+        etc_link.is_symlink = true;
+
+        db.insert_files_batch(&[backup_dir, backup_file, backup_link, etc_link])
+            .unwrap();
+
+        SearchExecutor::new(
+            db,
+            Arc::new(SearchConfig::default()),
+            Arc::new(LruCache::new(100)),
+            Arc::new(FileBloomFilter::default()),
+        )
+    }
+
+    fn names(executor: &SearchExecutor, input: &str) -> Vec<String> {
+        let query = crate::search::QueryParser::parse(input).unwrap();
+        let mut names: Vec<String> = executor
+            .execute(&query)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.file.name)
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_entry_kind_filters() {
+        let executor = seed_kinds();
+
+        assert_eq!(names(&executor, "is:dir backup"), vec!["backup"]);
+        assert_eq!(names(&executor, "is:file backup"), vec!["backup.tar"]);
+        assert_eq!(names(&executor, "is:symlink backup"), vec!["backup-latest"]);
+        assert_eq!(
+            names(&executor, "is:dir,symlink backup"),
+            vec!["backup", "backup-latest"]
+        );
+        assert_eq!(names(&executor, "is:symlink"), vec!["backup-latest", "localtime"]);
+        assert_eq!(names(&executor, "is:dir backup scope:path"), vec!["backup"]);
+        assert_eq!(names(&executor, "is:dir backup scope:content"), vec!["backup"]);
+        assert_eq!(names(&executor, "is:symlink scope:all"), vec!["backup-latest", "localtime"]);
+        assert_eq!(names(&executor, "is:dir backup mode:fuzzy"), vec!["backup"]);
+    }
+
+    #[test]
+    fn test_under_filter_matches_whole_path_components() {
+        let executor = seed_kinds();
+
+        assert_eq!(
+            names(&executor, "under:/data backup"),
+            vec!["backup", "backup-latest", "backup.tar"]
+        );
+        assert_eq!(names(&executor, "under:/data/backup backup"), vec!["backup"]);
+        assert_eq!(names(&executor, "under:/data/back backup"), Vec::<String>::new());
+        assert_eq!(names(&executor, "is:symlink under:/etc"), vec!["localtime"]);
+    }
+
+    #[test]
+    fn test_depth_filter_counts_from_the_search_root() {
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let entries: Vec<FileEntry> = [
+            "/work/app/README.md",
+            "/work/app/docs/README.md",
+            "/work/app/vendor/lib/dep/README.md",
+        ]
+        .iter()
+        .map(|path| FileEntry::new(PathBuf::from(path)))
+        .collect();
+        db.insert_files_batch(&entries).unwrap();
+        let executor = SearchExecutor::new(
+            db,
+            Arc::new(SearchConfig::default()),
+            Arc::new(LruCache::new(100)),
+            Arc::new(FileBloomFilter::default()),
+        );
+        let paths = |input: &str| {
+            let query = crate::search::QueryParser::parse(input).unwrap();
+            let mut paths: Vec<PathBuf> =
+                executor.execute(&query).unwrap().into_iter().map(|r| r.file.path).collect();
+            paths.sort();
+            paths
+        };
+
+        assert_eq!(
+            paths("readme in:/work/app depth:<3"),
+            vec![
+                PathBuf::from("/work/app/README.md"),
+                PathBuf::from("/work/app/docs/README.md"),
+            ]
+        );
+        assert_eq!(
+            paths("readme in:/work/app depth:1"),
+            vec![PathBuf::from("/work/app/README.md")]
+        );
+        assert_eq!(
+            paths("readme depth:>5"),
+            vec![PathBuf::from("/work/app/vendor/lib/dep/README.md")]
+        );
+        assert_eq!(paths("readme depth:3").len(), 1);
+    }
+
+    #[test]
+    fn test_in_filter_is_not_crowded_out_by_matches_elsewhere() {
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let mut entries: Vec<FileEntry> = (0..50)
+            .map(|i| FileEntry::new(PathBuf::from(format!("/projectB/crate{}/main.rs", i))))
+            .collect();
+        entries.push(FileEntry::new(PathBuf::from("/projectA/src/main.rs")));
+        db.insert_files_batch(&entries).unwrap();
+        let executor = SearchExecutor::new(
+            db,
+            Arc::new(SearchConfig::default()),
+            Arc::new(LruCache::new(100)),
+            Arc::new(FileBloomFilter::default()),
+        );
+
+        let query = crate::search::QueryParser::parse("main.rs in:/projectA limit:1").unwrap();
+        let results = executor.execute(&query).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].file.path, PathBuf::from("/projectA/src/main.rs"));
+    }
+
+    #[test]
+    fn test_filters_alone_find_every_entry_they_admit() {
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let entries: Vec<FileEntry> = [
+            "/proj/src/main.rs",
+            "/proj/src/lib.rs",
+            "/proj/README.md",
+            "/proj/.env",
+            "/other/notes.txt",
+        ]
+        .iter()
+        .map(|path| {
+            let mut entry = FileEntry::new(PathBuf::from(path));
+            entry.is_hidden = entry.name.starts_with('.');
+            entry
+        })
+        .collect();
+        db.insert_files_batch(&entries).unwrap();
+        let executor = SearchExecutor::new(
+            db,
+            Arc::new(SearchConfig::default()),
+            Arc::new(LruCache::new(100)),
+            Arc::new(FileBloomFilter::default()),
+        );
+
+        assert_eq!(names(&executor, "category:source"), vec!["lib.rs", "main.rs"]);
+        assert_eq!(names(&executor, "hidden:only"), vec![".env"]);
+        assert_eq!(
+            names(&executor, "in:/proj"),
+            vec![".env", "README.md", "lib.rs", "main.rs"]
+        );
+        assert_eq!(names(&executor, "in:/proj depth:1"), vec![".env", "README.md"]);
+        assert_eq!(names(&executor, "depth:<3"), vec![".env", "README.md", "notes.txt"]);
+    }
+
+    #[test]
+    fn test_mime_filter_matches_exact_types_and_prefixes() {
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let entries: Vec<FileEntry> = [
+            ("/docs/report.pdf", Some("application/pdf")),
+            ("/img/report.png", Some("image/png")),
+            ("/img/logo.svg", Some("image/svg+xml")),
+            ("/data/report.bin", None),
+        ]
+        .iter()
+        .map(|(path, mime)| {
+            let mut entry = FileEntry::new(PathBuf::from(path));
+            entry.mime_type = mime.map(str::to_string);
+            entry
+        })
+        .collect();
+        db.insert_files_batch(&entries).unwrap();
+        let executor = SearchExecutor::new(
+            db,
+            Arc::new(SearchConfig::default()),
+            Arc::new(LruCache::new(100)),
+            Arc::new(FileBloomFilter::default()),
+        );
+
+        assert_eq!(names(&executor, "mime:application/pdf"), vec!["report.pdf"]);
+        assert_eq!(names(&executor, "mime:image/*"), vec!["logo.svg", "report.png"]);
+        assert_eq!(names(&executor, "report mime:IMAGE/*"), vec!["report.png"]);
+        assert_eq!(names(&executor, "report mime:image/* scope:path"), vec!["report.png"]);
+        assert_eq!(names(&executor, "ext:png mime:image/*"), vec!["report.png"]);
+        assert_eq!(names(&executor, "report mime:*"), vec!["report.pdf", "report.png"]);
+        assert_eq!(names(&executor, "mime:image/%"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_repeated_size_filters_must_all_hold() {
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let entries: Vec<FileEntry> = [
+            ("/v/small.mp4", 1u64),
+            ("/v/medium.mp4", 50),
+            ("/v/large.mp4", 500),
+        ]
+        .iter()
+        .map(|(path, mb)| {
+            let mut entry = FileEntry::new(PathBuf::from(path));
+            entry.size = mb * 1024 * 1024;
+            entry
+        })
+        .collect();
+        db.insert_files_batch(&entries).unwrap();
+        let executor = SearchExecutor::new(
+            db,
+            Arc::new(SearchConfig::default()),
+            Arc::new(LruCache::new(100)),
+            Arc::new(FileBloomFilter::default()),
+        );
+
+        assert_eq!(names(&executor, "mp4 size:>1MB size:<100MB"), vec!["medium.mp4"]);
+        assert_eq!(names(&executor, "mp4 size:<100MB size:>=1MB").len(), 2);
+        assert_eq!(names(&executor, "mp4 size:>10MB size:<1MB"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_case_flag_applies_to_each_mode() {
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let entries: Vec<FileEntry> = ["/d/Report.TXT", "/d/report.txt"]
+            .iter()
+            .map(|path| FileEntry::new(PathBuf::from(path)))
+            .collect();
+        db.insert_files_batch(&entries).unwrap();
+        let executor = SearchExecutor::new(
+            db,
+            Arc::new(SearchConfig::default()),
+            Arc::new(LruCache::new(100)),
+            Arc::new(FileBloomFilter::default()),
+        );
+
+        for (query, expected) in [
+            ("Report", 2),
+            ("Report case:sensitive", 1),
+            ("report mode:exact", 1),
+            ("REPORT mode:exact case:insensitive", 2),
+            ("^/d/report mode:regex scope:path", 1),
+            ("^/d/report mode:regex scope:path case:insensitive", 2),
+            ("*.txt mode:glob scope:path", 1),
+            ("*.txt mode:glob scope:path case:insensitive", 2),
+            ("reprt mode:fuzzy case:sensitive", 2),
+        ] {
+            assert_eq!(names(&executor, query).len(), expected, "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_category_filter_uses_extension_categories() {
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let entries: Vec<FileEntry> = [
+            "/img/report.png",
+            "/src/report.rs",
+            "/docs/report",
+            "/docs/report.xyz",
+        ]
+        .iter()
+        .map(|path| FileEntry::new(PathBuf::from(path)))
+        .collect();
+        db.insert_files_batch(&entries).unwrap();
+        let executor = SearchExecutor::new(
+            db,
+            Arc::new(SearchConfig::default()),
+            Arc::new(LruCache::new(100)),
+            Arc::new(FileBloomFilter::default()),
+        );
+
+        assert_eq!(names(&executor, "report category:image"), vec!["report.png"]);
+        assert_eq!(names(&executor, "report category:source"), vec!["report.rs"]);
+        assert_eq!(names(&executor, "report category:other"), vec!["report", "report.xyz"]);
+        assert!(names(&executor, "report category:document").is_empty());
+    }
+
+    #[test]
+    fn test_hidden_filter_includes_excludes_or_keeps_only_hidden() {
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let entries: Vec<FileEntry> = ["/home/.bashrc", "/home/bashrc.bak", "/home/.config/bashrc"]
+            .iter()
+            .map(|path| {
+                let mut entry = FileEntry::new(PathBuf::from(path));
+                entry.is_hidden = entry.name.starts_with('.');
+                entry
+            })
+            .collect();
+        db.insert_files_batch(&entries).unwrap();
+        let executor = SearchExecutor::new(
+            db,
+            Arc::new(SearchConfig::default()),
+            Arc::new(LruCache::new(100)),
+            Arc::new(FileBloomFilter::default()),
+        );
+
+        let all = vec![".bashrc", "bashrc", "bashrc.bak"];
+        assert_eq!(names(&executor, "bashrc"), all);
+        assert_eq!(names(&executor, "bashrc hidden:true"), all);
+        assert_eq!(names(&executor, "bashrc hidden:false"), vec!["bashrc", "bashrc.bak"]);
+        assert_eq!(names(&executor, "bashrc hidden:only"), vec![".bashrc"]);
+    }
+
+    #[test]
+    fn test_negations_exclude_entries() {
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let mut entries: Vec<FileEntry> = [
+            "/docs/report.txt",
+            "/docs/report.tmp",
+            "/docs/Report-DRAFT.txt",
+            "/docs/report.rs",
+            "/docs/reports",
+        ]
+        .iter()
+        .map(|path| FileEntry::new(PathBuf::from(path)))
+        .collect();
+        entries[4].is_directory = true;
+        db.insert_files_batch(&entries).unwrap();
+        let executor = SearchExecutor::new(
+            db,
+            Arc::new(SearchConfig::default()),
+            Arc::new(LruCache::new(100)),
+            Arc::new(FileBloomFilter::default()),
+        );
+
+        assert_eq!(
+            names(&executor, "report -ext:tmp -draft"),
+            vec!["report.rs", "report.txt", "reports"]
+        );
+        assert_eq!(
+            names(&executor, "report !ext:code,tmp !is:dir"),
+            vec!["Report-DRAFT.txt", "report.txt"]
+        );
+        assert_eq!(
+            names(&executor, "report -Draft mode:fuzzy"),
+            vec!["report.rs", "report.tmp", "report.txt", "reports"]
+        );
+        assert_eq!(
+            names(&executor, "docs -draft -tmp scope:path"),
+            vec!["report.rs", "report.txt", "reports"]
+        );
+    }
+
+    #[test]
+    fn test_term_boosts_decide_ranking() {
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let entries: Vec<FileEntry> = [
+            "/docs/report_draft.txt",
+            "/docs/draft_report.txt",
+            "/docs/report.txt",
+        ]
+        .iter()
+        .map(|path| FileEntry::new(PathBuf::from(path)))
+        .collect();
+        db.insert_files_batch(&entries).unwrap();
+        let executor = SearchExecutor::new(
+            db,
+            Arc::new(SearchConfig::default()),
+            Arc::new(LruCache::new(100)),
+            Arc::new(FileBloomFilter::default()),
+        );
+        let ranked = |input: &str| -> Vec<String> {
+            let query = crate::search::QueryParser::parse(input).unwrap();
+            executor
+                .execute(&query)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.file.name)
+                .collect()
+        };
+
+        assert_eq!(
+            ranked("report^2 draft^0.5"),
+            vec!["report_draft.txt", "draft_report.txt"]
+        );
+        assert_eq!(
+            ranked("report^0.5 draft^2"),
+            vec!["draft_report.txt", "report_draft.txt"]
+        );
+        assert!(ranked("report draft").is_empty());
+    }
+
+    #[test]
+    fn test_explicit_sort_applies_before_the_limit() {
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let entries: Vec<FileEntry> = [("a_log.txt", 300), ("b_log.txt", 100), ("c_log.txt", 200)]
+            .iter()
+            .map(|(name, size)| {
+                let mut entry = FileEntry::new(PathBuf::from("/logs").join(name));
+                entry.size = *size;
+                entry
+            })
+            .collect();
+        db.insert_files_batch(&entries).unwrap();
+        let executor = SearchExecutor::new(
+            db,
+            Arc::new(SearchConfig::default()),
+            Arc::new(LruCache::new(100)),
+            Arc::new(FileBloomFilter::default()),
+        );
+        let sizes = |input: &str| -> Vec<u64> {
+            let query = crate::search::QueryParser::parse(input).unwrap();
+            executor
+                .execute(&query)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.file.size)
+                .collect()
+        };
+
+        assert_eq!(sizes("log sort:-size limit:2"), vec![300, 200]);
+        assert_eq!(sizes("log sort:size"), vec![100, 200, 300]);
+    }
+
+    #[test]
+    fn test_pages_split_the_ranked_results_and_count_every_match() {
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let mut entries: Vec<FileEntry> = (0..10)
+            .map(|i| {
+                let mut entry = FileEntry::new(PathBuf::from(format!("/logs/log_{}.txt", i)));
+                entry.size = 100 + i;
+                entry
+            })
+            .collect();
+        entries.push(FileEntry::new(PathBuf::from("/logs/log_script.rs")));
+        db.insert_files_batch(&entries).unwrap();
+        // No over-fetch: the pages still agree, as a paged search ranks a wider window.
+        let config = SearchConfig {
+            candidate_multiplier: 1,
+            ..Default::default()
+        };
+        let executor = SearchExecutor::new(
+            db,
+            Arc::new(config),
+            Arc::new(LruCache::new(100)),
+            Arc::new(FileBloomFilter::default()),
+        );
+        let page = |offset: usize| {
+            let input = format!("log ext:txt sort:size limit:4 offset:{}", offset);
+            let query = crate::search::QueryParser::parse(&input).unwrap();
+            executor.execute_paged(&query, None).unwrap()
+        };
+
+        let mut sizes = Vec::new();
+        for offset in [0, 4, 8, 12] {
+            let outcome = page(offset);
+            assert_eq!(outcome.total_matched, 10, "offset {}", offset);
+            sizes.extend(outcome.results.iter().map(|r| r.file.size));
+        }
+        assert_eq!(sizes, (100..110).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn test_totals_past_the_paged_window_are_counted_or_estimated() {
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let entries: Vec<FileEntry> = (0..PAGED_CANDIDATES + 200)
+            .map(|i| FileEntry::new(PathBuf::from(format!("/notes/note_{}.txt", i))))
+            .collect();
+        db.insert_files_batch(&entries).unwrap();
+        let executor = SearchExecutor::new(
+            db,
+            Arc::new(SearchConfig::default()),
+            Arc::new(LruCache::new(100)),
+            Arc::new(FileBloomFilter::default()),
+        );
+        let paged = |input: &str| {
+            let query = crate::search::QueryParser::parse(input).unwrap();
+            executor.execute_paged(&query, None).unwrap()
+        };
+
+        // A plain name search is counted in SQL.
+        let outcome = paged("note limit:10 offset:20");
+        assert_eq!(outcome.results.len(), 10);
+        assert_eq!(outcome.total_matched, PAGED_CANDIDATES + 200);
+        assert!(!outcome.total_estimated);
+
+        // A filter can reject candidates, so past the window the total is an estimate.
+        let outcome = paged("note ext:txt limit:10");
+        assert_eq!(outcome.results.len(), 10);
+        assert_eq!(outcome.total_matched, PAGED_CANDIDATES);
+        assert!(outcome.total_estimated);
+
+        // Within the window, every match is counted.
+        let outcome = paged("note_10 ext:txt");
+        assert_eq!(outcome.total_matched, 111);
+        assert!(!outcome.total_estimated);
+    }
+
+    #[test]
+    fn test_or_alternatives_share_filters_and_rank_by_best_match() {
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let entries: Vec<FileEntry> = [
+            "/docs/invoice.pdf",
+            "/docs/receipt.pdf",
+            "/docs/old_invoice_scan_receipt.pdf",
+            "/docs/receipt.txt",
+            "/docs/report.pdf",
+        ]
+        .iter()
+        .map(|path| FileEntry::new(PathBuf::from(path)))
+        .collect();
+        db.insert_files_batch(&entries).unwrap();
+        let executor = SearchExecutor::new(
+            db,
+            Arc::new(SearchConfig::default()),
+            Arc::new(LruCache::new(100)),
+            Arc::new(FileBloomFilter::default()),
+        );
+
+        assert_eq!(
+            names(&executor, "invoice OR receipt ext:pdf"),
+            vec!["invoice.pdf", "old_invoice_scan_receipt.pdf", "receipt.pdf"]
+        );
+        assert_eq!(
+            names(&executor, "invoice | receipt -scan"),
+            vec!["invoice.pdf", "receipt.pdf", "receipt.txt"]
+        );
+        assert_eq!(
+            names(&executor, "invoic OR recept ext:pdf mode:fuzzy"),
+            vec!["invoice.pdf", "old_invoice_scan_receipt.pdf", "receipt.pdf"]
+        );
+        assert_eq!(
+            names(&executor, "docs/invoice OR docs/report scope:path"),
+            vec!["invoice.pdf", "report.pdf"]
+        );
+
+        let query = crate::search::QueryParser::parse("receipt OR invoice ext:pdf").unwrap();
+        let results = executor.execute(&query).unwrap();
+        // Each file scores by the alternative it matches best, so both exact names lead.
+        let score = |name: &str| results.iter().find(|r| r.file.name == name).unwrap().score;
+        assert!(score("invoice.pdf") > score("old_invoice_scan_receipt.pdf"));
+        assert!(score("receipt.pdf") > score("old_invoice_scan_receipt.pdf"));
+        assert_eq!(results[2].file.name, "old_invoice_scan_receipt.pdf");
+    }
+
+    #[test]
+    fn test_dimension_filters_seed_candidates() {
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let entries: Vec<FileEntry> = ["/photos/beach.jpg", "/photos/icon.png", "/photos/pano.jpg"]
+            .iter()
+            .map(|path| FileEntry::new(PathBuf::from(path)))
+            .collect();
+        db.insert_files_batch(&entries).unwrap();
+        let id = |name: &str| db.search_by_name(name, 10).unwrap()[0].id.unwrap();
+        let size = |width, height| ImageDimensions { width, height };
+        db.set_image_dimensions(&[
+            (id("beach"), size(4032, 3024)),
+            (id("icon"), size(64, 64)),
+            (id("pano"), size(12000, 2000)),
+        ])
+        .unwrap();
+        db.tag_files(&[id("pano")], "trip").unwrap();
+
+        let executor = SearchExecutor::new(
+            Arc::clone(&db),
+            Arc::new(SearchConfig::default()),
+            Arc::new(LruCache::new(100)),
+            Arc::new(FileBloomFilter::default()),
+        );
+
+        assert_eq!(names(&executor, "width:>4000"), vec!["beach.jpg", "pano.jpg"]);
+        assert_eq!(names(&executor, "width:>4000 height:>2500"), vec!["beach.jpg"]);
+        assert_eq!(names(&executor, "height:64"), vec!["icon.png"]);
+        assert_eq!(names(&executor, "pano width:10000..13000"), vec!["pano.jpg"]);
+        assert_eq!(names(&executor, "tag:trip height:<2500"), vec!["pano.jpg"]);
+        assert!(names(&executor, "tag:trip height:>2500").is_empty());
+        assert_eq!(names(&executor, "bech width:>100 mode:fuzzy"), vec!["beach.jpg"]);
+    }
+
+    #[test]
+    fn test_tag_filters_seed_candidates() {
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let entries: Vec<FileEntry> = ["/tax/invoice-jan.pdf", "/tax/invoice-feb.pdf", "/tax/w.pdf"]
+            .iter()
+            .map(|path| FileEntry::new(PathBuf::from(path)))
+            .collect();
+        db.insert_files_batch(&entries).unwrap();
+        let ids: Vec<i64> = db
+            .search_by_name("invoice", 10)
+            .unwrap()
+            .into_iter()
+            .filter_map(|entry| entry.id)
+            .collect();
+        db.tag_files(&ids, "tax-2023").unwrap();
+        let form = db.search_by_name("w.pdf", 10).unwrap()[0].id.unwrap();
+        db.tag_files(&[form], "forms").unwrap();
+
+        let executor = SearchExecutor::new(
+            db,
+            Arc::new(SearchConfig::default()),
+            Arc::new(LruCache::new(100)),
+            Arc::new(FileBloomFilter::default()),
+        );
+
+        assert_eq!(names(&executor, "tag:tax-2023"), vec!["invoice-feb.pdf", "invoice-jan.pdf"]);
+        assert_eq!(names(&executor, "jan tag:tax-2023"), vec!["invoice-jan.pdf"]);
+        assert_eq!(names(&executor, "tag:tax-2023,forms ext:pdf").len(), 3);
+        assert_eq!(names(&executor, "invoce tag:tax-2023 mode:fuzzy").len(), 2);
+        assert!(names(&executor, "tag:unknown").is_empty());
+    }
+
+    fn seed_paths() -> SearchExecutor {
+        let db = Arc::new(Database::in_memory(10).unwrap());
+
+        // Windows-style entries are built directly so the case is exercised on every platform.
+        let entries: Vec<FileEntry> = [
+            r"C:\repo\src\server\main.rs",
+            r"C:\repo\src\cli\main.rs",
+            "/repo/src/server/api.rs",
+            "/repo/docs/server.md",
+            "/repo/100%_done/notes.txt",
+            "/repo/100x/notes.txt",
+        ]
+        .iter()
+        .map(|path| FileEntry::new(PathBuf::from(path)))
+        .collect();
+        db.insert_files_batch(&entries).unwrap();
+
+        SearchExecutor::new(
+            db,
+            Arc::new(SearchConfig::default()),
+            Arc::new(LruCache::new(100)),
+            Arc::new(FileBloomFilter::default()),
+        )
+    }
+
+    fn paths(executor: &SearchExecutor, input: &str) -> Vec<String> {
+        let query = crate::search::QueryParser::parse(input).unwrap();
+        let mut paths: Vec<String> = executor
+            .execute(&query)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.file.path.to_string_lossy().into_owned())
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn test_path_scope_matches_normalized_separators() {
+        let executor = seed_paths();
+        let expected = vec![
+            "/repo/src/server/api.rs".to_string(),
+            r"C:\repo\src\server\main.rs".to_string(),
+        ];
+
+        assert_eq!(paths(&executor, "src/server scope:path"), expected);
+        assert_eq!(paths(&executor, r"src\server scope:path"), expected);
+        assert_eq!(paths(&executor, "**/server/*.rs mode:glob scope:path"), expected);
+        assert_eq!(paths(&executor, r"server/[a-z]+\.rs$ mode:regex scope:path"), expected);
+        assert_eq!(
+            paths(&executor, "is:file src/cli scope:path"),
+            vec![r"C:\repo\src\cli\main.rs".to_string()]
+        );
+    }
+
+    fn result_summary(executor: &SearchExecutor, query: &Query) -> Vec<(String, f64)> {
+        executor
+            .execute(query)
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.file.path.to_string_lossy().into_owned(), r.score))
+            .collect()
+    }
+
+    #[test]
+    fn test_cached_plans_match_direct_execution() {
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let mut entries: Vec<FileEntry> = [
+            "/notes/todo.md",
+            "/notes/Notes.TXT",
+            "/notes/archive/old-notes.md",
+            "/src/notes/parser.rs",
+            "/src/main.rs",
+            "/photos/note.png",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, path)| {
+            let mut entry = FileEntry::new(PathBuf::from(path));
+            entry.size = i as u64 * 10;
+            entry
+        })
+        .collect();
+        let mut dir = FileEntry::new(PathBuf::from("/notes/archive"));
+        dir.is_directory = true;
+        entries.push(dir);
+        db.insert_files_batch(&entries).unwrap();
+        let tagged: Vec<i64> = db
+            .search_by_name("notes", 10)
+            .unwrap()
+            .into_iter()
+            .filter_map(|entry| entry.id)
+            .collect();
+        db.tag_files(&tagged, "work").unwrap();
+
+        let executor = |plan_cache_size| {
+            let config = SearchConfig {
+                query_plan_cache_size: plan_cache_size,
+                ..SearchConfig::default()
+            };
+            SearchExecutor::new(
+                Arc::clone(&db),
+                Arc::new(config),
+                Arc::new(LruCache::new(100)),
+                Arc::new(FileBloomFilter::default()),
+            )
+        };
+        let direct = executor(0);
+        let cached = executor(8);
+
+        let structures: Vec<fn(Query) -> Query> = vec![
+            |q| q,
+            |q| q.with_extensions(vec!["md".to_string()]),
+            |q| q.with_extensions(vec!["MD".to_string(), "txt".to_string(), "md".to_string()]),
+            |q| q.with_kinds(vec![EntryKind::Directory]),
+            |q| q.with_kinds(vec![EntryKind::File, EntryKind::Directory]),
+            |q| q.with_size_filter(SizeFilter::GreaterThan(15)),
+            |q| q.with_tags(vec!["work".to_string()]),
+            |q| q.with_tags(vec!["work".to_string()]).with_max_results(1),
+        ];
+        let modes = [
+            MatchMode::CaseInsensitive,
+            MatchMode::Exact,
+            MatchMode::Fuzzy,
+            MatchMode::Regex,
+            MatchMode::Glob,
+        ];
+        let scopes = [
+            SearchScope::Name,
+            SearchScope::Path,
+            SearchScope::Content,
+            SearchScope::All,
+        ];
+
+        let mut compared = 0;
+        for _ in 0..2 {
+            for mode in modes {
+                for scope in scopes {
+                    for structure in &structures {
+                        for pattern in ["", "note", "Notes", "n*e?", "notes/", "nts"] {
+                            let query = structure(
+                                Query::new(pattern.to_string())
+                                    .with_match_mode(mode)
+                                    .with_scope(scope),
+                            );
+                            assert_eq!(
+                                result_summary(&cached, &query),
+                                result_summary(&direct, &query),
+                                "{:?}",
+                                query
+                            );
+                            compared += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        let cached_metrics = cached.metrics.snapshot();
+        assert_eq!(cached_metrics.plan_cache_hits + cached_metrics.plan_cache_misses, compared);
+        assert!(cached_metrics.plan_cache_hits > cached_metrics.plan_cache_misses);
+        assert_eq!(direct.metrics.snapshot().plan_cache_hits, 0);
+    }
+
+    #[test]
+    fn test_name_index_matches_sql_candidates() {
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let mut entries: Vec<FileEntry> = [
+            "/notes/todo.md",
+            "/notes/Notes.TXT",
+            "/notes/archive/old_notes.md",
+            "/notes/100% done.md",
+            "/src/notes/parser.rs",
+            "/src/NOTES.rs",
+            "/fotos/München/Straße.png",
+            "/fotos/MÜNCHEN.txt",
+            "/fotos/munchen-notes.md",
+        ]
+        .iter()
+        .map(|path| FileEntry::new(PathBuf::from(path)))
+        .collect();
+        let mut dir = FileEntry::new(PathBuf::from("/notes/archive"));
+        dir.is_directory = true;
+        entries.push(dir);
+        db.insert_files_batch(&entries).unwrap();
+        db.delete_by_path(Path::new("/src/NOTES.rs")).unwrap();
+
+        let config = Arc::new(SearchConfig {
+            candidate_multiplier: 1,
+            ..SearchConfig::default()
+        });
+        let executor = || {
+            SearchExecutor::new(
+                Arc::clone(&db),
+                Arc::clone(&config),
+                Arc::new(LruCache::new(100)),
+                Arc::new(FileBloomFilter::default()),
+            )
+        };
+        let names = Arc::new(NameIndex::new(Arc::clone(&db)));
+        names.refresh().unwrap();
+        let indexed = executor().with_name_index(names);
+        let direct = executor();
+
+        let structures: Vec<fn(Query) -> Query> = vec![
+            |q| q,
+            |q| q.with_max_results(1),
+            |q| q.with_max_results(2),
+            |q| q.with_extensions(vec!["md".to_string()]),
+            |q| q.with_kinds(vec![EntryKind::Directory]),
+        ];
+        let patterns = [
+            "", "notes", "NOTES", "münchen", "MÜNCHEN", "munchen", "strasse", "old_", "_otes",
+            "100%", "%", "notes/", "NOTES/ARCH", "fotos/m", "\\notes", "zzz",
+        ];
+        for mode in [MatchMode::CaseInsensitive, MatchMode::Exact, MatchMode::Glob] {
+            for scope in [SearchScope::Name, SearchScope::Path] {
+                for structure in &structures {
+                    for pattern in patterns {
+                        let query = structure(
+                            Query::new(pattern.to_string())
+                                .with_match_mode(mode)
+                                .with_scope(scope),
+                        );
+                        assert_eq!(
+                            result_summary(&indexed, &query),
+                            result_summary(&direct, &query),
+                            "{:?}",
+                            query
+                        );
+                    }
+                }
+            }
+        }
+
+        let metrics = indexed.metrics.snapshot();
+        assert!(metrics.name_index_hits > 0);
+        assert_eq!(metrics.name_index_misses, 0);
+
+        // A size filter, or an index changed since the build, leaves lookups to SQLite.
+        let sized = Query::new("notes".to_string()).with_size_filter(SizeFilter::LessThan(1));
+        assert_eq!(result_summary(&indexed, &sized), result_summary(&direct, &sized));
+        assert_eq!(indexed.metrics.snapshot().name_index_hits, metrics.name_index_hits);
+        db.insert_file(&FileEntry::new(PathBuf::from("/notes/new-notes.md"))).unwrap();
+        let query = Query::new("new-notes".to_string());
+        assert_eq!(result_summary(&indexed, &query).len(), 1);
+        assert_eq!(indexed.metrics.snapshot().name_index_misses, 1);
+    }
+
+    #[test]
+    fn test_case_folding_agrees_between_candidates_and_matcher() {
+        use crate::utils::normalizer::{LowercaseNormalizer, UnicodeNormalizer};
+
+        let files = [
+            "straße.txt",
+            "STRASSE-plan.pdf",
+            "İSTANBUL.jpg",
+            "istanbul-map.png",
+            "ıstanbul-old.png",
+            "ΟΔΟΣ.md",
+            "οδος-notes.md",
+        ];
+        let cases: [(&str, &[&str]); 6] = [
+            ("strasse", &["STRASSE-plan.pdf", "straße.txt"]),
+            ("STRAßE", &["STRASSE-plan.pdf", "straße.txt"]),
+            ("istanbul", &["istanbul-map.png", "İSTANBUL.jpg"]),
+            ("İSTANBUL", &["istanbul-map.png", "İSTANBUL.jpg"]),
+            ("οδος", &["ΟΔΟΣ.md", "οδος-notes.md"]),
+            ("ΟΔΟΣ", &["ΟΔΟΣ.md", "οδος-notes.md"]),
+        ];
+
+        let normalizers: [Arc<dyn TextNormalizer>; 2] =
+            [Arc::new(UnicodeNormalizer), Arc::new(LowercaseNormalizer)];
+        for normalizer in normalizers {
+            let db = Database::in_memory(10).unwrap().with_normalizer(Arc::clone(&normalizer));
+            let entries: Vec<FileEntry> = files
+                .iter()
+                .map(|name| FileEntry::new(PathBuf::from("/data").join(name)))
+                .collect();
+            db.insert_files_batch(&entries).unwrap();
+            let db = Arc::new(db);
+
+            for (pattern, expected) in cases {
+                let mode = MatchMode::CaseInsensitive;
+                let matcher =
+                    create_normalized_matcher(pattern, mode, None, Arc::clone(&normalizer))
+                        .unwrap();
+                let mut matched: Vec<&str> =
+                    files.iter().copied().filter(|name| matcher.is_match(name)).collect();
+                matched.sort();
+                assert_eq!(matched, expected, "matcher for {}", pattern);
+
+                let candidates: Vec<String> = db
+                    .search_by_name(pattern, 100)
+                    .unwrap()
+                    .into_iter()
+                    .map(|entry| entry.name)
+                    .collect();
+                for name in &matched {
+                    assert!(
+                        candidates.iter().any(|candidate| candidate == name),
+                        "{} matches {} but is not a candidate",
+                        name,
+                        pattern
+                    );
+                }
+            }
+
+            let executor = SearchExecutor::new(
+                db,
+                Arc::new(SearchConfig::default()),
+                Arc::new(LruCache::new(100)),
+                Arc::new(FileBloomFilter::default()),
+            );
+            for (pattern, expected) in cases {
+                assert_eq!(names(&executor, pattern), expected, "search for {}", pattern);
+            }
+        }
+    }
 }
@@ -1,9 +1,12 @@
+use crate::utils::normalizer::{fold_case, TextNormalizer};
 use fuzzy_matcher::skim::SkimMatcherV2;
 use fuzzy_matcher::FuzzyMatcher as FuzzyMatcherTrait;
+use std::sync::Arc;
 
 pub struct FuzzyMatcher {
     matcher: SkimMatcherV2,
     threshold: i64,
+    normalizer: Option<Arc<dyn TextNormalizer>>,
 }
 
 impl FuzzyMatcher {
@@ -11,15 +14,26 @@ impl FuzzyMatcher {
         Self {
             matcher: SkimMatcherV2::default(),
             threshold: (threshold * 100.0) as i64,
+            normalizer: None,
         }
     }
 
+    pub fn with_normalizer(mut self, normalizer: Arc<dyn TextNormalizer>) -> Self {
+        self.normalizer = Some(normalizer);
+        self
+    }
+
     pub fn fuzzy_match(&self, choice: &str, pattern: &str) -> Option<i64> {
-        self.matcher.fuzzy_match(choice, pattern)
+        match self.normalizer {
+            Some(ref normalizer) => self
+                .matcher
+                .fuzzy_match(&normalizer.normalize(choice), &normalizer.normalize(pattern)),
+            None => self.matcher.fuzzy_match(choice, pattern),
+        }
     }
 
     pub fn fuzzy_match_with_threshold(&self, choice: &str, pattern: &str) -> Option<i64> {
-        if let Some(score) = self.matcher.fuzzy_match(choice, pattern) {
+        if let Some(score) = self.fuzzy_match(choice, pattern) {
             if score >= self.threshold {
                 return Some(score);
             }
@@ -60,11 +74,11 @@ pub fn levenshtein_distance(s1: &str, s2: &str) -> usize {
 
     let mut matrix = vec![vec![0; len2 + 1]; len1 + 1];
 
-    for i in 0..=len1 {
-        matrix[i][0] = i;
+    for (i, row) in matrix.iter_mut().enumerate() {
+        row[0] = i;
     }
-    for j in 0..=len2 {
-        matrix[0][j] = j;
+    for (j, cell) in matrix[0].iter_mut().enumerate() {
+        *cell = j;
     }
 
     let s1_chars: Vec<char> = s1.chars().collect();
@@ -94,18 +108,60 @@ pub fn similarity_score(s1: &str, s2: &str) -> f64 {
 }
 
 pub fn starts_with_score(text: &str, pattern: &str) -> f64 {
-    let text_lower = text.to_lowercase();
-    let pattern_lower = pattern.to_lowercase();
+    let text_lower = fold_case(text);
+    let pattern_lower = fold_case(pattern);
 
-    if text_lower.starts_with(&pattern_lower) {
+    if text_lower.starts_with(pattern_lower.as_ref()) {
         1.0
-    } else if text_lower.contains(&pattern_lower) {
+    } else if text_lower.contains(pattern_lower.as_ref()) {
         0.5
     } else {
         0.0
     }
 }
 
+/// Splits a file name into subwords with their byte offsets.
+///
+/// Separators such as `_`, `-`, `.` and spaces are dropped, and a new subword also starts at
+/// a lower-to-upper case change (`quarterlyReport`), at the last capital of an acronym
+/// (`HTMLParser`) and between letters and digits (`v7`).
+pub fn split_subwords(text: &str) -> Vec<(usize, &str)> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut subwords = Vec::new();
+    let mut start: Option<usize> = None;
+
+    for (i, &(offset, c)) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if let Some(begin) = start.take() {
+                subwords.push((begin, &text[begin..offset]));
+            }
+            continue;
+        }
+
+        let Some(begin) = start else {
+            start = Some(offset);
+            continue;
+        };
+
+        let prev = chars[i - 1].1;
+        let next = chars.get(i + 1).map(|&(_, c)| c);
+        let boundary = (prev.is_lowercase() && c.is_uppercase())
+            || (prev.is_uppercase() && c.is_uppercase() && next.is_some_and(char::is_lowercase))
+            || prev.is_alphabetic() != c.is_alphabetic();
+
+        if boundary {
+            subwords.push((begin, &text[begin..offset]));
+            start = Some(offset);
+        }
+    }
+
+    if let Some(begin) = start {
+        subwords.push((begin, &text[begin..]));
+    }
+
+    subwords
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -118,6 +174,15 @@ mod tests {
         assert!(matcher.fuzzy_match("hello", "xyz").is_none());
     }
 
+    #[test]
+    fn test_fuzzy_matcher_with_normalizer() {
+        use crate::utils::normalizer::UnicodeNormalizer;
+
+        let matcher = FuzzyMatcher::default().with_normalizer(Arc::new(UnicodeNormalizer));
+        assert!(matcher.fuzzy_match("München-Fotos", "mnchn").is_some());
+        assert!(matcher.fuzzy_match("cafe menu", "Café").is_some());
+    }
+
     #[test]
     fn test_levenshtein_distance() {
         assert_eq!(levenshtein_distance("kitten", "sitting"), 3);
@@ -144,6 +209,24 @@ mod tests {
         assert_eq!(starts_with_score("hello world", "xyz"), 0.0);
     }
 
+    #[test]
+    fn test_split_subwords() {
+        let words = |text| {
+            split_subwords(text)
+                .into_iter()
+                .map(|(_, word)| word)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(words("report_final.pdf"), ["report", "final", "pdf"]);
+        assert_eq!(words("quarterlyReport v2"), ["quarterly", "Report", "v", "2"]);
+        assert_eq!(words("HTMLParser.rs"), ["HTML", "Parser", "rs"]);
+        assert_eq!(words("__init__.py"), ["init", "py"]);
+        assert_eq!(words("Übersicht-2024"), ["Übersicht", "2024"]);
+        assert_eq!(split_subwords("a-b")[1], (2, "b"));
+        assert!(split_subwords("--").is_empty());
+    }
+
     #[test]
     fn test_score_normalized() {
         let matcher = FuzzyMatcher::default();
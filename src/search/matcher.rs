@@ -1,6 +1,7 @@
 use crate::core::error::Result;
 use crate::core::types::MatchMode;
-use globset::{Glob, GlobMatcher};
+use crate::utils::normalizer::{fold_case, TextNormalizer};
+use globset::{GlobBuilder, GlobMatcher};
 use regex::Regex;
 use std::sync::Arc;
 
@@ -12,6 +13,7 @@ pub trait Matcher: Send + Sync {
 pub struct ExactMatcher {
     pattern: String,
     case_sensitive: bool,
+    normalizer: Option<Arc<dyn TextNormalizer>>,
 }
 
 impl ExactMatcher {
@@ -19,37 +21,75 @@ impl ExactMatcher {
         Self {
             pattern,
             case_sensitive,
+            normalizer: None,
+        }
+    }
+
+    pub fn with_normalizer(mut self, normalizer: Arc<dyn TextNormalizer>) -> Self {
+        self.pattern = normalizer.normalize(&self.pattern).into_owned();
+        self.normalizer = Some(normalizer);
+        self
+    }
+
+    fn prepare_text(&self, text: &str) -> String {
+        if let Some(ref normalizer) = self.normalizer {
+            normalizer.normalize(text).into_owned()
+        } else if self.case_sensitive {
+            text.to_string()
+        } else {
+            fold_case(text).into_owned()
+        }
+    }
+
+    fn prepare_pattern(&self) -> String {
+        if self.case_sensitive || self.normalizer.is_some() {
+            self.pattern.clone()
+        } else {
+            fold_case(&self.pattern).into_owned()
         }
     }
 }
 
 impl Matcher for ExactMatcher {
     fn is_match(&self, text: &str) -> bool {
-        if self.case_sensitive {
+        if self.case_sensitive && self.normalizer.is_none() {
             text.contains(&self.pattern)
         } else {
-            text.to_lowercase().contains(&self.pattern.to_lowercase())
+            self.prepare_text(text).contains(&self.prepare_pattern())
         }
     }
 
+    /// Offsets are into `text` itself, even where folding case or accents changed the
+    /// length of what was compared.
     fn find_matches(&self, text: &str) -> Vec<(usize, usize)> {
         let mut matches = Vec::new();
-        let pattern = if self.case_sensitive {
-            self.pattern.clone()
-        } else {
-            self.pattern.to_lowercase()
-        };
-        let search_text = if self.case_sensitive {
-            text.to_string()
-        } else {
-            text.to_lowercase()
-        };
+        let pattern = self.prepare_pattern();
+        if pattern.is_empty() {
+            return matches;
+        }
+
+        // Prepared one character at a time, recording where in `text` each byte came from.
+        let mut search_text = String::with_capacity(text.len());
+        let mut origins = Vec::with_capacity(text.len() + 1);
+        let mut buffer = [0; 4];
+        for (index, c) in text.char_indices() {
+            let prepared = self.prepare_text(c.encode_utf8(&mut buffer));
+            search_text.push_str(&prepared);
+            origins.resize(origins.len() + prepared.len(), index);
+        }
+        origins.push(text.len());
 
         let mut start = 0;
         while let Some(pos) = search_text[start..].find(&pattern) {
             let absolute_pos = start + pos;
-            matches.push((absolute_pos, pattern.len()));
-            start = absolute_pos + 1;
+            // A match ending inside what one character folded to covers that character, and
+            // one ending before marks that folded away, like a decomposed accent, covers them.
+            let from = origins[absolute_pos];
+            let last = origins[absolute_pos + pattern.len() - 1];
+            let to = (last + text[last..].chars().next().map_or(0, char::len_utf8))
+                .max(origins[absolute_pos + pattern.len()]);
+            matches.push((from, to - from));
+            start = absolute_pos + next_char_len(&search_text[absolute_pos..]);
         }
 
         matches
@@ -94,7 +134,13 @@ pub struct GlobPatternMatcher {
 
 impl GlobPatternMatcher {
     pub fn new(pattern: &str) -> Result<Self> {
-        let glob = Glob::new(pattern)?;
+        Self::with_case(pattern, true)
+    }
+
+    pub fn with_case(pattern: &str, case_sensitive: bool) -> Result<Self> {
+        let glob = GlobBuilder::new(pattern)
+            .case_insensitive(!case_sensitive)
+            .build()?;
         Ok(Self {
             matcher: glob.compile_matcher(),
         })
@@ -157,15 +203,53 @@ impl Matcher for CompositeMatcher {
     }
 }
 
-pub fn create_matcher(pattern: &str, mode: MatchMode) -> Result<Arc<dyn Matcher>> {
+fn next_char_len(text: &str) -> usize {
+    text.chars().next().map_or(1, char::len_utf8)
+}
+
+/// Whether `mode` matches case-sensitively, with `case_sensitive` (from `case:`)
+/// overriding it. Fuzzy matching always ignores case.
+fn is_case_sensitive(mode: MatchMode, case_sensitive: Option<bool>) -> bool {
+    match mode {
+        MatchMode::Fuzzy => false,
+        MatchMode::CaseInsensitive => case_sensitive.unwrap_or(false),
+        MatchMode::Exact | MatchMode::Regex | MatchMode::Glob => case_sensitive.unwrap_or(true),
+    }
+}
+
+pub fn create_matcher(
+    pattern: &str,
+    mode: MatchMode,
+    case_sensitive: Option<bool>,
+) -> Result<Arc<dyn Matcher>> {
+    let case_sensitive = is_case_sensitive(mode, case_sensitive);
     match mode {
-        MatchMode::Exact => Ok(Arc::new(ExactMatcher::new(pattern.to_string(), true))),
-        MatchMode::CaseInsensitive => {
-            Ok(Arc::new(ExactMatcher::new(pattern.to_string(), false)))
+        MatchMode::Exact | MatchMode::CaseInsensitive | MatchMode::Fuzzy => {
+            Ok(Arc::new(ExactMatcher::new(pattern.to_string(), case_sensitive)))
         }
-        MatchMode::Regex => Ok(Arc::new(RegexMatcher::new(pattern)?)),
-        MatchMode::Glob => Ok(Arc::new(GlobPatternMatcher::new(pattern)?)),
-        MatchMode::Fuzzy => Ok(Arc::new(ExactMatcher::new(pattern.to_string(), false))),
+        MatchMode::Regex if case_sensitive => Ok(Arc::new(RegexMatcher::new(pattern)?)),
+        MatchMode::Regex => Ok(Arc::new(RegexMatcher::new_case_insensitive(pattern)?)),
+        MatchMode::Glob => Ok(Arc::new(GlobPatternMatcher::with_case(pattern, case_sensitive)?)),
+    }
+}
+
+/// Like [`create_matcher`], with substring matches that ignore case also folding accents
+/// through `normalizer`.
+pub fn create_normalized_matcher(
+    pattern: &str,
+    mode: MatchMode,
+    case_sensitive: Option<bool>,
+    normalizer: Arc<dyn TextNormalizer>,
+) -> Result<Arc<dyn Matcher>> {
+    match mode {
+        MatchMode::Exact | MatchMode::CaseInsensitive | MatchMode::Fuzzy
+            if !is_case_sensitive(mode, case_sensitive) =>
+        {
+            Ok(Arc::new(
+                ExactMatcher::new(pattern.to_string(), false).with_normalizer(normalizer),
+            ))
+        }
+        _ => create_matcher(pattern, mode, case_sensitive),
     }
 }
 
@@ -183,6 +267,44 @@ mod tests {
         assert!(matcher.is_match("this is a TEST"));
     }
 
+    #[test]
+    fn test_exact_matcher_with_normalizer() {
+        use crate::utils::normalizer::UnicodeNormalizer;
+
+        let matcher = ExactMatcher::new("munchen".to_string(), false)
+            .with_normalizer(Arc::new(UnicodeNormalizer));
+        assert!(matcher.is_match("München-Fotos"));
+
+        let matcher = ExactMatcher::new("Café".to_string(), false)
+            .with_normalizer(Arc::new(UnicodeNormalizer));
+        assert!(matcher.is_match("cafe.txt"));
+    }
+
+    #[test]
+    fn test_exact_matches_are_offsets_into_the_original_text() {
+        use crate::utils::normalizer::UnicodeNormalizer;
+
+        let matcher = ExactMatcher::new("report".to_string(), false)
+            .with_normalizer(Arc::new(UnicodeNormalizer));
+        let text = "Café_Report_report.txt";
+        let matches = matcher.find_matches(text);
+        assert_eq!(matches, [(6, 6), (13, 6)]);
+        assert_eq!(&text[6..12], "Report");
+
+        let matcher = ExactMatcher::new("cafe".to_string(), false)
+            .with_normalizer(Arc::new(UnicodeNormalizer));
+        assert_eq!(matcher.find_matches("Café.txt"), [(0, 5)]);
+        assert_eq!(matcher.find_matches("Cafe\u{301}.txt"), [(0, 6)]);
+
+        let matcher = ExactMatcher::new("strasse".to_string(), false)
+            .with_normalizer(Arc::new(UnicodeNormalizer));
+        assert_eq!(matcher.find_matches("Straße.pdf"), [(0, 7)]);
+        let matcher = ExactMatcher::new("stras".to_string(), false)
+            .with_normalizer(Arc::new(UnicodeNormalizer));
+        assert_eq!(matcher.find_matches("Straße.pdf"), [(0, 6)]);
+        assert!(ExactMatcher::new(String::new(), true).find_matches("any").is_empty());
+    }
+
     #[test]
     fn test_regex_matcher() {
         let matcher = RegexMatcher::new(r"\d+").unwrap();
@@ -200,6 +322,30 @@ mod tests {
         assert!(!matcher.is_match("file.rs"));
     }
 
+    #[test]
+    fn test_case_flag_overrides_mode_defaults() {
+        for (mode, pattern, text, defaults, sensitive, insensitive) in [
+            (MatchMode::Exact, "Read", "readme", false, false, true),
+            (MatchMode::CaseInsensitive, "Read", "readme", true, false, true),
+            (MatchMode::Regex, "^Read", "readme", false, false, true),
+            (MatchMode::Glob, "READ*", "readme", false, false, true),
+            (MatchMode::Fuzzy, "Read", "readme", true, true, true),
+        ] {
+            for (case_sensitive, expected) in
+                [(None, defaults), (Some(true), sensitive), (Some(false), insensitive)]
+            {
+                let matcher = create_matcher(pattern, mode, case_sensitive).unwrap();
+                assert_eq!(
+                    matcher.is_match(text),
+                    expected,
+                    "{:?} case_sensitive={:?}",
+                    mode,
+                    case_sensitive
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_composite_matcher_and() {
         let m1 = Arc::new(ExactMatcher::new("hello".to_string(), false));
@@ -1,11 +1,16 @@
 pub mod executor;
 pub mod fuzzy;
 pub mod matcher;
+pub mod plan;
 pub mod query;
 pub mod ranker;
 
-pub use executor::SearchExecutor;
-pub use fuzzy::{levenshtein_distance, similarity_score, FuzzyMatcher};
-pub use matcher::{create_matcher, Matcher};
-pub use query::{Query, QueryParser};
-pub use ranker::ResultRanker;
+pub use executor::{SearchExecutor, Visibility, SLOW_QUERY_TARGET};
+pub use fuzzy::{levenshtein_distance, similarity_score, split_subwords, FuzzyMatcher};
+pub use matcher::{create_matcher, create_normalized_matcher, Matcher};
+pub use plan::{CandidateSource, PlanCache, PlanKey, QueryPlan};
+pub use query::{
+    EffectiveDateFilter, EffectiveQuery, EffectiveSizeFilter, HiddenMode, Query, QueryDiagnostic,
+    QueryParser, ResultLimit, SortField, SortKey, WeightedTerm,
+};
+pub use ranker::{ResultRanker, ScoreWeights, WeightOverrides};
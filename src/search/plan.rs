@@ -0,0 +1,424 @@
+use crate::core::metrics::EngineCounters;
+use crate::core::types::{
+    DateField, DateFilter, DepthFilter, DimensionFilter, EntryKind, FieldFilter, MatchMode,
+    SearchScope, SizeFilter,
+};
+use crate::filters::ExtensionCategory;
+use crate::search::fuzzy::FuzzyMatcher;
+use crate::search::query::{HiddenMode, Query};
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// The structure of a query: everything except its pattern and result limit.
+///
+/// Relative dates are resolved when a query is parsed, so `modified:<7d` typed a few seconds
+/// apart gives two keys; such queries plan again, which costs time but never changes results.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PlanKey {
+    pub match_mode: MatchMode,
+    pub scope: SearchScope,
+    pub extensions: Vec<String>,
+    pub kinds: Vec<EntryKind>,
+    pub tags: Vec<String>,
+    pub dimensions: DimensionFilter,
+    pub size_filters: Vec<SizeFilter>,
+    pub date_filters: Vec<(DateField, DateFilter)>,
+    pub under: Option<PathBuf>,
+    pub mime_filter: Option<String>,
+    pub category: Option<ExtensionCategory>,
+    pub hidden: Option<HiddenMode>,
+    pub depth_filter: Option<DepthFilter>,
+    pub languages: Vec<String>,
+    pub fields: Vec<FieldFilter>,
+    pub excluded_extensions: Vec<String>,
+    pub excluded_kinds: Vec<EntryKind>,
+}
+
+impl PlanKey {
+    pub fn of(query: &Query) -> Self {
+        Self {
+            match_mode: query.match_mode,
+            scope: query.scope,
+            extensions: query.extensions.clone(),
+            kinds: query.kinds.clone(),
+            tags: query.tags.clone(),
+            dimensions: query.dimensions.clone(),
+            size_filters: query.size_filters.clone(),
+            date_filters: query.date_filters.clone(),
+            under: query.under.clone(),
+            mime_filter: query.mime_filter.clone(),
+            category: query.category,
+            hidden: query.hidden,
+            depth_filter: query.depth_filter,
+            languages: query.languages.clone(),
+            fields: query.fields.clone(),
+            excluded_extensions: query.excluded_extensions.clone(),
+            excluded_kinds: query.excluded_kinds.clone(),
+        }
+    }
+}
+
+/// Where a plan's candidates come from before filters and matchers narrow them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CandidateSource {
+    /// Files carrying any of the query's tags. Content hits come from the full-text index
+    /// and are narrowed to tagged files instead, since tags say nothing about content.
+    Tagged { narrowing: Option<Box<CandidateSource>> },
+    /// Images whose stored dimensions satisfy the query's bounds, narrowing content hits
+    /// the same way as tags.
+    Dimensioned { narrowing: Option<Box<CandidateSource>> },
+    /// Emails and web pages whose stored fields match the query's field filters, narrowing
+    /// content hits the same way as tags.
+    Fielded { narrowing: Option<Box<CandidateSource>> },
+    /// Entries of the query's kinds whose name contains the pattern.
+    KindsByName,
+    /// Every entry of the query's kinds.
+    Kinds,
+    /// Content scope with `is:` filters: files from `files`, and directories and symlinks,
+    /// which have no content, matched by name.
+    ContentKinds {
+        files: Option<Box<CandidateSource>>,
+        contentless: Vec<EntryKind>,
+    },
+    Extension(String),
+    /// Entries of the query's MIME type whose name contains the pattern.
+    Mime(String),
+    Name,
+    /// Entries under the query's `under:` directory whose name contains the pattern, with the
+    /// directory matched in SQL.
+    NameUnder,
+    Path,
+    Content,
+    /// Entries whose name, path or content matches the pattern, or every entry without one.
+    All,
+    /// Content search is disabled, so content scope finds nothing.
+    Empty,
+}
+
+impl CandidateSource {
+    fn resolve(key: &PlanKey, content_search: bool) -> Self {
+        if !key.tags.is_empty() {
+            let narrowing = (key.scope == SearchScope::Content).then(|| {
+                let untagged = PlanKey {
+                    tags: Vec::new(),
+                    ..key.clone()
+                };
+                Box::new(Self::resolve(&untagged, content_search))
+            });
+            return Self::Tagged { narrowing };
+        }
+
+        if !key.dimensions.is_empty() {
+            let narrowing = (key.scope == SearchScope::Content).then(|| {
+                let unbounded = PlanKey {
+                    dimensions: DimensionFilter::default(),
+                    ..key.clone()
+                };
+                Box::new(Self::resolve(&unbounded, content_search))
+            });
+            return Self::Dimensioned { narrowing };
+        }
+
+        if !key.fields.is_empty() {
+            let narrowing = (key.scope == SearchScope::Content).then(|| {
+                let unfielded = PlanKey {
+                    fields: Vec::new(),
+                    ..key.clone()
+                };
+                Box::new(Self::resolve(&unfielded, content_search))
+            });
+            return Self::Fielded { narrowing };
+        }
+
+        if !key.kinds.is_empty() {
+            return match key.scope {
+                SearchScope::Name => Self::KindsByName,
+                SearchScope::Path => Self::Path,
+                SearchScope::All => Self::Kinds,
+                SearchScope::Content => {
+                    let files = key.kinds.contains(&EntryKind::File).then(|| {
+                        let any_kind = PlanKey {
+                            kinds: Vec::new(),
+                            ..key.clone()
+                        };
+                        Box::new(Self::resolve(&any_kind, content_search))
+                    });
+                    let contentless = key
+                        .kinds
+                        .iter()
+                        .copied()
+                        .filter(|kind| *kind != EntryKind::File)
+                        .collect();
+                    Self::ContentKinds { files, contentless }
+                }
+            };
+        }
+
+        match key.scope {
+            // Category names such as `code` are not stored extensions.
+            SearchScope::Name
+                if key.extensions.len() == 1
+                    && ExtensionCategory::parse(&key.extensions[0]).is_none() =>
+            {
+                Self::Extension(key.extensions[0].clone())
+            }
+            SearchScope::Name if key.extensions.is_empty() && key.mime_filter.is_some() => {
+                Self::Mime(key.mime_filter.clone().unwrap_or_default())
+            }
+            SearchScope::Name if key.under.is_some() => Self::NameUnder,
+            SearchScope::Name => Self::Name,
+            SearchScope::Path => Self::Path,
+            SearchScope::Content if content_search => Self::Content,
+            SearchScope::Content => Self::Empty,
+            SearchScope::All => Self::All,
+        }
+    }
+}
+
+/// The pattern-independent half of executing a query, built once per [`PlanKey`].
+///
+/// Plans depend only on the query structure and the executor's configuration, which is fixed
+/// for its lifetime, so cached plans never go stale.
+pub struct QueryPlan {
+    key: PlanKey,
+    source: CandidateSource,
+    /// Extensions lowercased and deduplicated for the per-entry filter.
+    extensions: Vec<String>,
+    /// `ext:code` also takes extension-less scripts found through their shebang.
+    includes_scripts: bool,
+    /// Excluded extensions, prepared like `extensions`.
+    excluded_extensions: Vec<String>,
+    /// `-ext:code` also drops extension-less scripts.
+    excludes_scripts: bool,
+    fuzzy: Option<Arc<FuzzyMatcher>>,
+    normalize_separators: bool,
+}
+
+impl QueryPlan {
+    pub(crate) fn build(
+        key: PlanKey,
+        content_search: bool,
+        fuzzy: Option<FuzzyMatcher>,
+    ) -> Self {
+        let source = CandidateSource::resolve(&key, content_search);
+
+        let prepare = |extensions: &[String]| {
+            let mut extensions: Vec<String> =
+                extensions.iter().map(|ext| ext.to_ascii_lowercase()).collect();
+            extensions.sort();
+            extensions.dedup();
+            let scripts = extensions
+                .iter()
+                .any(|ext| ExtensionCategory::parse(ext) == Some(ExtensionCategory::SourceCode));
+            (extensions, scripts)
+        };
+        let (extensions, includes_scripts) = prepare(&key.extensions);
+        let (excluded_extensions, excludes_scripts) = prepare(&key.excluded_extensions);
+
+        // Backslashes are escapes in glob and regex patterns, so only plain patterns are
+        // rewritten.
+        let normalize_separators = key.scope == SearchScope::Path
+            && matches!(
+                key.match_mode,
+                MatchMode::Exact | MatchMode::CaseInsensitive | MatchMode::Fuzzy
+            );
+
+        Self {
+            key,
+            source,
+            extensions,
+            includes_scripts,
+            excluded_extensions,
+            excludes_scripts,
+            fuzzy: fuzzy.map(Arc::new),
+            normalize_separators,
+        }
+    }
+
+    pub fn key(&self) -> &PlanKey {
+        &self.key
+    }
+
+    pub fn source(&self) -> &CandidateSource {
+        &self.source
+    }
+
+    pub fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
+
+    pub fn includes_scripts(&self) -> bool {
+        self.includes_scripts
+    }
+
+    pub fn excluded_extensions(&self) -> &[String] {
+        &self.excluded_extensions
+    }
+
+    pub fn excludes_scripts(&self) -> bool {
+        self.excludes_scripts
+    }
+
+    /// The matcher for fuzzy queries, when fuzzy search is enabled.
+    pub fn fuzzy(&self) -> Option<&FuzzyMatcher> {
+        self.fuzzy.as_deref()
+    }
+
+    pub fn normalizes_separators(&self) -> bool {
+        self.normalize_separators
+    }
+}
+
+/// A small least-recently-used map from query structure to compiled plan.
+pub struct PlanCache {
+    capacity: usize,
+    inner: Mutex<PlanCacheInner>,
+    metrics: Arc<EngineCounters>,
+}
+
+struct PlanCacheInner {
+    plans: HashMap<PlanKey, Arc<QueryPlan>>,
+    order: VecDeque<PlanKey>,
+}
+
+impl PlanCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(PlanCacheInner {
+                plans: HashMap::with_capacity(capacity),
+                order: VecDeque::with_capacity(capacity),
+            }),
+            metrics: Arc::new(EngineCounters::new()),
+        }
+    }
+
+    /// Records lookups into `metrics` instead of a private set of counters.
+    pub fn with_metrics(mut self, metrics: Arc<EngineCounters>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Returns the plan cached for `key`, building and caching one with `build` on a miss.
+    pub fn get_or_build(
+        &self,
+        key: PlanKey,
+        build: impl FnOnce(PlanKey) -> QueryPlan,
+    ) -> Arc<QueryPlan> {
+        if self.capacity == 0 {
+            self.metrics.record_plan_lookup(false);
+            return Arc::new(build(key));
+        }
+
+        let mut inner = self.inner.lock();
+        if let Some(plan) = inner.plans.get(&key).cloned() {
+            if let Some(pos) = inner.order.iter().position(|k| *k == key) {
+                inner.order.remove(pos);
+            }
+            inner.order.push_back(key);
+            self.metrics.record_plan_lookup(true);
+            return plan;
+        }
+
+        self.metrics.record_plan_lookup(false);
+        let plan = Arc::new(build(key.clone()));
+        if inner.plans.len() >= self.capacity {
+            if let Some(oldest) = inner.order.pop_front() {
+                inner.plans.remove(&oldest);
+            }
+        }
+        inner.plans.insert(key.clone(), Arc::clone(&plan));
+        inner.order.push_back(key);
+        plan
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().plans.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(input: &str) -> PlanKey {
+        PlanKey::of(&crate::search::QueryParser::parse(input).unwrap())
+    }
+
+    #[test]
+    fn test_key_ignores_pattern_and_limit() {
+        assert_eq!(key("not ext:md limit:5"), key("notes ext:md"));
+        assert_ne!(key("notes ext:md"), key("notes ext:md,txt"));
+        assert_ne!(key("notes ext:md"), key("notes ext:md scope:path"));
+    }
+
+    #[test]
+    fn test_plan_cache_evicts_least_recently_used() {
+        let metrics = Arc::new(EngineCounters::new());
+        let cache = PlanCache::new(2).with_metrics(Arc::clone(&metrics));
+        let build = |key| QueryPlan::build(key, false, None);
+
+        let first = cache.get_or_build(key("a ext:md"), build);
+        cache.get_or_build(key("a ext:rs"), build);
+        assert!(Arc::ptr_eq(&first, &cache.get_or_build(key("b ext:md"), build)));
+        cache.get_or_build(key("a is:dir"), build);
+
+        assert_eq!(cache.len(), 2);
+        assert!(Arc::ptr_eq(&first, &cache.get_or_build(key("c ext:md"), build)));
+        let snapshot = metrics.snapshot();
+        assert_eq!((snapshot.plan_cache_hits, snapshot.plan_cache_misses), (2, 3));
+
+        cache.get_or_build(key("a ext:rs"), build);
+        assert_eq!(metrics.snapshot().plan_cache_misses, 4);
+    }
+
+    #[test]
+    fn test_candidate_source_resolution() {
+        let source = |input: &str| CandidateSource::resolve(&key(input), true);
+
+        assert_eq!(source("a ext:md"), CandidateSource::Extension("md".to_string()));
+        assert_eq!(source("a ext:md,txt"), CandidateSource::Name);
+        assert_eq!(source("a ext:code"), CandidateSource::Name);
+        assert_eq!(source("a mime:image/*"), CandidateSource::Mime("image/*".to_string()));
+        assert_eq!(source("a in:/home/me"), CandidateSource::NameUnder);
+        assert_eq!(source("a ext:md mime:text/*"), CandidateSource::Extension("md".to_string()));
+        assert_eq!(source("a is:dir scope:path"), CandidateSource::Path);
+        assert_eq!(
+            source("a tag:x scope:content"),
+            CandidateSource::Tagged {
+                narrowing: Some(Box::new(CandidateSource::Content))
+            }
+        );
+        assert_eq!(
+            source("a width:>100"),
+            CandidateSource::Dimensioned { narrowing: None }
+        );
+        assert_eq!(
+            source("a tag:x height:<100"),
+            CandidateSource::Tagged { narrowing: None }
+        );
+        assert_eq!(
+            source("invoice subject:q3 scope:content"),
+            CandidateSource::Fielded {
+                narrowing: Some(Box::new(CandidateSource::Content))
+            }
+        );
+        assert_eq!(
+            source("a is:file,dir scope:content"),
+            CandidateSource::ContentKinds {
+                files: Some(Box::new(CandidateSource::Content)),
+                contentless: vec![EntryKind::Directory],
+            }
+        );
+        assert_eq!(
+            CandidateSource::resolve(&key("a scope:content"), false),
+            CandidateSource::Empty
+        );
+    }
+}
@@ -1,16 +1,103 @@
 use crate::core::error::{Result, SearchError};
-use crate::core::types::{DateFilter, MatchMode, SearchScope, SizeFilter};
-use crate::filters::{parse_relative_date, parse_size};
+use crate::core::types::{
+    DateField, DateFilter, DepthFilter, DimensionBound, DimensionFilter, EntryKind, FieldFilter,
+    MatchMode, SearchResult, SearchScope, SizeFilter,
+};
+use crate::filters::{
+    ancient_cutoff, format_date, format_size, parse_date, parse_end_date, parse_entry_kinds,
+    parse_size, ExtensionCategory,
+};
+use crate::search::fuzzy::levenshtein_distance;
+use crate::search::ranker::{ScoreWeights, WeightOverrides};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Range;
+use std::path::PathBuf;
 
-#[derive(Debug, Clone)]
+/// A parsed search. Serializes with snake_case filter variants, e.g.
+/// `{"pattern": "report", "size_filters": [{"greater_than": 1024}], "sort_by": "-size"}`,
+/// and fields left out when deserializing take their [`Query::new`] values.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Query {
     pub pattern: String,
     pub match_mode: MatchMode,
+    /// From `case:`; overrides whether the pattern matches case-sensitively. `None` keeps
+    /// the match mode's own behavior, and fuzzy matching ignores it.
+    pub case_sensitive: Option<bool>,
     pub scope: SearchScope,
-    pub size_filter: Option<SizeFilter>,
-    pub date_filter: Option<DateFilter>,
+    /// Size bounds from `size:`; an entry must satisfy all of them.
+    pub size_filters: Vec<SizeFilter>,
+    /// Date bounds from `modified:`, `created:` and `accessed:`, each on its own timestamp;
+    /// an entry must satisfy all of them.
+    pub date_filters: Vec<(DateField, DateFilter)>,
     pub extensions: Vec<String>,
+    pub kinds: Vec<EntryKind>,
+    /// User tags; an entry matches when it carries any of them.
+    pub tags: Vec<String>,
+    /// Image pixel bounds from `width:` and `height:`.
+    pub dimensions: DimensionFilter,
+    /// From `under:` or `in:`; only entries whose path starts with this directory match.
+    pub under: Option<PathBuf>,
+    /// From `mime:`; an exact MIME type or a prefix ending in `*`, e.g. `image/*`. Entries
+    /// without a MIME type don't match.
+    pub mime_filter: Option<String>,
+    /// From `category:`; only entries whose extension falls in this category match.
+    pub category: Option<ExtensionCategory>,
+    /// From `hidden:`; whether hidden entries are left out or the only ones kept. `None`
+    /// returns whatever the index holds.
+    pub hidden: Option<HiddenMode>,
+    /// From `depth:`; how many directories deep entries may sit, counted from `under` when
+    /// it is set and from the filesystem root otherwise.
+    pub depth_filter: Option<DepthFilter>,
+    /// Script languages from `lang:`, lowercased; an entry matches any of them.
+    pub languages: Vec<String>,
+    /// From `content:`; whatever the scope of `pattern`, only files whose content also
+    /// matches this full-text query match.
+    pub content_pattern: Option<String>,
+    /// Email and HTML fields from `subject:`, `from:`, `to:` and `title:`; an entry must
+    /// match all of them.
+    pub fields: Vec<FieldFilter>,
+    /// From `-ext:`; entries with any of these extensions or categories don't match.
+    pub excluded_extensions: Vec<String>,
+    /// From `-is:`; entries of these kinds don't match.
+    pub excluded_kinds: Vec<EntryKind>,
+    /// From `-term`; entries whose name contains any of these, ignoring case, don't match.
+    pub excluded_terms: Vec<String>,
+    /// Pattern terms with their `term^weight` boosts. Empty unless some term is boosted;
+    /// then each term is matched on its own and `pattern` is the terms joined by spaces.
+    pub terms: Vec<WeightedTerm>,
+    /// Patterns separated by `OR` or `|`, any of which may match. Empty unless the query has
+    /// several; then `pattern` is them joined by ` OR `.
+    pub alternatives: Vec<String>,
+    /// Score weights set for this query alone by `recency^x` and `depth^x`.
+    pub weight_overrides: WeightOverrides,
     pub max_results: Option<usize>,
+    /// Ranked results skipped before the first one returned, for paging through them in
+    /// steps of `max_results`.
+    pub offset: usize,
+    /// From `context:N`; content matches carry this many lines before and after the
+    /// matching line in `matches`. 0 leaves `matches` empty.
+    pub context_lines: usize,
+    /// From `sort:`; results are ordered by this instead of by score before the limit is
+    /// applied.
+    pub sort_by: Option<SortKey>,
+    pub warnings: Vec<String>,
+}
+
+/// A pattern term and how much its name match counts towards the score.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeightedTerm {
+    pub text: String,
+    pub weight: f64,
+}
+
+impl Default for Query {
+    fn default() -> Self {
+        Self::new(String::new())
+    }
 }
 
 impl Query {
@@ -18,11 +105,33 @@ impl Query {
         Self {
             pattern,
             match_mode: MatchMode::CaseInsensitive,
+            case_sensitive: None,
             scope: SearchScope::Name,
-            size_filter: None,
-            date_filter: None,
+            size_filters: Vec::new(),
+            date_filters: Vec::new(),
             extensions: Vec::new(),
+            kinds: Vec::new(),
+            tags: Vec::new(),
+            dimensions: DimensionFilter::default(),
+            under: None,
+            mime_filter: None,
+            category: None,
+            hidden: None,
+            depth_filter: None,
+            languages: Vec::new(),
+            content_pattern: None,
+            fields: Vec::new(),
+            excluded_extensions: Vec::new(),
+            excluded_kinds: Vec::new(),
+            excluded_terms: Vec::new(),
+            terms: Vec::new(),
+            alternatives: Vec::new(),
+            weight_overrides: WeightOverrides::default(),
             max_results: None,
+            offset: 0,
+            context_lines: 0,
+            sort_by: None,
+            warnings: Vec::new(),
         }
     }
 
@@ -31,18 +140,28 @@ impl Query {
         self
     }
 
+    pub fn with_case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive = Some(case_sensitive);
+        self
+    }
+
     pub fn with_scope(mut self, scope: SearchScope) -> Self {
         self.scope = scope;
         self
     }
 
     pub fn with_size_filter(mut self, filter: SizeFilter) -> Self {
-        self.size_filter = Some(filter);
+        self.size_filters.push(filter);
         self
     }
 
-    pub fn with_date_filter(mut self, filter: DateFilter) -> Self {
-        self.date_filter = Some(filter);
+    /// Adds a bound on the modification time.
+    pub fn with_date_filter(self, filter: DateFilter) -> Self {
+        self.with_date_filter_on(DateField::Modified, filter)
+    }
+
+    pub fn with_date_filter_on(mut self, field: DateField, filter: DateFilter) -> Self {
+        self.date_filters.push((field, filter));
         self
     }
 
@@ -51,119 +170,1212 @@ impl Query {
         self
     }
 
+    pub fn with_kinds(mut self, kinds: Vec<EntryKind>) -> Self {
+        self.kinds = kinds;
+        self
+    }
+
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    pub fn with_dimensions(mut self, dimensions: DimensionFilter) -> Self {
+        self.dimensions = dimensions;
+        self
+    }
+
+    pub fn with_under(mut self, dir: PathBuf) -> Self {
+        self.under = Some(dir);
+        self
+    }
+
+    pub fn with_mime_filter(mut self, filter: String) -> Self {
+        self.mime_filter = Some(filter);
+        self
+    }
+
+    /// Narrows the query to files whose content matches `pattern` as well. A query with no
+    /// other pattern searches content for it instead.
+    pub fn with_content_pattern(mut self, pattern: String) -> Self {
+        if self.pattern.is_empty() && self.scope == SearchScope::Name {
+            self.pattern = pattern;
+            self.scope = SearchScope::Content;
+        } else {
+            self.content_pattern = Some(pattern);
+        }
+        self
+    }
+
+    pub fn with_languages(mut self, languages: Vec<String>) -> Self {
+        self.languages = languages;
+        self
+    }
+
+    pub fn with_fields(mut self, fields: Vec<FieldFilter>) -> Self {
+        self.fields = fields;
+        self
+    }
+
     pub fn with_max_results(mut self, max: usize) -> Self {
         self.max_results = Some(max);
         self
     }
+
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn with_context_lines(mut self, lines: usize) -> Self {
+        self.context_lines = lines;
+        self
+    }
+
+    pub fn with_sort(mut self, sort: SortKey) -> Self {
+        self.sort_by = Some(sort);
+        self
+    }
+
+    /// Text every match contains: the pattern, or the longest term when terms are matched
+    /// on their own.
+    pub fn required_text(&self) -> &str {
+        self.terms
+            .iter()
+            .map(|term| term.text.as_str())
+            .max_by_key(|text| text.len())
+            .unwrap_or(&self.pattern)
+    }
+
+    /// The patterns an entry may match: each alternative, or the pattern alone.
+    pub fn patterns(&self) -> Vec<&str> {
+        if self.alternatives.is_empty() {
+            vec![self.pattern.as_str()]
+        } else {
+            self.alternatives.iter().map(String::as_str).collect()
+        }
+    }
+
+    /// This query with `pattern` as its only pattern, for looking up one alternative.
+    pub(crate) fn alternative(&self, pattern: &str) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            alternatives: Vec::new(),
+            ..self.clone()
+        }
+    }
+
+    pub fn effective(&self) -> EffectiveQuery {
+        EffectiveQuery::from(self)
+    }
+
+    /// The number of results this query may return under `cap`: the explicit
+    /// `max_results` clamped to `cap`, or `cap` itself when none was requested.
+    pub fn result_limit(&self, cap: usize) -> Result<ResultLimit> {
+        match self.max_results {
+            Some(0) => Err(SearchError::InvalidQuery(
+                "result limit must be greater than zero".to_string(),
+            )),
+            Some(requested) => Ok(ResultLimit {
+                effective_limit: requested.min(cap),
+                limit_clamped: requested > cap,
+            }),
+            None => Ok(ResultLimit {
+                effective_limit: cap,
+                limit_clamped: false,
+            }),
+        }
+    }
+}
+
+/// What `sort:` orders results by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortField {
+    Score,
+    Size,
+    Modified,
+    Name,
+}
+
+/// An explicit result order: `size`, `modified`, `name` or `score`, ascending, or descending
+/// when written with a leading `-`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortKey {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+impl SortKey {
+    pub fn parse(input: &str) -> Option<Self> {
+        let input = input.trim().to_lowercase();
+        let (descending, name) = match input.strip_prefix('-') {
+            Some(name) => (true, name),
+            None => (false, input.as_str()),
+        };
+        let field = match name {
+            "score" | "relevance" => SortField::Score,
+            "size" => SortField::Size,
+            "modified" | "date" => SortField::Modified,
+            "name" => SortField::Name,
+            _ => return None,
+        };
+        Some(Self { field, descending })
+    }
+
+    pub fn sort(&self, results: &mut [SearchResult]) {
+        results.sort_by(|a, b| self.compare(a, b));
+    }
+
+    // Ties fall back to the path, like listings, so repeated runs agree.
+    pub fn compare(&self, a: &SearchResult, b: &SearchResult) -> Ordering {
+        let order = match self.field {
+            SortField::Score => a.score.partial_cmp(&b.score).unwrap_or(Ordering::Equal),
+            SortField::Size => a.file.size.cmp(&b.file.size),
+            SortField::Modified => a.file.modified_at.cmp(&b.file.modified_at),
+            SortField::Name => a.file.name.cmp(&b.file.name),
+        };
+        let order = if self.descending { order.reverse() } else { order };
+        order.then_with(|| a.file.path.cmp(&b.file.path))
+    }
+}
+
+impl fmt::Display for SortKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.field {
+            SortField::Score => "score",
+            SortField::Size => "size",
+            SortField::Modified => "modified",
+            SortField::Name => "name",
+        };
+        write!(f, "{}{}", if self.descending { "-" } else { "" }, name)
+    }
+}
+
+impl Serialize for SortKey {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SortKey {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        Self::parse(&value)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown sort: {}", value)))
+    }
+}
+
+/// What `hidden:` does with hidden entries: `true` keeps them alongside the rest, `false`
+/// leaves them out and `only` keeps nothing else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HiddenMode {
+    Include,
+    Exclude,
+    Only,
+}
+
+impl HiddenMode {
+    pub fn parse(input: &str) -> Option<Self> {
+        match input.to_lowercase().as_str() {
+            "true" => Some(Self::Include),
+            "false" => Some(Self::Exclude),
+            "only" => Some(Self::Only),
+            _ => None,
+        }
+    }
+
+    pub fn admits(&self, is_hidden: bool) -> bool {
+        match self {
+            Self::Include => true,
+            Self::Exclude => !is_hidden,
+            Self::Only => is_hidden,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ResultLimit {
+    pub effective_limit: usize,
+    pub limit_clamped: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveQuery {
+    pub pattern: String,
+    pub mode: MatchMode,
+    pub case_sensitive: Option<bool>,
+    pub scope: SearchScope,
+    pub extensions: Vec<String>,
+    pub kinds: Vec<EntryKind>,
+    pub tags: Vec<String>,
+    pub size: Vec<EffectiveSizeFilter>,
+    pub modified: Vec<EffectiveDateFilter>,
+    pub created: Vec<EffectiveDateFilter>,
+    pub accessed: Vec<EffectiveDateFilter>,
+    pub width: Option<EffectiveDimensionBound>,
+    pub height: Option<EffectiveDimensionBound>,
+    pub under: Option<PathBuf>,
+    pub mime: Option<String>,
+    pub category: Option<ExtensionCategory>,
+    pub hidden: Option<HiddenMode>,
+    pub depth: Option<EffectiveDepthFilter>,
+    pub languages: Vec<String>,
+    /// The `content:` terms files must also match.
+    pub content: Option<String>,
+    pub fields: Vec<FieldFilter>,
+    pub excluded_extensions: Vec<String>,
+    pub excluded_kinds: Vec<EntryKind>,
+    pub excluded_terms: Vec<String>,
+    pub terms: Vec<WeightedTerm>,
+    pub alternatives: Vec<String>,
+    /// The score weights results are ranked with, after the query's overrides.
+    pub weights: ScoreWeights,
+    pub max_results: Option<usize>,
+    pub offset: usize,
+    pub context_lines: usize,
+    /// The `sort:` order as written, e.g. `-size`; `None` ranks by score.
+    pub sort: Option<SortKey>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveSizeFilter {
+    pub op: &'static str,
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+    pub display: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveDimensionBound {
+    pub op: &'static str,
+    pub min: Option<u32>,
+    pub max: Option<u32>,
+    pub display: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveDepthFilter {
+    pub op: &'static str,
+    pub depth: usize,
+    pub display: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveDateFilter {
+    pub op: &'static str,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub display: String,
+}
+
+impl From<&Query> for EffectiveQuery {
+    fn from(query: &Query) -> Self {
+        Self {
+            pattern: query.pattern.clone(),
+            mode: query.match_mode,
+            case_sensitive: query.case_sensitive,
+            scope: query.scope,
+            extensions: query.extensions.clone(),
+            kinds: query.kinds.clone(),
+            tags: query.tags.clone(),
+            size: query.size_filters.iter().map(EffectiveSizeFilter::from).collect(),
+            modified: EffectiveDateFilter::of(query, DateField::Modified),
+            created: EffectiveDateFilter::of(query, DateField::Created),
+            accessed: EffectiveDateFilter::of(query, DateField::Accessed),
+            width: query
+                .dimensions
+                .width
+                .map(|bound| EffectiveDimensionBound::new("width", bound)),
+            height: query
+                .dimensions
+                .height
+                .map(|bound| EffectiveDimensionBound::new("height", bound)),
+            under: query.under.clone(),
+            mime: query.mime_filter.clone(),
+            category: query.category,
+            hidden: query.hidden,
+            depth: query.depth_filter.map(EffectiveDepthFilter::from),
+            languages: query.languages.clone(),
+            content: query.content_pattern.clone(),
+            fields: query.fields.clone(),
+            excluded_extensions: query.excluded_extensions.clone(),
+            excluded_kinds: query.excluded_kinds.clone(),
+            excluded_terms: query.excluded_terms.clone(),
+            terms: query.terms.clone(),
+            alternatives: query.alternatives.clone(),
+            weights: ScoreWeights::default().with_overrides(&query.weight_overrides),
+            max_results: query.max_results,
+            offset: query.offset,
+            context_lines: query.context_lines,
+            sort: query.sort_by,
+            warnings: query.warnings.clone(),
+        }
+    }
+}
+
+impl From<&SizeFilter> for EffectiveSizeFilter {
+    fn from(filter: &SizeFilter) -> Self {
+        let (op, min, max, display) = match *filter {
+            SizeFilter::Exact(size) => (
+                "eq",
+                Some(size),
+                Some(size),
+                format!("size = {}", format_size(size)),
+            ),
+            SizeFilter::GreaterThan(size) => (
+                "gt",
+                Some(size),
+                None,
+                format!("size > {}", format_size(size)),
+            ),
+            SizeFilter::LessThan(size) => (
+                "lt",
+                None,
+                Some(size),
+                format!("size < {}", format_size(size)),
+            ),
+            SizeFilter::AtLeast(size) => (
+                "gte",
+                Some(size),
+                None,
+                format!("size >= {}", format_size(size)),
+            ),
+            SizeFilter::AtMost(size) => (
+                "lte",
+                None,
+                Some(size),
+                format!("size <= {}", format_size(size)),
+            ),
+            SizeFilter::Range(min, max) => (
+                "range",
+                Some(min),
+                Some(max),
+                format!("size {}..{}", format_size(min), format_size(max)),
+            ),
+        };
+
+        Self {
+            op,
+            min,
+            max,
+            display,
+        }
+    }
+}
+
+impl EffectiveDimensionBound {
+    fn new(axis: &str, bound: DimensionBound) -> Self {
+        let (op, min, max, display) = match bound {
+            DimensionBound::Exact(px) => ("eq", Some(px), Some(px), format!("= {}px", px)),
+            DimensionBound::GreaterThan(px) => ("gt", Some(px), None, format!("> {}px", px)),
+            DimensionBound::LessThan(px) => ("lt", None, Some(px), format!("< {}px", px)),
+            DimensionBound::Range(min, max) => {
+                ("range", Some(min), Some(max), format!("{}..{}px", min, max))
+            }
+        };
+        let display = format!("{} {}", axis, display);
+
+        Self {
+            op,
+            min,
+            max,
+            display,
+        }
+    }
+}
+
+impl From<DepthFilter> for EffectiveDepthFilter {
+    fn from(filter: DepthFilter) -> Self {
+        let (op, depth, symbol) = match filter {
+            DepthFilter::Exact(depth) => ("eq", depth, "="),
+            DepthFilter::GreaterThan(depth) => ("gt", depth, ">"),
+            DepthFilter::LessThan(depth) => ("lt", depth, "<"),
+        };
+
+        Self {
+            op,
+            depth,
+            display: format!("depth {} {}", symbol, depth),
+        }
+    }
+}
+
+impl EffectiveDateFilter {
+    /// The query's date filters that apply to `field`.
+    fn of(query: &Query, field: DateField) -> Vec<Self> {
+        query
+            .date_filters
+            .iter()
+            .filter(|(filter_field, _)| *filter_field == field)
+            .map(|(_, filter)| Self::new(field, filter))
+            .collect()
+    }
+
+    fn new(field: DateField, filter: &DateFilter) -> Self {
+        let name = field.as_str();
+        let (op, from, to, display) = match *filter {
+            DateFilter::After(date) => (
+                "after",
+                Some(date),
+                None,
+                format!("{} after {}", name, format_date(date)),
+            ),
+            DateFilter::Before(date) => (
+                "before",
+                None,
+                Some(date),
+                format!("{} before {}", name, format_date(date)),
+            ),
+            DateFilter::On(date) => (
+                "on",
+                Some(date),
+                Some(date),
+                format!("{} on {}", name, date.format("%Y-%m-%d")),
+            ),
+            DateFilter::OnOrAfter(date) => (
+                "on_or_after",
+                Some(date),
+                None,
+                format!("{} on or after {}", name, format_date(date)),
+            ),
+            DateFilter::OnOrBefore(date) => (
+                "on_or_before",
+                None,
+                Some(date),
+                format!("{} on or before {}", name, format_date(date)),
+            ),
+            DateFilter::Between(start, end) => (
+                "between",
+                Some(start),
+                Some(end),
+                format!("{} {}..{}", name, format_date(start), format_date(end)),
+            ),
+            DateFilter::Future => ("future", None, None, format!("{} in the future", name)),
+            DateFilter::Ancient => (
+                "ancient",
+                None,
+                Some(ancient_cutoff()),
+                format!("{} before {}", name, ancient_cutoff().format("%Y-%m-%d")),
+            ),
+        };
+
+        Self {
+            op,
+            from,
+            to,
+            display,
+        }
+    }
+}
+
+const PLACEHOLDER: &str = "{}";
+
+/// Every filter key the parser reads, aliases included.
+const KEYS: &[&str] = &[
+    "ext", "extension", "size", "modified", "date", "created", "accessed", "mode", "case", "is",
+    "type", "scope", "tag", "tags", "width", "height", "lang", "language", "subject", "from",
+    "to", "title", "under", "in", "mime", "category", "hidden", "depth", "name", "content",
+    "limit", "max", "offset", "context", "sort",
+];
+const KIND_VALUES: &[&str] =
+    &["file", "files", "dir", "dirs", "directory", "folder", "symlink", "link"];
+/// Keys taking one of a fixed set of values, and those values.
+const KEY_VALUES: &[(&str, &[&str])] = &[
+    ("mode", &["exact", "insensitive", "fuzzy", "regex", "glob"]),
+    ("case", &["sensitive", "insensitive"]),
+    ("scope", &["name", "path", "content", "all"]),
+    ("is", KIND_VALUES),
+    ("type", KIND_VALUES),
+    ("hidden", &["true", "false", "only"]),
+    ("category", &ExtensionCategory::NAMES),
+];
+
+/// A problem with one part of a query, as [`QueryParser::validate`] reports it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QueryDiagnostic {
+    /// The offending text, as written in the input.
+    pub token: String,
+    /// Byte range of `token` in the input.
+    pub span: Range<usize>,
+    pub message: String,
+    /// What was probably meant instead of `token`, e.g. `mode` for the key `mod`.
+    pub suggestion: Option<String>,
+}
+
+impl QueryDiagnostic {
+    fn new(input: &str, span: Range<usize>, message: String, suggestion: Option<&str>) -> Self {
+        Self {
+            token: input[span.clone()].to_string(),
+            span,
+            message,
+            suggestion: suggestion.map(str::to_string),
+        }
+    }
+}
+
+impl fmt::Display for QueryDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)?;
+        match &self.suggestion {
+            Some(suggestion) => write!(f, ", did you mean '{}'?", suggestion),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The candidate `word` is most likely a typo of: one edit away for short words, two for
+/// longer ones. `None` when `word` is a candidate itself.
+fn closest<'a>(word: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    let word = word.to_lowercase();
+    let allowed = if word.chars().count() <= 4 { 1 } else { 2 };
+    candidates
+        .iter()
+        .map(|candidate| (levenshtein_distance(&word, candidate), *candidate))
+        .min_by_key(|(distance, _)| *distance)
+        .filter(|(distance, _)| *distance > 0 && *distance <= allowed)
+        .map(|(_, candidate)| candidate)
+}
+
+/// A whitespace-separated piece of a query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token {
+    text: String,
+    /// Where the token is in the input, quotes and escapes included.
+    span: Range<usize>,
+    /// The whole token was one quoted phrase, so it is pattern text rather than syntax.
+    quoted: bool,
+    /// The token's first colon was written `\:`, so it has no filter key.
+    colon_escaped: bool,
+}
+
+/// Splits `input` on whitespace, except inside double quotes. Quotes group words and are
+/// removed, so `modified:"last week"` is one token; `\"` is a literal quote and `\:` a
+/// literal colon anywhere.
+fn tokenize(input: &str) -> std::result::Result<Vec<Token>, QueryDiagnostic> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    loop {
+        while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        let Some(&(start, _)) = chars.peek() else {
+            break;
+        };
+
+        let mut text = String::new();
+        let mut opened_at = None;
+        let mut phrases = 0;
+        let mut ends_with_phrase = false;
+        let mut colon = None;
+        while let Some((at, c)) = chars.next_if(|(_, c)| opened_at.is_some() || !c.is_whitespace())
+        {
+            ends_with_phrase = false;
+            match c {
+                '\\' if chars.next_if(|(_, next)| *next == '"').is_some() => text.push('"'),
+                '\\' if chars.next_if(|(_, next)| *next == ':').is_some() => {
+                    colon.get_or_insert(true);
+                    text.push(':');
+                }
+                ':' => {
+                    colon.get_or_insert(false);
+                    text.push(':');
+                }
+                '"' if opened_at.is_some() => {
+                    opened_at = None;
+                    phrases += 1;
+                    ends_with_phrase = true;
+                }
+                '"' => opened_at = Some(at),
+                c => text.push(c),
+            }
+        }
+
+        if let Some(at) = opened_at {
+            let message = format!("Unterminated quote in query: {}", &input[at..]);
+            return Err(QueryDiagnostic::new(input, at..input.len(), message, None));
+        }
+        let end = chars.peek().map_or(input.len(), |(at, _)| *at);
+        tokens.push(Token {
+            quoted: input[start..].starts_with('"') && phrases == 1 && ends_with_phrase,
+            colon_escaped: colon == Some(true),
+            span: start..end,
+            text,
+        });
+    }
+
+    Ok(tokens)
 }
 
 pub struct QueryParser;
 
+/// A parse that carried on past invalid tokens, so all of them can be reported.
+struct Parsed {
+    query: Query,
+    errors: Vec<QueryDiagnostic>,
+    /// Tokens read as pattern text whose key is one edit or two from a real key.
+    typos: Vec<QueryDiagnostic>,
+}
+
 impl QueryParser {
     pub fn parse(input: &str) -> Result<Query> {
+        Self::parse_tokens(input, |_| None)
+    }
+
+    /// Parses `input` like [`QueryParser::parse`], but reports every problem with where it
+    /// is and, when there is an obvious one, a fix. Tokens whose key looks like a misspelt
+    /// filter key, such as `mod:regex`, are reported too, even though `parse` reads them as
+    /// pattern text; write `mod\:regex` to search for the text.
+    pub fn validate(input: &str) -> std::result::Result<Query, Vec<QueryDiagnostic>> {
+        let parsed = Self::parse_all(input, |_| None);
+        let mut diagnostics = parsed.errors;
+        diagnostics.extend(parsed.typos);
+        if diagnostics.is_empty() {
+            return Ok(parsed.query);
+        }
+        diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+        Err(diagnostics)
+    }
+
+    /// Parses `template` with each `{}` filled, in order, from `params`.
+    ///
+    /// Parameters are substituted after the template has been split into tokens, so a value
+    /// always lands in the pattern verbatim: whitespace, colons, quotes and leading dashes in
+    /// it are never read as filter syntax. Any template token holding a placeholder is
+    /// pattern text as well, e.g. `{} ext:log` filters on `log` but `ext:{}` does not.
+    pub fn parse_template(template: &str, params: &[&str]) -> Result<Query> {
+        let placeholders = template.matches(PLACEHOLDER).count();
+        if placeholders != params.len() {
+            return Err(SearchError::InvalidQuery(format!(
+                "Query template has {} placeholder(s) but {} parameter(s) were given",
+                placeholders,
+                params.len()
+            )));
+        }
+
+        let mut params = params.iter();
+        Self::parse_tokens(template, |part| {
+            if !part.contains(PLACEHOLDER) {
+                return None;
+            }
+            let mut pieces = part.split(PLACEHOLDER);
+            let mut literal = pieces.next().unwrap_or_default().to_string();
+            for piece in pieces {
+                literal.push_str(params.next().copied().unwrap_or_default());
+                literal.push_str(piece);
+            }
+            Some(literal)
+        })
+    }
+
+    /// `literal` sees each token first; when it returns text, that text joins the pattern
+    /// as is instead of being parsed.
+    fn parse_tokens(input: &str, literal: impl FnMut(&str) -> Option<String>) -> Result<Query> {
+        let parsed = Self::parse_all(input, literal);
+        match parsed.errors.into_iter().next() {
+            Some(error) => Err(SearchError::InvalidQuery(error.message)),
+            None => Ok(parsed.query),
+        }
+    }
+
+    fn parse_all(input: &str, mut literal: impl FnMut(&str) -> Option<String>) -> Parsed {
         let mut query = Query::new(String::new());
-        let parts: Vec<&str> = input.split_whitespace().collect();
+        let mut errors = Vec::new();
+        let mut typos = Vec::new();
+        let parts = match tokenize(input) {
+            Ok(parts) => parts,
+            Err(error) => {
+                errors.push(error);
+                Vec::new()
+            }
+        };
 
         let mut pattern_parts = Vec::new();
+        let mut alternatives = Vec::new();
+        let mut content_parts = Vec::new();
+        let mut named = false;
         let mut i = 0;
 
         while i < parts.len() {
-            let part = parts[i];
+            let token = &parts[i];
+            let part = token.text.as_str();
+            let quoted = token.quoted;
 
-            if part.contains(':') {
-                let (key, value) = part.split_once(':').unwrap();
-                match key.to_lowercase().as_str() {
-                    "ext" | "extension" => {
-                        query.extensions = value.split(',').map(|s| s.to_string()).collect();
-                    }
-                    "size" => {
-                        query.size_filter = Self::parse_size_filter(value)?;
-                    }
-                    "modified" | "date" => {
-                        query.date_filter = Self::parse_date_filter(value)?;
-                    }
-                    "mode" => {
-                        query.match_mode = Self::parse_match_mode(value)?;
-                    }
-                    "scope" => {
-                        query.scope = Self::parse_scope(value)?;
+            let outcome = (|| -> Result<()> {
+                if quoted && part.is_empty() {
+                    // An empty phrase adds nothing.
+                } else if let Some(text) = literal(part)
+                    .or_else(|| quoted.then(|| part.to_string()))
+                    .or_else(|| Self::escaped(part))
+                {
+                    pattern_parts.push((text, false));
+                } else if token.colon_escaped {
+                    match Self::negated(part) {
+                        Some(negated) => query.excluded_terms.push(negated.to_string()),
+                        None => pattern_parts.push((part.to_string(), true)),
                     }
-                    "limit" | "max" => {
-                        if let Ok(max) = value.parse::<usize>() {
-                            query.max_results = Some(max);
+                } else if part == "OR" || part == "|" {
+                    alternatives.push(std::mem::take(&mut pattern_parts));
+                } else if let Some(negated) = Self::negated(part) {
+                    Self::parse_negation(&mut query, part, negated)?;
+                } else if part.contains(':') {
+                    let (key, value) = part.split_once(':').unwrap();
+                    match key.to_lowercase().as_str() {
+                        "ext" | "extension" => {
+                            query.extensions = value.split(',').map(|s| s.to_string()).collect();
+                        }
+                        "size" => {
+                            query.size_filters.extend(Self::parse_size_filter(value)?);
+                        }
+                        "modified" | "date" => {
+                            Self::push_date_filter(&mut query, DateField::Modified, value)?;
+                        }
+                        "created" => {
+                            Self::push_date_filter(&mut query, DateField::Created, value)?;
+                        }
+                        "accessed" => {
+                            Self::push_date_filter(&mut query, DateField::Accessed, value)?;
+                        }
+                        "mode" => {
+                            query.match_mode = Self::parse_match_mode(value)?;
+                        }
+                        "case" => {
+                            query.case_sensitive = Some(Self::parse_case(value)?);
+                        }
+                        "is" | "type" => {
+                            query.kinds = parse_entry_kinds(value).ok_or_else(|| {
+                                SearchError::InvalidQuery(format!("Invalid entry kind: {}", value))
+                            })?;
+                        }
+                        "scope" => {
+                            query.scope = Self::parse_scope(value)?;
+                        }
+                        "tag" | "tags" => {
+                            query.tags = value
+                                .split(',')
+                                .filter(|tag| !tag.is_empty())
+                                .map(str::to_string)
+                                .collect();
+                        }
+                        "width" => {
+                            query.dimensions.width =
+                                Some(Self::parse_dimension_bound(key, value)?);
+                        }
+                        "height" => {
+                            query.dimensions.height =
+                                Some(Self::parse_dimension_bound(key, value)?);
+                        }
+                        "lang" | "language" => {
+                            query.languages = value
+                                .split(',')
+                                .filter(|lang| !lang.is_empty())
+                                .map(str::to_lowercase)
+                                .collect();
+                        }
+                        "subject" | "from" | "to" | "title" if !value.is_empty() => {
+                            query.fields.push(FieldFilter {
+                                name: key.to_lowercase(),
+                                value: value.to_string(),
+                            });
+                        }
+                        "under" | "in" if !value.is_empty() => {
+                            query.under = Some(PathBuf::from(value));
+                        }
+                        "mime" if !value.is_empty() => {
+                            query.mime_filter = Some(value.to_ascii_lowercase());
+                        }
+                        "category" => {
+                            query.category = Some(Self::parse_category(value)?);
+                        }
+                        "hidden" => {
+                            query.hidden = Some(HiddenMode::parse(value).ok_or_else(|| {
+                                SearchError::InvalidQuery(format!(
+                                    "Invalid hidden mode: {} (expected true, false or only)",
+                                    value
+                                ))
+                            })?);
+                        }
+                        "depth" => {
+                            query.depth_filter = Some(Self::parse_depth_filter(value)?);
+                        }
+                        "name" if !value.is_empty() => {
+                            pattern_parts.push((value.to_string(), true));
+                            named = true;
+                        }
+                        "content" if !value.is_empty() => {
+                            content_parts.push(value.to_string());
+                        }
+                        "limit" | "max" => match value.parse::<usize>() {
+                            Ok(max) => query.max_results = Some(max),
+                            Err(_) => query
+                                .warnings
+                                .push(format!("Ignored invalid limit: {}", part)),
+                        },
+                        "offset" => match value.parse::<usize>() {
+                            Ok(offset) => query.offset = offset,
+                            Err(_) => query
+                                .warnings
+                                .push(format!("Ignored invalid offset: {}", part)),
+                        },
+                        "context" => match value.parse::<usize>() {
+                            Ok(lines) => query.context_lines = lines,
+                            Err(_) => query
+                                .warnings
+                                .push(format!("Ignored invalid context: {}", part)),
+                        },
+                        "sort" => match SortKey::parse(value) {
+                            Some(sort) => query.sort_by = Some(sort),
+                            None => query.warnings.push(format!("Ignored invalid sort: {}", part)),
+                        },
+                        _ => {
+                            typos.extend(Self::misspelt_key(input, token, key));
+                            pattern_parts.push((part.to_string(), true));
                         }
                     }
-                    _ => {
-                        pattern_parts.push(part);
-                    }
+                } else {
+                    pattern_parts.push((part.to_string(), true));
                 }
-            } else {
-                pattern_parts.push(part);
-            }
+                Ok(())
+            })();
 
+            if let Err(error) = outcome {
+                errors.push(Self::diagnose(input, token, error));
+            }
             i += 1;
         }
 
-        query.pattern = pattern_parts.join(" ");
+        alternatives.push(pattern_parts);
+        // An `OR` with nothing on one side, e.g. a trailing one, adds no alternative.
+        let mut patterns: Vec<String> = alternatives
+            .into_iter()
+            .map(|parts| Self::alternative_pattern(&mut query, parts))
+            .filter(|pattern| !pattern.is_empty())
+            .collect();
+        if patterns.len() > 1 {
+            if !query.terms.is_empty() {
+                query.terms.clear();
+                query
+                    .warnings
+                    .push("Ignored term boosts: they don't apply between OR alternatives".to_string());
+            }
+            query.pattern = patterns.join(" OR ");
+            query.alternatives = patterns;
+        } else {
+            query.pattern = patterns.pop().unwrap_or_default();
+        }
 
-        if query.pattern.is_empty() {
-            return Err(SearchError::InvalidQuery(
-                "Query pattern cannot be empty".to_string(),
-            ));
+        if named && query.scope != SearchScope::Name {
+            query
+                .warnings
+                .push("Ignored scope: name: terms always search names".to_string());
+            query.scope = SearchScope::Name;
+        }
+        if !content_parts.is_empty() {
+            query = query.with_content_pattern(content_parts.join(" "));
         }
 
-        Ok(query)
+        if errors.is_empty()
+            && query.pattern.is_empty()
+            && query.kinds.is_empty()
+            && query.tags.is_empty()
+            && query.dimensions.is_empty()
+            && query.fields.is_empty()
+            && query.mime_filter.is_none()
+            && query.category.is_none()
+            && query.hidden.is_none()
+            && query.under.is_none()
+            && query.depth_filter.is_none()
+        {
+            let message = "Query pattern cannot be empty".to_string();
+            errors.push(QueryDiagnostic::new(input, 0..input.len(), message, None));
+        }
+
+        Parsed {
+            query,
+            errors,
+            typos,
+        }
     }
 
-    fn parse_size_filter(value: &str) -> Result<Option<SizeFilter>> {
-        if value.starts_with('>') {
-            let size_str = value.trim_start_matches('>');
-            if let Some(size) = parse_size(size_str) {
-                return Ok(Some(SizeFilter::GreaterThan(size)));
+    /// Points an error in `token` at its value when it has one, suggesting the closest valid
+    /// value for keys that take one of a fixed set.
+    fn diagnose(input: &str, token: &Token, error: SearchError) -> QueryDiagnostic {
+        let message = match error {
+            SearchError::InvalidQuery(message) => message,
+            error => error.to_string(),
+        };
+        let raw = &input[token.span.clone()];
+        let text = Self::negated(&token.text).unwrap_or(&token.text);
+        let Some((key, value)) = text.split_once(':').filter(|(_, value)| !value.is_empty())
+        else {
+            return QueryDiagnostic::new(input, token.span.clone(), message, None);
+        };
+
+        // Quotes or escapes in the value leave the whole token to point at.
+        let span = match raw.ends_with(value) {
+            true => token.span.end - value.len()..token.span.end,
+            false => token.span.clone(),
+        };
+        let key = key.to_lowercase();
+        let suggestion = KEY_VALUES
+            .iter()
+            .find(|(name, _)| *name == key)
+            .filter(|_| !value.contains(','))
+            .and_then(|(_, values)| closest(value, values));
+        QueryDiagnostic::new(input, span, message, suggestion)
+    }
+
+    /// Reports a `key:` that is no filter key but is close to one. Keys shorter than three
+    /// letters, such as drive letters, are left alone.
+    fn misspelt_key(input: &str, token: &Token, key: &str) -> Option<QueryDiagnostic> {
+        if key.len() < 3 || !key.chars().all(|c| c.is_ascii_alphabetic()) {
+            return None;
+        }
+        let suggestion = closest(key, KEYS)?;
+        let raw = &input[token.span.clone()];
+        let span = match raw.starts_with(key) {
+            true => token.span.start..token.span.start + key.len(),
+            false => token.span.clone(),
+        };
+        let message = format!("Unknown key '{}'", key);
+        Some(QueryDiagnostic::new(input, span, message, Some(suggestion)))
+    }
+
+    fn alternative_pattern(query: &mut Query, parts: Vec<(String, bool)>) -> String {
+        // A `^` is pattern syntax in regular expressions and globs, never a boost.
+        match query.match_mode {
+            MatchMode::Regex | MatchMode::Glob => parts
+                .into_iter()
+                .map(|(text, _)| text)
+                .collect::<Vec<_>>()
+                .join(" "),
+            _ => Self::take_boosts(query, parts),
+        }
+    }
+
+    /// Pattern text written with `\-` or `\!` for a leading dash or bang, so it isn't read
+    /// as a negation. Quoted phrases are told apart by the tokenizer.
+    fn escaped(part: &str) -> Option<String> {
+        part.strip_prefix('\\')
+            .filter(|rest| rest.starts_with(['-', '!']))
+            .map(str::to_string)
+    }
+
+    /// What follows a leading `-` or `!`, if anything does.
+    fn negated(part: &str) -> Option<&str> {
+        part.strip_prefix(['-', '!'])
+            .filter(|rest| !rest.is_empty())
+    }
+
+    fn parse_negation(query: &mut Query, part: &str, negated: &str) -> Result<()> {
+        let Some((key, value)) = negated.split_once(':') else {
+            query.excluded_terms.push(negated.to_string());
+            return Ok(());
+        };
+
+        match key.to_lowercase().as_str() {
+            "ext" | "extension" => {
+                query.excluded_extensions.extend(
+                    value
+                        .split(',')
+                        .filter(|ext| !ext.is_empty())
+                        .map(str::to_string),
+                );
             }
-        } else if value.starts_with('<') {
-            let size_str = value.trim_start_matches('<');
-            if let Some(size) = parse_size(size_str) {
-                return Ok(Some(SizeFilter::LessThan(size)));
+            "is" | "type" => {
+                let kinds = parse_entry_kinds(value).ok_or_else(|| {
+                    SearchError::InvalidQuery(format!("Invalid entry kind: {}", value))
+                })?;
+                query.excluded_kinds.extend(kinds);
             }
-        } else if value.contains("..") {
-            let parts: Vec<&str> = value.split("..").collect();
-            if parts.len() == 2 {
-                if let (Some(min), Some(max)) = (parse_size(parts[0]), parse_size(parts[1])) {
-                    return Ok(Some(SizeFilter::Range(min, max)));
+            // Other filters can't be turned around, and leaving one out would widen the
+            // search without saying so.
+            key if KEYS.contains(&key) => {
+                return Err(SearchError::InvalidQuery(format!(
+                    "Cannot negate {}: only ext:, is: and terms can be negated",
+                    part
+                )));
+            }
+            // Like any other unknown key, it is text to match.
+            _ => query.excluded_terms.push(negated.to_string()),
+        }
+        Ok(())
+    }
+
+    /// Splits `term^weight` boosts off the typed pattern parts and returns the pattern.
+    /// `recency^x` and `depth^x` set score weights instead of joining the pattern; a weight
+    /// that isn't a non-negative number is ignored with a warning.
+    fn take_boosts(query: &mut Query, parts: Vec<(String, bool)>) -> String {
+        let mut terms = Vec::new();
+        let mut boosted = false;
+
+        for (part, typed) in parts {
+            let boost = part
+                .rsplit_once('^')
+                .filter(|(text, _)| typed && !text.is_empty());
+            let Some((text, weight)) = boost else {
+                terms.push(WeightedTerm {
+                    text: part,
+                    weight: 1.0,
+                });
+                continue;
+            };
+
+            let weight = weight
+                .parse::<f64>()
+                .ok()
+                .filter(|weight| weight.is_finite() && *weight >= 0.0);
+            if weight.is_none() {
+                query.warnings.push(format!("Ignored invalid boost: {}", part));
+            }
+
+            match text.to_lowercase().as_str() {
+                "recency" | "recent" => {
+                    query.weight_overrides.recency = weight.or(query.weight_overrides.recency);
+                }
+                "depth" => {
+                    query.weight_overrides.path_depth =
+                        weight.or(query.weight_overrides.path_depth);
+                }
+                "frecency" => {
+                    if weight.is_some() {
+                        query
+                            .warnings
+                            .push(format!("Ignored {}: results have no frecency score", part));
+                    }
+                }
+                _ => {
+                    boosted |= weight.is_some();
+                    terms.push(WeightedTerm {
+                        text: text.to_string(),
+                        weight: weight.unwrap_or(1.0),
+                    });
                 }
             }
-        } else if let Some(size) = parse_size(value) {
-            return Ok(Some(SizeFilter::Exact(size)));
         }
 
-        Err(SearchError::InvalidQuery(format!(
-            "Invalid size filter: {}",
-            value
-        )))
+        let pattern = terms
+            .iter()
+            .map(|term| term.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        if boosted {
+            query.terms = terms;
+        }
+        pattern
+    }
+
+    fn parse_size_filter(value: &str) -> Result<Option<SizeFilter>> {
+        let size = |text: &str| {
+            if text.trim_start().starts_with('-') {
+                return Err(SearchError::InvalidQuery(format!(
+                    "Invalid size filter: {} (sizes cannot be negative)",
+                    value
+                )));
+            }
+            parse_size(text).ok_or_else(|| {
+                SearchError::InvalidQuery(format!("Invalid size filter: {}", value))
+            })
+        };
+
+        let filter = if let Some(min) = value.strip_prefix(">=") {
+            SizeFilter::AtLeast(size(min)?)
+        } else if let Some(max) = value.strip_prefix("<=") {
+            SizeFilter::AtMost(size(max)?)
+        } else if let Some(min) = value.strip_prefix('>') {
+            SizeFilter::GreaterThan(size(min)?)
+        } else if let Some(max) = value.strip_prefix('<') {
+            SizeFilter::LessThan(size(max)?)
+        } else if let Some((min, max)) = value.split_once("..") {
+            SizeFilter::Range(size(min)?, size(max)?)
+        } else {
+            SizeFilter::Exact(size(value)?)
+        };
+        Ok(Some(filter))
+    }
+
+    /// Pixel counts as `>N`, `<N`, `N..M` or `N`.
+    fn parse_dimension_bound(key: &str, value: &str) -> Result<DimensionBound> {
+        let pixels = |text: &str| text.trim().parse::<u32>().ok();
+        let bound = if let Some(min) = value.strip_prefix('>') {
+            pixels(min).map(DimensionBound::GreaterThan)
+        } else if let Some(max) = value.strip_prefix('<') {
+            pixels(max).map(DimensionBound::LessThan)
+        } else if let Some((min, max)) = value.split_once("..") {
+            pixels(min)
+                .zip(pixels(max))
+                .map(|(min, max)| DimensionBound::Range(min, max))
+        } else {
+            pixels(value).map(DimensionBound::Exact)
+        };
+
+        bound.ok_or_else(|| {
+            SearchError::InvalidQuery(format!("Invalid {} filter: {}", key, value))
+        })
+    }
+
+    /// Directory depths as `>N`, `<N` or `N`.
+    fn parse_depth_filter(value: &str) -> Result<DepthFilter> {
+        let depth = |text: &str| text.trim().parse::<usize>().ok();
+        let filter = if let Some(min) = value.strip_prefix('>') {
+            depth(min).map(DepthFilter::GreaterThan)
+        } else if let Some(max) = value.strip_prefix('<') {
+            depth(max).map(DepthFilter::LessThan)
+        } else {
+            depth(value).map(DepthFilter::Exact)
+        };
+
+        filter.ok_or_else(|| {
+            SearchError::InvalidQuery(format!("Invalid depth filter: {}", value))
+        })
+    }
+
+    fn push_date_filter(query: &mut Query, field: DateField, value: &str) -> Result<()> {
+        if let Some(filter) = Self::parse_date_filter(value)? {
+            query.date_filters.push((field, filter));
+        }
+        Ok(())
     }
 
     fn parse_date_filter(value: &str) -> Result<Option<DateFilter>> {
-        if value.starts_with('>') || value.starts_with("after:") {
+        if value.eq_ignore_ascii_case("future") {
+            return Ok(Some(DateFilter::Future));
+        }
+        if value.eq_ignore_ascii_case("ancient") {
+            return Ok(Some(DateFilter::Ancient));
+        }
+
+        // A bare date after `<=` includes its whole day, as the end of a range does.
+        if let Some(date_str) = value.strip_prefix(">=") {
+            if let Some(date) = parse_date(date_str) {
+                return Ok(Some(DateFilter::OnOrAfter(date)));
+            }
+        } else if let Some(date_str) = value.strip_prefix("<=") {
+            if let Some(date) = parse_end_date(date_str) {
+                return Ok(Some(DateFilter::OnOrBefore(date)));
+            }
+        } else if value.starts_with('>') || value.starts_with("after:") {
             let date_str = value.trim_start_matches('>').trim_start_matches("after:");
-            if let Some(date) = parse_relative_date(date_str) {
+            if let Some(date) = parse_date(date_str) {
                 return Ok(Some(DateFilter::After(date)));
             }
         } else if value.starts_with('<') || value.starts_with("before:") {
             let date_str = value.trim_start_matches('<').trim_start_matches("before:");
-            if let Some(date) = parse_relative_date(date_str) {
+            if let Some(date) = parse_date(date_str) {
                 return Ok(Some(DateFilter::Before(date)));
             }
         } else if value.contains("..") {
             let parts: Vec<&str> = value.split("..").collect();
             if parts.len() == 2 {
                 if let (Some(start), Some(end)) =
-                    (parse_relative_date(parts[0]), parse_relative_date(parts[1]))
+                    (parse_date(parts[0]), parse_end_date(parts[1]))
                 {
                     return Ok(Some(DateFilter::Between(start, end)));
                 }
             }
-        } else if let Some(date) = parse_relative_date(value) {
+        } else if let Some(date) = parse_date(value) {
             return Ok(Some(DateFilter::On(date)));
         }
 
@@ -188,6 +1400,27 @@ impl QueryParser {
         }
     }
 
+    fn parse_case(value: &str) -> Result<bool> {
+        match value.to_lowercase().as_str() {
+            "sensitive" => Ok(true),
+            "insensitive" => Ok(false),
+            _ => Err(SearchError::InvalidQuery(format!(
+                "Invalid case: {} (expected sensitive or insensitive)",
+                value
+            ))),
+        }
+    }
+
+    fn parse_category(value: &str) -> Result<ExtensionCategory> {
+        ExtensionCategory::from_name(value).ok_or_else(|| {
+            SearchError::InvalidQuery(format!(
+                "Invalid category: {} (expected one of: {})",
+                value,
+                ExtensionCategory::NAMES.join(", ")
+            ))
+        })
+    }
+
     fn parse_scope(value: &str) -> Result<SearchScope> {
         match value.to_lowercase().as_str() {
             "name" => Ok(SearchScope::Name),
@@ -224,14 +1457,131 @@ mod tests {
     fn test_parse_query_with_size() {
         let query = QueryParser::parse("test size:>1MB").unwrap();
         assert_eq!(query.pattern, "test");
-        assert!(query.size_filter.is_some());
+        assert!(!query.size_filters.is_empty());
+    }
+
+    #[test]
+    fn test_parse_fractional_and_negative_sizes() {
+        let query = QueryParser::parse("video size:>1.5GB").unwrap();
+        assert_eq!(query.size_filters, vec![SizeFilter::GreaterThan(1_610_612_736)]);
+        let query = QueryParser::parse("video size:\"0.5 MB..1.5 MB\"").unwrap();
+        assert_eq!(query.size_filters, vec![SizeFilter::Range(524_288, 1_572_864)]);
+
+        let err = QueryParser::parse("video size:>-5MB").unwrap_err();
+        assert!(err.to_string().contains("cannot be negative"), "{}", err);
+        assert!(QueryParser::parse("video size:1.5.5MB").is_err());
+    }
+
+    #[test]
+    fn test_parse_inclusive_comparisons() {
+        use chrono::TimeZone;
+
+        let query = QueryParser::parse("log size:>=4096").unwrap();
+        assert_eq!(query.size_filters, vec![SizeFilter::AtLeast(4096)]);
+        let query = QueryParser::parse("log size:<=1KB").unwrap();
+        assert_eq!(query.size_filters, vec![SizeFilter::AtMost(1024)]);
+        let effective = EffectiveQuery::from(&query);
+        assert_eq!(effective.size[0].display, "size <= 1.00 KB");
+        assert!(QueryParser::parse("log size:>=-1KB").is_err());
+
+        let query = QueryParser::parse("log modified:>=2024-01-01").unwrap();
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(query.date_filters, vec![(DateField::Modified, DateFilter::OnOrAfter(start))]);
+        let query = QueryParser::parse("log created:<=2024-01-01").unwrap();
+        let end = Utc.with_ymd_and_hms(2024, 1, 1, 23, 59, 59).unwrap();
+        assert_eq!(query.date_filters, vec![(DateField::Created, DateFilter::OnOrBefore(end))]);
+        assert!(QueryParser::parse("log modified:>=soon").is_err());
     }
 
     #[test]
     fn test_parse_query_with_date() {
         let query = QueryParser::parse("test modified:today").unwrap();
         assert_eq!(query.pattern, "test");
-        assert!(query.date_filter.is_some());
+        assert!(!query.date_filters.is_empty());
+    }
+
+    #[test]
+    fn test_parse_absolute_date_filters() {
+        use chrono::TimeZone;
+
+        let day = |d| Utc.with_ymd_and_hms(2024, 6, d, 0, 0, 0).unwrap();
+        let query = QueryParser::parse("report modified:>2024-06-01").unwrap();
+        assert_eq!(query.date_filters, vec![(DateField::Modified, DateFilter::After(day(1)))]);
+
+        let query = QueryParser::parse("report modified:<2024-06-01T14:30").unwrap();
+        let afternoon = Utc.with_ymd_and_hms(2024, 6, 1, 14, 30, 0).unwrap();
+        let before = DateFilter::Before(afternoon);
+        assert_eq!(query.date_filters, vec![(DateField::Modified, before)]);
+
+        let query = QueryParser::parse("report created:2024-06-01..2024-06-03").unwrap();
+        let end = day(3) + chrono::Duration::seconds(86_399);
+        let between = DateFilter::Between(day(1), end);
+        assert_eq!(query.date_filters, vec![(DateField::Created, between)]);
+
+        let query = QueryParser::parse("report modified:2024-06-02").unwrap();
+        assert_eq!(query.date_filters, vec![(DateField::Modified, DateFilter::On(day(2)))]);
+
+        let invalid = ["modified:2024-13-45", "modified:>2024-02-30", "modified:2024-06-01..soon"];
+        for invalid in invalid {
+            assert!(QueryParser::parse(&format!("report {}", invalid)).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_parse_skewed_date_tokens() {
+        let query = QueryParser::parse("backup modified:future").unwrap();
+        assert_eq!(query.pattern, "backup");
+        assert!(matches!(query.date_filters[..], [(DateField::Modified, DateFilter::Future)]));
+        let modified = query.effective().modified.remove(0);
+        assert_eq!((modified.op, modified.display.as_str()), ("future", "modified in the future"));
+
+        let query = QueryParser::parse("backup modified:ANCIENT").unwrap();
+        assert!(matches!(query.date_filters[..], [(_, DateFilter::Ancient)]));
+        let modified = query.effective().modified.remove(0);
+        assert_eq!(modified.display, "modified before 1980-01-01");
+        assert_eq!(modified.to, Some(ancient_cutoff()));
+    }
+
+    #[test]
+    fn test_parse_created_and_accessed_tokens() {
+        let query = QueryParser::parse("invoice created:>30d").unwrap();
+        assert_eq!(query.pattern, "invoice");
+        assert!(matches!(query.date_filters[..], [(DateField::Created, DateFilter::After(_))]));
+        let effective = query.effective();
+        assert!(effective.modified.is_empty());
+        assert!(effective.created[0].display.starts_with("created after "));
+
+        let query = QueryParser::parse("notes accessed:<1w").unwrap();
+        assert!(matches!(query.date_filters[..], [(DateField::Accessed, _)]));
+        assert_eq!(query.effective().accessed[0].op, "before");
+
+        assert!(QueryParser::parse("notes created:someday").is_err());
+    }
+
+    #[test]
+    fn test_repeated_size_and_date_filters_all_apply() {
+        let query = QueryParser::parse("video size:>1MB size:<100MB").unwrap();
+        assert_eq!(
+            query.size_filters,
+            vec![SizeFilter::GreaterThan(1_048_576), SizeFilter::LessThan(104_857_600)]
+        );
+        let effective = query.effective();
+        assert_eq!(effective.size.len(), 2);
+        assert_eq!(effective.size[1].display, "size < 100.00 MB");
+
+        // Bounds that can never both hold still parse; they just match nothing.
+        let query = QueryParser::parse("video size:>10MB size:<1MB").unwrap();
+        assert_eq!(query.size_filters.len(), 2);
+
+        let query = QueryParser::parse("notes accessed:<1w created:future modified:>2d").unwrap();
+        let fields: Vec<_> = query.date_filters.iter().map(|(field, _)| *field).collect();
+        assert_eq!(fields, vec![DateField::Accessed, DateField::Created, DateField::Modified]);
+        let effective = query.effective();
+        assert_eq!(effective.created[0].op, "future");
+        assert_eq!(effective.accessed[0].op, "before");
+        assert_eq!(effective.modified[0].op, "after");
+
+        assert!(QueryParser::parse("notes created:someday").is_err());
     }
 
     #[test]
@@ -241,13 +1591,702 @@ mod tests {
         assert_eq!(query.match_mode, MatchMode::Fuzzy);
     }
 
+    #[test]
+    fn test_parse_case_flag() {
+        let query = QueryParser::parse("Report case:sensitive").unwrap();
+        assert_eq!(query.pattern, "Report");
+        assert_eq!(query.case_sensitive, Some(true));
+        assert_eq!(query.match_mode, MatchMode::CaseInsensitive);
+
+        let query = QueryParser::parse("report mode:regex case:Insensitive").unwrap();
+        assert_eq!(query.case_sensitive, Some(false));
+        assert_eq!(QueryParser::parse("report").unwrap().case_sensitive, None);
+
+        assert!(QueryParser::parse("report case:upper").is_err());
+    }
+
     #[test]
     fn test_parse_complex_query() {
         let query = QueryParser::parse("test ext:rs,txt size:>100KB modified:today mode:fuzzy").unwrap();
         assert_eq!(query.pattern, "test");
         assert_eq!(query.extensions.len(), 2);
-        assert!(query.size_filter.is_some());
-        assert!(query.date_filter.is_some());
+        assert!(!query.size_filters.is_empty());
+        assert!(!query.date_filters.is_empty());
         assert_eq!(query.match_mode, MatchMode::Fuzzy);
     }
+
+    #[test]
+    fn test_effective_query_snapshot() {
+        use chrono::TimeZone;
+
+        let after = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let query = QueryParser::parse(
+            "report ext:rs,toml size:1KB..10MB mode:fuzzy scope:path limit:many",
+        )
+        .unwrap()
+        .with_date_filter(DateFilter::After(after));
+
+        let json = serde_json::to_value(query.effective()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "pattern": "report",
+                "mode": "fuzzy",
+                "case_sensitive": null,
+                "scope": "path",
+                "extensions": ["rs", "toml"],
+                "kinds": [],
+                "tags": [],
+                "size": [{
+                    "op": "range",
+                    "min": 1024,
+                    "max": 10485760,
+                    "display": "size 1.00 KB..10.00 MB"
+                }],
+                "modified": [{
+                    "op": "after",
+                    "from": "2024-01-01T00:00:00Z",
+                    "to": null,
+                    "display": "modified after 2024-01-01 00:00:00 UTC"
+                }],
+                "created": [],
+                "accessed": [],
+                "width": null,
+                "height": null,
+                "under": null,
+                "mime": null,
+                "category": null,
+                "hidden": null,
+                "depth": null,
+                "languages": [],
+                "content": null,
+                "fields": [],
+                "excluded_extensions": [],
+                "excluded_kinds": [],
+                "excluded_terms": [],
+                "terms": [],
+                "alternatives": [],
+                "weights": {
+                    "name_match": 0.5,
+                    "path_depth": 0.2,
+                    "recency": 0.3,
+                    "match_position": 0.3,
+                    "word_boundary": 0.3,
+                    "name_length": 0.1
+                },
+                "max_results": null,
+                "offset": 0,
+                "context_lines": 0,
+                "sort": null,
+                "warnings": ["Ignored invalid limit: limit:many"]
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_dimension_tokens() {
+        let query = QueryParser::parse("width:>4000 height:2000..3000").unwrap();
+        assert!(query.pattern.is_empty());
+        assert_eq!(query.dimensions.width, Some(DimensionBound::GreaterThan(4000)));
+        assert_eq!(query.dimensions.height, Some(DimensionBound::Range(2000, 3000)));
+
+        let query = QueryParser::parse("beach Width:<640").unwrap();
+        assert_eq!(query.pattern, "beach");
+        assert_eq!(query.dimensions.width, Some(DimensionBound::LessThan(640)));
+        let effective = serde_json::to_value(query.effective()).unwrap();
+        assert_eq!(effective["width"]["display"], "width < 640px");
+        assert!(effective["height"].is_null());
+
+        assert!(QueryParser::parse("width:wide").is_err());
+        assert!(QueryParser::parse("height:>4k").is_err());
+    }
+
+    #[test]
+    fn test_parse_entry_kind_tokens() {
+        let query = QueryParser::parse("is:dir,symlink backup").unwrap();
+        assert_eq!(query.pattern, "backup");
+        assert_eq!(query.kinds, vec![EntryKind::Directory, EntryKind::Symlink]);
+
+        let query = QueryParser::parse("is:dir").unwrap();
+        assert!(query.pattern.is_empty());
+
+        let query = QueryParser::parse("src type:dir -type:symlink").unwrap();
+        assert_eq!(query.pattern, "src");
+        assert_eq!(query.kinds, vec![EntryKind::Directory]);
+        assert_eq!(query.excluded_kinds, vec![EntryKind::Symlink]);
+
+        assert!(QueryParser::parse("is:socket test").is_err());
+    }
+
+    #[test]
+    fn test_parse_tag_tokens() {
+        let query = QueryParser::parse("tag:tax-2023,receipts invoice").unwrap();
+        assert_eq!(query.pattern, "invoice");
+        assert_eq!(query.tags, vec!["tax-2023".to_string(), "receipts".to_string()]);
+
+        let query = QueryParser::parse("tag:tax-2023").unwrap();
+        assert!(query.pattern.is_empty());
+        assert!(QueryParser::parse("tag:").is_err());
+    }
+
+    #[test]
+    fn test_parse_language_tokens() {
+        let query = QueryParser::parse("deploy lang:Bash,python").unwrap();
+        assert_eq!(query.pattern, "deploy");
+        assert_eq!(query.languages, vec!["bash".to_string(), "python".to_string()]);
+        assert_eq!(query.effective().languages, query.languages);
+
+        assert!(QueryParser::parse("lang:perl").is_err());
+    }
+
+    #[test]
+    fn test_parse_document_field_tokens() {
+        let query = QueryParser::parse("Subject:invoice from:acme.com title:").unwrap();
+        assert_eq!(query.pattern, "title:");
+        assert_eq!(
+            query.fields,
+            vec![
+                FieldFilter {
+                    name: "subject".to_string(),
+                    value: "invoice".to_string()
+                },
+                FieldFilter {
+                    name: "from".to_string(),
+                    value: "acme.com".to_string()
+                },
+            ]
+        );
+        assert_eq!(query.effective().fields, query.fields);
+
+        assert!(QueryParser::parse("to:billing").unwrap().pattern.is_empty());
+    }
+
+    #[test]
+    fn test_parse_under_token() {
+        let query = QueryParser::parse("main under:/home/dev/app ext:rs").unwrap();
+        assert_eq!(query.pattern, "main");
+        assert_eq!(query.under, Some(PathBuf::from("/home/dev/app")));
+        assert_eq!(query.effective().under, query.under);
+
+        let query = QueryParser::parse("main under:").unwrap();
+        assert_eq!(query.under, None);
+        assert_eq!(query.pattern, "main under:");
+
+        let query = QueryParser::parse(r#"main.rs in:"/home/me/my project""#).unwrap();
+        assert_eq!(query.pattern, "main.rs");
+        assert_eq!(query.under, Some(PathBuf::from("/home/me/my project")));
+    }
+
+    #[test]
+    fn test_parse_name_and_content_tokens() {
+        let query = QueryParser::parse("name:test content:timeout").unwrap();
+        assert_eq!(query.pattern, "test");
+        assert_eq!(query.scope, SearchScope::Name);
+        assert_eq!(query.content_pattern.as_deref(), Some("timeout"));
+        assert_eq!(query.effective().content, query.content_pattern);
+
+        let query = QueryParser::parse(r#"test content:"connection timeout" content:retry"#)
+            .unwrap();
+        assert_eq!(query.pattern, "test");
+        assert_eq!(query.content_pattern.as_deref(), Some("connection timeout retry"));
+
+        // With nothing else to match, content terms are a plain content search.
+        let query = QueryParser::parse("content:timeout ext:rs").unwrap();
+        assert_eq!(query.pattern, "timeout");
+        assert_eq!(query.scope, SearchScope::Content);
+        assert_eq!(query.content_pattern, None);
+
+        let query = QueryParser::parse("name:test scope:path content:timeout").unwrap();
+        assert_eq!(query.scope, SearchScope::Name);
+        assert_eq!(query.warnings.len(), 1);
+
+        let query = QueryParser::parse("src scope:path content:timeout").unwrap();
+        assert_eq!(query.scope, SearchScope::Path);
+        assert_eq!(query.content_pattern.as_deref(), Some("timeout"));
+    }
+
+    #[test]
+    fn test_parse_mime_token() {
+        let query = QueryParser::parse("logo mime:Image/*").unwrap();
+        assert_eq!(query.pattern, "logo");
+        assert_eq!(query.mime_filter.as_deref(), Some("image/*"));
+        assert_eq!(query.effective().mime, query.mime_filter);
+
+        let query = QueryParser::parse("mime:application/pdf").unwrap();
+        assert!(query.pattern.is_empty());
+        assert_eq!(query.mime_filter.as_deref(), Some("application/pdf"));
+
+        let query = QueryParser::parse("report mime:").unwrap();
+        assert_eq!(query.mime_filter, None);
+        assert_eq!(query.pattern, "report mime:");
+    }
+
+    #[test]
+    fn test_filters_alone_make_a_query() {
+        for input in [
+            "category:source",
+            "hidden:only",
+            "in:/srv/docs",
+            "depth:<2",
+            "is:dir",
+            "mime:text/*",
+        ] {
+            let query = QueryParser::parse(input).unwrap();
+            assert!(query.pattern.is_empty(), "{}", input);
+        }
+        assert!(QueryParser::parse("limit:5").is_err());
+        assert!(QueryParser::parse("sort:size").is_err());
+    }
+
+    #[test]
+    fn test_parse_category_token() {
+        let query = QueryParser::parse("logo category:Image").unwrap();
+        assert_eq!(query.pattern, "logo");
+        assert_eq!(query.category, Some(ExtensionCategory::Image));
+        let effective = serde_json::to_value(query.effective()).unwrap();
+        assert_eq!(effective["category"], "image");
+
+        let query = QueryParser::parse("main category:code").unwrap();
+        assert_eq!(query.category, Some(ExtensionCategory::SourceCode));
+
+        let err = QueryParser::parse("logo category:pictures").err().unwrap();
+        assert!(matches!(err, SearchError::InvalidQuery(_)));
+        assert!(err.to_string().contains("source, document, image"), "{}", err);
+        assert!(QueryParser::parse("logo category:").is_err());
+    }
+
+    #[test]
+    fn test_parse_hidden_token() {
+        let query = QueryParser::parse("config hidden:only").unwrap();
+        assert_eq!(query.pattern, "config");
+        assert_eq!(query.hidden, Some(HiddenMode::Only));
+        let effective = serde_json::to_value(query.effective()).unwrap();
+        assert_eq!(effective["hidden"], "only");
+
+        let hidden = |input: &str| QueryParser::parse(input).unwrap().hidden;
+        assert_eq!(hidden("config hidden:FALSE"), Some(HiddenMode::Exclude));
+        assert_eq!(hidden("config hidden:true"), Some(HiddenMode::Include));
+        assert_eq!(hidden("config"), None);
+
+        let err = QueryParser::parse("config hidden:maybe").err().unwrap();
+        assert!(matches!(err, SearchError::InvalidQuery(_)));
+    }
+
+    #[test]
+    fn test_parse_depth_token() {
+        let depth = |input: &str| QueryParser::parse(input).unwrap().depth_filter;
+        assert_eq!(depth("readme depth:<3"), Some(DepthFilter::LessThan(3)));
+        assert_eq!(depth("readme depth:>1"), Some(DepthFilter::GreaterThan(1)));
+        assert_eq!(depth("readme depth:2"), Some(DepthFilter::Exact(2)));
+
+        let query = QueryParser::parse("readme depth:<3").unwrap();
+        assert_eq!(query.pattern, "readme");
+        let effective = serde_json::to_value(query.effective()).unwrap();
+        assert_eq!(effective["depth"]["display"], "depth < 3");
+
+        assert!(QueryParser::parse("readme depth:deep").is_err());
+        assert!(QueryParser::parse("readme depth:<-1").is_err());
+    }
+
+    #[test]
+    fn test_parse_boosts() {
+        let query = QueryParser::parse("report^2 draft^0.5 notes ext:docx").unwrap();
+        assert_eq!(query.pattern, "report draft notes");
+        let weights: Vec<_> = query
+            .terms
+            .iter()
+            .map(|term| (term.text.as_str(), term.weight))
+            .collect();
+        assert_eq!(weights, vec![("report", 2.0), ("draft", 0.5), ("notes", 1.0)]);
+        assert_eq!(query.extensions, vec!["docx"]);
+        assert!(query.warnings.is_empty());
+
+        let query = QueryParser::parse("budget Recency^0 depth^1.5").unwrap();
+        assert_eq!(query.pattern, "budget");
+        assert!(query.terms.is_empty());
+        assert_eq!(query.weight_overrides.recency, Some(0.0));
+        assert_eq!(query.weight_overrides.path_depth, Some(1.5));
+        let weights = query.effective().weights;
+        assert_eq!((weights.recency, weights.path_depth, weights.name_match), (0.0, 1.5, 0.5));
+
+        let query = QueryParser::parse("^start end$ mode:regex").unwrap();
+        assert_eq!(query.pattern, "^start end$");
+        assert!(QueryParser::parse("recency^0").is_err());
+    }
+
+    #[test]
+    fn test_parse_malformed_boosts() {
+        let query =
+            QueryParser::parse("report^-1 draft^high memo^ depth^NaN recency^x frecency^2")
+                .unwrap();
+        assert_eq!(query.pattern, "report draft memo");
+        assert!(query.terms.is_empty());
+        assert_eq!(query.weight_overrides, WeightOverrides::default());
+        assert_eq!(
+            query.warnings,
+            vec![
+                "Ignored invalid boost: report^-1",
+                "Ignored invalid boost: draft^high",
+                "Ignored invalid boost: memo^",
+                "Ignored invalid boost: depth^NaN",
+                "Ignored invalid boost: recency^x",
+                "Ignored frecency^2: results have no frecency score",
+            ]
+        );
+
+        let query = QueryParser::parse_template("{} draft^2", &["a^3"]).unwrap();
+        let texts: Vec<_> = query.terms.iter().map(|term| term.text.as_str()).collect();
+        assert_eq!(texts, vec!["a^3", "draft"]);
+    }
+
+    #[test]
+    fn test_parse_negations() {
+        let query = QueryParser::parse("report -ext:tmp,bak -draft !is:dir").unwrap();
+        assert_eq!(query.pattern, "report");
+        assert_eq!(query.excluded_extensions, vec!["tmp", "bak"]);
+        assert_eq!(query.excluded_terms, vec!["draft"]);
+        assert_eq!(query.excluded_kinds, vec![EntryKind::Directory]);
+        assert!(query.extensions.is_empty());
+        assert_eq!(query.effective().excluded_terms, query.excluded_terms);
+
+        for input in ["report -size:>1MB", "report !modified:today", "report -under:/tmp"] {
+            let err = QueryParser::parse(input).unwrap_err();
+            assert!(matches!(err, SearchError::InvalidQuery(_)), "{}", input);
+            assert!(err.to_string().contains("Cannot negate"), "{}: {}", input, err);
+        }
+        let query = QueryParser::parse("report -todo:x").unwrap();
+        assert_eq!(query.excluded_terms, vec!["todo:x"]);
+        assert!(query.warnings.is_empty());
+        assert!(QueryParser::parse("report -is:socket").is_err());
+        assert!(QueryParser::parse("-draft").is_err());
+    }
+
+    #[test]
+    fn test_escaped_dash_stays_in_pattern() {
+        for (input, pattern) in [
+            (r"\-draft", "-draft"),
+            ("\"-draft\"", "-draft"),
+            (r"\!draft", "!draft"),
+        ] {
+            let query = QueryParser::parse(input).unwrap();
+            assert_eq!(query.pattern, pattern, "{}", input);
+            assert!(query.excluded_terms.is_empty());
+        }
+
+        let query = QueryParser::parse("\"ext:rs\" - notes").unwrap();
+        assert_eq!(query.pattern, "ext:rs - notes");
+        assert!(query.extensions.is_empty());
+    }
+
+    #[test]
+    fn test_parse_context_token() {
+        let query = QueryParser::parse("todo context:2 scope:content").unwrap();
+        assert_eq!(query.pattern, "todo");
+        assert_eq!(query.context_lines, 2);
+        assert_eq!(query.effective().context_lines, 2);
+
+        let query = QueryParser::parse("todo context:some").unwrap();
+        assert_eq!(query.context_lines, 0);
+        assert_eq!(query.warnings, vec!["Ignored invalid context: context:some"]);
+    }
+
+    #[test]
+    fn test_parse_sort_token() {
+        let query = QueryParser::parse("report sort:-size").unwrap();
+        assert_eq!(query.pattern, "report");
+        assert_eq!(
+            query.sort_by,
+            Some(SortKey {
+                field: SortField::Size,
+                descending: true,
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(query.effective()).unwrap()["sort"],
+            "-size"
+        );
+
+        let query = QueryParser::parse("report sort:Modified").unwrap();
+        assert_eq!(query.sort_by.unwrap().to_string(), "modified");
+
+        let query = QueryParser::parse("report sort:color").unwrap();
+        assert_eq!(query.sort_by, None);
+        assert_eq!(query.warnings, vec!["Ignored invalid sort: sort:color"]);
+    }
+
+    #[test]
+    fn test_parse_quoted_phrases() {
+        for input in [
+            "\"annual report 2024\" ext:xlsx",
+            "ext:xlsx \"annual report 2024\"",
+            "ext:xlsx  \"annual report 2024\"  ",
+        ] {
+            let query = QueryParser::parse(input).unwrap();
+            assert_eq!(query.pattern, "annual report 2024", "{}", input);
+            assert_eq!(query.extensions, vec!["xlsx"]);
+            assert!(query.terms.is_empty());
+        }
+
+        let query =
+            QueryParser::parse("modified:\"last week\" \"q3 budget\" -\"draft copy\" size:>1KB")
+                .unwrap();
+        assert!(matches!(query.date_filters[..], [(_, DateFilter::On(_))]));
+        assert_eq!(query.pattern, "q3 budget");
+        assert_eq!(query.excluded_terms, vec!["draft copy"]);
+        assert!(!query.size_filters.is_empty());
+
+        let query = QueryParser::parse(r#"\"quoted\" "say \"hi\" now" """#).unwrap();
+        assert_eq!(query.pattern, "\"quoted\" say \"hi\" now");
+
+        // Quotes inside a token only group words.
+        let query = QueryParser::parse("my\" \"notes").unwrap();
+        assert_eq!(query.pattern, "my notes");
+
+        let err = QueryParser::parse("ext:xlsx \"annual report").unwrap_err();
+        assert!(matches!(err, SearchError::InvalidQuery(_)));
+        assert!(err.to_string().contains("Unterminated quote"));
+        assert!(err.to_string().contains("\"annual report"));
+        assert!(QueryParser::parse("modified:\"last week").is_err());
+    }
+
+    #[test]
+    fn test_escaped_colons_round_trip() {
+        for text in [
+            "notes:today.txt",
+            "size:chart.png",
+            "mode:regex",
+            r"C:\temp",
+            "D:/backups/2024",
+            "https://example.com/files?id=3",
+            "file:///tmp/report.txt",
+        ] {
+            for written in [format!("\"{}\"", text), text.replacen(':', r"\:", 1)] {
+                let query = QueryParser::parse(&format!("{} ext:txt", written)).unwrap();
+                assert_eq!(query.pattern, text, "{}", written);
+                assert_eq!(query.extensions, vec!["txt"]);
+                assert!(query.size_filters.is_empty());
+                assert_eq!(query.match_mode, MatchMode::CaseInsensitive);
+                assert!(query.warnings.is_empty());
+            }
+        }
+
+        // Only the first colon decides; later ones are part of the value.
+        let query = QueryParser::parse(r"report under:C:\temp\a\:b").unwrap();
+        assert_eq!(query.pattern, "report");
+        assert_eq!(query.under, Some(PathBuf::from(r"C:\temp\a:b")));
+
+        let query = QueryParser::parse(r"report -size\:chart \-ext\:rs").unwrap();
+        assert_eq!(query.pattern, "report -ext:rs");
+        assert_eq!(query.excluded_terms, vec!["size:chart"]);
+        assert!(query.excluded_extensions.is_empty());
+    }
+
+    #[test]
+    fn test_parse_or_alternatives() {
+        let query = QueryParser::parse("invoice OR receipt ext:pdf").unwrap();
+        assert_eq!(query.alternatives, vec!["invoice", "receipt"]);
+        assert_eq!(query.pattern, "invoice OR receipt");
+        assert_eq!(query.extensions, vec!["pdf"]);
+        assert_eq!(query.patterns(), vec!["invoice", "receipt"]);
+
+        let query = QueryParser::parse("tax invoice | receipt | bill OR").unwrap();
+        assert_eq!(query.alternatives, vec!["tax invoice", "receipt", "bill"]);
+
+        // Lowercase `or` and quoted operators are pattern text.
+        let query = QueryParser::parse("this or that \"OR\" \"|\"").unwrap();
+        assert!(query.alternatives.is_empty());
+        assert_eq!(query.pattern, "this or that OR |");
+
+        let query = QueryParser::parse("OR report").unwrap();
+        assert!(query.alternatives.is_empty());
+        assert_eq!(query.patterns(), vec!["report"]);
+
+        let query = QueryParser::parse("report^2 OR draft recency^0").unwrap();
+        assert_eq!(query.alternatives, vec!["report", "draft"]);
+        assert!(query.terms.is_empty());
+        assert_eq!(query.weight_overrides.recency, Some(0.0));
+        assert_eq!(
+            query.warnings,
+            vec!["Ignored term boosts: they don't apply between OR alternatives"]
+        );
+
+        assert!(QueryParser::parse("OR | ext:pdf").is_err());
+    }
+
+    #[test]
+    fn test_result_limit_against_cap() {
+        let limit = |query: &str| QueryParser::parse(query).unwrap().result_limit(100);
+
+        let within = limit("test limit:10").unwrap();
+        assert_eq!((within.effective_limit, within.limit_clamped), (10, false));
+
+        let above = limit("test limit:5000").unwrap();
+        assert_eq!((above.effective_limit, above.limit_clamped), (100, true));
+
+        let unset = limit("test").unwrap();
+        assert_eq!((unset.effective_limit, unset.limit_clamped), (100, false));
+
+        assert!(matches!(limit("test limit:0"), Err(SearchError::InvalidQuery(_))));
+
+        assert_eq!(QueryParser::parse("test offset:20").unwrap().offset, 20);
+        let skipped = QueryParser::parse("test offset:last").unwrap();
+        assert_eq!(skipped.offset, 0);
+        assert_eq!(skipped.warnings, vec!["Ignored invalid offset: offset:last"]);
+    }
+
+    #[test]
+    fn test_template_params_stay_literal() {
+        for param in [
+            "foo ext:exe",
+            "\"quoted\"",
+            "-dash",
+            r"C:\Users\me\My Documents\report.log",
+            "size:<1KB  mode:regex",
+        ] {
+            let query = QueryParser::parse_template("{} ext:log size:>10MB", &[param]).unwrap();
+            assert_eq!(query.pattern, param);
+            assert_eq!(query.extensions, vec!["log".to_string()]);
+            assert!(matches!(query.size_filters[..], [SizeFilter::GreaterThan(_)]));
+            assert_eq!(query.match_mode, MatchMode::CaseInsensitive);
+        }
+
+        let query = QueryParser::parse_template("pre{}post {} is:file", &["a b", "ext:rs"]).unwrap();
+        assert_eq!(query.pattern, "prea bpost ext:rs");
+        assert!(query.extensions.is_empty());
+        assert_eq!(query.kinds, vec![EntryKind::File]);
+    }
+
+    #[test]
+    fn test_template_placeholder_count_must_match() {
+        for (template, params) in [
+            ("{} ext:rs", &[][..]),
+            ("{} ext:rs", &["a", "b"][..]),
+            ("notes", &["a"][..]),
+        ] {
+            let err = QueryParser::parse_template(template, params).unwrap_err();
+            assert!(matches!(err, SearchError::InvalidQuery(_)), "{}", template);
+        }
+    }
+
+    #[test]
+    fn test_validate_reports_every_problem_with_its_span() {
+        let summary = |input: &str| {
+            QueryParser::validate(input)
+                .unwrap_err()
+                .into_iter()
+                .map(|d| (d.to_string(), d.token, d.span, d.suggestion))
+                .collect::<Vec<_>>()
+        };
+
+        let input = "mod:regex notes size:1XB is:dirr";
+        assert_eq!(
+            summary(input),
+            vec![
+                (
+                    "Unknown key 'mod', did you mean 'mode'?".to_string(),
+                    "mod".to_string(),
+                    0..3,
+                    Some("mode".to_string())
+                ),
+                (
+                    "Invalid size filter: 1XB".to_string(),
+                    "1XB".to_string(),
+                    21..24,
+                    None
+                ),
+                (
+                    "Invalid entry kind: dirr, did you mean 'dir'?".to_string(),
+                    "dirr".to_string(),
+                    28..32,
+                    Some("dir".to_string())
+                ),
+            ]
+        );
+        // `parse` stops at the first error and reads the misspelt key as pattern text.
+        assert_eq!(
+            QueryParser::parse(input).unwrap_err().to_string(),
+            "Invalid query: Invalid size filter: 1XB"
+        );
+        assert_eq!(QueryParser::parse("mod:regex").unwrap().pattern, "mod:regex");
+
+        assert_eq!(
+            summary("a mode:regx scope:\"pth\""),
+            vec![
+                (
+                    "Invalid match mode: regx, did you mean 'regex'?".to_string(),
+                    "regx".to_string(),
+                    7..11,
+                    Some("regex".to_string())
+                ),
+                (
+                    "Invalid search scope: pth, did you mean 'path'?".to_string(),
+                    "scope:\"pth\"".to_string(),
+                    12..23,
+                    Some("path".to_string())
+                ),
+            ]
+        );
+        assert_eq!(
+            summary("notes \"last week"),
+            vec![(
+                "Unterminated quote in query: \"last week".to_string(),
+                "\"last week".to_string(),
+                6..16,
+                None
+            )]
+        );
+        assert_eq!(summary("").len(), 1);
+        assert_eq!(summary("-is:fil")[0].3, Some("file".to_string()));
+
+        for valid in [
+            "notes ext:rs",
+            "C:\\temp",
+            "todo:today",
+            "https://example.com",
+            "mod\\:regex",
+            "\"mod:regex\"",
+            "title:",
+        ] {
+            let query = QueryParser::validate(valid).unwrap();
+            assert_eq!(
+                format!("{:?}", query),
+                format!("{:?}", QueryParser::parse(valid).unwrap()),
+                "{}",
+                valid
+            );
+        }
+    }
+
+    #[test]
+    fn test_queries_round_trip_through_serde() {
+        let query = QueryParser::parse(
+            "report^2 | draft ext:pdf -ext:tmp is:file -is:dir size:>1MB modified:>2024-06-01 \
+             created:future width:>800 depth:<3 under:/srv hidden:only mime:application/* \
+             category:document content:budget subject:q3 sort:-size context:2 recency^0.5 \
+             limit:20 offset:40 case:sensitive",
+        )
+        .unwrap();
+        let json = serde_json::to_value(&query).unwrap();
+        assert_eq!(json["size_filters"], serde_json::json!([{"greater_than": 1024 * 1024}]));
+        assert_eq!(json["date_filters"][1], serde_json::json!(["created", "future"]));
+        assert_eq!(
+            json["dimensions"],
+            serde_json::json!({"width": {"greater_than": 800}, "height": null})
+        );
+        assert_eq!(json["sort_by"], "-size");
+
+        let back: Query = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&back).unwrap(), json);
+        assert_eq!(
+            serde_json::to_value(back.effective()).unwrap(),
+            serde_json::to_value(query.effective()).unwrap()
+        );
+
+        // Left-out fields take their defaults; unknown sorts are rejected.
+        let minimal: Query =
+            serde_json::from_str(r#"{"pattern": "notes", "scope": "path"}"#).unwrap();
+        assert_eq!((minimal.pattern.as_str(), minimal.scope), ("notes", SearchScope::Path));
+        assert_eq!(minimal.match_mode, MatchMode::CaseInsensitive);
+        assert!(serde_json::from_str::<Query>(r#"{"sort_by": "colour"}"#).is_err());
+    }
 }
@@ -1,75 +1,251 @@
-use crate::core::types::{FileEntry, SearchResult};
-use crate::search::fuzzy::{similarity_score, starts_with_score, FuzzyMatcher};
+use crate::core::types::{FileEntry, MatchField, SearchResult};
+use crate::filters::{timestamp_sanity, TimestampSanity};
+use crate::search::fuzzy::{similarity_score, split_subwords, FuzzyMatcher};
+use crate::search::query::{Query, SortKey};
+use crate::utils::normalizer::{fold_case, TextNormalizer};
 use crate::utils::path::get_path_depth;
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use std::sync::Arc;
+
+/// The match score of a `scope:all` hit in a path, and at most in content: below that of
+/// any name containing the query under the default weights, so name hits rank first.
+const OTHER_FIELD_SCORE: f64 = 0.3;
+
+/// Sorts the first `keep` of `items` in `order` and drops the rest, which are left unsorted
+/// so that keeping a page of many items costs little more than one pass over them.
+pub(crate) fn keep_best<T>(
+    items: &mut Vec<T>,
+    keep: usize,
+    mut order: impl FnMut(&T, &T) -> Ordering,
+) {
+    if keep == 0 {
+        items.clear();
+        return;
+    }
+    if items.len() > keep {
+        items.select_nth_unstable_by(keep - 1, &mut order);
+        items.truncate(keep);
+    }
+    items.sort_by(order);
+}
 
 pub struct ResultRanker {
     fuzzy_matcher: FuzzyMatcher,
+    normalizer: Option<Arc<dyn TextNormalizer>>,
+    weights: ScoreWeights,
 }
 
 impl ResultRanker {
     pub fn new(fuzzy_threshold: f64) -> Self {
         Self {
             fuzzy_matcher: FuzzyMatcher::new(fuzzy_threshold),
+            normalizer: None,
+            weights: ScoreWeights::default(),
         }
     }
 
+    pub fn with_normalizer(mut self, normalizer: Arc<dyn TextNormalizer>) -> Self {
+        self.normalizer = Some(normalizer);
+        self
+    }
+
+    pub fn with_weights(mut self, weights: ScoreWeights) -> Self {
+        self.weights = weights;
+        self
+    }
+
     pub fn rank(&self, results: Vec<SearchResult>, query: &str) -> Vec<SearchResult> {
+        self.rank_by(results, usize::MAX, None, |file| self.calculate_score(file, query))
+    }
+
+    /// Ranks `results` for a parsed query, honoring its term boosts and weight overrides.
+    /// Only the first `keep` are returned, in the query's `sort:` order if it has one.
+    pub fn rank_query(
+        &self,
+        results: Vec<SearchResult>,
+        query: &Query,
+        keep: usize,
+    ) -> Vec<SearchResult> {
+        self.rank_by(results, keep, query.sort_by, |file| self.query_score(file, query))
+    }
+
+    fn rank_by(
+        &self,
+        results: Vec<SearchResult>,
+        keep: usize,
+        sort: Option<SortKey>,
+        score: impl Fn(&FileEntry) -> f64,
+    ) -> Vec<SearchResult> {
         let mut ranked_results = results;
 
         for result in &mut ranked_results {
-            result.score = self.calculate_score(&result.file, query);
+            result.score = score(&result.file);
         }
 
-        ranked_results.sort_by(|a, b| {
-            b.score
-                .partial_cmp(&a.score)
-                .unwrap_or(Ordering::Equal)
-                .then_with(|| a.file.name.cmp(&b.file.name))
-        });
+        // Ties fall back to the path, so the first `keep` are the same whatever their order.
+        match sort {
+            Some(sort) => keep_best(&mut ranked_results, keep, |a, b| sort.compare(a, b)),
+            None => keep_best(&mut ranked_results, keep, |a, b| {
+                b.score
+                    .partial_cmp(&a.score)
+                    .unwrap_or(Ordering::Equal)
+                    .then_with(|| a.file.name.cmp(&b.file.name))
+                    .then_with(|| a.file.path.cmp(&b.file.path))
+            }),
+        }
 
         ranked_results
     }
 
     pub fn calculate_score(&self, file: &FileEntry, query: &str) -> f64 {
         let name_match_score = self.name_match_score(&file.name, query);
+        self.combine(&self.weights, file, name_match_score)
+    }
+
+    /// Like [`calculate_score`](Self::calculate_score), but boosted terms are scored one by
+    /// one and averaged by their weights, `OR` alternatives count by the best of them, and
+    /// `recency^x`/`depth^x` replace those weights.
+    pub fn query_score(&self, file: &FileEntry, query: &Query) -> f64 {
+        let weights = self.weights.with_overrides(&query.weight_overrides);
+        self.combine(&weights, file, self.query_match_score(file, query))
+    }
+
+    /// Ranks results of a query with `content:` terms, where `content_score` says from 0 to
+    /// 1 how well an entry's content matched. It counts as much as the name match. Only the
+    /// first `keep` are returned, as by [`rank_query`](Self::rank_query).
+    pub fn rank_query_with_content(
+        &self,
+        results: Vec<SearchResult>,
+        query: &Query,
+        keep: usize,
+        content_score: impl Fn(&FileEntry) -> f64,
+    ) -> Vec<SearchResult> {
+        let weights = self.weights.with_overrides(&query.weight_overrides);
+        self.rank_by(results, keep, query.sort_by, |file| {
+            let match_score = (self.query_match_score(file, query) + content_score(file)) / 2.0;
+            self.combine(&weights, file, match_score)
+        })
+    }
+
+    /// Ranks results of a `scope:all` search by the field `field` says each matched in. Name
+    /// matches score as in [`rank_query`](Self::rank_query), path matches a fixed
+    /// [`OTHER_FIELD_SCORE`], and content matches that times `content_score`, from 0 to 1.
+    /// Only the first `keep` are returned.
+    pub fn rank_query_by_field(
+        &self,
+        results: Vec<SearchResult>,
+        query: &Query,
+        keep: usize,
+        field: impl Fn(&FileEntry) -> MatchField,
+        content_score: impl Fn(&FileEntry) -> f64,
+    ) -> Vec<SearchResult> {
+        let weights = self.weights.with_overrides(&query.weight_overrides);
+        self.rank_by(results, keep, query.sort_by, |file| {
+            let match_score = match field(file) {
+                MatchField::Name => self.query_match_score(file, query),
+                MatchField::Path => OTHER_FIELD_SCORE,
+                MatchField::Content => OTHER_FIELD_SCORE * content_score(file),
+            };
+            self.combine(&weights, file, match_score)
+        })
+    }
+
+    fn query_match_score(&self, file: &FileEntry, query: &Query) -> f64 {
+        if !query.alternatives.is_empty() {
+            // An entry is ranked by the alternative it matches best.
+            query
+                .alternatives
+                .iter()
+                .map(|pattern| self.name_match_score(&file.name, pattern))
+                .fold(0.0, f64::max)
+        } else if query.terms.is_empty() {
+            self.name_match_score(&file.name, &query.pattern)
+        } else {
+            let total: f64 = query.terms.iter().map(|term| term.weight).sum();
+            let weighted: f64 = query
+                .terms
+                .iter()
+                .filter(|term| term.weight > 0.0)
+                .map(|term| term.weight * self.name_match_score(&file.name, &term.text))
+                .sum();
+            if total > 0.0 {
+                weighted / total
+            } else {
+                0.0
+            }
+        }
+    }
+
+    fn combine(&self, weights: &ScoreWeights, file: &FileEntry, name_match_score: f64) -> f64 {
         let path_depth_penalty = self.path_depth_penalty(file);
         let recency_score = self.recency_score(file);
 
-        let weights = ScoreWeights {
-            name_match: 0.5,
-            path_depth: 0.2,
-            recency: 0.3,
-        };
-
         weights.name_match * name_match_score
             + weights.path_depth * path_depth_penalty
             + weights.recency * recency_score
     }
 
+    fn normalize(&self, text: &str) -> String {
+        match self.normalizer {
+            Some(ref normalizer) => fold_case(&normalizer.normalize(text)).into_owned(),
+            None => fold_case(text).into_owned(),
+        }
+    }
+
     fn name_match_score(&self, name: &str, query: &str) -> f64 {
-        let exact_match = if name.eq_ignore_ascii_case(query) {
-            1.0
-        } else {
-            0.0
-        };
+        let normalized_name = self.normalize(name);
+        let normalized_query = self.normalize(query);
+        let (name_form, query_form) = (normalized_name.as_str(), normalized_query.as_str());
 
-        if exact_match > 0.0 {
-            return exact_match;
+        if name_form == query_form {
+            return 1.0;
         }
 
-        let starts_with = starts_with_score(name, query);
-        if starts_with > 0.0 {
-            return 0.9 * starts_with;
-        }
+        let quality = match name_form.find(query_form) {
+            Some(offset) => {
+                let weights = &self.weights;
+                // Prefer the earliest match that starts a subword, so `a_final` is judged by
+                // `final` even when the query also occurs earlier inside another word.
+                let (offset, len, at_boundary) = match self.subword_match(name, query_form) {
+                    Some(boundary) => (boundary, name.len(), true),
+                    None => (offset, name_form.len(), false),
+                };
+                let position = offset as f64 / len.max(1) as f64;
+                let boundary_penalty = if at_boundary { 0.0 } else { 1.0 };
+
+                0.9 * (1.0
+                    - weights.match_position * position
+                    - weights.word_boundary * boundary_penalty)
+            }
+            None => {
+                let fuzzy_score = self.fuzzy_matcher.score_normalized(name_form, query_form);
+                if fuzzy_score > 0.0 {
+                    0.7 * fuzzy_score
+                } else {
+                    0.5 * similarity_score(name_form, query_form)
+                }
+            }
+        };
 
-        let fuzzy_score = self.fuzzy_matcher.score_normalized(name, query);
-        if fuzzy_score > 0.0 {
-            return 0.7 * fuzzy_score;
-        }
+        quality * self.length_factor(name_form, query_form)
+    }
 
-        let similarity = similarity_score(name, query);
-        0.5 * similarity
+    /// Byte offset in `name` of the first subword that the normalized query is a prefix of.
+    fn subword_match(&self, name: &str, query: &str) -> Option<usize> {
+        split_subwords(name)
+            .into_iter()
+            .map(|(offset, _)| offset)
+            .find(|&offset| self.normalize(&name[offset..]).starts_with(query))
+    }
+
+    /// Mildly favors names with less text beyond the query.
+    fn length_factor(&self, name: &str, query: &str) -> f64 {
+        let name_len = name.chars().count().max(1) as f64;
+        let query_len = query.chars().count() as f64;
+        let excess = (1.0 - query_len / name_len).clamp(0.0, 1.0);
+
+        1.0 - self.weights.name_length * excess
     }
 
     fn path_depth_penalty(&self, file: &FileEntry) -> f64 {
@@ -80,12 +256,18 @@ impl ResultRanker {
         1.0 - (normalized_depth * 0.5)
     }
 
+    // Skewed or implausibly old timestamps say nothing about recency, so they score like a
+    // missing one. Drift within the skew tolerance is clamped to now.
     fn recency_score(&self, file: &FileEntry) -> f64 {
         use chrono::Utc;
 
-        if let Some(modified) = file.modified_at {
-            let now = Utc::now();
-            let age = now.signed_duration_since(modified);
+        let now = Utc::now();
+        let modified = file
+            .modified_at
+            .filter(|modified| timestamp_sanity(*modified, now) == TimestampSanity::Plausible);
+
+        if let Some(modified) = modified {
+            let age = now.signed_duration_since(modified.min(now));
             let days = age.num_days() as f64;
 
             if days < 1.0 {
@@ -109,7 +291,7 @@ impl ResultRanker {
     pub fn boost_by_extension(&self, mut results: Vec<SearchResult>, preferred_extensions: &[String]) -> Vec<SearchResult> {
         for result in &mut results {
             if let Some(ref ext) = result.file.extension {
-                if preferred_extensions.iter().any(|e| e.eq_ignore_ascii_case(ext)) {
+                if preferred_extensions.iter().any(|e| fold_case(e) == fold_case(ext)) {
                     result.score *= 1.2;
                 }
             }
@@ -159,10 +341,54 @@ impl Default for ResultRanker {
     }
 }
 
-struct ScoreWeights {
-    name_match: f64,
-    path_depth: f64,
-    recency: f64,
+/// How the parts of a result's score are combined.
+///
+/// `name_match`, `path_depth` and `recency` weight the top-level components. The rest shape
+/// the name-match component: `match_position` penalizes matches further into the name,
+/// `word_boundary` penalizes matches that start mid-token, and `name_length` penalizes
+/// names that carry more text beyond the query.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ScoreWeights {
+    pub name_match: f64,
+    pub path_depth: f64,
+    pub recency: f64,
+    pub match_position: f64,
+    pub word_boundary: f64,
+    pub name_length: f64,
+}
+
+impl Default for ScoreWeights {
+    fn default() -> Self {
+        Self {
+            name_match: 0.5,
+            path_depth: 0.2,
+            recency: 0.3,
+            match_position: 0.3,
+            word_boundary: 0.3,
+            name_length: 0.1,
+        }
+    }
+}
+
+impl ScoreWeights {
+    /// These weights with the components a query sets for itself replaced.
+    pub fn with_overrides(mut self, overrides: &WeightOverrides) -> Self {
+        if let Some(recency) = overrides.recency {
+            self.recency = recency;
+        }
+        if let Some(path_depth) = overrides.path_depth {
+            self.path_depth = path_depth;
+        }
+        self
+    }
+}
+
+/// Top-level score weights set for one query by `recency^x` and `depth^x`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WeightOverrides {
+    pub recency: Option<f64>,
+    pub path_depth: Option<f64>,
 }
 
 #[cfg(test)]
@@ -224,6 +450,145 @@ mod tests {
         assert_eq!(ranked[0].file.name, "test.txt");
     }
 
+    #[test]
+    fn test_keep_best_orders_only_what_it_keeps() {
+        let mut items: Vec<u32> = (0..50).map(|i| (i * 37) % 50).collect();
+        keep_best(&mut items, 5, |a, b| b.cmp(a));
+        assert_eq!(items, vec![49, 48, 47, 46, 45]);
+
+        let mut items = vec![3, 1, 2];
+        keep_best(&mut items, 10, |a, b| a.cmp(b));
+        assert_eq!(items, vec![1, 2, 3]);
+
+        keep_best(&mut items, 0, |a, b| a.cmp(b));
+        assert!(items.is_empty());
+    }
+
+    /// Ranks `names` as siblings with identical timestamps, so only the name decides.
+    fn rank_names(query: &str, names: &[&str]) -> Vec<String> {
+        let ranker = ResultRanker::default()
+            .with_normalizer(Arc::new(crate::utils::normalizer::UnicodeNormalizer));
+        let modified = Utc::now();
+        let results = names
+            .iter()
+            .rev()
+            .map(|name| {
+                let mut file = create_test_file(name, &format!("/data/{}", name));
+                file.modified_at = Some(modified);
+                SearchResult {
+                    file,
+                    score: 0.0,
+                    snippet: None,
+                    matches: vec![],
+                }
+            })
+            .collect();
+
+        ranker
+            .rank(results, query)
+            .into_iter()
+            .map(|result| result.file.name)
+            .collect()
+    }
+
+    #[test]
+    fn test_relevance_regressions() {
+        let cases: &[(&str, &[&str])] = &[
+            (
+                "final",
+                &["final.pdf", "final_report.pdf", "report_final.pdf", "semifinal.pdf"],
+            ),
+            (
+                "final",
+                &["final.pdf", "final_consolidated_report_v7_revised.pdf"],
+            ),
+            (
+                "report",
+                &[
+                    "report.txt",
+                    "sales-report-final.txt",
+                    "quarterlyReport.txt",
+                    "misreported.txt",
+                ],
+            ),
+            ("parser", &["parser.rs", "HTMLParser.rs", "subparser.rs"]),
+            (
+                "2024",
+                &["2024-budget.xlsx", "budget2024.xlsx", "ref12024.csv"],
+            ),
+            (
+                "readme",
+                &["README", "readme.md", "readme_old.md", "myreadme.txt"],
+            ),
+            ("munchen", &["München.jpg", "Fotos-München.jpg", "Ummünchen.jpg"]),
+        ];
+
+        for (query, expected) in cases {
+            assert_eq!(rank_names(query, expected), *expected, "query {:?}", query);
+        }
+    }
+
+    #[test]
+    fn test_score_weights_are_configurable() {
+        let position_blind = ResultRanker::default().with_weights(ScoreWeights {
+            match_position: 0.0,
+            word_boundary: 0.0,
+            name_length: 0.0,
+            ..ScoreWeights::default()
+        });
+        let mut file = create_test_file("final_report.pdf", "/data/final_report.pdf");
+        let prefix = position_blind.calculate_score(&file, "report");
+        file.name = "report_final.pdf".to_string();
+
+        assert_eq!(prefix, position_blind.calculate_score(&file, "report"));
+    }
+
+    #[test]
+    fn test_recency_ignores_skewed_timestamps() {
+        let ranker = ResultRanker::default();
+        let now = Utc::now();
+        let score_at = |modified| {
+            let mut file = create_test_file("notes.txt", "/data/notes.txt");
+            file.modified_at = modified;
+            ranker.recency_score(&file)
+        };
+
+        assert_eq!(score_at(Some(now)), 1.0);
+        assert_eq!(score_at(Some(now + chrono::Duration::seconds(30))), 1.0);
+        assert_eq!(score_at(Some(now + chrono::Duration::days(3650))), score_at(None));
+        assert_eq!(score_at(Some(chrono::DateTime::<Utc>::UNIX_EPOCH)), score_at(None));
+        assert_eq!(score_at(Some(now - chrono::Duration::days(400))), 0.1);
+    }
+
+    #[test]
+    fn test_recency_override_applies_to_one_query() {
+        let ranker = ResultRanker::default();
+        let mut exact = create_test_file("report.txt", "/data/report.txt");
+        exact.modified_at = Some(Utc::now() - chrono::Duration::days(400));
+        let fuzzy = create_test_file("repport.txt", "/data/repport.txt");
+        let rank = |input: &str| {
+            let query = crate::search::QueryParser::parse(input).unwrap();
+            let results = [&exact, &fuzzy]
+                .into_iter()
+                .map(|file| SearchResult {
+                    file: file.clone(),
+                    score: 0.0,
+                    snippet: None,
+                    matches: vec![],
+                })
+                .collect();
+            ranker
+                .rank_query(results, &query, usize::MAX)
+                .into_iter()
+                .map(|result| result.file.name)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(rank("report"), vec!["repport.txt", "report.txt"]);
+        assert_eq!(rank("report recency^0"), vec!["report.txt", "repport.txt"]);
+        assert_eq!(ranker.weights, ScoreWeights::default());
+    }
+
     #[test]
     fn test_boost_by_extension() {
         let ranker = ResultRanker::default();
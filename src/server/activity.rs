@@ -0,0 +1,294 @@
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::mpsc::Receiver;
+use tokio::sync::broadcast;
+
+use crate::WatchEvent;
+
+/// Something the server did, as reported by `GET /activity` and the WebSocket.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ActivityEvent {
+    IndexStarted {
+        path: PathBuf,
+        quick: bool,
+    },
+    IndexFinished {
+        path: PathBuf,
+        indexed: usize,
+        skipped: usize,
+        /// False when some batches failed; what they held is not indexed.
+        complete: bool,
+        took_ms: u64,
+    },
+    UpdateFinished {
+        roots: Vec<PathBuf>,
+        added: usize,
+        updated: usize,
+        removed: usize,
+        /// Roots that could not be updated; the others were.
+        failed: usize,
+        took_ms: u64,
+    },
+    WatchStarted {
+        watch_id: String,
+        path: PathBuf,
+    },
+    WatchStopped {
+        watch_id: String,
+        path: PathBuf,
+    },
+    /// File events queued under a watched root were applied to the index.
+    WatchApplied {
+        path: PathBuf,
+        changes: usize,
+    },
+    WatchScanFinished {
+        path: PathBuf,
+        indexed: usize,
+    },
+    WatchDegraded {
+        path: PathBuf,
+        error: String,
+    },
+    WatchRecovered {
+        path: PathBuf,
+        caught_up: usize,
+    },
+    /// `task` is `rebuild_fts`, `backup`, or `background` for a pass of idle maintenance.
+    MaintenanceFinished {
+        task: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        took_ms: Option<u64>,
+    },
+    Error {
+        operation: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        path: Option<PathBuf>,
+        message: String,
+    },
+    /// Events after the reader's `since` that left the buffer before they were read. Its id
+    /// is that of the last one missed, so resuming from it continues without repeating it.
+    Gap {
+        missed: u64,
+    },
+}
+
+/// An [`ActivityEvent`] with its place in the feed. Ids count up from 1 for as long as the
+/// server runs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActivityRecord {
+    pub id: u64,
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub event: ActivityEvent,
+}
+
+/// The last `capacity` events, for readers that poll, and a channel for those that follow.
+pub struct ActivityFeed {
+    capacity: usize,
+    ring: Mutex<Ring>,
+    tx: broadcast::Sender<ActivityRecord>,
+}
+
+struct Ring {
+    records: VecDeque<ActivityRecord>,
+    last_id: u64,
+}
+
+impl ActivityFeed {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        let (tx, _) = broadcast::channel(capacity);
+        Self {
+            capacity,
+            ring: Mutex::new(Ring {
+                records: VecDeque::with_capacity(capacity),
+                last_id: 0,
+            }),
+            tx,
+        }
+    }
+
+    /// Adds an event, dropping the oldest once the buffer is full, and returns its id.
+    pub fn record(&self, event: ActivityEvent) -> u64 {
+        let mut ring = self.ring.lock();
+        ring.last_id += 1;
+        let record = ActivityRecord {
+            id: ring.last_id,
+            timestamp: Utc::now(),
+            event,
+        };
+        if ring.records.len() == self.capacity {
+            ring.records.pop_front();
+        }
+        ring.records.push_back(record.clone());
+        // Sent under the lock, so followers get records in id order and none twice.
+        let _ = self.tx.send(record);
+        ring.last_id
+    }
+
+    pub fn last_id(&self) -> u64 {
+        self.ring.lock().last_id
+    }
+
+    /// The buffered events after `since`, oldest first, led by a `gap` when some of them
+    /// were dropped; every buffered event for `None`. A `since` past the last id is from
+    /// before a restart and reads from the start.
+    pub fn since(&self, since: Option<u64>) -> Vec<ActivityRecord> {
+        self.ring.lock().since(since)
+    }
+
+    /// [`since`](Self::since), and a receiver of every event recorded after it.
+    pub fn subscribe(
+        &self,
+        since: Option<u64>,
+    ) -> (Vec<ActivityRecord>, broadcast::Receiver<ActivityRecord>) {
+        let ring = self.ring.lock();
+        (ring.since(since), self.tx.subscribe())
+    }
+}
+
+impl Ring {
+    fn since(&self, since: Option<u64>) -> Vec<ActivityRecord> {
+        let since = since.map(|since| if since > self.last_id { 0 } else { since });
+        let first_kept = self.records.front().map_or(self.last_id + 1, |record| record.id);
+
+        let mut records = Vec::new();
+        if let Some(since) = since.filter(|&since| since + 1 < first_kept) {
+            records.push(ActivityRecord {
+                id: first_kept - 1,
+                timestamp: Utc::now(),
+                event: ActivityEvent::Gap {
+                    missed: first_kept - 1 - since,
+                },
+            });
+        }
+        let after = since.unwrap_or(0);
+        records.extend(self.records.iter().filter(|record| record.id > after).cloned());
+        records
+    }
+}
+
+/// Records what a watch reports until it stops and the channel closes.
+pub fn forward_watch_events(events: Receiver<WatchEvent>, feed: &ActivityFeed) {
+    for event in events {
+        let event = match event {
+            WatchEvent::IndexChanged { root, changes } => ActivityEvent::WatchApplied {
+                path: root,
+                changes,
+            },
+            WatchEvent::InitialScanComplete { root, indexed } => ActivityEvent::WatchScanFinished {
+                path: root,
+                indexed,
+            },
+            WatchEvent::Degraded { root, error } => ActivityEvent::WatchDegraded {
+                path: root,
+                error,
+            },
+            WatchEvent::Recovered { root, caught_up } => ActivityEvent::WatchRecovered {
+                path: root,
+                caught_up,
+            },
+            WatchEvent::Failed { root, error } => ActivityEvent::Error {
+                operation: "watch".to_string(),
+                path: Some(root),
+                message: error,
+            },
+            WatchEvent::InitialScanProgress { .. } => continue,
+        };
+        feed.record(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn started(path: &str) -> ActivityEvent {
+        ActivityEvent::IndexStarted {
+            path: PathBuf::from(path),
+            quick: false,
+        }
+    }
+
+    fn ids(records: &[ActivityRecord]) -> Vec<u64> {
+        records.iter().map(|record| record.id).collect()
+    }
+
+    #[test]
+    fn test_ring_keeps_the_newest_events_in_order() {
+        let feed = ActivityFeed::new(3);
+        for i in 1..=5 {
+            assert_eq!(feed.record(started(&format!("/data/{}", i))), i);
+        }
+
+        let records = feed.since(None);
+        assert_eq!(ids(&records), [3, 4, 5]);
+        assert_eq!(records[0].event, started("/data/3"));
+        assert!(records.windows(2).all(|pair| pair[0].timestamp <= pair[1].timestamp));
+        assert_eq!(feed.last_id(), 5);
+    }
+
+    #[test]
+    fn test_resuming_reports_dropped_events_as_a_gap() {
+        let feed = ActivityFeed::new(3);
+        for i in 1..=5 {
+            feed.record(started(&format!("/data/{}", i)));
+        }
+
+        assert_eq!(ids(&feed.since(Some(3))), [4, 5]);
+        assert!(feed.since(Some(5)).is_empty());
+        // 2 is still buffered as far as the reader knows: nothing was missed.
+        assert_eq!(ids(&feed.since(Some(2))), [3, 4, 5]);
+
+        let resumed = feed.since(Some(1));
+        assert_eq!(ids(&resumed), [2, 3, 4, 5]);
+        assert_eq!(resumed[0].event, ActivityEvent::Gap { missed: 1 });
+        assert_eq!(feed.since(Some(0))[0].event, ActivityEvent::Gap { missed: 2 });
+
+        // Ids from before a restart read from the start.
+        assert_eq!(feed.since(Some(40))[0].event, ActivityEvent::Gap { missed: 2 });
+    }
+
+    #[test]
+    fn test_subscribers_get_what_follows_the_backlog() {
+        let feed = ActivityFeed::new(10);
+        feed.record(started("/data/1"));
+        let (backlog, mut rx) = feed.subscribe(Some(0));
+        feed.record(started("/data/2"));
+
+        assert_eq!(ids(&backlog), [1]);
+        assert_eq!(rx.try_recv().unwrap().id, 2);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_records_serialize_flat_with_their_type() {
+        let record = ActivityRecord {
+            id: 7,
+            timestamp: DateTime::parse_from_rfc3339("2024-06-01T08:00:00Z")
+                .unwrap()
+                .with_timezone(&Utc),
+            event: ActivityEvent::WatchStopped {
+                watch_id: "w1".to_string(),
+                path: PathBuf::from("/srv"),
+            },
+        };
+        let json = serde_json::to_value(&record).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "id": 7,
+                "timestamp": "2024-06-01T08:00:00Z",
+                "type": "watch_stopped",
+                "watch_id": "w1",
+                "path": "/srv",
+            })
+        );
+        assert_eq!(serde_json::from_value::<ActivityRecord>(json).unwrap(), record);
+    }
+}
@@ -1,55 +1,476 @@
-use actix_web::{web, HttpResponse, Result};
-use std::time::Instant;
-use std::sync::atomic::Ordering;
+use actix_web::http::{header, StatusCode};
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError, Result};
+use bytes::Bytes;
+use futures::StreamExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use std::sync::Arc;
+use tokio_stream::wrappers::ReceiverStream;
 use tracing::{info, error};
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 
-use crate::{Query, MatchMode, SearchScope, SizeFilter};
+use crate::{
+    AuditEventType, DateField, DateFilter, ErrorCode, FileEntry, Listing, Pagination, Progress,
+    Query,
+    QueryParser, MatchMode, RequestId, SearchEngine, SearchError, SearchScope, SizeFilter, SortKey,
+    WatchOptions,
+};
+use crate::core::ProgressCallback;
+use crate::server::activity::{self, ActivityEvent, ActivityFeed};
+use crate::server::config::SystemUser;
+use crate::server::federation::{self, PeerResults};
+use crate::server::fields::{FieldSelection, Selected, UnknownField};
 use crate::server::models::*;
+use crate::server::permissions::{self, Access};
+use crate::server::security::{validate_request_path, PathRejection};
 use crate::server::state::AppState;
 
 // ============ Search Endpoint ============
 
 pub async fn search(
     state: web::Data<AppState>,
+    http_req: HttpRequest,
+    request_id: RequestId,
+    params: web::Query<SearchParams>,
     req: web::Json<SearchRequest>,
 ) -> Result<HttpResponse> {
-    let start = Instant::now();
+    let query = build_query(&req, state.config.performance.max_search_results)?;
+    let peers = federation::selected_peers(
+        &state.config.peers,
+        &http_req,
+        req.federate,
+        params.federate.as_deref(),
+    )
+    .map_err(actix_web::error::ErrorBadRequest)?;
+
+    let remote = match peers {
+        Some(peers) => {
+            match request_access(&state, &http_req) {
+                // A denied request must not reach the peers.
+                Access::Denied => return Ok(unauthorized()),
+                // Peers answer for this server's key, not for the caller's account.
+                Access::Limited(_) => return Ok(federation_forbidden()),
+                Access::Unrestricted => {}
+            }
+            let timeout = Duration::from_millis(state.config.performance.peer_timeout_ms);
+            let sub_request = federation::sub_request(&req);
+            let remote = federation::fan_out(&peers, &sub_request, &request_id, timeout).await;
+            state.federation.record(remote.iter().map(|peer| &peer.status));
+            Some(remote)
+        }
+        None => None,
+    };
+    run_search(&state, &http_req, request_id, query, req.limit, req.fields.as_deref(), remote)
+}
+
+/// Runs a query sent as JSON, taking every filter it can express rather than the subset
+/// `SearchRequest` carries.
+pub async fn search_structured(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    request_id: RequestId,
+    req: web::Json<StructuredSearchRequest>,
+) -> Result<HttpResponse> {
+    let StructuredSearchRequest { query, fields } = req.into_inner();
+    let requested = query.max_results.unwrap_or_else(default_limit);
+    if requested == 0 {
+        return Err(actix_web::error::ErrorBadRequest(
+            "max_results must be greater than zero",
+        ));
+    }
+    let query = query.with_max_results(requested.min(state.config.performance.max_search_results));
+    run_search(&state, &http_req, request_id, query, requested, fields.as_deref(), None)
+}
 
-    info!("Search request: {:?}", req.query);
+/// Searches with `query`, whose `max_results` is the page size after the server's cap and
+/// `requested_limit` the one asked for, and answers with a page of results. For a federated
+/// search, the page is taken from this server's results merged with the `peers'` ones.
+fn run_search(
+    state: &AppState,
+    http_req: &HttpRequest,
+    request_id: RequestId,
+    query: Query,
+    requested_limit: usize,
+    fields: Option<&[String]>,
+    peers: Option<Vec<PeerResults>>,
+) -> Result<HttpResponse> {
+    let start = Instant::now();
 
-    // Build query from request
-    let query = build_query(&req)?;
+    info!("Search request: {:?}", state.sensitive.redact_path(&query.pattern));
+    let user = match request_access(state, http_req) {
+        Access::Denied => return Ok(unauthorized()),
+        access => access.user(),
+    };
+    let fields = match FieldSelection::parse::<FileResult, _>(fields) {
+        Ok(fields) => fields,
+        Err(unknown) => return Ok(invalid_field(unknown)),
+    };
+    let offset = query.offset;
 
     // Execute search
     let engine = state.engine.read();
-    let results = engine
-        .search_with_query(&query)
-        .map_err(|e| {
-            error!("Search failed: {}", e);
-            actix_web::error::ErrorInternalServerError(e)
-        })?;
+    let limit = engine
+        .result_limit(&query)
+        .map_err(actix_web::error::ErrorBadRequest)?;
+    // Peers' results are merged in before the page is taken, so a federated search runs
+    // from the first result to one past the page.
+    let searched = if peers.is_some() {
+        query
+            .clone()
+            .with_offset(0)
+            .with_max_results(offset.saturating_add(limit.effective_limit).saturating_add(1))
+    } else {
+        query.clone()
+    };
+    // A limited user only sees the files they could, and no one sees sensitive files when
+    // they are hidden. Those are left out before paging, so pages stay full and the total
+    // counts only what can be seen.
+    let hide_sensitive = state.config.security.hide_sensitive_in_api;
+    let visible = |mut entries: Vec<FileEntry>| {
+        if let Some(user) = user {
+            let ids: Vec<i64> = entries.iter().filter_map(|entry| entry.id).collect();
+            entries = permissions::filter_entries(entries, &engine.permissions(&ids)?, user);
+        }
+        if hide_sensitive {
+            entries.retain(|entry| !state.sensitive.is_sensitive(&entry.path));
+        }
+        Ok(entries)
+    };
+    let outcome = if user.is_some() || hide_sensitive {
+        engine.search_paged_visible(&searched, Some(&request_id), &visible)
+    } else {
+        engine.search_paged(&searched, Some(&request_id))
+    };
+    let outcome = match outcome {
+        Ok(outcome) => outcome,
+        Err(SearchError::FeatureUnavailable(message)) => {
+            return Ok(HttpResponse::NotImplemented().json(ErrorResponse {
+                error: "feature_unavailable".to_string(),
+                message,
+                code: 501,
+                details: None,
+            }));
+        }
+        Err(e) => {
+            error!("Search failed: {}", state.sensitive.redact_error(&e));
+            return Err(actix_web::Error::from(e));
+        }
+    };
 
-    let took_ms = start.elapsed().as_millis() as u64;
+    let content_partial = engine.content_coverage_partial(&query)?;
 
-    // Record metrics
-    state.metrics.record_search(took_ms);
+    let mut total = outcome.total_matched;
+    let total_estimated = outcome.total_estimated;
+    let mut results = outcome.results;
+    if let Some(user) = user {
+        // Previews and matching lines of files the user cannot read are dropped.
+        let ids: Vec<i64> = results.iter().filter_map(|result| result.file.id).collect();
+        results = permissions::filter_results(results, &engine.permissions(&ids)?, user);
+    }
 
     // Convert to API response
-    let total = results.len();
-    let has_more = total > req.limit;
-    let results: Vec<FileResult> = results
-        .into_iter()
-        .skip(req.offset)
-        .take(req.limit)
-        .map(convert_result)
-        .collect();
+    let mut results: Vec<FileResult> = results.into_iter().map(convert_result).collect();
+    if fields.fields().contains(&"link_target") {
+        let ids: Vec<i64> = results.iter().filter_map(|result| result.id).collect();
+        let mut targets = engine.link_targets(&ids)?;
+        for result in &mut results {
+            result.link_target = result.id.and_then(|id| targets.remove(&id));
+        }
+    }
+    drop(engine);
+
+    let peers = peers.map(|mut peers| {
+        let merged = federation::merge(std::mem::take(&mut results), &mut peers, query.sort_by);
+        results = merged.into_iter().skip(offset).take(limit.effective_limit).collect();
+        total += peers.iter().map(|peer| peer.status.total).sum::<usize>();
+        peers.into_iter().map(|peer| peer.status).collect()
+    });
+
+    let took_ms = start.elapsed().as_millis() as u64;
+    // Past an estimated total, a full page may still be followed by more.
+    let has_more = offset.saturating_add(results.len()) < total
+        || total_estimated && results.len() == limit.effective_limit;
 
     Ok(HttpResponse::Ok().json(SearchResponse {
-        results,
+        results: Selected::new(results, fields),
         total,
+        total_estimated,
         took_ms,
         has_more,
+        effective_limit: limit.effective_limit,
+        limit_clamped: requested_limit > limit.effective_limit,
+        effective_query: query.effective(),
+        content_partial,
+        peers,
+    }))
+}
+
+/// Parses a query written in the query syntax without running it. Problems come back as a
+/// `400` with every one, its byte range and any suggested fix in `details.diagnostics`.
+pub async fn validate_query(req: web::Json<ValidateQueryRequest>) -> Result<HttpResponse> {
+    match QueryParser::validate(&req.query) {
+        Ok(query) => Ok(HttpResponse::Ok().json(ValidateQueryResponse {
+            effective_query: query.effective(),
+        })),
+        Err(diagnostics) => Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: ErrorCode::InvalidQuery.as_str().to_string(),
+            message: diagnostics[0].to_string(),
+            code: 400,
+            details: Some(serde_json::json!(QueryDiagnostics { diagnostics })),
+        })),
+    }
+}
+
+// ============ Smart Folder Endpoint ============
+
+pub async fn smart_folder(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    name: web::Path<String>,
+    params: web::Query<ListingParams>,
+) -> Result<HttpResponse> {
+    if params.limit == 0 {
+        return Err(actix_web::error::ErrorBadRequest(
+            "limit must be greater than zero",
+        ));
+    }
+    let fields = match FieldSelection::parse_list::<ListingEntry>(params.fields.as_deref()) {
+        Ok(fields) => fields,
+        Err(unknown) => return Ok(invalid_field(unknown)),
+    };
+
+    let user = match request_access(&state, &http_req) {
+        Access::Denied => return Ok(unauthorized()),
+        access => access.user(),
+    };
+
+    let engine = state.engine.read();
+    let pagination = Pagination {
+        limit: Some(params.limit),
+        offset: params.offset,
+    };
+    // A limited user's page is taken from the entries they can see.
+    let requested = if user.is_some() { Pagination::default() } else { pagination };
+    let listing = match engine.run_saved_as_listing(&name, requested) {
+        Ok(listing) => match user {
+            Some(user) => visible_listing(&engine, listing, pagination, user)?,
+            None => listing,
+        },
+        Err(SearchError::SavedSearchNotFound(name)) => {
+            return Ok(HttpResponse::NotFound().json(ErrorResponse {
+                error: "not_found".to_string(),
+                message: format!("Saved search '{}' not found", name),
+                code: 404,
+                details: None,
+            }))
+        }
+        Err(e) => {
+            error!("Smart folder failed: {}", e);
+            return Err(actix_web::Error::from(e));
+        }
+    };
+
+    let etag = format!("\"{}\"", listing.etag);
+    let not_modified = http_req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag));
+    if not_modified {
+        return Ok(HttpResponse::NotModified()
+            .insert_header((header::ETAG, etag))
+            .finish());
+    }
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::ETAG, etag))
+        .json(ListingResponse {
+            name: listing.name,
+            has_more: listing.offset + listing.entries.len() < listing.total,
+            entries: Selected::new(
+                listing.entries.into_iter().map(convert_entry).collect(),
+                fields,
+            ),
+            total: listing.total,
+            offset: listing.offset,
+            limit: params.limit,
+            sort: listing.sort,
+        }))
+}
+
+// ============ Thumbnail Endpoint ============
+
+/// Smallest and largest thumbnail edge, in pixels, the endpoint will render.
+const THUMBNAIL_SIZES: std::ops::RangeInclusive<u32> = 16..=1024;
+
+pub async fn thumbnail(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    id: web::Path<i64>,
+    params: web::Query<ThumbnailParams>,
+) -> Result<HttpResponse> {
+    let id = id.into_inner();
+    let size = params.size;
+    if !THUMBNAIL_SIZES.contains(&size) {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "size must be between {} and {}",
+            THUMBNAIL_SIZES.start(),
+            THUMBNAIL_SIZES.end()
+        )));
+    }
+
+    // A thumbnail shows content, so a limited user needs to be able to read the image.
+    let user = match request_access(&state, &http_req) {
+        Access::Denied => return Ok(unauthorized()),
+        access => access.user(),
+    };
+    if let Some(user) = user {
+        let permissions = state.engine.read().permissions(&[id])?;
+        if !permissions.get(&id).is_some_and(|permissions| user.can_read(permissions)) {
+            return Ok(HttpResponse::NotFound().json(ErrorResponse {
+                error: "not_found".to_string(),
+                message: SearchError::EntryNotFound(id).to_string(),
+                code: 404,
+                details: None,
+            }));
+        }
+    }
+
+    // Decoding is CPU and memory heavy, so only a few thumbnails render at once.
+    let _permit = Arc::clone(&state.thumbnail_slots)
+        .acquire_owned()
+        .await
+        .map_err(actix_web::error::ErrorServiceUnavailable)?;
+    let engine = Arc::clone(&state.engine);
+    let rendered = web::block(move || engine.read().thumbnail(id, size)).await?;
+
+    let thumbnail = match rendered {
+        Ok(thumbnail) => thumbnail,
+        Err(e) => {
+            let (status, code) = match &e {
+                SearchError::EntryNotFound(_) | SearchError::PathNotFound(_) => {
+                    (StatusCode::NOT_FOUND, "not_found")
+                }
+                SearchError::UnsupportedMedia(_) => {
+                    (StatusCode::UNSUPPORTED_MEDIA_TYPE, "unsupported_media_type")
+                }
+                SearchError::FeatureUnavailable(_) => {
+                    (StatusCode::NOT_IMPLEMENTED, "feature_unavailable")
+                }
+                _ => {
+                    error!("Thumbnail for {} failed: {}", id, e);
+                    return Err(actix_web::Error::from(e));
+                }
+            };
+            return Ok(HttpResponse::build(status).json(ErrorResponse {
+                error: code.to_string(),
+                message: e.to_string(),
+                code: status.as_u16(),
+                details: None,
+            }));
+        }
+    };
+
+    let etag = format!("\"{}\"", thumbnail.key);
+    let not_modified = http_req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag));
+    if not_modified {
+        return Ok(HttpResponse::NotModified()
+            .insert_header((header::ETAG, etag))
+            .finish());
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type("image/jpeg")
+        .insert_header((header::ETAG, etag))
+        .insert_header((header::CACHE_CONTROL, "private, max-age=86400"))
+        .body(thumbnail.bytes))
+}
+
+// ============ Tag Endpoints ============
+
+pub async fn tag_files(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    req: web::Json<TagRequest>,
+) -> Result<HttpResponse> {
+    let user = match request_access(&state, &http_req) {
+        Access::Denied => return Ok(unauthorized()),
+        access => access.user(),
+    };
+    info!("Tag request: {} files as {:?}", req.ids.len(), req.tag);
+
+    let engine = state.engine.read();
+    // A limited user only tags files they can see; others are skipped like unknown ids.
+    let ids = match user {
+        Some(user) => {
+            let permissions = engine.permissions(&req.ids)?;
+            req.ids
+                .iter()
+                .copied()
+                .filter(|id| permissions.get(id).is_some_and(|perms| user.can_see(perms)))
+                .collect()
+        }
+        None => req.ids.clone(),
+    };
+    match engine.tag_files(&ids, &req.tag) {
+        Ok(tagged) => Ok(HttpResponse::Ok().json(TagResponse {
+            tag: req.tag.trim().to_string(),
+            tagged,
+        })),
+        Err(SearchError::InvalidQuery(message)) => {
+            Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                error: "invalid_tag".to_string(),
+                message,
+                code: 400,
+                details: None,
+            }))
+        }
+        Err(e) => {
+            error!("Tagging failed: {}", e);
+            Err(actix_web::Error::from(e))
+        }
+    }
+}
+
+pub async fn list_tags(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse> {
+    let user = match request_access(&state, &http_req) {
+        Access::Denied => return Ok(unauthorized()),
+        access => access.user(),
+    };
+
+    let engine = state.engine.read();
+    let mut tags = engine.list_tags().map_err(|e| {
+        error!("Failed to list tags: {}", e);
+        actix_web::Error::from(e)
+    })?;
+    // A limited user's counts only take in the files they can see.
+    if let Some(user) = user {
+        let mut visible = Vec::new();
+        for (tag, _) in tags {
+            let tagged = engine.find_by_tag(&tag, i64::MAX as usize)?;
+            let ids: Vec<i64> = tagged.iter().filter_map(|entry| entry.id).collect();
+            let count =
+                permissions::filter_entries(tagged, &engine.permissions(&ids)?, user).len();
+            if count > 0 {
+                visible.push((tag, count));
+            }
+        }
+        tags = visible;
+    }
+
+    Ok(HttpResponse::Ok().json(TagsResponse {
+        tags: tags
+            .into_iter()
+            .map(|(tag, count)| TagCount { tag, count })
+            .collect(),
     }))
 }
 
@@ -61,35 +482,51 @@ pub async fn index(
 ) -> Result<HttpResponse> {
     let start = Instant::now();
 
-    info!("Index request: {:?}", req.path);
+    info!("Index request: {:?}", state.sensitive.redact_path(&req.path));
 
-    // Validate path
-    if !req.path.exists() {
-        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
-            error: "invalid_path".to_string(),
-            message: "Path does not exist".to_string(),
-            code: 400,
-            details: None,
-        }));
-    }
+    let path = match validate_request_path(&state.config.security, &req.path) {
+        Ok(path) => path,
+        Err(rejection) => return Ok(reject_path(rejection)),
+    };
+
+    state.activity.record(ActivityEvent::IndexStarted {
+        path: path.clone(),
+        quick: req.quick,
+    });
 
     let engine = state.engine.read();
 
-    let count = engine
-        .index_directory(&req.path, None)
-        .map_err(|e| {
-            error!("Indexing failed: {}", e);
-            actix_web::error::ErrorInternalServerError(e)
-        })?;
+    let indexed = if req.quick {
+        engine.index_quick(&path, None)
+    } else {
+        engine.index_directory_with_report(&path, None)
+    };
+    let report = match indexed {
+        Ok(report) => report,
+        Err(e) => return index_failure(&state.activity, "index", Some(path), e),
+    };
 
     let took_ms = start.elapsed().as_millis() as u64;
+    state.activity.record(ActivityEvent::IndexFinished {
+        path,
+        indexed: report.indexed,
+        skipped: report.skipped.len(),
+        complete: report.is_complete(),
+        took_ms,
+    });
 
     Ok(HttpResponse::Ok().json(IndexResponse {
-        indexed_count: count,
-        skipped_count: 0,
-        error_count: 0,
+        indexed_count: report.indexed,
+        skipped_count: report.skipped.len(),
+        content_skipped_count: report.content_skipped.len(),
+        possibly_inconsistent_count: report.possibly_inconsistent.len(),
+        error_count: report.failed_batches,
         took_ms,
-        status: IndexStatus::Completed,
+        status: if report.is_complete() {
+            IndexStatus::Completed
+        } else {
+            IndexStatus::Partial
+        },
     }))
 }
 
@@ -100,26 +537,115 @@ pub async fn update(
     req: web::Json<UpdateRequest>,
 ) -> Result<HttpResponse> {
     let start = Instant::now();
+    let req = req.into_inner();
 
-    info!("Update request: {:?}", req.path);
+    let redact = |path: &PathBuf| state.sensitive.redact_path(path);
+    info!(
+        "Update request: path={:?} paths={:?} all={}",
+        req.path.as_ref().map(redact),
+        req.paths.iter().map(redact).collect::<Vec<_>>(),
+        req.all
+    );
 
     let engine = state.engine.read();
 
-    let stats = engine
-        .update_index(&req.path, None)
-        .map_err(|e| {
-            error!("Update failed: {}", e);
-            actix_web::error::ErrorInternalServerError(e)
-        })?;
+    let mut roots = Vec::new();
+    match (req.path, req.paths.is_empty(), req.all) {
+        (Some(path), true, false) => {
+            let path = match validate_request_path(&state.config.security, &path) {
+                Ok(path) => path,
+                Err(rejection) => return Ok(reject_path(rejection)),
+            };
+            let stats = match engine.update_index(&path, None) {
+                Ok(stats) => stats,
+                Err(e) => return index_failure(&state.activity, "update", Some(path), e),
+            };
+            roots.push(root_update(path, Ok(stats)));
+        }
+        (None, false, false) => {
+            let mut valid = Vec::with_capacity(req.paths.len());
+            for path in &req.paths {
+                match validate_request_path(&state.config.security, path) {
+                    Ok(path) => valid.push(path),
+                    Err(rejection) => return Ok(reject_path(rejection)),
+                }
+            }
+            match engine.update_roots(&valid, true, None) {
+                Ok(results) => roots.extend(results.into_iter().map(|(p, r)| root_update(p, r))),
+                Err(e) => return index_failure(&state.activity, "update", None, e),
+            }
+        }
+        (None, true, true) => {
+            let recorded = match engine.indexed_roots() {
+                Ok(recorded) => recorded,
+                Err(e) => return index_failure(&state.activity, "update", None, e),
+            };
+            // Recorded roots outside the allowed ones are reported, not updated.
+            let mut valid = Vec::with_capacity(recorded.len());
+            for root in recorded {
+                match validate_request_path(&state.config.security, &root.path) {
+                    Ok(path) => valid.push(path),
+                    Err(rejection) => roots.push(RootUpdate {
+                        path: root.path,
+                        added: 0,
+                        updated: 0,
+                        removed: 0,
+                        error: Some(rejection.to_string()),
+                    }),
+                }
+            }
+            match engine.update_roots(&valid, true, None) {
+                Ok(results) => roots.extend(results.into_iter().map(|(p, r)| root_update(p, r))),
+                Err(e) => return index_failure(&state.activity, "update", None, e),
+            }
+        }
+        _ => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                error: "invalid_request".to_string(),
+                message: "Give exactly one of `path`, `paths` or `\"all\": true`".to_string(),
+                code: 400,
+                details: None,
+            }));
+        }
+    }
 
     let took_ms = start.elapsed().as_millis() as u64;
-
-    Ok(HttpResponse::Ok().json(UpdateResponse {
-        added: stats.added,
-        updated: stats.updated,
-        removed: stats.removed,
+    let response = UpdateResponse {
+        added: roots.iter().map(|root| root.added).sum(),
+        updated: roots.iter().map(|root| root.updated).sum(),
+        removed: roots.iter().map(|root| root.removed).sum(),
         took_ms,
-    }))
+        roots,
+    };
+    state.activity.record(ActivityEvent::UpdateFinished {
+        roots: response.roots.iter().map(|root| root.path.clone()).collect(),
+        added: response.added,
+        updated: response.updated,
+        removed: response.removed,
+        failed: response.roots.iter().filter(|root| root.error.is_some()).count(),
+        took_ms,
+    });
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+fn root_update(path: PathBuf, result: crate::Result<crate::UpdateStats>) -> RootUpdate {
+    match result {
+        Ok(stats) => RootUpdate {
+            path,
+            added: stats.added,
+            updated: stats.updated,
+            removed: stats.removed,
+            error: None,
+        },
+        Err(e) => RootUpdate {
+            path,
+            added: 0,
+            updated: 0,
+            removed: 0,
+            error: Some(e.to_string()),
+        },
+    }
 }
 
 // ============ Watch Endpoint ============
@@ -128,25 +654,49 @@ pub async fn start_watch(
     state: web::Data<AppState>,
     req: web::Json<WatchRequest>,
 ) -> Result<HttpResponse> {
-    info!("Watch request: {:?}", req.path);
+    info!("Watch request: {:?}", state.sensitive.redact_path(&req.path));
+
+    let path = match validate_request_path(&state.config.security, &req.path) {
+        Ok(path) => path,
+        Err(rejection) => return Ok(reject_path(rejection)),
+    };
 
     let watch_id = uuid::Uuid::new_v4().to_string();
 
     // Start watching
+    let options = WatchOptions {
+        audit: req.audit,
+        watch_id: Some(watch_id.clone()),
+        initial_scan: req.initial_scan,
+        recursive: req.recursive,
+    };
     let mut engine = state.engine.write();
     engine
-        .start_watching(&req.path)
+        .start_watching_with_options(&path, options)
         .map_err(|e| {
-            error!("Watch failed: {}", e);
-            actix_web::error::ErrorInternalServerError(e)
+            error!("Watch failed: {}", state.sensitive.redact_error(&e));
+            state.activity.record(ActivityEvent::Error {
+                operation: "watch".to_string(),
+                path: Some(path.clone()),
+                message: e.to_string(),
+            });
+            actix_web::Error::from(e)
         })?;
+    if let Some(events) = engine.subscribe_watch_events() {
+        let feed = Arc::clone(&state.activity);
+        std::thread::spawn(move || activity::forward_watch_events(events, &feed));
+    }
+    state.activity.record(ActivityEvent::WatchStarted {
+        watch_id: watch_id.clone(),
+        path: path.clone(),
+    });
 
     // Store watch handle
     use crate::server::state::WatchHandle;
     state.watchers.insert(
         watch_id.clone(),
         WatchHandle {
-            path: req.path.clone(),
+            path: path.clone(),
             recursive: req.recursive,
             created_at: Utc::now(),
         },
@@ -154,11 +704,33 @@ pub async fn start_watch(
 
     Ok(HttpResponse::Ok().json(WatchResponse {
         watch_id,
-        path: req.path.clone(),
+        path,
         status: "active".to_string(),
     }))
 }
 
+pub async fn watch_status(
+    state: web::Data<AppState>,
+    watch_id: web::Path<String>,
+) -> Result<HttpResponse> {
+    match state.watchers.get(watch_id.as_str()) {
+        Some(handle) => {
+            let engine = state.engine.read();
+            Ok(HttpResponse::Ok().json(WatchStatusResponse {
+                watch_id: watch_id.into_inner(),
+                path: handle.path.clone(),
+                status: engine.watch_status(),
+            }))
+        }
+        None => Ok(HttpResponse::NotFound().json(ErrorResponse {
+            error: "not_found".to_string(),
+            message: "Watch ID not found".to_string(),
+            code: 404,
+            details: None,
+        })),
+    }
+}
+
 pub async fn stop_watch(
     state: web::Data<AppState>,
     watch_id: web::Path<String>,
@@ -171,8 +743,12 @@ pub async fn stop_watch(
             .stop_watching()
             .map_err(|e| {
                 error!("Stop watch failed: {}", e);
-                actix_web::error::ErrorInternalServerError(e)
+                actix_web::Error::from(e)
             })?;
+        state.activity.record(ActivityEvent::WatchStopped {
+            watch_id: watch_id.into_inner(),
+            path: handle.path.clone(),
+        });
 
         Ok(HttpResponse::Ok().json(serde_json::json!({
             "message": "Watch stopped",
@@ -188,108 +764,697 @@ pub async fn stop_watch(
     }
 }
 
-// ============ Stats Endpoint ============
-
-pub async fn get_stats(state: web::Data<AppState>) -> Result<HttpResponse> {
-    let engine = state.engine.read();
-    let db_stats = engine.get_stats().map_err(|e| {
-        error!("Failed to get stats: {}", e);
-        actix_web::error::ErrorInternalServerError(e)
-    })?;
-
-    Ok(HttpResponse::Ok().json(StatsResponse {
-        total_files: db_stats.total_files,
-        total_directories: db_stats.total_directories,
-        total_size: db_stats.total_size,
-        index_size_mb: db_stats.index_size as f64 / 1_000_000.0,
-        last_update: Some(db_stats.last_update),
-        uptime_seconds: state.uptime_seconds(),
-        performance: PerformanceStats {
-            total_searches: state.metrics.total_searches.load(Ordering::Relaxed),
-            avg_search_time_ms: state.metrics.avg_search_time_ms(),
-            cache_hit_rate: state.metrics.cache_hit_rate(),
-            memory_usage_mb: get_memory_usage_mb(),
-        },
-    }))
-}
+// ============ Audit Endpoint ============
 
-// ============ Health Endpoint ============
+pub async fn audit_log(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    params: web::Query<AuditParams>,
+) -> Result<HttpResponse> {
+    if let Some(denied) = authorize(&http_req, &state) {
+        return Ok(denied);
+    }
 
-pub async fn health_check(state: web::Data<AppState>) -> Result<HttpResponse> {
-    let mut checks = Vec::new();
+    let event_types = match parse_event_types(params.types.as_deref()) {
+        Ok(types) => types,
+        Err(unknown) => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                error: "invalid_event_type".to_string(),
+                message: format!("Unknown audit event type: {}", unknown),
+                code: 400,
+                details: None,
+            }))
+        }
+    };
 
-    // Database check
-    let db_check_start = Instant::now();
     let engine = state.engine.read();
-    let db_healthy = engine.get_stats().is_ok();
-    checks.push(HealthCheck {
-        name: "database".to_string(),
-        status: if db_healthy {
-            HealthStatus::Healthy
-        } else {
-            HealthStatus::Unhealthy
-        },
-        message: None,
-        response_time_ms: Some(db_check_start.elapsed().as_millis() as u64),
-    });
-
-    // Memory check
-    let memory_mb = get_memory_usage_mb();
-    let memory_healthy = memory_mb < 1000.0; // Less than 1GB
-    checks.push(HealthCheck {
-        name: "memory".to_string(),
-        status: if memory_healthy {
-            HealthStatus::Healthy
-        } else {
-            HealthStatus::Degraded
-        },
-        message: Some(format!("{:.2} MB", memory_mb)),
-        response_time_ms: None,
-    });
+    let records = engine
+        .query_audit_log(
+            params.path.as_deref(),
+            (params.since, params.until),
+            &event_types,
+            params.after_id,
+            params.limit,
+        )
+        .map_err(|e| {
+            error!("Audit query failed: {}", e);
+            actix_web::Error::from(e)
+        })?;
 
-    let overall_status = if checks
-        .iter()
-        .all(|c| matches!(c.status, HealthStatus::Healthy))
-    {
-        HealthStatus::Healthy
-    } else if checks
-        .iter()
-        .any(|c| matches!(c.status, HealthStatus::Unhealthy))
-    {
-        HealthStatus::Unhealthy
+    let next_cursor = if records.len() == params.limit {
+        records.last().and_then(|record| record.id)
     } else {
-        HealthStatus::Degraded
+        None
     };
 
-    Ok(HttpResponse::Ok().json(HealthResponse {
-        status: overall_status,
-        version: env!("CARGO_PKG_VERSION").to_string(),
-        uptime_seconds: state.uptime_seconds(),
-        checks,
+    Ok(HttpResponse::Ok().json(AuditResponse {
+        records,
+        next_cursor,
     }))
 }
 
-// ============ Helper Functions ============
-
-fn build_query(req: &SearchRequest) -> Result<Query> {
-    let mut query = Query::new(req.query.clone());
+fn parse_event_types(types: Option<&str>) -> std::result::Result<Vec<AuditEventType>, String> {
+    types
+        .unwrap_or_default()
+        .split(',')
+        .filter(|value| !value.trim().is_empty())
+        .map(|value| AuditEventType::parse(value).ok_or_else(|| value.trim().to_string()))
+        .collect()
+}
 
-    // Set match mode
-    query = match req.mode {
-        SearchMode::Exact => query.with_match_mode(MatchMode::Exact),
-        SearchMode::Fuzzy => query.with_match_mode(MatchMode::Fuzzy),
-        SearchMode::Regex => query.with_match_mode(MatchMode::Regex),
-        SearchMode::Glob => query.with_match_mode(MatchMode::Glob),
-    };
+// ============ Activity Endpoint ============
 
-    // Apply filters
-    if let Some(ref extensions) = req.filters.extensions {
-        query = query.with_extensions(extensions.clone());
+/// Events after `since`, for clients that poll; the WebSocket pushes the same ones.
+pub async fn activity(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    params: web::Query<ActivityParams>,
+) -> Result<HttpResponse> {
+    if let Some(denied) = authorize(&http_req, &state) {
+        return Ok(denied);
     }
 
-    if let Some(size_min) = req.filters.size_min {
-        query = query.with_size_filter(SizeFilter::GreaterThan(size_min));
-    }
+    let events = state.activity.since(params.since);
+    // Taken from what was read, as an event recorded since would otherwise be skipped.
+    let last_id = events
+        .last()
+        .map_or(params.since.unwrap_or(0), |record| record.id);
+    Ok(HttpResponse::Ok().json(ActivityResponse { events, last_id }))
+}
+
+// ============ Maintenance Endpoints ============
+
+/// Rebuilds the full-text index from stored content. Searches keep being served while it
+/// runs, so it happens on the blocking pool rather than on a server worker.
+pub async fn rebuild_fts(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse> {
+    if let Some(denied) = authorize(&http_req, &state) {
+        return Ok(denied);
+    }
+
+    let start = Instant::now();
+    info!("Full-text rebuild requested");
+
+    let engine = Arc::clone(&state.engine);
+    let report = web::block(move || engine.read().rebuild_fts(None))
+        .await?
+        .map_err(|e| {
+            error!("Full-text rebuild failed: {}", e);
+            state.activity.record(ActivityEvent::Error {
+                operation: "rebuild_fts".to_string(),
+                path: None,
+                message: e.to_string(),
+            });
+            actix_web::Error::from(e)
+        })?;
+
+    let took_ms = start.elapsed().as_millis() as u64;
+    state.activity.record(ActivityEvent::MaintenanceFinished {
+        task: "rebuild_fts".to_string(),
+        took_ms: Some(took_ms),
+    });
+
+    Ok(HttpResponse::Ok().json(RebuildFtsResponse { report, took_ms }))
+}
+
+/// Clears [`AppState::backup_running`] once the backup that claimed it ends.
+struct BackupSlot(Arc<AtomicBool>);
+
+impl BackupSlot {
+    fn claim(running: &Arc<AtomicBool>) -> Option<Self> {
+        running
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .ok()
+            .map(|_| Self(Arc::clone(running)))
+    }
+}
+
+impl Drop for BackupSlot {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Copies the live index to a file on the server, streaming progress as NDJSON events.
+/// Searches and writes carry on meanwhile; a second backup is refused until this one ends.
+pub async fn backup(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    req: web::Json<BackupRequest>,
+) -> Result<HttpResponse> {
+    if let Some(denied) = authorize(&http_req, &state) {
+        return Ok(denied);
+    }
+
+    // The backup does not exist yet, so it is its directory that has to be allowed.
+    let Some(file_name) = req.path.file_name() else {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "invalid_path".to_string(),
+            message: format!("Not a file path: {}", req.path.display()),
+            code: 400,
+            details: None,
+        }));
+    };
+    let dir = req.path.parent().filter(|dir| !dir.as_os_str().is_empty());
+    let dest = match validate_request_path(&state.config.security, dir.unwrap_or(Path::new("."))) {
+        Ok(dir) => dir.join(file_name),
+        Err(rejection) => return Ok(reject_path(rejection)),
+    };
+
+    let Some(slot) = BackupSlot::claim(&state.backup_running) else {
+        return Ok(HttpResponse::Conflict().json(ErrorResponse {
+            error: "backup_in_progress".to_string(),
+            message: "Another backup is still running".to_string(),
+            code: 409,
+            details: None,
+        }));
+    };
+
+    info!("Backup requested to {}", dest.display());
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<Bytes>(16);
+    let engine = Arc::clone(&state.engine);
+    let feed = Arc::clone(&state.activity);
+    let compress = req.compress;
+
+    tokio::task::spawn_blocking(move || {
+        let _slot = slot;
+        let start = Instant::now();
+        let send = move |tx: &tokio::sync::mpsc::Sender<Bytes>, event: BackupEvent| {
+            if let Ok(mut line) = serde_json::to_vec(&event) {
+                line.push(b'\n');
+                // A client that went away does not stop the backup.
+                let _ = tx.blocking_send(Bytes::from(line));
+            }
+        };
+
+        let progress_tx = tx.clone();
+        let callback: ProgressCallback = Box::new(move |progress: Progress| {
+            let event = BackupEvent::Progress {
+                copied_pages: progress.current,
+                total_pages: progress.total,
+            };
+            send(&progress_tx, event);
+        });
+
+        let backed_up = engine.read().backup(&dest, Some(callback));
+        let backed_up = backed_up.and_then(|mut report| {
+            if compress {
+                report.path = crate::storage::backup::compress(&report.path)?;
+                report.size_bytes = std::fs::metadata(&report.path).map_or(0, |m| m.len());
+            }
+            Ok(report)
+        });
+
+        let event = match backed_up {
+            Ok(report) => {
+                info!("Backup written to {}", report.path.display());
+                let took_ms = start.elapsed().as_millis() as u64;
+                feed.record(ActivityEvent::MaintenanceFinished {
+                    task: "backup".to_string(),
+                    took_ms: Some(took_ms),
+                });
+                BackupEvent::Done { report, took_ms }
+            }
+            Err(e) => {
+                error!("Backup failed: {}", e);
+                feed.record(ActivityEvent::Error {
+                    operation: "backup".to_string(),
+                    path: None,
+                    message: e.to_string(),
+                });
+                BackupEvent::Error {
+                    error: e.code().as_str().to_string(),
+                    message: e.to_string(),
+                }
+            }
+        };
+        send(&tx, event);
+    });
+
+    let body = ReceiverStream::new(rx).map(Ok::<_, actix_web::Error>);
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .streaming(body))
+}
+
+/// Restoring replaces the database under every open connection, so it is done offline with
+/// `filesearch restore` while the server is stopped.
+pub async fn restore(state: web::Data<AppState>, http_req: HttpRequest) -> Result<HttpResponse> {
+    if let Some(denied) = authorize(&http_req, &state) {
+        return Ok(denied);
+    }
+
+    Ok(HttpResponse::Conflict().json(ErrorResponse {
+        error: "restore_requires_offline".to_string(),
+        message: "Stop the server, run `filesearch --index <index> restore <backup>`, then \
+                  start the server again"
+            .to_string(),
+        code: 409,
+        details: None,
+    }))
+}
+
+// ============ Stats Endpoint ============
+
+pub async fn get_stats(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse> {
+    if let Some(refused) = require_unrestricted(&state, &http_req) {
+        return Ok(refused);
+    }
+    let engine = state.engine.read();
+    let db_stats = engine.get_stats().map_err(|e| {
+        error!("Failed to get stats: {}", e);
+        actix_web::Error::from(e)
+    })?;
+
+    // Search counters live in the engine so the library and the server report the same numbers.
+    let metrics = engine.metrics();
+
+    Ok(HttpResponse::Ok().json(StatsResponse {
+        total_files: db_stats.total_files,
+        total_directories: db_stats.total_directories,
+        total_size: db_stats.total_size,
+        store_aliases: db_stats.store_aliases,
+        index_size_mb: db_stats.index_size as f64 / 1_000_000.0,
+        last_update: Some(db_stats.last_update),
+        uptime_seconds: state.uptime_seconds(),
+        performance: PerformanceStats {
+            total_searches: metrics.total_searches,
+            avg_search_time_ms: metrics.avg_search_time_ms,
+            cache_hit_rate: metrics.cache_hit_rate(),
+            memory_usage_mb: get_memory_usage_mb(),
+        },
+        quota: db_stats.quota.map(QuotaStats::from),
+        enrichment: db_stats.enrichment,
+        write_lease: db_stats.write_lease,
+        name_index: db_stats.name_index,
+        maintenance: db_stats.maintenance,
+        federation: (!state.config.peers.is_empty()).then(|| state.federation.usage()),
+    }))
+}
+
+// ============ Export Endpoint ============
+
+pub async fn export_changes(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    params: web::Query<ExportChangesParams>,
+) -> Result<HttpResponse> {
+    if let Some(refused) = require_unrestricted(&state, &http_req) {
+        return Ok(refused);
+    }
+    if let Some(denied) = authorize(&http_req, &state) {
+        return Ok(denied);
+    }
+    let since = params.since.unwrap_or(DateTime::<Utc>::UNIX_EPOCH);
+    let include_sensitive =
+        params.include_sensitive && !state.config.security.hide_sensitive_in_api;
+
+    info!("Export changes request since {}", since);
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<Bytes>(16);
+    let engine = Arc::clone(&state.engine);
+
+    tokio::task::spawn_blocking(move || {
+        let engine = engine.read();
+        let writer = std::io::BufWriter::new(ChannelWriter { tx });
+        if let Err(e) = engine.export_changes(since, writer, include_sensitive) {
+            error!("Export failed: {}", e);
+        }
+    });
+
+    let body = ReceiverStream::new(rx).map(Ok::<_, actix_web::Error>);
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .streaming(body))
+}
+
+struct ChannelWriter {
+    tx: tokio::sync::mpsc::Sender<Bytes>,
+}
+
+impl std::io::Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.tx
+            .blocking_send(Bytes::copy_from_slice(buf))
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "client disconnected"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// ============ Health Endpoint ============
+
+pub async fn health_check(state: web::Data<AppState>) -> Result<HttpResponse> {
+    let mut checks = Vec::new();
+
+    // Database check
+    let db_check_start = Instant::now();
+    let engine = state.engine.read();
+    let db_stats = engine.get_stats();
+    checks.push(HealthCheck {
+        name: "database".to_string(),
+        status: if db_stats.is_ok() {
+            HealthStatus::Healthy
+        } else {
+            HealthStatus::Unhealthy
+        },
+        message: None,
+        response_time_ms: Some(db_check_start.elapsed().as_millis() as u64),
+    });
+
+    // Searches keep working at the quota, so a full index only degrades the service.
+    if let Some(quota) = db_stats.as_ref().ok().and_then(|stats| stats.quota) {
+        let usage_percent = quota.usage_percent();
+        checks.push(HealthCheck {
+            name: "quota".to_string(),
+            status: if usage_percent < QUOTA_WARNING_PERCENT {
+                HealthStatus::Healthy
+            } else {
+                HealthStatus::Degraded
+            },
+            message: Some(format!(
+                "{:.1}% of {} bytes used",
+                usage_percent, quota.limit_bytes
+            )),
+            response_time_ms: None,
+        });
+    }
+
+    // Maintenance only tidies up, so how long ago it ran is reported without judging it.
+    if let Ok(stats) = &db_stats {
+        let last_runs: Vec<String> = stats
+            .maintenance
+            .iter()
+            .filter(|status| status.enabled)
+            .map(|status| {
+                let last_run = status
+                    .last_run
+                    .map_or_else(|| "never".to_string(), |at| at.to_rfc3339());
+                format!("{} {}", status.task.as_str(), last_run)
+            })
+            .collect();
+        checks.push(HealthCheck {
+            name: "maintenance".to_string(),
+            status: HealthStatus::Healthy,
+            message: Some(last_runs.join(", ")),
+            response_time_ms: None,
+        });
+    }
+
+    // Memory check
+    let memory_mb = get_memory_usage_mb();
+    let memory_healthy = memory_mb < 1000.0; // Less than 1GB
+    checks.push(HealthCheck {
+        name: "memory".to_string(),
+        status: if memory_healthy {
+            HealthStatus::Healthy
+        } else {
+            HealthStatus::Degraded
+        },
+        message: Some(format!("{:.2} MB", memory_mb)),
+        response_time_ms: None,
+    });
+
+    let overall_status = if checks
+        .iter()
+        .all(|c| matches!(c.status, HealthStatus::Healthy))
+    {
+        HealthStatus::Healthy
+    } else if checks
+        .iter()
+        .any(|c| matches!(c.status, HealthStatus::Unhealthy))
+    {
+        HealthStatus::Unhealthy
+    } else {
+        HealthStatus::Degraded
+    };
+
+    Ok(HttpResponse::Ok().json(HealthResponse {
+        status: overall_status,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        uptime_seconds: state.uptime_seconds(),
+        checks,
+        capabilities: engine.capabilities().clone(),
+    }))
+}
+
+// ============ Capabilities Endpoint ============
+
+pub async fn capabilities(state: web::Data<AppState>) -> Result<HttpResponse> {
+    let engine = state.engine.read();
+
+    Ok(HttpResponse::Ok().json(CapabilitiesResponse {
+        engine: engine.capabilities().clone(),
+        auth_required: state.config.security.enable_auth,
+        permission_filtering: permissions::SUPPORTED
+            && state.config.security.enforce_fs_permissions,
+    }))
+}
+
+// ============ Helper Functions ============
+
+/// The API key sent in `X-API-Key` or `Authorization: Bearer`.
+fn provided_api_key(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get("X-API-Key")
+        .and_then(|value| value.to_str().ok())
+        .or_else(|| {
+            req.headers()
+                .get("Authorization")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "))
+        })
+}
+
+/// What this request may see under `enforce_fs_permissions`.
+fn request_access<'a>(state: &'a AppState, req: &HttpRequest) -> Access<'a> {
+    permissions::access(&state.config.security, provided_api_key(req))
+}
+
+/// Narrows a full `listing` to the entries `user` can see and takes the requested page of
+/// them, so neither the page nor `total` counts hidden entries.
+fn visible_listing(
+    engine: &SearchEngine,
+    listing: Listing,
+    pagination: Pagination,
+    user: &SystemUser,
+) -> crate::Result<Listing> {
+    let ids: Vec<i64> = listing.entries.iter().filter_map(|entry| entry.id).collect();
+    let entries = permissions::filter_entries(listing.entries, &engine.permissions(&ids)?, user);
+    let total = entries.len();
+    let entries: Vec<_> = entries
+        .into_iter()
+        .skip(pagination.offset)
+        .take(pagination.limit.unwrap_or(usize::MAX))
+        .collect();
+
+    let mut fingerprint = format!("{}|{}|{}", listing.etag, user.uid, pagination.offset);
+    for entry in &entries {
+        fingerprint.push_str(&format!("|{}", entry.path.display()));
+    }
+    Ok(Listing {
+        entries,
+        total,
+        offset: pagination.offset,
+        etag: crate::utils::hash::hash_string(&fingerprint),
+        ..listing
+    })
+}
+
+/// Returns a 401 response unless auth is disabled or the request carries the
+/// configured API key in `X-API-Key` or `Authorization: Bearer`.
+pub(crate) fn authorize(req: &HttpRequest, state: &AppState) -> Option<HttpResponse> {
+    let security = &state.config.security;
+    if !security.enable_auth {
+        return None;
+    }
+
+    match (security.api_key.as_deref(), provided_api_key(req)) {
+        (Some(expected), Some(provided)) if expected == provided => None,
+        _ => Some(unauthorized()),
+    }
+}
+
+fn federation_forbidden() -> HttpResponse {
+    HttpResponse::Forbidden().json(ErrorResponse {
+        error: "federation_forbidden".to_string(),
+        message: "A key mapped to a system account can only search this server's index"
+            .to_string(),
+        code: 403,
+        details: None,
+    })
+}
+
+/// Refuses a request that may not see the whole index, for endpoints whose answers aren't
+/// narrowed to an account's files: `401` when denied, `403` when limited to an account.
+fn require_unrestricted(state: &AppState, req: &HttpRequest) -> Option<HttpResponse> {
+    match request_access(state, req) {
+        Access::Unrestricted => None,
+        Access::Denied => Some(unauthorized()),
+        Access::Limited(_) => Some(HttpResponse::Forbidden().json(ErrorResponse {
+            error: "forbidden".to_string(),
+            message: "This endpoint reports on the whole index, which a key mapped to a \
+                      system account may not see"
+                .to_string(),
+            code: 403,
+            details: None,
+        })),
+    }
+}
+
+fn unauthorized() -> HttpResponse {
+    HttpResponse::Unauthorized().json(ErrorResponse {
+        error: "unauthorized".to_string(),
+        message: "A valid API key is required".to_string(),
+        code: 401,
+        details: None,
+    })
+}
+
+/// Engine errors that reach a handler without a more specific mapping answer with their
+/// [`ErrorCode`] as `error` and the path involved, if any, in `details`.
+impl ResponseError for SearchError {
+    fn status_code(&self) -> StatusCode {
+        match self.code() {
+            ErrorCode::InvalidQuery
+            | ErrorCode::InvalidRegex
+            | ErrorCode::InvalidGlob
+            | ErrorCode::InvalidContentQuery
+            | ErrorCode::Parse => StatusCode::BAD_REQUEST,
+            ErrorCode::PermissionDenied => StatusCode::FORBIDDEN,
+            ErrorCode::NotFound
+            | ErrorCode::EntryNotFound
+            | ErrorCode::SavedSearchNotFound
+            | ErrorCode::IndexNotFound => StatusCode::NOT_FOUND,
+            ErrorCode::UnsupportedMedia => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ErrorCode::StorageBusy | ErrorCode::PoolTimeout | ErrorCode::WriteLeaseHeld => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            ErrorCode::DiskFull | ErrorCode::QuotaExceeded => StatusCode::INSUFFICIENT_STORAGE,
+            ErrorCode::FeatureUnavailable => StatusCode::NOT_IMPLEMENTED,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        HttpResponse::build(status).json(ErrorResponse {
+            error: self.code().as_str().to_string(),
+            message: self.to_string(),
+            code: status.as_u16(),
+            details: self.path().map(|path| serde_json::json!({ "path": path })),
+        })
+    }
+}
+
+/// Index quota use at which the health check reports the service as degraded.
+const QUOTA_WARNING_PERCENT: f64 = 90.0;
+
+/// Answers 507 when indexing stopped at the quota, reporting how far it got; batches
+/// committed before the stop stay indexed.
+fn index_failure(
+    feed: &ActivityFeed,
+    operation: &str,
+    path: Option<PathBuf>,
+    e: SearchError,
+) -> Result<HttpResponse> {
+    error!("{} failed: {}", operation, e);
+    feed.record(ActivityEvent::Error {
+        operation: operation.to_string(),
+        path,
+        message: e.to_string(),
+    });
+
+    match e {
+        SearchError::QuotaExceeded {
+            limit,
+            used,
+            indexed,
+        } => Ok(HttpResponse::InsufficientStorage().json(ErrorResponse {
+            error: "quota_exceeded".to_string(),
+            message: e.to_string(),
+            code: StatusCode::INSUFFICIENT_STORAGE.as_u16(),
+            details: Some(serde_json::json!({
+                "indexed_count": indexed,
+                "limit_bytes": limit,
+                "used_bytes": used,
+            })),
+        })),
+        e => Err(actix_web::Error::from(e)),
+    }
+}
+
+fn reject_path(rejection: PathRejection) -> HttpResponse {
+    let status = match rejection {
+        PathRejection::NotFound(_) => StatusCode::BAD_REQUEST,
+        _ => StatusCode::FORBIDDEN,
+    };
+
+    HttpResponse::build(status).json(ErrorResponse {
+        error: rejection.code().to_string(),
+        message: rejection.to_string(),
+        code: status.as_u16(),
+        details: None,
+    })
+}
+
+/// `server_cap` is `PerformanceSettings.max_search_results`; the engine's own cap is
+/// applied on top of it when the query executes.
+pub fn build_query(req: &SearchRequest, server_cap: usize) -> Result<Query> {
+    if req.limit == 0 {
+        return Err(actix_web::error::ErrorBadRequest(
+            "limit must be greater than zero",
+        ));
+    }
+
+    let mut query = Query::new(req.query.clone());
+
+    // Set match mode
+    query = match req.mode {
+        SearchMode::Exact => query.with_match_mode(MatchMode::Exact),
+        SearchMode::Fuzzy => query.with_match_mode(MatchMode::Fuzzy),
+        SearchMode::Regex => query.with_match_mode(MatchMode::Regex),
+        SearchMode::Glob => query.with_match_mode(MatchMode::Glob),
+    };
+
+    // Apply filters
+    if let Some(ref extensions) = req.filters.extensions {
+        query = query.with_extensions(extensions.clone());
+    }
+
+    match (req.filters.size_min, req.filters.size_max) {
+        (Some(min), Some(max)) => query = query.with_size_filter(SizeFilter::Range(min, max)),
+        (Some(min), None) => query = query.with_size_filter(SizeFilter::GreaterThan(min)),
+        (None, Some(max)) => query = query.with_size_filter(SizeFilter::LessThan(max)),
+        (None, None) => {}
+    }
+
+    let filters = &req.filters;
+    let date_bounds = [
+        (DateField::Modified, filters.modified_after, filters.modified_before),
+        (DateField::Created, filters.created_after, filters.created_before),
+        (DateField::Accessed, filters.accessed_after, filters.accessed_before),
+    ];
+    for (field, after, before) in date_bounds {
+        let filter = match (after, before) {
+            (Some(after), Some(before)) => DateFilter::Between(after, before),
+            (Some(after), None) => DateFilter::After(after),
+            (None, Some(before)) => DateFilter::Before(before),
+            (None, None) => continue,
+        };
+        query = query.with_date_filter_on(field, filter);
+    }
 
     if let Some(ref scope) = req.filters.scope {
         query = query.with_scope(match scope {
@@ -300,27 +1465,72 @@ fn build_query(req: &SearchRequest) -> Result<Query> {
         });
     }
 
+    if let Some(ref tags) = req.filters.tags {
+        query = query.with_tags(tags.clone());
+    }
+
+    if let Some(ref content) = req.filters.content_query {
+        query = query.with_content_pattern(content.clone());
+    }
+
     // Set limit
-    query = query.with_max_results(req.limit);
+    query = query
+        .with_max_results(req.limit.min(server_cap))
+        .with_offset(req.offset);
+    query = query.with_context_lines(req.context_lines);
+
+    if let Some(ref sort) = req.sort {
+        let sort = SortKey::parse(sort).ok_or_else(|| {
+            actix_web::error::ErrorBadRequest(format!("unknown sort: {}", sort))
+        })?;
+        query = query.with_sort(sort);
+    }
 
     Ok(query)
 }
 
-fn convert_result(result: crate::SearchResult) -> FileResult {
+fn invalid_field(unknown: UnknownField) -> HttpResponse {
+    HttpResponse::BadRequest().json(ErrorResponse {
+        error: "invalid_field".to_string(),
+        message: unknown.to_string(),
+        code: 400,
+        details: Some(serde_json::json!({ "valid_fields": unknown.valid })),
+    })
+}
+
+pub fn convert_result(result: crate::SearchResult) -> FileResult {
     FileResult {
+        id: result.file.id,
         path: result.file.path.clone(),
         name: result.file.name.clone(),
         size: result.file.size,
-        modified: result.file.modified_at.unwrap_or_else(|| Utc::now()),
-        file_type: if result.file.is_directory {
-            FileType::Directory
-        } else if result.file.is_symlink {
-            FileType::Symlink
-        } else {
-            FileType::File
-        },
+        modified: result.file.modified_at.unwrap_or_else(Utc::now),
+        file_type: file_type(&result.file),
         score: result.score as f32,
         content_preview: result.snippet,
+        link_target: None,
+        matches: result.matches,
+        source: None,
+    }
+}
+
+pub fn convert_entry(entry: crate::FileEntry) -> ListingEntry {
+    ListingEntry {
+        file_type: file_type(&entry),
+        path: entry.path,
+        name: entry.name,
+        size: entry.size,
+        modified: entry.modified_at,
+    }
+}
+
+fn file_type(entry: &crate::FileEntry) -> FileType {
+    if entry.is_directory {
+        FileType::Directory
+    } else if entry.is_symlink {
+        FileType::Symlink
+    } else {
+        FileType::File
     }
 }
 
@@ -343,3 +1553,1636 @@ fn get_memory_usage_mb() -> f64 {
 
     0.0 // Fallback
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::QueryParser;
+
+    #[test]
+    fn test_http_and_parsed_queries_report_identically() {
+        let req: SearchRequest = serde_json::from_value(serde_json::json!({
+            "query": "report",
+            "mode": "fuzzy",
+            "filters": {
+                "extensions": ["rs", "toml"],
+                "size_min": 1024,
+                "size_max": 10485760,
+                "scope": "path",
+                "content_query": "timeout"
+            },
+            "limit": 50,
+            "sort": "-modified"
+        }))
+        .unwrap();
+
+        let from_http = build_query(&req, 1000).unwrap().effective();
+        let from_parser = QueryParser::parse(
+            "report ext:rs,toml size:1KB..10MB mode:fuzzy scope:path limit:50 sort:-modified \
+             content:timeout",
+        )
+        .unwrap()
+        .effective();
+
+        assert_eq!(
+            serde_json::to_value(&from_http).unwrap(),
+            serde_json::to_value(&from_parser).unwrap()
+        );
+        assert_eq!(from_http.size[0].display, "size 1.00 KB..10.00 MB");
+
+        let mut req = req;
+        req.sort = Some("color".to_string());
+        assert!(build_query(&req, 1000).is_err());
+    }
+
+    #[test]
+    fn test_search_filters_apply_each_date_field() {
+        let mut req: SearchRequest = serde_json::from_value(serde_json::json!({
+            "query": "invoice",
+            "filters": { "created_after": "2024-01-01T00:00:00Z" }
+        }))
+        .unwrap();
+
+        let effective = build_query(&req, 1000).unwrap().effective();
+        assert!(effective.modified.is_empty());
+        assert_eq!(effective.created[0].op, "after");
+
+        req.filters.accessed_before = req.filters.created_after;
+        let effective = build_query(&req, 1000).unwrap().effective();
+        assert_eq!(effective.created[0].op, "after");
+        assert_eq!(effective.accessed[0].op, "before");
+    }
+
+    #[actix_web::test]
+    async fn test_audit_endpoint_requires_api_key() {
+        use crate::server::config::ServerConfig;
+        use actix_web::{http::StatusCode, test, App};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let engine = crate::SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+        let mut config = ServerConfig::default();
+        config.security.enable_auth = true;
+        config.security.api_key = Some("secret".to_string());
+        let state = web::Data::new(AppState::new(engine, config));
+
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .route("/audit", web::get().to(audit_log)),
+        )
+        .await;
+
+        let denied = test::TestRequest::get().uri("/audit").to_request();
+        assert_eq!(
+            test::call_service(&app, denied).await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+
+        let allowed = test::TestRequest::get()
+            .uri("/audit?types=deleted,renamed")
+            .insert_header(("Authorization", "Bearer secret"))
+            .to_request();
+        assert_eq!(test::call_service(&app, allowed).await.status(), StatusCode::OK);
+
+        let invalid = test::TestRequest::get()
+            .uri("/audit?types=exploded")
+            .insert_header(("X-API-Key", "secret"))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, invalid).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_rebuild_fts_endpoint_requires_api_key() {
+        use crate::server::config::ServerConfig;
+        use actix_web::{http::StatusCode, test, App};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        std::fs::create_dir(&data_dir).unwrap();
+        std::fs::write(data_dir.join("notes.txt"), "quarterly figures").unwrap();
+        let engine = crate::SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .enable_content_search(true)
+            .build()
+            .unwrap();
+        engine.index_directory(&data_dir, None).unwrap();
+
+        let mut config = ServerConfig::default();
+        config.security.enable_auth = true;
+        config.security.api_key = Some("secret".to_string());
+        let state = web::Data::new(AppState::new(engine, config));
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .route("/maintenance/rebuild-fts", web::post().to(rebuild_fts)),
+        )
+        .await;
+
+        let denied = test::TestRequest::post().uri("/maintenance/rebuild-fts").to_request();
+        assert_eq!(
+            test::call_service(&app, denied).await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+
+        let allowed = test::TestRequest::post()
+            .uri("/maintenance/rebuild-fts")
+            .insert_header(("X-API-Key", "secret"))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, allowed).await;
+        assert_eq!(body["files"], 1);
+        assert_eq!(body["blobs"], 1);
+        assert_eq!(body["skipped"], 0);
+    }
+
+    #[actix_web::test]
+    async fn test_backup_endpoint_streams_progress_and_refuses_overlap() {
+        use crate::server::config::ServerConfig;
+        use actix_web::{http::StatusCode, test, App};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let engine = crate::SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .build()
+            .unwrap();
+        let mut config = ServerConfig::default();
+        config.security.enable_auth = true;
+        config.security.api_key = Some("secret".to_string());
+        let state = web::Data::new(AppState::new(engine, config));
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .route("/maintenance/backup", web::post().to(backup))
+                .route("/maintenance/restore", web::post().to(restore)),
+        )
+        .await;
+        let dest = temp_dir.path().join("backup.db");
+        let request = || {
+            test::TestRequest::post()
+                .uri("/maintenance/backup")
+                .set_json(serde_json::json!({ "path": dest, "compress": true }))
+        };
+
+        let denied = test::call_service(&app, request().to_request()).await;
+        assert_eq!(denied.status(), StatusCode::UNAUTHORIZED);
+
+        state.backup_running.store(true, Ordering::SeqCst);
+        let busy = request().insert_header(("X-API-Key", "secret")).to_request();
+        let busy = test::call_service(&app, busy).await;
+        assert_eq!(busy.status(), StatusCode::CONFLICT);
+        state.backup_running.store(false, Ordering::SeqCst);
+
+        let allowed = request().insert_header(("X-API-Key", "secret")).to_request();
+        let body = test::call_and_read_body(&app, allowed).await;
+        let events: Vec<serde_json::Value> = body
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+        let (done, progress) = events.split_last().unwrap();
+        assert!(!progress.is_empty());
+        assert!(progress.iter().all(|event| event["event"] == "progress"));
+        assert_eq!(done["event"], "done");
+        assert_eq!(done["path"], serde_json::json!(temp_dir.path().join("backup.db.gz")));
+        assert!(temp_dir.path().join("backup.db.gz").exists());
+        assert!(!state.backup_running.load(Ordering::SeqCst));
+
+        let restore = test::TestRequest::post()
+            .uri("/maintenance/restore")
+            .insert_header(("X-API-Key", "secret"))
+            .to_request();
+        let res = test::call_service(&app, restore).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+        let body: serde_json::Value = test::read_body_json(res).await;
+        assert_eq!(body["error"], "restore_requires_offline");
+    }
+
+    #[actix_web::test]
+    async fn test_sensitive_files_hidden_from_search_and_exports() {
+        use crate::server::config::ServerConfig;
+        use crate::ChangeRecord;
+        use actix_web::{test, App};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        std::fs::create_dir(&data_dir).unwrap();
+        for name in ["deploy.txt", "deploy.pem", "deploy.key"] {
+            std::fs::write(data_dir.join(name), name).unwrap();
+        }
+
+        for (hide, searched, exported) in [(false, 3, 3), (true, 1, 1)] {
+            let engine = crate::SearchEngine::builder()
+                .index_path(temp_dir.path().join(format!("index-{}.db", hide)))
+                .build()
+                .unwrap();
+            engine.index_directory(&data_dir, None).unwrap();
+            let mut config = ServerConfig::default();
+            config.security.hide_sensitive_in_api = hide;
+            let app = test::init_service(
+                App::new()
+                    .app_data(web::Data::new(AppState::new(engine, config)))
+                    .route("/search", web::post().to(search))
+                    .route("/export/changes", web::get().to(export_changes)),
+            )
+            .await;
+
+            let req = test::TestRequest::post()
+                .uri("/search")
+                .set_json(serde_json::json!({"query": "deploy"}))
+                .to_request();
+            let response: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+            assert_eq!(response["total"], searched, "hide={}", hide);
+            assert_eq!(response["results"].as_array().unwrap().len(), searched, "hide={}", hide);
+
+            // Exports leave sensitive entries out unless asked, which a hiding server ignores.
+            for (uri, upserts) in [
+                ("/export/changes", 1),
+                ("/export/changes?include_sensitive=true", exported),
+            ] {
+                let req = test::TestRequest::get().uri(uri).to_request();
+                let body = test::call_and_read_body(&app, req).await;
+                let records: Vec<ChangeRecord> = body
+                    .split(|b| *b == b'\n')
+                    .filter(|line| !line.is_empty())
+                    .map(|line| serde_json::from_slice(line).unwrap())
+                    .collect();
+                let omitted = match records.last() {
+                    Some(ChangeRecord::Cursor {
+                        omitted_sensitive, ..
+                    }) => *omitted_sensitive,
+                    other => panic!("no cursor record: {:?}", other),
+                };
+                assert_eq!(records.len() - 1, upserts, "{} hide={}", uri, hide);
+                assert_eq!(omitted, 3 - upserts, "{} hide={}", uri, hide);
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_hidden_files_ranked_first_leave_pages_full() {
+        use crate::server::config::ServerConfig;
+        use actix_web::{test, App};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        std::fs::create_dir(&data_dir).unwrap();
+        // The sensitive files are the largest, so they come first by size.
+        for i in 0..4 {
+            std::fs::write(data_dir.join(format!("deploy{}.pem", i)), [b'k'; 1000]).unwrap();
+        }
+        for i in 0..5 {
+            std::fs::write(data_dir.join(format!("deploy{}.txt", i)), vec![b't'; 10 + i])
+                .unwrap();
+        }
+
+        let engine = crate::SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .build()
+            .unwrap();
+        engine.index_directory(&data_dir, None).unwrap();
+        let mut config = ServerConfig::default();
+        config.security.hide_sensitive_in_api = true;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(engine, config)))
+                .route("/search", web::post().to(search)),
+        )
+        .await;
+
+        let mut seen = Vec::new();
+        for (offset, count) in [(0, 2), (2, 2), (4, 1)] {
+            let req = test::TestRequest::post()
+                .uri("/search")
+                .set_json(serde_json::json!({
+                    "query": "deploy",
+                    "sort": "-size",
+                    "limit": 2,
+                    "offset": offset,
+                }))
+                .to_request();
+            let response: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+            assert_eq!(response["total"], 5, "offset {}", offset);
+            assert_eq!(response["has_more"], offset < 4, "offset {}", offset);
+            let results = response["results"].as_array().unwrap();
+            assert_eq!(results.len(), count, "offset {}", offset);
+            seen.extend(results.iter().map(|result| result["name"].as_str().unwrap().to_string()));
+        }
+        assert_eq!(
+            seen,
+            ["deploy4.txt", "deploy3.txt", "deploy2.txt", "deploy1.txt", "deploy0.txt"]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_search_limit_combinations() {
+        use crate::server::config::ServerConfig;
+        use actix_web::{http::StatusCode, test, App};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        std::fs::create_dir(&data_dir).unwrap();
+        for i in 0..8 {
+            std::fs::write(data_dir.join(format!("note{}.txt", i)), "content").unwrap();
+        }
+
+        let engine = crate::SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .max_search_results(5)
+            .build()
+            .unwrap();
+        engine.index_directory(&data_dir, None).unwrap();
+
+        let mut config = ServerConfig::default();
+        config.performance.max_search_results = 3;
+        let state = web::Data::new(AppState::new(engine, config));
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .route("/search", web::post().to(search)),
+        )
+        .await;
+
+        for (body, count, clamped) in [
+            (serde_json::json!({"query": "note", "limit": 2}), 2, false),
+            (serde_json::json!({"query": "note", "limit": 5000}), 3, true),
+            (serde_json::json!({"query": "note"}), 3, true),
+        ] {
+            let req = test::TestRequest::post()
+                .uri("/search")
+                .set_json(&body)
+                .to_request();
+            let response: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+            assert_eq!(response["results"].as_array().unwrap().len(), count, "{}", body);
+            assert_eq!(response["effective_limit"], count, "{}", body);
+            assert_eq!(response["limit_clamped"], clamped, "{}", body);
+        }
+
+        let zero = test::TestRequest::post()
+            .uri("/search")
+            .set_json(serde_json::json!({"query": "note", "limit": 0}))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, zero).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+
+        // Pages follow each other without gaps or repeats, and only the last has no more.
+        let mut seen = Vec::new();
+        for offset in [0, 3, 6] {
+            let page = test::TestRequest::post()
+                .uri("/search")
+                .set_json(serde_json::json!({"query": "note", "offset": offset}))
+                .to_request();
+            let response: serde_json::Value = test::call_and_read_body_json(&app, page).await;
+            assert_eq!(response["total"], 8, "offset {}", offset);
+            assert_eq!(response["has_more"], offset < 6, "offset {}", offset);
+            for result in response["results"].as_array().unwrap() {
+                seen.push(result["name"].as_str().unwrap().to_string());
+            }
+        }
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len(), 8);
+
+        let bad_regex = test::TestRequest::post()
+            .uri("/search")
+            .set_json(serde_json::json!({"query": "note[", "mode": "regex"}))
+            .to_request();
+        let response = test::call_service(&app, bad_regex).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["error"], "invalid_query.regex");
+    }
+
+    #[actix_web::test]
+    async fn test_structured_search_takes_a_serialized_query() {
+        use crate::server::config::ServerConfig;
+        use actix_web::{http::StatusCode, test, App};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        std::fs::create_dir(&data_dir).unwrap();
+        for (name, content) in [
+            ("note1.txt", "long enough"),
+            ("note2.txt", "tiny"),
+            ("note3.md", "long enough"),
+            ("note4.txt", "long enough"),
+            ("note5.txt", "long enough"),
+        ] {
+            std::fs::write(data_dir.join(name), content).unwrap();
+        }
+
+        let engine = crate::SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .build()
+            .unwrap();
+        engine.index_directory(&data_dir, None).unwrap();
+
+        let mut config = ServerConfig::default();
+        config.performance.max_search_results = 2;
+        let state = web::Data::new(AppState::new(engine, config));
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .route("/search/structured", web::post().to(search_structured)),
+        )
+        .await;
+
+        // Exclusions and `at_least` sizes have no `SearchRequest` filter.
+        let query = serde_json::json!({
+            "pattern": "note",
+            "excluded_extensions": ["md"],
+            "size_filters": [{"at_least": 5}],
+            "sort_by": "name",
+            "max_results": 10,
+            "offset": 1
+        });
+        let req = test::TestRequest::post()
+            .uri("/search/structured")
+            .set_json(serde_json::json!({"query": query, "fields": ["name"]}))
+            .to_request();
+        let response: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let names: Vec<&str> = response["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| result["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["note4.txt", "note5.txt"]);
+        assert_eq!(response["total"], 3);
+        assert_eq!(response["has_more"], false);
+        assert_eq!(response["limit_clamped"], true);
+        assert_eq!(response["effective_query"]["excluded_extensions"], serde_json::json!(["md"]));
+        assert_eq!(response["effective_query"]["sort"], "name");
+
+        for body in [
+            serde_json::json!({"query": {"pattern": "note", "max_results": 0}}),
+            serde_json::json!({"query": {"pattern": "note", "sort_by": "colour"}}),
+        ] {
+            let req = test::TestRequest::post()
+                .uri("/search/structured")
+                .set_json(&body)
+                .to_request();
+            let status = test::call_service(&app, req).await.status();
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+        }
+    }
+
+    /// A server indexing `files` under `dir`, federating with `peers`.
+    fn federating_state(
+        dir: &Path,
+        files: &[&str],
+        peers: Vec<crate::server::config::PeerConfig>,
+    ) -> web::Data<AppState> {
+        let data_dir = dir.join("data");
+        std::fs::create_dir(&data_dir).unwrap();
+        for name in files {
+            std::fs::write(data_dir.join(name), "figures").unwrap();
+        }
+        let engine = crate::SearchEngine::builder()
+            .index_path(dir.join("index.db"))
+            .build()
+            .unwrap();
+        engine.index_directory(&data_dir, None).unwrap();
+
+        let mut config = crate::server::config::ServerConfig {
+            peers,
+            ..Default::default()
+        };
+        config.performance.peer_timeout_ms = 300;
+        web::Data::new(AppState::new(engine, config))
+    }
+
+    #[cfg(unix)]
+    #[actix_web::test]
+    async fn test_mapped_keys_cannot_federate() {
+        use crate::server::config::{PeerConfig, SystemUser};
+        use actix_web::{http::StatusCode, test, App};
+
+        let dir_b = tempfile::TempDir::new().unwrap();
+        let server_b = peer_server(federating_state(dir_b.path(), &["beta_report.txt"], vec![]));
+
+        let dir_a = tempfile::TempDir::new().unwrap();
+        let data_dir = dir_a.path().join("data");
+        std::fs::create_dir(&data_dir).unwrap();
+        std::fs::write(data_dir.join("alpha_report.txt"), "figures").unwrap();
+        let engine = crate::SearchEngine::builder()
+            .index_path(dir_a.path().join("index.db"))
+            .record_ownership(true)
+            .build()
+            .unwrap();
+        engine.index_directory(&data_dir, None).unwrap();
+        let mut config = crate::server::config::ServerConfig {
+            peers: vec![PeerConfig {
+                name: "b".to_string(),
+                base_url: server_b.url(""),
+                api_key: None,
+            }],
+            ..Default::default()
+        };
+        config.security.enable_auth = true;
+        config.security.api_key = Some("admin".to_string());
+        config.security.enforce_fs_permissions = true;
+        let bob = SystemUser {
+            uid: 4242,
+            gid: 4242,
+            groups: vec![],
+        };
+        config.security.api_key_users.insert("bob-key".to_string(), bob);
+        let state = web::Data::new(AppState::new(engine, config));
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .route("/search", web::post().to(search)),
+        )
+        .await;
+        let federate = |key: &str| {
+            test::TestRequest::post()
+                .uri("/search")
+                .insert_header(("X-API-Key", key.to_string()))
+                .set_json(serde_json::json!({"query": "report", "federate": true}))
+                .to_request()
+        };
+
+        let response = test::call_service(&app, federate("bob-key")).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["error"], "federation_forbidden");
+        assert!(body.get("results").is_none());
+        assert_eq!(state.federation.usage().federated_queries, 0);
+
+        let response: serde_json::Value =
+            test::call_and_read_body_json(&app, federate("admin")).await;
+        let sources: Vec<&str> = response["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| result["source"].as_str().unwrap())
+            .collect();
+        assert!(sources.contains(&"b"));
+    }
+
+    fn peer_server(state: web::Data<AppState>) -> actix_test::TestServer {
+        actix_test::start(move || {
+            actix_web::App::new().app_data(state.clone()).service(
+                web::scope("/api/v1").route("/search", web::post().to(search)),
+            )
+        })
+    }
+
+    #[actix_web::test]
+    async fn test_federated_search_merges_peer_results() {
+        use crate::server::client::FEDERATED_HEADER;
+        use crate::server::config::PeerConfig;
+        use actix_web::{http::StatusCode, test, App};
+
+        let peer = |name: &str, base_url: String| PeerConfig {
+            name: name.to_string(),
+            base_url,
+            api_key: None,
+        };
+
+        // `b` federates with `c` itself, which a sub-request from `a` must not set off.
+        let dir_c = tempfile::TempDir::new().unwrap();
+        let server_c = peer_server(federating_state(dir_c.path(), &["gamma_report.txt"], vec![]));
+        let dir_b = tempfile::TempDir::new().unwrap();
+        let state_b = federating_state(
+            dir_b.path(),
+            &["beta_report.txt", "beta_notes.txt"],
+            vec![peer("c", server_c.url(""))],
+        );
+        let server_b = peer_server(state_b.clone());
+        // Accepts connections but never answers.
+        let silent = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let dir_a = tempfile::TempDir::new().unwrap();
+        let state_a = federating_state(
+            dir_a.path(),
+            &["alpha_report.txt"],
+            vec![
+                peer("b", server_b.url("")),
+                peer("silent", format!("http://{}", silent.local_addr().unwrap())),
+            ],
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(state_a.clone())
+                .route("/search", web::post().to(search))
+                .route("/stats", web::get().to(get_stats)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/search")
+            .set_json(serde_json::json!({"query": "report", "federate": true}))
+            .to_request();
+        let response: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let mut results: Vec<(&str, &str)> = response["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| {
+                assert!(result["score"].as_f64().unwrap() <= 1.0);
+                (result["name"].as_str().unwrap(), result["source"].as_str().unwrap())
+            })
+            .collect();
+        // Both sources' best results score 1, so their order follows the tempdir paths.
+        results.sort();
+        assert_eq!(results, [("alpha_report.txt", "local"), ("beta_report.txt", "b")]);
+        assert_eq!(response["total"], 2);
+        let peers = response["peers"].as_array().unwrap();
+        assert_eq!(peers.len(), 2);
+        assert_eq!(peers[0]["name"], "b");
+        assert_eq!(peers[0]["status"], "ok");
+        assert_eq!(peers[0]["results"], 1);
+        assert_eq!(peers[1]["name"], "silent");
+        assert_eq!(peers[1]["status"], "timeout");
+        assert!(peers[1]["took_ms"].as_u64().unwrap() >= 300);
+
+        let req = test::TestRequest::post()
+            .uri("/search?federate=b")
+            .set_json(serde_json::json!({"query": "beta", "limit": 1, "offset": 1}))
+            .to_request();
+        let response: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(response["peers"].as_array().unwrap().len(), 1);
+        assert_eq!(response["total"], 2);
+        assert_eq!(response["results"].as_array().unwrap().len(), 1);
+        assert_eq!(response["has_more"], false);
+
+        let req = test::TestRequest::post()
+            .uri("/search?federate=b,nope")
+            .set_json(serde_json::json!({"query": "report"}))
+            .to_request();
+        let status = test::call_service(&app, req).await.status();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::post()
+            .uri("/search")
+            .set_json(serde_json::json!({"query": "report"}))
+            .to_request();
+        let response: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert!(response.get("peers").is_none());
+        assert!(response["results"][0].get("source").is_none());
+
+        // A sub-request asking to federate is answered from the peer's own index.
+        let mut sub_request = server_b
+            .post("/api/v1/search?federate=all")
+            .insert_header((FEDERATED_HEADER, "1"))
+            .send_json(&serde_json::json!({"query": "report", "federate": true}))
+            .await
+            .unwrap();
+        let response: serde_json::Value = sub_request.json().await.unwrap();
+        assert!(response.get("peers").is_none());
+        assert_eq!(response["total"], 1);
+        assert_eq!(state_b.federation.usage().federated_queries, 0);
+
+        let req = test::TestRequest::get().uri("/stats").to_request();
+        let stats: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let federation = &stats["federation"];
+        assert_eq!(federation["federated_queries"], 2);
+        assert_eq!(federation["peers"][0]["name"], "b");
+        assert_eq!(federation["peers"][0]["requests"], 2);
+        assert_eq!(federation["peers"][0]["ok"], 2);
+        assert_eq!(federation["peers"][1]["name"], "silent");
+        assert_eq!(federation["peers"][1]["timeouts"], 1);
+        assert!(federation["peers"][1]["max_latency_ms"].as_u64().unwrap() >= 300);
+    }
+
+    #[actix_web::test]
+    async fn test_capabilities_match_endpoint_behavior() {
+        use crate::server::config::ServerConfig;
+        use actix_web::{http::StatusCode, test, App};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let engine = crate::SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .enable_content_search(false)
+            .build()
+            .unwrap();
+        let mut config = ServerConfig::default();
+        config.security.enable_auth = true;
+        let state = web::Data::new(AppState::new(engine, config));
+
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .route("/capabilities", web::get().to(capabilities))
+                .route("/health", web::get().to(health_check))
+                .route("/search", web::post().to(search)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/capabilities").to_request();
+        let manifest: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(manifest["content_search"], false);
+        assert_eq!(manifest["fuzzy_search"], true);
+        assert_eq!(manifest["auth_required"], true);
+
+        let req = test::TestRequest::get().uri("/health").to_request();
+        let health: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(health["capabilities"]["content_search"], false);
+
+        let req = test::TestRequest::post()
+            .uri("/search")
+            .set_json(serde_json::json!({"query": "report", "filters": {"scope": "content"}}))
+            .to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::NOT_IMPLEMENTED);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["error"], "feature_unavailable");
+    }
+
+    #[test]
+    fn test_capabilities_tolerate_unknown_and_missing_fields() {
+        let parsed: crate::Capabilities = serde_json::from_value(serde_json::json!({
+            "content_search": true,
+            "facets": true
+        }))
+        .unwrap();
+
+        assert!(parsed.content_search);
+        assert!(!parsed.fuzzy_search);
+    }
+
+    #[actix_web::test]
+    async fn test_query_validation_returns_diagnostics() {
+        use actix_web::{http::StatusCode, test, App};
+
+        let app = test::init_service(
+            App::new().route("/query/validate", web::post().to(validate_query)),
+        )
+        .await;
+        let validate = |query: &str| {
+            test::TestRequest::post()
+                .uri("/query/validate")
+                .set_json(serde_json::json!({ "query": query }))
+                .to_request()
+        };
+
+        let response = test::call_service(&app, validate("report ext:pdf")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["effective_query"]["pattern"], "report");
+        assert_eq!(body["effective_query"]["extensions"][0], "pdf");
+
+        let response = test::call_service(&app, validate("report mod:regex size:big")).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["error"], "invalid_query");
+        assert_eq!(body["message"], "Unknown key 'mod', did you mean 'mode'?");
+        assert_eq!(
+            body["details"]["diagnostics"],
+            serde_json::json!([
+                {
+                    "token": "mod",
+                    "span": {"start": 7, "end": 10},
+                    "message": "Unknown key 'mod'",
+                    "suggestion": "mode"
+                },
+                {
+                    "token": "big",
+                    "span": {"start": 22, "end": 25},
+                    "message": "Invalid size filter: big",
+                    "suggestion": null
+                }
+            ])
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_smart_folder_etag_tracks_index_changes() {
+        use crate::server::config::ServerConfig;
+        use actix_web::{http::StatusCode, test, App};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("downloads");
+        std::fs::create_dir(&data_dir).unwrap();
+        std::fs::write(data_dir.join("first.pdf"), "one").unwrap();
+
+        let engine = crate::SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+        engine.index_directory(&data_dir, None).unwrap();
+        engine.save_search("recent", "pdf ext:pdf", None).unwrap();
+
+        let state = web::Data::new(AppState::new(engine, ServerConfig::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .route("/smart/{name}", web::get().to(smart_folder)),
+        )
+        .await;
+
+        let first = test::TestRequest::get().uri("/smart/recent").to_request();
+        let first = test::call_service(&app, first).await;
+        assert_eq!(first.status(), StatusCode::OK);
+        let etag = first.headers().get(header::ETAG).unwrap().clone();
+        let body: serde_json::Value = test::read_body_json(first).await;
+        assert_eq!(body["total"], 1);
+        assert_eq!(body["sort"], "modified_desc");
+
+        let cached = test::TestRequest::get()
+            .uri("/smart/recent")
+            .insert_header((header::IF_NONE_MATCH, etag.clone()))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, cached).await.status(),
+            StatusCode::NOT_MODIFIED
+        );
+
+        std::fs::write(data_dir.join("second.pdf"), "two").unwrap();
+        state.engine.read().index_directory(&data_dir, None).unwrap();
+
+        let changed = test::TestRequest::get()
+            .uri("/smart/recent")
+            .insert_header((header::IF_NONE_MATCH, etag.clone()))
+            .to_request();
+        let changed = test::call_service(&app, changed).await;
+        assert_eq!(changed.status(), StatusCode::OK);
+        assert_ne!(changed.headers().get(header::ETAG).unwrap(), &etag);
+        let body: serde_json::Value = test::read_body_json(changed).await;
+        assert_eq!(body["total"], 2);
+        assert_eq!(body["entries"].as_array().unwrap().len(), 2);
+
+        state.engine.read().delete_saved_search("recent").unwrap();
+        let missing = test::TestRequest::get().uri("/smart/recent").to_request();
+        assert_eq!(
+            test::call_service(&app, missing).await.status(),
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_field_selection_over_http() {
+        use crate::server::config::ServerConfig;
+        use actix_web::{http::StatusCode, test, App};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("reports");
+        std::fs::create_dir(&data_dir).unwrap();
+        std::fs::write(data_dir.join("q1.txt"), "quarterly figures for q1").unwrap();
+        std::fs::write(data_dir.join("q2.txt"), "quarterly figures for q2").unwrap();
+
+        let engine = crate::SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .enable_content_search(true)
+            .build()
+            .unwrap();
+        engine.index_directory(&data_dir, None).unwrap();
+        engine.save_search("texts", "q ext:txt", None).unwrap();
+
+        let state = web::Data::new(AppState::new(engine, ServerConfig::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .route("/search", web::post().to(search))
+                .route("/smart/{name}", web::get().to(smart_folder)),
+        )
+        .await;
+        let search_with = |fields: serde_json::Value| {
+            let mut body = serde_json::json!({
+                "query": "quarterly",
+                "filters": {"scope": "content"},
+            });
+            if !fields.is_null() {
+                body["fields"] = fields;
+            }
+            test::TestRequest::post().uri("/search").set_json(body).to_request()
+        };
+
+        let full: serde_json::Value =
+            test::call_and_read_body_json(&app, search_with(serde_json::Value::Null)).await;
+        let full = full["results"].as_array().unwrap().clone();
+        assert_eq!(full.len(), 2);
+        assert!(full.iter().all(|r| r.get("content_preview").is_none() && r["id"].is_i64()));
+
+        let picked: serde_json::Value = test::call_and_read_body_json(
+            &app,
+            search_with(serde_json::json!(["score", "path", "name", "content_preview"])),
+        )
+        .await;
+        let picked = picked["results"].as_array().unwrap();
+        for (picked, full) in picked.iter().zip(&full) {
+            let keys: Vec<&String> = picked.as_object().unwrap().keys().collect();
+            assert_eq!(keys, ["content_preview", "name", "path", "score"]);
+            assert!(picked["content_preview"].as_str().unwrap().starts_with("**quarterly** "));
+            assert_eq!(picked["path"], full["path"]);
+            assert_eq!(picked["score"], full["score"]);
+        }
+
+        let rejected = test::call_service(&app, search_with(serde_json::json!(["owner"]))).await;
+        assert_eq!(rejected.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::read_body_json(rejected).await;
+        assert_eq!(body["error"], "invalid_field");
+        assert_eq!(
+            body["message"],
+            "Unknown field 'owner'; valid fields: id, path, name, size, modified, file_type, \
+             score, content_preview, link_target, matches, source"
+        );
+
+        let listing = test::TestRequest::get()
+            .uri("/smart/texts?fields=name,size")
+            .to_request();
+        let listing: serde_json::Value = test::call_and_read_body_json(&app, listing).await;
+        assert_eq!(listing["total"], 2);
+        for entry in listing["entries"].as_array().unwrap() {
+            assert_eq!(entry.as_object().unwrap().len(), 2);
+            assert_eq!(entry["size"], 24);
+        }
+
+        let bad_listing = test::TestRequest::get()
+            .uri("/smart/texts?fields=score")
+            .to_request();
+        let bad_listing = test::call_service(&app, bad_listing).await;
+        assert_eq!(bad_listing.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::read_body_json(bad_listing).await;
+        assert_eq!(body["details"]["valid_fields"][0], "path");
+    }
+
+    #[cfg(feature = "thumbnails")]
+    #[actix_web::test]
+    async fn test_thumbnail_endpoint() {
+        use crate::server::config::ServerConfig;
+        use actix_web::{http::StatusCode, test, App};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("photos");
+        std::fs::create_dir(&data_dir).unwrap();
+        image::RgbImage::from_pixel(600, 300, image::Rgb([10, 120, 200]))
+            .save(data_dir.join("sunset.png"))
+            .unwrap();
+        std::fs::write(data_dir.join("notes.txt"), "not a picture").unwrap();
+
+        let engine = crate::SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .extract_image_metadata(true)
+            .build()
+            .unwrap();
+        engine.index_directory(&data_dir, None).unwrap();
+        let id = |query: &str| engine.search(query).unwrap()[0].file.id.unwrap();
+        let (photo, notes) = (id("sunset"), id("notes"));
+        assert_eq!(id("width:>500 height:300"), photo);
+
+        let state = web::Data::new(AppState::new(engine, ServerConfig::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .route("/files/{id}/thumbnail", web::get().to(thumbnail)),
+        )
+        .await;
+        let uri = |id: i64, size: u32| format!("/files/{}/thumbnail?size={}", id, size);
+
+        let first = test::TestRequest::get().uri(&uri(photo, 128)).to_request();
+        let first = test::call_service(&app, first).await;
+        assert_eq!(first.status(), StatusCode::OK);
+        assert_eq!(first.headers().get(header::CONTENT_TYPE).unwrap(), "image/jpeg");
+        let etag = first.headers().get(header::ETAG).unwrap().clone();
+        let decoded = image::load_from_memory(&test::read_body(first).await).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (128, 64));
+        assert!(state.engine.read().thumbnail(photo, 128).unwrap().cached);
+
+        let revalidated = test::TestRequest::get()
+            .uri(&uri(photo, 128))
+            .insert_header((header::IF_NONE_MATCH, etag))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, revalidated).await.status(),
+            StatusCode::NOT_MODIFIED
+        );
+
+        for (uri, status) in [
+            (uri(photo, 4), StatusCode::BAD_REQUEST),
+            (uri(notes, 128), StatusCode::UNSUPPORTED_MEDIA_TYPE),
+            (uri(9999, 128), StatusCode::NOT_FOUND),
+        ] {
+            let req = test::TestRequest::get().uri(&uri).to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), status, "{}", uri);
+        }
+
+        std::fs::remove_file(data_dir.join("sunset.png")).unwrap();
+        let gone = test::TestRequest::get().uri(&uri(photo, 256)).to_request();
+        assert_eq!(test::call_service(&app, gone).await.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_path_endpoints_enforce_allowed_roots() {
+        use crate::server::config::ServerConfig;
+        use actix_web::{test, App};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let allowed = temp_dir.path().join("allowed");
+        let outside = temp_dir.path().join("outside");
+        std::fs::create_dir(&allowed).unwrap();
+        std::fs::create_dir(&outside).unwrap();
+        std::fs::write(allowed.join("notes.txt"), "content").unwrap();
+
+        let engine = crate::SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+        let mut config = ServerConfig::default();
+        config.security.allowed_roots = vec![allowed.clone()];
+        let state = web::Data::new(AppState::new(engine, config));
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .route("/index", web::post().to(index))
+                .route("/update", web::post().to(update))
+                .route("/watch", web::post().to(start_watch)),
+        )
+        .await;
+
+        let accepted = test::TestRequest::post()
+            .uri("/index")
+            .set_json(serde_json::json!({ "path": allowed }))
+            .to_request();
+        let response: serde_json::Value = test::call_and_read_body_json(&app, accepted).await;
+        assert_eq!(response["indexed_count"], 1);
+
+        for (uri, path, status, error) in [
+            ("/index", outside.clone(), StatusCode::FORBIDDEN, "path_not_allowed"),
+            ("/watch", outside.clone(), StatusCode::FORBIDDEN, "path_not_allowed"),
+            ("/update", allowed.join("..").join("outside"), StatusCode::FORBIDDEN, "path_traversal"),
+            ("/index", allowed.join("missing"), StatusCode::BAD_REQUEST, "invalid_path"),
+        ] {
+            let req = test::TestRequest::post()
+                .uri(uri)
+                .set_json(serde_json::json!({ "path": path }))
+                .to_request();
+            let response = test::call_service(&app, req).await;
+            assert_eq!(response.status(), status, "{} {}", uri, path.display());
+            let body: serde_json::Value = test::read_body_json(response).await;
+            assert_eq!(body["error"], error, "{} {}", uri, path.display());
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_activity_feed_keeps_operations_in_order() {
+        use crate::server::config::ServerConfig;
+        use actix_web::{test, App};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data = temp_dir.path().join("data");
+        std::fs::create_dir(&data).unwrap();
+        std::fs::write(data.join("notes.txt"), "content").unwrap();
+        let engine = crate::SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+
+        let mut config = ServerConfig::default();
+        config.security.enable_auth = true;
+        config.security.api_key = Some("secret".to_string());
+        config.performance.activity_buffer = 4;
+        let state = web::Data::new(AppState::new(engine, config));
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .route("/index", web::post().to(index))
+                .route("/update", web::post().to(update))
+                .route("/watch", web::post().to(start_watch))
+                .route("/watch/{id}", web::delete().to(stop_watch))
+                .route("/activity", web::get().to(activity)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/index")
+            .set_json(serde_json::json!({ "path": data }))
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        let req = test::TestRequest::post()
+            .uri("/update")
+            .set_json(serde_json::json!({ "path": data }))
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        let req = test::TestRequest::post()
+            .uri("/watch")
+            .set_json(serde_json::json!({ "path": data, "initial_scan": false }))
+            .to_request();
+        let watch: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let req = test::TestRequest::delete()
+            .uri(&format!("/watch/{}", watch["watch_id"].as_str().unwrap()))
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let read = |uri: &str| {
+            test::TestRequest::get()
+                .uri(uri)
+                .insert_header(("X-API-Key", "secret"))
+                .to_request()
+        };
+        let feed: serde_json::Value = test::call_and_read_body_json(&app, read("/activity")).await;
+        let events: Vec<(u64, &str)> = feed["events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|event| (event["id"].as_u64().unwrap(), event["type"].as_str().unwrap()))
+            .collect();
+        // The buffer holds 4, so `index_started` (1) is gone.
+        assert_eq!(
+            events,
+            [
+                (2, "index_finished"),
+                (3, "update_finished"),
+                (4, "watch_started"),
+                (5, "watch_stopped"),
+            ]
+        );
+        assert_eq!(feed["events"][0]["indexed"], 1);
+        assert_eq!(feed["events"][2]["watch_id"], watch["watch_id"]);
+        assert_eq!(feed["last_id"], 5);
+
+        let resumed: serde_json::Value =
+            test::call_and_read_body_json(&app, read("/activity?since=3")).await;
+        let ids: Vec<u64> = resumed["events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|event| event["id"].as_u64().unwrap())
+            .collect();
+        assert_eq!(ids, [4, 5]);
+
+        let behind: serde_json::Value =
+            test::call_and_read_body_json(&app, read("/activity?since=0")).await;
+        assert_eq!(behind["events"][0]["type"], "gap");
+        assert_eq!(behind["events"][0]["id"], 1);
+        assert_eq!(behind["events"][0]["missed"], 1);
+        assert_eq!(behind["events"][1]["id"], 2);
+
+        let current: serde_json::Value =
+            test::call_and_read_body_json(&app, read("/activity?since=5")).await;
+        assert_eq!(current["events"].as_array().unwrap().len(), 0);
+        assert_eq!(current["last_id"], 5);
+
+        let denied = test::TestRequest::get().uri("/activity").to_request();
+        assert_eq!(
+            test::call_service(&app, denied).await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    /// The id and type of the next activity record on a WebSocket.
+    async fn next_record<S>(socket: &mut S) -> (u64, String)
+    where
+        S: futures::Stream<Item = Result<awc::ws::Frame, awc::error::WsProtocolError>> + Unpin,
+    {
+        let frame = tokio::time::timeout(Duration::from_secs(10), socket.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let awc::ws::Frame::Text(text) = frame else {
+            panic!("unexpected frame {:?}", frame);
+        };
+        let record: serde_json::Value = serde_json::from_slice(&text).unwrap();
+        (record["id"].as_u64().unwrap(), record["type"].as_str().unwrap().to_string())
+    }
+
+    #[actix_web::test]
+    async fn test_websocket_pushes_new_activity() {
+        use crate::server::activity::ActivityEvent;
+        use crate::server::client::PeerClient;
+        use crate::server::config::ServerConfig;
+        use crate::server::websocket::websocket_handler;
+        use futures::SinkExt;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data = temp_dir.path().join("data");
+        std::fs::create_dir(&data).unwrap();
+        std::fs::write(data.join("notes.txt"), "content").unwrap();
+        let engine = crate::SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+        let state = web::Data::new(AppState::new(engine, ServerConfig::default()));
+        state.activity.record(ActivityEvent::MaintenanceFinished {
+            task: "background".to_string(),
+            took_ms: None,
+        });
+
+        let app_state = state.clone();
+        let mut server = actix_test::start(move || {
+            actix_web::App::new()
+                .app_data(app_state.clone())
+                .route("/ws", web::get().to(websocket_handler))
+                .service(
+                    web::scope("/api/v1")
+                        .route("/index", web::post().to(index))
+                        .route("/activity", web::get().to(activity)),
+                )
+        });
+
+        let mut socket = server.ws_at("/ws").await.unwrap();
+        let subscribe = serde_json::json!({ "type": "subscribe_activity", "since": 0 });
+        socket
+            .send(awc::ws::Message::Text(subscribe.to_string().into()))
+            .await
+            .unwrap();
+        let backlog = next_record(&mut socket).await;
+        assert_eq!(backlog, (1, "maintenance_finished".to_string()));
+
+        let response = server
+            .post("/api/v1/index")
+            .send_json(&serde_json::json!({ "path": data }))
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+        assert_eq!(next_record(&mut socket).await, (2, "index_started".to_string()));
+        assert_eq!(next_record(&mut socket).await, (3, "index_finished".to_string()));
+
+        // Polling sees the same events.
+        let polled = PeerClient::connect(&server.url(""), None).activity(Some(1)).await.unwrap();
+        let ids: Vec<u64> = polled.events.iter().map(|record| record.id).collect();
+        assert_eq!(ids, [2, 3]);
+        assert_eq!(polled.last_id, 3);
+    }
+
+    #[actix_web::test]
+    async fn test_update_several_roots_over_http() {
+        use crate::server::config::ServerConfig;
+        use actix_web::{test, App};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let docs = temp_dir.path().join("docs");
+        let notes = temp_dir.path().join("notes");
+        let engine = crate::SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+        for dir in [&docs, &notes] {
+            std::fs::create_dir(dir).unwrap();
+            engine.index_directory(dir, None).unwrap();
+            std::fs::write(dir.join("new.txt"), "content").unwrap();
+        }
+        std::fs::write(notes.join("more.txt"), "content").unwrap();
+
+        let state = web::Data::new(AppState::new(engine, ServerConfig::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .route("/update", web::post().to(update)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/update")
+            .set_json(serde_json::json!({ "paths": [docs, notes] }))
+            .to_request();
+        let response: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(response["added"], 3);
+        assert_eq!(response["roots"][0]["added"], 1);
+        assert_eq!(response["roots"][1]["added"], 2);
+
+        std::fs::write(docs.join("latest.txt"), "content").unwrap();
+        let req = test::TestRequest::post()
+            .uri("/update")
+            .set_json(serde_json::json!({ "all": true }))
+            .to_request();
+        let response: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(response["added"], 1);
+        assert_eq!(response["roots"].as_array().unwrap().len(), 2);
+
+        for body in [
+            serde_json::json!({}),
+            serde_json::json!({ "path": docs, "all": true }),
+            serde_json::json!({ "path": docs, "paths": [notes] }),
+        ] {
+            let req = test::TestRequest::post()
+                .uri("/update")
+                .set_json(&body)
+                .to_request();
+            let response = test::call_service(&app, req).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", body);
+            let error: serde_json::Value = test::read_body_json(response).await;
+            assert_eq!(error["error"], "invalid_request", "{}", body);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_stats_report_engine_search_metrics() {
+        use crate::server::config::ServerConfig;
+        use actix_web::{test, App};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let engine = crate::SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+        // Searches made through the library count toward the server's stats as well.
+        engine.search("note").unwrap();
+        let state = web::Data::new(AppState::new(engine, ServerConfig::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .route("/search", web::post().to(search))
+                .route("/stats", web::get().to(get_stats)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/search")
+            .set_json(serde_json::json!({"query": "note", "mode": "glob"}))
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::get().uri("/stats").to_request();
+        let stats: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let metrics = state.engine.read().metrics();
+        assert_eq!(metrics.total_searches, 2);
+        assert_eq!(metrics.searches_by_mode.glob, 1);
+        assert_eq!(stats["performance"]["total_searches"], metrics.total_searches);
+        assert_eq!(
+            stats["performance"]["avg_search_time_ms"].as_f64().unwrap(),
+            metrics.avg_search_time_ms
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_tag_search_results_over_http() {
+        use crate::server::config::ServerConfig;
+        use actix_web::{http::StatusCode, test, App};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        std::fs::create_dir(&data_dir).unwrap();
+        for name in ["receipt-1.pdf", "receipt-2.pdf", "notes.txt"] {
+            std::fs::write(data_dir.join(name), name).unwrap();
+        }
+        let engine = crate::SearchEngine::new(temp_dir.path().join("index.db")).unwrap();
+        engine.index_directory(&data_dir, None).unwrap();
+
+        let state = web::Data::new(AppState::new(engine, ServerConfig::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .route("/search", web::post().to(search))
+                .route("/tags", web::post().to(tag_files))
+                .route("/tags", web::get().to(list_tags)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/search")
+            .set_json(serde_json::json!({"query": "receipt"}))
+            .to_request();
+        let found: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let ids: Vec<i64> = found["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| result["id"].as_i64().unwrap())
+            .collect();
+        assert_eq!(ids.len(), 2);
+
+        let req = test::TestRequest::post()
+            .uri("/tags")
+            .set_json(serde_json::json!({"tag": "tax-2023", "ids": ids}))
+            .to_request();
+        let tagged: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(tagged["tagged"], 2);
+
+        let req = test::TestRequest::get().uri("/tags").to_request();
+        let tags: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(tags["tags"], serde_json::json!([{"tag": "tax-2023", "count": 2}]));
+
+        let req = test::TestRequest::post()
+            .uri("/search")
+            .set_json(serde_json::json!({"query": "2", "filters": {"tags": ["tax-2023"]}}))
+            .to_request();
+        let filtered: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(filtered["results"][0]["name"], "receipt-2.pdf");
+        assert_eq!(filtered["effective_query"]["tags"], serde_json::json!(["tax-2023"]));
+
+        let invalid = test::TestRequest::post()
+            .uri("/tags")
+            .set_json(serde_json::json!({"tag": " ", "ids": [1]}))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, invalid).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_index_over_quota_returns_insufficient_storage() {
+        use crate::server::config::ServerConfig;
+        use actix_web::{test, App};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data = temp_dir.path().join("data");
+        std::fs::create_dir(&data).unwrap();
+        std::fs::write(data.join("notes.txt"), "content").unwrap();
+
+        let config = crate::SearchConfigBuilder::new().max_index_size_bytes(1).build();
+        let engine = crate::SearchEngine::with_config(temp_dir.path().join("index.db"), config)
+            .unwrap();
+        let state = web::Data::new(AppState::new(engine, ServerConfig::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .route("/index", web::post().to(index))
+                .route("/stats", web::get().to(get_stats)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/index")
+            .set_json(serde_json::json!({ "path": data }))
+            .to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::INSUFFICIENT_STORAGE);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["error"], "quota_exceeded");
+        assert_eq!(body["details"]["indexed_count"], 0);
+        assert_eq!(body["details"]["limit_bytes"], 1);
+
+        let req = test::TestRequest::get().uri("/stats").to_request();
+        let stats: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(stats["total_files"], 0);
+        assert_eq!(stats["quota"]["limit_bytes"], 1);
+        assert!(stats["quota"]["usage_percent"].as_f64().unwrap() > 100.0);
+    }
+
+    #[cfg(unix)]
+    #[actix_web::test]
+    async fn test_whole_index_endpoints_refuse_or_narrow_mapped_keys() {
+        use crate::server::config::{ServerConfig, SystemUser};
+        use actix_web::{http::StatusCode, test, App};
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        let shared = data_dir.join("shared");
+        let private = data_dir.join("private");
+        fs::create_dir_all(&shared).unwrap();
+        fs::create_dir_all(&private).unwrap();
+        fs::set_permissions(&shared, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::set_permissions(&private, fs::Permissions::from_mode(0o700)).unwrap();
+        fs::write(shared.join("report-open.txt"), "open").unwrap();
+        fs::write(private.join("report-private.txt"), "private").unwrap();
+
+        let engine = crate::SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .record_ownership(true)
+            .build()
+            .unwrap();
+        engine.index_directory(&data_dir, None).unwrap();
+        let query = crate::QueryParser::parse("report").unwrap();
+        let ids: Vec<i64> = engine
+            .search_paged(&query, None)
+            .unwrap()
+            .results
+            .iter()
+            .filter_map(|result| result.file.id)
+            .collect();
+        assert_eq!(ids.len(), 2);
+        engine.tag_files(&ids, "q3").unwrap();
+
+        let mut config = ServerConfig::default();
+        config.security.enable_auth = true;
+        config.security.api_key = Some("admin".to_string());
+        config.security.enforce_fs_permissions = true;
+        let bob = SystemUser {
+            uid: 4242,
+            gid: 4242,
+            groups: vec![],
+        };
+        config.security.api_key_users.insert("bob-key".to_string(), bob);
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(engine, config)))
+                .route("/tags", web::post().to(tag_files))
+                .route("/tags", web::get().to(list_tags))
+                .route("/stats", web::get().to(get_stats))
+                .route("/export/changes", web::get().to(export_changes)),
+        )
+        .await;
+        let get = |uri: &str, key: Option<&str>| {
+            let mut request = test::TestRequest::get().uri(uri);
+            if let Some(key) = key {
+                request = request.insert_header(("X-API-Key", key.to_string()));
+            }
+            request.to_request()
+        };
+
+        for uri in ["/stats", "/export/changes"] {
+            for (key, status) in [
+                (None, StatusCode::UNAUTHORIZED),
+                (Some("bob-key"), StatusCode::FORBIDDEN),
+                (Some("admin"), StatusCode::OK),
+            ] {
+                let response = test::call_service(&app, get(uri, key)).await;
+                assert_eq!(response.status(), status, "{} {:?}", uri, key);
+            }
+        }
+
+        let anonymous = test::call_service(&app, get("/tags", None)).await;
+        assert_eq!(anonymous.status(), StatusCode::UNAUTHORIZED);
+        for (key, count) in [("admin", 2), ("bob-key", 1)] {
+            let tags: serde_json::Value =
+                test::call_and_read_body_json(&app, get("/tags", Some(key))).await;
+            assert_eq!(tags["tags"], serde_json::json!([{"tag": "q3", "count": count}]));
+        }
+
+        let tag = test::TestRequest::post()
+            .uri("/tags")
+            .insert_header(("X-API-Key", "bob-key"))
+            .set_json(serde_json::json!({"tag": "mine", "ids": ids}))
+            .to_request();
+        let tagged: serde_json::Value = test::call_and_read_body_json(&app, tag).await;
+        assert_eq!(tagged["tagged"], 1);
+    }
+
+    #[cfg(unix)]
+    #[actix_web::test]
+    async fn test_mapped_keys_only_see_what_their_account_can_read() {
+        use crate::server::config::{ServerConfig, SystemUser};
+        use actix_web::{http::StatusCode, test, App};
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        let shared = data_dir.join("shared");
+        let private = data_dir.join("private");
+        fs::create_dir_all(&shared).unwrap();
+        fs::create_dir_all(&private).unwrap();
+        fs::set_permissions(&shared, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::set_permissions(&private, fs::Permissions::from_mode(0o700)).unwrap();
+        for (path, mode) in [
+            (shared.join("report-open.txt"), 0o644),
+            (shared.join("report-locked.txt"), 0o600),
+            (private.join("report-private.txt"), 0o644),
+        ] {
+            fs::write(&path, "quarterly report").unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        }
+
+        let engine = crate::SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .enable_content_search(true)
+            .record_ownership(true)
+            .build()
+            .unwrap();
+        engine.index_directory(&data_dir, None).unwrap();
+        engine.save_search("reports", "report ext:txt", None).unwrap();
+
+        let mut config = ServerConfig::default();
+        config.security.enable_auth = true;
+        config.security.api_key = Some("admin".to_string());
+        config.security.enforce_fs_permissions = true;
+        let bob = SystemUser {
+            uid: 4242,
+            gid: 4242,
+            groups: vec![],
+        };
+        config.security.api_key_users.insert("bob-key".to_string(), bob);
+        let state = web::Data::new(AppState::new(engine, config));
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .route("/search", web::post().to(search))
+                .route("/smart/{name}", web::get().to(smart_folder))
+                .route("/capabilities", web::get().to(capabilities)),
+        )
+        .await;
+        let search_as = |key: Option<&str>| {
+            let body = serde_json::json!({
+                "query": "quarterly",
+                "filters": {"scope": "content"},
+                "context_lines": 1,
+            });
+            let mut request = test::TestRequest::post().uri("/search").set_json(body);
+            if let Some(key) = key {
+                request = request.insert_header(("X-API-Key", key.to_string()));
+            }
+            request.to_request()
+        };
+
+        let anonymous = test::call_service(&app, search_as(None)).await;
+        assert_eq!(anonymous.status(), StatusCode::UNAUTHORIZED);
+
+        let admin: serde_json::Value =
+            test::call_and_read_body_json(&app, search_as(Some("admin"))).await;
+        assert_eq!(admin["total"], 3);
+
+        let limited: serde_json::Value =
+            test::call_and_read_body_json(&app, search_as(Some("bob-key"))).await;
+        assert_eq!(limited["total"], 2);
+        let mut seen: Vec<(String, usize)> = limited["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| {
+                let name = result["name"].as_str().unwrap().to_string();
+                (name, result["matches"].as_array().map_or(0, Vec::len))
+            })
+            .collect();
+        seen.sort();
+        assert_eq!(
+            seen,
+            [("report-locked.txt".to_string(), 0), ("report-open.txt".to_string(), 1)]
+        );
+
+        let listing = test::TestRequest::get()
+            .uri("/smart/reports?limit=1")
+            .insert_header(("X-API-Key", "bob-key"))
+            .to_request();
+        let listing: serde_json::Value = test::call_and_read_body_json(&app, listing).await;
+        assert_eq!(listing["total"], 2);
+        assert_eq!(listing["entries"].as_array().unwrap().len(), 1);
+        assert_eq!(listing["has_more"], true);
+
+        let capabilities = test::TestRequest::get().uri("/capabilities").to_request();
+        let capabilities: serde_json::Value =
+            test::call_and_read_body_json(&app, capabilities).await;
+        assert_eq!(capabilities["permission_filtering"], true);
+    }
+}
@@ -0,0 +1,158 @@
+use actix_web::http::header::HeaderName;
+use futures::{SinkExt, StreamExt};
+
+use crate::server::activity::ActivityRecord;
+use crate::server::config::PeerConfig;
+use crate::server::models::{ActivityResponse, ErrorResponse, PeerSearchResponse, SearchRequest};
+use crate::server::request_id::REQUEST_ID_HEADER;
+use crate::RequestId;
+
+/// Marks a search sent by a federating server. The receiving server answers from its own
+/// index only, so peers that list each other do not ask each other in a loop.
+pub const FEDERATED_HEADER: HeaderName = HeaderName::from_static("x-filesearch-federated");
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("request failed: {0}")]
+    Request(String),
+
+    #[error("peer answered {status}: {body}")]
+    Status { status: u16, body: String },
+
+    #[error("unreadable response: {0}")]
+    Response(String),
+}
+
+/// Typed client for another server's API.
+pub struct PeerClient {
+    base_url: String,
+    api_key: Option<String>,
+    client: awc::Client,
+}
+
+impl PeerClient {
+    /// Requests have no timeout of their own; callers bound them.
+    pub fn new(peer: &PeerConfig) -> Self {
+        Self::connect(&peer.base_url, peer.api_key.clone())
+    }
+
+    /// A client for the server at `base_url`, e.g. `http://127.0.0.1:8080`.
+    pub fn connect(base_url: &str, api_key: Option<String>) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            client: awc::Client::builder().disable_timeout().finish(),
+        }
+    }
+
+    /// Runs `request` on the peer as a federated sub-request, under the same request id.
+    pub async fn search(
+        &self,
+        request: &SearchRequest,
+        request_id: &RequestId,
+    ) -> Result<PeerSearchResponse, ClientError> {
+        let mut builder = self
+            .client
+            .post(format!("{}/api/v1/search", self.base_url))
+            .insert_header((FEDERATED_HEADER, "1"))
+            .insert_header((REQUEST_ID_HEADER, request_id.as_str()));
+        if let Some(key) = &self.api_key {
+            builder = builder.insert_header(("X-API-Key", key.as_str()));
+        }
+
+        let mut response = builder
+            .send_json(request)
+            .await
+            .map_err(|e| ClientError::Request(e.to_string()))?;
+        if !response.status().is_success() {
+            let body = response.body().await.unwrap_or_default();
+            return Err(ClientError::Status {
+                status: response.status().as_u16(),
+                body: String::from_utf8_lossy(&body).into_owned(),
+            });
+        }
+        // Pages of up to `max_search_results` with content previews outgrow awc's 2 MB default.
+        response
+            .json::<PeerSearchResponse>()
+            .limit(64 * 1024 * 1024)
+            .await
+            .map_err(|e| ClientError::Response(e.to_string()))
+    }
+
+    /// The server's activity events after `since`.
+    pub async fn activity(&self, since: Option<u64>) -> Result<ActivityResponse, ClientError> {
+        let mut url = format!("{}/api/v1/activity", self.base_url);
+        if let Some(since) = since {
+            url.push_str(&format!("?since={}", since));
+        }
+        let mut builder = self.client.get(url);
+        if let Some(key) = &self.api_key {
+            builder = builder.insert_header(("X-API-Key", key.as_str()));
+        }
+
+        let mut response = builder
+            .send()
+            .await
+            .map_err(|e| ClientError::Request(e.to_string()))?;
+        if !response.status().is_success() {
+            let body = response.body().await.unwrap_or_default();
+            return Err(ClientError::Status {
+                status: response.status().as_u16(),
+                body: String::from_utf8_lossy(&body).into_owned(),
+            });
+        }
+        response
+            .json::<ActivityResponse>()
+            .limit(64 * 1024 * 1024)
+            .await
+            .map_err(|e| ClientError::Response(e.to_string()))
+    }
+
+    /// Subscribes to the server's activity over its WebSocket, handing `on_record` the
+    /// events after `since` and then each new one, until the server closes the connection.
+    pub async fn follow_activity(
+        &self,
+        since: Option<u64>,
+        mut on_record: impl FnMut(ActivityRecord),
+    ) -> Result<(), ClientError> {
+        let mut request = self.client.ws(format!("{}/ws", self.base_url));
+        if let Some(key) = &self.api_key {
+            request = request.set_header("X-API-Key", key.as_str());
+        }
+        let (_, mut socket) = request
+            .connect()
+            .await
+            .map_err(|e| ClientError::Request(e.to_string()))?;
+
+        let subscribe = serde_json::json!({ "type": "subscribe_activity", "since": since });
+        socket
+            .send(awc::ws::Message::Text(subscribe.to_string().into()))
+            .await
+            .map_err(|e| ClientError::Request(e.to_string()))?;
+
+        while let Some(frame) = socket.next().await {
+            match frame.map_err(|e| ClientError::Response(e.to_string()))? {
+                awc::ws::Frame::Text(text) => {
+                    // File change events share the socket; activity records carry an id.
+                    if let Ok(record) = serde_json::from_slice::<ActivityRecord>(&text) {
+                        on_record(record);
+                    } else if let Ok(error) = serde_json::from_slice::<ErrorResponse>(&text) {
+                        return Err(ClientError::Status {
+                            status: error.code,
+                            body: error.message,
+                        });
+                    }
+                }
+                awc::ws::Frame::Ping(bytes) => {
+                    socket
+                        .send(awc::ws::Message::Pong(bytes))
+                        .await
+                        .map_err(|e| ClientError::Request(e.to_string()))?;
+                }
+                awc::ws::Frame::Close(_) => break,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
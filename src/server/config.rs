@@ -1,4 +1,7 @@
+use crate::core::maintenance::MaintenanceWindow;
+use crate::core::types::MaintenanceTask;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -8,6 +11,21 @@ pub struct ServerConfig {
     pub security: SecuritySettings,
     pub performance: PerformanceSettings,
     pub logging: LoggingSettings,
+    /// Other servers that federated searches also ask.
+    #[serde(default)]
+    pub peers: Vec<PeerConfig>,
+}
+
+/// A server whose results a federated search merges with this one's.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PeerConfig {
+    /// Names the peer in `federate=` selections and in the `source` of its results.
+    pub name: String,
+    /// Where the peer listens, e.g. `http://10.0.0.2:8080`.
+    pub base_url: String,
+    /// Sent as `X-API-Key` when the peer requires one.
+    #[serde(default)]
+    pub api_key: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -27,6 +45,12 @@ pub struct DatabaseSettings {
     pub pool_size: u32,
     pub max_connections: u32,
     pub connection_timeout: u64,
+    #[serde(default = "default_create_if_missing")]
+    pub create_if_missing: bool,
+}
+
+fn default_create_if_missing() -> bool {
+    true
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -36,6 +60,31 @@ pub struct SecuritySettings {
     pub jwt_expiry: u64,
     pub api_key: Option<String>,
     pub rate_limit_per_minute: u32,
+    /// Directories that index, update and watch requests may target. Empty allows any path.
+    #[serde(default)]
+    pub allowed_roots: Vec<PathBuf>,
+    /// Hide what the system account of a request's API key could not read. Needs an index
+    /// built with ownership recorded, which the server turns on with this. Unix only.
+    #[serde(default)]
+    pub enforce_fs_permissions: bool,
+    /// System accounts that API keys act as, keyed by the key.
+    #[serde(default)]
+    pub api_key_users: HashMap<String, SystemUser>,
+    /// Leave files matching the engine's `sensitive_patterns` out of search results, and out
+    /// of change exports even when they ask to include them. For servers shared by several
+    /// users.
+    #[serde(default)]
+    pub hide_sensitive_in_api: bool,
+}
+
+/// A system account whose file access a request is limited to.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SystemUser {
+    pub uid: u32,
+    pub gid: u32,
+    /// Supplementary groups.
+    #[serde(default)]
+    pub groups: Vec<u32>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -45,6 +94,68 @@ pub struct PerformanceSettings {
     pub index_batch_size: usize,
     pub cache_size: usize,
     pub enable_compression: bool,
+    /// Thumbnails rendered at once; further requests wait for a free slot.
+    #[serde(default = "default_thumbnail_concurrency")]
+    pub thumbnail_concurrency: usize,
+    /// Read the content of quick-indexed roots in the background whenever no searches
+    /// are arriving.
+    #[serde(default = "default_auto_enrich")]
+    pub auto_enrich: bool,
+    /// Merge the full-text index, refresh planner statistics, checkpoint and prune old data
+    /// in short slices whenever the server is idle.
+    #[serde(default = "default_auto_maintenance")]
+    pub auto_maintenance: bool,
+    /// Time without searches or index operations before maintenance starts.
+    #[serde(default = "default_idle_threshold_secs")]
+    pub idle_threshold_secs: u64,
+    #[serde(default = "default_maintenance_slice_ms")]
+    pub maintenance_slice_ms: u64,
+    /// Blackout during which maintenance does not run, e.g. `Mon-Fri 09:00-18:00`.
+    #[serde(default)]
+    pub maintenance_window: Option<MaintenanceWindow>,
+    #[serde(default)]
+    pub disabled_maintenance_tasks: Vec<MaintenanceTask>,
+    /// Searches taking at least this long are logged with their request id.
+    #[serde(default = "default_slow_query_ms")]
+    pub slow_query_ms: u64,
+    /// How long a federated search waits for each peer before reporting it as timed out.
+    #[serde(default = "default_peer_timeout_ms")]
+    pub peer_timeout_ms: u64,
+    /// Activity events kept for `GET /activity` and for WebSocket clients that resume.
+    #[serde(default = "default_activity_buffer")]
+    pub activity_buffer: usize,
+}
+
+fn default_thumbnail_concurrency() -> usize {
+    4
+}
+
+fn default_auto_enrich() -> bool {
+    true
+}
+
+fn default_auto_maintenance() -> bool {
+    true
+}
+
+fn default_idle_threshold_secs() -> u64 {
+    60
+}
+
+fn default_maintenance_slice_ms() -> u64 {
+    200
+}
+
+fn default_slow_query_ms() -> u64 {
+    1000
+}
+
+fn default_peer_timeout_ms() -> u64 {
+    2000
+}
+
+fn default_activity_buffer() -> usize {
+    1000
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -71,6 +182,7 @@ impl Default for ServerConfig {
                 pool_size: 10,
                 max_connections: 100,
                 connection_timeout: 30,
+                create_if_missing: true,
             },
             security: SecuritySettings {
                 enable_auth: false,
@@ -78,6 +190,10 @@ impl Default for ServerConfig {
                 jwt_expiry: 3600,
                 api_key: None,
                 rate_limit_per_minute: 100,
+                allowed_roots: Vec::new(),
+                enforce_fs_permissions: false,
+                api_key_users: HashMap::new(),
+                hide_sensitive_in_api: false,
             },
             performance: PerformanceSettings {
                 max_search_results: 1000,
@@ -85,12 +201,23 @@ impl Default for ServerConfig {
                 index_batch_size: 1000,
                 cache_size: 10000,
                 enable_compression: true,
+                thumbnail_concurrency: default_thumbnail_concurrency(),
+                auto_enrich: default_auto_enrich(),
+                auto_maintenance: default_auto_maintenance(),
+                idle_threshold_secs: default_idle_threshold_secs(),
+                maintenance_slice_ms: default_maintenance_slice_ms(),
+                maintenance_window: None,
+                disabled_maintenance_tasks: Vec::new(),
+                slow_query_ms: default_slow_query_ms(),
+                peer_timeout_ms: default_peer_timeout_ms(),
+                activity_buffer: default_activity_buffer(),
             },
             logging: LoggingSettings {
                 level: "info".to_string(),
                 format: "pretty".to_string(),
                 file: None,
             },
+            peers: Vec::new(),
         }
     }
 }
@@ -0,0 +1,98 @@
+use actix_web::web;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::server::state::AppState;
+use crate::{CancellationToken, EnrichmentStats, SearchError};
+
+/// How long the server must go without a search before enrichment starts.
+const IDLE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Reads the content of quick-indexed roots while no searches are arriving. Work happens one
+/// batch at a time with the engine lock released in between, so a search or watch request
+/// waits for at most one batch, and enrichment pauses until the server is idle again.
+pub async fn auto_enrich(state: web::Data<AppState>) {
+    let mut searches = total_searches(&state);
+
+    loop {
+        tokio::time::sleep(IDLE_INTERVAL).await;
+
+        loop {
+            let current = total_searches(&state);
+            if current != searches {
+                searches = current;
+                break;
+            }
+
+            match enrich_batch(&state).await {
+                Ok(stats) if stats.enriched > 0 && stats.remaining > 0 => continue,
+                Ok(stats) => {
+                    if stats.enriched > 0 {
+                        info!("Background enrichment finished");
+                    }
+                    break;
+                }
+                Err(e) => {
+                    warn!("Background enrichment failed: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Enriches a single batch of pending files.
+pub async fn enrich_batch(state: &AppState) -> Result<EnrichmentStats, SearchError> {
+    let engine = Arc::clone(&state.engine);
+    web::block(move || {
+        let cancel = CancellationToken::new();
+        let stop = cancel.clone();
+        engine
+            .read()
+            .enrich(None, Some(Box::new(move |_| stop.cancel())), &cancel)
+    })
+    .await
+    // The blocking pool only goes away while the server shuts down.
+    .map_err(|_| SearchError::Cancelled)?
+}
+
+fn total_searches(state: &AppState) -> u64 {
+    state.engine.read().metrics().total_searches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::config::ServerConfig;
+
+    #[actix_web::test]
+    async fn test_enrich_batch_drains_queue_one_batch_at_a_time() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        std::fs::create_dir(&data_dir).unwrap();
+        for i in 0..5 {
+            std::fs::write(data_dir.join(format!("note{}.txt", i)), "quarterly figures").unwrap();
+        }
+        let engine = crate::SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .enable_content_search(true)
+            .batch_size(2)
+            .build()
+            .unwrap();
+        engine.index_quick(&data_dir, None).unwrap();
+        let state = AppState::new(engine, ServerConfig::default());
+
+        let mut batches = Vec::new();
+        loop {
+            let stats = enrich_batch(&state).await.unwrap();
+            if stats.enriched == 0 {
+                break;
+            }
+            batches.push((stats.enriched, stats.remaining));
+        }
+
+        assert_eq!(batches, vec![(2, 3), (2, 1), (1, 0)]);
+        assert!(state.engine.read().enrichment_progress().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,308 @@
+use actix_web::HttpRequest;
+use futures::future::join_all;
+use parking_lot::Mutex;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::server::client::{PeerClient, FEDERATED_HEADER};
+use crate::server::config::PeerConfig;
+use crate::server::fields::SelectFields;
+use crate::server::models::{
+    FederationUsage, FileResult, PeerState, PeerStatus, PeerUsage, SearchRequest,
+};
+use crate::{RequestId, SortField, SortKey};
+
+/// `source` of this server's own results in a federated search.
+pub const LOCAL_SOURCE: &str = "local";
+
+/// The peers a search asks: those named by the `federate` parameter, `all` naming every
+/// one, or every one when the body sets `federate`. `None` when the search is not
+/// federated, which sub-requests of another server's federated search never are.
+pub fn selected_peers(
+    peers: &[PeerConfig],
+    http_req: &HttpRequest,
+    federate: bool,
+    param: Option<&str>,
+) -> Result<Option<Vec<PeerConfig>>, String> {
+    if http_req.headers().contains_key(FEDERATED_HEADER) {
+        return Ok(None);
+    }
+    match param.map(str::trim) {
+        None if !federate => Ok(None),
+        None | Some("all") => Ok(Some(peers.to_vec())),
+        Some(names) => names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                peers
+                    .iter()
+                    .find(|peer| peer.name == name)
+                    .cloned()
+                    .ok_or_else(|| format!("unknown peer: {}", name))
+            })
+            .collect::<Result<_, _>>()
+            .map(Some),
+    }
+}
+
+/// What peers are asked for a page of `request`: every result up to the end of the page, so
+/// the page can be taken from the merged results, with the fields merging needs.
+pub fn sub_request(request: &SearchRequest) -> SearchRequest {
+    let mut fields: Vec<String> = FileResult::DEFAULT_FIELDS
+        .iter()
+        .map(|field| field.to_string())
+        .collect();
+    for field in request.fields.iter().flatten() {
+        if !fields.contains(field) {
+            fields.push(field.clone());
+        }
+    }
+    // Peers set no source of their own; older ones would reject the field.
+    fields.retain(|field| field != "source");
+
+    SearchRequest {
+        offset: 0,
+        limit: request.offset.saturating_add(request.limit),
+        fields: Some(fields),
+        federate: false,
+        ..request.clone()
+    }
+}
+
+/// A peer's answer to a federated search.
+pub struct PeerResults {
+    pub status: PeerStatus,
+    pub results: Vec<FileResult>,
+}
+
+/// Sends `request` to every peer at once and waits up to `timeout` for each.
+pub async fn fan_out(
+    peers: &[PeerConfig],
+    request: &SearchRequest,
+    request_id: &RequestId,
+    timeout: Duration,
+) -> Vec<PeerResults> {
+    join_all(
+        peers
+            .iter()
+            .map(|peer| ask_peer(peer, request, request_id, timeout)),
+    )
+    .await
+}
+
+async fn ask_peer(
+    peer: &PeerConfig,
+    request: &SearchRequest,
+    request_id: &RequestId,
+    timeout: Duration,
+) -> PeerResults {
+    let start = Instant::now();
+    let client = PeerClient::new(peer);
+    let outcome = tokio::time::timeout(timeout, client.search(request, request_id)).await;
+    let took_ms = start.elapsed().as_millis() as u64;
+
+    let mut status = PeerStatus {
+        name: peer.name.clone(),
+        status: PeerState::Ok,
+        results: 0,
+        total: 0,
+        took_ms,
+        error: None,
+    };
+    let results = match outcome {
+        Ok(Ok(response)) => {
+            status.results = response.results.len();
+            status.total = response.total;
+            response.results
+        }
+        Ok(Err(e)) => {
+            warn!("Peer {} failed: {}", peer.name, e);
+            status.status = PeerState::Error;
+            status.error = Some(e.to_string());
+            Vec::new()
+        }
+        Err(_) => {
+            warn!("Peer {} did not answer within {:?}", peer.name, timeout);
+            status.status = PeerState::Timeout;
+            Vec::new()
+        }
+    };
+    PeerResults { status, results }
+}
+
+/// Labels every result with where it came from and orders them all by `sort`, or by score.
+/// Each source's scores are scaled so its best result scores 1, as ranking scores from
+/// different indexes are not comparable.
+pub fn merge(
+    local: Vec<FileResult>,
+    peers: &mut [PeerResults],
+    sort: Option<SortKey>,
+) -> Vec<FileResult> {
+    let mut merged = labeled(local, LOCAL_SOURCE);
+    for peer in peers.iter_mut() {
+        let results = std::mem::take(&mut peer.results);
+        merged.extend(labeled(results, &peer.status.name));
+    }
+
+    let sort = sort.unwrap_or(SortKey {
+        field: SortField::Score,
+        descending: true,
+    });
+    merged.sort_by(|a, b| {
+        let order = match sort.field {
+            SortField::Score => a.score.partial_cmp(&b.score).unwrap_or(Ordering::Equal),
+            SortField::Size => a.size.cmp(&b.size),
+            SortField::Modified => a.modified.cmp(&b.modified),
+            SortField::Name => a.name.cmp(&b.name),
+        };
+        let order = if sort.descending { order.reverse() } else { order };
+        order
+            .then_with(|| a.path.cmp(&b.path))
+            .then_with(|| a.source.cmp(&b.source))
+    });
+    merged
+}
+
+fn labeled(mut results: Vec<FileResult>, source: &str) -> Vec<FileResult> {
+    let best = results.iter().map(|result| result.score).fold(0.0, f32::max);
+    for result in &mut results {
+        if best > 0.0 {
+            result.score /= best;
+        }
+        result.source = Some(source.to_string());
+    }
+    results
+}
+
+/// Counts of federated searches and of how each peer answered them, for `GET /stats`.
+#[derive(Default)]
+pub struct FederationStats {
+    inner: Mutex<FederationCounters>,
+}
+
+#[derive(Default)]
+struct FederationCounters {
+    queries: u64,
+    peers: BTreeMap<String, PeerCounters>,
+}
+
+#[derive(Default)]
+struct PeerCounters {
+    requests: u64,
+    ok: u64,
+    timeouts: u64,
+    errors: u64,
+    total_latency_ms: u64,
+    max_latency_ms: u64,
+}
+
+impl FederationStats {
+    pub fn record<'a>(&self, statuses: impl IntoIterator<Item = &'a PeerStatus>) {
+        let mut inner = self.inner.lock();
+        inner.queries += 1;
+        for status in statuses {
+            let counters = inner.peers.entry(status.name.clone()).or_default();
+            counters.requests += 1;
+            match status.status {
+                PeerState::Ok => counters.ok += 1,
+                PeerState::Timeout => counters.timeouts += 1,
+                PeerState::Error => counters.errors += 1,
+            }
+            counters.total_latency_ms += status.took_ms;
+            counters.max_latency_ms = counters.max_latency_ms.max(status.took_ms);
+        }
+    }
+
+    pub fn usage(&self) -> FederationUsage {
+        let inner = self.inner.lock();
+        FederationUsage {
+            federated_queries: inner.queries,
+            peers: inner
+                .peers
+                .iter()
+                .map(|(name, counters)| PeerUsage {
+                    name: name.clone(),
+                    requests: counters.requests,
+                    ok: counters.ok,
+                    timeouts: counters.timeouts,
+                    errors: counters.errors,
+                    avg_latency_ms: counters.total_latency_ms as f64
+                        / counters.requests.max(1) as f64,
+                    max_latency_ms: counters.max_latency_ms,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::models::FileType;
+    use chrono::Utc;
+    use std::path::PathBuf;
+
+    fn result(path: &str, score: f32, size: u64) -> FileResult {
+        FileResult {
+            id: None,
+            path: PathBuf::from(path),
+            name: path.rsplit('/').next().unwrap().to_string(),
+            size,
+            modified: Utc::now(),
+            file_type: FileType::File,
+            score,
+            content_preview: None,
+            link_target: None,
+            matches: Vec::new(),
+            source: None,
+        }
+    }
+
+    fn peer(name: &str, results: Vec<FileResult>) -> PeerResults {
+        PeerResults {
+            status: PeerStatus {
+                name: name.to_string(),
+                status: PeerState::Ok,
+                results: results.len(),
+                total: results.len(),
+                took_ms: 0,
+                error: None,
+            },
+            results,
+        }
+    }
+
+    #[test]
+    fn test_merge_normalizes_scores_per_source() {
+        let local = vec![result("/a/low", 2.0, 30), result("/a/best", 8.0, 10)];
+        let mut peers = [peer(
+            "nas",
+            vec![result("/b/best", 100.0, 20), result("/b/half", 50.0, 40)],
+        )];
+
+        let merged = merge(local, &mut peers, None);
+        let order: Vec<(&str, f32, &str)> = merged
+            .iter()
+            .map(|r| (r.path.to_str().unwrap(), r.score, r.source.as_deref().unwrap()))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                ("/a/best", 1.0, "local"),
+                ("/b/best", 1.0, "nas"),
+                ("/b/half", 0.5, "nas"),
+                ("/a/low", 0.25, "local"),
+            ]
+        );
+        assert!(peers[0].results.is_empty());
+
+        let local = vec![result("/a/low", 2.0, 30), result("/a/best", 8.0, 10)];
+        let mut peers = [peer("nas", vec![result("/b/best", 100.0, 20)])];
+        let merged = merge(local, &mut peers, SortKey::parse("-size"));
+        let sizes: Vec<u64> = merged.iter().map(|r| r.size).collect();
+        assert_eq!(sizes, [30, 20, 10]);
+    }
+}
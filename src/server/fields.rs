@@ -0,0 +1,188 @@
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+/// A response item whose fields can be picked by the client.
+pub trait SelectFields {
+    /// Every field a client may request, in the order they are emitted.
+    const FIELDS: &'static [&'static str];
+    /// Fields returned when the request does not name any. Heavyweight fields are left out.
+    const DEFAULT_FIELDS: &'static [&'static str];
+
+    /// Writes `field` into `map`. Empty optional fields are skipped.
+    fn serialize_field<M: SerializeMap>(&self, field: &str, map: &mut M) -> Result<(), M::Error>;
+}
+
+/// The fields to emit for each item of a response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldSelection(Vec<&'static str>);
+
+impl FieldSelection {
+    pub fn defaults<T: SelectFields>() -> Self {
+        Self(T::DEFAULT_FIELDS.to_vec())
+    }
+
+    /// Validates the requested field names against `T::FIELDS`; `None` selects the defaults.
+    pub fn parse<T: SelectFields, S: AsRef<str>>(
+        requested: Option<&[S]>,
+    ) -> Result<Self, UnknownField> {
+        let Some(requested) = requested else {
+            return Ok(Self::defaults::<T>());
+        };
+
+        if let Some(unknown) = requested
+            .iter()
+            .map(|field| field.as_ref().trim())
+            .find(|field| !T::FIELDS.contains(field))
+        {
+            return Err(UnknownField {
+                field: unknown.to_string(),
+                valid: T::FIELDS,
+            });
+        }
+
+        Ok(Self(
+            T::FIELDS
+                .iter()
+                .copied()
+                .filter(|field| requested.iter().any(|r| r.as_ref().trim() == *field))
+                .collect(),
+        ))
+    }
+
+    /// Parses a comma-separated `?fields=` query parameter.
+    pub fn parse_list<T: SelectFields>(requested: Option<&str>) -> Result<Self, UnknownField> {
+        let requested: Option<Vec<&str>> = requested.map(|list| {
+            list.split(',')
+                .map(str::trim)
+                .filter(|field| !field.is_empty())
+                .collect()
+        });
+        Self::parse::<T, _>(requested.as_deref())
+    }
+
+    pub fn fields(&self) -> &[&'static str] {
+        &self.0
+    }
+}
+
+/// A field name that is not in the item's allowlist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownField {
+    pub field: String,
+    pub valid: &'static [&'static str],
+}
+
+impl fmt::Display for UnknownField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Unknown field '{}'; valid fields: {}",
+            self.field,
+            self.valid.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for UnknownField {}
+
+/// Items serialized with only the selected fields.
+#[derive(Debug)]
+pub struct Selected<T> {
+    items: Vec<T>,
+    fields: FieldSelection,
+}
+
+impl<T> Selected<T> {
+    pub fn new(items: Vec<T>, fields: FieldSelection) -> Self {
+        Self { items, fields }
+    }
+}
+
+impl<T: SelectFields> Serialize for Selected<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.items.len()))?;
+        for item in &self.items {
+            seq.serialize_element(&View {
+                item,
+                fields: self.fields.fields(),
+            })?;
+        }
+        seq.end()
+    }
+}
+
+struct View<'a, T> {
+    item: &'a T,
+    fields: &'a [&'static str],
+}
+
+impl<T: SelectFields> Serialize for View<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        for field in self.fields {
+            self.item.serialize_field(field, &mut map)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::models::{FileResult, FileType, ListingEntry};
+    use chrono::Utc;
+    use std::path::PathBuf;
+
+    fn result() -> FileResult {
+        FileResult {
+            id: Some(7),
+            path: PathBuf::from("/data/report.txt"),
+            name: "report.txt".to_string(),
+            size: 2048,
+            modified: Utc::now(),
+            file_type: FileType::File,
+            score: 0.75,
+            content_preview: Some("quarterly figures".to_string()),
+            link_target: None,
+            matches: Vec::new(),
+            source: None,
+        }
+    }
+
+    #[test]
+    fn test_selection_emits_only_requested_fields() {
+        let fields = FieldSelection::parse::<FileResult, _>(Some(&["score", "path", "name"]))
+            .unwrap();
+        assert_eq!(fields.fields(), ["path", "name", "score"]);
+        let json = serde_json::to_value(Selected::new(vec![result()], fields)).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!([{"path": "/data/report.txt", "name": "report.txt", "score": 0.75}])
+        );
+    }
+
+    #[test]
+    fn test_default_selection_leaves_out_content_preview() {
+        let fields = FieldSelection::parse::<FileResult, String>(None).unwrap();
+        let json = serde_json::to_value(Selected::new(vec![result()], fields)).unwrap();
+        assert!(json[0].get("content_preview").is_none());
+        assert_eq!(json[0]["id"], 7);
+        assert_eq!(json[0]["file_type"], "file");
+
+        let fields = FieldSelection::parse::<FileResult, _>(Some(&["content_preview"])).unwrap();
+        let json = serde_json::to_value(Selected::new(vec![result()], fields)).unwrap();
+        assert_eq!(json, serde_json::json!([{"content_preview": "quarterly figures"}]));
+    }
+
+    #[test]
+    fn test_unknown_field_lists_valid_options() {
+        let err = FieldSelection::parse_list::<ListingEntry>(Some("path, owner")).unwrap_err();
+        assert_eq!(err.field, "owner");
+        assert_eq!(
+            err.to_string(),
+            "Unknown field 'owner'; valid fields: path, name, size, modified, file_type"
+        );
+    }
+}
@@ -4,7 +4,9 @@ use rusty_files::SearchEngine;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 // Import server modules from the library
-use rusty_files::server::{api, config, state, websocket};
+use rusty_files::server::{
+    api, config, enrichment, maintenance, permissions, request_id, state, websocket,
+};
 
 use config::ServerConfig;
 use state::AppState;
@@ -27,21 +29,66 @@ async fn main() -> std::io::Result<()> {
         ServerConfig::default()
     });
 
+    if config.security.allowed_roots.is_empty() {
+        tracing::warn!(
+            "security.allowed_roots is empty; API clients can index and watch any path on this host"
+        );
+    }
+
+    let security = &config.security;
+    if security.enforce_fs_permissions && !permissions::SUPPORTED {
+        tracing::warn!(
+            "security.enforce_fs_permissions needs unix file ownership; results are not filtered"
+        );
+    }
+
     let bind_addr = format!("{}:{}", config.server.host, config.server.port);
 
     tracing::info!("Initializing search engine...");
 
     // Initialize search engine
-    let engine = SearchEngine::new(&config.database.path).map_err(|e| {
-        std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("Failed to initialize search engine: {}", e),
-        )
-    })?;
+    let performance = &config.performance;
+    let mut builder = SearchEngine::builder()
+        .index_path(&config.database.path)
+        .create_if_missing(config.database.create_if_missing)
+        .idle_threshold_secs(performance.idle_threshold_secs)
+        .maintenance_slice_ms(performance.maintenance_slice_ms)
+        .slow_query_ms(performance.slow_query_ms)
+        .disabled_maintenance_tasks(performance.disabled_maintenance_tasks.clone())
+        .record_ownership(security.enforce_fs_permissions);
+    if let Some(window) = &performance.maintenance_window {
+        builder = builder.maintenance_window(window.clone());
+    }
+    let engine = builder
+        .build()
+        .map_err(|e| {
+            std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("Failed to initialize search engine: {}", e),
+            )
+        })?;
+
+    if security.enforce_fs_permissions
+        && permissions::SUPPORTED
+        && !engine.has_permissions().unwrap_or(false)
+    {
+        tracing::warn!(
+            "No file ownership is recorded in the index yet; users mapped in \
+             security.api_key_users see no results until their roots are indexed again"
+        );
+    }
 
     // Create application state
     let state = web::Data::new(AppState::new(engine, config.clone()));
 
+    if config.performance.auto_enrich {
+        actix_web::rt::spawn(enrichment::auto_enrich(state.clone()));
+    }
+
+    if config.performance.auto_maintenance {
+        actix_web::rt::spawn(maintenance::auto_maintain(state.clone()));
+    }
+
     tracing::info!("Starting server on {}", bind_addr);
     tracing::info!("API endpoints available at http://{}/api/v1", bind_addr);
     tracing::info!("WebSocket available at ws://{}/ws", bind_addr);
@@ -56,18 +103,36 @@ async fn main() -> std::io::Result<()> {
 
         App::new()
             .app_data(state.clone())
+            .wrap(middleware::from_fn(request_id::trace_requests))
             .wrap(cors)
-            .wrap(middleware::Logger::default())
+            // The default format, plus the id given by `trace_requests`.
+            .wrap(middleware::Logger::new(
+                r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T %{x-request-id}o"#,
+            ))
             .wrap(middleware::Compress::default())
             // API routes
             .service(
                 web::scope("/api/v1")
                     .route("/search", web::post().to(api::search))
+                    .route("/search/structured", web::post().to(api::search_structured))
+                    .route("/query/validate", web::post().to(api::validate_query))
                     .route("/index", web::post().to(api::index))
                     .route("/update", web::post().to(api::update))
+                    .route("/smart/{name}", web::get().to(api::smart_folder))
+                    .route("/files/{id}/thumbnail", web::get().to(api::thumbnail))
+                    .route("/tags", web::post().to(api::tag_files))
+                    .route("/tags", web::get().to(api::list_tags))
                     .route("/watch", web::post().to(api::start_watch))
+                    .route("/watch/{id}", web::get().to(api::watch_status))
                     .route("/watch/{id}", web::delete().to(api::stop_watch))
+                    .route("/export/changes", web::get().to(api::export_changes))
+                    .route("/audit", web::get().to(api::audit_log))
+                    .route("/activity", web::get().to(api::activity))
+                    .route("/maintenance/rebuild-fts", web::post().to(api::rebuild_fts))
+                    .route("/maintenance/backup", web::post().to(api::backup))
+                    .route("/maintenance/restore", web::post().to(api::restore))
                     .route("/stats", web::get().to(api::get_stats))
+                    .route("/capabilities", web::get().to(api::capabilities))
                     .route("/health", web::get().to(api::health_check)),
             )
             // WebSocket route
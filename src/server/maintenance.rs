@@ -0,0 +1,103 @@
+use actix_web::web;
+use parking_lot::Mutex;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::server::activity::ActivityEvent;
+use crate::server::state::AppState;
+use crate::{MaintenanceScheduler, SearchError, SliceOutcome};
+
+/// Keeps the index tidy while no requests are arriving. Each slice runs on the blocking pool
+/// under the engine's read lock, so a request that needs the write lock waits for at most one
+/// slice, while searches start right away and make the slice stop at its next step.
+pub async fn auto_maintain(state: web::Data<AppState>) {
+    let scheduler = Arc::new(Mutex::new(MaintenanceScheduler::new(
+        state.engine.read().config(),
+    )));
+    let interval = scheduler.lock().poll_interval();
+
+    loop {
+        tokio::time::sleep(interval).await;
+
+        loop {
+            match maintenance_slice(&state, &scheduler).await {
+                Ok(SliceOutcome::Yielded) => continue,
+                Ok(SliceOutcome::Finished) => {
+                    info!("Background maintenance finished");
+                    state.activity.record(ActivityEvent::MaintenanceFinished {
+                        task: "background".to_string(),
+                        took_ms: None,
+                    });
+                    break;
+                }
+                Ok(SliceOutcome::NotDue | SliceOutcome::Preempted) => break,
+                Err(e) => {
+                    warn!("Background maintenance failed: {}", e);
+                    state.activity.record(ActivityEvent::Error {
+                        operation: "maintenance".to_string(),
+                        path: None,
+                        message: e.to_string(),
+                    });
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Runs one slice of maintenance if the server is idle.
+pub async fn maintenance_slice(
+    state: &AppState,
+    scheduler: &Arc<Mutex<MaintenanceScheduler>>,
+) -> Result<SliceOutcome, SearchError> {
+    let engine = Arc::clone(&state.engine);
+    let scheduler = Arc::clone(scheduler);
+    web::block(move || scheduler.lock().run_slice(&engine.read()))
+        .await
+        // The blocking pool only goes away while the server shuts down.
+        .map_err(|_| SearchError::Cancelled)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::config::ServerConfig;
+
+    #[actix_web::test]
+    async fn test_maintenance_slices_run_until_the_pass_finishes() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let engine = crate::SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .idle_threshold_secs(0)
+            .maintenance_slice_ms(0)
+            .build()
+            .unwrap();
+        let state = AppState::new(engine, ServerConfig::default());
+        let scheduler = Arc::new(Mutex::new(MaintenanceScheduler::new(
+            state.engine.read().config(),
+        )));
+
+        let mut slices = 0;
+        loop {
+            slices += 1;
+            match maintenance_slice(&state, &scheduler).await.unwrap() {
+                SliceOutcome::Yielded => continue,
+                outcome => {
+                    assert_eq!(outcome, SliceOutcome::Finished);
+                    break;
+                }
+            }
+        }
+
+        assert!(slices > 1);
+        let stats = state.engine.read().get_stats().unwrap();
+        assert!(stats
+            .maintenance
+            .iter()
+            .all(|status| status.last_run.is_some()));
+        assert_eq!(
+            maintenance_slice(&state, &scheduler).await.unwrap(),
+            SliceOutcome::NotDue
+        );
+    }
+}
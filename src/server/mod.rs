@@ -1,9 +1,19 @@
+pub mod activity;
 pub mod api;
+pub mod client;
 pub mod config;
+pub mod enrichment;
+pub mod federation;
+pub mod fields;
+pub mod maintenance;
 pub mod models;
+pub mod permissions;
+pub mod request_id;
+pub mod security;
 pub mod state;
 pub mod websocket;
 
 pub use config::ServerConfig;
+pub use fields::{FieldSelection, SelectFields, Selected, UnknownField};
 pub use models::*;
 pub use state::AppState;
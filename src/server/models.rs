@@ -1,10 +1,20 @@
+use serde::ser::SerializeMap;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use chrono::{DateTime, Utc};
 
+use crate::server::activity::ActivityRecord;
+use crate::server::fields::{SelectFields, Selected};
+
+use crate::{
+    AuditRecord, BackupReport, Capabilities, EffectiveQuery, EnrichmentProgress, FtsRebuildReport, ListingSort,
+    MaintenanceStatus, MatchLocation, NameIndexUsage, QueryDiagnostic, QuotaUsage, WatchStatus,
+    WriteLease,
+};
+
 // ============ Search Models ============
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct SearchRequest {
     pub query: String,
 
@@ -19,9 +29,44 @@ pub struct SearchRequest {
 
     #[serde(default)]
     pub offset: usize,
+
+    /// Result fields to return; `None` returns every field except `content_preview`.
+    #[serde(default)]
+    pub fields: Option<Vec<String>>,
+
+    /// Lines of context around each matching line of content searches, like `grep -C`.
+    #[serde(default)]
+    pub context_lines: usize,
+
+    /// Result order such as `size` or `-modified`, like the `sort:` query token; `None`
+    /// ranks by score.
+    #[serde(default)]
+    pub sort: Option<String>,
+
+    /// Ask every configured peer as well and merge their results with this server's.
+    #[serde(default)]
+    pub federate: bool,
+}
+
+/// Query string of `POST /search`.
+#[derive(Debug, Deserialize)]
+pub struct SearchParams {
+    /// `all`, or comma-separated peer names, to federate with; overrides `federate`.
+    pub federate: Option<String>,
+}
+
+/// A search given as a serialized [`Query`](crate::Query) instead of a pattern and filters.
+/// Its `max_results` and `offset` page the results like `limit` and `offset` do.
+#[derive(Debug, Deserialize)]
+pub struct StructuredSearchRequest {
+    pub query: crate::Query,
+
+    /// Result fields to return; `None` returns every field except `content_preview`.
+    #[serde(default)]
+    pub fields: Option<Vec<String>>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Default)]
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum SearchMode {
     #[default]
@@ -31,17 +76,29 @@ pub enum SearchMode {
     Glob,
 }
 
-#[derive(Debug, Deserialize, Default)]
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
 pub struct SearchFilters {
     pub extensions: Option<Vec<String>>,
     pub size_min: Option<u64>,
     pub size_max: Option<u64>,
     pub modified_after: Option<DateTime<Utc>>,
     pub modified_before: Option<DateTime<Utc>>,
+    /// Bounds on the creation time; only one of the modified, created and accessed pairs
+    /// may be set.
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    /// Bounds on the last access time.
+    pub accessed_after: Option<DateTime<Utc>>,
+    pub accessed_before: Option<DateTime<Utc>>,
     pub scope: Option<SearchScope>,
+    /// Match entries carrying any of these user tags.
+    pub tags: Option<Vec<String>>,
+    /// Full-text query the content of every result must match as well, like `content:`
+    /// terms in `query`.
+    pub content_query: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum SearchScope {
     Name,
@@ -52,26 +109,150 @@ pub enum SearchScope {
 
 #[derive(Debug, Serialize)]
 pub struct SearchResponse {
-    pub results: Vec<FileResult>,
+    pub results: Selected<FileResult>,
     pub total: usize,
+    /// `total` only counts the matches among the candidates the search looked at, as there
+    /// were more than it looks at; there may be more.
+    pub total_estimated: bool,
     pub took_ms: u64,
     pub has_more: bool,
+    pub effective_limit: usize,
+    pub limit_clamped: bool,
+    pub effective_query: EffectiveQuery,
+    /// Content matching was involved while some quick-indexed roots are not enriched yet,
+    /// so files under them may be missing from the results.
+    pub content_partial: bool,
+    /// How each peer of a federated search answered.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peers: Option<Vec<PeerStatus>>,
 }
 
-#[derive(Debug, Serialize, Clone)]
+/// The part of a peer's `SearchResponse` a federated search merges.
+#[derive(Debug, Deserialize)]
+pub struct PeerSearchResponse {
+    pub results: Vec<FileResult>,
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerStatus {
+    pub name: String,
+    pub status: PeerState,
+    /// Results the peer sent, before the merged page was taken.
+    pub results: usize,
+    /// Matches the peer reported in all.
+    pub total: usize,
+    pub took_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PeerState {
+    Ok,
+    Timeout,
+    Error,
+}
+
+/// A query in the query syntax (`report ext:pdf size:>1MB`) to check without running it.
+#[derive(Debug, Deserialize)]
+pub struct ValidateQueryRequest {
+    pub query: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ValidateQueryResponse {
+    pub effective_query: EffectiveQuery,
+}
+
+/// `details` of a `400` from `POST /query/validate`.
+#[derive(Debug, Serialize)]
+pub struct QueryDiagnostics {
+    pub diagnostics: Vec<QueryDiagnostic>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct FileResult {
+    /// Index id, used to tag results through `POST /tags` on the server in `source`.
+    #[serde(default)]
+    pub id: Option<i64>,
     pub path: PathBuf,
     pub name: String,
     pub size: u64,
     pub modified: DateTime<Utc>,
     pub file_type: FileType,
     pub score: f32,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub content_preview: Option<String>,
+    /// Where a store alias really is; only looked up when the field is requested.
+    #[serde(default)]
+    pub link_target: Option<PathBuf>,
+    /// Matching lines with their context, for content searches with `context_lines`.
+    #[serde(default)]
+    pub matches: Vec<MatchLocation>,
+    /// The peer a federated result came from, or `local`; unset outside federated searches.
+    #[serde(default)]
+    pub source: Option<String>,
 }
 
-#[derive(Debug, Serialize, Clone)]
+impl SelectFields for FileResult {
+    const FIELDS: &'static [&'static str] = &[
+        "id",
+        "path",
+        "name",
+        "size",
+        "modified",
+        "file_type",
+        "score",
+        "content_preview",
+        "link_target",
+        "matches",
+        "source",
+    ];
+    const DEFAULT_FIELDS: &'static [&'static str] = &[
+        "id",
+        "path",
+        "name",
+        "size",
+        "modified",
+        "file_type",
+        "score",
+        "matches",
+        "source",
+    ];
+
+    fn serialize_field<M: SerializeMap>(&self, field: &str, map: &mut M) -> Result<(), M::Error> {
+        match field {
+            "id" => match self.id {
+                Some(id) => map.serialize_entry(field, &id),
+                None => Ok(()),
+            },
+            "path" => map.serialize_entry(field, &self.path),
+            "name" => map.serialize_entry(field, &self.name),
+            "size" => map.serialize_entry(field, &self.size),
+            "modified" => map.serialize_entry(field, &self.modified),
+            "file_type" => map.serialize_entry(field, &self.file_type),
+            "score" => map.serialize_entry(field, &self.score),
+            "content_preview" => match &self.content_preview {
+                Some(preview) => map.serialize_entry(field, preview),
+                None => Ok(()),
+            },
+            "link_target" => match &self.link_target {
+                Some(target) => map.serialize_entry(field, target),
+                None => Ok(()),
+            },
+            "matches" if !self.matches.is_empty() => map.serialize_entry(field, &self.matches),
+            "source" => match &self.source {
+                Some(source) => map.serialize_entry(field, source),
+                None => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum FileType {
     File,
@@ -79,6 +260,90 @@ pub enum FileType {
     Symlink,
 }
 
+// ============ Listing Models ============
+
+#[derive(Debug, Deserialize)]
+pub struct ListingParams {
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+
+    #[serde(default)]
+    pub offset: usize,
+
+    /// Comma-separated entry fields to return; every field when absent.
+    pub fields: Option<String>,
+}
+
+/// Directory-style page of entries; smart folders and directory browsing share this shape.
+#[derive(Debug, Serialize)]
+pub struct ListingResponse {
+    pub name: String,
+    pub entries: Selected<ListingEntry>,
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub has_more: bool,
+    pub sort: ListingSort,
+}
+
+#[derive(Debug, Clone)]
+pub struct ListingEntry {
+    pub path: PathBuf,
+    pub name: String,
+    pub size: u64,
+    pub modified: Option<DateTime<Utc>>,
+    pub file_type: FileType,
+}
+
+impl SelectFields for ListingEntry {
+    const FIELDS: &'static [&'static str] = &["path", "name", "size", "modified", "file_type"];
+    const DEFAULT_FIELDS: &'static [&'static str] = Self::FIELDS;
+
+    fn serialize_field<M: SerializeMap>(&self, field: &str, map: &mut M) -> Result<(), M::Error> {
+        match field {
+            "path" => map.serialize_entry(field, &self.path),
+            "name" => map.serialize_entry(field, &self.name),
+            "size" => map.serialize_entry(field, &self.size),
+            "modified" => map.serialize_entry(field, &self.modified),
+            "file_type" => map.serialize_entry(field, &self.file_type),
+            _ => Ok(()),
+        }
+    }
+}
+
+// ============ Thumbnail Models ============
+
+#[derive(Debug, Deserialize)]
+pub struct ThumbnailParams {
+    #[serde(default = "default_thumbnail_size")]
+    pub size: u32,
+}
+
+// ============ Tag Models ============
+
+#[derive(Debug, Deserialize)]
+pub struct TagRequest {
+    pub tag: String,
+    pub ids: Vec<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagResponse {
+    pub tag: String,
+    pub tagged: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagsResponse {
+    pub tags: Vec<TagCount>,
+}
+
 // ============ Index Models ============
 
 #[derive(Debug, Deserialize)]
@@ -93,12 +358,21 @@ pub struct IndexRequest {
 
     #[serde(default)]
     pub exclusions: Vec<String>,
+
+    /// Index names and metadata only; content follows through background enrichment.
+    #[serde(default)]
+    pub quick: bool,
 }
 
 #[derive(Debug, Serialize)]
 pub struct IndexResponse {
     pub indexed_count: usize,
     pub skipped_count: usize,
+    /// Files indexed without their content because analysis timed out or they are not
+    /// regular files.
+    pub content_skipped_count: usize,
+    /// Files that changed while their content was read; the next update reads them again.
+    pub possibly_inconsistent_count: usize,
     pub error_count: usize,
     pub took_ms: u64,
     pub status: IndexStatus,
@@ -124,15 +398,37 @@ pub struct IndexProgress {
 
 #[derive(Debug, Deserialize)]
 pub struct UpdateRequest {
-    pub path: PathBuf,
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+
+    /// Several roots, updated one after another.
+    #[serde(default)]
+    pub paths: Vec<PathBuf>,
+
+    /// Every root recorded in the index.
+    #[serde(default)]
+    pub all: bool,
 }
 
+/// Totals over every root, with each root's own counts.
 #[derive(Debug, Serialize)]
 pub struct UpdateResponse {
     pub added: usize,
     pub updated: usize,
     pub removed: usize,
     pub took_ms: u64,
+    pub roots: Vec<RootUpdate>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RootUpdate {
+    pub path: PathBuf,
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
 // ============ Watch Models ============
@@ -141,8 +437,16 @@ pub struct UpdateResponse {
 pub struct WatchRequest {
     pub path: PathBuf,
 
-    #[serde(default)]
+    /// Watch subdirectories too; on unless set to false.
+    #[serde(default = "default_true")]
     pub recursive: bool,
+
+    #[serde(default)]
+    pub audit: bool,
+
+    /// Index files already under `path` in the background; on unless set to false.
+    #[serde(default = "default_true")]
+    pub initial_scan: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -152,6 +456,13 @@ pub struct WatchResponse {
     pub status: String,
 }
 
+#[derive(Debug, Serialize)]
+pub struct WatchStatusResponse {
+    pub watch_id: String,
+    pub path: PathBuf,
+    pub status: Option<WatchStatus>,
+}
+
 #[derive(Debug, Serialize, Clone)]
 pub struct FileChangeEvent {
     pub event_type: FileEventType,
@@ -168,6 +479,94 @@ pub enum FileEventType {
     Renamed,
 }
 
+// ============ Export Models ============
+
+#[derive(Debug, Deserialize)]
+pub struct ExportChangesParams {
+    pub since: Option<DateTime<Utc>>,
+    /// Export entries matching `sensitive_patterns` too, unless the server hides them.
+    #[serde(default)]
+    pub include_sensitive: bool,
+}
+
+// ============ Audit Models ============
+
+#[derive(Debug, Deserialize)]
+pub struct AuditParams {
+    pub path: Option<PathBuf>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+
+    /// Comma-separated event types, e.g. `created,deleted`.
+    pub types: Option<String>,
+
+    #[serde(default)]
+    pub after_id: i64,
+
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditResponse {
+    pub records: Vec<AuditRecord>,
+
+    /// Pass back as `after_id` to fetch the next page; absent on the last page.
+    pub next_cursor: Option<i64>,
+}
+
+// ============ Activity Models ============
+
+#[derive(Debug, Deserialize)]
+pub struct ActivityParams {
+    /// The last event id already seen; absent reads every buffered event.
+    pub since: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ActivityResponse {
+    pub events: Vec<ActivityRecord>,
+
+    /// The id to pass as `since` next time, also when `events` is empty.
+    pub last_id: u64,
+}
+
+// ============ Maintenance Models ============
+
+#[derive(Debug, Serialize)]
+pub struct RebuildFtsResponse {
+    #[serde(flatten)]
+    pub report: FtsRebuildReport,
+    pub took_ms: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BackupRequest {
+    /// File to write; its directory must already exist and be allowed.
+    pub path: PathBuf,
+    #[serde(default)]
+    pub compress: bool,
+}
+
+/// One line of the NDJSON stream `POST /maintenance/backup` answers with.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum BackupEvent {
+    Progress {
+        copied_pages: usize,
+        total_pages: usize,
+    },
+    Done {
+        #[serde(flatten)]
+        report: BackupReport,
+        took_ms: u64,
+    },
+    Error {
+        error: String,
+        message: String,
+    },
+}
+
 // ============ Stats Models ============
 
 #[derive(Debug, Serialize)]
@@ -175,10 +574,61 @@ pub struct StatsResponse {
     pub total_files: usize,
     pub total_directories: usize,
     pub total_size: u64,
+    /// Files indexed as links into a package store, counted in `total_files` but not in
+    /// `total_size`.
+    pub store_aliases: usize,
     pub index_size_mb: f64,
     pub last_update: Option<DateTime<Utc>>,
     pub uptime_seconds: u64,
     pub performance: PerformanceStats,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota: Option<QuotaStats>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub enrichment: Vec<EnrichmentProgress>,
+    /// The process currently writing to the index, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub write_lease: Option<WriteLease>,
+    /// The in-memory name index, when `fast_name_index` is on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name_index: Option<NameIndexUsage>,
+    pub maintenance: Vec<MaintenanceStatus>,
+    /// Federated searches and how each peer answered them, when peers are configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub federation: Option<FederationUsage>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FederationUsage {
+    pub federated_queries: u64,
+    pub peers: Vec<PeerUsage>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PeerUsage {
+    pub name: String,
+    pub requests: u64,
+    pub ok: u64,
+    pub timeouts: u64,
+    pub errors: u64,
+    pub avg_latency_ms: f64,
+    pub max_latency_ms: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QuotaStats {
+    pub limit_bytes: u64,
+    pub used_bytes: u64,
+    pub usage_percent: f64,
+}
+
+impl From<QuotaUsage> for QuotaStats {
+    fn from(usage: QuotaUsage) -> Self {
+        Self {
+            limit_bytes: usage.limit_bytes,
+            used_bytes: usage.used_bytes,
+            usage_percent: usage.usage_percent(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -189,6 +639,17 @@ pub struct PerformanceStats {
     pub memory_usage_mb: f64,
 }
 
+// ============ Capabilities Models ============
+
+#[derive(Debug, Serialize)]
+pub struct CapabilitiesResponse {
+    #[serde(flatten)]
+    pub engine: Capabilities,
+    pub auth_required: bool,
+    /// Results are limited to what the system account of a mapped API key could read.
+    pub permission_filtering: bool,
+}
+
 // ============ Health Models ============
 
 #[derive(Debug, Serialize)]
@@ -197,6 +658,7 @@ pub struct HealthResponse {
     pub version: String,
     pub uptime_seconds: u64,
     pub checks: Vec<HealthCheck>,
+    pub capabilities: Capabilities,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -217,18 +679,26 @@ pub struct HealthCheck {
 
 // ============ Error Models ============
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ErrorResponse {
     pub error: String,
     pub message: String,
     pub code: u16,
 
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub details: Option<serde_json::Value>,
 }
 
 // ============ Utilities ============
 
-fn default_limit() -> usize {
+pub(crate) fn default_limit() -> usize {
     100
 }
+
+fn default_thumbnail_size() -> u32 {
+    256
+}
+
+fn default_true() -> bool {
+    true
+}
@@ -0,0 +1,285 @@
+//! Limits what requests acting as a system account see to what that account could read,
+//! judged from the ownership recorded while indexing rather than the filesystem.
+
+use std::collections::HashMap;
+
+use crate::server::config::{SecuritySettings, SystemUser};
+use crate::{FileEntry, FilePermissions, SearchResult};
+
+/// Whether permissions can be enforced on this platform.
+pub const SUPPORTED: bool = cfg!(unix);
+
+impl SystemUser {
+    fn gids(&self) -> Vec<u32> {
+        let mut gids = self.groups.clone();
+        gids.push(self.gid);
+        gids
+    }
+
+    /// Whether the user can see the entry's name.
+    pub fn can_see(&self, permissions: &FilePermissions) -> bool {
+        permissions.visible_to(self.uid, &self.gids())
+    }
+
+    /// Whether the user can read the entry's content.
+    pub fn can_read(&self, permissions: &FilePermissions) -> bool {
+        permissions.readable_by(self.uid, &self.gids())
+    }
+}
+
+/// What a request may see.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Access<'a> {
+    Unrestricted,
+    Limited(&'a SystemUser),
+    /// Permissions are enforced and the request is not known to act as anyone.
+    Denied,
+}
+
+impl<'a> Access<'a> {
+    /// The account a limited request acts as.
+    pub fn user(&self) -> Option<&'a SystemUser> {
+        match *self {
+            Access::Limited(user) => Some(user),
+            _ => None,
+        }
+    }
+}
+
+/// What a request made with `api_key` may see. With permissions enforced, a mapped key is
+/// limited to its account, the configured API key sees everything and any other request
+/// is denied.
+pub fn access<'a>(security: &'a SecuritySettings, api_key: Option<&str>) -> Access<'a> {
+    if !SUPPORTED || !security.enforce_fs_permissions {
+        return Access::Unrestricted;
+    }
+    let Some(api_key) = api_key else {
+        return Access::Denied;
+    };
+    match security.api_key_users.get(api_key) {
+        Some(user) => Access::Limited(user),
+        None if security.api_key.as_deref() == Some(api_key) => Access::Unrestricted,
+        None => Access::Denied,
+    }
+}
+
+/// Drops the results `user` cannot see and the previews and matching lines of those it
+/// cannot read. Entries without recorded ownership are dropped.
+pub fn filter_results(
+    results: Vec<SearchResult>,
+    permissions: &HashMap<i64, FilePermissions>,
+    user: &SystemUser,
+) -> Vec<SearchResult> {
+    results
+        .into_iter()
+        .filter_map(|mut result| {
+            let permissions = permissions.get(&result.file.id?)?;
+            if !user.can_see(permissions) {
+                return None;
+            }
+            if !user.can_read(permissions) {
+                result.snippet = None;
+                result.matches.clear();
+            }
+            Some(result)
+        })
+        .collect()
+}
+
+/// Drops the entries `user` cannot see. Entries without recorded ownership are dropped.
+pub fn filter_entries(
+    entries: Vec<FileEntry>,
+    permissions: &HashMap<i64, FilePermissions>,
+    user: &SystemUser,
+) -> Vec<FileEntry> {
+    entries
+        .into_iter()
+        .filter(|entry| {
+            entry
+                .id
+                .and_then(|id| permissions.get(&id))
+                .is_some_and(|permissions| user.can_see(permissions))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::config::ServerConfig;
+    use crate::{MatchLocation, Ownership};
+    use std::path::PathBuf;
+
+    const ALICE: u32 = 1000;
+    const BOB: u32 = 1001;
+    const STAFF: u32 = 50;
+
+    fn user(uid: u32, groups: &[u32]) -> SystemUser {
+        SystemUser {
+            uid,
+            gid: uid,
+            groups: groups.to_vec(),
+        }
+    }
+
+    fn owned(uid: u32, gid: u32, mode: u32) -> Ownership {
+        Ownership { uid, gid, mode }
+    }
+
+    fn result(id: i64, name: &str) -> SearchResult {
+        let mut file = FileEntry::new(PathBuf::from("/srv").join(name));
+        file.id = Some(id);
+        SearchResult::new(file, 1.0)
+            .with_snippet("quarterly numbers")
+            .with_matches(vec![MatchLocation {
+                line: 1,
+                column: 0,
+                length: 9,
+                context: "quarterly numbers".to_string(),
+                context_start_line: 1,
+                field: Default::default(),
+            }])
+    }
+
+    /// 1: public file, 2: Alice's private file, 3: a staff-readable file, 4: a world-readable
+    /// file in Alice's private directory, 5: Alice's group-readable file, 6: not recorded.
+    /// 1, 2, 3 and 5 are in a directory anyone can list.
+    fn fixture() -> (Vec<SearchResult>, HashMap<i64, FilePermissions>) {
+        let open_dir = Some(owned(0, 0, 0o755));
+        let permissions = HashMap::from([
+            (
+                1,
+                FilePermissions {
+                    entry: owned(0, 0, 0o644),
+                    parent: open_dir,
+                },
+            ),
+            (
+                2,
+                FilePermissions {
+                    entry: owned(ALICE, ALICE, 0o600),
+                    parent: open_dir,
+                },
+            ),
+            (
+                3,
+                FilePermissions {
+                    entry: owned(0, STAFF, 0o640),
+                    parent: open_dir,
+                },
+            ),
+            (
+                4,
+                FilePermissions {
+                    entry: owned(ALICE, ALICE, 0o644),
+                    parent: Some(owned(ALICE, ALICE, 0o700)),
+                },
+            ),
+            (
+                5,
+                FilePermissions {
+                    entry: owned(ALICE, ALICE, 0o640),
+                    parent: open_dir,
+                },
+            ),
+        ]);
+        let results = (1..=6)
+            .map(|id| result(id, &format!("file-{}", id)))
+            .collect();
+        (results, permissions)
+    }
+
+    fn visible(results: &[SearchResult]) -> Vec<(i64, bool)> {
+        results
+            .iter()
+            .map(|result| (result.file.id.unwrap(), result.snippet.is_some()))
+            .collect()
+    }
+
+    #[test]
+    fn test_results_per_identity() {
+        let (results, permissions) = fixture();
+        for (user, expected) in [
+            (
+                user(0, &[]),
+                vec![(1, true), (2, true), (3, true), (4, true), (5, true)],
+            ),
+            (
+                user(ALICE, &[]),
+                vec![(1, true), (2, true), (3, false), (4, true), (5, true)],
+            ),
+            (
+                user(BOB, &[]),
+                vec![(1, true), (2, false), (3, false), (5, false)],
+            ),
+            (
+                user(BOB, &[STAFF]),
+                vec![(1, true), (2, false), (3, true), (5, false)],
+            ),
+        ] {
+            let filtered = filter_results(results.clone(), &permissions, &user);
+            assert_eq!(visible(&filtered), expected, "{:?}", user);
+        }
+    }
+
+    #[test]
+    fn test_unreadable_results_lose_previews_and_matches() {
+        let (results, permissions) = fixture();
+        let filtered = filter_results(results, &permissions, &user(BOB, &[]));
+        let listed_only = filtered
+            .iter()
+            .find(|result| result.file.id == Some(5))
+            .unwrap();
+        assert_eq!(listed_only.snippet, None);
+        assert!(listed_only.matches.is_empty());
+        assert_eq!(filtered[0].matches.len(), 1);
+    }
+
+    #[test]
+    fn test_owner_bits_win_over_group_and_other() {
+        // The owner is denied even though everyone else may read.
+        let permissions = FilePermissions {
+            entry: owned(ALICE, STAFF, 0o044),
+            parent: None,
+        };
+        assert!(!user(ALICE, &[STAFF]).can_read(&permissions));
+        assert!(user(BOB, &[STAFF]).can_read(&permissions));
+        assert!(user(BOB, &[]).can_read(&permissions));
+    }
+
+    #[test]
+    fn test_entries_per_identity() {
+        let (results, permissions) = fixture();
+        let entries: Vec<FileEntry> = results.into_iter().map(|result| result.file).collect();
+        for (user, expected) in [
+            (user(BOB, &[]), vec![1, 2, 3, 5]),
+            (user(ALICE, &[]), vec![1, 2, 3, 4, 5]),
+        ] {
+            let filtered = filter_entries(entries.clone(), &permissions, &user);
+            let ids: Vec<i64> = filtered.iter().filter_map(|entry| entry.id).collect();
+            assert_eq!(ids, expected, "{:?}", user);
+        }
+    }
+
+    #[test]
+    fn test_access_per_api_key() {
+        let mut security = ServerConfig::default().security;
+        security.api_key = Some("admin-key".to_string());
+        security
+            .api_key_users
+            .insert("bob-key".to_string(), user(BOB, &[]));
+        assert_eq!(access(&security, None), Access::Unrestricted);
+        assert_eq!(access(&security, Some("bob-key")), Access::Unrestricted);
+
+        security.enforce_fs_permissions = true;
+        if !SUPPORTED {
+            assert_eq!(access(&security, Some("bob-key")), Access::Unrestricted);
+            return;
+        }
+        let bob = &security.api_key_users["bob-key"];
+        assert_eq!(access(&security, Some("bob-key")), Access::Limited(bob));
+        assert_eq!(access(&security, Some("admin-key")), Access::Unrestricted);
+        assert_eq!(access(&security, Some("guess")), Access::Denied);
+        assert_eq!(access(&security, None), Access::Denied);
+    }
+}
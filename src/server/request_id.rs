@@ -0,0 +1,358 @@
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{Error, FromRequest, HttpMessage, HttpRequest};
+use std::convert::Infallible;
+use std::future::{ready, Ready};
+use tracing::Instrument;
+
+use crate::RequestId;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Middleware giving every request an id: the client's `X-Request-Id` if it sent a usable
+/// one, or a new UUID.
+///
+/// The handler runs in a `request` span carrying the id, so everything it logs, including
+/// engine work it runs inline, can be told apart from concurrent requests. The id is echoed
+/// in the `X-Request-Id` response header and, for JSON error responses, in
+/// `details.request_id`.
+pub async fn trace_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(RequestId::parse)
+        .unwrap_or_default();
+    req.extensions_mut().insert(request_id.clone());
+
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %req.method(),
+        path = %req.path(),
+    );
+    let res = next.call(req).instrument(span).await?.map_into_boxed_body();
+
+    let mut res = with_request_id_details(res, &request_id).await?;
+    if let Ok(value) = HeaderValue::from_str(request_id.as_str()) {
+        res.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    Ok(res)
+}
+
+/// Adds `request_id` to the `details` of an [`ErrorResponse`](crate::server::ErrorResponse)
+/// body; other responses pass through untouched.
+async fn with_request_id_details(
+    res: ServiceResponse<BoxBody>,
+    request_id: &RequestId,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let is_json = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+    let status = res.status();
+    if !is_json || !(status.is_client_error() || status.is_server_error()) {
+        return Ok(res);
+    }
+
+    let (req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+    let bytes = body::to_bytes(body)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    let body = match serde_json::from_slice::<serde_json::Value>(&bytes) {
+        Ok(mut error) if error.get("error").is_some() => {
+            let details = &mut error["details"];
+            if details.is_null() {
+                *details = serde_json::json!({});
+            }
+            if let Some(details) = details.as_object_mut() {
+                details.insert("request_id".to_string(), request_id.as_str().into());
+            }
+            serde_json::to_vec(&error).map_or(bytes, Into::into)
+        }
+        _ => bytes,
+    };
+
+    Ok(ServiceResponse::new(req, res.set_body(BoxBody::new(body))))
+}
+
+/// The id [`trace_requests`] gave the request, or a new one where the middleware isn't used.
+impl FromRequest for RequestId {
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Infallible>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(req
+            .extensions()
+            .get::<RequestId>()
+            .cloned()
+            .unwrap_or_default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::SLOW_QUERY_TARGET;
+    use crate::server::api;
+    use crate::server::config::ServerConfig;
+    use crate::server::state::AppState;
+    use actix_web::http::StatusCode;
+    use actix_web::{middleware, test, web, App};
+    use parking_lot::Mutex;
+    use std::sync::Arc;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id};
+    use tracing::{Event, Subscriber};
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::registry::LookupSpan;
+    use tracing_subscriber::Layer;
+
+    /// An event as seen by [`Capture`]: its own `request_id`, `pattern` and message, and the
+    /// `request_id` of the spans it was emitted in, innermost first.
+    #[derive(Debug, Clone)]
+    struct Captured {
+        target: String,
+        request_id: Option<String>,
+        pattern: Option<String>,
+        message: Option<String>,
+        span_request_ids: Vec<String>,
+    }
+
+    #[derive(Default)]
+    struct Fields {
+        request_id: Option<String>,
+        pattern: Option<String>,
+        message: Option<String>,
+    }
+
+    impl Fields {
+        fn slot(&mut self, field: &Field) -> Option<&mut Option<String>> {
+            match field.name() {
+                "request_id" => Some(&mut self.request_id),
+                "pattern" => Some(&mut self.pattern),
+                "message" => Some(&mut self.message),
+                _ => None,
+            }
+        }
+    }
+
+    impl Visit for Fields {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            if let Some(slot) = self.slot(field) {
+                *slot = Some(value.to_string());
+            }
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            if let Some(slot) = self.slot(field) {
+                *slot = Some(format!("{:?}", value));
+            }
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<Captured>>>);
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Capture {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            let mut fields = Fields::default();
+            attrs.record(&mut fields);
+            if let (Some(span), Some(request_id)) = (ctx.span(id), fields.request_id) {
+                span.extensions_mut().insert(request_id);
+            }
+        }
+
+        fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+            let mut fields = Fields::default();
+            event.record(&mut fields);
+            let span_request_ids = ctx
+                .event_scope(event)
+                .into_iter()
+                .flatten()
+                .filter_map(|span| span.extensions().get::<String>().cloned())
+                .collect();
+            self.0.lock().push(Captured {
+                target: event.metadata().target().to_string(),
+                request_id: fields.request_id,
+                pattern: fields.pattern,
+                message: fields.message,
+                span_request_ids,
+            });
+        }
+    }
+
+    impl Capture {
+        fn slow_queries(&self) -> Vec<Captured> {
+            self.0
+                .lock()
+                .iter()
+                .filter(|event| event.target == SLOW_QUERY_TARGET)
+                .cloned()
+                .collect()
+        }
+    }
+
+    fn state(temp_dir: &tempfile::TempDir) -> web::Data<AppState> {
+        let data_dir = temp_dir.path().join("data");
+        std::fs::create_dir(&data_dir).unwrap();
+        std::fs::write(data_dir.join("notes.txt"), "").unwrap();
+        let engine = crate::SearchEngine::builder()
+            .index_path(temp_dir.path().join("index.db"))
+            .slow_query_ms(0)
+            .build()
+            .unwrap();
+        engine.index_directory(&data_dir, None).unwrap();
+        web::Data::new(AppState::new(engine, ServerConfig::default()))
+    }
+
+    fn search(query: &str, mode: &str) -> test::TestRequest {
+        test::TestRequest::post()
+            .uri("/search")
+            .set_json(serde_json::json!({ "query": query, "mode": mode }))
+    }
+
+    fn echoed(res: &ServiceResponse) -> &str {
+        res.headers()
+            .get(REQUEST_ID_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn test_request_id_round_trips_and_reaches_the_slow_query_log() {
+        let capture = Capture::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(capture.clone()));
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(state(&temp_dir))
+                .wrap(middleware::from_fn(trace_requests))
+                .route("/search", web::post().to(api::search)),
+        )
+        .await;
+
+        let res = test::call_service(
+            &app,
+            search("notes", "exact")
+                .insert_header(("X-Request-Id", "client-id-1"))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(echoed(&res), "client-id-1");
+        let slow = capture.slow_queries();
+        assert_eq!(slow.len(), 1);
+        assert_eq!(slow[0].request_id.as_deref(), Some("client-id-1"));
+
+        // A missing or unusable id is replaced with a generated one.
+        let res = test::call_service(
+            &app,
+            search("notes", "exact")
+                .insert_header(("X-Request-Id", "not usable"))
+                .to_request(),
+        )
+        .await;
+        assert!(uuid::Uuid::parse_str(echoed(&res)).is_ok());
+
+        let res = test::call_service(
+            &app,
+            search("notes[", "regex")
+                .insert_header(("X-Request-Id", "client-id-2"))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(echoed(&res), "client-id-2");
+        let body: serde_json::Value = test::read_body_json(res).await;
+        assert_eq!(body["error"], "invalid_query.regex");
+        assert_eq!(body["details"]["request_id"], "client-id-2");
+    }
+
+    #[actix_web::test]
+    async fn test_sensitive_names_are_masked_in_request_and_slow_query_logs() {
+        let capture = Capture::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(capture.clone()));
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(state(&temp_dir))
+                .wrap(middleware::from_fn(trace_requests))
+                .route("/search", web::post().to(api::search)),
+        )
+        .await;
+
+        for query in ["id_rsa", "notes"] {
+            let res = test::call_service(&app, search(query, "exact").to_request()).await;
+            assert_eq!(res.status(), StatusCode::OK);
+        }
+
+        let patterns: Vec<_> =
+            capture.slow_queries().into_iter().filter_map(|event| event.pattern).collect();
+        assert_eq!(patterns, vec![crate::filters::REDACTED, "notes"]);
+        let requests: Vec<_> = capture
+            .0
+            .lock()
+            .iter()
+            .filter_map(|event| event.message.clone())
+            .filter(|message| message.starts_with("Search request"))
+            .collect();
+        assert_eq!(
+            requests,
+            vec![
+                format!("Search request: {:?}", crate::filters::REDACTED),
+                "Search request: \"notes\"".to_string()
+            ]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_concurrent_requests_keep_their_own_ids() {
+        let capture = Capture::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(capture.clone()));
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(state(&temp_dir))
+                .wrap(middleware::from_fn(trace_requests))
+                .route("/search", web::post().to(api::search)),
+        )
+        .await;
+
+        let (first, second) = futures::join!(
+            test::call_service(&app, search("notes", "exact").to_request()),
+            test::call_service(&app, search("notes", "fuzzy").to_request()),
+        );
+        let ids: Vec<String> = [first, second]
+            .iter()
+            .map(|res| echoed(res).to_string())
+            .collect();
+        assert_ne!(ids[0], ids[1]);
+
+        let mut logged: Vec<String> = capture
+            .slow_queries()
+            .into_iter()
+            .map(|event| {
+                let id = event.request_id.unwrap();
+                // The engine's `search` span and the middleware's `request` span agree.
+                assert_eq!(event.span_request_ids, vec![id.clone(), id.clone()]);
+                id
+            })
+            .collect();
+        logged.sort();
+        let mut ids = ids;
+        ids.sort();
+        assert_eq!(logged, ids);
+    }
+}
@@ -0,0 +1,249 @@
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+use crate::server::config::SecuritySettings;
+
+/// Pseudo filesystems that are never worth indexing and misbehave when walked.
+#[cfg(unix)]
+const SPECIAL_ROOTS: &[&str] = &["/proc", "/sys", "/dev"];
+#[cfg(not(unix))]
+const SPECIAL_ROOTS: &[&str] = &[];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathRejection {
+    NotFound(PathBuf),
+    ParentTraversal(PathBuf),
+    SpecialFilesystem(PathBuf),
+    OutsideAllowedRoots(PathBuf),
+}
+
+impl PathRejection {
+    pub fn code(&self) -> &'static str {
+        match self {
+            PathRejection::NotFound(_) => "invalid_path",
+            PathRejection::ParentTraversal(_) => "path_traversal",
+            PathRejection::SpecialFilesystem(_) => "special_filesystem",
+            PathRejection::OutsideAllowedRoots(_) => "path_not_allowed",
+        }
+    }
+}
+
+impl fmt::Display for PathRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathRejection::NotFound(path) => write!(f, "Path does not exist: {}", path.display()),
+            PathRejection::ParentTraversal(path) => write!(
+                f,
+                "Path must not contain '..' components: {}",
+                path.display()
+            ),
+            PathRejection::SpecialFilesystem(path) => write!(
+                f,
+                "{} is on a special filesystem and is not allowlisted",
+                path.display()
+            ),
+            PathRejection::OutsideAllowedRoots(path) => write!(
+                f,
+                "{} is outside the server's allowed roots",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// Resolves a client-supplied path and checks it against `allowed_roots`.
+///
+/// The path is canonicalized first, so symlinks and relative paths are judged by where they
+/// actually lead. An empty allowlist admits everything except special filesystems, which are
+/// only reachable when an allowed root lies inside them.
+pub fn validate_request_path(
+    security: &SecuritySettings,
+    path: &Path,
+) -> Result<PathBuf, PathRejection> {
+    if path.components().any(|c| matches!(c, Component::ParentDir)) {
+        return Err(PathRejection::ParentTraversal(path.to_path_buf()));
+    }
+
+    let resolved =
+        dunce::canonicalize(path).map_err(|_| PathRejection::NotFound(path.to_path_buf()))?;
+
+    let allowed_roots: Vec<PathBuf> = security
+        .allowed_roots
+        .iter()
+        .map(|root| dunce::canonicalize(root).unwrap_or_else(|_| root.clone()))
+        .collect();
+
+    let special = SPECIAL_ROOTS
+        .iter()
+        .map(Path::new)
+        .find(|special| resolved.starts_with(special));
+    if let Some(special) = special {
+        if !allowed_roots.iter().any(|root| root.starts_with(special)) {
+            return Err(PathRejection::SpecialFilesystem(resolved));
+        }
+    }
+
+    if !allowed_roots.is_empty() && !allowed_roots.iter().any(|root| resolved.starts_with(root)) {
+        return Err(PathRejection::OutsideAllowedRoots(resolved));
+    }
+
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::config::ServerConfig;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn security(allowed_roots: Vec<PathBuf>) -> SecuritySettings {
+        let mut security = ServerConfig::default().security;
+        security.allowed_roots = allowed_roots;
+        security
+    }
+
+    fn layout() -> (TempDir, PathBuf, PathBuf) {
+        let temp_dir = TempDir::new().unwrap();
+        let allowed = temp_dir.path().join("allowed");
+        let outside = temp_dir.path().join("outside");
+        fs::create_dir_all(allowed.join("docs")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        (temp_dir, allowed, outside)
+    }
+
+    #[test]
+    fn test_accepts_paths_under_allowed_root() {
+        let (_temp_dir, allowed, _) = layout();
+        let security = security(vec![allowed.clone()]);
+
+        let resolved = validate_request_path(&security, &allowed.join("docs")).unwrap();
+        assert_eq!(resolved, dunce::canonicalize(allowed.join("docs")).unwrap());
+        assert!(validate_request_path(&security, &allowed).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_paths_outside_allowed_roots() {
+        let (_temp_dir, allowed, outside) = layout();
+        let security = security(vec![allowed]);
+
+        assert!(matches!(
+            validate_request_path(&security, &outside),
+            Err(PathRejection::OutsideAllowedRoots(_))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_sibling_with_shared_prefix() {
+        let (temp_dir, allowed, _) = layout();
+        let sibling = temp_dir.path().join("allowed-not");
+        fs::create_dir(&sibling).unwrap();
+
+        assert!(matches!(
+            validate_request_path(&security(vec![allowed]), &sibling),
+            Err(PathRejection::OutsideAllowedRoots(_))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_parent_components() {
+        let (_temp_dir, allowed, _) = layout();
+        let escaping = allowed.join("docs").join("..").join("..").join("outside");
+
+        for security in [security(vec![allowed.clone()]), security(Vec::new())] {
+            assert!(matches!(
+                validate_request_path(&security, &escaping),
+                Err(PathRejection::ParentTraversal(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_rejects_missing_paths() {
+        let (_temp_dir, allowed, _) = layout();
+
+        assert!(matches!(
+            validate_request_path(&security(Vec::new()), &allowed.join("missing")),
+            Err(PathRejection::NotFound(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_rejects_symlink_escaping_allowed_root() {
+        let (_temp_dir, allowed, outside) = layout();
+        let link = allowed.join("escape");
+        std::os::unix::fs::symlink(&outside, &link).unwrap();
+
+        assert_eq!(
+            validate_request_path(&security(vec![allowed]), &link),
+            Err(PathRejection::OutsideAllowedRoots(
+                dunce::canonicalize(&outside).unwrap()
+            ))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_accepts_symlinked_allowed_root() {
+        let (temp_dir, allowed, _) = layout();
+        let link = temp_dir.path().join("link-to-allowed");
+        std::os::unix::fs::symlink(&allowed, &link).unwrap();
+
+        assert!(validate_request_path(&security(vec![link]), &allowed.join("docs")).is_ok());
+    }
+
+    #[test]
+    fn test_relative_paths_resolve_against_working_directory() {
+        let cwd = std::env::current_dir().unwrap();
+        let relative = Path::new("src");
+
+        assert!(validate_request_path(&security(vec![cwd.clone()]), relative).is_ok());
+        assert!(matches!(
+            validate_request_path(&security(vec![cwd.join("tests")]), relative),
+            Err(PathRejection::OutsideAllowedRoots(_))
+        ));
+    }
+
+    #[test]
+    fn test_empty_allowlist_allows_any_existing_path() {
+        let (_temp_dir, _, outside) = layout();
+
+        assert!(validate_request_path(&security(Vec::new()), &outside).is_ok());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_special_filesystems_need_explicit_allowlisting() {
+        for path in ["/proc", "/proc/self", "/sys", "/dev"] {
+            assert!(
+                matches!(
+                    validate_request_path(&security(Vec::new()), Path::new(path)),
+                    Err(PathRejection::SpecialFilesystem(_))
+                ),
+                "{}",
+                path
+            );
+        }
+        assert!(matches!(
+            validate_request_path(&security(vec![PathBuf::from("/")]), Path::new("/sys")),
+            Err(PathRejection::SpecialFilesystem(_))
+        ));
+
+        let allowlisted = security(vec![PathBuf::from("/sys")]);
+        assert!(validate_request_path(&allowlisted, Path::new("/sys")).is_ok());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_unc_paths_outside_allowed_roots() {
+        let (_temp_dir, allowed, _) = layout();
+        let security = security(vec![allowed.clone()]);
+
+        // Unreachable shares fail to resolve instead of being indexed over the network.
+        assert!(validate_request_path(&security, Path::new(r"\\server\share\docs")).is_err());
+
+        let verbatim = PathBuf::from(format!(r"\\?\{}", allowed.join("docs").display()));
+        assert!(validate_request_path(&security, &verbatim).is_ok());
+    }
+}
@@ -1,35 +1,54 @@
+use crate::filters::SensitiveFilter;
 use crate::SearchEngine;
+use crate::server::activity::ActivityFeed;
 use crate::server::config::ServerConfig;
+use crate::server::federation::FederationStats;
 use crate::server::models::FileChangeEvent;
 use dashmap::DashMap;
 use parking_lot::RwLock;
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 use std::time::Instant;
 use chrono::{DateTime, Utc};
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, Semaphore};
 
 pub struct AppState {
     pub engine: Arc<RwLock<SearchEngine>>,
     pub config: Arc<ServerConfig>,
-    pub metrics: Arc<Metrics>,
+    /// The engine's sensitive paths, for masking them in request logs without its lock.
+    pub sensitive: Arc<SensitiveFilter>,
     pub watchers: Arc<DashMap<String, WatchHandle>>,
     pub event_tx: broadcast::Sender<FileChangeEvent>,
     pub start_time: Instant,
+    pub thumbnail_slots: Arc<Semaphore>,
+    /// Set while `POST /maintenance/backup` runs, so backups do not overlap.
+    pub backup_running: Arc<AtomicBool>,
+    pub federation: Arc<FederationStats>,
+    pub activity: Arc<ActivityFeed>,
 }
 
 impl AppState {
     pub fn new(engine: SearchEngine, config: ServerConfig) -> Self {
         let (event_tx, _) = broadcast::channel(1000);
+        let thumbnail_slots = Arc::new(Semaphore::new(
+            config.performance.thumbnail_concurrency.max(1),
+        ));
+
+        let sensitive = Arc::new(engine.sensitive().clone());
+        let activity = Arc::new(ActivityFeed::new(config.performance.activity_buffer));
 
         Self {
             engine: Arc::new(RwLock::new(engine)),
             config: Arc::new(config),
-            metrics: Arc::new(Metrics::new()),
+            sensitive,
             watchers: Arc::new(DashMap::new()),
             event_tx,
             start_time: Instant::now(),
+            thumbnail_slots,
+            backup_running: Arc::new(AtomicBool::new(false)),
+            federation: Arc::new(FederationStats::default()),
+            activity,
         }
     }
 
@@ -38,50 +57,6 @@ impl AppState {
     }
 }
 
-pub struct Metrics {
-    pub total_searches: AtomicU64,
-    pub total_search_time_ms: AtomicU64,
-    pub cache_hits: AtomicU64,
-    pub cache_misses: AtomicU64,
-}
-
-impl Metrics {
-    pub fn new() -> Self {
-        Self {
-            total_searches: AtomicU64::new(0),
-            total_search_time_ms: AtomicU64::new(0),
-            cache_hits: AtomicU64::new(0),
-            cache_misses: AtomicU64::new(0),
-        }
-    }
-
-    pub fn record_search(&self, duration_ms: u64) {
-        self.total_searches.fetch_add(1, Ordering::Relaxed);
-        self.total_search_time_ms
-            .fetch_add(duration_ms, Ordering::Relaxed);
-    }
-
-    pub fn avg_search_time_ms(&self) -> f64 {
-        let total = self.total_searches.load(Ordering::Relaxed);
-        if total == 0 {
-            return 0.0;
-        }
-        self.total_search_time_ms.load(Ordering::Relaxed) as f64 / total as f64
-    }
-
-    pub fn cache_hit_rate(&self) -> f32 {
-        let hits = self.cache_hits.load(Ordering::Relaxed);
-        let misses = self.cache_misses.load(Ordering::Relaxed);
-        let total = hits + misses;
-
-        if total == 0 {
-            return 0.0;
-        }
-
-        hits as f32 / total as f32
-    }
-}
-
 pub struct WatchHandle {
     pub path: PathBuf,
     pub recursive: bool,
@@ -1,43 +1,92 @@
 use actix_web::{web, Error, HttpRequest, HttpResponse};
-use actix_ws::Message;
+use actix_ws::{Message, Session};
 use futures::StreamExt;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
-use tracing::info;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{info, info_span, warn, Instrument};
 
-use crate::server::models::FileEventType;
+use crate::server::activity::ActivityRecord;
+use crate::server::api::authorize;
+use crate::server::models::{ErrorResponse, FileEventType};
 use crate::server::state::AppState;
+use crate::RequestId;
 
+/// The connection logs under the upgrade request's id; each client message gets its own,
+/// taken from its `request_id` field if it has a usable one.
 pub async fn websocket_handler(
     req: HttpRequest,
     stream: web::Payload,
     state: web::Data<AppState>,
+    request_id: RequestId,
 ) -> Result<HttpResponse, Error> {
     info!("WebSocket connection request");
 
+    // The activity feed is for administrators; the key is checked on the upgrade request.
+    let denied = authorize(&req, &state).is_some();
     let (res, mut session, mut stream) = actix_ws::handle(&req, stream)?;
 
     // Subscribe to file change events
     let mut event_rx = state.event_tx.subscribe();
+    let mut activity: Option<broadcast::Receiver<ActivityRecord>> = None;
+    let mut last_sent = 0;
 
     // Spawn task to forward events to WebSocket
-    actix_web::rt::spawn(async move {
+    let forward = async move {
         let close_reason = loop {
             tokio::select! {
                 // Handle incoming WebSocket messages
                 Some(Ok(msg)) = stream.next() => {
                     match msg {
                         Message::Text(text) => {
-                            // Handle client messages (e.g., filter events)
-                            if let Ok(filter) = serde_json::from_str::<EventFilter>(&text.to_string()) {
-                                info!("Received filter: {:?}", filter);
-                                // Apply filter logic (for future enhancement)
+                            if let Ok(command) = serde_json::from_str::<Command>(text.as_ref()) {
+                                let Command::SubscribeActivity { since, request_id } = command;
+                                let message_id = request_id
+                                    .as_deref()
+                                    .and_then(RequestId::parse)
+                                    .unwrap_or_default();
+                                if denied {
+                                    let error = ErrorResponse {
+                                        error: "unauthorized".to_string(),
+                                        message: "A valid API key is required".to_string(),
+                                        code: 401,
+                                        details: None,
+                                    };
+                                    if send(&mut session, &error).await.is_err() {
+                                        break None;
+                                    }
+                                    continue;
+                                }
+                                info_span!("message", request_id = %message_id).in_scope(|| {
+                                    info!("Subscribed to activity since {:?}", since);
+                                });
+                                let (backlog, rx) = state.activity.subscribe(since);
+                                activity = Some(rx);
+                                // Ids from before a restart are read from the start.
+                                last_sent = since
+                                    .filter(|&since| since <= state.activity.last_id())
+                                    .unwrap_or(0);
+                                let sent =
+                                    send_records(&mut session, backlog, &mut last_sent).await;
+                                if sent.is_err() {
+                                    break None;
+                                }
+                            } else if let Ok(filter) =
+                                serde_json::from_str::<EventFilter>(text.as_ref())
+                            {
+                                let message_id = filter
+                                    .request_id
+                                    .as_deref()
+                                    .and_then(RequestId::parse)
+                                    .unwrap_or_default();
+                                info_span!("message", request_id = %message_id).in_scope(|| {
+                                    info!("Received filter: {:?}", filter);
+                                    // Apply filter logic (for future enhancement)
+                                });
                             }
                         }
-                        Message::Ping(bytes) => {
-                            if session.pong(&bytes).await.is_err() {
-                                break None;
-                            }
+                        Message::Ping(bytes) if session.pong(&bytes).await.is_err() => {
+                            break None;
                         }
                         Message::Close(reason) => {
                             break reason;
@@ -53,20 +102,82 @@ pub async fn websocket_handler(
                         }
                     }
                 }
+                received = next_activity(&mut activity) => {
+                    let records = match received {
+                        Ok(record) => vec![record],
+                        // Fallen behind the channel: catch up from the buffer, which reports
+                        // what it no longer holds as a gap.
+                        Err(RecvError::Lagged(skipped)) => {
+                            warn!("Activity subscriber lagged by {} events", skipped);
+                            state.activity.since(Some(last_sent))
+                        }
+                        Err(RecvError::Closed) => {
+                            activity = None;
+                            continue;
+                        }
+                    };
+                    if send_records(&mut session, records, &mut last_sent).await.is_err() {
+                        break None;
+                    }
+                }
                 else => break None
             }
         };
 
         let _ = session.close(close_reason).await;
         info!("WebSocket connection closed");
-    });
+    };
+    actix_web::rt::spawn(forward.instrument(info_span!("websocket", request_id = %request_id)));
 
     Ok(res)
 }
 
+async fn next_activity(
+    rx: &mut Option<broadcast::Receiver<ActivityRecord>>,
+) -> Result<ActivityRecord, RecvError> {
+    match rx {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Sends the records not sent yet, as catching up after a lag can repeat some.
+async fn send_records(
+    session: &mut Session,
+    records: Vec<ActivityRecord>,
+    last_sent: &mut u64,
+) -> Result<(), actix_ws::Closed> {
+    for record in records {
+        if record.id > *last_sent {
+            *last_sent = record.id;
+            send(session, &record).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn send<T: Serialize>(session: &mut Session, message: &T) -> Result<(), actix_ws::Closed> {
+    match serde_json::to_string(message) {
+        Ok(json) => session.text(json).await,
+        Err(_) => Ok(()),
+    }
+}
+
+/// A message asking the server for something, told apart from filters by its `type`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Command {
+    /// Sends the activity events after `since`, then each new one as it happens.
+    SubscribeActivity {
+        since: Option<u64>,
+        request_id: Option<String>,
+    },
+}
+
 #[derive(Debug, Deserialize)]
 #[allow(dead_code)]
 struct EventFilter {
+    request_id: Option<String>,
     paths: Option<Vec<PathBuf>>,
     event_types: Option<Vec<FileEventType>>,
 }
@@ -0,0 +1,168 @@
+use crate::core::error::{IoResultExt, Result, SearchError};
+use crate::storage::migrations::MigrationManager;
+use crate::storage::schema::CURRENT_SCHEMA_VERSION;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rusqlite::{Connection, OpenFlags};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Gzips the backup at `path` into `<path>.gz` and removes the uncompressed copy.
+pub fn compress(path: &Path) -> Result<PathBuf> {
+    let mut compressed = path.as_os_str().to_owned();
+    compressed.push(".gz");
+    let compressed = PathBuf::from(compressed);
+
+    let written = (|| -> io::Result<()> {
+        let mut input = BufReader::new(File::open(path)?);
+        let output = BufWriter::new(File::create(&compressed)?);
+        let mut encoder = GzEncoder::new(output, Compression::default());
+        io::copy(&mut input, &mut encoder)?;
+        encoder.finish()?.flush()
+    })();
+    if let Err(e) = written {
+        let _ = fs::remove_file(&compressed);
+        return Err(SearchError::io_at(&compressed, e));
+    }
+
+    fs::remove_file(path).at_path(path)?;
+    Ok(compressed)
+}
+
+/// A backup ready to be restored: the database itself, unpacked to a temporary file beside
+/// the index when the backup was gzipped. The temporary file is removed on drop.
+pub struct RestoreSource {
+    path: PathBuf,
+    unpacked: bool,
+    schema_version: i32,
+}
+
+impl RestoreSource {
+    /// Opens the backup at `source`, unpacking it next to `index_path` if it is gzipped, and
+    /// checks it is an intact index whose schema this build can migrate.
+    pub fn open(source: &Path, index_path: &Path) -> Result<Self> {
+        let mut magic = [0u8; 2];
+        let gzipped = File::open(source)
+            .and_then(|mut file| file.read_exact(&mut magic))
+            .is_ok_and(|()| magic == GZIP_MAGIC);
+
+        let mut restore = Self {
+            path: source.to_path_buf(),
+            unpacked: false,
+            schema_version: 0,
+        };
+        if gzipped {
+            let mut unpacked = index_path.as_os_str().to_owned();
+            unpacked.push(".restore");
+            restore.path = PathBuf::from(unpacked);
+            restore.unpacked = true;
+            File::create(&restore.path)
+                .and_then(|output| {
+                    let mut input = GzDecoder::new(BufReader::new(File::open(source)?));
+                    io::copy(&mut input, &mut BufWriter::new(output))
+                })
+                .at_path(source)?;
+        }
+
+        restore.schema_version = inspect(&restore.path)
+            .map_err(|e| SearchError::IndexCorrupted(format!("{}: {}", source.display(), e)))?;
+        Ok(restore)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn schema_version(&self) -> i32 {
+        self.schema_version
+    }
+}
+
+impl Drop for RestoreSource {
+    fn drop(&mut self) {
+        if self.unpacked {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// The schema version of the database at `path`, once it passes SQLite's quick check.
+fn inspect(path: &Path) -> std::result::Result<i32, String> {
+    // Not read-only: FTS5 checks its index through statements that need a writable handle.
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE)
+        .map_err(|e| e.to_string())?;
+    let check: String = conn
+        .query_row("PRAGMA quick_check", [], |row| row.get(0))
+        .map_err(|e| format!("not a readable database ({})", e))?;
+    if check != "ok" {
+        return Err(format!("integrity check failed: {}", check));
+    }
+
+    let version = MigrationManager::get_current_version(&conn)
+        .map_err(|_| "not a rusty-files index".to_string())?;
+    match version {
+        0 => Err("not a rusty-files index".to_string()),
+        v if v > CURRENT_SCHEMA_VERSION => Err(format!(
+            "schema version {} is newer than supported version {}",
+            v, CURRENT_SCHEMA_VERSION
+        )),
+        v => Ok(v),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Database;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_compressed_backups_unpack_and_foreign_files_are_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("index.db");
+        let db = Database::new(&index_path, 2).unwrap();
+        let backup = db
+            .backup_to(&temp_dir.path().join("backup.db"), |_, _| {})
+            .unwrap();
+        drop(db);
+
+        let compressed = compress(&backup.path).unwrap();
+        assert!(!backup.path.exists());
+        let source = RestoreSource::open(&compressed, &index_path).unwrap();
+        assert_eq!(source.schema_version(), CURRENT_SCHEMA_VERSION);
+        let unpacked = source.path().to_path_buf();
+        assert_ne!(unpacked, compressed);
+        drop(source);
+        assert!(!unpacked.exists());
+
+        let text = temp_dir.path().join("notes.txt");
+        fs::write(&text, "not a database").unwrap();
+        assert!(matches!(
+            RestoreSource::open(&text, &index_path).err().unwrap(),
+            SearchError::IndexCorrupted(_)
+        ));
+
+        let other = temp_dir.path().join("other.db");
+        Connection::open(&other)
+            .unwrap()
+            .execute_batch("CREATE TABLE t (x)")
+            .unwrap();
+        assert!(RestoreSource::open(&other, &index_path).is_err());
+
+        let newer = temp_dir.path().join("newer.db");
+        fs::copy(&index_path, &newer).unwrap();
+        Connection::open(&newer)
+            .unwrap()
+            .execute(
+                "INSERT INTO schema_version (version, applied_at) VALUES (?1, datetime('now'))",
+                [CURRENT_SCHEMA_VERSION + 1],
+            )
+            .unwrap();
+        let err = RestoreSource::open(&newer, &index_path).err().unwrap();
+        assert!(err.to_string().contains("newer than supported"));
+    }
+}
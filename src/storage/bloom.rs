@@ -1,10 +1,13 @@
+use crate::core::metrics::EngineCounters;
 use parking_lot::RwLock;
 use probabilistic_collections::bloom::BloomFilter;
+use std::sync::Arc;
 
 pub struct FileBloomFilter {
     filter: RwLock<BloomFilter<String>>,
     capacity: usize,
     error_rate: f64,
+    metrics: Arc<EngineCounters>,
 }
 
 impl FileBloomFilter {
@@ -14,9 +17,16 @@ impl FileBloomFilter {
             filter: RwLock::new(filter),
             capacity,
             error_rate,
+            metrics: Arc::new(EngineCounters::new()),
         }
     }
 
+    /// Records hits into `metrics` instead of a private set of counters.
+    pub fn with_metrics(mut self, metrics: Arc<EngineCounters>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
     pub fn insert<S: AsRef<str>>(&self, item: S) {
         let mut filter = self.filter.write();
         filter.insert(&item.as_ref().to_string());
@@ -24,7 +34,11 @@ impl FileBloomFilter {
 
     pub fn contains<S: AsRef<str>>(&self, item: S) -> bool {
         let filter = self.filter.read();
-        filter.contains(&item.as_ref().to_string())
+        let hit = filter.contains(&item.as_ref().to_string());
+        if hit {
+            self.metrics.record_bloom_filter_hit();
+        }
+        hit
     }
 
     pub fn clear(&self) {
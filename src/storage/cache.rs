@@ -1,11 +1,15 @@
+use crate::core::metrics::EngineCounters;
 use crate::core::types::FileEntry;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use std::collections::{HashMap, VecDeque};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 pub struct LruCache {
     capacity: usize,
     cache: RwLock<LruCacheInner>,
+    metrics: Arc<EngineCounters>,
 }
 
 struct LruCacheInner {
@@ -21,9 +25,16 @@ impl LruCache {
                 map: HashMap::with_capacity(capacity),
                 order: VecDeque::with_capacity(capacity),
             }),
+            metrics: Arc::new(EngineCounters::new()),
         }
     }
 
+    /// Records lookups into `metrics` instead of a private set of counters.
+    pub fn with_metrics(mut self, metrics: Arc<EngineCounters>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
     pub fn get(&self, path: &PathBuf) -> Option<FileEntry> {
         let mut cache = self.cache.write();
 
@@ -32,8 +43,10 @@ impl LruCache {
                 cache.order.remove(pos);
             }
             cache.order.push_back(path.clone());
+            self.metrics.record_cache_lookup(true);
             Some(entry)
         } else {
+            self.metrics.record_cache_lookup(false);
             None
         }
     }
@@ -84,6 +97,130 @@ impl LruCache {
     }
 }
 
+/// Paths recently looked up and not found, so that repeated misses skip the database.
+///
+/// Writes in this process invalidate the paths they add once committed, and a lookup only
+/// records its miss if nothing was invalidated since it began, so a miss racing an insert is
+/// dropped rather than cached. Rows written by other processes stay hidden until the miss
+/// expires after `ttl`.
+pub struct MissCache {
+    capacity: usize,
+    ttl: Duration,
+    inner: Mutex<MissCacheInner>,
+    metrics: Arc<EngineCounters>,
+}
+
+#[derive(Default)]
+struct MissCacheInner {
+    misses: HashMap<PathBuf, Instant>,
+    /// Bumped by every invalidation.
+    generation: u64,
+}
+
+impl MissCache {
+    /// A cache of at most `capacity` misses; 0 disables it.
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            inner: Mutex::new(MissCacheInner::default()),
+            metrics: Arc::new(EngineCounters::new()),
+        }
+    }
+
+    /// Records lookups into `metrics` instead of a private set of counters.
+    pub fn with_metrics(mut self, metrics: Arc<EngineCounters>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0 && !self.ttl.is_zero()
+    }
+
+    /// Whether `path` missed recently enough that looking it up again would miss too.
+    pub fn contains(&self, path: &Path) -> bool {
+        if !self.is_enabled() {
+            return false;
+        }
+
+        let mut inner = self.inner.lock();
+        let fresh = match inner.misses.get(path) {
+            Some(missed_at) if missed_at.elapsed() < self.ttl => true,
+            Some(_) => {
+                inner.misses.remove(path);
+                false
+            }
+            None => false,
+        };
+        self.metrics.record_miss_cache_lookup(fresh);
+        fresh
+    }
+
+    /// Taken before a lookup and handed to [`insert`](Self::insert) with its miss.
+    pub fn generation(&self) -> u64 {
+        self.inner.lock().generation
+    }
+
+    /// Records that `path` was not found by a lookup that began at `generation`. Dropped if
+    /// anything was invalidated since, as the lookup may have missed a row written meanwhile.
+    pub fn insert(&self, path: &Path, generation: u64) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut inner = self.inner.lock();
+        if inner.generation != generation {
+            return;
+        }
+        if inner.misses.len() >= self.capacity {
+            let ttl = self.ttl;
+            inner.misses.retain(|_, missed_at| missed_at.elapsed() < ttl);
+        }
+        if inner.misses.len() >= self.capacity {
+            let oldest = inner
+                .misses
+                .iter()
+                .min_by_key(|(_, missed_at)| **missed_at)
+                .map(|(path, _)| path.clone());
+            if let Some(oldest) = oldest {
+                inner.misses.remove(&oldest);
+            }
+        }
+        inner.misses.insert(path.to_path_buf(), Instant::now());
+    }
+
+    /// Forgets the miss recorded for `path`, once a row for it has been written.
+    pub fn invalidate(&self, path: &Path) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut inner = self.inner.lock();
+        inner.generation += 1;
+        inner.misses.remove(path);
+    }
+
+    /// Forgets every recorded miss.
+    pub fn clear(&self) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut inner = self.inner.lock();
+        inner.generation += 1;
+        inner.misses.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().misses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -143,4 +280,52 @@ mod tests {
         assert!(!cache.contains(&path2));
         assert!(cache.contains(&path3));
     }
+
+    #[test]
+    fn test_lru_cache_records_hits_and_misses() {
+        let metrics = Arc::new(EngineCounters::new());
+        let cache = LruCache::new(2).with_metrics(Arc::clone(&metrics));
+        let path = PathBuf::from("/test/file1.txt");
+
+        assert!(cache.get(&path).is_none());
+        cache.insert(path.clone(), FileEntry::new(path.clone()));
+        assert!(cache.get(&path).is_some());
+        assert!(cache.get(&path).is_some());
+
+        let snapshot = metrics.snapshot();
+        assert_eq!((snapshot.cache_hits, snapshot.cache_misses), (2, 1));
+    }
+
+    #[test]
+    fn test_miss_cache_expires_and_drops_misses_racing_a_write() {
+        let metrics = Arc::new(EngineCounters::new());
+        let cache = MissCache::new(2, Duration::from_millis(50)).with_metrics(Arc::clone(&metrics));
+        let path = Path::new("/test/missing.txt");
+
+        assert!(!cache.contains(path));
+        cache.insert(path, cache.generation());
+        assert!(cache.contains(path));
+        std::thread::sleep(Duration::from_millis(80));
+        assert!(!cache.contains(path));
+        assert!(cache.is_empty());
+
+        // A write landing between the lookup and its miss being recorded wins.
+        let generation = cache.generation();
+        cache.invalidate(Path::new("/test/other.txt"));
+        cache.insert(path, generation);
+        assert!(!cache.contains(path));
+
+        for name in ["a", "b", "c"] {
+            cache.insert(&Path::new("/test").join(name), cache.generation());
+        }
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.contains(Path::new("/test/a")));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!((snapshot.miss_cache_hits, snapshot.miss_cache_misses), (1, 4));
+
+        let disabled = MissCache::new(0, Duration::from_secs(5));
+        disabled.insert(path, disabled.generation());
+        assert!(!disabled.contains(path));
+    }
 }
@@ -0,0 +1,80 @@
+/// Default `fts_chunk_bytes`: the most text one full-text row holds.
+pub const DEFAULT_FTS_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Smallest `fts_chunk_bytes` accepted; smaller chunks mostly add rows.
+pub const MIN_FTS_CHUNK_BYTES: usize = 1024;
+
+/// A piece of a content blob's text, indexed as a full-text row of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextChunk<'a> {
+    pub index: usize,
+    /// Where the chunk starts in the blob's text, in bytes.
+    pub byte_offset: usize,
+    pub text: &'a str,
+}
+
+/// Splits `text` into chunks of at most `max_bytes`, each ending at a line break where there
+/// is one. A line longer than `max_bytes` is cut at a character boundary. Empty text gives
+/// one empty chunk, so every blob has a row.
+pub fn split_chunks(text: &str, max_bytes: usize) -> Vec<TextChunk<'_>> {
+    let max_bytes = max_bytes.max(1);
+    let mut chunks = Vec::new();
+    let mut start = 0;
+
+    loop {
+        let end = if text.len() - start <= max_bytes {
+            text.len()
+        } else {
+            let mut limit = start + max_bytes;
+            while !text.is_char_boundary(limit) {
+                limit -= 1;
+            }
+            match text[start..limit].rfind('\n') {
+                Some(newline) => start + newline + 1,
+                // A character wider than `max_bytes` still makes progress.
+                None if limit == start => {
+                    start + text[start..].chars().next().map_or(1, char::len_utf8)
+                }
+                None => limit,
+            }
+        };
+
+        chunks.push(TextChunk {
+            index: chunks.len(),
+            byte_offset: start,
+            text: &text[start..end],
+        });
+        if end >= text.len() {
+            return chunks;
+        }
+        start = end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_chunks_on_line_boundaries() {
+        let text = "alpha\nbeta\ngamma\ndelta\n";
+        let chunks = split_chunks(text, 12);
+        let texts: Vec<&str> = chunks.iter().map(|chunk| chunk.text).collect();
+        assert_eq!(texts, vec!["alpha\nbeta\n", "gamma\ndelta\n"]);
+        assert_eq!(chunks[1].index, 1);
+        assert_eq!(chunks[1].byte_offset, 11);
+
+        let long = split_chunks("ééééé", 4);
+        let texts: Vec<&str> = long.iter().map(|chunk| chunk.text).collect();
+        assert_eq!(texts, vec!["éé", "éé", "é"]);
+
+        assert_eq!(split_chunks("", 16).len(), 1);
+        assert_eq!(split_chunks(text, 1024).len(), 1);
+        for chunk in split_chunks(text, 5) {
+            assert_eq!(
+                &text[chunk.byte_offset..chunk.byte_offset + chunk.text.len()],
+                chunk.text
+            );
+        }
+    }
+}
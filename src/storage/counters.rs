@@ -0,0 +1,132 @@
+use crate::core::error::Result;
+use crate::core::types::StatsCounters;
+use crate::storage::settings::{keys, Settings};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::ops::{AddAssign, Sub};
+
+const KEYS: [&str; 4] = [
+    keys::STATS_FILES,
+    keys::STATS_DIRECTORIES,
+    keys::STATS_TOTAL_SIZE,
+    keys::STATS_CONTENT_INDEXED,
+];
+
+impl StatsCounters {
+    /// What one `files` row adds to the totals. A store alias's size is its target's, which
+    /// is counted where the store is indexed.
+    pub(crate) fn of_entry(is_directory: bool, size: i64, is_alias: bool) -> Self {
+        Self {
+            files: (!is_directory) as i64,
+            directories: is_directory as i64,
+            total_size: if is_directory || is_alias { 0 } else { size },
+            content_indexed: 0,
+        }
+    }
+
+    /// What the row stored for a file id adds to the totals, if there is one.
+    pub(crate) fn of_row(conn: &Connection, file_id: i64) -> Result<Option<Self>> {
+        let row = conn
+            .prepare_cached(
+                "SELECT is_directory, size, id IN (SELECT file_id FROM link_targets)
+                 FROM files WHERE id = ?1",
+            )?
+            .query_row(params![file_id], |row| {
+                Ok(Self::of_entry(row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .optional()?;
+        Ok(row)
+    }
+
+    pub(crate) fn content(count: i64) -> Self {
+        Self {
+            content_indexed: count,
+            ..Self::default()
+        }
+    }
+
+    /// The stored counters, or `None` for an index that has not recorded them yet.
+    pub(crate) fn load(conn: &Connection) -> Result<Option<Self>> {
+        let settings = Settings::new(conn);
+        let mut values = [0; 4];
+        for (value, key) in values.iter_mut().zip(KEYS) {
+            match settings.get_i64(key)? {
+                Some(stored) => *value = stored,
+                None => return Ok(None),
+            }
+        }
+        let [files, directories, total_size, content_indexed] = values;
+        Ok(Some(Self {
+            files,
+            directories,
+            total_size,
+            content_indexed,
+        }))
+    }
+
+    /// Counts the totals with full table scans.
+    pub(crate) fn count(conn: &Connection) -> Result<Self> {
+        let (files, directories, total_size) = conn.query_row(
+            "SELECT COALESCE(SUM(is_directory = 0), 0), COALESCE(SUM(is_directory = 1), 0),
+                    COALESCE(SUM(CASE WHEN is_directory = 0
+                                       AND id NOT IN (SELECT file_id FROM link_targets)
+                                      THEN size END), 0)
+             FROM files",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        let content_indexed =
+            conn.query_row("SELECT COUNT(*) FROM file_contents", [], |row| row.get(0))?;
+        Ok(Self {
+            files,
+            directories,
+            total_size,
+            content_indexed,
+        })
+    }
+
+    pub(crate) fn store(&self, conn: &Connection) -> Result<()> {
+        let settings = Settings::new(conn);
+        for (key, value) in KEYS.into_iter().zip(self.values()) {
+            settings.set_i64(key, value)?;
+        }
+        Ok(())
+    }
+
+    /// Adds these counters, read as a change, to the stored ones. Call it in the transaction
+    /// that made the change, so the totals never disagree with the rows.
+    pub(crate) fn apply(&self, conn: &Connection) -> Result<()> {
+        let settings = Settings::new(conn);
+        for (key, delta) in KEYS.into_iter().zip(self.values()) {
+            if delta != 0 {
+                settings.add_i64(key, delta)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn values(&self) -> [i64; 4] {
+        [self.files, self.directories, self.total_size, self.content_indexed]
+    }
+}
+
+impl AddAssign for StatsCounters {
+    fn add_assign(&mut self, other: Self) {
+        self.files += other.files;
+        self.directories += other.directories;
+        self.total_size += other.total_size;
+        self.content_indexed += other.content_indexed;
+    }
+}
+
+impl Sub for StatsCounters {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self {
+            files: self.files - other.files,
+            directories: self.directories - other.directories,
+            total_size: self.total_size - other.total_size,
+            content_indexed: self.content_indexed - other.content_indexed,
+        }
+    }
+}
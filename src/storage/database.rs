@@ -1,436 +1,3281 @@
 use crate::core::error::{Result, SearchError};
-use crate::core::types::{ContentPreview, ExclusionRule, ExclusionRuleType, FileEntry, IndexStats};
+use crate::core::types::{
+    AuditEventType, AuditRecord, BackupReport, ChunkHit, ContentPreview, DimensionBound,
+    DimensionFilter, DocumentField,
+    EnrichmentProgress, EntryKind, ExclusionRule, ExclusionRuleType, FieldFilter, FileEntry,
+    FilePermissions, FtsRebuildReport, ImageDimensions, IndexStats, IndexedRoot, ListingSort,
+    MaintenanceTask, Ownership, SavedSearch,
+    ScriptInfo, StatsCounters, StatsDrift, TimestampAdjustment, WriteLease,
+};
+use crate::storage::chunks::{split_chunks, DEFAULT_FTS_CHUNK_BYTES};
+use crate::storage::cache::MissCache;
 use crate::storage::migrations::MigrationManager;
-use chrono::{TimeZone, Utc};
+use crate::storage::schema::{self, PathLayout};
+use crate::storage::settings::{keys, Setting, Settings};
+use crate::utils::normalizer::{fold_case, TextNormalizer, UnicodeNormalizer};
+use crate::utils::path::normalize_separators;
+use chrono::{DateTime, TimeZone, Utc};
+use dashmap::DashMap;
+use parking_lot::RwLock;
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::{params, OptionalExtension};
-use std::path::{Path, PathBuf};
+use rusqlite::backup::{Backup, StepResult};
+use rusqlite::types::Value;
+use rusqlite::{
+    params, params_from_iter, Connection, OpenFlags, OptionalExtension, TransactionBehavior,
+};
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf, MAIN_SEPARATOR, MAIN_SEPARATOR_STR};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 pub type DbPool = Pool<SqliteConnectionManager>;
 
+const STATEMENT_CACHE_CAPACITY: usize = 64;
+
+const UPSERT_FILE_SQL: &str = r#"
+    INSERT INTO files (
+        path, name, extension, size, created_at, modified_at, accessed_at,
+        is_directory, is_hidden, is_symlink, parent_path, mime_type, file_hash,
+        indexed_at, last_verified, name_normalized, parent_id
+    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)
+    ON CONFLICT(path) DO UPDATE SET
+        name = excluded.name,
+        name_normalized = excluded.name_normalized,
+        extension = excluded.extension,
+        size = excluded.size,
+        modified_at = excluded.modified_at,
+        accessed_at = excluded.accessed_at,
+        is_directory = excluded.is_directory,
+        is_hidden = excluded.is_hidden,
+        is_symlink = excluded.is_symlink,
+        mime_type = excluded.mime_type,
+        file_hash = excluded.file_hash,
+        last_verified = excluded.last_verified
+"#;
+
+// Compact rows are keyed by their parent directory and name instead of the path.
+const UPSERT_COMPACT_FILE_SQL: &str = r#"
+    INSERT INTO files (
+        path, name, extension, size, created_at, modified_at, accessed_at,
+        is_directory, is_hidden, is_symlink, parent_path, mime_type, file_hash,
+        indexed_at, last_verified, name_normalized, parent_id
+    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)
+    ON CONFLICT(parent_id, name) DO UPDATE SET
+        name_normalized = excluded.name_normalized,
+        extension = excluded.extension,
+        size = excluded.size,
+        modified_at = excluded.modified_at,
+        accessed_at = excluded.accessed_at,
+        is_directory = excluded.is_directory,
+        is_hidden = excluded.is_hidden,
+        is_symlink = excluded.is_symlink,
+        mime_type = excluded.mime_type,
+        file_hash = excluded.file_hash,
+        last_verified = excluded.last_verified
+"#;
+
+// Rebuilds the path of every path_dirs row, joining components with ?1. Select from
+// `files f LEFT JOIN dir_paths dp ON dp.id = f.parent_id` and use FULL_PATH_SQL for the
+// path of any row, compact or not.
+const DIR_PATHS_CTE: &str = r#"
+    WITH RECURSIVE dir_paths(id, path) AS (
+        SELECT id, name FROM path_dirs WHERE parent_id = 0
+        UNION ALL
+        SELECT d.id, CASE WHEN dp.path = '' THEN d.name
+                          ELSE RTRIM(dp.path, '/\') || ?1 || d.name END
+        FROM path_dirs d JOIN dir_paths dp ON d.parent_id = dp.id
+    )
+"#;
+
+const FULL_PATH_SQL: &str = r#"
+    COALESCE(CASE WHEN dp.path = '' THEN f.name
+                  ELSE RTRIM(dp.path, '/\') || ?1 || f.name END, f.path)
+"#;
+
+const COMPACT_BATCH_SIZE: usize = 10_000;
+
+// Ids bound per statement by `find_by_ids`, well under SQLite's variable limit.
+const FIND_BY_IDS_BATCH: usize = 500;
+
+// An online backup copies this many pages, then pauses so writers can take their turn.
+const BACKUP_PAGES_PER_STEP: i32 = 256;
+const BACKUP_STEP_PAUSE: Duration = Duration::from_millis(10);
+
+// Built beside files_fts by `rebuild_fts` and renamed over it once complete.
+const FTS_REBUILD_TABLE: &str = "files_fts_rebuild";
+
+/// Paths per query in `find_by_paths`. A compact lookup binds three parameters per path, which
+/// keeps each query under SQLite's historical limit of 999.
+const PATH_LOOKUP_CHUNK: usize = 300;
+
+fn escape_like(pattern: &str) -> String {
+    let mut escaped = String::with_capacity(pattern.len());
+    for c in pattern.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// FTS5 reports bad `MATCH` syntax ("unterminated string", "fts5: syntax error ...") as a
+/// generic SQLite error. The statement itself prepared fine, so such an error while stepping
+/// it comes from the caller's query, not from storage.
+fn fts_error(err: rusqlite::Error) -> SearchError {
+    match &err {
+        rusqlite::Error::SqliteFailure(failure, Some(message))
+            if failure.code == rusqlite::ErrorCode::Unknown =>
+        {
+            SearchError::InvalidContentQuery(message.clone())
+        }
+        _ => SearchError::Database(err),
+    }
+}
+
+/// Splits a directory into its root (`/`, `C:\`, or empty when relative) and the names
+/// below it. Returns `None` when joining those names would not reproduce `dir` exactly.
+fn split_dir(dir: &Path) -> Option<(String, Vec<&str>)> {
+    let mut root = PathBuf::new();
+    let mut names = Vec::new();
+    for component in dir.components() {
+        match component {
+            Component::Normal(name) => names.push(name.to_str()?),
+            _ if names.is_empty() => root.push(component.as_os_str()),
+            _ => return None,
+        }
+    }
+
+    let root = root.to_str()?.to_string();
+    let mut rebuilt = PathBuf::from(&root);
+    rebuilt.extend(&names);
+    (rebuilt.as_os_str() == dir.as_os_str()).then_some((root, names))
+}
+
+/// The parent directory a compact index stores `path` under, if its path can be rebuilt from
+/// that directory and `name`.
+fn compact_parent<'a>(path: &'a Path, name: &str) -> Option<&'a Path> {
+    let parent = path.parent()?;
+    if name.is_empty() || path.file_name()?.to_str()? != name {
+        return None;
+    }
+    split_dir(parent)?;
+    (parent.join(name).as_os_str() == path.as_os_str()).then_some(parent)
+}
+
+/// Directory paths of a compact index by `path_dirs` id, and back. Rows are only added once
+/// committed, and directory rows are never rewritten, so entries stay valid until cleared.
+#[derive(Default)]
+struct DirCache {
+    paths: DashMap<i64, PathBuf>,
+    ids: DashMap<PathBuf, i64>,
+}
+
+impl DirCache {
+    fn insert(&self, id: i64, path: PathBuf) {
+        self.ids.insert(path.clone(), id);
+        self.paths.insert(id, path);
+    }
+
+    fn clear(&self) {
+        self.paths.clear();
+        self.ids.clear();
+    }
+}
+
+/// Connection counts for the read and write pools.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolSizes {
+    pub read: u32,
+    pub write: u32,
+}
+
+impl PoolSizes {
+    pub fn new(read: u32, write: u32) -> Self {
+        Self {
+            read: read.max(1),
+            write: write.max(1),
+        }
+    }
+}
+
+/// Splits a single pool size from before reads and writes had their own pools: one writer,
+/// the rest readers.
+impl From<u32> for PoolSizes {
+    fn from(total: u32) -> Self {
+        Self::new(total.saturating_sub(1), 1)
+    }
+}
+
+/// Reads and writes go through separate pools so searches never queue for a connection
+/// behind a long write transaction. Under WAL, readers also keep seeing the last committed
+/// state while a write is in progress.
 pub struct Database {
-    pool: DbPool,
+    reader: DbPool,
+    writer: DbPool,
+    normalizer: Arc<dyn TextNormalizer>,
+    fts_tokenizer: String,
+    fts_chunk_bytes: usize,
+    layout: RwLock<PathLayout>,
+    dirs: DirCache,
+    misses: MissCache,
+    // A shared in-memory database is freed once its last connection closes, so in-memory
+    // instances hold one open outside the pool, which may recycle its own.
+    _memory_anchor: Option<Mutex<rusqlite::Connection>>,
 }
 
 impl Database {
-    pub fn new<P: AsRef<Path>>(path: P, pool_size: u32) -> Result<Self> {
-        let manager = SqliteConnectionManager::file(path.as_ref());
-        let pool = Pool::builder()
-            .max_size(pool_size)
+    pub fn new<P: AsRef<Path>>(path: P, pool_sizes: impl Into<PoolSizes>) -> Result<Self> {
+        Self::with_layout(path, pool_sizes, PathLayout::Legacy)
+    }
+
+    /// Opens the index at `path`, creating it with `layout` if it does not exist yet.
+    pub fn with_layout<P: AsRef<Path>>(
+        path: P,
+        pool_sizes: impl Into<PoolSizes>,
+        layout: PathLayout,
+    ) -> Result<Self> {
+        let sizes = pool_sizes.into();
+        let manager = SqliteConnectionManager::file(path.as_ref()).with_init(Self::init_connection);
+        let writer = Pool::builder()
+            .max_size(sizes.write)
             .build(manager)?;
 
-        {
-            let conn = pool.get()?;
-            MigrationManager::initialize_schema(&conn)?;
-        }
+        let layout = {
+            let conn = writer.get()?;
+            MigrationManager::initialize_schema_with_layout(&conn, layout)?;
+            MigrationManager::path_layout(&conn)?
+        };
 
-        Ok(Self { pool })
+        // Opened only once the schema exists, since read-only connections cannot create it.
+        let manager = SqliteConnectionManager::file(path.as_ref())
+            .with_flags(Self::read_only_flags())
+            .with_init(Self::init_read_connection);
+        let reader = Pool::builder()
+            .max_size(sizes.read)
+            .build(manager)?;
+
+        Ok(Self {
+            reader,
+            writer,
+            normalizer: Arc::new(UnicodeNormalizer),
+            fts_tokenizer: schema::DEFAULT_FTS_TOKENIZER.to_string(),
+            fts_chunk_bytes: DEFAULT_FTS_CHUNK_BYTES,
+            layout: RwLock::new(layout),
+            dirs: DirCache::default(),
+            misses: MissCache::new(0, Duration::ZERO),
+            _memory_anchor: None,
+        })
+    }
+
+    /// Opens a private in-memory database shared by every connection in the pool.
+    ///
+    /// A plain `:memory:` connection would give each pooled connection its own empty
+    /// database, so this uses a uniquely named shared-cache URI instead.
+    pub fn in_memory(pool_sizes: impl Into<PoolSizes>) -> Result<Self> {
+        Self::in_memory_with_layout(pool_sizes, PathLayout::Legacy)
     }
 
-    pub fn in_memory(pool_size: u32) -> Result<Self> {
-        let manager = SqliteConnectionManager::memory();
-        let pool = Pool::builder()
-            .max_size(pool_size)
+    pub fn in_memory_with_layout(
+        pool_sizes: impl Into<PoolSizes>,
+        layout: PathLayout,
+    ) -> Result<Self> {
+        let sizes = pool_sizes.into();
+        static NEXT_MEMORY_DB: AtomicUsize = AtomicUsize::new(0);
+        let uri = format!(
+            "file:rusty-files-{}-{}?mode=memory&cache=shared",
+            std::process::id(),
+            NEXT_MEMORY_DB.fetch_add(1, Ordering::Relaxed)
+        );
+        let flags = OpenFlags::default() | OpenFlags::SQLITE_OPEN_URI;
+
+        let anchor = rusqlite::Connection::open_with_flags(&uri, flags)?;
+        MigrationManager::initialize_schema_with_layout(&anchor, layout)?;
+
+        let manager = SqliteConnectionManager::file(&uri)
+            .with_flags(flags)
+            .with_init(Self::init_memory_connection);
+        let writer = Pool::builder()
+            .max_size(sizes.write)
             .build(manager)?;
 
-        {
-            let conn = pool.get()?;
-            MigrationManager::initialize_schema(&conn)?;
-        }
+        let manager = SqliteConnectionManager::file(&uri)
+            .with_flags(flags)
+            .with_init(|conn| {
+                Self::init_memory_connection(conn)?;
+                conn.pragma_update(None, "query_only", true)
+            });
+        let reader = Pool::builder()
+            .max_size(sizes.read)
+            .build(manager)?;
 
-        Ok(Self { pool })
+        Ok(Self {
+            reader,
+            writer,
+            normalizer: Arc::new(UnicodeNormalizer),
+            fts_tokenizer: schema::DEFAULT_FTS_TOKENIZER.to_string(),
+            fts_chunk_bytes: DEFAULT_FTS_CHUNK_BYTES,
+            layout: RwLock::new(layout),
+            dirs: DirCache::default(),
+            misses: MissCache::new(0, Duration::ZERO),
+            _memory_anchor: Some(Mutex::new(anchor)),
+        })
     }
 
-    pub fn insert_file(&self, file: &FileEntry) -> Result<i64> {
-        let conn = self.pool.get()?;
+    // Shared-cache connections lock whole tables instead of waiting on busy_timeout;
+    // reading uncommitted rows keeps searches from failing while a batch is being written.
+    fn init_memory_connection(conn: &mut rusqlite::Connection) -> rusqlite::Result<()> {
+        Self::init_connection(conn)?;
+        conn.pragma_update(None, "read_uncommitted", true)
+    }
 
-        let created_at = file.created_at.map(|dt| dt.timestamp());
-        let modified_at = file.modified_at.map(|dt| dt.timestamp());
-        let accessed_at = file.accessed_at.map(|dt| dt.timestamp());
-        let indexed_at = file.indexed_at.timestamp();
-        let last_verified = file.last_verified.timestamp();
+    fn init_connection(conn: &mut rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+        MigrationManager::apply_connection_pragmas(conn)
+    }
 
-        conn.execute(
-            r#"
-            INSERT INTO files (
-                path, name, extension, size, created_at, modified_at, accessed_at,
-                is_directory, is_hidden, is_symlink, parent_path, mime_type, file_hash,
-                indexed_at, last_verified
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
-            ON CONFLICT(path) DO UPDATE SET
-                name = excluded.name,
-                extension = excluded.extension,
-                size = excluded.size,
-                modified_at = excluded.modified_at,
-                accessed_at = excluded.accessed_at,
-                is_directory = excluded.is_directory,
-                is_hidden = excluded.is_hidden,
-                is_symlink = excluded.is_symlink,
-                mime_type = excluded.mime_type,
-                file_hash = excluded.file_hash,
-                last_verified = excluded.last_verified
-            "#,
-            params![
-                file.path.to_string_lossy().to_string(),
-                file.name,
-                file.extension,
-                file.size as i64,
-                created_at,
-                modified_at,
-                accessed_at,
-                file.is_directory as i32,
-                file.is_hidden as i32,
-                file.is_symlink as i32,
-                file.parent_path.as_ref().map(|p| p.to_string_lossy().to_string()),
-                file.mime_type,
-                file.file_hash,
-                indexed_at,
-                last_verified,
-            ],
-        )?;
+    fn read_only_flags() -> OpenFlags {
+        OpenFlags::SQLITE_OPEN_READ_ONLY
+            | OpenFlags::SQLITE_OPEN_URI
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX
+    }
 
-        Ok(conn.last_insert_rowid())
+    fn init_read_connection(conn: &mut rusqlite::Connection) -> rusqlite::Result<()> {
+        Self::init_connection(conn)?;
+        conn.pragma_update(None, "query_only", true)
+    }
+
+    pub fn with_normalizer(mut self, normalizer: Arc<dyn TextNormalizer>) -> Self {
+        self.normalizer = normalizer;
+        self
+    }
+
+    pub fn normalizer(&self) -> Arc<dyn TextNormalizer> {
+        Arc::clone(&self.normalizer)
+    }
+
+    /// Tokenizer that `rebuild_fts` creates the full-text table with.
+    pub fn with_fts_tokenizer(mut self, tokenizer: impl Into<String>) -> Self {
+        self.fts_tokenizer = tokenizer.into();
+        self
+    }
+
+    /// Most bytes of text in one full-text row; longer content is split into chunks.
+    pub fn with_fts_chunk_bytes(mut self, bytes: usize) -> Self {
+        self.fts_chunk_bytes = bytes;
+        self
+    }
+
+    /// Answers repeated `find_by_path` misses from `misses` instead of the index.
+    pub fn with_miss_cache(mut self, misses: MissCache) -> Self {
+        self.misses = misses;
+        self
+    }
+
+    pub fn path_layout(&self) -> PathLayout {
+        *self.layout.read()
+    }
+
+    /// Returns how many pooled connections, read and write together, are checked out and
+    /// how many are idle.
+    pub fn pool_usage(&self) -> (usize, usize) {
+        [self.reader.state(), self.writer.state()]
+            .iter()
+            .fold((0, 0), |(in_use, idle), state| {
+                let pool_idle = state.idle_connections as usize;
+                (in_use + state.connections as usize - pool_idle, idle + pool_idle)
+            })
+    }
+
+    pub fn renormalize_names(&self, batch_size: usize) -> Result<usize> {
+        let conn = self.writer.get()?;
+        let tx = conn.unchecked_transaction()?;
+
+        tx.execute("UPDATE files SET name_normalized = NULL", [])?;
+        let updated =
+            MigrationManager::backfill_normalized_names(&tx, self.normalizer.as_ref(), batch_size)?;
+
+        tx.commit()?;
+        Ok(updated)
+    }
+
+    pub fn insert_file(&self, file: &FileEntry) -> Result<i64> {
+        let conn = self.writer.get()?;
+        let parent_ids = self.ensure_parent_dirs(&conn, std::slice::from_ref(file))?;
+        let tx = conn.unchecked_transaction()?;
+
+        let (id, change) = self.execute_upsert(&tx, file, parent_ids[0])?;
+        change.apply(&tx)?;
+        tx.commit()?;
+        self.misses.invalidate(&file.path);
+
+        Ok(id)
     }
 
     pub fn insert_files_batch(&self, files: &[FileEntry]) -> Result<()> {
-        let mut conn = self.pool.get()?;
+        let mut conn = self.writer.get()?;
+        let parent_ids = self.ensure_parent_dirs(&conn, files)?;
         let tx = conn.transaction()?;
 
-        for file in files {
-            let created_at = file.created_at.map(|dt| dt.timestamp());
-            let modified_at = file.modified_at.map(|dt| dt.timestamp());
-            let accessed_at = file.accessed_at.map(|dt| dt.timestamp());
-            let indexed_at = file.indexed_at.timestamp();
-            let last_verified = file.last_verified.timestamp();
-
-            tx.execute(
-                r#"
-                INSERT INTO files (
-                    path, name, extension, size, created_at, modified_at, accessed_at,
-                    is_directory, is_hidden, is_symlink, parent_path, mime_type, file_hash,
-                    indexed_at, last_verified
-                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
-                ON CONFLICT(path) DO UPDATE SET
-                    name = excluded.name,
-                    extension = excluded.extension,
-                    size = excluded.size,
-                    modified_at = excluded.modified_at,
-                    accessed_at = excluded.accessed_at,
-                    is_directory = excluded.is_directory,
-                    is_hidden = excluded.is_hidden,
-                    is_symlink = excluded.is_symlink,
-                    mime_type = excluded.mime_type,
-                    file_hash = excluded.file_hash,
-                    last_verified = excluded.last_verified
-                "#,
-                params![
-                    file.path.to_string_lossy().to_string(),
-                    file.name,
-                    file.extension,
-                    file.size as i64,
-                    created_at,
-                    modified_at,
-                    accessed_at,
-                    file.is_directory as i32,
-                    file.is_hidden as i32,
-                    file.is_symlink as i32,
-                    file.parent_path.as_ref().map(|p| p.to_string_lossy().to_string()),
-                    file.mime_type,
-                    file.file_hash,
-                    indexed_at,
-                    last_verified,
-                ],
-            )?;
+        let mut change = StatsCounters::default();
+        for (file, parent_id) in files.iter().zip(parent_ids) {
+            change += self.execute_upsert(&tx, file, parent_id)?.1;
         }
+        change.apply(&tx)?;
 
         tx.commit()?;
+        self.misses.clear();
         Ok(())
     }
 
+    /// Inserts or updates the row for `file`, returning its id and how the statistics
+    /// counters change: an update replaces what the old row contributed.
+    fn execute_upsert(
+        &self,
+        conn: &Connection,
+        file: &FileEntry,
+        parent_id: Option<i64>,
+    ) -> Result<(i64, StatsCounters)> {
+        let existing: Option<i64> = match parent_id {
+            Some(parent_id) => conn
+                .prepare_cached("SELECT id FROM files WHERE parent_id = ?1 AND name = ?2")?
+                .query_row(params![parent_id, file.name], |row| row.get(0))
+                .optional()?,
+            None => conn
+                .prepare_cached("SELECT id FROM files WHERE path = ?1")?
+                .query_row(params![file.path.to_string_lossy()], |row| row.get(0))
+                .optional()?,
+        };
+        let before = match existing {
+            Some(id) => StatsCounters::of_row(conn, id)?.unwrap_or_default(),
+            None => StatsCounters::default(),
+        };
+
+        let (sql, path, parent_path) = match parent_id {
+            Some(_) => (UPSERT_COMPACT_FILE_SQL, None, None),
+            None => (
+                UPSERT_FILE_SQL,
+                Some(file.path.to_string_lossy().to_string()),
+                file.parent_path.as_ref().map(|p| p.to_string_lossy().to_string()),
+            ),
+        };
+
+        conn.prepare_cached(sql)?.execute(params![
+            path,
+            file.name,
+            file.extension.as_deref().map(str::to_ascii_lowercase),
+            file.size as i64,
+            file.created_at.map(|dt| dt.timestamp()),
+            file.modified_at.map(|dt| dt.timestamp()),
+            file.accessed_at.map(|dt| dt.timestamp()),
+            file.is_directory as i32,
+            file.is_hidden as i32,
+            file.is_symlink as i32,
+            parent_path,
+            file.mime_type,
+            file.file_hash,
+            file.indexed_at.timestamp(),
+            file.last_verified.timestamp(),
+            self.normalizer.normalize(&file.name),
+            parent_id,
+        ])?;
+
+        let id = existing.unwrap_or_else(|| conn.last_insert_rowid());
+        let after = StatsCounters::of_row(conn, id)?.unwrap_or_default();
+        Ok((id, after - before))
+    }
+
+    /// Looks up or creates the `path_dirs` rows compact indexes store `files` under. Directory
+    /// rows are committed before the entries themselves, so the cache never holds ids that a
+    /// failed batch rolled back.
+    fn ensure_parent_dirs(
+        &self,
+        conn: &Connection,
+        files: &[FileEntry],
+    ) -> Result<Vec<Option<i64>>> {
+        if self.path_layout() == PathLayout::Legacy {
+            return Ok(vec![None; files.len()]);
+        }
+
+        let tx = conn.unchecked_transaction()?;
+        let mut created = HashMap::new();
+        let parent_ids = files
+            .iter()
+            .map(|file| match compact_parent(&file.path, &file.name) {
+                Some(parent) => self.ensure_dir(&tx, parent, &mut created).map(Some),
+                None => Ok(None),
+            })
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        tx.commit()?;
+
+        for (path, id) in created {
+            self.dirs.insert(id, path);
+        }
+        Ok(parent_ids)
+    }
+
+    fn ensure_dir(
+        &self,
+        conn: &Connection,
+        dir: &Path,
+        created: &mut HashMap<PathBuf, i64>,
+    ) -> rusqlite::Result<i64> {
+        if let Some(id) = self.cached_dir_id(dir, created) {
+            return Ok(id);
+        }
+
+        let (root, names) = split_dir(dir).expect("compact_parent only returns splittable dirs");
+        let mut path = PathBuf::from(&root);
+        let mut id = self.ensure_dir_row(conn, 0, &root, &path, created)?;
+        for name in names {
+            path.push(name);
+            id = self.ensure_dir_row(conn, id, name, &path, created)?;
+        }
+        Ok(id)
+    }
+
+    fn ensure_dir_row(
+        &self,
+        conn: &Connection,
+        parent_id: i64,
+        name: &str,
+        path: &Path,
+        created: &mut HashMap<PathBuf, i64>,
+    ) -> rusqlite::Result<i64> {
+        if let Some(id) = self.cached_dir_id(path, created) {
+            return Ok(id);
+        }
+
+        conn.prepare_cached("INSERT OR IGNORE INTO path_dirs (parent_id, name) VALUES (?1, ?2)")?
+            .execute(params![parent_id, name])?;
+        let id = conn
+            .prepare_cached("SELECT id FROM path_dirs WHERE parent_id = ?1 AND name = ?2")?
+            .query_row(params![parent_id, name], |row| row.get(0))?;
+
+        created.insert(path.to_path_buf(), id);
+        Ok(id)
+    }
+
+    fn cached_dir_id(&self, path: &Path, created: &HashMap<PathBuf, i64>) -> Option<i64> {
+        self.dirs
+            .ids
+            .get(path)
+            .map(|id| *id)
+            .or_else(|| created.get(path).copied())
+    }
+
+    /// The `path_dirs` id of an existing directory, without creating any rows.
+    fn find_dir(&self, conn: &Connection, dir: &Path) -> rusqlite::Result<Option<i64>> {
+        if let Some(id) = self.dirs.ids.get(dir) {
+            return Ok(Some(*id));
+        }
+        let Some((root, names)) = split_dir(dir) else {
+            return Ok(None);
+        };
+
+        let mut stmt =
+            conn.prepare_cached("SELECT id FROM path_dirs WHERE parent_id = ?1 AND name = ?2")?;
+        let mut id = 0;
+        for name in std::iter::once(root.as_str()).chain(names) {
+            match stmt.query_row(params![id, name], |row| row.get(0)).optional()? {
+                Some(child) => id = child,
+                None => return Ok(None),
+            }
+        }
+
+        self.dirs.insert(id, dir.to_path_buf());
+        Ok(Some(id))
+    }
+
+    /// Rebuilds a directory's path from its ancestors, caching every level on the way.
+    fn dir_path(&self, conn: &Connection, id: i64) -> rusqlite::Result<PathBuf> {
+        if let Some(path) = self.dirs.paths.get(&id) {
+            return Ok(path.clone());
+        }
+
+        let (parent_id, name): (i64, String) = conn
+            .prepare_cached("SELECT parent_id, name FROM path_dirs WHERE id = ?1")?
+            .query_row(params![id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        let path = match parent_id {
+            0 => PathBuf::from(name),
+            parent_id => self.dir_path(conn, parent_id)?.join(name),
+        };
+
+        self.dirs.insert(id, path.clone());
+        Ok(path)
+    }
+
+    /// A `WHERE` clause matching the row stored for `path` under either layout, with its
+    /// parameters.
+    fn path_filter(
+        &self,
+        conn: &Connection,
+        path: &Path,
+    ) -> rusqlite::Result<(&'static str, Vec<Value>)> {
+        let path_str = Value::Text(path.to_string_lossy().to_string());
+        if self.path_layout() == PathLayout::Legacy {
+            return Ok(("path = ?1", vec![path_str]));
+        }
+
+        Ok(match self.compact_key(conn, path)? {
+            Some((parent_id, name)) => (
+                "path = ?1 OR (parent_id = ?2 AND name = ?3)",
+                vec![path_str, Value::Integer(parent_id), Value::Text(name.to_string())],
+            ),
+            None => ("path = ?1", vec![path_str]),
+        })
+    }
+
+    /// The `(parent_id, name)` a compact row for `path` would have, if its directory is known.
+    fn compact_key<'a>(
+        &self,
+        conn: &Connection,
+        path: &'a Path,
+    ) -> rusqlite::Result<Option<(i64, &'a str)>> {
+        match path.parent().zip(path.file_name().and_then(|n| n.to_str())) {
+            Some((parent, name)) => Ok(self.find_dir(conn, parent)?.map(|id| (id, name))),
+            None => Ok(None),
+        }
+    }
+
+    /// A `WHERE` clause matching `dir` and every row below it under either layout, numbering
+    /// its parameters from `?first`. Legacy paths are compared as a range so the path index
+    /// serves it; compact rows are found by walking `path_dirs` down from `dir`.
+    fn under_filter(
+        &self,
+        conn: &Connection,
+        dir: &Path,
+        first: usize,
+    ) -> rusqlite::Result<(String, Vec<Value>)> {
+        let dir_str = dir.to_string_lossy().to_string();
+        let mut prefix = dir_str.clone();
+        if !prefix.ends_with(MAIN_SEPARATOR) {
+            prefix.push(MAIN_SEPARATOR);
+        }
+        // The first string past every path starting with `prefix`.
+        let mut upper = prefix.clone();
+        upper.pop();
+        upper.push((MAIN_SEPARATOR as u8 + 1) as char);
+
+        let mut clause = format!(
+            "path = ?{} OR (path >= ?{} AND path < ?{})",
+            first,
+            first + 1,
+            first + 2
+        );
+        let mut values = vec![Value::Text(dir_str), Value::Text(prefix), Value::Text(upper)];
+        if self.path_layout() == PathLayout::Legacy {
+            return Ok((format!("({})", clause), values));
+        }
+
+        if let Some(id) = self.find_dir(conn, dir)? {
+            clause.push_str(&format!(
+                " OR parent_id IN (WITH RECURSIVE below(id) AS (SELECT ?{} UNION ALL \
+                 SELECT d.id FROM path_dirs d JOIN below b ON d.parent_id = b.id) \
+                 SELECT id FROM below)",
+                first + values.len()
+            ));
+            values.push(Value::Integer(id));
+        }
+        if let Some((parent_id, name)) = self.compact_key(conn, dir)? {
+            clause.push_str(&format!(
+                " OR (parent_id = ?{} AND name = ?{})",
+                first + values.len(),
+                first + values.len() + 1
+            ));
+            values.push(Value::Integer(parent_id));
+            values.push(Value::Text(name.to_string()));
+        }
+        Ok((format!("({})", clause), values))
+    }
+
     pub fn find_by_path(&self, path: &Path) -> Result<Option<FileEntry>> {
-        let conn = self.pool.get()?;
+        if self.misses.contains(path) {
+            return Ok(None);
+        }
+        let generation = self.misses.generation();
 
-        let result = conn
-            .query_row(
+        let conn = self.reader.get()?;
+        let (filter, values) = self.path_filter(&conn, path)?;
+
+        let mut stmt = conn.prepare_cached(&format!(
+            r#"
+            SELECT id, path, name, extension, size, created_at, modified_at, accessed_at,
+                   is_directory, is_hidden, is_symlink, parent_path, mime_type, file_hash,
+                   indexed_at, last_verified, parent_id
+            FROM files WHERE {}
+            "#,
+            filter
+        ))?;
+
+        let result = stmt
+            .query_row(params_from_iter(values), |row| self.row_to_file_entry(&conn, row))
+            .optional()?;
+        if result.is_none() {
+            self.misses.insert(path, generation);
+        }
+
+        Ok(result)
+    }
+
+    /// Looks up many paths with one query per [`PATH_LOOKUP_CHUNK`] paths. Paths that are not
+    /// indexed are left out, and entries come back in no particular order.
+    pub fn find_by_paths(&self, paths: &[PathBuf]) -> Result<Vec<FileEntry>> {
+        let conn = self.reader.get()?;
+        let compact = self.path_layout() == PathLayout::Compact;
+        let mut found = Vec::with_capacity(paths.len());
+
+        for chunk in paths.chunks(PATH_LOOKUP_CHUNK) {
+            let mut values: Vec<Value> = chunk
+                .iter()
+                .map(|path| Value::Text(path.to_string_lossy().to_string()))
+                .collect();
+            let mut filter = format!("path IN ({})", vec!["?"; chunk.len()].join(", "));
+
+            if compact {
+                let mut keys = Vec::new();
+                for path in chunk {
+                    if let Some((parent_id, name)) = self.compact_key(&conn, path)? {
+                        keys.push("(?, ?)");
+                        values.push(Value::Integer(parent_id));
+                        values.push(Value::Text(name.to_string()));
+                    }
+                }
+                if !keys.is_empty() {
+                    let keys = keys.join(", ");
+                    filter.push_str(&format!(" OR (parent_id, name) IN (VALUES {})", keys));
+                }
+            }
+
+            let mut stmt = conn.prepare(&format!(
                 r#"
                 SELECT id, path, name, extension, size, created_at, modified_at, accessed_at,
                        is_directory, is_hidden, is_symlink, parent_path, mime_type, file_hash,
-                       indexed_at, last_verified
-                FROM files WHERE path = ?1
+                       indexed_at, last_verified, parent_id
+                FROM files WHERE {}
                 "#,
-                params![path.to_string_lossy().to_string()],
-                |row| Self::row_to_file_entry(row),
-            )
-            .optional()?;
+                filter
+            ))?;
+            let rows = stmt.query_map(params_from_iter(values), |row| {
+                self.row_to_file_entry(&conn, row)
+            })?;
+            for row in rows {
+                found.push(row?);
+            }
+        }
 
-        Ok(result)
+        Ok(found)
     }
 
     pub fn find_by_id(&self, id: i64) -> Result<Option<FileEntry>> {
-        let conn = self.pool.get()?;
+        let conn = self.reader.get()?;
 
-        let result = conn
-            .query_row(
+        let mut stmt = conn.prepare_cached(
+            r#"
+            SELECT id, path, name, extension, size, created_at, modified_at, accessed_at,
+                   is_directory, is_hidden, is_symlink, parent_path, mime_type, file_hash,
+                   indexed_at, last_verified, parent_id
+            FROM files WHERE id = ?1
+            "#,
+        )?;
+
+        let result = stmt
+            .query_row(params![id], |row| self.row_to_file_entry(&conn, row))
+            .optional()?;
+
+        Ok(result)
+    }
+
+    /// Entries with the given ids, in the order given; ids without an entry are left out.
+    pub fn find_by_ids(&self, ids: &[i64]) -> Result<Vec<FileEntry>> {
+        let conn = self.reader.get()?;
+        let mut found = HashMap::with_capacity(ids.len());
+        for chunk in ids.chunks(FIND_BY_IDS_BATCH) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            let mut stmt = conn.prepare_cached(&format!(
                 r#"
                 SELECT id, path, name, extension, size, created_at, modified_at, accessed_at,
                        is_directory, is_hidden, is_symlink, parent_path, mime_type, file_hash,
-                       indexed_at, last_verified
-                FROM files WHERE id = ?1
+                       indexed_at, last_verified, parent_id
+                FROM files WHERE id IN ({})
                 "#,
-                params![id],
-                |row| Self::row_to_file_entry(row),
-            )
-            .optional()?;
+                placeholders
+            ))?;
+            let entries = stmt.query_map(params_from_iter(chunk), |row| {
+                self.row_to_file_entry(&conn, row)
+            })?;
+            for entry in entries {
+                let entry = entry?;
+                found.insert(entry.id, entry);
+            }
+        }
 
-        Ok(result)
+        Ok(ids.iter().filter_map(|id| found.remove(&Some(*id))).collect())
     }
 
-    pub fn delete_by_path(&self, path: &Path) -> Result<()> {
-        let conn = self.pool.get()?;
-        conn.execute(
-            "DELETE FROM files WHERE path = ?1",
-            params![path.to_string_lossy().to_string()],
-        )?;
-        Ok(())
+    /// How many times entry names and paths have changed, as counted by triggers on `files`.
+    pub fn name_generation(&self) -> Result<u64> {
+        let conn = self.reader.get()?;
+        Self::read_name_generation(&conn)
     }
 
-    pub fn search_by_name(&self, pattern: &str, limit: usize) -> Result<Vec<FileEntry>> {
-        let conn = self.pool.get()?;
-        let mut stmt = conn.prepare(
+    fn read_name_generation(conn: &Connection) -> Result<u64> {
+        let generation: i64 = conn
+            .prepare_cached("SELECT generation FROM name_generation WHERE id = 1")?
+            .query_row([], |row| row.get(0))
+            .optional()?
+            .unwrap_or(0);
+        Ok(generation as u64)
+    }
+
+    /// Calls `visit` with the id, name, normalized name and full path of every entry, in id
+    /// order, with `\` in paths turned into `/` as path searches see them. Returns the name
+    /// generation the entries were read at.
+    pub fn for_each_name(
+        &self,
+        mut visit: impl FnMut(i64, &str, Option<&str>, &str),
+    ) -> Result<u64> {
+        let conn = self.reader.get()?;
+        // One read transaction, so the generation belongs to exactly the rows read.
+        let tx = conn.unchecked_transaction()?;
+        let generation = Self::read_name_generation(&tx)?;
+        let mut stmt = tx.prepare(&format!(
             r#"
-            SELECT id, path, name, extension, size, created_at, modified_at, accessed_at,
-                   is_directory, is_hidden, is_symlink, parent_path, mime_type, file_hash,
-                   indexed_at, last_verified
-            FROM files WHERE name LIKE ?1 LIMIT ?2
+            {}
+            SELECT f.id, f.name, f.name_normalized, REPLACE({}, '\', '/')
+            FROM files f LEFT JOIN dir_paths dp ON dp.id = f.parent_id
+            ORDER BY f.id
             "#,
-        )?;
+            DIR_PATHS_CTE, FULL_PATH_SQL
+        ))?;
+        let mut rows = stmt.query(params![MAIN_SEPARATOR_STR])?;
+        while let Some(row) = rows.next()? {
+            let name: String = row.get(1)?;
+            let normalized: Option<String> = row.get(2)?;
+            let path: Option<String> = row.get(3)?;
+            visit(
+                row.get(0)?,
+                &name,
+                normalized.as_deref(),
+                path.as_deref().unwrap_or_default(),
+            );
+        }
+        Ok(generation)
+    }
 
-        let files = stmt
-            .query_map(params![format!("%{}%", pattern), limit], |row| {
-                Self::row_to_file_entry(row)
-            })?
-            .collect::<rusqlite::Result<Vec<_>>>()?;
+    pub fn delete_by_path(&self, path: &Path) -> Result<()> {
+        let conn = self.writer.get()?;
+        let tx = conn.unchecked_transaction()?;
+        let path_str = path.to_string_lossy().to_string();
 
-        Ok(files)
+        let (filter, values) = self.path_filter(&tx, path)?;
+        let file_id: Option<i64> = tx
+            .prepare_cached(&format!("SELECT id FROM files WHERE {}", filter))?
+            .query_row(params_from_iter(values), |row| row.get(0))
+            .optional()?;
+        if let Some(file_id) = file_id {
+            let mut change = StatsCounters::of_row(&tx, file_id)?.unwrap_or_default();
+            // Release content first; the files delete cascades to file_contents.
+            if Self::release_content(&tx, file_id)? {
+                change += StatsCounters::content(1);
+            }
+            tx.prepare_cached("DELETE FROM files WHERE id = ?1")?
+                .execute(params![file_id])?;
+            (StatsCounters::default() - change).apply(&tx)?;
+            tx.prepare_cached(
+                "INSERT OR REPLACE INTO deleted_files (path, deleted_at) VALUES (?1, ?2)",
+            )?
+            .execute(params![path_str, Utc::now().timestamp()])?;
+        }
+
+        tx.commit()?;
+        Ok(())
     }
 
-    pub fn search_by_extension(&self, extension: &str, limit: usize) -> Result<Vec<FileEntry>> {
-        let conn = self.pool.get()?;
+    pub fn get_files_changed_since(
+        &self,
+        since: DateTime<Utc>,
+        after_id: i64,
+        limit: usize,
+    ) -> Result<Vec<FileEntry>> {
+        let conn = self.reader.get()?;
         let mut stmt = conn.prepare(
             r#"
             SELECT id, path, name, extension, size, created_at, modified_at, accessed_at,
                    is_directory, is_hidden, is_symlink, parent_path, mime_type, file_hash,
-                   indexed_at, last_verified
-            FROM files WHERE extension = ?1 LIMIT ?2
+                   indexed_at, last_verified, parent_id
+            FROM files
+            WHERE (indexed_at >= ?1 OR last_verified >= ?1) AND id > ?2
+            ORDER BY id
+            LIMIT ?3
             "#,
         )?;
 
         let files = stmt
-            .query_map(params![extension, limit], |row| {
-                Self::row_to_file_entry(row)
-            })?
+            .query_map(
+                params![since.timestamp(), after_id, limit],
+                |row| self.row_to_file_entry(&conn, row),
+            )?
             .collect::<rusqlite::Result<Vec<_>>>()?;
 
         Ok(files)
     }
 
-    pub fn get_all_files(&self, limit: usize, offset: usize) -> Result<Vec<FileEntry>> {
-        let conn = self.pool.get()?;
+    pub fn get_deletions_since(&self, since: DateTime<Utc>) -> Result<Vec<(PathBuf, DateTime<Utc>)>> {
+        let conn = self.reader.get()?;
         let mut stmt = conn.prepare(
-            r#"
-            SELECT id, path, name, extension, size, created_at, modified_at, accessed_at,
-                   is_directory, is_hidden, is_symlink, parent_path, mime_type, file_hash,
-                   indexed_at, last_verified
-            FROM files LIMIT ?1 OFFSET ?2
-            "#,
+            "SELECT path, deleted_at FROM deleted_files WHERE deleted_at >= ?1 ORDER BY deleted_at",
         )?;
 
-        let files = stmt
-            .query_map(params![limit, offset], |row| Self::row_to_file_entry(row))?
+        let deletions = stmt
+            .query_map(params![since.timestamp()], |row| {
+                let path: String = row.get(0)?;
+                let deleted_at: i64 = row.get(1)?;
+                Ok((
+                    PathBuf::from(path),
+                    Utc.timestamp_opt(deleted_at, 0).single().unwrap_or_else(Utc::now),
+                ))
+            })?
             .collect::<rusqlite::Result<Vec<_>>>()?;
 
-        Ok(files)
+        Ok(deletions)
     }
 
-    pub fn insert_content(&self, file_id: i64, preview: &ContentPreview) -> Result<()> {
-        let conn = self.pool.get()?;
-
-        conn.execute(
-            r#"
-            INSERT INTO file_contents (file_id, content_preview, word_count, line_count, encoding)
-            VALUES (?1, ?2, ?3, ?4, ?5)
-            ON CONFLICT(file_id) DO UPDATE SET
-                content_preview = excluded.content_preview,
-                word_count = excluded.word_count,
-                line_count = excluded.line_count,
-                encoding = excluded.encoding
-            "#,
-            params![
-                file_id,
-                preview.preview,
-                preview.word_count as i64,
-                preview.line_count as i64,
-                preview.encoding
-            ],
+    pub fn prune_deletions(&self, before: DateTime<Utc>) -> Result<usize> {
+        let conn = self.writer.get()?;
+        let pruned = conn.execute(
+            "DELETE FROM deleted_files WHERE deleted_at < ?1",
+            params![before.timestamp()],
         )?;
-
-        Ok(())
+        Ok(pruned)
     }
 
-    pub fn insert_fts_entry(&self, file_id: i64, name: &str, path: &str, content: &str) -> Result<()> {
-        let conn = self.pool.get()?;
+    pub fn insert_audit_batch(&self, records: &[AuditRecord]) -> Result<()> {
+        let mut conn = self.writer.get()?;
+        let tx = conn.transaction()?;
 
-        conn.execute(
-            "INSERT INTO files_fts (file_id, name, path, content) VALUES (?1, ?2, ?3, ?4)",
-            params![file_id, name, path, content],
-        )?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO audit_log (path, event_type, occurred_at, size, file_hash, watch_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for record in records {
+                stmt.execute(params![
+                    record.path.to_string_lossy(),
+                    record.event_type.as_str(),
+                    record.occurred_at.timestamp(),
+                    record.size.map(|size| size as i64),
+                    record.file_hash,
+                    record.watch_id,
+                ])?;
+            }
+        }
 
+        tx.commit()?;
         Ok(())
     }
 
-    pub fn search_content(&self, query: &str, limit: usize) -> Result<Vec<i64>> {
-        let conn = self.pool.get()?;
-        let mut stmt = conn.prepare(
-            "SELECT file_id FROM files_fts WHERE files_fts MATCH ?1 LIMIT ?2"
-        )?;
+    // Chronological; pass the last returned id as `after_id` to fetch the next page.
+    pub fn query_audit_log(
+        &self,
+        path_prefix: Option<&Path>,
+        time_range: (Option<DateTime<Utc>>, Option<DateTime<Utc>>),
+        event_types: &[AuditEventType],
+        after_id: i64,
+        limit: usize,
+    ) -> Result<Vec<AuditRecord>> {
+        let mut clauses = vec!["id > ?".to_string()];
+        let mut values = vec![Value::Integer(after_id)];
 
-        let file_ids = stmt
-            .query_map(params![query, limit], |row| row.get(0))?
-            .collect::<rusqlite::Result<Vec<_>>>()?;
+        if let Some(prefix) = path_prefix {
+            let prefix = prefix.to_string_lossy();
+            let prefix = prefix.trim_end_matches(['/', '\\']);
+            clauses.push(
+                "(path = ? OR path LIKE ? ESCAPE '\\' OR path LIKE ? ESCAPE '\\')".to_string(),
+            );
+            values.push(Value::Text(prefix.to_string()));
+            values.push(Value::Text(format!("{}/%", escape_like(prefix))));
+            values.push(Value::Text(format!("{}\\\\%", escape_like(prefix))));
+        }
+
+        if let Some(since) = time_range.0 {
+            clauses.push("occurred_at >= ?".to_string());
+            values.push(Value::Integer(since.timestamp()));
+        }
+
+        if let Some(until) = time_range.1 {
+            clauses.push("occurred_at <= ?".to_string());
+            values.push(Value::Integer(until.timestamp()));
+        }
+
+        if !event_types.is_empty() {
+            let placeholders = vec!["?"; event_types.len()].join(", ");
+            clauses.push(format!("event_type IN ({})", placeholders));
+            values.extend(
+                event_types
+                    .iter()
+                    .map(|event_type| Value::Text(event_type.as_str().to_string())),
+            );
+        }
+
+        values.push(Value::Integer(limit as i64));
+
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, path, event_type, occurred_at, size, file_hash, watch_id
+             FROM audit_log WHERE {} ORDER BY id LIMIT ?",
+            clauses.join(" AND ")
+        ))?;
+
+        let records = stmt
+            .query_map(params_from_iter(values), |row| {
+                let event_type: String = row.get(2)?;
+                let occurred_at: i64 = row.get(3)?;
+                Ok(AuditRecord {
+                    id: Some(row.get(0)?),
+                    path: PathBuf::from(row.get::<_, String>(1)?),
+                    event_type: AuditEventType::parse(&event_type)
+                        .unwrap_or(AuditEventType::Modified),
+                    occurred_at: Utc
+                        .timestamp_opt(occurred_at, 0)
+                        .single()
+                        .unwrap_or_else(Utc::now),
+                    size: row.get::<_, Option<i64>>(4)?.map(|size| size as u64),
+                    file_hash: row.get(5)?,
+                    watch_id: row.get(6)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(records)
+    }
+
+    pub fn prune_audit_log(&self, before: DateTime<Utc>) -> Result<usize> {
+        let conn = self.writer.get()?;
+        let pruned = conn.execute(
+            "DELETE FROM audit_log WHERE occurred_at < ?1",
+            params![before.timestamp()],
+        )?;
+        Ok(pruned)
+    }
+
+    pub fn clear_audit_log(&self) -> Result<usize> {
+        let conn = self.writer.get()?;
+        Ok(conn.execute("DELETE FROM audit_log", [])?)
+    }
+
+    pub fn search_by_name(&self, pattern: &str, limit: usize) -> Result<Vec<FileEntry>> {
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(
+            r#"
+            SELECT id, path, name, extension, size, created_at, modified_at, accessed_at,
+                   is_directory, is_hidden, is_symlink, parent_path, mime_type, file_hash,
+                   indexed_at, last_verified, parent_id
+            FROM files WHERE name LIKE ?1 OR name_normalized LIKE ?2 LIMIT ?3
+            "#,
+        )?;
+
+        let normalized = self.normalizer.normalize(pattern);
+        let files = stmt
+            .query_map(
+                params![format!("%{}%", pattern), format!("%{}%", normalized), limit],
+                |row| self.row_to_file_entry(&conn, row),
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(files)
+    }
+
+    /// How many entries [`search_by_name`](Self::search_by_name) finds without a limit.
+    pub fn count_by_name(&self, pattern: &str) -> Result<usize> {
+        let conn = self.reader.get()?;
+        let normalized = self.normalizer.normalize(pattern);
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM files WHERE name LIKE ?1 OR name_normalized LIKE ?2",
+            params![format!("%{}%", pattern), format!("%{}%", normalized)],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    /// Entries at or below `dir` whose name contains `pattern`, with the directory matched in
+    /// SQL so the limit only counts entries inside it.
+    pub fn search_by_name_under(
+        &self,
+        pattern: &str,
+        dir: &Path,
+        limit: usize,
+    ) -> Result<Vec<FileEntry>> {
+        let conn = self.reader.get()?;
+        let (under, mut values) = self.under_filter(&conn, dir, 1)?;
+        let next = values.len() + 1;
+        let mut stmt = conn.prepare_cached(&format!(
+            r#"
+            SELECT id, path, name, extension, size, created_at, modified_at, accessed_at,
+                   is_directory, is_hidden, is_symlink, parent_path, mime_type, file_hash,
+                   indexed_at, last_verified, parent_id
+            FROM files WHERE {} AND (name LIKE ?{} OR name_normalized LIKE ?{}) LIMIT ?{}
+            "#,
+            under,
+            next,
+            next + 1,
+            next + 2
+        ))?;
+
+        let normalized = self.normalizer.normalize(pattern);
+        values.push(Value::Text(format!("%{}%", pattern)));
+        values.push(Value::Text(format!("%{}%", normalized)));
+        values.push(Value::Integer(limit as i64));
+        let files = stmt
+            .query_map(params_from_iter(values), |row| self.row_to_file_entry(&conn, row))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(files)
+    }
+
+    /// Entries whose MIME type matches `filter`, exact or a prefix ending in `*`, and whose
+    /// name contains `pattern`.
+    pub fn search_by_mime(
+        &self,
+        filter: &str,
+        pattern: &str,
+        limit: usize,
+    ) -> Result<Vec<FileEntry>> {
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(
+            r#"
+            SELECT id, path, name, extension, size, created_at, modified_at, accessed_at,
+                   is_directory, is_hidden, is_symlink, parent_path, mime_type, file_hash,
+                   indexed_at, last_verified, parent_id
+            FROM files
+            WHERE mime_type LIKE ?1 ESCAPE '\' AND (name LIKE ?2 OR name_normalized LIKE ?3)
+            LIMIT ?4
+            "#,
+        )?;
+
+        // LIKE ignores ASCII case, as MIME types do.
+        let mime = match filter.strip_suffix('*') {
+            Some(prefix) => format!("{}%", escape_like(prefix)),
+            None => escape_like(filter),
+        };
+        let normalized = self.normalizer.normalize(pattern);
+        let files = stmt
+            .query_map(
+                params![mime, format!("%{}%", pattern), format!("%{}%", normalized), limit],
+                |row| self.row_to_file_entry(&conn, row),
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(files)
+    }
+
+    pub fn search_by_path_contains(&self, pattern: &str, limit: usize) -> Result<Vec<FileEntry>> {
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(&format!(
+            r#"
+            {}
+            SELECT f.id, f.path, f.name, f.extension, f.size, f.created_at, f.modified_at,
+                   f.accessed_at, f.is_directory, f.is_hidden, f.is_symlink, f.parent_path,
+                   f.mime_type, f.file_hash, f.indexed_at, f.last_verified, f.parent_id
+            FROM files f LEFT JOIN dir_paths dp ON dp.id = f.parent_id
+            WHERE REPLACE({}, '\', '/') LIKE ?2 ESCAPE '\' LIMIT ?3
+            "#,
+            DIR_PATHS_CTE, FULL_PATH_SQL
+        ))?;
+
+        let pattern = escape_like(&normalize_separators(pattern));
+        let files = stmt
+            .query_map(
+                params![MAIN_SEPARATOR_STR, format!("%{}%", pattern), limit],
+                |row| self.row_to_file_entry(&conn, row),
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(files)
+    }
+
+    pub fn search_by_kind(
+        &self,
+        pattern: &str,
+        kinds: &[EntryKind],
+        limit: usize,
+    ) -> Result<Vec<FileEntry>> {
+        let kind_clause = kinds
+            .iter()
+            .map(|kind| match kind {
+                EntryKind::File => "(is_directory = 0 AND is_symlink = 0)",
+                EntryKind::Directory => "(is_directory = 1 AND is_symlink = 0)",
+                EntryKind::Symlink => "is_symlink = 1",
+            })
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        let kind_clause = if kind_clause.is_empty() { "1" } else { &kind_clause };
+
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(&format!(
+            r#"
+            SELECT id, path, name, extension, size, created_at, modified_at, accessed_at,
+                   is_directory, is_hidden, is_symlink, parent_path, mime_type, file_hash,
+                   indexed_at, last_verified, parent_id
+            FROM files WHERE (name LIKE ?1 OR name_normalized LIKE ?2) AND ({}) LIMIT ?3
+            "#,
+            kind_clause
+        ))?;
+
+        let normalized = self.normalizer.normalize(pattern);
+        let files = stmt
+            .query_map(
+                params![format!("%{}%", pattern), format!("%{}%", normalized), limit],
+                |row| self.row_to_file_entry(&conn, row),
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(files)
+    }
+
+    /// Files with the extension, in any case; extensions are stored lowercased.
+    pub fn search_by_extension(&self, extension: &str, limit: usize) -> Result<Vec<FileEntry>> {
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(
+            r#"
+            SELECT id, path, name, extension, size, created_at, modified_at, accessed_at,
+                   is_directory, is_hidden, is_symlink, parent_path, mime_type, file_hash,
+                   indexed_at, last_verified, parent_id
+            FROM files WHERE extension = ?1 LIMIT ?2
+            "#,
+        )?;
+
+        let files = stmt
+            .query_map(params![extension.to_ascii_lowercase(), limit], |row| {
+                self.row_to_file_entry(&conn, row)
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(files)
+    }
+
+    /// The most common file extensions in the index with their counts, most frequent first.
+    pub fn top_extensions(&self, limit: usize) -> Result<Vec<(String, usize)>> {
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(
+            r#"
+            SELECT extension, COUNT(*) AS count FROM files
+            WHERE extension IS NOT NULL AND extension != '' AND is_directory = 0
+            GROUP BY extension ORDER BY count DESC, extension LIMIT ?1
+            "#,
+        )?;
+
+        let extensions = stmt
+            .query_map(params![limit], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(extensions)
+    }
+
+    /// Files with the newest modification times, newest first. Directories are left out.
+    pub fn recently_modified(&self, limit: usize) -> Result<Vec<FileEntry>> {
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(
+            r#"
+            SELECT id, path, name, extension, size, created_at, modified_at, accessed_at,
+                   is_directory, is_hidden, is_symlink, parent_path, mime_type, file_hash,
+                   indexed_at, last_verified, parent_id
+            FROM files WHERE is_directory = 0 AND modified_at IS NOT NULL
+            ORDER BY modified_at DESC, id DESC LIMIT ?1
+            "#,
+        )?;
+
+        let files = stmt
+            .query_map(params![limit], |row| self.row_to_file_entry(&conn, row))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(files)
+    }
+
+    pub fn get_all_files(&self, limit: usize, offset: usize) -> Result<Vec<FileEntry>> {
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT id, path, name, extension, size, created_at, modified_at, accessed_at,
+                   is_directory, is_hidden, is_symlink, parent_path, mime_type, file_hash,
+                   indexed_at, last_verified, parent_id
+            FROM files LIMIT ?1 OFFSET ?2
+            "#,
+        )?;
+
+        let files = stmt
+            .query_map(params![limit, offset], |row| self.row_to_file_entry(&conn, row))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(files)
+    }
+
+    /// The indexed files and their content in a canonical text form, for comparing indexes:
+    /// one JSON object per line, files by path, then content by hash. Row ids and timestamps
+    /// are left out, so two indexes of the same tree dump the same whenever and in whatever
+    /// order they were built.
+    pub fn logical_dump(&self) -> Result<String> {
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT f.id, f.path, f.name, f.extension, f.size, f.created_at, f.modified_at,
+                   f.accessed_at, f.is_directory, f.is_hidden, f.is_symlink, f.parent_path,
+                   f.mime_type, f.file_hash, f.indexed_at, f.last_verified, f.parent_id,
+                   f.name_normalized, fc.content_hash
+            FROM files f
+            LEFT JOIN file_contents fc ON fc.file_id = f.id
+            "#,
+        )?;
+        let mut files = stmt
+            .query_map([], |row| {
+                let entry = self.row_to_file_entry(&conn, row)?;
+                let normalized: Option<String> = row.get(17)?;
+                let content: Option<String> = row.get(18)?;
+                Ok((entry, normalized, content))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        files.sort_by(|a, b| a.0.path.cmp(&b.0.path));
+
+        let mut dump = String::new();
+        for (entry, normalized, content) in files {
+            let kind = if entry.is_directory { "dir" } else { "file" };
+            let line = serde_json::json!({
+                "path": entry.path.to_string_lossy(),
+                "name": entry.name,
+                "name_normalized": normalized,
+                "extension": entry.extension,
+                "kind": kind,
+                "size": entry.size,
+                "hidden": entry.is_hidden,
+                "symlink": entry.is_symlink,
+                "mime_type": entry.mime_type,
+                "file_hash": entry.file_hash,
+                "content": content,
+            });
+            dump.push_str(&line.to_string());
+            dump.push('\n');
+        }
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT hash, content_preview, word_count, line_count, encoding, refcount, sampled
+            FROM content_blobs ORDER BY hash
+            "#,
+        )?;
+        let contents = stmt
+            .query_map([], |row| {
+                Ok(serde_json::json!({
+                    "hash": row.get::<_, String>(0)?,
+                    "preview": row.get::<_, Option<String>>(1)?,
+                    "word_count": row.get::<_, Option<i64>>(2)?,
+                    "line_count": row.get::<_, Option<i64>>(3)?,
+                    "encoding": row.get::<_, Option<String>>(4)?,
+                    "refcount": row.get::<_, i64>(5)?,
+                    "sampled": row.get::<_, bool>(6)?,
+                }))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for content in contents {
+            dump.push_str(&content.to_string());
+            dump.push('\n');
+        }
+
+        Ok(dump)
+    }
+
+    pub fn insert_content(&self, file_id: i64, preview: &ContentPreview) -> Result<()> {
+        let conn = self.writer.get()?;
+        let tx = conn.unchecked_transaction()?;
+
+        let previous: Option<String> = tx
+            .prepare_cached("SELECT content_hash FROM file_contents WHERE file_id = ?1")?
+            .query_row(params![file_id], |row| row.get(0))
+            .optional()?;
+
+        if previous.as_deref() == Some(preview.content_hash.as_str()) {
+            return Ok(());
+        }
+
+        let refcount: i64 = tx
+            .prepare_cached(
+                r#"
+                INSERT INTO content_blobs (hash, content_preview, word_count, line_count, encoding, sampled, refcount)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, 1)
+                ON CONFLICT(hash) DO UPDATE SET refcount = refcount + 1
+                RETURNING refcount
+                "#,
+            )?
+            .query_row(
+                params![
+                    preview.content_hash,
+                    preview.preview,
+                    preview.word_count as i64,
+                    preview.line_count as i64,
+                    preview.encoding,
+                    preview.sampled
+                ],
+                |row| row.get(0),
+            )?;
+
+        if refcount == 1 {
+            self.insert_fts_chunks(&tx, "files_fts", &preview.content_hash, &preview.preview)?;
+        }
+
+        tx.prepare_cached(
+            r#"
+            INSERT INTO file_contents (file_id, content_hash) VALUES (?1, ?2)
+            ON CONFLICT(file_id) DO UPDATE SET content_hash = excluded.content_hash
+            "#,
+        )?
+        .execute(params![file_id, preview.content_hash])?;
+
+        match previous {
+            Some(old_hash) => Self::release_blob(&tx, &old_hash)?,
+            None => StatsCounters::content(1).apply(&tx)?,
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn insert_fts_chunks(
+        &self,
+        conn: &rusqlite::Connection,
+        table: &str,
+        hash: &str,
+        text: &str,
+    ) -> Result<()> {
+        let mut insert = conn.prepare_cached(&format!(
+            r#"
+            INSERT INTO {} (content_hash, chunk_index, byte_offset, content)
+            VALUES (?1, ?2, ?3, ?4)
+            "#,
+            table
+        ))?;
+        for chunk in split_chunks(text, self.fts_chunk_bytes) {
+            insert.execute(params![
+                hash,
+                chunk.index as i64,
+                chunk.byte_offset as i64,
+                chunk.text
+            ])?;
+        }
+        Ok(())
+    }
+
+    /// Returns whether the file had content; the caller adjusts the statistics counters.
+    fn release_content(conn: &rusqlite::Connection, file_id: i64) -> Result<bool> {
+        let hash: Option<String> = conn
+            .prepare_cached("DELETE FROM file_contents WHERE file_id = ?1 RETURNING content_hash")?
+            .query_row(params![file_id], |row| row.get(0))
+            .optional()?;
+
+        match hash {
+            Some(hash) => {
+                Self::release_blob(conn, &hash)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn release_blob(conn: &rusqlite::Connection, hash: &str) -> Result<()> {
+        let refcount: Option<i64> = conn
+            .prepare_cached(
+                "UPDATE content_blobs SET refcount = refcount - 1 WHERE hash = ?1 RETURNING refcount",
+            )?
+            .query_row(params![hash], |row| row.get(0))
+            .optional()?;
+
+        if refcount.is_some_and(|count| count <= 0) {
+            conn.prepare_cached("DELETE FROM files_fts WHERE content_hash = ?1")?
+                .execute(params![hash])?;
+            conn.prepare_cached("DELETE FROM content_blobs WHERE hash = ?1")?
+                .execute(params![hash])?;
+        }
+
+        Ok(())
+    }
+
+    /// Ids of files whose content matches the full-text query `query`, best match first. A
+    /// file's rank is that of its best matching chunk.
+    pub fn search_content(&self, query: &str, limit: usize) -> Result<Vec<i64>> {
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(
+            r#"
+            SELECT fc.file_id
+            FROM (
+                SELECT content_hash, MIN(rank) AS rank FROM files_fts
+                WHERE files_fts MATCH ?1 GROUP BY content_hash
+            ) m
+            JOIN file_contents fc ON fc.content_hash = m.content_hash
+            ORDER BY m.rank LIMIT ?2
+            "#,
+        )?;
+
+        let file_ids = stmt
+            .query_map(params![query, limit], |row| row.get(0))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(fts_error)?;
 
         Ok(file_ids)
     }
 
-    pub fn add_exclusion_rule(&self, rule: &ExclusionRule) -> Result<i64> {
-        let conn = self.pool.get()?;
+    pub fn search_content_files(&self, query: &str, limit: usize) -> Result<Vec<FileEntry>> {
+        Ok(self
+            .search_content_ranked(query, limit)?
+            .into_iter()
+            .map(|(entry, _)| entry)
+            .collect())
+    }
+
+    /// Files whose content matches the full-text query `query`, best match first, each with
+    /// the rank of its best matching chunk as in
+    /// [`search_by_name_with_content`](Self::search_by_name_with_content).
+    pub fn search_content_ranked(
+        &self,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<(FileEntry, f64)>> {
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(
+            r#"
+            SELECT f.id, f.path, f.name, f.extension, f.size, f.created_at, f.modified_at,
+                   f.accessed_at, f.is_directory, f.is_hidden, f.is_symlink, f.parent_path,
+                   f.mime_type, f.file_hash, f.indexed_at, f.last_verified, f.parent_id,
+                   m.rank
+            FROM (
+                SELECT content_hash, MIN(rank) AS rank FROM files_fts
+                WHERE files_fts MATCH ?1 GROUP BY content_hash
+            ) m
+            JOIN file_contents fc ON fc.content_hash = m.content_hash
+            JOIN files f ON f.id = fc.file_id
+            ORDER BY m.rank LIMIT ?2
+            "#,
+        )?;
+
+        let files = stmt
+            .query_map(params![query, limit], |row| {
+                Ok((self.row_to_file_entry(&conn, row)?, row.get(17)?))
+            })
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(fts_error)?;
+
+        Ok(files)
+    }
+
+    /// Up to `limit` chunks of a file's content matching the full-text query `query`, best
+    /// first. Empty for sampled content, whose offsets are not positions in the file.
+    pub fn content_chunk_hits(
+        &self,
+        file_id: i64,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<ChunkHit>> {
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(
+            r#"
+            SELECT chunk_index, byte_offset, length(CAST(content AS BLOB)), rank
+            FROM files_fts
+            WHERE files_fts MATCH ?2 AND content_hash = (
+                SELECT fc.content_hash FROM file_contents fc
+                JOIN content_blobs b ON b.hash = fc.content_hash
+                WHERE fc.file_id = ?1 AND b.sampled = 0
+            )
+            ORDER BY rank LIMIT ?3
+            "#,
+        )?;
+
+        let hits = stmt
+            .query_map(params![file_id, query, limit as i64], |row| {
+                Ok(ChunkHit {
+                    chunk_index: row.get::<_, i64>(0)? as usize,
+                    byte_offset: row.get::<_, i64>(1)? as usize,
+                    length: row.get::<_, i64>(2)? as usize,
+                    rank: row.get(3)?,
+                })
+            })
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(fts_error)?;
+
+        Ok(hits)
+    }
+
+    /// Files whose name contains `pattern` and whose content matches the full-text query
+    /// `content`, in one query, each with the rank of its best content match: FTS5's bm25,
+    /// where lower is better.
+    pub fn search_by_name_with_content(
+        &self,
+        pattern: &str,
+        content: &str,
+        limit: usize,
+    ) -> Result<Vec<(FileEntry, f64)>> {
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(
+            r#"
+            SELECT f.id, f.path, f.name, f.extension, f.size, f.created_at, f.modified_at,
+                   f.accessed_at, f.is_directory, f.is_hidden, f.is_symlink, f.parent_path,
+                   f.mime_type, f.file_hash, f.indexed_at, f.last_verified, f.parent_id,
+                   c.rank
+            FROM files f
+            JOIN (
+                SELECT fc.file_id, MIN(m.rank) AS rank
+                FROM (SELECT content_hash, rank FROM files_fts WHERE files_fts MATCH ?3) m
+                JOIN file_contents fc ON fc.content_hash = m.content_hash
+                GROUP BY fc.file_id
+            ) c ON c.file_id = f.id
+            WHERE f.name LIKE ?1 OR f.name_normalized LIKE ?2
+            LIMIT ?4
+            "#,
+        )?;
+
+        let normalized = self.normalizer.normalize(pattern);
+        let files = stmt
+            .query_map(
+                params![format!("%{}%", pattern), format!("%{}%", normalized), content, limit],
+                |row| Ok((self.row_to_file_entry(&conn, row)?, row.get(17)?)),
+            )
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(fts_error)?;
+
+        Ok(files)
+    }
+
+    /// Every file whose content matches the full-text query `content`, by id, with the rank
+    /// of its best match as in [`search_by_name_with_content`](Self::search_by_name_with_content).
+    pub fn content_match_ranks(&self, content: &str) -> Result<HashMap<i64, f64>> {
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(
+            r#"
+            SELECT fc.file_id, MIN(m.rank)
+            FROM (SELECT content_hash, rank FROM files_fts WHERE files_fts MATCH ?1) m
+            JOIN file_contents fc ON fc.content_hash = m.content_hash
+            GROUP BY fc.file_id
+            "#,
+        )?;
+
+        let ranks = stmt
+            .query_map(params![content], |row| Ok((row.get(0)?, row.get(1)?)))
+            .and_then(|rows| rows.collect::<rusqlite::Result<HashMap<_, _>>>())
+            .map_err(fts_error)?;
+
+        Ok(ranks)
+    }
+
+    /// Marks `file_ids` as waiting for their content to be read, on behalf of a quick build of
+    /// `root`. Returns how many were not already pending.
+    pub fn queue_pending_content(&self, root: &Path, file_ids: &[i64]) -> Result<usize> {
+        let conn = self.writer.get()?;
+        let tx = conn.unchecked_transaction()?;
+        let root = root.to_string_lossy();
+
+        let mut queued = 0;
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT OR IGNORE INTO pending_content (file_id, root) VALUES (?1, ?2)",
+            )?;
+            for file_id in file_ids {
+                queued += insert.execute(params![file_id, root])?;
+            }
+        }
+
+        tx.prepare_cached(
+            r#"
+            INSERT INTO enrichment_roots (root, total, queued_at) VALUES (?1, ?2, ?3)
+            ON CONFLICT(root) DO UPDATE SET
+                total = total + excluded.total,
+                queued_at = excluded.queued_at
+            "#,
+        )?
+        .execute(params![root, queued as i64, Utc::now().timestamp()])?;
+
+        tx.commit()?;
+        Ok(queued)
+    }
+
+    /// Up to `limit` pending files, under `root` if given, most recently modified first.
+    pub fn pending_content_batch(
+        &self,
+        root: Option<&Path>,
+        limit: usize,
+    ) -> Result<Vec<FileEntry>> {
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(
+            r#"
+            SELECT f.id, f.path, f.name, f.extension, f.size, f.created_at, f.modified_at,
+                   f.accessed_at, f.is_directory, f.is_hidden, f.is_symlink, f.parent_path,
+                   f.mime_type, f.file_hash, f.indexed_at, f.last_verified, f.parent_id
+            FROM pending_content p JOIN files f ON f.id = p.file_id
+            WHERE ?1 IS NULL OR p.root = ?1
+            ORDER BY f.modified_at DESC, f.id LIMIT ?2
+            "#,
+        )?;
+
+        let root = root.map(|root| root.to_string_lossy().into_owned());
+        let files = stmt
+            .query_map(params![root, limit as i64], |row| self.row_to_file_entry(&conn, row))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(files)
+    }
+
+    pub fn pending_content_count(&self, root: Option<&Path>) -> Result<usize> {
+        let conn = self.reader.get()?;
+        let root = root.map(|root| root.to_string_lossy().into_owned());
+        let count: i64 = conn
+            .prepare_cached("SELECT COUNT(*) FROM pending_content WHERE ?1 IS NULL OR root = ?1")?
+            .query_row(params![root], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    /// Clears the pending mark of files whose content has been read, forgetting roots that
+    /// have nothing left to enrich.
+    pub fn complete_pending_content(&self, file_ids: &[i64]) -> Result<()> {
+        let conn = self.writer.get()?;
+        let tx = conn.unchecked_transaction()?;
+
+        {
+            let mut delete = tx.prepare_cached("DELETE FROM pending_content WHERE file_id = ?1")?;
+            for file_id in file_ids {
+                delete.execute(params![file_id])?;
+            }
+        }
+        tx.execute(
+            "DELETE FROM enrichment_roots WHERE root NOT IN (SELECT root FROM pending_content)",
+            [],
+        )?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Drops everything a quick build of `root` left pending, once a full build has read it.
+    pub fn clear_pending_content(&self, root: &Path) -> Result<()> {
+        let conn = self.writer.get()?;
+        let tx = conn.unchecked_transaction()?;
+        let root = root.to_string_lossy();
+
+        tx.execute("DELETE FROM pending_content WHERE root = ?1", params![root])?;
+        tx.execute("DELETE FROM enrichment_roots WHERE root = ?1", params![root])?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Flags the files in `changed` as having changed while their content was read, and clears
+    /// the flag of the files in `consistent`, which were read cleanly since.
+    pub fn mark_inconsistent(&self, changed: &[i64], consistent: &[i64]) -> Result<()> {
+        if changed.is_empty() && consistent.is_empty() {
+            return Ok(());
+        }
+
+        let conn = self.writer.get()?;
+        let tx = conn.unchecked_transaction()?;
+        {
+            let now = Utc::now().timestamp();
+            let mut insert = tx.prepare_cached(
+                "INSERT OR REPLACE INTO inconsistent_files (file_id, detected_at) VALUES (?1, ?2)",
+            )?;
+            for file_id in changed {
+                insert.execute(params![file_id, now])?;
+            }
+            let mut delete =
+                tx.prepare_cached("DELETE FROM inconsistent_files WHERE file_id = ?1")?;
+            for file_id in consistent {
+                delete.execute(params![file_id])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The files flagged by [`mark_inconsistent`](Self::mark_inconsistent), in path order.
+    pub fn inconsistent_files(&self) -> Result<Vec<FileEntry>> {
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(
+            r#"
+            SELECT f.id, f.path, f.name, f.extension, f.size, f.created_at, f.modified_at,
+                   f.accessed_at, f.is_directory, f.is_hidden, f.is_symlink, f.parent_path,
+                   f.mime_type, f.file_hash, f.indexed_at, f.last_verified, f.parent_id
+            FROM inconsistent_files i JOIN files f ON f.id = i.file_id
+            "#,
+        )?;
+
+        let mut files = stmt
+            .query_map([], |row| self.row_to_file_entry(&conn, row))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(files)
+    }
+
+    /// Roots with files still waiting for enrichment. Files deleted while pending leave the
+    /// queue without being counted as enriched.
+    pub fn enrichment_progress(&self) -> Result<Vec<EnrichmentProgress>> {
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(
+            r#"
+            SELECT r.root, r.total, COUNT(p.file_id) AS pending
+            FROM enrichment_roots r JOIN pending_content p ON p.root = r.root
+            GROUP BY r.root ORDER BY r.root
+            "#,
+        )?;
+
+        let progress = stmt
+            .query_map([], |row| {
+                Ok(EnrichmentProgress {
+                    root: PathBuf::from(row.get::<_, String>(0)?),
+                    total: row.get::<_, i64>(1)? as usize,
+                    pending: row.get::<_, i64>(2)? as usize,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(progress)
+    }
+
+    pub fn add_exclusion_rule(&self, rule: &ExclusionRule) -> Result<i64> {
+        let conn = self.writer.get()?;
+
+        let rule_type = match rule.rule_type {
+            ExclusionRuleType::Glob => "glob",
+            ExclusionRuleType::Regex => "regex",
+            ExclusionRuleType::Path => "path",
+            ExclusionRuleType::Include => "include",
+        };
+
+        conn.execute(
+            "INSERT INTO exclusion_rules (pattern, rule_type, created_at) VALUES (?1, ?2, ?3)",
+            params![rule.pattern, rule_type, Utc::now().timestamp()],
+        )?;
+
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn get_exclusion_rules(&self) -> Result<Vec<ExclusionRule>> {
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare("SELECT pattern, rule_type FROM exclusion_rules")?;
+
+        let rules = stmt
+            .query_map([], |row| {
+                let pattern: String = row.get(0)?;
+                let rule_type_str: String = row.get(1)?;
+                let rule_type = match rule_type_str.as_str() {
+                    "glob" => ExclusionRuleType::Glob,
+                    "regex" => ExclusionRuleType::Regex,
+                    "path" => ExclusionRuleType::Path,
+                    "include" => ExclusionRuleType::Include,
+                    _ => ExclusionRuleType::Glob,
+                };
+
+                Ok(ExclusionRule { pattern, rule_type })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(rules)
+    }
+
+    /// Records `lease` as the index's write lease unless another holder's lease is still
+    /// live, i.e. heartbeated within `stale_after`; `steal` takes over regardless. Returns
+    /// the live lease that blocked it, or `None` once `lease` is recorded.
+    pub fn try_acquire_write_lease(
+        &self,
+        lease: &WriteLease,
+        stale_after: chrono::Duration,
+        steal: bool,
+    ) -> Result<Option<WriteLease>> {
+        let mut conn = self.writer.get()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        if let Some(current) = Self::read_write_lease(&tx)? {
+            let blocked = current.holder_id != lease.holder_id
+                && !current.is_stale(stale_after, lease.heartbeat_at)
+                && !steal;
+            if blocked {
+                return Ok(Some(current));
+            }
+            if current.holder_id != lease.holder_id {
+                log::warn!(
+                    "Taking over the write lease of pid {} on {}",
+                    current.pid,
+                    current.hostname
+                );
+            }
+        }
+
+        Self::write_write_lease(&tx, lease)?;
+        tx.commit()?;
+        Ok(None)
+    }
+
+    /// Moves the heartbeat of `holder_id`'s lease to `now`. Returns `false` when the lease
+    /// is no longer theirs, e.g. because it went stale and was taken over.
+    pub fn heartbeat_write_lease(&self, holder_id: &str, now: DateTime<Utc>) -> Result<bool> {
+        let mut conn = self.writer.get()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let Some(mut lease) = Self::read_write_lease(&tx)? else {
+            return Ok(false);
+        };
+        if lease.holder_id != holder_id {
+            return Ok(false);
+        }
+        lease.heartbeat_at = now;
+        Self::write_write_lease(&tx, &lease)?;
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// Drops the write lease if `holder_id` still holds it.
+    pub fn release_write_lease(&self, holder_id: &str) -> Result<bool> {
+        let mut conn = self.writer.get()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let held = Self::read_write_lease(&tx)?.is_some_and(|lease| lease.holder_id == holder_id);
+        if held {
+            Settings::new(&tx).remove(keys::WRITE_LEASE)?;
+            tx.commit()?;
+        }
+        Ok(held)
+    }
+
+    pub fn write_lease(&self) -> Result<Option<WriteLease>> {
+        let conn = self.reader.get()?;
+        Self::read_write_lease(&conn)
+    }
+
+    fn read_write_lease(conn: &Connection) -> Result<Option<WriteLease>> {
+        Settings::new(conn).get_json(keys::WRITE_LEASE)
+    }
+
+    fn write_write_lease(conn: &Connection, lease: &WriteLease) -> Result<()> {
+        Settings::new(conn).set_json(keys::WRITE_LEASE, lease)
+    }
+
+    /// Every setting stored for this index, ordered by key.
+    pub fn settings(&self) -> Result<Vec<Setting>> {
+        let conn = self.reader.get()?;
+        Settings::new(&conn).all()
+    }
+
+    pub fn setting(&self, key: &str) -> Result<Option<Setting>> {
+        let conn = self.reader.get()?;
+        Settings::new(&conn).get(key)
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<()> {
+        let conn = self.writer.get()?;
+        Settings::new(&conn).set_str(key, value)
+    }
+
+    pub fn maintenance_last_run(&self, task: MaintenanceTask) -> Result<Option<DateTime<Utc>>> {
+        let conn = self.reader.get()?;
+        let timestamp = Settings::new(&conn).get_i64(&keys::maintenance_last_run(task))?;
+        Ok(timestamp.and_then(|timestamp| Utc.timestamp_opt(timestamp, 0).single()))
+    }
+
+    pub fn record_maintenance_run(&self, task: MaintenanceTask, at: DateTime<Utc>) -> Result<()> {
+        let conn = self.writer.get()?;
+        Settings::new(&conn).set_i64(&keys::maintenance_last_run(task), at.timestamp())
+    }
+
+    pub fn record_indexed_root(&self, root: &IndexedRoot) -> Result<()> {
+        let conn = self.writer.get()?;
+        let options = serde_json::to_string(&root.options)
+            .map_err(|e| SearchError::Parse(e.to_string()))?;
+
+        conn.prepare_cached(
+            "INSERT INTO indexed_roots (path, options, file_count, indexed_at)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(path) DO UPDATE SET
+                options = excluded.options,
+                file_count = excluded.file_count,
+                indexed_at = excluded.indexed_at",
+        )?
+        .execute(params![
+            root.path.to_string_lossy(),
+            options,
+            root.file_count as i64,
+            root.indexed_at.timestamp(),
+        ])?;
+
+        Ok(())
+    }
+
+    pub fn get_indexed_roots(&self) -> Result<Vec<IndexedRoot>> {
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT path, options, file_count, indexed_at FROM indexed_roots ORDER BY path",
+        )?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, i64>(3)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        rows.into_iter()
+            .map(|(path, options, file_count, indexed_at)| {
+                let options = serde_json::from_str(&options).map_err(|e| {
+                    SearchError::IndexCorrupted(format!(
+                        "Invalid options recorded for root {}: {}",
+                        path, e
+                    ))
+                })?;
+                Ok(IndexedRoot {
+                    path: PathBuf::from(path),
+                    options,
+                    file_count: file_count as usize,
+                    indexed_at: Utc.timestamp_opt(indexed_at, 0).single().unwrap_or_else(Utc::now),
+                })
+            })
+            .collect()
+    }
+
+    pub fn record_timestamp_adjustment(&self, adjustment: &TimestampAdjustment) -> Result<()> {
+        let conn = self.writer.get()?;
+        conn.prepare_cached(
+            "INSERT INTO timestamp_adjustments (root, filesystem, utc_offset_secs, recorded_at)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(root) DO UPDATE SET
+                filesystem = excluded.filesystem,
+                utc_offset_secs = excluded.utc_offset_secs,
+                recorded_at = excluded.recorded_at",
+        )?
+        .execute(params![
+            adjustment.root.to_string_lossy(),
+            adjustment.filesystem,
+            adjustment.utc_offset_secs,
+            adjustment.recorded_at.timestamp(),
+        ])?;
+
+        Ok(())
+    }
+
+    pub fn clear_timestamp_adjustment(&self, root: &Path) -> Result<()> {
+        let conn = self.writer.get()?;
+        conn.prepare_cached("DELETE FROM timestamp_adjustments WHERE root = ?1")?
+            .execute(params![root.to_string_lossy()])?;
+        Ok(())
+    }
+
+    pub fn get_timestamp_adjustments(&self) -> Result<Vec<TimestampAdjustment>> {
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT root, filesystem, utc_offset_secs, recorded_at
+             FROM timestamp_adjustments ORDER BY root",
+        )?;
+
+        let adjustments = stmt
+            .query_map([], |row| {
+                Ok(TimestampAdjustment {
+                    root: PathBuf::from(row.get::<_, String>(0)?),
+                    filesystem: row.get(1)?,
+                    utc_offset_secs: row.get(2)?,
+                    recorded_at: Utc
+                        .timestamp_opt(row.get(3)?, 0)
+                        .single()
+                        .unwrap_or_else(Utc::now),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(adjustments)
+    }
+
+    /// Creates or replaces the named search, keeping its creation time and run history.
+    pub fn save_search(&self, search: &SavedSearch) -> Result<()> {
+        let conn = self.writer.get()?;
+        conn.prepare_cached(
+            "INSERT INTO saved_searches (name, query, sort, created_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(name) DO UPDATE SET query = excluded.query, sort = excluded.sort",
+        )?
+        .execute(params![
+            search.name,
+            search.query,
+            search.sort.map(|sort| sort.as_str()),
+            search.created_at.timestamp(),
+        ])?;
+        Ok(())
+    }
+
+    pub fn get_saved_search(&self, name: &str) -> Result<Option<SavedSearch>> {
+        let conn = self.reader.get()?;
+        let search = conn
+            .prepare_cached(
+                "SELECT name, query, sort, created_at, last_run_at, last_result_count
+                 FROM saved_searches WHERE name = ?1",
+            )?
+            .query_row(params![name], Self::row_to_saved_search)
+            .optional()?;
+        Ok(search)
+    }
+
+    pub fn list_saved_searches(&self) -> Result<Vec<SavedSearch>> {
+        let conn = self.reader.get()?;
+        let searches = conn
+            .prepare_cached(
+                "SELECT name, query, sort, created_at, last_run_at, last_result_count
+                 FROM saved_searches ORDER BY name",
+            )?
+            .query_map([], Self::row_to_saved_search)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(searches)
+    }
+
+    /// Returns whether a search with that name existed.
+    pub fn delete_saved_search(&self, name: &str) -> Result<bool> {
+        let conn = self.writer.get()?;
+        let deleted = conn
+            .prepare_cached("DELETE FROM saved_searches WHERE name = ?1")?
+            .execute(params![name])?;
+        Ok(deleted > 0)
+    }
+
+    pub fn record_saved_search_run(
+        &self,
+        name: &str,
+        result_count: usize,
+        run_at: DateTime<Utc>,
+    ) -> Result<()> {
+        let conn = self.writer.get()?;
+        conn.prepare_cached(
+            "UPDATE saved_searches SET last_run_at = ?2, last_result_count = ?3 WHERE name = ?1",
+        )?
+        .execute(params![name, run_at.timestamp(), result_count as i64])?;
+        Ok(())
+    }
+
+    /// Tags every indexed file in `file_ids` with `tag`. Ids missing from the index are
+    /// skipped; returns how many files gained the tag.
+    pub fn tag_files(&self, file_ids: &[i64], tag: &str) -> Result<usize> {
+        let conn = self.writer.get()?;
+        let tx = conn.unchecked_transaction()?;
+        let mut tagged = 0;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT OR IGNORE INTO user_tags (file_id, tag, created_at)
+                 SELECT id, ?2, ?3 FROM files WHERE id = ?1",
+            )?;
+            let now = Utc::now().timestamp();
+            for id in file_ids {
+                tagged += stmt.execute(params![id, tag, now])?;
+            }
+        }
+        tx.commit()?;
+        Ok(tagged)
+    }
+
+    /// Returns how many of `file_ids` had the tag.
+    pub fn untag_files(&self, file_ids: &[i64], tag: &str) -> Result<usize> {
+        let conn = self.writer.get()?;
+        let tx = conn.unchecked_transaction()?;
+        let mut untagged = 0;
+        {
+            let mut stmt =
+                tx.prepare_cached("DELETE FROM user_tags WHERE file_id = ?1 AND tag = ?2")?;
+            for id in file_ids {
+                untagged += stmt.execute(params![id, tag])?;
+            }
+        }
+        tx.commit()?;
+        Ok(untagged)
+    }
+
+    /// Every tag in use with the number of files carrying it, most used first.
+    pub fn list_tags(&self) -> Result<Vec<(String, usize)>> {
+        let conn = self.reader.get()?;
+        let tags = conn
+            .prepare_cached(
+                "SELECT tag, COUNT(*) AS count FROM user_tags
+                 GROUP BY tag ORDER BY count DESC, tag",
+            )?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(tags)
+    }
+
+    /// Files carrying any of `tags`.
+    pub fn find_by_tags(&self, tags: &[String], limit: usize) -> Result<Vec<FileEntry>> {
+        if tags.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let placeholders = vec!["?"; tags.len()].join(", ");
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(&format!(
+            r#"
+            SELECT id, path, name, extension, size, created_at, modified_at, accessed_at,
+                   is_directory, is_hidden, is_symlink, parent_path, mime_type, file_hash,
+                   indexed_at, last_verified, parent_id
+            FROM files WHERE id IN (SELECT file_id FROM user_tags WHERE tag IN ({}))
+            ORDER BY id LIMIT ?
+            "#,
+            placeholders
+        ))?;
+
+        let params = tags
+            .iter()
+            .map(|tag| tag as &dyn rusqlite::ToSql)
+            .chain(std::iter::once(&limit as &dyn rusqlite::ToSql));
+        let files = stmt
+            .query_map(params_from_iter(params), |row| self.row_to_file_entry(&conn, row))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(files)
+    }
+
+    /// Stores pixel sizes for image files, replacing any recorded earlier.
+    pub fn set_image_dimensions(&self, dimensions: &[(i64, ImageDimensions)]) -> Result<()> {
+        if dimensions.is_empty() {
+            return Ok(());
+        }
+
+        let conn = self.writer.get()?;
+        let tx = conn.unchecked_transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT OR REPLACE INTO image_dimensions (file_id, width, height)
+                 VALUES (?1, ?2, ?3)",
+            )?;
+            for (file_id, size) in dimensions {
+                stmt.execute(params![file_id, size.width, size.height])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn get_image_dimensions(&self, file_id: i64) -> Result<Option<ImageDimensions>> {
+        let conn = self.reader.get()?;
+        let dimensions = conn
+            .prepare_cached("SELECT width, height FROM image_dimensions WHERE file_id = ?1")?
+            .query_row(params![file_id], |row| {
+                Ok(ImageDimensions {
+                    width: row.get(0)?,
+                    height: row.get(1)?,
+                })
+            })
+            .optional()?;
+
+        Ok(dimensions)
+    }
+
+    /// Records what was found sniffing files for a shebang; `None` forgets an earlier script
+    /// whose shebang is gone.
+    pub fn set_scripts(&self, scripts: &[(i64, Option<ScriptInfo>)]) -> Result<()> {
+        if scripts.is_empty() {
+            return Ok(());
+        }
+
+        let conn = self.writer.get()?;
+        let tx = conn.unchecked_transaction()?;
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT OR REPLACE INTO scripts (file_id, interpreter, language, is_executable)
+                 VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            let mut delete = tx.prepare_cached("DELETE FROM scripts WHERE file_id = ?1")?;
+            for (file_id, script) in scripts {
+                match script {
+                    Some(script) => insert.execute(params![
+                        file_id,
+                        script.interpreter,
+                        script.language,
+                        script.is_executable
+                    ])?,
+                    None => delete.execute(params![file_id])?,
+                };
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Scripts among `ids`, keyed by file id.
+    pub fn scripts_for(&self, ids: &[i64]) -> Result<HashMap<i64, ScriptInfo>> {
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT interpreter, language, is_executable FROM scripts WHERE file_id = ?1",
+        )?;
+        let mut scripts = HashMap::new();
+        for &id in ids {
+            let script = stmt
+                .query_row(params![id], |row| {
+                    Ok(ScriptInfo {
+                        interpreter: row.get(0)?,
+                        language: row.get(1)?,
+                        is_executable: row.get(2)?,
+                    })
+                })
+                .optional()?;
+            if let Some(script) = script {
+                scripts.insert(id, script);
+            }
+        }
 
-        let rule_type = match rule.rule_type {
-            ExclusionRuleType::Glob => "glob",
-            ExclusionRuleType::Regex => "regex",
-            ExclusionRuleType::Path => "path",
+        Ok(scripts)
+    }
+
+    /// Records where store aliases point; `None` forgets the target of an entry that is no
+    /// longer an alias. Aliases are indexed without content, so any content they had is
+    /// released.
+    pub fn set_link_targets(&self, targets: &[(i64, Option<PathBuf>)]) -> Result<()> {
+        if targets.is_empty() {
+            return Ok(());
+        }
+
+        let conn = self.writer.get()?;
+        let tx = conn.unchecked_transaction()?;
+        let mut change = StatsCounters::default();
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT OR REPLACE INTO link_targets (file_id, target_path) VALUES (?1, ?2)",
+            )?;
+            let mut delete = tx.prepare_cached("DELETE FROM link_targets WHERE file_id = ?1")?;
+            for (file_id, target) in targets {
+                let before = StatsCounters::of_row(&tx, *file_id)?.unwrap_or_default();
+                match target {
+                    Some(target) => {
+                        insert.execute(params![file_id, target.to_string_lossy()])?;
+                        if Self::release_content(&tx, *file_id)? {
+                            change += StatsCounters::content(-1);
+                        }
+                    }
+                    None => {
+                        delete.execute(params![file_id])?;
+                    }
+                }
+                change += StatsCounters::of_row(&tx, *file_id)?.unwrap_or_default() - before;
+            }
+        }
+        change.apply(&tx)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Link targets of the store aliases among `ids`, keyed by file id.
+    pub fn link_targets_for(&self, ids: &[i64]) -> Result<HashMap<i64, PathBuf>> {
+        let conn = self.reader.get()?;
+        let mut stmt =
+            conn.prepare_cached("SELECT target_path FROM link_targets WHERE file_id = ?1")?;
+        let mut targets = HashMap::new();
+        for &id in ids {
+            let target: Option<String> = stmt
+                .query_row(params![id], |row| row.get(0))
+                .optional()?;
+            if let Some(target) = target {
+                targets.insert(id, PathBuf::from(target));
+            }
+        }
+
+        Ok(targets)
+    }
+
+    /// Records the ownership of entries; `None` forgets what an entry had, e.g. when it can
+    /// no longer be read.
+    pub fn set_permissions(&self, permissions: &[(i64, Option<FilePermissions>)]) -> Result<()> {
+        if permissions.is_empty() {
+            return Ok(());
+        }
+
+        let conn = self.writer.get()?;
+        let tx = conn.unchecked_transaction()?;
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT OR REPLACE INTO file_permissions
+                 (file_id, uid, gid, mode, parent_uid, parent_gid, parent_mode)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            let mut delete =
+                tx.prepare_cached("DELETE FROM file_permissions WHERE file_id = ?1")?;
+            for (file_id, permissions) in permissions {
+                match permissions {
+                    Some(FilePermissions { entry, parent }) => insert.execute(params![
+                        file_id,
+                        entry.uid,
+                        entry.gid,
+                        entry.mode,
+                        parent.map(|parent| parent.uid),
+                        parent.map(|parent| parent.gid),
+                        parent.map(|parent| parent.mode),
+                    ])?,
+                    None => delete.execute(params![file_id])?,
+                };
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Recorded ownership of the entries among `ids`, keyed by file id.
+    pub fn permissions_for(&self, ids: &[i64]) -> Result<HashMap<i64, FilePermissions>> {
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT uid, gid, mode, parent_uid, parent_gid, parent_mode
+             FROM file_permissions WHERE file_id = ?1",
+        )?;
+        let mut permissions = HashMap::new();
+        for &id in ids {
+            let found = stmt
+                .query_row(params![id], |row| {
+                    let parent = match (row.get(3)?, row.get(4)?, row.get(5)?) {
+                        (Some(uid), Some(gid), Some(mode)) => Some(Ownership { uid, gid, mode }),
+                        _ => None,
+                    };
+                    Ok(FilePermissions {
+                        entry: Ownership {
+                            uid: row.get(0)?,
+                            gid: row.get(1)?,
+                            mode: row.get(2)?,
+                        },
+                        parent,
+                    })
+                })
+                .optional()?;
+            if let Some(found) = found {
+                permissions.insert(id, found);
+            }
+        }
+
+        Ok(permissions)
+    }
+
+    pub fn has_permissions(&self) -> Result<bool> {
+        let conn = self.reader.get()?;
+        Ok(conn.query_row("SELECT EXISTS (SELECT 1 FROM file_permissions)", [], |row| {
+            row.get(0)
+        })?)
+    }
+
+    /// Ids of the scripts in any of `languages`, or of every script when it is empty.
+    pub fn script_ids(&self, languages: &[String]) -> Result<HashSet<i64>> {
+        let conn = self.reader.get()?;
+        let ids = if languages.is_empty() {
+            conn.prepare_cached("SELECT file_id FROM scripts")?
+                .query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<HashSet<i64>>>()?
+        } else {
+            let placeholders = vec!["?"; languages.len()].join(", ");
+            conn.prepare(&format!(
+                "SELECT file_id FROM scripts WHERE language IN ({})",
+                placeholders
+            ))?
+            .query_map(params_from_iter(languages), |row| row.get(0))?
+            .collect::<rusqlite::Result<HashSet<i64>>>()?
         };
 
-        conn.execute(
-            "INSERT INTO exclusion_rules (pattern, rule_type, created_at) VALUES (?1, ?2, ?3)",
-            params![rule.pattern, rule_type, Utc::now().timestamp()],
+        Ok(ids)
+    }
+
+    /// Replaces the document fields stored for each file; an empty list forgets fields whose
+    /// source no longer has them.
+    pub fn set_document_fields(&self, fields: &[(i64, Vec<DocumentField>)]) -> Result<()> {
+        if fields.is_empty() {
+            return Ok(());
+        }
+
+        let conn = self.writer.get()?;
+        let tx = conn.unchecked_transaction()?;
+        {
+            let mut delete = tx.prepare_cached("DELETE FROM document_fields WHERE file_id = ?1")?;
+            let mut insert = tx.prepare_cached(
+                "INSERT OR REPLACE INTO document_fields (file_id, name, value, value_folded)
+                 VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for (file_id, file_fields) in fields {
+                delete.execute(params![file_id])?;
+                for field in file_fields {
+                    insert.execute(params![
+                        file_id,
+                        field.name,
+                        field.value,
+                        fold_case(&field.value)
+                    ])?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Document fields of the files among `ids` that have any, keyed by file id.
+    pub fn document_fields_for(&self, ids: &[i64]) -> Result<HashMap<i64, Vec<DocumentField>>> {
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT name, value FROM document_fields WHERE file_id = ?1 ORDER BY rowid",
         )?;
+        let mut fields = HashMap::new();
+        for &id in ids {
+            let file_fields = stmt
+                .query_map(params![id], |row| {
+                    Ok(DocumentField {
+                        name: row.get(0)?,
+                        value: row.get(1)?,
+                    })
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            if !file_fields.is_empty() {
+                fields.insert(id, file_fields);
+            }
+        }
 
-        Ok(conn.last_insert_rowid())
+        Ok(fields)
     }
 
-    pub fn get_exclusion_rules(&self) -> Result<Vec<ExclusionRule>> {
-        let conn = self.pool.get()?;
-        let mut stmt = conn.prepare("SELECT pattern, rule_type FROM exclusion_rules")?;
+    /// Files whose document fields satisfy every filter in `filters`.
+    pub fn find_by_document_fields(
+        &self,
+        filters: &[FieldFilter],
+        limit: usize,
+    ) -> Result<Vec<FileEntry>> {
+        let (condition, mut values) = Self::field_condition(filters);
+        values.push(Value::Integer(limit.min(i64::MAX as usize) as i64));
 
-        let rules = stmt
-            .query_map([], |row| {
-                let pattern: String = row.get(0)?;
-                let rule_type_str: String = row.get(1)?;
-                let rule_type = match rule_type_str.as_str() {
-                    "glob" => ExclusionRuleType::Glob,
-                    "regex" => ExclusionRuleType::Regex,
-                    "path" => ExclusionRuleType::Path,
-                    _ => ExclusionRuleType::Glob,
-                };
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(&format!(
+            r#"
+            SELECT id, path, name, extension, size, created_at, modified_at, accessed_at,
+                   is_directory, is_hidden, is_symlink, parent_path, mime_type, file_hash,
+                   indexed_at, last_verified, parent_id
+            FROM files WHERE {}
+            ORDER BY id LIMIT ?
+            "#,
+            condition
+        ))?;
+        let files = stmt
+            .query_map(params_from_iter(values), |row| self.row_to_file_entry(&conn, row))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
 
-                Ok(ExclusionRule { pattern, rule_type })
-            })?
+        Ok(files)
+    }
+
+    /// Ids of the files whose document fields satisfy every filter in `filters`.
+    pub fn document_field_ids(&self, filters: &[FieldFilter]) -> Result<HashSet<i64>> {
+        let (condition, values) = Self::field_condition(filters);
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(&format!("SELECT id FROM files WHERE {}", condition))?;
+        let ids = stmt
+            .query_map(params_from_iter(values), |row| row.get(0))?
+            .collect::<rusqlite::Result<HashSet<i64>>>()?;
+
+        Ok(ids)
+    }
+
+    // One subquery per filter, since each names a different row of the same file.
+    fn field_condition(filters: &[FieldFilter]) -> (String, Vec<Value>) {
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        for filter in filters {
+            conditions.push(
+                "id IN (SELECT file_id FROM document_fields
+                        WHERE name = ? AND value_folded LIKE ? ESCAPE '\\')"
+                    .to_string(),
+            );
+            values.push(Value::Text(filter.name.clone()));
+            let folded = fold_case(&filter.value);
+            values.push(Value::Text(format!("%{}%", escape_like(&folded))));
+        }
+
+        if conditions.is_empty() {
+            return ("1".to_string(), values);
+        }
+        (conditions.join(" AND "), values)
+    }
+
+    /// Files whose stored image dimensions satisfy `filter`.
+    pub fn find_by_dimensions(
+        &self,
+        filter: &DimensionFilter,
+        limit: usize,
+    ) -> Result<Vec<FileEntry>> {
+        let (condition, mut values) = Self::dimension_condition(filter);
+        values.push(limit.min(i64::MAX as usize) as i64);
+
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(&format!(
+            r#"
+            SELECT id, path, name, extension, size, created_at, modified_at, accessed_at,
+                   is_directory, is_hidden, is_symlink, parent_path, mime_type, file_hash,
+                   indexed_at, last_verified, parent_id
+            FROM files WHERE id IN (SELECT file_id FROM image_dimensions WHERE {})
+            ORDER BY id LIMIT ?
+            "#,
+            condition
+        ))?;
+        let files = stmt
+            .query_map(params_from_iter(values), |row| self.row_to_file_entry(&conn, row))?
             .collect::<rusqlite::Result<Vec<_>>>()?;
 
-        Ok(rules)
+        Ok(files)
+    }
+
+    /// Ids of the files whose stored image dimensions satisfy `filter`.
+    pub fn image_ids_matching(&self, filter: &DimensionFilter) -> Result<HashSet<i64>> {
+        let (condition, values) = Self::dimension_condition(filter);
+        let conn = self.reader.get()?;
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT file_id FROM image_dimensions WHERE {}",
+            condition
+        ))?;
+        let ids = stmt
+            .query_map(params_from_iter(values), |row| row.get(0))?
+            .collect::<rusqlite::Result<HashSet<i64>>>()?;
+
+        Ok(ids)
+    }
+
+    fn dimension_condition(filter: &DimensionFilter) -> (String, Vec<i64>) {
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        for (column, bound) in [("width", filter.width), ("height", filter.height)] {
+            match bound {
+                Some(DimensionBound::Exact(exact)) => {
+                    conditions.push(format!("{} = ?", column));
+                    values.push(i64::from(exact));
+                }
+                Some(DimensionBound::Range(min, max)) => {
+                    conditions.push(format!("{} BETWEEN ? AND ?", column));
+                    values.extend([i64::from(min), i64::from(max)]);
+                }
+                Some(DimensionBound::GreaterThan(min)) => {
+                    conditions.push(format!("{} > ?", column));
+                    values.push(i64::from(min));
+                }
+                Some(DimensionBound::LessThan(max)) => {
+                    conditions.push(format!("{} < ?", column));
+                    values.push(i64::from(max));
+                }
+                None => {}
+            }
+        }
+
+        if conditions.is_empty() {
+            return ("1".to_string(), values);
+        }
+        (conditions.join(" AND "), values)
     }
 
     pub fn log_access(&self, file_id: i64) -> Result<()> {
-        let conn = self.pool.get()?;
-        conn.execute(
-            "INSERT INTO access_log (file_id, accessed_at) VALUES (?1, ?2)",
-            params![file_id, Utc::now().timestamp()],
-        )?;
+        let conn = self.writer.get()?;
+        let mut stmt =
+            conn.prepare_cached("INSERT INTO access_log (file_id, accessed_at) VALUES (?1, ?2)")?;
+        stmt.execute(params![file_id, Utc::now().timestamp()])?;
         Ok(())
     }
 
+    pub fn has_full_text_index(&self) -> Result<bool> {
+        let conn = self.reader.get()?;
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'files_fts'",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    pub fn is_read_only(&self) -> Result<bool> {
+        let conn = self.writer.get()?;
+        Ok(conn.is_readonly(rusqlite::DatabaseName::Main)?)
+    }
+
     pub fn get_stats(&self) -> Result<IndexStats> {
-        let conn = self.pool.get()?;
+        let conn = self.reader.get()?;
+
+        // Indexes opened read-only before the counters existed fall back to counting.
+        let counters = match StatsCounters::load(&conn)? {
+            Some(counters) => counters,
+            None => StatsCounters::count(&conn)?,
+        };
+
+        let store_aliases: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM link_targets",
+            [],
+            |row| row.get(0),
+        )?;
+
+        let last_update_ts: Option<i64> = conn
+            .query_row(
+                "SELECT MAX(indexed_at) FROM files",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+
+        let last_update = last_update_ts
+            .and_then(|ts| Utc.timestamp_opt(ts, 0).single())
+            .unwrap_or_else(Utc::now);
+
+        let index_size = std::fs::metadata(
+            conn.path().ok_or_else(|| {
+                SearchError::Configuration("Cannot get database path".to_string())
+            })?,
+        )
+        .map(|m| m.len())
+        .unwrap_or(0);
+
+        Ok(IndexStats {
+            total_files: counters.files.max(0) as usize,
+            total_directories: counters.directories.max(0) as usize,
+            total_size: counters.total_size.max(0) as u64,
+            indexed_files: counters.content_indexed.max(0) as usize,
+            store_aliases: store_aliases as usize,
+            last_update,
+            index_size,
+            quota: None,
+            enrichment: Vec::new(),
+            write_lease: None,
+            maintenance: Vec::new(),
+            name_index: None,
+        })
+    }
+
+    /// Rebuilds the statistics counters from a full scan of the index and returns them.
+    pub fn recompute_stats(&self) -> Result<StatsCounters> {
+        let conn = self.writer.get()?;
+        let tx = conn.unchecked_transaction()?;
+        let counters = StatsCounters::count(&tx)?;
+        counters.store(&tx)?;
+        tx.commit()?;
+        Ok(counters)
+    }
+
+    /// Compares the stored statistics counters with a recount, read in one transaction so
+    /// concurrent writes can't make them disagree. `None` when they match.
+    pub fn stats_drift(&self) -> Result<Option<StatsDrift>> {
+        let conn = self.reader.get()?;
+        let tx = conn.unchecked_transaction()?;
+        let stored = StatsCounters::load(&tx)?.unwrap_or_default();
+        let counted = StatsCounters::count(&tx)?;
+        Ok((stored != counted).then_some(StatsDrift { stored, counted }))
+    }
+
+    pub fn clear_all(&self) -> Result<()> {
+        let conn = self.writer.get()?;
+        let tx = conn.unchecked_transaction()?;
+
+        tx.execute(
+            &format!(
+                r#"
+                {}
+                INSERT OR REPLACE INTO deleted_files (path, deleted_at)
+                SELECT {}, ?2 FROM files f LEFT JOIN dir_paths dp ON dp.id = f.parent_id
+                "#,
+                DIR_PATHS_CTE, FULL_PATH_SQL
+            ),
+            params![MAIN_SEPARATOR_STR, Utc::now().timestamp()],
+        )?;
+        tx.execute("DELETE FROM files", [])?;
+        tx.execute("DELETE FROM path_dirs", [])?;
+        tx.execute("DELETE FROM file_contents", [])?;
+        tx.execute("DELETE FROM content_blobs", [])?;
+        tx.execute("DELETE FROM files_fts", [])?;
+        tx.execute("DELETE FROM access_log", [])?;
+        tx.execute("DELETE FROM search_history", [])?;
+        tx.execute("DELETE FROM indexed_roots", [])?;
+        tx.execute("DELETE FROM timestamp_adjustments", [])?;
+        tx.execute("DELETE FROM pending_content", [])?;
+        tx.execute("DELETE FROM enrichment_roots", [])?;
+        tx.execute("DELETE FROM inconsistent_files", [])?;
+        StatsCounters::default().store(&tx)?;
+
+        tx.commit()?;
+        self.dirs.clear();
+        self.misses.clear();
+        Ok(())
+    }
+
+    /// Converts a legacy index to the compact path layout and returns how many entries now
+    /// reference their parent directory. Row ids are kept, so content and access history stay
+    /// attached. Run `vacuum` afterwards to return the freed pages to the filesystem.
+    pub fn convert_to_compact_paths(&self) -> Result<usize> {
+        if self.path_layout() == PathLayout::Compact {
+            return Ok(0);
+        }
+
+        let conn = self.writer.get()?;
+        // Dropping `files` would cascade into file_contents, access_log and user_tags, so
+        // foreign keys stay off while the table is rebuilt. The pragma is a no-op inside a
+        // transaction.
+        conn.execute_batch("PRAGMA foreign_keys = OFF")?;
+        let converted = self.rebuild_files_compact(&conn);
+        conn.execute_batch("PRAGMA foreign_keys = ON")?;
+        let (converted, created) = converted?;
+
+        for (path, id) in created {
+            self.dirs.insert(id, path);
+        }
+        *self.layout.write() = PathLayout::Compact;
+        self.misses.clear();
+        Ok(converted)
+    }
 
-        let total_files: i64 = conn.query_row(
-            "SELECT COUNT(*) FROM files WHERE is_directory = 0",
+    fn rebuild_files_compact(&self, conn: &Connection) -> Result<(usize, HashMap<PathBuf, i64>)> {
+        let tx = conn.unchecked_transaction()?;
+
+        const COLUMNS: &str = "id, path, name, name_normalized, extension, size, created_at, \
+            modified_at, accessed_at, is_directory, is_hidden, is_symlink, parent_path, \
+            mime_type, file_hash, indexed_at, last_verified, parent_id";
+        tx.execute(
+            &schema::CREATE_COMPACT_FILES_TABLE.replacen("files (", "files_compact (", 1),
+            [],
+        )?;
+        tx.execute(
+            &format!("INSERT INTO files_compact ({0}) SELECT {0} FROM files", COLUMNS),
+            [],
+        )?;
+
+        let mut created = HashMap::new();
+        let mut converted = 0;
+        let mut after_id = 0;
+        loop {
+            let rows = tx
+                .prepare_cached(
+                    "SELECT id, path, name FROM files_compact WHERE id > ?1 ORDER BY id LIMIT ?2",
+                )?
+                .query_map(params![after_id, COMPACT_BATCH_SIZE], |row| {
+                    let (id, path, name): (i64, String, String) =
+                        (row.get(0)?, row.get(1)?, row.get(2)?);
+                    Ok((id, path, name))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            let Some(&(last_id, _, _)) = rows.last() else {
+                break;
+            };
+            after_id = last_id;
+
+            for (id, path, name) in rows {
+                let path = PathBuf::from(path);
+                if let Some(parent) = compact_parent(&path, &name) {
+                    let parent_id = self.ensure_dir(&tx, parent, &mut created)?;
+                    tx.prepare_cached(
+                        r#"
+                        UPDATE files_compact SET path = NULL, parent_path = NULL, parent_id = ?1
+                        WHERE id = ?2
+                        "#,
+                    )?
+                    .execute(params![parent_id, id])?;
+                    converted += 1;
+                }
+            }
+        }
+
+        tx.execute("DROP TABLE files", [])?;
+        tx.execute("ALTER TABLE files_compact RENAME TO files", [])?;
+        for statement in schema::CREATE_COMPACT_FILES_INDEXES {
+            tx.execute(statement, [])?;
+        }
+        MigrationManager::create_name_generation(&tx)?;
+        MigrationManager::set_path_layout(&tx, PathLayout::Compact)?;
+        tx.commit()?;
+        Ok((converted, created))
+    }
+
+    /// Recreates `files_fts` with the configured tokenizer from the text already stored in
+    /// `content_blobs`, without reading any file. The new table is filled beside the live one
+    /// in batches and renamed over it at the end, so content searches keep working meanwhile;
+    /// content indexed or released during the rebuild is reconciled before the swap.
+    /// `progress` receives the blobs written so far and the total.
+    pub fn rebuild_fts(
+        &self,
+        batch_size: usize,
+        progress: impl Fn(usize, usize),
+    ) -> Result<FtsRebuildReport> {
+        let total: i64 = self.writer.get()?.query_row(
+            "SELECT COUNT(*) FROM content_blobs WHERE content_preview IS NOT NULL",
             [],
             |row| row.get(0),
         )?;
 
-        let total_directories: i64 = conn.query_row(
-            "SELECT COUNT(*) FROM files WHERE is_directory = 1",
+        let create = schema::create_fts_table_sql(FTS_REBUILD_TABLE, &self.fts_tokenizer);
+        self.writer
+            .get()?
+            .execute_batch(&format!("DROP TABLE IF EXISTS {}; {}", FTS_REBUILD_TABLE, create))
+            .map_err(|e| match e {
+                rusqlite::Error::SqliteFailure(_, Some(message)) => SearchError::Configuration(
+                    format!("FTS tokenizer '{}': {}", self.fts_tokenizer, message),
+                ),
+                other => other.into(),
+            })?;
+
+        let mut after_hash = String::new();
+        let mut written = 0;
+        loop {
+            // A connection per batch, returned before reporting progress, so indexing can
+            // write between batches.
+            let conn = self.writer.get()?;
+            let tx = conn.unchecked_transaction()?;
+            let rows = tx
+                .prepare_cached(
+                    r#"
+                    SELECT hash, content_preview FROM content_blobs
+                    WHERE hash > ?1 AND content_preview IS NOT NULL
+                    ORDER BY hash LIMIT ?2
+                    "#,
+                )?
+                .query_map(params![after_hash, batch_size.max(1) as i64], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            let Some((last_hash, _)) = rows.last() else {
+                break;
+            };
+            after_hash = last_hash.clone();
+
+            for (hash, content) in &rows {
+                self.insert_fts_chunks(&tx, FTS_REBUILD_TABLE, hash, content)?;
+            }
+            tx.commit()?;
+            drop(conn);
+
+            written += rows.len();
+            progress(written, total as usize);
+        }
+
+        let conn = self.writer.get()?;
+        let tx = conn.unchecked_transaction()?;
+        tx.execute(
+            &format!(
+                "DELETE FROM {0} WHERE content_hash NOT IN (SELECT hash FROM content_blobs)",
+                FTS_REBUILD_TABLE
+            ),
+            [],
+        )?;
+        let missing = tx
+            .prepare(&format!(
+                r#"
+                SELECT hash, content_preview FROM content_blobs
+                WHERE content_preview IS NOT NULL
+                  AND hash NOT IN (SELECT content_hash FROM {0})
+                "#,
+                FTS_REBUILD_TABLE
+            ))?
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for (hash, content) in &missing {
+            self.insert_fts_chunks(&tx, FTS_REBUILD_TABLE, hash, content)?;
+        }
+        tx.execute("DROP TABLE IF EXISTS files_fts", [])?;
+        tx.execute(&format!("ALTER TABLE {} RENAME TO files_fts", FTS_REBUILD_TABLE), [])?;
+
+        let blobs: i64 = tx.query_row(
+            "SELECT COUNT(DISTINCT content_hash) FROM files_fts",
             [],
             |row| row.get(0),
         )?;
+        let (files, with_record): (i64, i64) = tx.query_row(
+            r#"
+            SELECT COUNT(b.content_preview), COUNT(*) FROM file_contents fc
+            LEFT JOIN content_blobs b ON b.hash = fc.content_hash
+            "#,
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        tx.commit()?;
+
+        Ok(FtsRebuildReport {
+            blobs: blobs as usize,
+            files: files as usize,
+            skipped: (with_record - files) as usize,
+        })
+    }
+
+    /// Copies the index to `dest` with SQLite's online backup API, reporting copied and
+    /// total pages after each step.
+    ///
+    /// The copy is one consistent snapshot: a read transaction is held on the source for the
+    /// whole backup, which under WAL does not hold up writers. It is written beside `dest`
+    /// and renamed into place once complete, as a single file without a write-ahead log or
+    /// the source's write lease.
+    pub fn backup_to<F>(&self, dest: &Path, mut progress: F) -> Result<BackupReport>
+    where
+        F: FnMut(usize, usize),
+    {
+        if dest.exists() {
+            return Err(SearchError::io_at(
+                dest,
+                std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    "backup destination already exists",
+                ),
+            ));
+        }
+        let mut partial = dest.as_os_str().to_owned();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+
+        let src = self.reader.get()?;
+        src.execute_batch("BEGIN")?;
+        let copied = src
+            .query_row("SELECT COUNT(*) FROM sqlite_master", [], |_| Ok(()))
+            .map_err(SearchError::from)
+            .and_then(|()| {
+                let mut dst = Connection::open(&partial)?;
+                let pages = Self::copy_pages(&src, &mut dst, &mut progress)?;
+        self.misses.clear();
+                Settings::new(&dst).remove(keys::WRITE_LEASE)?;
+                dst.pragma_update(None, "journal_mode", "DELETE")?;
+                dst.close().map_err(|(_, e)| e)?;
+                Ok(pages)
+            });
+        let ended = src.execute_batch("COMMIT");
+
+        let copied = copied.and_then(|pages| {
+            ended?;
+            Ok(pages)
+        });
+        let pages = match copied {
+            Ok(pages) => pages,
+            Err(e) => {
+                let _ = std::fs::remove_file(&partial);
+                return Err(e);
+            }
+        };
+        std::fs::rename(&partial, dest).map_err(|e| SearchError::io_at(dest, e))?;
+
+        let size_bytes = std::fs::metadata(dest)
+            .map_err(|e| SearchError::io_at(dest, e))?
+            .len();
+        Ok(BackupReport {
+            path: dest.to_path_buf(),
+            pages,
+            size_bytes,
+        })
+    }
+
+    /// Overwrites the index with the database at `source` through the online backup API,
+    /// so other connections see either the old contents or the new ones. The write lease
+    /// held before the restore is kept. Caches built from the old contents are not
+    /// refreshed: reopen the index afterwards.
+    pub fn restore_from<F>(&self, source: &Path, mut progress: F) -> Result<usize>
+    where
+        F: FnMut(usize, usize),
+    {
+        let src = Connection::open_with_flags(source, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let mut dst = self.writer.get()?;
+        let lease = Self::read_write_lease(&dst)?;
+
+        let pages = Self::copy_pages(&src, &mut dst, &mut progress)?;
+        match lease {
+            Some(lease) => Self::write_write_lease(&dst, &lease)?,
+            None => {
+                Settings::new(&dst).remove(keys::WRITE_LEASE)?;
+            }
+        }
+        Ok(pages)
+    }
+
+    // `Backup::run_to_completion` only reports progress to a plain function, so the steps
+    // are driven here instead.
+    fn copy_pages<F>(src: &Connection, dst: &mut Connection, progress: &mut F) -> Result<usize>
+    where
+        F: FnMut(usize, usize),
+    {
+        let backup = Backup::new(src, dst)?;
+        loop {
+            let step = backup.step(BACKUP_PAGES_PER_STEP)?;
+            let state = backup.progress();
+            let total = state.pagecount.max(0) as usize;
+            progress(total - (state.remaining.max(0) as usize).min(total), total);
+            if step == StepResult::Done {
+                return Ok(total);
+            }
+            // More pages, or a lock to wait out before retrying.
+            std::thread::sleep(BACKUP_STEP_PAUSE);
+        }
+    }
+
+    pub fn vacuum(&self) -> Result<()> {
+        let conn = self.writer.get()?;
+        conn.execute("VACUUM", [])?;
+        Ok(())
+    }
+
+    /// Bytes the index occupies on disk: the database file plus its write-ahead log. In-memory
+    /// indexes report their page total instead.
+    pub fn storage_size(&self) -> Result<u64> {
+        let conn = self.reader.get()?;
+        if let Some(path) = conn.path().filter(|path| !path.is_empty()) {
+            let file_len = |path: &str| std::fs::metadata(path).map_or(0, |m| m.len());
+            return Ok(file_len(path) + file_len(&format!("{}-wal", path)));
+        }
+
+        let pages: i64 = conn.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+        let page_size: i64 = conn.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+        Ok((pages * page_size) as u64)
+    }
+
+    /// Copies the write-ahead log into the database file and truncates it, so the log no
+    /// longer counts pages that the file already holds.
+    pub fn checkpoint(&self) -> Result<()> {
+        let conn = self.writer.get()?;
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+        Ok(())
+    }
+
+    /// Checkpoints as much of the write-ahead log as readers allow, without waiting for them.
+    pub fn checkpoint_passive(&self) -> Result<()> {
+        let conn = self.writer.get()?;
+        conn.query_row("PRAGMA wal_checkpoint(PASSIVE)", [], |_| Ok(()))?;
+        Ok(())
+    }
+
+    /// Runs one incremental FTS5 merge that writes at most `pages` pages. Returns whether
+    /// the full-text index had nothing left to merge.
+    pub fn merge_fts(&self, pages: usize) -> Result<bool> {
+        if !self.has_full_text_index()? {
+            return Ok(true);
+        }
+
+        let conn = self.writer.get()?;
+        let total_changes = || -> Result<i64> {
+            Ok(conn.query_row("SELECT total_changes()", [], |row| row.get(0))?)
+        };
+        let before = total_changes()?;
+        conn.execute(
+            "INSERT INTO files_fts(files_fts, rank) VALUES ('merge', ?1)",
+            params![pages as i64],
+        )?;
+        // FTS5 documents a change of less than two as a merge that found no work.
+        Ok(total_changes()? - before < 2)
+    }
+
+    /// Refreshes the query planner's statistics, sampling a bounded number of rows per
+    /// index so it stays quick on large indexes.
+    pub fn analyze(&self) -> Result<()> {
+        let conn = self.writer.get()?;
+        conn.query_row("PRAGMA analysis_limit = 400", [], |_| Ok(()))?;
+        conn.execute_batch("ANALYZE")?;
+        Ok(())
+    }
+
+    /// Deletes up to `limit` content blobs that no file refers to any more, along with
+    /// their full-text rows. Returns how many were deleted.
+    pub fn prune_orphan_blobs(&self, limit: usize) -> Result<usize> {
+        let conn = self.writer.get()?;
+        let tx = conn.unchecked_transaction()?;
+
+        let hashes = tx
+            .prepare_cached(
+                r#"
+                SELECT b.hash FROM content_blobs b
+                WHERE NOT EXISTS (SELECT 1 FROM file_contents fc WHERE fc.content_hash = b.hash)
+                LIMIT ?1
+                "#,
+            )?
+            .query_map(params![limit as i64], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
 
-        let total_size: i64 = conn.query_row(
-            "SELECT COALESCE(SUM(size), 0) FROM files WHERE is_directory = 0",
-            [],
-            |row| row.get(0),
-        )?;
+        for hash in &hashes {
+            tx.prepare_cached("DELETE FROM files_fts WHERE content_hash = ?1")?
+                .execute(params![hash])?;
+            tx.prepare_cached("DELETE FROM content_blobs WHERE hash = ?1")?
+                .execute(params![hash])?;
+        }
 
-        let indexed_files: i64 = conn.query_row(
-            "SELECT COUNT(*) FROM file_contents",
-            [],
-            |row| row.get(0),
-        )?;
+        tx.commit()?;
+        Ok(hashes.len())
+    }
 
-        let last_update_ts: Option<i64> = conn
-            .query_row(
-                "SELECT MAX(indexed_at) FROM files",
-                [],
-                |row| row.get(0),
+    /// Deletes up to `limit` access-log entries older than `before`, oldest first. Returns
+    /// how many were deleted.
+    pub fn prune_access_log(&self, before: DateTime<Utc>, limit: usize) -> Result<usize> {
+        let conn = self.writer.get()?;
+        let pruned = conn.execute(
+            r#"
+            DELETE FROM access_log WHERE rowid IN (
+                SELECT rowid FROM access_log WHERE accessed_at < ?1
+                ORDER BY accessed_at LIMIT ?2
             )
-            .optional()?
-            .flatten();
+            "#,
+            params![before.timestamp(), limit as i64],
+        )?;
+        Ok(pruned)
+    }
 
-        let last_update = last_update_ts
-            .and_then(|ts| Utc.timestamp_opt(ts, 0).single())
-            .unwrap_or_else(Utc::now);
+    /// Drops the content search data of `file_ids`, keeping their metadata rows. Returns how
+    /// many of them had content.
+    pub fn drop_content(&self, file_ids: &[i64]) -> Result<usize> {
+        if file_ids.is_empty() {
+            return Ok(0);
+        }
+        let conn = self.writer.get()?;
+        let tx = conn.unchecked_transaction()?;
 
-        let index_size = std::fs::metadata(
-            conn.path().ok_or_else(|| {
-                SearchError::Configuration("Cannot get database path".to_string())
-            })?,
-        )
-        .map(|m| m.len())
-        .unwrap_or(0);
+        let mut dropped = 0;
+        for file_id in file_ids {
+            if Self::release_content(&tx, *file_id)? {
+                dropped += 1;
+            }
+        }
+        StatsCounters::content(-(dropped as i64)).apply(&tx)?;
 
-        Ok(IndexStats {
-            total_files: total_files as usize,
-            total_directories: total_directories as usize,
-            total_size: total_size as u64,
-            indexed_files: indexed_files as usize,
-            last_update,
-            index_size,
-        })
+        tx.commit()?;
+        Ok(dropped)
     }
 
-    pub fn clear_all(&self) -> Result<()> {
-        let conn = self.pool.get()?;
+    /// Drops content search data for up to `limit` files, those accessed longest ago first,
+    /// keeping their metadata rows. Files never accessed go first, oldest indexed first.
+    /// Returns how many files lost their content.
+    pub fn evict_least_recently_accessed_content(&self, limit: usize) -> Result<usize> {
+        let conn = self.writer.get()?;
         let tx = conn.unchecked_transaction()?;
 
-        tx.execute("DELETE FROM files", [])?;
-        tx.execute("DELETE FROM file_contents", [])?;
-        tx.execute("DELETE FROM files_fts", [])?;
-        tx.execute("DELETE FROM access_log", [])?;
-        tx.execute("DELETE FROM search_history", [])?;
+        let file_ids = tx
+            .prepare_cached(
+                r#"
+                SELECT fc.file_id FROM file_contents fc
+                JOIN files f ON f.id = fc.file_id
+                LEFT JOIN (
+                    SELECT file_id, MAX(accessed_at) AS last_access
+                    FROM access_log GROUP BY file_id
+                ) a ON a.file_id = fc.file_id
+                ORDER BY COALESCE(a.last_access, 0), f.indexed_at, fc.file_id
+                LIMIT ?1
+                "#,
+            )?
+            .query_map(params![limit as i64], |row| row.get::<_, i64>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        for file_id in &file_ids {
+            Self::release_content(&tx, *file_id)?;
+        }
+        StatsCounters::content(-(file_ids.len() as i64)).apply(&tx)?;
 
         tx.commit()?;
-        Ok(())
+        Ok(file_ids.len())
     }
 
-    pub fn vacuum(&self) -> Result<()> {
-        let conn = self.pool.get()?;
-        conn.execute("VACUUM", [])?;
-        Ok(())
+    fn row_to_saved_search(row: &rusqlite::Row) -> rusqlite::Result<SavedSearch> {
+        let sort: Option<String> = row.get(2)?;
+        let created_at: i64 = row.get(3)?;
+        let last_run_at: Option<i64> = row.get(4)?;
+        let last_result_count: Option<i64> = row.get(5)?;
+
+        Ok(SavedSearch {
+            name: row.get(0)?,
+            query: row.get(1)?,
+            sort: sort.as_deref().and_then(ListingSort::parse),
+            created_at: Utc.timestamp_opt(created_at, 0).single().unwrap_or_else(Utc::now),
+            last_run_at: last_run_at.and_then(|ts| Utc.timestamp_opt(ts, 0).single()),
+            last_result_count: last_result_count.map(|count| count as usize),
+        })
     }
 
-    fn row_to_file_entry(row: &rusqlite::Row) -> rusqlite::Result<FileEntry> {
+    fn row_to_file_entry(
+        &self,
+        conn: &Connection,
+        row: &rusqlite::Row,
+    ) -> rusqlite::Result<FileEntry> {
         let id: i64 = row.get(0)?;
-        let path: String = row.get(1)?;
+        let path: Option<String> = row.get(1)?;
         let name: String = row.get(2)?;
         let extension: Option<String> = row.get(3)?;
         let size: i64 = row.get(4)?;
@@ -445,10 +3290,22 @@ impl Database {
         let file_hash: Option<String> = row.get(13)?;
         let indexed_at: i64 = row.get(14)?;
         let last_verified: i64 = row.get(15)?;
+        let parent_id: Option<i64> = row.get(16)?;
+
+        let (path, parent_path) = match parent_id {
+            Some(parent_id) => {
+                let parent = self.dir_path(conn, parent_id)?;
+                (parent.join(&name), Some(parent))
+            }
+            None => (
+                PathBuf::from(path.unwrap_or_default()),
+                parent_path.map(PathBuf::from),
+            ),
+        };
 
         Ok(FileEntry {
             id: Some(id),
-            path: PathBuf::from(path),
+            path,
             name,
             extension,
             size: size as u64,
@@ -458,7 +3315,7 @@ impl Database {
             is_directory: is_directory != 0,
             is_hidden: is_hidden != 0,
             is_symlink: is_symlink != 0,
-            parent_path: parent_path.map(PathBuf::from),
+            parent_path,
             mime_type,
             file_hash,
             indexed_at: Utc.timestamp_opt(indexed_at, 0).single().unwrap_or_else(Utc::now),
@@ -466,3 +3323,1034 @@ impl Database {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_in_memory_pool_connections_share_one_database() {
+        let db = Database::in_memory(4).unwrap();
+
+        let writer = db.writer.get().unwrap();
+        let reader = db.reader.get().unwrap();
+        writer
+            .execute(
+                "INSERT INTO index_metadata (key, value, updated_at) VALUES ('probe', 'x', 0)",
+                [],
+            )
+            .unwrap();
+        let value: String = reader
+            .query_row("SELECT value FROM index_metadata WHERE key = 'probe'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(value, "x");
+        drop((writer, reader));
+
+        db.insert_file(&FileEntry::new(PathBuf::from("/tmp/shared.txt"))).unwrap();
+        let _held: Vec<_> = (0..2).map(|_| db.reader.get().unwrap()).collect();
+        assert!(db.find_by_path(Path::new("/tmp/shared.txt")).unwrap().is_some());
+
+        let other = Database::in_memory(1).unwrap();
+        assert!(other.find_by_path(Path::new("/tmp/shared.txt")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_name_prefilter_uses_normalized_column() {
+        let db = Database::in_memory(1).unwrap();
+        db.insert_file(&FileEntry::new(PathBuf::from("/docs/Café menu.pdf")))
+            .unwrap();
+
+        let raw_matches: i64 = db
+            .reader
+            .get()
+            .unwrap()
+            .query_row(
+                "SELECT COUNT(*) FROM files WHERE name LIKE '%cafe%'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(raw_matches, 0);
+
+        let results = db.search_by_name("cafe", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "Café menu.pdf");
+    }
+
+    #[test]
+    fn test_renormalize_names_with_custom_normalizer() {
+        use crate::utils::normalizer::LowercaseNormalizer;
+
+        let db = Database::in_memory(1).unwrap();
+        db.insert_file(&FileEntry::new(PathBuf::from("/docs/Café.txt")))
+            .unwrap();
+        assert_eq!(db.search_by_name("cafe", 10).unwrap().len(), 1);
+
+        let db = db.with_normalizer(Arc::new(LowercaseNormalizer));
+        assert_eq!(db.renormalize_names(100).unwrap(), 1);
+        assert!(db.search_by_name("cafe", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_upsert_updates_existing_row() {
+        let db = Database::in_memory(1).unwrap();
+        let path = PathBuf::from("/data/report.txt");
+
+        let mut entry = FileEntry::new(path.clone());
+        entry.size = 10;
+        db.insert_file(&entry).unwrap();
+
+        entry.size = 20;
+        db.insert_file(&entry).unwrap();
+        assert_eq!(db.find_by_path(&path).unwrap().unwrap().size, 20);
+
+        let mut updated = FileEntry::new(path.clone());
+        updated.size = 30;
+        db.insert_files_batch(&[FileEntry::new(PathBuf::from("/data/other.txt")), updated.clone(), updated])
+            .unwrap();
+
+        assert_eq!(db.get_all_files(10, 0).unwrap().len(), 2);
+        assert_eq!(db.find_by_path(&path).unwrap().unwrap().size, 30);
+    }
+
+    #[test]
+    fn test_concurrent_settings_from_two_connections() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("settings.db");
+        Database::new(&db_path, 1).unwrap();
+
+        let handles: Vec<_> = ["a", "b"]
+            .into_iter()
+            .map(|writer| {
+                let db = Database::new(&db_path, 1).unwrap();
+                std::thread::spawn(move || {
+                    for i in 0..50 {
+                        db.set_setting(&format!("user.{}", writer), &i.to_string())
+                            .unwrap();
+                        db.set_setting("user.shared", writer).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let db = Database::new(&db_path, 1).unwrap();
+        let value = |key: &str| db.setting(key).unwrap().map(|setting| setting.value);
+        assert_eq!(value("user.a").as_deref(), Some("49"));
+        assert_eq!(value("user.b").as_deref(), Some("49"));
+        assert!(matches!(value("user.shared").as_deref(), Some("a" | "b")));
+        let settings = db.settings().unwrap();
+        let user_keys = settings.iter().filter(|s| s.key.starts_with(keys::USER_PREFIX));
+        assert_eq!(user_keys.count(), 3);
+    }
+
+    #[test]
+    fn test_concurrent_initialization_on_fresh_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("race.db");
+
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let db_path = db_path.clone();
+                std::thread::spawn(move || Database::new(&db_path, 2).map(|_| ()))
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap().unwrap();
+        }
+
+        let db = Database::new(&db_path, 1).unwrap();
+        let conn = db.reader.get().unwrap();
+        assert!(MigrationManager::verify_schema(&conn).unwrap());
+
+        let versions: i64 = conn
+            .query_row("SELECT COUNT(*) FROM schema_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(versions, 1);
+
+        for statement in crate::storage::schema::get_all_index_creation_statements() {
+            let name = statement.split_whitespace().nth(5).unwrap();
+            let found: i64 = conn
+                .query_row(
+                    "SELECT COUNT(*) FROM sqlite_master WHERE type = 'index' AND name = ?1",
+                    params![name],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(found, 1, "missing index {}", name);
+        }
+    }
+
+    #[test]
+    fn test_connection_pragmas_applied_to_every_pooled_connection() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = Database::new(temp_dir.path().join("pragmas.db"), 3).unwrap();
+
+        let connections: Vec<_> = (0..2)
+            .map(|_| db.reader.get().unwrap())
+            .chain(std::iter::once(db.writer.get().unwrap()))
+            .collect();
+        for conn in &connections {
+            let cache_size: i64 = conn.query_row("PRAGMA cache_size", [], |row| row.get(0)).unwrap();
+            let temp_store: i64 = conn.query_row("PRAGMA temp_store", [], |row| row.get(0)).unwrap();
+            assert_eq!(cache_size, -64000);
+            assert_eq!(temp_store, 2);
+        }
+    }
+
+    #[test]
+    fn test_read_pool_rejects_writes() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let file_db = Database::new(temp_dir.path().join("readonly.db"), 2).unwrap();
+        let memory_db = Database::in_memory(2).unwrap();
+
+        for db in [&file_db, &memory_db] {
+            let insert = db.reader.get().unwrap().execute(
+                "INSERT INTO index_metadata (key, value, updated_at) VALUES ('probe', 'x', 0)",
+                [],
+            );
+            assert!(insert.is_err());
+            assert!(!db.is_read_only().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_searches_are_not_starved_by_bulk_inserts() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+        use std::time::{Duration, Instant};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = Arc::new(Database::new(temp_dir.path().join("starve.db"), 5).unwrap());
+        db.insert_file(&FileEntry::new(PathBuf::from("/seed/report.txt"))).unwrap();
+
+        let entries: Vec<_> = (0..10_000)
+            .map(|i| FileEntry::new(PathBuf::from(format!("/bulk/dir{}/file{}.rs", i % 100, i))))
+            .collect();
+        let writing = Arc::new(AtomicBool::new(true));
+
+        let readers: Vec<_> = (0..3)
+            .map(|_| {
+                let db = Arc::clone(&db);
+                let writing = Arc::clone(&writing);
+                std::thread::spawn(move || {
+                    let mut latencies = Vec::new();
+                    while writing.load(Ordering::Acquire) {
+                        let start = Instant::now();
+                        assert_eq!(db.search_by_name("report", 10).unwrap().len(), 1);
+                        latencies.push(start.elapsed());
+                    }
+                    latencies
+                })
+            })
+            .collect();
+
+        let start = Instant::now();
+        db.insert_files_batch(&entries).unwrap();
+        let write_duration = start.elapsed();
+        writing.store(false, Ordering::Release);
+
+        let mut latencies: Vec<Duration> =
+            readers.into_iter().flat_map(|handle| handle.join().unwrap()).collect();
+        latencies.sort();
+        assert!(latencies.len() > 3, "searches did not run during the insert");
+
+        let p95 = latencies[latencies.len() * 95 / 100];
+        let slowest = *latencies.last().unwrap();
+        assert!(p95 < Duration::from_millis(250), "p95 search latency was {:?}", p95);
+        assert!(
+            slowest < write_duration,
+            "a search waited {:?} for a {:?} write",
+            slowest,
+            write_duration
+        );
+        assert_eq!(db.get_stats().unwrap().total_files, 10_001);
+    }
+
+    #[test]
+    fn test_identical_content_shares_one_blob() {
+        let db = Database::in_memory(1).unwrap();
+        let preview = ContentPreview {
+            preview: "Permission is hereby granted, free of charge".to_string(),
+            word_count: 7,
+            line_count: 1,
+            encoding: "UTF-8".to_string(),
+            content_hash: crate::utils::hash::hash_string("Permission is hereby granted, free of charge"),
+            sampled: false,
+        };
+
+        let paths: Vec<PathBuf> = (0..50)
+            .map(|i| PathBuf::from(format!("/vendor/crate{}/LICENSE", i)))
+            .collect();
+        for path in &paths {
+            let id = db.insert_file(&FileEntry::new(path.clone())).unwrap();
+            db.insert_content(id, &preview).unwrap();
+        }
+
+        let count = |sql: &str| -> i64 {
+            db.reader.get().unwrap().query_row(sql, [], |row| row.get(0)).unwrap()
+        };
+        assert_eq!(count("SELECT COUNT(*) FROM files_fts"), 1);
+        assert_eq!(count("SELECT refcount FROM content_blobs"), 50);
+        assert_eq!(db.search_content("hereby", 100).unwrap().len(), 50);
+
+        for path in &paths[..49] {
+            db.delete_by_path(path).unwrap();
+        }
+        assert_eq!(count("SELECT COUNT(*) FROM files_fts"), 1);
+        assert_eq!(count("SELECT refcount FROM content_blobs"), 1);
+        assert_eq!(db.search_content("hereby", 100).unwrap().len(), 1);
+
+        db.delete_by_path(&paths[49]).unwrap();
+        assert_eq!(count("SELECT COUNT(*) FROM files_fts"), 0);
+        assert_eq!(count("SELECT COUNT(*) FROM content_blobs"), 0);
+        assert_eq!(count("SELECT COUNT(*) FROM file_contents"), 0);
+    }
+
+    #[test]
+    fn test_large_content_is_indexed_in_chunks() {
+        let db = Database::in_memory(1).unwrap().with_fts_chunk_bytes(4096);
+        let preview = |text: &str| ContentPreview {
+            preview: text.to_string(),
+            word_count: text.split_whitespace().count(),
+            line_count: text.lines().count(),
+            encoding: "UTF-8".to_string(),
+            content_hash: crate::utils::hash::hash_string(text),
+            sampled: false,
+        };
+        let mut text: String = (0..1000).map(|i| format!("filler line {}\n", i)).collect();
+        let offset = text.len();
+        text.push_str("the zebracorn is here\n");
+
+        let id = db.insert_file(&FileEntry::new(PathBuf::from("/dumps/big.sql"))).unwrap();
+        db.insert_content(id, &preview(&text)).unwrap();
+
+        let count = |sql: &str| -> i64 {
+            db.reader.get().unwrap().query_row(sql, [], |row| row.get(0)).unwrap()
+        };
+        let chunks = count("SELECT COUNT(*) FROM files_fts");
+        assert!(chunks > 1, "{} chunks", chunks);
+        assert_eq!(db.search_content("zebracorn", 10).unwrap(), vec![id]);
+        // Filler is in every chunk but counts once for the file.
+        assert_eq!(db.search_content("filler", 10).unwrap(), vec![id]);
+
+        let hits = db.content_chunk_hits(id, "zebracorn", 3).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].chunk_index as i64, chunks - 1);
+        assert!(hits[0].byte_offset <= offset);
+        assert!(offset < hits[0].byte_offset + hits[0].length);
+        assert_eq!(db.content_chunk_hits(id, "filler", 2).unwrap().len(), 2);
+
+        db.insert_content(id, &preview("a short replacement")).unwrap();
+        assert_eq!(count("SELECT COUNT(*) FROM files_fts"), 1);
+        assert!(db.search_content("zebracorn", 10).unwrap().is_empty());
+
+        db.insert_content(id, &preview(&text)).unwrap();
+        let report = db.rebuild_fts(10, |_, _| {}).unwrap();
+        assert_eq!(report.blobs, 1);
+        assert_eq!(count("SELECT COUNT(*) FROM files_fts"), chunks);
+        db.delete_by_path(Path::new("/dumps/big.sql")).unwrap();
+        assert_eq!(count("SELECT COUNT(*) FROM files_fts"), 0);
+    }
+
+    #[test]
+    fn test_rebuild_fts_reconciles_writes_made_during_the_rebuild() {
+        let db = Database::in_memory(1).unwrap();
+        let preview = |text: &str| ContentPreview {
+            preview: text.to_string(),
+            word_count: text.split_whitespace().count(),
+            line_count: 1,
+            encoding: "UTF-8".to_string(),
+            content_hash: crate::utils::hash::hash_string(text),
+            sampled: false,
+        };
+        let add = |path: &str, text: &str| {
+            let id = db.insert_file(&FileEntry::new(PathBuf::from(path))).unwrap();
+            db.insert_content(id, &preview(text)).unwrap();
+        };
+        for i in 0..5 {
+            add(&format!("/notes/{}.txt", i), &format!("entry number{} of the journal", i));
+        }
+
+        let calls = std::cell::Cell::new(0);
+        let report = db
+            .rebuild_fts(2, |_, total| {
+                assert_eq!(total, 5);
+                if calls.replace(calls.get() + 1) == 0 {
+                    add("/notes/late.txt", "a late arrival");
+                    db.delete_by_path(Path::new("/notes/4.txt")).unwrap();
+                }
+            })
+            .unwrap();
+
+        assert!(calls.get() > 1);
+        assert_eq!(report, FtsRebuildReport { blobs: 5, files: 5, skipped: 0 });
+        assert_eq!(db.search_content("arrival", 10).unwrap().len(), 1);
+        assert!(db.search_content("number4", 10).unwrap().is_empty());
+        assert_eq!(db.search_content("journal", 10).unwrap().len(), 4);
+        let leftover: i64 = db
+            .reader
+            .get()
+            .unwrap()
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE name = ?1",
+                params![FTS_REBUILD_TABLE],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(leftover, 0);
+    }
+
+    #[test]
+    fn test_maintenance_prunes_orphans_and_old_accesses_in_batches() {
+        let db = Database::in_memory(1).unwrap();
+        for i in 0..3 {
+            let id = db
+                .insert_file(&FileEntry::new(PathBuf::from(format!("/notes/{}.txt", i))))
+                .unwrap();
+            let text = format!("memo number{}", i);
+            db.insert_content(
+                id,
+                &ContentPreview {
+                    preview: text.clone(),
+                    word_count: 2,
+                    line_count: 1,
+                    encoding: "UTF-8".to_string(),
+                    content_hash: crate::utils::hash::hash_string(&text),
+                    sampled: false,
+                },
+            )
+            .unwrap();
+            let conn = db.writer.get().unwrap();
+            for days_ago in [400, 200, 1] {
+                conn.execute(
+                    "INSERT INTO access_log (file_id, accessed_at) VALUES (?1, ?2)",
+                    params![id, (Utc::now() - chrono::Duration::days(days_ago)).timestamp()],
+                )
+                .unwrap();
+            }
+        }
+        // A cascading delete skips the refcount bookkeeping, leaving the content behind.
+        db.writer
+            .get()
+            .unwrap()
+            .execute("DELETE FROM files WHERE path != '/notes/0.txt'", [])
+            .unwrap();
+        let count = |sql: &str| -> i64 {
+            db.reader.get().unwrap().query_row(sql, [], |row| row.get(0)).unwrap()
+        };
+        assert_eq!(count("SELECT COUNT(*) FROM content_blobs"), 3);
+
+        assert_eq!(db.prune_orphan_blobs(1).unwrap(), 1);
+        assert_eq!(db.prune_orphan_blobs(10).unwrap(), 1);
+        assert_eq!(db.prune_orphan_blobs(10).unwrap(), 0);
+        assert_eq!(count("SELECT COUNT(*) FROM files_fts"), 1);
+        assert_eq!(db.search_content("number0", 10).unwrap().len(), 1);
+
+        let cutoff = Utc::now() - chrono::Duration::days(90);
+        assert_eq!(db.prune_access_log(cutoff, 1).unwrap(), 1);
+        assert_eq!(db.prune_access_log(cutoff, 10).unwrap(), 1);
+        assert_eq!(db.prune_access_log(cutoff, 10).unwrap(), 0);
+        assert_eq!(count("SELECT COUNT(*) FROM access_log"), 1);
+
+        assert!((0..10).any(|_| db.merge_fts(16).unwrap()));
+        db.analyze().unwrap();
+        db.checkpoint_passive().unwrap();
+    }
+
+    #[test]
+    fn test_path_contains_escapes_like_wildcards() {
+        let db = Database::in_memory(10).unwrap();
+        db.insert_files_batch(&[
+            FileEntry::new(PathBuf::from("/repo/100%_done/notes.txt")),
+            FileEntry::new(PathBuf::from("/repo/100x/notes.txt")),
+            FileEntry::new(PathBuf::from(r"C:\repo\src\server\main.rs")),
+        ])
+        .unwrap();
+
+        let found = db.search_by_path_contains("100%_", 10).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].path, PathBuf::from("/repo/100%_done/notes.txt"));
+
+        assert_eq!(db.search_by_path_contains("repo/src/server", 10).unwrap().len(), 1);
+        assert_eq!(db.search_by_path_contains(r"repo\src", 10).unwrap().len(), 1);
+    }
+
+    fn null_path_rows(db: &Database) -> i64 {
+        db.reader
+            .get()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM files WHERE path IS NULL", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_compact_layout_round_trips_paths() {
+        let db = Database::in_memory_with_layout(1, PathLayout::Compact).unwrap();
+        assert_eq!(db.path_layout(), PathLayout::Compact);
+
+        let mut renamed = FileEntry::new(PathBuf::from("/repo/src/custom.rs"));
+        renamed.name = "display name".to_string();
+        let entries = vec![
+            FileEntry::new(PathBuf::from("/repo/src/server/main.rs")),
+            FileEntry::new(PathBuf::from("/repo/src/server")),
+            FileEntry::new(PathBuf::from("/repo/100%_done/München notes.txt")),
+            FileEntry::new(PathBuf::from("relative/dir/file.txt")),
+            FileEntry::new(PathBuf::from("/")),
+            FileEntry::new(PathBuf::from("/repo/a/../b.txt")),
+            renamed,
+        ];
+        db.insert_files_batch(&entries).unwrap();
+        // Rows that cannot be rebuilt from their parent and name keep their full path.
+        assert_eq!(null_path_rows(&db), 4);
+
+        // A fresh cache has to rebuild every path from path_dirs.
+        db.dirs.clear();
+        for entry in &entries {
+            let found = db.find_by_path(&entry.path).unwrap().unwrap();
+            assert_eq!(found.path, entry.path);
+            assert_eq!(found.name, entry.name);
+            assert_eq!(found.parent_path, entry.parent_path, "{}", entry.path.display());
+        }
+
+        let mut updated = entries[0].clone();
+        updated.size = 42;
+        db.insert_file(&updated).unwrap();
+        assert_eq!(db.get_stats().unwrap().total_files, entries.len());
+        assert_eq!(db.find_by_path(&updated.path).unwrap().unwrap().size, 42);
+
+        assert_eq!(db.search_by_path_contains("src/server", 10).unwrap().len(), 2);
+        let found = db.search_by_path_contains("100%_", 10).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].path, entries[2].path);
+        assert!(db.find_by_path(Path::new("/repo/src/missing.rs")).unwrap().is_none());
+
+        let since = Utc::now() - chrono::Duration::seconds(5);
+        db.delete_by_path(&entries[0].path).unwrap();
+        assert!(db.find_by_path(&entries[0].path).unwrap().is_none());
+        db.clear_all().unwrap();
+        let mut deleted: Vec<_> = db
+            .get_deletions_since(since)
+            .unwrap()
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        let mut expected: Vec<_> = entries.iter().map(|e| e.path.clone()).collect();
+        deleted.sort();
+        expected.sort();
+        assert_eq!(deleted, expected);
+    }
+
+    #[test]
+    fn test_find_by_paths_in_both_layouts() {
+        for layout in [PathLayout::Legacy, PathLayout::Compact] {
+            let db = Database::in_memory_with_layout(1, layout).unwrap();
+            let mut entries: Vec<FileEntry> = (0..PATH_LOOKUP_CHUNK + 5)
+                .map(|i| FileEntry::new(PathBuf::from(format!("/repo/src/file{}.rs", i))))
+                .collect();
+            entries.push(FileEntry::new(PathBuf::from("/repo/a/../b.txt")));
+            db.insert_files_batch(&entries).unwrap();
+
+            let mut wanted: Vec<PathBuf> = entries.iter().map(|e| e.path.clone()).collect();
+            wanted.push(PathBuf::from("/repo/src/missing.rs"));
+            wanted.push(PathBuf::from("/elsewhere/file0.rs"));
+
+            let mut found: Vec<PathBuf> =
+                db.find_by_paths(&wanted).unwrap().into_iter().map(|e| e.path).collect();
+            let mut expected: Vec<PathBuf> = entries.iter().map(|e| e.path.clone()).collect();
+            found.sort();
+            expected.sort();
+            assert_eq!(found, expected, "{:?}", layout);
+            assert!(db.find_by_paths(&[]).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_search_by_name_under_in_both_layouts() {
+        for layout in [PathLayout::Legacy, PathLayout::Compact] {
+            let db = Database::in_memory_with_layout(1, layout).unwrap();
+            let mut project = FileEntry::new(PathBuf::from("/home/me/project"));
+            project.is_directory = true;
+            db.insert_files_batch(&[
+                project,
+                FileEntry::new(PathBuf::from("/home/me/project/main.rs")),
+                FileEntry::new(PathBuf::from("/home/me/project/src/bin/main.rs")),
+                FileEntry::new(PathBuf::from("/home/me/projectB/main.rs")),
+                FileEntry::new(PathBuf::from("/home/me/main.rs")),
+            ])
+            .unwrap();
+
+            let found = |pattern: &str, dir: &str| {
+                let mut paths: Vec<PathBuf> = db
+                    .search_by_name_under(pattern, Path::new(dir), 10)
+                    .unwrap()
+                    .into_iter()
+                    .map(|e| e.path)
+                    .collect();
+                paths.sort();
+                paths
+            };
+
+            assert_eq!(
+                found("main", "/home/me/project"),
+                vec![
+                    PathBuf::from("/home/me/project/main.rs"),
+                    PathBuf::from("/home/me/project/src/bin/main.rs"),
+                ],
+                "{:?}",
+                layout
+            );
+            assert_eq!(found("project", "/home/me/project").len(), 1, "{:?}", layout);
+            assert_eq!(found("main", "/home/me/").len(), 4, "{:?}", layout);
+            assert!(found("main", "/home/me/proj").is_empty(), "{:?}", layout);
+            assert!(found("main", "/elsewhere").is_empty(), "{:?}", layout);
+        }
+    }
+
+    #[test]
+    fn test_miss_cache_answers_repeated_misses_until_a_write() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let index = temp_dir.path().join("index.db");
+        let db = Database::new(&index, 2)
+            .unwrap()
+            .with_miss_cache(MissCache::new(16, Duration::from_millis(50)));
+        let other = Database::new(&index, 1).unwrap();
+        let path = Path::new("/srv/late.txt");
+
+        assert!(db.find_by_path(path).unwrap().is_none());
+        db.insert_file(&FileEntry::new(path.to_path_buf())).unwrap();
+        assert!(db.find_by_path(path).unwrap().is_some());
+
+        let elsewhere = Path::new("/srv/elsewhere.txt");
+        assert!(db.find_by_path(elsewhere).unwrap().is_none());
+        other.insert_file(&FileEntry::new(elsewhere.to_path_buf())).unwrap();
+        assert!(db.find_by_path(elsewhere).unwrap().is_none());
+        std::thread::sleep(Duration::from_millis(80));
+        assert!(db.find_by_path(elsewhere).unwrap().is_some());
+
+        let batched = Path::new("/srv/batched.txt");
+        assert!(db.find_by_path(batched).unwrap().is_none());
+        db.insert_files_batch(&[FileEntry::new(batched.to_path_buf())]).unwrap();
+        assert!(db.find_by_path(batched).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_convert_to_compact_keeps_ids_and_content() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = Database::new(temp_dir.path().join("index.db"), 2).unwrap();
+        let path = PathBuf::from("/vendor/crate/LICENSE");
+        db.insert_files_batch(&[
+            FileEntry::new(path.clone()),
+            FileEntry::new(PathBuf::from("/vendor/crate/src/lib.rs")),
+        ])
+        .unwrap();
+        let id = db.find_by_path(&path).unwrap().unwrap().id.unwrap();
+        db.insert_content(
+            id,
+            &ContentPreview {
+                preview: "Permission is hereby granted".to_string(),
+                word_count: 4,
+                line_count: 1,
+                encoding: "UTF-8".to_string(),
+                content_hash: crate::utils::hash::hash_string("Permission is hereby granted"),
+                sampled: false,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(db.convert_to_compact_paths().unwrap(), 2);
+        assert_eq!(db.path_layout(), PathLayout::Compact);
+        assert_eq!(null_path_rows(&db), 2);
+        assert_eq!(db.find_by_path(&path).unwrap().unwrap().id, Some(id));
+        assert_eq!(db.search_content_files("hereby", 10).unwrap()[0].path, path);
+
+        db.insert_file(&FileEntry::new(path.clone())).unwrap();
+        assert_eq!(db.get_stats().unwrap().total_files, 2);
+        assert_eq!(db.convert_to_compact_paths().unwrap(), 0);
+
+        drop(db);
+        let reopened = Database::new(temp_dir.path().join("index.db"), 1).unwrap();
+        assert_eq!(reopened.path_layout(), PathLayout::Compact);
+        assert_eq!(reopened.find_by_path(&path).unwrap().unwrap().id, Some(id));
+    }
+
+    #[test]
+    fn test_compact_layout_shrinks_deep_trees() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut entries = Vec::new();
+        for shard in 0..20 {
+            let mut dir = PathBuf::from("/datasets/training-corpus-2024/node_modules");
+            for depth in 0..12 {
+                dir.push(format!("package-with-a-long-name-{}-{}", shard, depth));
+            }
+            for file in 0..50 {
+                entries.push(FileEntry::new(dir.join(format!("part-{:05}.json", file))));
+            }
+        }
+
+        let size = |layout: PathLayout| {
+            let path = temp_dir.path().join(layout.as_str());
+            let db = Database::with_layout(path, 1, layout).unwrap();
+            db.insert_files_batch(&entries).unwrap();
+            db.vacuum().unwrap();
+            let conn = db.reader.get().unwrap();
+            let pages: i64 = conn.query_row("PRAGMA page_count", [], |row| row.get(0)).unwrap();
+            let page_size: i64 = conn.query_row("PRAGMA page_size", [], |row| row.get(0)).unwrap();
+            pages * page_size
+        };
+
+        let legacy = size(PathLayout::Legacy);
+        let compact = size(PathLayout::Compact);
+        assert!(
+            compact * 3 < legacy,
+            "compact {} bytes vs legacy {} bytes",
+            compact,
+            legacy
+        );
+    }
+
+    fn audit_record(path: &str, event_type: AuditEventType, days_ago: i64) -> AuditRecord {
+        AuditRecord {
+            id: None,
+            path: PathBuf::from(path),
+            event_type,
+            occurred_at: Utc::now() - chrono::Duration::days(days_ago),
+            size: None,
+            file_hash: None,
+            watch_id: Some("w".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_top_extensions_ranks_by_count() {
+        let db = Database::in_memory(1).unwrap();
+        for path in ["/p/a.rs", "/p/b.rs", "/p/c.rs", "/p/d.md", "/p/e.md", "/p/f.toml", "/p/g"] {
+            db.insert_file(&FileEntry::new(PathBuf::from(path))).unwrap();
+        }
+
+        let top = db.top_extensions(2).unwrap();
+        assert_eq!(top, vec![("rs".to_string(), 3), ("md".to_string(), 2)]);
+        assert_eq!(db.top_extensions(10).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_extensions_are_stored_lowercased() {
+        let db = Database::in_memory(1).unwrap();
+        for path in ["/p/IMG_0001.JPG", "/p/IMG_0002.jpg", "/p/scan.Jpg", "/p/a.md"] {
+            db.insert_file(&FileEntry::new(PathBuf::from(path))).unwrap();
+        }
+        // Entries built by hand, as imports do, are canonicalized on write too.
+        let mut entry = FileEntry::new(PathBuf::from("/p/b.MD"));
+        entry.extension = Some("MD".to_string());
+        db.insert_file(&entry).unwrap();
+
+        let mut names: Vec<String> = db
+            .search_by_extension("JPG", 10)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        names.sort();
+        assert_eq!(names, ["IMG_0001.JPG", "IMG_0002.jpg", "scan.Jpg"]);
+        assert_eq!(
+            db.top_extensions(10).unwrap(),
+            vec![("jpg".to_string(), 3), ("md".to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn test_user_tags_cascade_with_files() {
+        let db = Database::in_memory(1).unwrap();
+        for path in ["/t/a.pdf", "/t/b.pdf", "/t/c.pdf"] {
+            db.insert_file(&FileEntry::new(PathBuf::from(path))).unwrap();
+        }
+        let ids: Vec<i64> = db
+            .get_all_files(10, 0)
+            .unwrap()
+            .iter()
+            .filter_map(|entry| entry.id)
+            .collect();
+
+        assert_eq!(db.tag_files(&ids, "tax-2023").unwrap(), 3);
+        assert_eq!(db.tag_files(&[ids[0], 9999], "tax-2023").unwrap(), 0);
+        db.tag_files(&ids[..1], "receipts").unwrap();
+        assert_eq!(
+            db.list_tags().unwrap(),
+            vec![("tax-2023".to_string(), 3), ("receipts".to_string(), 1)]
+        );
+
+        db.delete_by_path(Path::new("/t/a.pdf")).unwrap();
+        assert_eq!(db.list_tags().unwrap(), vec![("tax-2023".to_string(), 2)]);
+        let tagged = db.find_by_tags(&["tax-2023".to_string()], 10).unwrap();
+        assert_eq!(tagged.len(), 2);
+
+        assert_eq!(db.untag_files(&ids, "tax-2023").unwrap(), 2);
+        assert!(db.list_tags().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_audit_log_pagination_and_filters() {
+        let db = Database::in_memory(1).unwrap();
+        db.insert_audit_batch(&[
+            audit_record("/vault/a.txt", AuditEventType::Created, 3),
+            audit_record("/vault/a.txt", AuditEventType::Modified, 2),
+            audit_record("/vault-old/b.txt", AuditEventType::Deleted, 1),
+            audit_record("/vault/c.txt", AuditEventType::Deleted, 0),
+        ])
+        .unwrap();
+
+        let first = db
+            .query_audit_log(Some(Path::new("/vault/")), (None, None), &[], 0, 2)
+            .unwrap();
+        assert_eq!(first.len(), 2);
+        let rest = db
+            .query_audit_log(Some(Path::new("/vault")), (None, None), &[], first[1].id.unwrap(), 2)
+            .unwrap();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].path, PathBuf::from("/vault/c.txt"));
+
+        let since = Utc::now() - chrono::Duration::hours(36);
+        let recent_deletes = db
+            .query_audit_log(None, (Some(since), None), &[AuditEventType::Deleted], 0, 10)
+            .unwrap();
+        assert_eq!(recent_deletes.len(), 2);
+    }
+
+    #[test]
+    fn test_audit_log_retention_and_clear_survival() {
+        let db = Database::in_memory(1).unwrap();
+        db.insert_file(&FileEntry::new(PathBuf::from("/vault/a.txt")))
+            .unwrap();
+        db.insert_audit_batch(&[
+            audit_record("/vault/a.txt", AuditEventType::Created, 400),
+            audit_record("/vault/a.txt", AuditEventType::Modified, 10),
+        ])
+        .unwrap();
+
+        db.clear_all().unwrap();
+        assert_eq!(db.get_stats().unwrap().total_files, 0);
+        assert_eq!(
+            db.query_audit_log(None, (None, None), &[], 0, 10).unwrap().len(),
+            2
+        );
+
+        assert_eq!(
+            db.prune_audit_log(Utc::now() - chrono::Duration::days(365))
+                .unwrap(),
+            1
+        );
+        let remaining = db.query_audit_log(None, (None, None), &[], 0, 10).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].event_type, AuditEventType::Modified);
+
+        assert_eq!(db.clear_audit_log().unwrap(), 1);
+        assert!(db.query_audit_log(None, (None, None), &[], 0, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_backup_of_a_live_index_is_a_consistent_snapshot() {
+        use std::sync::atomic::AtomicBool;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = Arc::new(Database::new(temp_dir.path().join("index.db"), 4).unwrap());
+        let entry = |i: usize| FileEntry::new(PathBuf::from(format!("/data/file{:06}.txt", i)));
+        let initial: Vec<_> = (0..5000).map(entry).collect();
+        db.insert_files_batch(&initial).unwrap();
+
+        // Numbered inserts in order, so any consistent snapshot holds a gapless prefix.
+        let stop = Arc::new(AtomicBool::new(false));
+        let inserted = Arc::new(AtomicUsize::new(initial.len()));
+        let writer = {
+            let (db, stop, inserted) = (Arc::clone(&db), Arc::clone(&stop), Arc::clone(&inserted));
+            std::thread::spawn(move || {
+                while !stop.load(Ordering::SeqCst) {
+                    let next = inserted.load(Ordering::SeqCst);
+                    db.insert_file(&entry(next)).unwrap();
+                    inserted.store(next + 1, Ordering::SeqCst);
+                }
+            })
+        };
+        while inserted.load(Ordering::SeqCst) < initial.len() + 10 {
+            std::thread::yield_now();
+        }
+
+        let committed_before = inserted.load(Ordering::SeqCst);
+        let dest = temp_dir.path().join("backup.db");
+        let mut steps = Vec::new();
+        let report = db.backup_to(&dest, |copied, total| steps.push((copied, total))).unwrap();
+        let committed_after = inserted.load(Ordering::SeqCst);
+        stop.store(true, Ordering::SeqCst);
+        writer.join().unwrap();
+
+        assert!(steps.len() > 1);
+        assert_eq!(steps.last(), Some(&(report.pages, report.pages)));
+        assert!(committed_after > committed_before);
+        assert_eq!(report.size_bytes, std::fs::metadata(&dest).unwrap().len());
+        assert!(!temp_dir.path().join("backup.db.partial").exists());
+        assert!(db.backup_to(&dest, |_, _| {}).is_err());
+
+        let copy = Connection::open(&dest).unwrap();
+        let check: String = copy.query_row("PRAGMA integrity_check", [], |row| row.get(0)).unwrap();
+        assert_eq!(check, "ok");
+        let mode: String = copy.query_row("PRAGMA journal_mode", [], |row| row.get(0)).unwrap();
+        assert_eq!(mode, "delete");
+        let (count, last): (i64, String) = copy
+            .query_row("SELECT COUNT(*), MAX(name) FROM files", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert!(count as usize >= committed_before);
+        assert_eq!(last, format!("file{:06}.txt", count - 1));
+    }
+
+    #[test]
+    fn test_upsert_adjusts_stats_by_what_changed() {
+        let db = Database::in_memory(2).unwrap();
+        let mut file = FileEntry::new(PathBuf::from("/data/report.pdf"));
+        file.size = 100;
+        let id = db.insert_file(&file).unwrap();
+        db.insert_file(&FileEntry::new(PathBuf::from("/data/other.txt"))).unwrap();
+
+        file.size = 40;
+        assert_eq!(db.insert_file(&file).unwrap(), id);
+        let stats = db.get_stats().unwrap();
+        assert_eq!((stats.total_files, stats.total_size), (2, 40));
+
+        db.set_link_targets(&[(id, Some(PathBuf::from("/store/report.pdf")))]).unwrap();
+        assert_eq!(db.get_stats().unwrap().total_size, 0);
+        file.size = 70;
+        db.insert_file(&file).unwrap();
+        assert_eq!(db.get_stats().unwrap().total_size, 0);
+        db.set_link_targets(&[(id, None)]).unwrap();
+        assert_eq!(db.get_stats().unwrap().total_size, 70);
+
+        file.is_directory = true;
+        db.insert_file(&file).unwrap();
+        let stats = db.get_stats().unwrap();
+        assert_eq!((stats.total_files, stats.total_directories, stats.total_size), (1, 1, 0));
+        assert_eq!(db.stats_drift().unwrap(), None);
+    }
+
+    #[test]
+    fn test_recompute_stats_repairs_drift() {
+        let db = Database::in_memory(2).unwrap();
+        let mut file = FileEntry::new(PathBuf::from("/data/a.txt"));
+        file.size = 10;
+        db.insert_file(&file).unwrap();
+        db.writer
+            .get()
+            .unwrap()
+            .execute("DELETE FROM files", [])
+            .unwrap();
+
+        let drift = db.stats_drift().unwrap().unwrap();
+        assert_eq!((drift.stored.files, drift.counted.files), (1, 0));
+        assert_eq!(db.get_stats().unwrap().total_files, 1);
+
+        assert_eq!(db.recompute_stats().unwrap(), StatsCounters::default());
+        assert_eq!(db.stats_drift().unwrap(), None);
+        assert_eq!(db.get_stats().unwrap().total_files, 0);
+    }
+
+    #[derive(Debug, Clone)]
+    enum StatsOp {
+        Insert(usize, u64, bool),
+        Batch(Vec<(usize, u64, bool)>),
+        Delete(usize),
+        Content(usize, u8),
+        Alias(usize, bool),
+        Evict(usize),
+        Clear,
+    }
+
+    fn stats_op() -> impl proptest::strategy::Strategy<Value = StatsOp> {
+        use proptest::prelude::*;
+
+        let entry = (0..8usize, 0..5_000u64, any::<bool>());
+        prop_oneof![
+            4 => entry.clone().prop_map(|(path, size, dir)| StatsOp::Insert(path, size, dir)),
+            2 => proptest::collection::vec(entry, 1..6).prop_map(StatsOp::Batch),
+            2 => (0..8usize).prop_map(StatsOp::Delete),
+            2 => (0..8usize, 0..3u8).prop_map(|(path, text)| StatsOp::Content(path, text)),
+            1 => (0..8usize, any::<bool>()).prop_map(|(path, alias)| StatsOp::Alias(path, alias)),
+            1 => (0..4usize).prop_map(StatsOp::Evict),
+            1 => Just(StatsOp::Clear),
+        ]
+    }
+
+    fn apply_stats_op(db: &Database, op: &StatsOp) {
+        let path = |i: usize| PathBuf::from(format!("/stats/{}/file{}", i % 3, i));
+        let entry = |&(i, size, directory): &(usize, u64, bool)| {
+            let mut entry = FileEntry::new(path(i));
+            entry.size = size;
+            entry.is_directory = directory;
+            entry
+        };
+        let id = |i: usize| db.find_by_path(&path(i)).unwrap().and_then(|entry| entry.id);
+
+        match op {
+            StatsOp::Insert(i, size, directory) => {
+                db.insert_file(&entry(&(*i, *size, *directory))).unwrap();
+            }
+            StatsOp::Batch(entries) => {
+                let entries: Vec<FileEntry> = entries.iter().map(entry).collect();
+                db.insert_files_batch(&entries).unwrap();
+            }
+            StatsOp::Delete(i) => db.delete_by_path(&path(*i)).unwrap(),
+            StatsOp::Content(i, text) => {
+                if let Some(id) = id(*i) {
+                    let text = format!("content {}", text);
+                    let preview = ContentPreview {
+                        preview: text.clone(),
+                        word_count: 2,
+                        line_count: 1,
+                        encoding: "UTF-8".to_string(),
+                        content_hash: crate::utils::hash::hash_string(&text),
+                        sampled: false,
+                    };
+                    db.insert_content(id, &preview).unwrap();
+                }
+            }
+            StatsOp::Alias(i, alias) => {
+                if let Some(id) = id(*i) {
+                    let target = alias.then(|| PathBuf::from("/store/target"));
+                    db.set_link_targets(&[(id, target)]).unwrap();
+                }
+            }
+            StatsOp::Evict(limit) => {
+                db.evict_least_recently_accessed_content(*limit).unwrap();
+            }
+            StatsOp::Clear => db.clear_all().unwrap(),
+        }
+    }
+
+    proptest::proptest! {
+        #![proptest_config(proptest::test_runner::Config::with_cases(48))]
+
+        #[test]
+        fn test_stats_counters_always_match_a_recount(
+            ops in proptest::collection::vec(stats_op(), 1..40),
+            compact: bool,
+        ) {
+            let layout = if compact { PathLayout::Compact } else { PathLayout::Legacy };
+            let db = Database::in_memory_with_layout(2, layout).unwrap();
+            for op in &ops {
+                apply_stats_op(&db, op);
+                proptest::prop_assert_eq!(db.stats_drift().unwrap(), None, "after {:?}", op);
+            }
+
+            let stats = db.get_stats().unwrap();
+            let writer = db.writer.get().unwrap();
+            let counted = StatsCounters::count(&writer).unwrap();
+            proptest::prop_assert_eq!(stats.total_files as i64, counted.files);
+            proptest::prop_assert_eq!(stats.total_directories as i64, counted.directories);
+            proptest::prop_assert_eq!(stats.total_size as i64, counted.total_size);
+            proptest::prop_assert_eq!(stats.indexed_files as i64, counted.content_indexed);
+        }
+    }
+}
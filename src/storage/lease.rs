@@ -0,0 +1,248 @@
+use crate::core::config::SearchConfig;
+use crate::core::error::{Result, SearchError};
+use crate::core::types::{LeaseWait, WriteLease};
+use crate::storage::Database;
+use chrono::Utc;
+use parking_lot::Mutex;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How often a waiting writer checks whether the lease has come free.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Holds an index's write lease on behalf of one engine.
+///
+/// The lease is taken when the first operation starts and released when the last one ends,
+/// so nested and concurrent operations of the same engine share it. While it is held, a
+/// background thread heartbeats it so other processes can tell a long run from a crash.
+pub struct WriteLeaseKeeper {
+    database: Arc<Database>,
+    holder_id: String,
+    pid: u32,
+    hostname: String,
+    wait: Duration,
+    stale_after: Duration,
+    state: Mutex<LeaseState>,
+}
+
+#[derive(Default)]
+struct LeaseState {
+    holds: usize,
+    heartbeat: Option<Heartbeat>,
+}
+
+struct Heartbeat {
+    stop: mpsc::Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl WriteLeaseKeeper {
+    pub fn new(database: Arc<Database>, config: &SearchConfig) -> Self {
+        Self {
+            database,
+            holder_id: uuid::Uuid::new_v4().to_string(),
+            pid: std::process::id(),
+            hostname: hostname(),
+            wait: Duration::from_secs(config.write_lease_wait_secs),
+            stale_after: Duration::from_secs(config.write_lease_stale_secs),
+            state: Mutex::new(LeaseState::default()),
+        }
+    }
+
+    pub fn holder_id(&self) -> &str {
+        &self.holder_id
+    }
+
+    /// The lease currently recorded in the index, whoever holds it.
+    pub fn current(&self) -> Result<Option<WriteLease>> {
+        self.database.write_lease()
+    }
+
+    /// Takes the lease, waiting up to `write_lease_wait_secs` for another holder.
+    pub fn acquire(self: &Arc<Self>) -> Result<WriteLeaseGuard> {
+        self.acquire_with(LeaseWait::Wait(self.wait), |_| {})
+    }
+
+    /// Takes the lease, calling `on_wait` each time a different holder is found in the way.
+    pub fn acquire_with(
+        self: &Arc<Self>,
+        wait: LeaseWait,
+        mut on_wait: impl FnMut(&WriteLease),
+    ) -> Result<WriteLeaseGuard> {
+        let deadline = match wait {
+            LeaseWait::Wait(timeout) => Some(Instant::now() + timeout),
+            LeaseWait::NoWait | LeaseWait::Steal => None,
+        };
+        let mut last_holder: Option<String> = None;
+
+        loop {
+            let holder = match self.try_hold(wait == LeaseWait::Steal)? {
+                None => return Ok(WriteLeaseGuard::new(Arc::clone(self))),
+                Some(holder) => holder,
+            };
+
+            if deadline.map_or(true, |deadline| Instant::now() >= deadline) {
+                return Err(SearchError::WriteLeaseHeld {
+                    pid: holder.pid,
+                    hostname: holder.hostname,
+                });
+            }
+            if last_holder.as_deref() != Some(holder.holder_id.as_str()) {
+                on_wait(&holder);
+                last_holder = Some(holder.holder_id);
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Takes the lease only if it is free right now.
+    pub fn try_acquire(self: &Arc<Self>) -> Result<Option<WriteLeaseGuard>> {
+        Ok(self
+            .try_hold(false)?
+            .is_none()
+            .then(|| WriteLeaseGuard::new(Arc::clone(self))))
+    }
+
+    /// Adds a hold, recording the lease first when this is the only one. Returns the
+    /// holder in the way when the lease could not be taken.
+    fn try_hold(&self, steal: bool) -> Result<Option<WriteLease>> {
+        let mut state = self.state.lock();
+        if state.holds > 0 {
+            state.holds += 1;
+            return Ok(None);
+        }
+
+        let now = Utc::now();
+        let lease = WriteLease {
+            holder_id: self.holder_id.clone(),
+            pid: self.pid,
+            hostname: self.hostname.clone(),
+            acquired_at: now,
+            heartbeat_at: now,
+        };
+        let stale_secs = self.stale_after.as_secs().min(u32::MAX as u64) as i64;
+        let stale_after = chrono::Duration::seconds(stale_secs);
+        if let Some(holder) = self.database.try_acquire_write_lease(&lease, stale_after, steal)? {
+            return Ok(Some(holder));
+        }
+
+        state.holds = 1;
+        state.heartbeat = Some(self.start_heartbeat());
+        Ok(None)
+    }
+
+    fn start_heartbeat(&self) -> Heartbeat {
+        let (stop, stopped) = mpsc::channel();
+        let database = Arc::clone(&self.database);
+        let holder_id = self.holder_id.clone();
+        let interval = (self.stale_after / 3).max(POLL_INTERVAL);
+
+        let handle = thread::spawn(move || {
+            // Anything but a timeout means the lease is being released.
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                match database.heartbeat_write_lease(&holder_id, Utc::now()) {
+                    Ok(true) => {}
+                    Ok(false) => log::warn!("The index write lease was taken over"),
+                    Err(e) => log::warn!("Failed to heartbeat the index write lease: {}", e),
+                }
+            }
+        });
+
+        Heartbeat { stop, handle }
+    }
+
+    fn release(&self) {
+        let mut state = self.state.lock();
+        state.holds = state.holds.saturating_sub(1);
+        if state.holds > 0 {
+            return;
+        }
+
+        if let Some(heartbeat) = state.heartbeat.take() {
+            let _ = heartbeat.stop.send(());
+            let _ = heartbeat.handle.join();
+        }
+        if let Err(e) = self.database.release_write_lease(&self.holder_id) {
+            log::error!("Failed to release the index write lease: {}", e);
+        }
+    }
+}
+
+/// Keeps the write lease held until dropped.
+#[must_use = "the write lease is released when the guard is dropped"]
+pub struct WriteLeaseGuard {
+    keeper: Arc<WriteLeaseKeeper>,
+}
+
+impl WriteLeaseGuard {
+    fn new(keeper: Arc<WriteLeaseKeeper>) -> Self {
+        Self { keeper }
+    }
+}
+
+impl Drop for WriteLeaseGuard {
+    fn drop(&mut self) {
+        self.keeper.release();
+    }
+}
+
+fn hostname() -> String {
+    ["HOSTNAME", "COMPUTERNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok())
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "localhost".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keeper(database: &Arc<Database>, stale_secs: u64) -> Arc<WriteLeaseKeeper> {
+        let config = SearchConfig {
+            write_lease_wait_secs: 0,
+            write_lease_stale_secs: stale_secs,
+            ..SearchConfig::default()
+        };
+        Arc::new(WriteLeaseKeeper::new(Arc::clone(database), &config))
+    }
+
+    #[test]
+    fn test_holds_nest_and_release_with_the_last_guard() {
+        let database = Arc::new(Database::in_memory(4).unwrap());
+        let first = keeper(&database, 30);
+        let second = keeper(&database, 30);
+
+        let outer = first.acquire().unwrap();
+        let inner = first.acquire().unwrap();
+        assert!(second.try_acquire().unwrap().is_none());
+
+        drop(inner);
+        let current = database.write_lease().unwrap().unwrap();
+        assert_eq!(current.holder_id, first.holder_id());
+        assert_eq!(current.pid, std::process::id());
+
+        drop(outer);
+        assert!(database.write_lease().unwrap().is_none());
+        assert!(second.try_acquire().unwrap().is_some());
+    }
+
+    #[test]
+    fn test_steal_takes_over_a_live_lease() {
+        let database = Arc::new(Database::in_memory(4).unwrap());
+        let stuck = keeper(&database, 30);
+        let rescuer = keeper(&database, 30);
+
+        let _held = stuck.acquire().unwrap();
+        let _stolen = rescuer.acquire_with(LeaseWait::Steal, |_| {}).unwrap();
+
+        let current = database.write_lease().unwrap().unwrap();
+        assert_eq!(current.holder_id, rescuer.holder_id());
+        // The old holder's heartbeat and release no longer touch the new lease.
+        assert!(!database.heartbeat_write_lease(stuck.holder_id(), Utc::now()).unwrap());
+    }
+}
@@ -1,25 +1,47 @@
 use crate::core::error::{Result, SearchError};
-use crate::storage::schema;
+use crate::core::types::StatsCounters;
+use crate::storage::chunks::{split_chunks, DEFAULT_FTS_CHUNK_BYTES};
+use crate::storage::schema::{self, PathLayout};
+use crate::storage::settings::{keys, Settings};
+use crate::utils::hash::hash_string;
+use crate::utils::normalizer::{fold_case, TextNormalizer, UnicodeNormalizer};
 use chrono::Utc;
-use rusqlite::Connection;
+use rusqlite::{params, Connection, ErrorCode, OptionalExtension, Transaction, TransactionBehavior};
+use std::thread;
+use std::time::Duration;
+
+const BACKFILL_BATCH_SIZE: usize = 1000;
+const INIT_LOCK_ATTEMPTS: u32 = 8;
+const INIT_LOCK_BACKOFF_MS: u64 = 50;
 
 pub struct MigrationManager;
 
 impl MigrationManager {
+    #[cfg(test)]
     pub fn initialize_schema(conn: &Connection) -> Result<()> {
-        for pragma in schema::OPTIMIZE_PRAGMAS {
+        Self::initialize_schema_with_layout(conn, PathLayout::Legacy)
+    }
+
+    /// Like `initialize_schema`, creating a new index with `layout`. Existing indexes keep the
+    /// layout they were created with.
+    pub fn initialize_schema_with_layout(conn: &Connection, layout: PathLayout) -> Result<()> {
+        for pragma in schema::DATABASE_PRAGMAS {
             // Use query_row() instead of execute() because PRAGMAs return results
             let _ = conn.query_row(pragma, [], |_| Ok(()));
         }
 
-        conn.execute(schema::CREATE_SCHEMA_VERSION_TABLE, [])?;
+        // Another process may be initializing the same file; the immediate transaction
+        // serializes us behind it, and the version is re-read once the lock is held.
+        let tx = Self::begin_exclusive(conn)?;
 
-        let current_version = Self::get_current_version(conn)?;
+        tx.execute(schema::CREATE_SCHEMA_VERSION_TABLE, [])?;
+
+        let current_version = Self::get_current_version(&tx)?;
 
         if current_version == 0 {
-            Self::apply_initial_schema(conn)?;
+            Self::apply_initial_schema(&tx, layout)?;
         } else if current_version < schema::CURRENT_SCHEMA_VERSION {
-            Self::migrate(conn, current_version, schema::CURRENT_SCHEMA_VERSION)?;
+            Self::migrate(&tx, current_version, schema::CURRENT_SCHEMA_VERSION)?;
         } else if current_version > schema::CURRENT_SCHEMA_VERSION {
             return Err(SearchError::IndexCorrupted(format!(
                 "Database schema version {} is newer than supported version {}",
@@ -27,10 +49,44 @@ impl MigrationManager {
             )));
         }
 
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    pub fn apply_connection_pragmas(conn: &Connection) -> rusqlite::Result<()> {
+        for pragma in schema::CONNECTION_PRAGMAS {
+            conn.query_row(pragma, [], |_| Ok(())).or_else(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(()),
+                e => Err(e),
+            })?;
+        }
         Ok(())
     }
 
-    fn get_current_version(conn: &Connection) -> Result<i32> {
+    fn begin_exclusive(conn: &Connection) -> Result<Transaction<'_>> {
+        let mut backoff = Duration::from_millis(INIT_LOCK_BACKOFF_MS);
+        let mut attempt = 1;
+
+        loop {
+            match Transaction::new_unchecked(conn, TransactionBehavior::Immediate) {
+                Ok(tx) => return Ok(tx),
+                Err(rusqlite::Error::SqliteFailure(err, _))
+                    if matches!(err.code, ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+                        && attempt < INIT_LOCK_ATTEMPTS =>
+                {
+                    log::debug!("Schema lock busy, retrying in {:?}", backoff);
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                Err(e) => return Err(SearchError::Database(e)),
+            }
+        }
+    }
+
+    /// Newest migration applied to the database behind `conn`; 0 when none has been.
+    pub fn get_current_version(conn: &Connection) -> Result<i32> {
         let version: rusqlite::Result<Option<i32>> = conn.query_row(
             "SELECT MAX(version) FROM schema_version",
             [],
@@ -45,27 +101,57 @@ impl MigrationManager {
         }
     }
 
-    fn apply_initial_schema(conn: &Connection) -> Result<()> {
-        let tx = conn.unchecked_transaction()?;
-
+    fn apply_initial_schema(conn: &Connection, layout: PathLayout) -> Result<()> {
         for statement in schema::get_all_table_creation_statements() {
-            tx.execute(statement, [])?;
+            let statement = match layout {
+                PathLayout::Compact if statement == schema::CREATE_FILES_TABLE => {
+                    schema::CREATE_COMPACT_FILES_TABLE
+                }
+                _ => statement,
+            };
+            conn.execute(statement, [])?;
         }
 
         for statement in schema::get_all_index_creation_statements() {
-            tx.execute(statement, [])?;
+            if layout == PathLayout::Compact && schema::CREATE_FILES_INDEXES.contains(&statement) {
+                continue;
+            }
+            conn.execute(statement, [])?;
+        }
+        if layout == PathLayout::Compact {
+            for statement in schema::CREATE_COMPACT_FILES_INDEXES {
+                conn.execute(statement, [])?;
+            }
         }
 
-        tx.execute(
-            "INSERT INTO schema_version (version, applied_at) VALUES (?1, ?2)",
-            [schema::CURRENT_SCHEMA_VERSION.to_string(), Utc::now().to_rfc3339()],
-        )?;
-
-        tx.commit()?;
+        Self::create_name_generation(conn)?;
+        Self::set_path_layout(conn, layout)?;
+        StatsCounters::default().store(conn)?;
+        Self::record_version(conn, schema::CURRENT_SCHEMA_VERSION)
+    }
 
+    /// Seeds the name generation and creates the triggers on `files` that advance it.
+    pub fn create_name_generation(conn: &Connection) -> Result<()> {
+        conn.execute(schema::SEED_NAME_GENERATION, [])?;
+        for statement in schema::CREATE_NAME_GENERATION_TRIGGERS {
+            conn.execute(statement, [])?;
+        }
         Ok(())
     }
 
+    pub fn path_layout(conn: &Connection) -> Result<PathLayout> {
+        match Settings::new(conn).get_str(keys::PATH_LAYOUT)? {
+            None => Ok(PathLayout::Legacy),
+            Some(value) => PathLayout::parse(&value).ok_or_else(|| {
+                SearchError::IndexCorrupted(format!("Unknown path layout: {}", value))
+            }),
+        }
+    }
+
+    pub fn set_path_layout(conn: &Connection, layout: PathLayout) -> Result<()> {
+        Settings::new(conn).set_str(keys::PATH_LAYOUT, layout.as_str())
+    }
+
     fn migrate(conn: &Connection, from: i32, to: i32) -> Result<()> {
         for version in from..to {
             Self::apply_migration(conn, version, version + 1)?;
@@ -74,39 +160,582 @@ impl MigrationManager {
     }
 
     fn apply_migration(conn: &Connection, _from: i32, to: i32) -> Result<()> {
-        let tx = conn.unchecked_transaction()?;
+        if to == 2 {
+            if !Self::column_exists(conn, "files", "name_normalized")? {
+                conn.execute("ALTER TABLE files ADD COLUMN name_normalized TEXT", [])?;
+            }
+            conn.execute(schema::CREATE_FILES_NAME_NORMALIZED_INDEX, [])?;
+            Self::backfill_normalized_names(conn, &UnicodeNormalizer, BACKFILL_BATCH_SIZE)?;
+        }
+
+        if to == 3 {
+            conn.execute(schema::CREATE_DELETED_FILES_TABLE, [])?;
+            for statement in schema::CREATE_DELETED_FILES_INDEXES {
+                conn.execute(statement, [])?;
+            }
+            conn.execute(schema::CREATE_FILES_LAST_VERIFIED_INDEX, [])?;
+        }
+
+        if to == 4 {
+            Self::migrate_content_to_blobs(conn)?;
+        }
+
+        if to == 5 {
+            conn.execute(schema::CREATE_INDEXED_ROOTS_TABLE, [])?;
+        }
+
+        if to == 6 {
+            conn.execute(schema::CREATE_AUDIT_LOG_TABLE, [])?;
+            for statement in schema::CREATE_AUDIT_LOG_INDEXES {
+                conn.execute(statement, [])?;
+            }
+        }
+
+        if to == 7 {
+            conn.execute(schema::CREATE_SAVED_SEARCHES_TABLE, [])?;
+        }
+
+        if to == 8 && !Self::column_exists(conn, "content_blobs", "sampled")? {
+            conn.execute(
+                "ALTER TABLE content_blobs ADD COLUMN sampled INTEGER NOT NULL DEFAULT 0",
+                [],
+            )?;
+        }
+
+        if to == 9 {
+            if !Self::column_exists(conn, "files", "parent_id")? {
+                conn.execute("ALTER TABLE files ADD COLUMN parent_id INTEGER", [])?;
+            }
+            conn.execute(schema::CREATE_PATH_DIRS_TABLE, [])?;
+        }
+
+        if to == 10 {
+            conn.execute(schema::CREATE_TIMESTAMP_ADJUSTMENTS_TABLE, [])?;
+        }
+
+        if to == 11 {
+            conn.execute(schema::CREATE_USER_TAGS_TABLE, [])?;
+            for statement in schema::CREATE_USER_TAGS_INDEXES {
+                conn.execute(statement, [])?;
+            }
+        }
+
+        if to == 12 {
+            conn.execute(schema::CREATE_IMAGE_DIMENSIONS_TABLE, [])?;
+            for statement in schema::CREATE_IMAGE_DIMENSIONS_INDEXES {
+                conn.execute(statement, [])?;
+            }
+        }
+
+        if to == 13 {
+            conn.execute(schema::CREATE_PENDING_CONTENT_TABLE, [])?;
+            for statement in schema::CREATE_PENDING_CONTENT_INDEXES {
+                conn.execute(statement, [])?;
+            }
+            conn.execute(schema::CREATE_ENRICHMENT_ROOTS_TABLE, [])?;
+        }
+
+        if to == 14 {
+            Self::refold_normalized_names(conn, BACKFILL_BATCH_SIZE)?;
+        }
+
+        if to == 15 {
+            conn.execute(schema::CREATE_SCRIPTS_TABLE, [])?;
+            for statement in schema::CREATE_SCRIPTS_INDEXES {
+                conn.execute(statement, [])?;
+            }
+        }
+
+        if to == 16 {
+            conn.execute(schema::CREATE_DOCUMENT_FIELDS_TABLE, [])?;
+            for statement in schema::CREATE_DOCUMENT_FIELDS_INDEXES {
+                conn.execute(statement, [])?;
+            }
+        }
+
+        if to == 17 {
+            conn.execute(schema::CREATE_INDEX_METADATA_TABLE, [])?;
+            for (old, new) in schema::RENAMED_SETTING_KEYS {
+                conn.execute(
+                    "UPDATE index_metadata SET key = ?2 WHERE key = ?1",
+                    params![old, new],
+                )?;
+            }
+        }
+
+        if to == 18 {
+            conn.execute(schema::CREATE_LINK_TARGETS_TABLE, [])?;
+        }
+
+        if to == 19 {
+            Self::chunk_full_text_rows(conn)?;
+        }
+
+        if to == 20 {
+            conn.execute(schema::CREATE_FILE_PERMISSIONS_TABLE, [])?;
+        }
+
+        if to == 21 {
+            conn.execute(schema::CREATE_FILES_INDEXED_AT_INDEX, [])?;
+            StatsCounters::count(conn)?.store(conn)?;
+        }
+
+        if to == 22 {
+            conn.execute(schema::CREATE_INCONSISTENT_FILES_TABLE, [])?;
+        }
+
+        if to == 23 {
+            conn.execute(schema::CREATE_NAME_GENERATION_TABLE, [])?;
+            Self::create_name_generation(conn)?;
+        }
+
+        // Extensions were stored as the file names had them; SQLite's `LOWER` folds ASCII
+        // only, like `to_ascii_lowercase` does for new rows.
+        if to == 24 {
+            conn.execute(
+                "UPDATE files SET extension = LOWER(extension) WHERE extension != LOWER(extension)",
+                [],
+            )?;
+        }
+
+        Self::record_version(conn, to)
+    }
+
+    // Earlier versions kept one preview per file and one FTS row per file. Existing previews
+    // are keyed by the hash of the stored preview, as the full text is no longer available.
+    fn migrate_content_to_blobs(conn: &Connection) -> Result<()> {
+        conn.execute(schema::CREATE_CONTENT_BLOBS_TABLE, [])?;
+
+        let mut rows = Vec::new();
+        if Self::column_exists(conn, "file_contents", "content_preview")? {
+            rows = conn
+                .prepare(
+                    "SELECT file_id, content_preview, word_count, line_count, encoding FROM file_contents",
+                )?
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                        row.get::<_, Option<i64>>(2)?,
+                        row.get::<_, Option<i64>>(3)?,
+                        row.get::<_, Option<String>>(4)?,
+                    ))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            conn.execute("DROP TABLE file_contents", [])?;
+        }
+
+        conn.execute(schema::CREATE_FILE_CONTENTS_TABLE, [])?;
 
-        tx.execute(
-            "INSERT INTO schema_version (version, applied_at) VALUES (?1, ?2)",
-            [to.to_string(), Utc::now().to_rfc3339()],
+        for (file_id, preview, word_count, line_count, encoding) in rows {
+            let hash = hash_string(&preview);
+            conn.execute(
+                r#"
+                INSERT INTO content_blobs (hash, content_preview, word_count, line_count, encoding, refcount)
+                VALUES (?1, ?2, ?3, ?4, ?5, 1)
+                ON CONFLICT(hash) DO UPDATE SET refcount = refcount + 1
+                "#,
+                params![hash, preview, word_count, line_count, encoding],
+            )?;
+            conn.execute(
+                "INSERT INTO file_contents (file_id, content_hash) VALUES (?1, ?2)",
+                params![file_id, hash],
+            )?;
+        }
+
+        conn.execute("DROP TABLE IF EXISTS files_fts", [])?;
+        conn.execute(schema::CREATE_FILES_FTS_TABLE, [])?;
+        conn.execute(
+            "INSERT INTO files_fts (content_hash, content) SELECT hash, content_preview FROM content_blobs",
+            [],
         )?;
 
-        tx.commit()?;
+        for statement in schema::CREATE_FILE_CONTENTS_INDEXES {
+            conn.execute(statement, [])?;
+        }
+
+        Ok(())
+    }
+
+    // FTS5 tables can't gain columns, so the table is recreated with the tokenizer it had and
+    // each blob's text split into chunks of the default size.
+    fn chunk_full_text_rows(conn: &Connection) -> Result<()> {
+        let sql: Option<String> = conn
+            .query_row(
+                "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'files_fts'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(sql) = sql else {
+            return Ok(());
+        };
+
+        let rows = conn
+            .prepare("SELECT content_hash, content FROM files_fts")?
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        if Self::column_exists(conn, "files_fts", "chunk_index")? {
+            // Created by an earlier migration in this run, whose transaction it can't be
+            // dropped and recreated in.
+            conn.execute("DELETE FROM files_fts", [])?;
+        } else {
+            let tokenizer = sql
+                .split_once("tokenize = '")
+                .and_then(|(_, rest)| rest.rfind('\'').map(|end| rest[..end].replace("''", "'")))
+                .unwrap_or_else(|| schema::DEFAULT_FTS_TOKENIZER.to_string());
+            conn.execute("DROP TABLE files_fts", [])?;
+            conn.execute(&schema::create_fts_table_sql("files_fts", &tokenizer), [])?;
+        }
+        {
+            let mut insert = conn.prepare(
+                r#"
+                INSERT INTO files_fts (content_hash, chunk_index, byte_offset, content)
+                VALUES (?1, ?2, ?3, ?4)
+                "#,
+            )?;
+            for (hash, content) in &rows {
+                for chunk in split_chunks(content, DEFAULT_FTS_CHUNK_BYTES) {
+                    insert.execute(params![
+                        hash,
+                        chunk.index as i64,
+                        chunk.byte_offset as i64,
+                        chunk.text
+                    ])?;
+                }
+            }
+        }
 
         Ok(())
     }
 
+    fn record_version(conn: &Connection, version: i32) -> Result<()> {
+        conn.execute(
+            "INSERT OR IGNORE INTO schema_version (version, applied_at) VALUES (?1, ?2)",
+            [version.to_string(), Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    fn column_exists(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+        let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+        let exists = stmt
+            .query_map([], |row| row.get::<_, String>(1))?
+            .filter_map(|name| name.ok())
+            .any(|name| name == column);
+        Ok(exists)
+    }
+
+    // Names used to be lowercased rather than case-folded. Folding the stored form again
+    // brings it in line with what either built-in normalizer now produces, without needing
+    // to know which one wrote it.
+    fn refold_normalized_names(conn: &Connection, batch_size: usize) -> Result<usize> {
+        let mut select = conn.prepare(
+            "SELECT id, name_normalized FROM files
+             WHERE id > ?1 AND name_normalized IS NOT NULL ORDER BY id LIMIT ?2",
+        )?;
+        let mut update = conn.prepare("UPDATE files SET name_normalized = ?1 WHERE id = ?2")?;
+        let mut last_id = 0;
+        let mut updated = 0;
+
+        loop {
+            let batch = select
+                .query_map(params![last_id, batch_size], |row| {
+                    Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            let Some(&(id, _)) = batch.last() else {
+                break;
+            };
+            last_id = id;
+
+            for (id, name) in &batch {
+                let folded = fold_case(name);
+                if folded != name.as_str() {
+                    update.execute(params![folded, id])?;
+                    updated += 1;
+                }
+            }
+        }
+
+        Ok(updated)
+    }
+
+    pub fn backfill_normalized_names(
+        conn: &Connection,
+        normalizer: &dyn TextNormalizer,
+        batch_size: usize,
+    ) -> Result<usize> {
+        let mut select = conn.prepare(
+            "SELECT id, name FROM files WHERE name_normalized IS NULL LIMIT ?1",
+        )?;
+        let mut update = conn.prepare("UPDATE files SET name_normalized = ?1 WHERE id = ?2")?;
+        let mut updated = 0;
+
+        loop {
+            let batch = select
+                .query_map(params![batch_size], |row| {
+                    Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            if batch.is_empty() {
+                break;
+            }
+
+            for (id, name) in &batch {
+                update.execute(params![normalizer.normalize(name), id])?;
+            }
+
+            updated += batch.len();
+        }
+
+        Ok(updated)
+    }
+
+    #[cfg(test)]
     pub fn verify_schema(conn: &Connection) -> Result<bool> {
         let current_version = Self::get_current_version(conn)?;
         Ok(current_version == schema::CURRENT_SCHEMA_VERSION)
     }
 
-    pub fn rebuild_indexes(conn: &Connection) -> Result<()> {
-        let tx = conn.unchecked_transaction()?;
+}
 
-        for statement in schema::get_all_index_creation_statements() {
-            let drop_statement = statement.replace("CREATE INDEX IF NOT EXISTS", "DROP INDEX IF EXISTS");
-            let drop_statement = drop_statement.split(" ON ").next().unwrap_or("");
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            if !drop_statement.is_empty() {
-                let _ = tx.execute(drop_statement, []);
-            }
+    #[test]
+    fn test_migration_backfills_normalized_names() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(schema::CREATE_SCHEMA_VERSION_TABLE, []).unwrap();
+        conn.execute(
+            r#"
+            CREATE TABLE files (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                path TEXT NOT NULL UNIQUE,
+                name TEXT NOT NULL,
+                extension TEXT,
+                size INTEGER NOT NULL,
+                created_at INTEGER,
+                modified_at INTEGER,
+                accessed_at INTEGER,
+                is_directory INTEGER NOT NULL DEFAULT 0,
+                is_hidden INTEGER NOT NULL DEFAULT 0,
+                is_symlink INTEGER NOT NULL DEFAULT 0,
+                parent_path TEXT,
+                mime_type TEXT,
+                file_hash TEXT,
+                indexed_at INTEGER NOT NULL,
+                last_verified INTEGER NOT NULL
+            )
+            "#,
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO schema_version (version, applied_at) VALUES (1, ?1)",
+            [Utc::now().to_rfc3339()],
+        )
+        .unwrap();
 
-            tx.execute(statement, [])?;
+        for i in 0..(BACKFILL_BATCH_SIZE + 5) {
+            conn.execute(
+                "INSERT INTO files (path, name, size, indexed_at, last_verified) VALUES (?1, ?2, 0, 0, 0)",
+                params![format!("/photos/München-{}.jpg", i), format!("München-{}.jpg", i)],
+            )
+            .unwrap();
         }
 
-        tx.commit()?;
+        MigrationManager::initialize_schema(&conn).unwrap();
+        assert!(MigrationManager::verify_schema(&conn).unwrap());
 
-        Ok(())
+        let missing: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM files WHERE name_normalized IS NULL",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(missing, 0);
+
+        let normalized: String = conn
+            .query_row(
+                "SELECT name_normalized FROM files WHERE name = 'München-0.jpg'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(normalized, "munchen-0.jpg");
+    }
+
+    #[test]
+    fn test_migration_refolds_lowercased_names() {
+        let conn = Connection::open_in_memory().unwrap();
+        MigrationManager::initialize_schema(&conn).unwrap();
+        let lowercased = [(1, "Straße.txt", "straße.txt"), (2, "ΟΔΟΣ.md", "οδος.md")];
+        for (id, name, stored) in lowercased {
+            conn.execute(
+                "INSERT INTO files
+                 (id, path, name, name_normalized, size, indexed_at, last_verified)
+                 VALUES (?1, ?2, ?3, ?4, 0, 0, 0)",
+                params![id, format!("/docs/{}", name), name, stored],
+            )
+            .unwrap();
+        }
+        conn.execute("DELETE FROM schema_version", []).unwrap();
+        MigrationManager::record_version(&conn, 13).unwrap();
+
+        MigrationManager::initialize_schema(&conn).unwrap();
+        assert!(MigrationManager::verify_schema(&conn).unwrap());
+
+        let folded: Vec<String> = conn
+            .prepare("SELECT name_normalized FROM files ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(folded, ["strasse.txt", "οδοσ.md"]);
+    }
+
+    #[test]
+    fn test_migration_namespaces_setting_keys() {
+        let conn = Connection::open_in_memory().unwrap();
+        MigrationManager::initialize_schema_with_layout(&conn, PathLayout::Compact).unwrap();
+        conn.execute_batch(
+            "UPDATE index_metadata SET key = 'path_layout' WHERE key = 'storage.path_layout';
+             INSERT INTO index_metadata (key, value, updated_at)
+             VALUES ('write_lease', '{}', 0);
+             DELETE FROM schema_version;",
+        )
+        .unwrap();
+        MigrationManager::record_version(&conn, 16).unwrap();
+
+        MigrationManager::initialize_schema(&conn).unwrap();
+
+        let keys: Vec<String> = Settings::new(&conn)
+            .all()
+            .unwrap()
+            .into_iter()
+            .map(|setting| setting.key)
+            .collect();
+        let expected = [
+            keys::WRITE_LEASE,
+            keys::STATS_CONTENT_INDEXED,
+            keys::STATS_DIRECTORIES,
+            keys::STATS_FILES,
+            keys::STATS_TOTAL_SIZE,
+            keys::PATH_LAYOUT,
+        ];
+        assert_eq!(keys, expected);
+        assert_eq!(MigrationManager::path_layout(&conn).unwrap(), PathLayout::Compact);
+    }
+
+    #[test]
+    fn test_migration_counts_stats() {
+        let conn = Connection::open_in_memory().unwrap();
+        MigrationManager::initialize_schema(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO files (path, name, size, is_directory, indexed_at, last_verified)
+             VALUES ('/a.txt', 'a.txt', 10, 0, 0, 0), ('/b.txt', 'b.txt', 32, 0, 0, 0),
+                    ('/src', 'src', 4096, 1, 0, 0);
+             DELETE FROM index_metadata WHERE key LIKE 'stats.%';
+             DROP INDEX idx_files_indexed_at;
+             DELETE FROM schema_version;",
+        )
+        .unwrap();
+        MigrationManager::record_version(&conn, 20).unwrap();
+
+        MigrationManager::initialize_schema(&conn).unwrap();
+
+        let counters = StatsCounters::load(&conn).unwrap().unwrap();
+        assert_eq!((counters.files, counters.directories, counters.total_size), (2, 1, 42));
+        let indexed_at_index: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE name = 'idx_files_indexed_at'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(indexed_at_index, 1);
+    }
+
+    #[test]
+    fn test_migration_lowercases_extensions() {
+        let conn = Connection::open_in_memory().unwrap();
+        MigrationManager::initialize_schema(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO files (path, name, extension, size, indexed_at, last_verified)
+             VALUES ('/p/IMG_0001.JPG', 'IMG_0001.JPG', 'JPG', 0, 0, 0),
+                    ('/p/scan.Jpg', 'scan.Jpg', 'Jpg', 0, 0, 0),
+                    ('/p/a.jpg', 'a.jpg', 'jpg', 0, 0, 0),
+                    ('/p/Makefile', 'Makefile', NULL, 0, 0, 0);
+             DELETE FROM schema_version;",
+        )
+        .unwrap();
+        MigrationManager::record_version(&conn, 23).unwrap();
+
+        MigrationManager::initialize_schema(&conn).unwrap();
+        assert!(MigrationManager::verify_schema(&conn).unwrap());
+
+        let rows: Vec<(String, Option<String>)> = conn
+            .prepare("SELECT name, extension FROM files ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        let jpg = Some("jpg".to_string());
+        assert_eq!(
+            rows,
+            [
+                ("IMG_0001.JPG".to_string(), jpg.clone()),
+                ("scan.Jpg".to_string(), jpg.clone()),
+                ("a.jpg".to_string(), jpg),
+                ("Makefile".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_migration_chunks_full_text_rows_keeping_tokenizer() {
+        let conn = Connection::open_in_memory().unwrap();
+        MigrationManager::initialize_schema(&conn).unwrap();
+        let text = format!("{}needle\n", "hay\n".repeat(DEFAULT_FTS_CHUNK_BYTES / 4));
+        conn.execute_batch(
+            "DROP TABLE files_fts;
+             CREATE VIRTUAL TABLE files_fts USING fts5(
+                 content_hash UNINDEXED, content, tokenize = 'trigram'
+             );
+             DELETE FROM schema_version;",
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO files_fts (content_hash, content) VALUES ('h1', ?1)",
+            params![text],
+        )
+        .unwrap();
+        MigrationManager::record_version(&conn, 18).unwrap();
+
+        MigrationManager::initialize_schema(&conn).unwrap();
+
+        let chunks: Vec<(i64, i64)> = conn
+            .prepare("SELECT chunk_index, byte_offset FROM files_fts ORDER BY chunk_index")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(chunks, vec![(0, 0), (1, DEFAULT_FTS_CHUNK_BYTES as i64)]);
+        // Trigrams match inside words, which the default tokenizer doesn't.
+        let hits: i64 = conn
+            .query_row("SELECT COUNT(*) FROM files_fts WHERE files_fts MATCH 'eedl'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(hits, 1);
     }
 }
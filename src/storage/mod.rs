@@ -1,10 +1,26 @@
+pub mod backup;
 pub mod bloom;
 pub mod cache;
+pub mod chunks;
+pub(crate) mod counters;
 pub mod database;
-pub mod migrations;
-pub mod schema;
+pub mod lease;
+pub(crate) mod migrations;
+pub mod names;
+pub(crate) mod schema;
+pub mod settings;
+pub mod store;
+pub mod thumbnails;
+pub mod undo;
 
+pub use backup::RestoreSource;
 pub use bloom::FileBloomFilter;
-pub use cache::LruCache;
-pub use database::Database;
-pub use migrations::MigrationManager;
+pub use cache::{LruCache, MissCache};
+pub use database::{Database, PoolSizes};
+pub use lease::{WriteLeaseGuard, WriteLeaseKeeper};
+pub use names::{NameField, NameIndex};
+pub use schema::PathLayout;
+pub use settings::{Setting, Settings};
+pub use store::FileStore;
+pub use thumbnails::ThumbnailCache;
+pub use undo::UndoLog;
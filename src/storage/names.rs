@@ -0,0 +1,571 @@
+use crate::core::error::{IoResultExt, Result};
+use crate::core::types::NameIndexUsage;
+use crate::storage::Database;
+use crate::utils::path::normalize_separators;
+use memchr::memmem::Finder;
+use parking_lot::RwLock;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+const SIDECAR_MAGIC: &[u8; 8] = b"RFNAMES1";
+
+/// What a name index lookup matches against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameField {
+    /// The name or normalized name, as [`Database::search_by_name`] matches them.
+    Name,
+    /// The full path, as [`Database::search_by_path_contains`] matches it.
+    Path,
+}
+
+/// Every entry's name and path held in memory, so plain name and path searches skip SQLite.
+///
+/// Lookups give the same ids, in the same order, as the SQL they stand in for: text is
+/// folded to lowercase for ASCII only, as `LIKE` does, and `%` and `_` in a name pattern
+/// are wildcards there too. The copy is tied to the index's name generation; once the
+/// index has moved on, lookups return `None` while a rebuild runs in the background.
+pub struct NameIndex {
+    database: Arc<Database>,
+    sidecar: Option<PathBuf>,
+    snapshot: RwLock<Option<Arc<Snapshot>>>,
+    rebuilding: AtomicBool,
+}
+
+impl NameIndex {
+    pub fn new(database: Arc<Database>) -> Self {
+        Self {
+            database,
+            sidecar: None,
+            snapshot: RwLock::new(None),
+            rebuilding: AtomicBool::new(false),
+        }
+    }
+
+    /// Saves each build to `path` and loads from it on `start`.
+    pub fn with_sidecar(mut self, path: PathBuf) -> Self {
+        self.sidecar = Some(path);
+        self
+    }
+
+    /// Where the name index of the index at `index_path` is saved.
+    pub fn sidecar_for(index_path: &Path) -> PathBuf {
+        let mut path = index_path.as_os_str().to_owned();
+        path.push(".names");
+        PathBuf::from(path)
+    }
+
+    /// Loads the saved copy if it is still current, and otherwise builds one in the
+    /// background.
+    pub fn start(self: &Arc<Self>) {
+        match self.load_sidecar() {
+            Ok(true) => return,
+            Ok(false) => {}
+            Err(e) => log::warn!("Ignoring the saved name index: {}", e),
+        }
+        self.rebuild_in_background();
+    }
+
+    /// Builds the copy again if the index changed since the last build. Returns whether it
+    /// did.
+    pub fn refresh(&self) -> Result<bool> {
+        if self.current()?.is_some() {
+            return Ok(false);
+        }
+
+        let mut builder = SnapshotBuilder::default();
+        let generation = self.database.for_each_name(|id, name, normalized, path| {
+            builder.push(id, name, normalized, path)
+        })?;
+        let snapshot = builder.finish(generation);
+        if let Some(sidecar) = self.sidecar.as_ref().filter(|_| !snapshot.missing_normalized) {
+            if let Err(e) = snapshot.save(sidecar) {
+                log::warn!("Could not save the name index: {}", e);
+            }
+        }
+        *self.snapshot.write() = Some(Arc::new(snapshot));
+        Ok(true)
+    }
+
+    /// Drops the copy and the saved one, for when the index was replaced wholesale and its
+    /// generation no longer says anything about the copy.
+    pub fn invalidate(&self) {
+        *self.snapshot.write() = None;
+        if let Some(sidecar) = &self.sidecar {
+            if let Err(e) = fs::remove_file(sidecar) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    log::warn!("Could not remove the saved name index: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Ids of the entries whose `field` contains `pattern`, in id order, at most `limit`.
+    /// `None` when the copy is not current or cannot answer the pattern exactly.
+    pub fn lookup(
+        self: &Arc<Self>,
+        field: NameField,
+        pattern: &str,
+        limit: usize,
+    ) -> Result<Option<Vec<i64>>> {
+        if pattern.contains('\0') {
+            return Ok(None);
+        }
+        let Some(snapshot) = self.current()? else {
+            self.rebuild_in_background();
+            return Ok(None);
+        };
+
+        let ids = match field {
+            NameField::Name if snapshot.missing_normalized => return Ok(None),
+            NameField::Name => {
+                let normalized = self.database.normalizer().normalize(pattern);
+                snapshot.names_like(pattern, &normalized, limit)
+            }
+            NameField::Path => snapshot.paths_containing(&normalize_separators(pattern), limit),
+        };
+        Ok(Some(ids))
+    }
+
+    /// Entries held, memory used and whether the copy is current.
+    pub fn usage(&self) -> Result<NameIndexUsage> {
+        let snapshot = self.snapshot.read().clone();
+        let current = match &snapshot {
+            Some(snapshot) => snapshot.generation == self.database.name_generation()?,
+            None => false,
+        };
+        Ok(NameIndexUsage {
+            entries: snapshot.as_ref().map_or(0, |snapshot| snapshot.ids.len()),
+            memory_bytes: snapshot.as_ref().map_or(0, |snapshot| snapshot.memory_bytes()),
+            current,
+        })
+    }
+
+    fn current(&self) -> Result<Option<Arc<Snapshot>>> {
+        let Some(snapshot) = self.snapshot.read().clone() else {
+            return Ok(None);
+        };
+        let generation = self.database.name_generation()?;
+        Ok((snapshot.generation == generation).then_some(snapshot))
+    }
+
+    fn rebuild_in_background(self: &Arc<Self>) {
+        if self.rebuilding.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let index = Arc::clone(self);
+        std::thread::spawn(move || {
+            if let Err(e) = index.refresh() {
+                log::warn!("Could not build the name index: {}", e);
+            }
+            index.rebuilding.store(false, Ordering::Release);
+        });
+    }
+
+    fn load_sidecar(&self) -> Result<bool> {
+        let Some(sidecar) = &self.sidecar else {
+            return Ok(false);
+        };
+        let bytes = match fs::read(sidecar) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e).at_path(sidecar),
+        };
+        let Some(snapshot) = Snapshot::decode(&bytes) else {
+            log::warn!("Ignoring a malformed name index at {}", sidecar.display());
+            return Ok(false);
+        };
+        if snapshot.generation != self.database.name_generation()? {
+            return Ok(false);
+        }
+        *self.snapshot.write() = Some(Arc::new(snapshot));
+        Ok(true)
+    }
+}
+
+/// One text per entry, `\0`-separated, with ASCII letters lowercased.
+#[derive(Default)]
+struct Column {
+    text: String,
+    starts: Vec<usize>,
+}
+
+impl Column {
+    fn push(&mut self, text: &str) {
+        self.starts.push(self.text.len());
+        self.text.push_str(text);
+        self.text[self.starts[self.starts.len() - 1]..].make_ascii_lowercase();
+        self.text.push('\0');
+    }
+
+    fn from_text(text: String) -> Self {
+        let mut starts = Vec::new();
+        let mut start = 0;
+        for end in memchr::memchr_iter(0, text.as_bytes()) {
+            starts.push(start);
+            start = end + 1;
+        }
+        Self { text, starts }
+    }
+
+    fn len(&self) -> usize {
+        self.starts.len()
+    }
+
+    fn get(&self, entry: usize) -> &str {
+        let end = self.starts.get(entry + 1).map_or(self.text.len(), |next| *next);
+        &self.text[self.starts[entry]..end - 1]
+    }
+
+    /// Entries containing `needle`, ascending, at most `limit`.
+    fn containing(&self, needle: &str, limit: usize) -> Vec<usize> {
+        if needle.is_empty() {
+            return (0..self.len().min(limit)).collect();
+        }
+
+        let finder = Finder::new(needle.as_bytes());
+        let haystack = self.text.as_bytes();
+        let mut found = Vec::new();
+        let mut from = 0;
+        while found.len() < limit {
+            let Some(at) = finder.find(&haystack[from..]) else {
+                break;
+            };
+            let entry = self.starts.partition_point(|start| *start <= from + at) - 1;
+            found.push(entry);
+            // A match never spans the separator, so the next one is in a later entry.
+            from = self.starts.get(entry + 1).map_or(haystack.len(), |next| *next);
+        }
+        found
+    }
+
+    /// Entries matching the `LIKE` pattern `%pattern%`, ascending, at most `limit`.
+    fn like(&self, pattern: &str, limit: usize) -> Vec<usize> {
+        let pattern = format!("%{}%", pattern);
+        (0..self.len())
+            .filter(|entry| like(pattern.as_bytes(), self.get(*entry).as_bytes()))
+            .take(limit)
+            .collect()
+    }
+
+    fn memory_bytes(&self) -> u64 {
+        (self.text.capacity() + self.starts.capacity() * std::mem::size_of::<usize>()) as u64
+    }
+}
+
+/// SQLite's `LIKE` with no escape character, on text already folded to lowercase: `%`
+/// matches any run of characters and `_` exactly one.
+fn like(pattern: &[u8], text: &[u8]) -> bool {
+    let char_len = |lead: u8| match lead {
+        0xf0..=0xff => 4,
+        0xe0..=0xef => 3,
+        0xc0..=0xdf => 2,
+        _ => 1,
+    };
+    let (mut p, mut t) = (0, 0);
+    let mut retry = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'%') => {
+                p += 1;
+                retry = Some((p, t));
+                continue;
+            }
+            Some(b'_') => {
+                p += 1;
+                t += char_len(text[t]);
+                continue;
+            }
+            Some(&byte) if byte == text[t] => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            _ => {}
+        }
+        // Let the last `%` take one more character and try again from there.
+        let Some((after_percent, taken)) = retry else {
+            return false;
+        };
+        let next = taken + char_len(text[taken]);
+        retry = Some((after_percent, next));
+        p = after_percent;
+        t = next;
+    }
+    pattern[p..].iter().all(|byte| *byte == b'%')
+}
+
+#[derive(Default)]
+struct SnapshotBuilder {
+    ids: Vec<i64>,
+    names: Column,
+    normalized: Column,
+    paths: Column,
+    missing_normalized: bool,
+}
+
+impl SnapshotBuilder {
+    fn push(&mut self, id: i64, name: &str, normalized: Option<&str>, path: &str) {
+        self.ids.push(id);
+        self.names.push(name);
+        self.missing_normalized |= normalized.is_none();
+        self.normalized.push(normalized.unwrap_or_default());
+        self.paths.push(path);
+    }
+
+    fn finish(self, generation: u64) -> Snapshot {
+        Snapshot {
+            generation,
+            ids: self.ids,
+            names: self.names,
+            normalized: self.normalized,
+            paths: self.paths,
+            missing_normalized: self.missing_normalized,
+        }
+    }
+}
+
+struct Snapshot {
+    generation: u64,
+    ids: Vec<i64>,
+    names: Column,
+    normalized: Column,
+    paths: Column,
+    /// Some entry had no normalized name, as while names are normalized again. `NULL`
+    /// never matches where `""` would, so name lookups go to SQLite until a rebuild, and
+    /// the snapshot is not saved.
+    missing_normalized: bool,
+}
+
+impl Snapshot {
+    /// `name LIKE '%pattern%' OR name_normalized LIKE '%normalized%'`, in id order.
+    fn names_like(&self, pattern: &str, normalized: &str, limit: usize) -> Vec<i64> {
+        let matching = |column: &Column, pattern: &str| {
+            let pattern = pattern.to_ascii_lowercase();
+            if pattern.contains(['%', '_']) {
+                column.like(&pattern, limit)
+            } else {
+                column.containing(&pattern, limit)
+            }
+        };
+        let mut entries = matching(&self.names, pattern);
+        entries.extend(matching(&self.normalized, normalized));
+        entries.sort_unstable();
+        entries.dedup();
+        entries.truncate(limit);
+        entries.into_iter().map(|entry| self.ids[entry]).collect()
+    }
+
+    /// Paths containing `needle` with ASCII case folded, in id order.
+    fn paths_containing(&self, needle: &str, limit: usize) -> Vec<i64> {
+        self.paths
+            .containing(&needle.to_ascii_lowercase(), limit)
+            .into_iter()
+            .map(|entry| self.ids[entry])
+            .collect()
+    }
+
+    fn memory_bytes(&self) -> u64 {
+        (self.ids.capacity() * std::mem::size_of::<i64>()) as u64
+            + self.names.memory_bytes()
+            + self.normalized.memory_bytes()
+            + self.paths.memory_bytes()
+    }
+
+    /// Writes the snapshot under a temporary name, then renames it over `path`, so a reader
+    /// never sees a partial file.
+    fn save(&self, path: &Path) -> Result<()> {
+        let mut bytes = Vec::with_capacity(self.memory_bytes() as usize);
+        bytes.extend_from_slice(SIDECAR_MAGIC);
+        bytes.extend_from_slice(&self.generation.to_le_bytes());
+        bytes.extend_from_slice(&(self.ids.len() as u64).to_le_bytes());
+        for id in &self.ids {
+            bytes.extend_from_slice(&id.to_le_bytes());
+        }
+        for column in [&self.names, &self.normalized, &self.paths] {
+            bytes.extend_from_slice(&(column.text.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(column.text.as_bytes());
+        }
+
+        let partial = path.with_extension("names.partial");
+        fs::write(&partial, bytes).at_path(&partial)?;
+        fs::rename(&partial, path).at_path(path)?;
+        Ok(())
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader(bytes.strip_prefix(SIDECAR_MAGIC.as_slice())?);
+        let generation = reader.number()?;
+        let count = reader.number()? as usize;
+        let ids = reader
+            .take(count.checked_mul(8)?)?
+            .chunks_exact(8)
+            .map(|id| i64::from_le_bytes(id.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        let mut column = || {
+            let len = reader.number()? as usize;
+            let text = String::from_utf8(reader.take(len)?.to_vec()).ok()?;
+            Some(Column::from_text(text)).filter(|column| column.len() == count)
+        };
+        let (names, normalized, paths) = (column()?, column()?, column()?);
+        reader.0.is_empty().then_some(Self {
+            generation,
+            ids,
+            names,
+            normalized,
+            paths,
+            missing_normalized: false,
+        })
+    }
+}
+
+/// Reads a saved snapshot front to back.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(taken)
+    }
+
+    fn number(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::FileEntry;
+    use crate::storage::PathLayout;
+    use tempfile::TempDir;
+
+    fn populated(layout: PathLayout) -> Arc<Database> {
+        let db = Database::in_memory_with_layout(4, layout).unwrap();
+        let names = [
+            "Notes.TXT",
+            "notes.md",
+            "old_notes.md",
+            "100% done.md",
+            "München.png",
+            "MÜNCHEN.txt",
+            "Straße.rs",
+            "back\\slash",
+            "time 10:30.log",
+            "ǅemal.md",
+        ];
+        let dirs = ["/notes", "/src/Notes", "/fotos/München"];
+        let entries: Vec<FileEntry> = dirs
+            .iter()
+            .flat_map(|dir| names.iter().map(move |name| format!("{}/{}", dir, name)))
+            .map(|path| FileEntry::new(PathBuf::from(path)))
+            .collect();
+        db.insert_files_batch(&entries).unwrap();
+        for path in ["/notes/notes.md", "/src/Notes/München.png"] {
+            db.delete_by_path(Path::new(path)).unwrap();
+        }
+        Arc::new(db)
+    }
+
+    fn ids(entries: Vec<FileEntry>) -> Vec<i64> {
+        entries.into_iter().map(|entry| entry.id.unwrap()).collect()
+    }
+
+    #[test]
+    fn test_lookups_match_sql() {
+        let patterns = [
+            "", "notes", "NOTES", "n", "münchen", "MÜNCHEN", "munchen", "strasse", "Straße",
+            "old_", "_otes", "o_d", "100%", "%", "%.md", "n%s", "_", "__", "back\\", "\\slash",
+            "10:30", ".md", "ǆ", "dž", "/notes/", "NOTES/", "src/n", "fotos\\m", "zzz",
+        ];
+        for layout in [PathLayout::Legacy, PathLayout::Compact] {
+            let db = populated(layout);
+            let names = Arc::new(NameIndex::new(Arc::clone(&db)));
+            assert!(names.refresh().unwrap());
+            assert!(!names.refresh().unwrap());
+
+            for pattern in patterns {
+                for limit in [0, 1, 3, 1000] {
+                    assert_eq!(
+                        names.lookup(NameField::Name, pattern, limit).unwrap(),
+                        Some(ids(db.search_by_name(pattern, limit).unwrap())),
+                        "name {:?} limit {} in {:?}",
+                        pattern,
+                        limit,
+                        layout
+                    );
+                    assert_eq!(
+                        names.lookup(NameField::Path, pattern, limit).unwrap(),
+                        Some(ids(db.search_by_path_contains(pattern, limit).unwrap())),
+                        "path {:?} limit {} in {:?}",
+                        pattern,
+                        limit,
+                        layout
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_like_matches_wildcards() {
+        for (pattern, text, expected) in [
+            ("%", "", true),
+            ("_", "", false),
+            ("_", "ü", true),
+            ("__", "ü", false),
+            ("m_nchen", "münchen", true),
+            ("%chen", "münchen", true),
+            ("%ch%n%", "münchen", true),
+            ("%x%", "münchen", false),
+            ("a%b%c", "aXbYbZc", true),
+            ("a%b%c", "aXbYbZ", false),
+        ] {
+            assert_eq!(like(pattern.as_bytes(), text.as_bytes()), expected, "{:?}", pattern);
+        }
+    }
+
+    #[test]
+    fn test_sidecar_tracks_the_generation() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("index.db");
+        let db = Arc::new(Database::new(&index_path, 4).unwrap());
+        db.insert_file(&FileEntry::new(PathBuf::from("/data/report.pdf"))).unwrap();
+        let sidecar = NameIndex::sidecar_for(&index_path);
+        let open = || NameIndex::new(Arc::clone(&db)).with_sidecar(sidecar.clone());
+
+        let names = open();
+        names.refresh().unwrap();
+        assert!(sidecar.exists());
+        let usage = names.usage().unwrap();
+        assert_eq!(usage.entries, 1);
+        assert!(usage.memory_bytes > 0);
+        assert!(usage.current);
+        assert!(open().load_sidecar().unwrap());
+
+        // Metadata alone leaves the copy current; a new name does not.
+        let mut entry = db.find_by_path(Path::new("/data/report.pdf")).unwrap().unwrap();
+        entry.size = 42;
+        db.insert_file(&entry).unwrap();
+        assert!(names.usage().unwrap().current);
+        db.insert_file(&FileEntry::new(PathBuf::from("/data/summary.pdf"))).unwrap();
+        assert!(!names.usage().unwrap().current);
+        assert!(!open().load_sidecar().unwrap());
+
+        names.refresh().unwrap();
+        assert_eq!(names.usage().unwrap().entries, 2);
+        assert!(open().load_sidecar().unwrap());
+
+        fs::write(&sidecar, b"RFNAMES1 truncated").unwrap();
+        assert!(!open().load_sidecar().unwrap());
+
+        names.invalidate();
+        assert!(!sidecar.exists());
+        assert_eq!(names.usage().unwrap().entries, 0);
+    }
+}
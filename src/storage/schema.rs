@@ -1,4 +1,39 @@
-pub const CURRENT_SCHEMA_VERSION: i32 = 1;
+use crate::storage::settings;
+
+pub const CURRENT_SCHEMA_VERSION: i32 = 24;
+
+/// `index_metadata` keys written before settings were namespaced, with their current names.
+pub const RENAMED_SETTING_KEYS: &[(&str, &str)] = &[
+    ("path_layout", settings::keys::PATH_LAYOUT),
+    ("write_lease", settings::keys::WRITE_LEASE),
+];
+
+/// How an index stores file paths, fixed when it is created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PathLayout {
+    /// Every row stores its full path and parent path.
+    #[default]
+    Legacy,
+    /// Rows reference their parent directory in `path_dirs` and store only the final component.
+    Compact,
+}
+
+impl PathLayout {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PathLayout::Legacy => "legacy",
+            PathLayout::Compact => "compact",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "legacy" => Some(PathLayout::Legacy),
+            "compact" => Some(PathLayout::Compact),
+            _ => None,
+        }
+    }
+}
 
 pub const CREATE_SCHEMA_VERSION_TABLE: &str = r#"
 CREATE TABLE IF NOT EXISTS schema_version (
@@ -12,6 +47,33 @@ CREATE TABLE IF NOT EXISTS files (
     id INTEGER PRIMARY KEY AUTOINCREMENT,
     path TEXT NOT NULL UNIQUE,
     name TEXT NOT NULL,
+    name_normalized TEXT,
+    extension TEXT,
+    size INTEGER NOT NULL,
+    created_at INTEGER,
+    modified_at INTEGER,
+    accessed_at INTEGER,
+    is_directory INTEGER NOT NULL DEFAULT 0,
+    is_hidden INTEGER NOT NULL DEFAULT 0,
+    is_symlink INTEGER NOT NULL DEFAULT 0,
+    parent_path TEXT,
+    mime_type TEXT,
+    file_hash TEXT,
+    indexed_at INTEGER NOT NULL,
+    last_verified INTEGER NOT NULL,
+    parent_id INTEGER
+)
+"#;
+
+// Compact indexes leave `path` and `parent_path` NULL and store the parent directory as
+// `parent_id` into path_dirs, keeping only the final component in `name`. Entries whose path
+// cannot be rebuilt that way keep their full `path` and a NULL `parent_id`.
+pub const CREATE_COMPACT_FILES_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS files (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    path TEXT UNIQUE,
+    name TEXT NOT NULL,
+    name_normalized TEXT,
     extension TEXT,
     size INTEGER NOT NULL,
     created_at INTEGER,
@@ -24,12 +86,27 @@ CREATE TABLE IF NOT EXISTS files (
     mime_type TEXT,
     file_hash TEXT,
     indexed_at INTEGER NOT NULL,
-    last_verified INTEGER NOT NULL
+    last_verified INTEGER NOT NULL,
+    parent_id INTEGER
+)
+"#;
+
+// One row per directory component; roots (`/`, `C:\`) have parent_id 0.
+pub const CREATE_PATH_DIRS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS path_dirs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    parent_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    UNIQUE (parent_id, name)
 )
 "#;
 
+pub const CREATE_FILES_NAME_NORMALIZED_INDEX: &str =
+    "CREATE INDEX IF NOT EXISTS idx_files_name_normalized ON files(name_normalized)";
+
 pub const CREATE_FILES_INDEXES: &[&str] = &[
     "CREATE INDEX IF NOT EXISTS idx_files_name ON files(name COLLATE NOCASE)",
+    CREATE_FILES_NAME_NORMALIZED_INDEX,
     "CREATE INDEX IF NOT EXISTS idx_files_extension ON files(extension)",
     "CREATE INDEX IF NOT EXISTS idx_files_path ON files(path)",
     "CREATE INDEX IF NOT EXISTS idx_files_parent_path ON files(parent_path)",
@@ -37,29 +114,91 @@ pub const CREATE_FILES_INDEXES: &[&str] = &[
     "CREATE INDEX IF NOT EXISTS idx_files_size ON files(size)",
     "CREATE INDEX IF NOT EXISTS idx_files_is_directory ON files(is_directory)",
     "CREATE INDEX IF NOT EXISTS idx_files_file_hash ON files(file_hash)",
+    CREATE_FILES_LAST_VERIFIED_INDEX,
+    CREATE_FILES_INDEXED_AT_INDEX,
 ];
 
+pub const CREATE_COMPACT_FILES_INDEXES: &[&str] = &[
+    "CREATE INDEX IF NOT EXISTS idx_files_name ON files(name COLLATE NOCASE)",
+    CREATE_FILES_NAME_NORMALIZED_INDEX,
+    "CREATE UNIQUE INDEX IF NOT EXISTS idx_files_parent_id_name ON files(parent_id, name)",
+    "CREATE INDEX IF NOT EXISTS idx_files_extension ON files(extension)",
+    "CREATE INDEX IF NOT EXISTS idx_files_modified_at ON files(modified_at)",
+    "CREATE INDEX IF NOT EXISTS idx_files_size ON files(size)",
+    "CREATE INDEX IF NOT EXISTS idx_files_is_directory ON files(is_directory)",
+    "CREATE INDEX IF NOT EXISTS idx_files_file_hash ON files(file_hash)",
+    CREATE_FILES_LAST_VERIFIED_INDEX,
+    CREATE_FILES_INDEXED_AT_INDEX,
+];
+
+pub const CREATE_FILES_LAST_VERIFIED_INDEX: &str =
+    "CREATE INDEX IF NOT EXISTS idx_files_last_verified ON files(last_verified)";
+
+// Lets the statistics find the last update without scanning the table.
+pub const CREATE_FILES_INDEXED_AT_INDEX: &str =
+    "CREATE INDEX IF NOT EXISTS idx_files_indexed_at ON files(indexed_at)";
+
+pub const CREATE_DELETED_FILES_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS deleted_files (
+    path TEXT PRIMARY KEY,
+    deleted_at INTEGER NOT NULL
+)
+"#;
+
+pub const CREATE_DELETED_FILES_INDEXES: &[&str] = &[
+    "CREATE INDEX IF NOT EXISTS idx_deleted_files_deleted_at ON deleted_files(deleted_at)",
+];
+
+// One FTS row per chunk of each distinct content blob, at `byte_offset` in the blob's text;
+// file_contents maps blobs back to files.
 pub const CREATE_FILES_FTS_TABLE: &str = r#"
 CREATE VIRTUAL TABLE IF NOT EXISTS files_fts USING fts5(
-    file_id,
-    name,
-    path,
+    content_hash UNINDEXED,
+    chunk_index UNINDEXED,
+    byte_offset UNINDEXED,
     content,
     tokenize = 'porter unicode61'
 )
 "#;
 
-pub const CREATE_FILE_CONTENTS_TABLE: &str = r#"
-CREATE TABLE IF NOT EXISTS file_contents (
-    file_id INTEGER PRIMARY KEY,
+pub const DEFAULT_FTS_TOKENIZER: &str = "porter unicode61";
+
+/// `CREATE_FILES_FTS_TABLE` for another table name and tokenizer.
+pub fn create_fts_table_sql(table: &str, tokenizer: &str) -> String {
+    CREATE_FILES_FTS_TABLE
+        .replacen("files_fts USING", &format!("{} USING", table), 1)
+        .replacen(
+            &format!("'{}'", DEFAULT_FTS_TOKENIZER),
+            &format!("'{}'", tokenizer.replace('\'', "''")),
+            1,
+        )
+}
+
+pub const CREATE_CONTENT_BLOBS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS content_blobs (
+    hash TEXT PRIMARY KEY,
     content_preview TEXT,
     word_count INTEGER,
     line_count INTEGER,
     encoding TEXT,
-    FOREIGN KEY (file_id) REFERENCES files(id) ON DELETE CASCADE
+    refcount INTEGER NOT NULL DEFAULT 0,
+    sampled INTEGER NOT NULL DEFAULT 0
 )
 "#;
 
+pub const CREATE_FILE_CONTENTS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS file_contents (
+    file_id INTEGER PRIMARY KEY,
+    content_hash TEXT NOT NULL,
+    FOREIGN KEY (file_id) REFERENCES files(id) ON DELETE CASCADE,
+    FOREIGN KEY (content_hash) REFERENCES content_blobs(hash)
+)
+"#;
+
+pub const CREATE_FILE_CONTENTS_INDEXES: &[&str] = &[
+    "CREATE INDEX IF NOT EXISTS idx_file_contents_content_hash ON file_contents(content_hash)",
+];
+
 pub const CREATE_EXCLUSION_RULES_TABLE: &str = r#"
 CREATE TABLE IF NOT EXISTS exclusion_rules (
     id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -77,6 +216,55 @@ CREATE TABLE IF NOT EXISTS index_metadata (
 )
 "#;
 
+// Options are stored as JSON so later updates can reuse them per root.
+pub const CREATE_INDEXED_ROOTS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS indexed_roots (
+    path TEXT PRIMARY KEY,
+    options TEXT NOT NULL,
+    file_count INTEGER NOT NULL,
+    indexed_at INTEGER NOT NULL
+)
+"#;
+
+// Written when a root is indexed from a local-time filesystem such as FAT.
+pub const CREATE_TIMESTAMP_ADJUSTMENTS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS timestamp_adjustments (
+    root TEXT PRIMARY KEY,
+    filesystem TEXT,
+    utc_offset_secs INTEGER NOT NULL,
+    recorded_at INTEGER NOT NULL
+)
+"#;
+
+// Append-only; survives clear_all so compliance history outlives index rebuilds.
+pub const CREATE_AUDIT_LOG_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    path TEXT NOT NULL,
+    event_type TEXT NOT NULL,
+    occurred_at INTEGER NOT NULL,
+    size INTEGER,
+    file_hash TEXT,
+    watch_id TEXT
+)
+"#;
+
+pub const CREATE_AUDIT_LOG_INDEXES: &[&str] = &[
+    "CREATE INDEX IF NOT EXISTS idx_audit_log_occurred_at ON audit_log(occurred_at)",
+    "CREATE INDEX IF NOT EXISTS idx_audit_log_path ON audit_log(path)",
+];
+
+pub const CREATE_SAVED_SEARCHES_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS saved_searches (
+    name TEXT PRIMARY KEY,
+    query TEXT NOT NULL,
+    sort TEXT,
+    created_at INTEGER NOT NULL,
+    last_run_at INTEGER,
+    last_result_count INTEGER
+)
+"#;
+
 pub const CREATE_SEARCH_HISTORY_TABLE: &str = r#"
 CREATE TABLE IF NOT EXISTS search_history (
     id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -94,29 +282,198 @@ CREATE TABLE IF NOT EXISTS access_log (
 )
 "#;
 
+// Tags assigned through the app, independent of anything stored on the files themselves.
+pub const CREATE_USER_TAGS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS user_tags (
+    file_id INTEGER NOT NULL,
+    tag TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (file_id, tag),
+    FOREIGN KEY (file_id) REFERENCES files(id) ON DELETE CASCADE
+)
+"#;
+
+pub const CREATE_USER_TAGS_INDEXES: &[&str] =
+    &["CREATE INDEX IF NOT EXISTS idx_user_tags_tag ON user_tags(tag)"];
+
+// Pixel sizes read from image headers when `extract_image_metadata` is on.
+pub const CREATE_IMAGE_DIMENSIONS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS image_dimensions (
+    file_id INTEGER PRIMARY KEY,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    FOREIGN KEY (file_id) REFERENCES files(id) ON DELETE CASCADE
+)
+"#;
+
+pub const CREATE_IMAGE_DIMENSIONS_INDEXES: &[&str] = &[
+    "CREATE INDEX IF NOT EXISTS idx_image_dimensions_width ON image_dimensions(width)",
+    "CREATE INDEX IF NOT EXISTS idx_image_dimensions_height ON image_dimensions(height)",
+];
+
+// Interpreters named by the `#!` lines of extension-less files when `detect_shebangs` is on.
+pub const CREATE_SCRIPTS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS scripts (
+    file_id INTEGER PRIMARY KEY,
+    interpreter TEXT NOT NULL,
+    language TEXT NOT NULL,
+    is_executable INTEGER NOT NULL DEFAULT 0,
+    FOREIGN KEY (file_id) REFERENCES files(id) ON DELETE CASCADE
+)
+"#;
+
+pub const CREATE_SCRIPTS_INDEXES: &[&str] =
+    &["CREATE INDEX IF NOT EXISTS idx_scripts_language ON scripts(language)"];
+
+// Where store aliases point: links into a `symlink_store_roots` directory, indexed by their
+// logical path without content.
+pub const CREATE_LINK_TARGETS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS link_targets (
+    file_id INTEGER PRIMARY KEY,
+    target_path TEXT NOT NULL,
+    FOREIGN KEY (file_id) REFERENCES files(id) ON DELETE CASCADE
+)
+"#;
+
+// Owner, group and mode of entries and their directories, when `record_ownership` is on.
+pub const CREATE_FILE_PERMISSIONS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS file_permissions (
+    file_id INTEGER PRIMARY KEY,
+    uid INTEGER NOT NULL,
+    gid INTEGER NOT NULL,
+    mode INTEGER NOT NULL,
+    parent_uid INTEGER,
+    parent_gid INTEGER,
+    parent_mode INTEGER,
+    FOREIGN KEY (file_id) REFERENCES files(id) ON DELETE CASCADE
+)
+"#;
+
+// Email headers and HTML head fields when `extract_document_fields` is on. `value_folded` is
+// `value` case-folded for `subject:`-style filters.
+pub const CREATE_DOCUMENT_FIELDS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS document_fields (
+    file_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    value TEXT NOT NULL,
+    value_folded TEXT NOT NULL,
+    PRIMARY KEY (file_id, name),
+    FOREIGN KEY (file_id) REFERENCES files(id) ON DELETE CASCADE
+)
+"#;
+
+pub const CREATE_DOCUMENT_FIELDS_INDEXES: &[&str] =
+    &["CREATE INDEX IF NOT EXISTS idx_document_fields_name ON document_fields(name)"];
+
+// Files written by a quick build whose content and image details are still to be read.
+pub const CREATE_PENDING_CONTENT_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS pending_content (
+    file_id INTEGER PRIMARY KEY,
+    root TEXT NOT NULL,
+    FOREIGN KEY (file_id) REFERENCES files(id) ON DELETE CASCADE
+)
+"#;
+
+pub const CREATE_PENDING_CONTENT_INDEXES: &[&str] = &[
+    "CREATE INDEX IF NOT EXISTS idx_pending_content_root ON pending_content(root)",
+];
+
+// Files that changed while their content was read, so their stored size, times and content
+// may not belong together. Updates read them again whatever their metadata says.
+pub const CREATE_INCONSISTENT_FILES_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS inconsistent_files (
+    file_id INTEGER PRIMARY KEY,
+    detected_at INTEGER NOT NULL,
+    FOREIGN KEY (file_id) REFERENCES files(id) ON DELETE CASCADE
+)
+"#;
+
+// Counts every change to the names and paths of entries, so an in-memory copy of them can
+// tell whether it is still current. Kept by the triggers below rather than by each write.
+pub const CREATE_NAME_GENERATION_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS name_generation (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    generation INTEGER NOT NULL
+)
+"#;
+
+pub const SEED_NAME_GENERATION: &str =
+    "INSERT OR IGNORE INTO name_generation (id, generation) VALUES (1, 0)";
+
+// Dropped with the files table, so recreated whenever it is rebuilt.
+pub const CREATE_NAME_GENERATION_TRIGGERS: &[&str] = &[
+    r#"
+    CREATE TRIGGER IF NOT EXISTS files_name_inserted AFTER INSERT ON files
+    BEGIN UPDATE name_generation SET generation = generation + 1; END
+    "#,
+    r#"
+    CREATE TRIGGER IF NOT EXISTS files_name_deleted AFTER DELETE ON files
+    BEGIN UPDATE name_generation SET generation = generation + 1; END
+    "#,
+    r#"
+    CREATE TRIGGER IF NOT EXISTS files_name_updated
+    AFTER UPDATE OF path, name, name_normalized, parent_id ON files
+    WHEN old.path IS NOT new.path OR old.name IS NOT new.name
+        OR old.name_normalized IS NOT new.name_normalized OR old.parent_id IS NOT new.parent_id
+    BEGIN UPDATE name_generation SET generation = generation + 1; END
+    "#,
+];
+
+// How many files each quick-built root queued, for reporting enrichment progress.
+pub const CREATE_ENRICHMENT_ROOTS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS enrichment_roots (
+    root TEXT PRIMARY KEY,
+    total INTEGER NOT NULL,
+    queued_at INTEGER NOT NULL
+)
+"#;
+
 pub const CREATE_ACCESS_LOG_INDEXES: &[&str] = &[
     "CREATE INDEX IF NOT EXISTS idx_access_log_file_id ON access_log(file_id)",
     "CREATE INDEX IF NOT EXISTS idx_access_log_accessed_at ON access_log(accessed_at)",
 ];
 
-pub const OPTIMIZE_PRAGMAS: &[&str] = &[
+// Persisted in the database file; applied once during schema initialization.
+pub const DATABASE_PRAGMAS: &[&str] = &[
+    "PRAGMA page_size = 4096",
     "PRAGMA journal_mode = WAL",
+];
+
+// Scoped to a single connection; applied to every pooled connection as it is opened.
+pub const CONNECTION_PRAGMAS: &[&str] = &[
+    "PRAGMA busy_timeout = 5000",
     "PRAGMA synchronous = NORMAL",
     "PRAGMA cache_size = -64000",
     "PRAGMA temp_store = MEMORY",
     "PRAGMA mmap_size = 268435456",
-    "PRAGMA page_size = 4096",
 ];
 
 pub fn get_all_table_creation_statements() -> Vec<&'static str> {
     vec![
         CREATE_SCHEMA_VERSION_TABLE,
         CREATE_FILES_TABLE,
+        CREATE_PATH_DIRS_TABLE,
+        CREATE_CONTENT_BLOBS_TABLE,
         CREATE_FILE_CONTENTS_TABLE,
         CREATE_EXCLUSION_RULES_TABLE,
         CREATE_INDEX_METADATA_TABLE,
+        CREATE_INDEXED_ROOTS_TABLE,
+        CREATE_TIMESTAMP_ADJUSTMENTS_TABLE,
+        CREATE_AUDIT_LOG_TABLE,
+        CREATE_SAVED_SEARCHES_TABLE,
         CREATE_SEARCH_HISTORY_TABLE,
         CREATE_ACCESS_LOG_TABLE,
+        CREATE_USER_TAGS_TABLE,
+        CREATE_IMAGE_DIMENSIONS_TABLE,
+        CREATE_SCRIPTS_TABLE,
+        CREATE_LINK_TARGETS_TABLE,
+        CREATE_FILE_PERMISSIONS_TABLE,
+        CREATE_DOCUMENT_FIELDS_TABLE,
+        CREATE_PENDING_CONTENT_TABLE,
+        CREATE_ENRICHMENT_ROOTS_TABLE,
+        CREATE_INCONSISTENT_FILES_TABLE,
+        CREATE_NAME_GENERATION_TABLE,
+        CREATE_DELETED_FILES_TABLE,
         CREATE_FILES_FTS_TABLE,
     ]
 }
@@ -125,5 +482,13 @@ pub fn get_all_index_creation_statements() -> Vec<&'static str> {
     let mut indexes = Vec::new();
     indexes.extend_from_slice(CREATE_FILES_INDEXES);
     indexes.extend_from_slice(CREATE_ACCESS_LOG_INDEXES);
+    indexes.extend_from_slice(CREATE_DELETED_FILES_INDEXES);
+    indexes.extend_from_slice(CREATE_FILE_CONTENTS_INDEXES);
+    indexes.extend_from_slice(CREATE_AUDIT_LOG_INDEXES);
+    indexes.extend_from_slice(CREATE_USER_TAGS_INDEXES);
+    indexes.extend_from_slice(CREATE_IMAGE_DIMENSIONS_INDEXES);
+    indexes.extend_from_slice(CREATE_SCRIPTS_INDEXES);
+    indexes.extend_from_slice(CREATE_DOCUMENT_FIELDS_INDEXES);
+    indexes.extend_from_slice(CREATE_PENDING_CONTENT_INDEXES);
     indexes
 }
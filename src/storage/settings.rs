@@ -0,0 +1,261 @@
+use crate::core::error::{Result, SearchError};
+use chrono::{DateTime, TimeZone, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Every key stored in `index_metadata`, namespaced by the feature that owns it.
+pub mod keys {
+    use crate::core::types::MaintenanceTask;
+
+    /// How the files table stores paths: `legacy` or `compact`.
+    pub const PATH_LAYOUT: &str = "storage.path_layout";
+
+    /// The current write lease as JSON.
+    pub const WRITE_LEASE: &str = "lease.write";
+
+    /// Running totals behind the index statistics, adjusted by every write.
+    pub const STATS_FILES: &str = "stats.files";
+    pub const STATS_DIRECTORIES: &str = "stats.directories";
+    pub const STATS_TOTAL_SIZE: &str = "stats.total_size";
+    pub const STATS_CONTENT_INDEXED: &str = "stats.content_indexed";
+
+    /// When a maintenance task last completed, as a Unix timestamp.
+    pub fn maintenance_last_run(task: MaintenanceTask) -> String {
+        format!("maintenance.{}.last_run", task.as_str())
+    }
+
+    /// Keys under this prefix are free for notes; the index never reads them.
+    pub const USER_PREFIX: &str = "user.";
+
+    /// Keys that `filesearch meta set` may change without `--force`.
+    pub fn is_user_settable(key: &str) -> bool {
+        key.strip_prefix(USER_PREFIX)
+            .is_some_and(|name| !name.is_empty())
+    }
+}
+
+/// One stored setting with its raw value.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Setting {
+    pub key: String,
+    pub value: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Typed access to the per-index settings in `index_metadata`.
+///
+/// Values are stored as text: booleans as `true`/`false`, integers in decimal and anything
+/// else as JSON. Reading a key that was never set gives `None`; reading one whose value
+/// doesn't have the requested type is an error, since only this index wrote it.
+pub struct Settings<'c> {
+    conn: &'c Connection,
+}
+
+impl<'c> Settings<'c> {
+    pub fn new(conn: &'c Connection) -> Self {
+        Self { conn }
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<Setting>> {
+        let setting = self
+            .conn
+            .prepare_cached("SELECT key, value, updated_at FROM index_metadata WHERE key = ?1")?
+            .query_row([key], Self::row_to_setting)
+            .optional()?;
+        Ok(setting)
+    }
+
+    pub fn get_str(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.get(key)?.map(|setting| setting.value))
+    }
+
+    pub fn get_bool(&self, key: &str) -> Result<Option<bool>> {
+        self.get_parsed(key, "a boolean", |value| match value {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        })
+    }
+
+    pub fn get_i64(&self, key: &str) -> Result<Option<i64>> {
+        self.get_parsed(key, "an integer", |value| value.parse().ok())
+    }
+
+    pub fn get_json<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        self.get_parsed(key, "valid JSON", |value| serde_json::from_str(value).ok())
+    }
+
+    pub fn set_str(&self, key: &str, value: &str) -> Result<()> {
+        self.conn
+            .prepare_cached(
+                "INSERT INTO index_metadata (key, value, updated_at) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(key) DO UPDATE
+                 SET value = excluded.value, updated_at = excluded.updated_at",
+            )?
+            .execute(params![key, value, Utc::now().timestamp()])?;
+        Ok(())
+    }
+
+    pub fn set_bool(&self, key: &str, value: bool) -> Result<()> {
+        self.set_str(key, if value { "true" } else { "false" })
+    }
+
+    pub fn set_i64(&self, key: &str, value: i64) -> Result<()> {
+        self.set_str(key, &value.to_string())
+    }
+
+    /// Adds `delta` to an integer setting in one statement, so concurrent writers don't lose
+    /// each other's updates. A missing key starts from zero.
+    pub fn add_i64(&self, key: &str, delta: i64) -> Result<()> {
+        self.conn
+            .prepare_cached(
+                "INSERT INTO index_metadata (key, value, updated_at) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(key) DO UPDATE
+                 SET value = CAST(value AS INTEGER) + excluded.value,
+                     updated_at = excluded.updated_at",
+            )?
+            .execute(params![key, delta, Utc::now().timestamp()])?;
+        Ok(())
+    }
+
+    pub fn set_json<T: Serialize>(&self, key: &str, value: &T) -> Result<()> {
+        let value = serde_json::to_string(value).map_err(|e| SearchError::Parse(e.to_string()))?;
+        self.set_str(key, &value)
+    }
+
+    /// Returns whether `key` was set.
+    pub fn remove(&self, key: &str) -> Result<bool> {
+        let removed = self
+            .conn
+            .prepare_cached("DELETE FROM index_metadata WHERE key = ?1")?
+            .execute([key])?;
+        Ok(removed > 0)
+    }
+
+    /// Every stored setting, ordered by key.
+    pub fn all(&self) -> Result<Vec<Setting>> {
+        let settings = self
+            .conn
+            .prepare_cached("SELECT key, value, updated_at FROM index_metadata ORDER BY key")?
+            .query_map([], Self::row_to_setting)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(settings)
+    }
+
+    fn get_parsed<T>(
+        &self,
+        key: &str,
+        expected: &str,
+        parse: impl FnOnce(&str) -> Option<T>,
+    ) -> Result<Option<T>> {
+        let Some(value) = self.get_str(key)? else {
+            return Ok(None);
+        };
+        parse(&value).map(Some).ok_or_else(|| {
+            SearchError::IndexCorrupted(format!("Setting {} is not {}: {}", key, expected, value))
+        })
+    }
+
+    fn row_to_setting(row: &rusqlite::Row) -> rusqlite::Result<Setting> {
+        Ok(Setting {
+            key: row.get(0)?,
+            value: row.get(1)?,
+            updated_at: Utc
+                .timestamp_opt(row.get(2)?, 0)
+                .single()
+                .unwrap_or_else(Utc::now),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::migrations::MigrationManager;
+    use serde::Deserialize;
+
+    fn connection() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        MigrationManager::initialize_schema(&conn).unwrap();
+        conn
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Budget {
+        used_bytes: u64,
+        roots: Vec<String>,
+    }
+
+    #[test]
+    fn test_typed_values_round_trip() {
+        let conn = connection();
+        let settings = Settings::new(&conn);
+
+        settings.set_bool("user.enabled", true).unwrap();
+        settings.set_i64("user.count", -42).unwrap();
+        settings.set_str("user.note", "héllo, \"world\"").unwrap();
+        let budget = Budget {
+            used_bytes: u64::MAX,
+            roots: vec!["/data".to_string(), "C:\\Users".to_string()],
+        };
+        settings.set_json("user.budget", &budget).unwrap();
+
+        assert_eq!(settings.get_bool("user.enabled").unwrap(), Some(true));
+        assert_eq!(settings.get_i64("user.count").unwrap(), Some(-42));
+        assert_eq!(
+            settings.get_str("user.note").unwrap().as_deref(),
+            Some("héllo, \"world\"")
+        );
+        assert_eq!(
+            settings.get_json::<Budget>("user.budget").unwrap(),
+            Some(budget)
+        );
+
+        settings.set_bool("user.enabled", false).unwrap();
+        assert_eq!(settings.get_bool("user.enabled").unwrap(), Some(false));
+        assert!(matches!(
+            settings.get_i64("user.note"),
+            Err(SearchError::IndexCorrupted(_))
+        ));
+        assert!(settings.get_json::<Budget>("user.count").is_err());
+    }
+
+    #[test]
+    fn test_unknown_keys_and_listing() {
+        let conn = connection();
+        let settings = Settings::new(&conn);
+
+        assert_eq!(settings.get("user.missing").unwrap(), None);
+        assert_eq!(settings.get_bool("user.missing").unwrap(), None);
+        assert_eq!(settings.get_json::<Budget>("user.missing").unwrap(), None);
+        assert!(!settings.remove("user.missing").unwrap());
+
+        settings.set_str("user.b", "2").unwrap();
+        settings.set_str("user.a", "1").unwrap();
+        let keys: Vec<_> = settings.all().unwrap().into_iter().map(|s| s.key).collect();
+        let expected = [
+            keys::STATS_CONTENT_INDEXED,
+            keys::STATS_DIRECTORIES,
+            keys::STATS_FILES,
+            keys::STATS_TOTAL_SIZE,
+            keys::PATH_LAYOUT,
+            "user.a",
+            "user.b",
+        ];
+        assert_eq!(keys, expected);
+
+        let stored = settings.get("user.a").unwrap().unwrap();
+        assert!((Utc::now() - stored.updated_at).num_seconds() < 5);
+        assert!(settings.remove("user.a").unwrap());
+        assert_eq!(settings.get_str("user.a").unwrap(), None);
+    }
+
+    #[test]
+    fn test_user_settable_keys() {
+        assert!(keys::is_user_settable("user.owner"));
+        assert!(!keys::is_user_settable("user."));
+        assert!(!keys::is_user_settable(keys::PATH_LAYOUT));
+        assert!(!keys::is_user_settable(keys::WRITE_LEASE));
+    }
+}
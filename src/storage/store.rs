@@ -0,0 +1,21 @@
+use crate::core::error::Result;
+use crate::core::types::FileEntry;
+use crate::storage::Database;
+
+/// The writes indexing performs on file rows, split out so batch failure handling can be
+/// exercised against a store that fails on demand.
+pub trait FileStore: Send + Sync {
+    fn insert_files_batch(&self, files: &[FileEntry]) -> Result<()>;
+
+    fn insert_file(&self, file: &FileEntry) -> Result<i64>;
+}
+
+impl FileStore for Database {
+    fn insert_files_batch(&self, files: &[FileEntry]) -> Result<()> {
+        Database::insert_files_batch(self, files)
+    }
+
+    fn insert_file(&self, file: &FileEntry) -> Result<i64> {
+        Database::insert_file(self, file)
+    }
+}
@@ -0,0 +1,192 @@
+use crate::core::config::SearchConfig;
+use crate::core::error::{IoResultExt, Result, SearchError};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const THUMBNAIL_SUFFIX: &str = ".jpg";
+
+/// Thumbnails cached in a sidecar directory next to the index, `<index>.thumbs/`.
+///
+/// Files are named `<key>-<size>.jpg`, where the key identifies the source content, so an
+/// edited image gets new thumbnails and the stale ones age out. Once the directory passes its
+/// byte budget the least recently written thumbnails are deleted.
+pub struct ThumbnailCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl ThumbnailCache {
+    pub fn new<P: Into<PathBuf>>(dir: P, config: &SearchConfig) -> Self {
+        Self {
+            dir: dir.into(),
+            max_bytes: config.thumbnail_cache_max_bytes,
+        }
+    }
+
+    pub fn for_index(index_path: &Path, config: &SearchConfig) -> Self {
+        let mut dir = index_path.as_os_str().to_owned();
+        dir.push(".thumbs");
+        Self::new(dir, config)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn get(&self, key: &str, size: u32) -> Option<Vec<u8>> {
+        fs::read(self.path_for(key, size)).ok()
+    }
+
+    /// Stores a thumbnail, then deletes the oldest ones while the cache is over budget.
+    pub fn put(&self, key: &str, size: u32, bytes: &[u8]) -> Result<()> {
+        fs::create_dir_all(&self.dir).at_path(&self.dir)?;
+
+        // Written under a temporary name so concurrent readers never see a partial file.
+        let path = self.path_for(key, size);
+        let partial = path.with_extension("partial");
+        let mut file = fs::File::create(&partial).at_path(&partial)?;
+        file.write_all(bytes).at_path(&partial)?;
+        file.sync_all().at_path(&partial)?;
+        fs::rename(&partial, &path).at_path(&path)?;
+
+        self.prune()
+    }
+
+    /// Bytes currently held by cached thumbnails.
+    pub fn size_bytes(&self) -> Result<u64> {
+        Ok(self.entries()?.iter().map(|(_, len, _)| len).sum())
+    }
+
+    fn prune(&self) -> Result<()> {
+        let mut entries = self.entries()?;
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        if total <= self.max_bytes {
+            return Ok(());
+        }
+
+        entries.sort_by_key(|(_, _, modified)| *modified);
+        for (path, len, _) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            match fs::remove_file(&path) {
+                Ok(()) => total = total.saturating_sub(len),
+                Err(e) => log::warn!("Failed to evict thumbnail {}: {}", path.display(), e),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn entries(&self) -> Result<Vec<(PathBuf, u64, SystemTime)>> {
+        let read_dir = match fs::read_dir(&self.dir) {
+            Ok(read_dir) => read_dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(SearchError::io_at(&self.dir, e)),
+        };
+
+        let mut entries = Vec::new();
+        for entry in read_dir {
+            let entry = entry.at_path(&self.dir)?;
+            let path = entry.path();
+            if !path.to_string_lossy().ends_with(THUMBNAIL_SUFFIX) {
+                continue;
+            }
+            let metadata = entry.metadata().at_path(&path)?;
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            entries.push((path, metadata.len(), modified));
+        }
+
+        Ok(entries)
+    }
+
+    fn path_for(&self, key: &str, size: u32) -> PathBuf {
+        self.dir.join(format!("{}-{}{}", key, size, THUMBNAIL_SUFFIX))
+    }
+}
+
+/// Decodes the image at `path` and encodes a JPEG at most `size` pixels on its longer side.
+#[cfg(feature = "thumbnails")]
+pub fn render_thumbnail(path: &Path, size: u32) -> Result<Vec<u8>> {
+    use image::codecs::jpeg::JpegEncoder;
+
+    let source = image::ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .at_path(path)?
+        .decode()
+        .map_err(|e| match e {
+            image::ImageError::IoError(e) => SearchError::io_at(path, e),
+            e => {
+                log::debug!("Cannot decode {}: {}", path.display(), e);
+                SearchError::UnsupportedMedia(path.to_path_buf())
+            }
+        })?;
+
+    let thumbnail = source.thumbnail(size, size).into_rgb8();
+    let mut bytes = Vec::new();
+    JpegEncoder::new_with_quality(&mut bytes, 85)
+        .encode_image(&thumbnail)
+        .map_err(|e| SearchError::Encoding(e.to_string()))?;
+    Ok(bytes)
+}
+
+#[cfg(not(feature = "thumbnails"))]
+pub fn render_thumbnail(_path: &Path, _size: u32) -> Result<Vec<u8>> {
+    Err(SearchError::FeatureUnavailable(
+        "thumbnails require the `thumbnails` feature".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_cache_evicts_oldest_past_budget() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = SearchConfig {
+            thumbnail_cache_max_bytes: 250,
+            ..Default::default()
+        };
+        let cache = ThumbnailCache::for_index(&temp_dir.path().join("index.db"), &config);
+        assert!(cache.dir().ends_with("index.db.thumbs"));
+        assert_eq!(cache.size_bytes().unwrap(), 0);
+
+        cache.put("first", 256, &[1; 100]).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        cache.put("second", 256, &[2; 100]).unwrap();
+        assert_eq!(cache.get("first", 256), Some(vec![1; 100]));
+        assert_eq!(cache.get("first", 128), None);
+
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        cache.put("third", 256, &[3; 100]).unwrap();
+        assert_eq!(cache.get("first", 256), None);
+        assert!(cache.get("second", 256).is_some());
+        assert!(cache.get("third", 256).is_some());
+        assert_eq!(cache.size_bytes().unwrap(), 200);
+    }
+
+    #[cfg(feature = "thumbnails")]
+    #[test]
+    fn test_render_thumbnail_fits_requested_size() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("wide.png");
+        image::RgbImage::from_pixel(400, 100, image::Rgb([200, 40, 40]))
+            .save(&source)
+            .unwrap();
+
+        let bytes = render_thumbnail(&source, 64).unwrap();
+        let decoded = image::load_from_memory(&bytes).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (64, 16));
+
+        let text = temp_dir.path().join("fake.png");
+        fs::write(&text, "not an image").unwrap();
+        assert!(matches!(
+            render_thumbnail(&text, 64),
+            Err(SearchError::UnsupportedMedia(_))
+        ));
+    }
+}
@@ -0,0 +1,319 @@
+use crate::core::config::SearchConfig;
+use crate::core::error::{IoResultExt, Result, SearchError};
+use crate::core::types::{ChangeRecord, FileEntry, UndoSnapshot};
+use crate::storage::Database;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+const SNAPSHOT_SUFFIX: &str = ".jsonl.gz";
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%S%.6fZ";
+
+/// Undo snapshots kept in a sidecar directory next to the index, `<index>.undo/`.
+///
+/// A snapshot is a gzipped change feed of `upsert` records, the lines `export_changes_since`
+/// writes, named `<timestamp>-<operation>.jsonl.gz` so listing needs no extra metadata.
+/// It holds entry metadata only; content search data and tags are not restored.
+pub struct UndoLog {
+    dir: PathBuf,
+    max_snapshot_bytes: u64,
+    retention: Duration,
+    max_snapshots: usize,
+}
+
+impl UndoLog {
+    pub fn new<P: Into<PathBuf>>(dir: P, config: &SearchConfig) -> Self {
+        Self {
+            dir: dir.into(),
+            max_snapshot_bytes: config.undo_max_snapshot_bytes,
+            retention: Duration::days(config.undo_retention_days.min(i64::MAX as u64) as i64),
+            max_snapshots: config.undo_max_snapshots,
+        }
+    }
+
+    pub fn for_index(index_path: &Path, config: &SearchConfig) -> Self {
+        let mut dir = index_path.as_os_str().to_owned();
+        dir.push(".undo");
+        Self::new(dir, config)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Saves every entry in `database` before `operation` runs, then prunes old snapshots.
+    ///
+    /// Returns `None` for an empty index. Fails with `UndoSnapshotTooLarge`, leaving no
+    /// partial file behind, once the compressed snapshot passes the size limit.
+    pub fn snapshot(
+        &self,
+        operation: &str,
+        database: &Database,
+        batch_size: usize,
+    ) -> Result<Option<UndoSnapshot>> {
+        let mut after_id = 0;
+        let batches = std::iter::from_fn(|| {
+            match database.get_files_changed_since(DateTime::UNIX_EPOCH, after_id, batch_size) {
+                Ok(batch) if batch.is_empty() => None,
+                Ok(batch) => {
+                    after_id = batch.last().and_then(|entry| entry.id).unwrap_or(after_id);
+                    Some(Ok(batch))
+                }
+                Err(e) => Some(Err(e)),
+            }
+        });
+
+        let snapshot = self.write(operation, Utc::now(), batches)?;
+        self.prune(Utc::now())?;
+        Ok(snapshot)
+    }
+
+    fn write(
+        &self,
+        operation: &str,
+        created_at: DateTime<Utc>,
+        batches: impl Iterator<Item = Result<Vec<FileEntry>>>,
+    ) -> Result<Option<UndoSnapshot>> {
+        fs::create_dir_all(&self.dir).at_path(&self.dir)?;
+        let name = format!(
+            "{}-{}{}",
+            created_at.format(TIMESTAMP_FORMAT),
+            operation,
+            SNAPSHOT_SUFFIX
+        );
+        let path = self.dir.join(name);
+
+        let written = self.write_entries(&path, batches).map_err(|e| e.at_path(&path));
+        match written {
+            Ok(0) | Err(_) => {
+                let _ = fs::remove_file(&path);
+                written.map(|_| None)
+            }
+            Ok(_) => Ok(Some(UndoSnapshot {
+                operation: operation.to_string(),
+                created_at,
+                size_bytes: fs::metadata(&path).at_path(&path)?.len(),
+                path,
+            })),
+        }
+    }
+
+    fn write_entries(
+        &self,
+        path: &Path,
+        batches: impl Iterator<Item = Result<Vec<FileEntry>>>,
+    ) -> Result<usize> {
+        let file = CountingWriter::new(BufWriter::new(File::create(path)?));
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        let mut count = 0;
+
+        for batch in batches {
+            for entry in batch? {
+                serde_json::to_writer(&mut encoder, &ChangeRecord::Upsert { entry })
+                    .map_err(std::io::Error::from)?;
+                encoder.write_all(b"\n")?;
+                count += 1;
+            }
+            if encoder.get_ref().written > self.max_snapshot_bytes {
+                return Err(SearchError::UndoSnapshotTooLarge(self.max_snapshot_bytes));
+            }
+        }
+
+        let mut file = encoder.finish()?;
+        if file.written > self.max_snapshot_bytes {
+            return Err(SearchError::UndoSnapshotTooLarge(self.max_snapshot_bytes));
+        }
+        file.flush()?;
+        Ok(count)
+    }
+
+    /// Snapshots on disk, newest first.
+    pub fn list(&self) -> Result<Vec<UndoSnapshot>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(SearchError::io_at(&self.dir, e)),
+        };
+
+        let mut snapshots = Vec::new();
+        for entry in entries {
+            let entry = entry.at_path(&self.dir)?;
+            let name = entry.file_name();
+            let Some((created_at, operation)) = name.to_str().and_then(parse_snapshot_name)
+            else {
+                continue;
+            };
+            snapshots.push(UndoSnapshot {
+                operation,
+                created_at,
+                path: entry.path(),
+                size_bytes: entry.metadata().at_path(entry.path())?.len(),
+            });
+        }
+
+        snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.created_at));
+        Ok(snapshots)
+    }
+
+    /// Feeds the snapshot's entries to `apply` in batches and returns how many there were.
+    pub fn restore(
+        &self,
+        snapshot: &UndoSnapshot,
+        batch_size: usize,
+        mut apply: impl FnMut(&[FileEntry]) -> Result<()>,
+    ) -> Result<usize> {
+        let file = File::open(&snapshot.path).at_path(&snapshot.path)?;
+        let reader = BufReader::new(GzDecoder::new(file));
+        let mut batch = Vec::with_capacity(batch_size);
+        let mut restored = 0;
+
+        for line in reader.lines() {
+            let line = line.at_path(&snapshot.path)?;
+            if line.is_empty() {
+                continue;
+            }
+            let record: ChangeRecord = serde_json::from_str(&line).map_err(|e| {
+                SearchError::Parse(format!("{}: {}", snapshot.path.display(), e))
+            })?;
+            if let ChangeRecord::Upsert { entry } = record {
+                batch.push(entry);
+            }
+            if batch.len() >= batch_size.max(1) {
+                apply(&batch)?;
+                restored += batch.len();
+                batch.clear();
+            }
+        }
+
+        if !batch.is_empty() {
+            apply(&batch)?;
+            restored += batch.len();
+        }
+        Ok(restored)
+    }
+
+    pub fn remove(&self, snapshot: &UndoSnapshot) -> Result<()> {
+        fs::remove_file(&snapshot.path).at_path(&snapshot.path)?;
+        Ok(())
+    }
+
+    /// Deletes snapshots older than the retention period or beyond the newest
+    /// `undo_max_snapshots`, returning how many were removed.
+    pub fn prune(&self, now: DateTime<Utc>) -> Result<usize> {
+        let cutoff = now - self.retention;
+        let mut removed = 0;
+
+        for (position, snapshot) in self.list()?.iter().enumerate() {
+            if position >= self.max_snapshots || snapshot.created_at < cutoff {
+                self.remove(snapshot)?;
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+fn parse_snapshot_name(name: &str) -> Option<(DateTime<Utc>, String)> {
+    let (timestamp, operation) = name.strip_suffix(SNAPSHOT_SUFFIX)?.split_once('-')?;
+    let created_at = NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).ok()?;
+    Some((created_at.and_utc(), operation.to_string()))
+}
+
+/// Tracks how many compressed bytes have been produced so far.
+struct CountingWriter<W> {
+    inner: W,
+    written: u64,
+}
+
+impl<W> CountingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, written: 0 }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entries(count: usize) -> Vec<FileEntry> {
+        (0..count)
+            .map(|i| FileEntry::new(PathBuf::from(format!("/data/file{}.txt", i))))
+            .collect()
+    }
+
+    #[test]
+    fn test_prune_by_age_and_count() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = SearchConfig {
+            undo_retention_days: 7,
+            undo_max_snapshots: 2,
+            ..SearchConfig::default()
+        };
+        let undo = UndoLog::new(temp_dir.path().join("index.db.undo"), &config);
+        let now = Utc::now();
+
+        for days_ago in [30, 3, 2, 1] {
+            let created_at = now - Duration::days(days_ago);
+            undo.write("clear_index", created_at, std::iter::once(Ok(entries(3))))
+                .unwrap()
+                .unwrap();
+        }
+        assert!(undo
+            .write("clear_index", now, std::iter::once(Ok(Vec::new())))
+            .unwrap()
+            .is_none());
+        assert_eq!(undo.list().unwrap().len(), 4);
+
+        assert_eq!(undo.prune(now).unwrap(), 2);
+        let kept: Vec<i64> = undo
+            .list()
+            .unwrap()
+            .iter()
+            .map(|snapshot| (now - snapshot.created_at).num_days())
+            .collect();
+        assert_eq!(kept, vec![1, 2]);
+
+        let mut restored = Vec::new();
+        let newest = &undo.list().unwrap()[0];
+        let count = undo
+            .restore(newest, 2, |batch| {
+                restored.extend_from_slice(batch);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(restored[2].path, PathBuf::from("/data/file2.txt"));
+    }
+
+    #[test]
+    fn test_oversized_snapshot_leaves_nothing_behind() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = SearchConfig {
+            undo_max_snapshot_bytes: 64,
+            ..SearchConfig::default()
+        };
+        let undo = UndoLog::new(temp_dir.path().join("index.db.undo"), &config);
+
+        let result = undo.write("clear_index", Utc::now(), std::iter::once(Ok(entries(50))));
+        assert!(matches!(result, Err(SearchError::UndoSnapshotTooLarge(64))));
+        assert!(undo.list().unwrap().is_empty());
+    }
+}
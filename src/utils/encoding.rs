@@ -24,13 +24,17 @@ pub fn read_file_with_encoding<P: AsRef<Path>>(path: P, max_size: u64) -> std::i
 
     file.read_exact(&mut buffer)?;
 
-    let encoding = detect_encoding(&buffer);
-    let (decoded, _, had_errors) = encoding.decode(&buffer);
+    Ok(decode_text(&buffer))
+}
+
+pub fn decode_text(buffer: &[u8]) -> String {
+    let encoding = detect_encoding(buffer);
+    let (decoded, _, had_errors) = encoding.decode(buffer);
 
     if had_errors {
-        Ok(String::from_utf8_lossy(&buffer).to_string())
+        String::from_utf8_lossy(buffer).to_string()
     } else {
-        Ok(decoded.to_string())
+        decoded.to_string()
     }
 }
 
@@ -0,0 +1,103 @@
+use std::path::{Path, PathBuf};
+
+/// Filesystems that store timestamps as local wall-clock time rather than UTC.
+const LOCAL_TIME_FILESYSTEMS: &[&str] =
+    &["vfat", "msdos", "fat", "fat12", "fat16", "fat32", "exfat"];
+
+pub fn is_local_time_filesystem(filesystem: &str) -> bool {
+    LOCAL_TIME_FILESYSTEMS
+        .iter()
+        .any(|known| known.eq_ignore_ascii_case(filesystem))
+}
+
+/// Returns the type of the filesystem holding `path`, e.g. `ext4` or `vfat`.
+///
+/// Only implemented on Linux, where the mount table is read from `/proc/self/mounts`;
+/// other platforms return `None`.
+pub fn filesystem_type<P: AsRef<Path>>(path: P) -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        let path = dunce::canonicalize(path.as_ref()).ok()?;
+        let mounts = std::fs::read_to_string("/proc/self/mounts").ok()?;
+        filesystem_for(&path, &parse_mount_table(&mounts)).map(str::to_string)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// Parses `/proc/mounts`-style lines into mount point and filesystem type pairs.
+pub fn parse_mount_table(content: &str) -> Vec<(PathBuf, String)> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _device = fields.next()?;
+            let mount_point = fields.next()?;
+            let filesystem = fields.next()?;
+            Some((PathBuf::from(unescape_mount_field(mount_point)), filesystem.to_string()))
+        })
+        .collect()
+}
+
+/// Picks the filesystem of the most specific mount point containing `path`.
+pub fn filesystem_for<'a>(path: &Path, mounts: &'a [(PathBuf, String)]) -> Option<&'a str> {
+    mounts
+        .iter()
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.components().count())
+        .map(|(_, filesystem)| filesystem.as_str())
+}
+
+// The kernel writes spaces, tabs, newlines and backslashes in mount points as octal escapes.
+fn unescape_mount_field(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut rest = field;
+
+    while let Some(pos) = rest.find('\\') {
+        out.push_str(&rest[..pos]);
+        let escaped = rest
+            .get(pos + 1..pos + 4)
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte as char);
+                rest = &rest[pos + 4..];
+            }
+            None => {
+                out.push('\\');
+                rest = &rest[pos + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_most_specific_mount_wins() {
+        let mounts = parse_mount_table(
+            "/dev/sda1 / ext4 rw,relatime 0 0\n\
+             /dev/sdb1 /media/usb\\040stick vfat rw,fmask=0022 0 0\n\
+             tmpfs /tmp tmpfs rw 0 0\n",
+        );
+
+        assert_eq!(
+            filesystem_for(Path::new("/media/usb stick/DCIM/img.jpg"), &mounts),
+            Some("vfat")
+        );
+        assert_eq!(filesystem_for(Path::new("/media/other"), &mounts), Some("ext4"));
+        assert_eq!(filesystem_for(Path::new("/tmp/x"), &mounts), Some("tmpfs"));
+
+        assert!(is_local_time_filesystem("vfat"));
+        assert!(is_local_time_filesystem("exFAT"));
+        assert!(!is_local_time_filesystem("ext4"));
+    }
+}
@@ -1,12 +1,19 @@
 pub mod encoding;
+pub mod filesystem;
 pub mod hash;
 pub mod mime;
+pub mod normalizer;
 pub mod path;
 
 pub use encoding::{detect_encoding, is_likely_text, is_utf8, read_file_with_encoding};
+pub use filesystem::{filesystem_type, is_local_time_filesystem};
 pub use hash::{hash_bytes, hash_file, hash_string};
 pub use mime::{categorize_file, detect_mime_type, FileCategory};
+pub use normalizer::{
+    default_normalizer, fold_case, LowercaseNormalizer, TextNormalizer, UnicodeNormalizer,
+};
 pub use path::{
-    ensure_parent_exists, get_extension, get_file_name, get_file_stem, get_path_depth,
-    get_relative_path, is_hidden, is_same_file, join_paths, normalize_path,
+    depth_below, ensure_parent_exists, find_workspace_root, get_extension, get_file_name,
+    get_file_stem, get_path_depth, get_relative_path, is_hidden, is_same_file, join_paths,
+    normalize_path, normalize_separators, path_match_form,
 };
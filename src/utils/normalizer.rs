@@ -0,0 +1,131 @@
+use std::borrow::Cow;
+use std::sync::Arc;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+pub trait TextNormalizer: Send + Sync {
+    fn normalize<'a>(&self, s: &'a str) -> Cow<'a, str>;
+}
+
+/// Folds `s` for caseless comparison, so that `fold_case(a) == fold_case(b)` whenever `a` and
+/// `b` differ only in case.
+///
+/// This follows Unicode full case folding where it differs from `char::to_lowercase`: `ß` and
+/// `ẞ` fold to "ss", `ſ` to "s" and final `ς` to `σ`. Characters are folded one at a time, so
+/// `Σ` always becomes `σ` regardless of its position in a word. Folding is not locale-aware:
+/// `I` folds to `i` and Turkish dotless `ı` stays distinct from it, while dotted `İ` folds to a
+/// plain `i` instead of `i` plus a combining dot so that `İSTANBUL` still matches `istanbul`.
+pub fn fold_case(s: &str) -> Cow<'_, str> {
+    if s.is_ascii() {
+        return if s.bytes().any(|b| b.is_ascii_uppercase()) {
+            Cow::Owned(s.to_ascii_lowercase())
+        } else {
+            Cow::Borrowed(s)
+        };
+    }
+
+    if !s.chars().any(changes_when_folded) {
+        return Cow::Borrowed(s);
+    }
+
+    let mut folded = String::with_capacity(s.len());
+    for c in s.chars() {
+        push_folded(c, &mut folded);
+    }
+    Cow::Owned(folded)
+}
+
+fn changes_when_folded(c: char) -> bool {
+    matches!(c, 'ß' | 'ẞ' | 'ſ' | 'ς' | 'İ') || c.to_lowercase().ne(std::iter::once(c))
+}
+
+fn push_folded(c: char, out: &mut String) {
+    match c {
+        'ß' | 'ẞ' => out.push_str("ss"),
+        'ſ' => out.push('s'),
+        'ς' => out.push('σ'),
+        'İ' => out.push('i'),
+        c => out.extend(c.to_lowercase()),
+    }
+}
+
+pub struct UnicodeNormalizer;
+
+impl TextNormalizer for UnicodeNormalizer {
+    fn normalize<'a>(&self, s: &'a str) -> Cow<'a, str> {
+        if s.is_ascii() {
+            return if s.bytes().any(|b| b.is_ascii_uppercase()) {
+                Cow::Owned(s.to_ascii_lowercase())
+            } else {
+                Cow::Borrowed(s)
+            };
+        }
+
+        let mut normalized = String::with_capacity(s.len());
+        for c in s.nfkd().filter(|c| !is_combining_mark(*c)) {
+            push_folded(c, &mut normalized);
+        }
+        Cow::Owned(normalized)
+    }
+}
+
+/// Folds case with `fold_case` but keeps diacritics.
+pub struct LowercaseNormalizer;
+
+impl TextNormalizer for LowercaseNormalizer {
+    fn normalize<'a>(&self, s: &'a str) -> Cow<'a, str> {
+        fold_case(s)
+    }
+}
+
+pub fn default_normalizer(normalize_unicode: bool) -> Arc<dyn TextNormalizer> {
+    if normalize_unicode {
+        Arc::new(UnicodeNormalizer)
+    } else {
+        Arc::new(LowercaseNormalizer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unicode_normalizer_strips_diacritics() {
+        let normalizer = UnicodeNormalizer;
+        assert_eq!(normalizer.normalize("München-Fotos"), "munchen-fotos");
+        assert_eq!(normalizer.normalize("Café menu.pdf"), "cafe menu.pdf");
+        assert_eq!(normalizer.normalize("ﬁle"), "file");
+    }
+
+    #[test]
+    fn test_unicode_normalizer_borrows_lowercase_ascii() {
+        let normalizer = UnicodeNormalizer;
+        assert!(matches!(normalizer.normalize("plain.txt"), Cow::Borrowed(_)));
+        assert_eq!(normalizer.normalize("README.md"), "readme.md");
+    }
+
+    #[test]
+    fn test_lowercase_normalizer_keeps_diacritics() {
+        let normalizer = LowercaseNormalizer;
+        assert_eq!(normalizer.normalize("Café"), "café");
+    }
+
+    #[test]
+    fn test_fold_case_special_cases() {
+        assert_eq!(fold_case("STRASSE"), fold_case("straße"));
+        assert_eq!(fold_case("Maßstab"), "massstab");
+        assert_eq!(fold_case("İSTANBUL"), "istanbul");
+        assert_eq!(fold_case("ISTANBUL"), "istanbul");
+        assert_ne!(fold_case("ıstanbul"), "istanbul");
+        assert_eq!(fold_case("ΟΔΟΣ"), fold_case("οδος"));
+        assert_eq!(fold_case("οδοσ"), fold_case("οδος"));
+        assert!(matches!(fold_case("café"), Cow::Borrowed(_)));
+
+        for normalizer in [&UnicodeNormalizer as &dyn TextNormalizer, &LowercaseNormalizer] {
+            assert_eq!(normalizer.normalize("STRASSE"), normalizer.normalize("Straße"));
+            assert_eq!(normalizer.normalize("İSTANBUL"), "istanbul");
+            assert_eq!(normalizer.normalize("ΟΔΟΣ"), normalizer.normalize("οδος"));
+        }
+    }
+}
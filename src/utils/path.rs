@@ -25,8 +25,50 @@ pub fn is_hidden<P: AsRef<Path>>(path: P) -> bool {
     false
 }
 
+// Canonical form used for display and path matching: always `/`, whatever the platform.
+pub fn normalize_separators(path: &str) -> String {
+    path.replace('\\', "/")
+}
+
+pub fn path_match_form<P: AsRef<Path>>(path: P) -> String {
+    normalize_separators(&path.as_ref().to_string_lossy())
+}
+
 pub fn get_path_depth<P: AsRef<Path>>(path: P) -> usize {
-    path.as_ref().components().count()
+    Path::new(&path_match_form(path)).components().count()
+}
+
+/// How many components `path` has below `root`, or below the filesystem root when there is no
+/// `root` or `path` is outside it. Either separator is accepted and drive prefixes don't count,
+/// so `C:\src\main.rs` and `/src/main.rs` are both 2 deep.
+pub fn depth_below<P: AsRef<Path>>(path: P, root: Option<&Path>) -> usize {
+    // The drive, if any, and the remaining components.
+    fn components(form: &str) -> (Option<String>, Vec<&str>) {
+        let mut parts: Vec<&str> =
+            form.split('/').filter(|part| !part.is_empty() && *part != ".").collect();
+        let drive = parts.first().and_then(|first| {
+            let bytes = first.as_bytes();
+            (bytes.len() == 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':')
+                .then(|| first.to_ascii_uppercase())
+        });
+        if drive.is_some() {
+            parts.remove(0);
+        }
+        (drive, parts)
+    }
+
+    let path = path_match_form(path);
+    let (drive, parts) = components(&path);
+    let Some(root) = root else {
+        return parts.len();
+    };
+    let root = path_match_form(root);
+    let (root_drive, root_parts) = components(&root);
+    if drive == root_drive && parts.starts_with(&root_parts) {
+        parts.len() - root_parts.len()
+    } else {
+        parts.len()
+    }
 }
 
 pub fn get_relative_path<P: AsRef<Path>>(base: P, target: P) -> Option<PathBuf> {
@@ -74,6 +116,16 @@ pub fn join_paths<P: AsRef<Path>>(base: P, paths: &[P]) -> PathBuf {
     result
 }
 
+/// The nearest of `start` and its ancestors that holds one of `markers`, file or directory
+/// names such as `.git` or `Cargo.toml`. `None` once the filesystem root is passed without a
+/// match.
+pub fn find_workspace_root<S: AsRef<str>>(start: &Path, markers: &[S]) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| markers.iter().any(|marker| dir.join(marker.as_ref()).exists()))
+        .map(Path::to_path_buf)
+}
+
 pub fn ensure_parent_exists<P: AsRef<Path>>(path: P) -> std::io::Result<()> {
     if let Some(parent) = path.as_ref().parent() {
         std::fs::create_dir_all(parent)?;
@@ -97,6 +149,56 @@ mod tests {
     fn test_get_path_depth() {
         assert_eq!(get_path_depth("/"), 1);
         assert_eq!(get_path_depth("/path/to/file"), 4);
+        assert_eq!(get_path_depth(r"C:\path\to\file"), get_path_depth("C:/path/to/file"));
+    }
+
+    #[test]
+    fn test_depth_below() {
+        assert_eq!(depth_below("/readme.md", None), 1);
+        assert_eq!(depth_below("/home/me/app/readme.md", None), 4);
+        assert_eq!(depth_below(r"C:\Users\me\readme.md", None), 3);
+        assert_eq!(depth_below("C:/Users/me/readme.md", None), 3);
+
+        let root = Path::new(r"C:\Users\me");
+        assert_eq!(depth_below(r"C:\Users\me\readme.md", Some(root)), 1);
+        assert_eq!(depth_below(r"C:\Users\me\vendor\lib\readme.md", Some(root)), 3);
+        assert_eq!(depth_below("/home/me/app/readme.md", Some(Path::new("/home/me/"))), 2);
+        assert_eq!(depth_below(r"D:\Users\me\readme.md", Some(root)), 3);
+    }
+
+    #[test]
+    fn test_path_match_form() {
+        assert_eq!(
+            path_match_form(r"C:\Users\dev\src\server\main.rs"),
+            "C:/Users/dev/src/server/main.rs"
+        );
+        assert_eq!(path_match_form("/home/dev/src/server"), "/home/dev/src/server");
+        assert_eq!(normalize_separators(r"src\server"), "src/server");
+    }
+
+    #[test]
+    fn test_find_workspace_root_prefers_the_nearest_marker() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let repo = temp_dir.path().join("work");
+        let member = repo.join("crates/app");
+        std::fs::create_dir_all(member.join("src/bin")).unwrap();
+        std::fs::create_dir(repo.join(".git")).unwrap();
+        std::fs::write(member.join("Cargo.toml"), "").unwrap();
+        let markers = [".git", "Cargo.toml"];
+
+        assert_eq!(find_workspace_root(&member.join("src/bin"), &markers), Some(member.clone()));
+        assert_eq!(find_workspace_root(&member, &markers), Some(member));
+        assert_eq!(find_workspace_root(&repo.join("crates"), &markers), Some(repo.clone()));
+        assert_eq!(find_workspace_root(&repo, &[".git"]), Some(repo));
+    }
+
+    #[test]
+    fn test_find_workspace_root_stops_at_the_filesystem_root() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let marker = "no-such-marker-7f3a2c";
+        assert_eq!(find_workspace_root(temp_dir.path(), &[marker]), None);
+        assert_eq!(find_workspace_root(Path::new("/"), &[marker]), None);
+        assert_eq!(find_workspace_root::<&str>(temp_dir.path(), &[]), None);
     }
 
     #[test]
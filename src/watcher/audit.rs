@@ -0,0 +1,122 @@
+use crate::core::error::Result;
+use crate::core::types::{AuditEventType, AuditRecord};
+use crate::storage::Database;
+use crate::utils::hash::hash_file;
+use crate::watcher::debouncer::FileEventType;
+use chrono::Utc;
+use parking_lot::Mutex;
+use std::path::Path;
+use std::sync::Arc;
+
+pub struct AuditRecorder {
+    database: Arc<Database>,
+    watch_id: Option<String>,
+    batch_size: usize,
+    pending: Mutex<Vec<AuditRecord>>,
+}
+
+impl AuditRecorder {
+    pub fn new(database: Arc<Database>, watch_id: Option<String>, batch_size: usize) -> Self {
+        Self {
+            database,
+            watch_id,
+            batch_size: batch_size.max(1),
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+
+    // Size and hash are captured now, while the file is still in the state that caused the event.
+    pub fn record(&self, path: &Path, event_type: FileEventType) {
+        let metadata = std::fs::metadata(path).ok();
+        let size = metadata.as_ref().map(|m| m.len());
+        let file_hash = metadata
+            .filter(|m| m.is_file())
+            .and_then(|_| hash_file(path).ok());
+
+        let record = AuditRecord {
+            id: None,
+            path: path.to_path_buf(),
+            event_type: audit_event_type(event_type),
+            occurred_at: Utc::now(),
+            size,
+            file_hash,
+            watch_id: self.watch_id.clone(),
+        };
+
+        let full = {
+            let mut pending = self.pending.lock();
+            pending.push(record);
+            pending.len() >= self.batch_size
+        };
+
+        if full {
+            if let Err(e) = self.flush() {
+                log::error!("Failed to write audit records: {}", e);
+            }
+        }
+    }
+
+    pub fn flush(&self) -> Result<usize> {
+        let records = std::mem::take(&mut *self.pending.lock());
+        if records.is_empty() {
+            return Ok(0);
+        }
+
+        if let Err(e) = self.database.insert_audit_batch(&records) {
+            // Keep the batch so the next flush retries it instead of dropping history.
+            let mut pending = self.pending.lock();
+            let newer = std::mem::replace(&mut *pending, records);
+            pending.extend(newer);
+            return Err(e);
+        }
+
+        Ok(records.len())
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.lock().len()
+    }
+}
+
+fn audit_event_type(event_type: FileEventType) -> AuditEventType {
+    match event_type {
+        FileEventType::Created => AuditEventType::Created,
+        FileEventType::Modified => AuditEventType::Modified,
+        FileEventType::Deleted => AuditEventType::Deleted,
+        FileEventType::Renamed => AuditEventType::Renamed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_recorder_batches_writes() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("report.txt");
+        fs::write(&file, "quarterly").unwrap();
+
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let recorder = AuditRecorder::new(Arc::clone(&db), Some("w1".to_string()), 3);
+
+        recorder.record(&file, FileEventType::Created);
+        recorder.record(&file, FileEventType::Modified);
+        assert_eq!(recorder.pending(), 2);
+        assert!(db.query_audit_log(None, (None, None), &[], 0, 10).unwrap().is_empty());
+
+        recorder.record(&temp_dir.path().join("gone.txt"), FileEventType::Deleted);
+        assert_eq!(recorder.pending(), 0);
+
+        let records = db.query_audit_log(None, (None, None), &[], 0, 10).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].size, Some(9));
+        assert_eq!(records[0].file_hash, Some(hash_file(&file).unwrap()));
+        assert_eq!(records[0].watch_id.as_deref(), Some("w1"));
+        assert_eq!(records[2].event_type, AuditEventType::Deleted);
+        assert_eq!(records[2].size, None);
+        assert_eq!(records[2].file_hash, None);
+    }
+}
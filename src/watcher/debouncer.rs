@@ -64,17 +64,10 @@ impl EventDebouncer {
         });
     }
 
-    pub fn clear(&self) {
-        self.events.clear();
-    }
-
+    #[cfg(test)]
     pub fn len(&self) -> usize {
         self.events.len()
     }
-
-    pub fn is_empty(&self) -> bool {
-        self.events.is_empty()
-    }
 }
 
 impl Default for EventDebouncer {
@@ -1,7 +1,11 @@
-pub mod debouncer;
+pub mod audit;
+pub(crate) mod debouncer;
 pub mod monitor;
 pub mod synchronizer;
 
-pub use debouncer::{EventDebouncer, FileEventType};
-pub use monitor::FileSystemMonitor;
+pub use audit::AuditRecorder;
+pub use monitor::{
+    FileSystemMonitor, InitialScanState, WatchEvent, WatchOptions, WatchState, WatchStatus,
+    WATCH_BACKEND,
+};
 pub use synchronizer::{FileEvent, IndexSynchronizer};
@@ -1,21 +1,127 @@
 use crate::core::config::SearchConfig;
 use crate::core::error::Result;
+use crate::core::metrics::EngineCounters;
+use crate::core::types::Progress;
 use crate::filters::ExclusionFilter;
-use crate::storage::Database;
+use crate::storage::{Database, WriteLeaseKeeper};
+use crate::watcher::audit::AuditRecorder;
 use crate::watcher::debouncer::{EventDebouncer, FileEventType};
 use crate::watcher::synchronizer::{FileEvent, IndexSynchronizer};
+use chrono::{DateTime, Utc};
+use notify::event::ModifyKind;
 use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
-use std::path::Path;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{mpsc as std_mpsc, Arc};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_millis(200);
+const RECOVERY_BACKOFF_BASE: Duration = Duration::from_millis(100);
+const RECOVERY_BACKOFF_MAX: Duration = Duration::from_secs(5);
+const RECOVERY_MAX_ATTEMPTS: u32 = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchState {
+    Healthy,
+    Degraded,
+    Failed,
+    Stopped,
+}
+
+/// Progress of the update that indexes files already present when a watch starts.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum InitialScanState {
+    Pending,
+    Running { progress: Progress },
+    Complete,
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchStatus {
+    pub state: WatchState,
+    pub last_error: Option<String>,
+    pub changed_at: DateTime<Utc>,
+    /// `None` when the watch was started without an initial scan.
+    pub initial_scan_state: Option<InitialScanState>,
+    pub recursive: bool,
+}
+
+impl WatchStatus {
+    fn new(state: WatchState, last_error: Option<String>) -> Self {
+        Self {
+            state,
+            last_error,
+            changed_at: Utc::now(),
+            initial_scan_state: None,
+            recursive: true,
+        }
+    }
+
+    /// Changes the watch state, leaving the initial scan state alone.
+    fn set_state(&mut self, state: WatchState, last_error: Option<String>) {
+        self.state = state;
+        self.last_error = last_error;
+        self.changed_at = Utc::now();
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum WatchEvent {
+    Degraded { root: PathBuf, error: String },
+    Recovered { root: PathBuf, caught_up: usize },
+    Failed { root: PathBuf, error: String },
+    InitialScanProgress { root: PathBuf, progress: Progress },
+    InitialScanComplete { root: PathBuf, indexed: usize },
+    /// File events queued since the last health check were applied to the index.
+    IndexChanged { root: PathBuf, changes: usize },
+}
+
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    pub audit: bool,
+    pub watch_id: Option<String>,
+    /// Index files that already exist under the root in the background. File events that
+    /// arrive meanwhile are queued and applied once the scan finishes, so a file seen by
+    /// both is simply upserted twice.
+    pub initial_scan: bool,
+    /// Watch the whole tree under the root. Otherwise only the root's direct children are
+    /// watched, scanned and caught up; a new subdirectory is indexed but not its contents.
+    pub recursive: bool,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            audit: false,
+            watch_id: None,
+            initial_scan: true,
+            recursive: true,
+        }
+    }
+}
+
+type Subscribers = Arc<Mutex<Vec<std_mpsc::Sender<WatchEvent>>>>;
+
 pub struct FileSystemMonitor {
+    database: Arc<Database>,
+    config: Arc<SearchConfig>,
     exclusion_filter: Arc<ExclusionFilter>,
     synchronizer: Arc<IndexSynchronizer>,
     debouncer: Arc<EventDebouncer>,
     is_running: Arc<AtomicBool>,
-    watcher: Option<RecommendedWatcher>,
+    status: Arc<Mutex<WatchStatus>>,
+    subscribers: Subscribers,
+    audit: Option<Arc<AuditRecorder>>,
+    metrics: Arc<EngineCounters>,
+    write_lease: Arc<WriteLeaseKeeper>,
+    supervisor: Option<JoinHandle<()>>,
 }
 
 impl FileSystemMonitor {
@@ -25,68 +131,178 @@ impl FileSystemMonitor {
         exclusion_filter: Arc<ExclusionFilter>,
     ) -> Self {
         let synchronizer = Arc::new(IndexSynchronizer::new(
-            database,
+            Arc::clone(&database),
             Arc::clone(&config),
             Arc::clone(&exclusion_filter),
         ));
 
         let debouncer = Arc::new(EventDebouncer::new(config.watch_debounce_ms));
+        let write_lease = Arc::new(WriteLeaseKeeper::new(Arc::clone(&database), &config));
 
         Self {
+            database,
+            config,
             exclusion_filter,
             synchronizer,
             debouncer,
             is_running: Arc::new(AtomicBool::new(false)),
-            watcher: None,
+            status: Arc::new(Mutex::new(WatchStatus::new(WatchState::Stopped, None))),
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+            audit: None,
+            metrics: Arc::new(EngineCounters::new()),
+            write_lease,
+            supervisor: None,
         }
     }
 
+    /// Records synchronizer activity into `metrics`. Must be called before watching starts.
+    pub fn with_metrics(mut self, metrics: Arc<EngineCounters>) -> Self {
+        self.metrics = metrics;
+        self.synchronizer = self.synchronizer_for(Arc::clone(&self.config));
+        self
+    }
+
+    /// Shares the engine's write lease, so the watch and the engine's own operations hold
+    /// it together. Must be called before watching starts.
+    pub fn with_write_lease(mut self, write_lease: Arc<WriteLeaseKeeper>) -> Self {
+        self.write_lease = write_lease;
+        self.synchronizer = self.synchronizer_for(Arc::clone(&self.config));
+        self
+    }
+
+    fn synchronizer_for(&self, config: Arc<SearchConfig>) -> Arc<IndexSynchronizer> {
+        Arc::new(
+            IndexSynchronizer::new(
+                Arc::clone(&self.database),
+                config,
+                Arc::clone(&self.exclusion_filter),
+            )
+            .with_metrics(Arc::clone(&self.metrics))
+            .with_write_lease(Arc::clone(&self.write_lease)),
+        )
+    }
+
     pub fn start<P: AsRef<Path>>(&mut self, root: P) -> Result<()> {
+        self.start_with_options(root, WatchOptions::default())
+    }
+
+    pub fn start_with_options<P: AsRef<Path>>(
+        &mut self,
+        root: P,
+        options: WatchOptions,
+    ) -> Result<()> {
         if self.is_running.load(Ordering::Relaxed) {
             return Ok(());
         }
 
-        let sender = self.synchronizer.get_sender();
-        let debouncer = Arc::clone(&self.debouncer);
-        let exclusion_filter = Arc::clone(&self.exclusion_filter);
+        self.audit = options.audit.then(|| {
+            Arc::new(AuditRecorder::new(
+                Arc::clone(&self.database),
+                options.watch_id.clone(),
+                self.config.batch_size,
+            ))
+        });
 
-        let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
-            if let Ok(event) = res {
-                Self::handle_notify_event(event, &sender, &debouncer, &exclusion_filter);
-            }
-        })?;
+        // A shallow watch scans and catches up one level deep, like its events.
+        let config = if options.recursive {
+            Arc::clone(&self.config)
+        } else {
+            Arc::new(SearchConfig {
+                max_depth: Some(1),
+                ..(*self.config).clone()
+            })
+        };
+        self.synchronizer = self.synchronizer_for(config);
 
-        watcher.watch(root.as_ref(), RecursiveMode::Recursive)?;
+        let root = dunce::canonicalize(root.as_ref()).unwrap_or_else(|_| root.as_ref().to_path_buf());
+        let backend_error = Arc::new(Mutex::new(None));
+        let watcher = self.create_watcher(&root, options.recursive, &backend_error)?;
 
-        self.watcher = Some(watcher);
+        *self.status.lock() = WatchStatus {
+            initial_scan_state: options.initial_scan.then_some(InitialScanState::Pending),
+            recursive: options.recursive,
+            ..WatchStatus::new(WatchState::Healthy, None)
+        };
         self.is_running.store(true, Ordering::Relaxed);
 
+        let supervisor = Supervisor {
+            root,
+            recursive: options.recursive,
+            backend_error,
+            exclusion_filter: Arc::clone(&self.exclusion_filter),
+            synchronizer: Arc::clone(&self.synchronizer),
+            debouncer: Arc::clone(&self.debouncer),
+            is_running: Arc::clone(&self.is_running),
+            status: Arc::clone(&self.status),
+            subscribers: Arc::clone(&self.subscribers),
+            audit: self.audit.clone(),
+        };
+        let initial_scan = options.initial_scan;
+        self.supervisor = Some(thread::spawn(move || supervisor.run(watcher, initial_scan)));
+
         Ok(())
     }
 
     pub fn stop(&mut self) -> Result<()> {
-        if !self.is_running.load(Ordering::Relaxed) {
+        if !self.is_running.swap(false, Ordering::Relaxed) {
             return Ok(());
         }
 
-        self.watcher = None;
-        self.is_running.store(false, Ordering::Relaxed);
+        if let Some(handle) = self.supervisor.take() {
+            let _ = handle.join();
+        }
+        self.status.lock().set_state(WatchState::Stopped, None);
 
         Ok(())
     }
 
     pub fn is_running(&self) -> bool {
-        self.is_running.load(Ordering::Relaxed)
+        self.is_running.load(Ordering::Relaxed) && self.status.lock().state == WatchState::Healthy
+    }
+
+    pub fn status(&self) -> WatchStatus {
+        self.status.lock().clone()
+    }
+
+    pub fn subscribe(&self) -> std_mpsc::Receiver<WatchEvent> {
+        let (sender, receiver) = std_mpsc::channel();
+        self.subscribers.lock().push(sender);
+        receiver
+    }
+
+    fn create_watcher(
+        &self,
+        root: &Path,
+        recursive: bool,
+        backend_error: &Arc<Mutex<Option<String>>>,
+    ) -> Result<RecommendedWatcher> {
+        create_watcher(
+            root,
+            recursive,
+            self.synchronizer.get_sender(),
+            Arc::clone(&self.debouncer),
+            Arc::clone(&self.exclusion_filter),
+            self.audit.clone(),
+            Arc::clone(backend_error),
+        )
+    }
+
+    pub fn is_auditing(&self) -> bool {
+        self.audit.is_some()
     }
 
+    /// With `shallow_root`, only events for its direct children get through.
     fn handle_notify_event(
         event: Event,
         sender: &mpsc::UnboundedSender<FileEvent>,
         debouncer: &Arc<EventDebouncer>,
         exclusion_filter: &Arc<ExclusionFilter>,
+        audit: Option<&AuditRecorder>,
+        shallow_root: Option<&Path>,
     ) {
         let event_type = match event.kind {
             EventKind::Create(_) => FileEventType::Created,
+            EventKind::Modify(ModifyKind::Name(_)) => FileEventType::Renamed,
             EventKind::Modify(_) => FileEventType::Modified,
             EventKind::Remove(_) => FileEventType::Deleted,
             EventKind::Any => FileEventType::Modified,
@@ -94,14 +310,31 @@ impl FileSystemMonitor {
         };
 
         for path in event.paths {
+            // Some backends emulate non-recursive watches by watching everything anyway.
+            if shallow_root.is_some_and(|root| path.parent() != Some(root)) {
+                continue;
+            }
             if exclusion_filter.is_excluded(&path) {
                 continue;
             }
 
+            // Removals still go through so entries under a removed directory are dropped.
+            let is_file_write = matches!(
+                event_type,
+                FileEventType::Created | FileEventType::Modified
+            ) && !path.is_dir();
+            if is_file_write && !exclusion_filter.is_included(&path) {
+                continue;
+            }
+
             if !debouncer.should_process(path.clone(), event_type) {
                 continue;
             }
 
+            if let Some(recorder) = audit {
+                recorder.record(&path, event_type);
+            }
+
             let file_event = FileEvent { path, event_type };
 
             if sender.send(file_event).is_err() {
@@ -111,7 +344,7 @@ impl FileSystemMonitor {
     }
 
     pub async fn run_cleanup_task(&self) {
-        use tokio::time::{interval, Duration};
+        use tokio::time::interval;
 
         let mut interval = interval(Duration::from_secs(60));
         let debouncer = Arc::clone(&self.debouncer);
@@ -129,9 +362,302 @@ impl Drop for FileSystemMonitor {
     }
 }
 
+/// The event source `notify::RecommendedWatcher` uses on this platform.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub const WATCH_BACKEND: &str = "inotify";
+#[cfg(target_os = "macos")]
+pub const WATCH_BACKEND: &str = "fsevents";
+#[cfg(target_os = "windows")]
+pub const WATCH_BACKEND: &str = "read_directory_changes";
+#[cfg(any(
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "dragonfly",
+    target_os = "ios"
+))]
+pub const WATCH_BACKEND: &str = "kqueue";
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "windows",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "dragonfly",
+    target_os = "ios"
+)))]
+pub const WATCH_BACKEND: &str = "poll";
+
+fn create_watcher(
+    root: &Path,
+    recursive: bool,
+    sender: mpsc::UnboundedSender<FileEvent>,
+    debouncer: Arc<EventDebouncer>,
+    exclusion_filter: Arc<ExclusionFilter>,
+    audit: Option<Arc<AuditRecorder>>,
+    backend_error: Arc<Mutex<Option<String>>>,
+) -> Result<RecommendedWatcher> {
+    let watched_root = root.to_path_buf();
+
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| match res {
+        Ok(event) => {
+            if matches!(event.kind, EventKind::Remove(_))
+                && event.paths.iter().any(|p| p == &watched_root)
+            {
+                *backend_error.lock() = Some("watched root was removed".to_string());
+            }
+            FileSystemMonitor::handle_notify_event(
+                event,
+                &sender,
+                &debouncer,
+                &exclusion_filter,
+                audit.as_deref(),
+                (!recursive).then_some(watched_root.as_path()),
+            );
+        }
+        Err(e) => {
+            log::warn!("Watch backend error on {}: {}", watched_root.display(), e);
+            *backend_error.lock() = Some(e.to_string());
+        }
+    })?;
+
+    let mode = if recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    watcher.watch(root, mode)?;
+
+    Ok(watcher)
+}
+
+struct Supervisor {
+    root: PathBuf,
+    recursive: bool,
+    backend_error: Arc<Mutex<Option<String>>>,
+    exclusion_filter: Arc<ExclusionFilter>,
+    synchronizer: Arc<IndexSynchronizer>,
+    debouncer: Arc<EventDebouncer>,
+    is_running: Arc<AtomicBool>,
+    status: Arc<Mutex<WatchStatus>>,
+    subscribers: Subscribers,
+    audit: Option<Arc<AuditRecorder>>,
+}
+
+impl Supervisor {
+    fn run(self, watcher: RecommendedWatcher, initial_scan: bool) {
+        let mut watcher = Some(watcher);
+        let mut scan = initial_scan.then(|| self.spawn_initial_scan());
+
+        while self.sleep_while_running(HEALTH_CHECK_INTERVAL) {
+            if scan.as_ref().is_some_and(JoinHandle::is_finished) {
+                let _ = scan.take().map(JoinHandle::join);
+            }
+            // Queued events wait for the initial scan so they land on top of its results.
+            if scan.is_none() {
+                self.apply_pending();
+            }
+            self.flush_audit();
+
+            let problem = self.backend_error.lock().take().or_else(|| {
+                (!self.root.exists()).then(|| "watched root no longer exists".to_string())
+            });
+
+            if let Some(error) = problem {
+                // Release the dead watch before retrying so the backend handle is freed.
+                watcher.take();
+                log::warn!("Watch on {} degraded: {}", self.root.display(), error);
+                self.transition(WatchState::Degraded, Some(error.clone()));
+                self.emit(WatchEvent::Degraded {
+                    root: self.root.clone(),
+                    error,
+                });
+
+                watcher = self.recover();
+                if watcher.is_none() {
+                    break;
+                }
+            }
+        }
+
+        drop(watcher);
+        if scan.is_none() {
+            self.apply_pending_with(IndexSynchronizer::flush_pending);
+        }
+        self.flush_audit();
+    }
+
+    /// Runs the initial scan on its own thread so health checks and recovery keep going.
+    /// Stopping the watch does not wait for it.
+    fn spawn_initial_scan(&self) -> JoinHandle<()> {
+        let root = self.root.clone();
+        let synchronizer = Arc::clone(&self.synchronizer);
+        let status = Arc::clone(&self.status);
+        let subscribers = Arc::clone(&self.subscribers);
+
+        thread::spawn(move || {
+            let set_scan_state = |state: InitialScanState| {
+                status.lock().initial_scan_state = Some(state);
+            };
+            set_scan_state(InitialScanState::Running {
+                progress: Progress::new(0, 0, "Scanning".to_string()),
+            });
+
+            let progress = {
+                let root = root.clone();
+                let status = Arc::clone(&status);
+                let subscribers = Arc::clone(&subscribers);
+                Box::new(move |progress: Progress| {
+                    status.lock().initial_scan_state = Some(InitialScanState::Running {
+                        progress: progress.clone(),
+                    });
+                    emit(
+                        &subscribers,
+                        WatchEvent::InitialScanProgress {
+                            root: root.clone(),
+                            progress,
+                        },
+                    );
+                })
+            };
+
+            match synchronizer.catch_up_with_progress(&root, progress) {
+                Ok(stats) => {
+                    log::info!(
+                        "Initial scan of {} indexed {} changes",
+                        root.display(),
+                        stats.total()
+                    );
+                    set_scan_state(InitialScanState::Complete);
+                    emit(
+                        &subscribers,
+                        WatchEvent::InitialScanComplete {
+                            root: root.clone(),
+                            indexed: stats.total(),
+                        },
+                    );
+                }
+                Err(e) => {
+                    log::warn!("Initial scan of {} failed: {}", root.display(), e);
+                    set_scan_state(InitialScanState::Failed {
+                        error: e.to_string(),
+                    });
+                }
+            }
+        })
+    }
+
+    fn apply_pending(&self) {
+        self.apply_pending_with(IndexSynchronizer::apply_pending);
+    }
+
+    fn apply_pending_with(&self, apply: fn(&IndexSynchronizer) -> usize) {
+        let changes = apply(&self.synchronizer);
+        if changes > 0 {
+            self.emit(WatchEvent::IndexChanged {
+                root: self.root.clone(),
+                changes,
+            });
+        }
+    }
+
+    fn flush_audit(&self) {
+        if let Some(ref recorder) = self.audit {
+            if let Err(e) = recorder.flush() {
+                log::error!("Failed to write audit records for {}: {}", self.root.display(), e);
+            }
+        }
+    }
+
+    fn recover(&self) -> Option<RecommendedWatcher> {
+        let mut backoff = RECOVERY_BACKOFF_BASE;
+        let mut last_error = String::from("watched root no longer exists");
+
+        for _ in 0..RECOVERY_MAX_ATTEMPTS {
+            if !self.sleep_while_running(backoff) {
+                return None;
+            }
+
+            if self.root.exists() {
+                self.backend_error.lock().take();
+
+                match create_watcher(
+                    &self.root,
+                    self.recursive,
+                    self.synchronizer.get_sender(),
+                    Arc::clone(&self.debouncer),
+                    Arc::clone(&self.exclusion_filter),
+                    self.audit.clone(),
+                    Arc::clone(&self.backend_error),
+                ) {
+                    Ok(watcher) => {
+                        let caught_up = match self.synchronizer.catch_up(&self.root) {
+                            Ok(stats) => stats.total(),
+                            Err(e) => {
+                                log::warn!("Catch-up update of {} failed: {}", self.root.display(), e);
+                                0
+                            }
+                        };
+
+                        log::info!("Watch on {} recovered", self.root.display());
+                        self.transition(WatchState::Healthy, None);
+                        self.emit(WatchEvent::Recovered {
+                            root: self.root.clone(),
+                            caught_up,
+                        });
+                        return Some(watcher);
+                    }
+                    Err(e) => last_error = e.to_string(),
+                }
+            }
+
+            backoff = (backoff * 2).min(RECOVERY_BACKOFF_MAX);
+        }
+
+        log::error!("Watch on {} failed: {}", self.root.display(), last_error);
+        self.transition(WatchState::Failed, Some(last_error.clone()));
+        self.emit(WatchEvent::Failed {
+            root: self.root.clone(),
+            error: last_error,
+        });
+        None
+    }
+
+    fn sleep_while_running(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        while self.is_running.load(Ordering::Relaxed) {
+            let now = Instant::now();
+            if now >= deadline {
+                return true;
+            }
+            thread::sleep((deadline - now).min(Duration::from_millis(50)));
+        }
+        false
+    }
+
+    fn transition(&self, state: WatchState, last_error: Option<String>) {
+        self.status.lock().set_state(state, last_error);
+    }
+
+    fn emit(&self, event: WatchEvent) {
+        emit(&self.subscribers, event);
+    }
+}
+
+fn emit(subscribers: &Subscribers, event: WatchEvent) {
+    subscribers
+        .lock()
+        .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::types::AuditEventType;
+    use std::fs;
+    use std::path::PathBuf;
     use tempfile::TempDir;
 
     #[test]
@@ -160,4 +686,145 @@ mod tests {
         assert!(monitor.stop().is_ok());
         assert!(!monitor.is_running());
     }
+
+    #[test]
+    fn test_transient_event_never_reaches_synchronizer() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let debouncer = Arc::new(EventDebouncer::new(0));
+        let filter = Arc::new(ExclusionFilter::from_patterns(&[]).unwrap().with_transient_artifacts(true));
+
+        let event = Event::new(EventKind::Create(notify::event::CreateKind::File))
+            .add_path(PathBuf::from("/project/foo.txt.swp"))
+            .add_path(PathBuf::from("/project/foo.txt"));
+        FileSystemMonitor::handle_notify_event(event, &sender, &debouncer, &filter, None, None);
+
+        let received = receiver.try_recv().unwrap();
+        assert_eq!(received.path, PathBuf::from("/project/foo.txt"));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_audited_events_are_recorded_before_sync() {
+        let temp_dir = TempDir::new().unwrap();
+        let kept = temp_dir.path().join("ledger.csv");
+        fs::write(&kept, "a,b,c").unwrap();
+        let removed = temp_dir.path().join("draft.csv");
+
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let recorder = AuditRecorder::new(Arc::clone(&db), Some("watch-1".to_string()), 100);
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let debouncer = Arc::new(EventDebouncer::new(0));
+        let filter = Arc::new(ExclusionFilter::from_patterns(&[]).unwrap());
+
+        let events = [
+            Event::new(EventKind::Create(notify::event::CreateKind::File)).add_path(kept.clone()),
+            Event::new(EventKind::Modify(ModifyKind::Name(notify::event::RenameMode::To)))
+                .add_path(kept.clone()),
+            Event::new(EventKind::Remove(notify::event::RemoveKind::File)).add_path(removed.clone()),
+        ];
+        for event in events {
+            FileSystemMonitor::handle_notify_event(
+                event,
+                &sender,
+                &debouncer,
+                &filter,
+                Some(&recorder),
+                None,
+            );
+        }
+
+        assert_eq!(recorder.pending(), 3);
+        assert_eq!(receiver.try_recv().unwrap().event_type, FileEventType::Created);
+        assert_eq!(receiver.try_recv().unwrap().event_type, FileEventType::Renamed);
+        assert_eq!(receiver.try_recv().unwrap().event_type, FileEventType::Deleted);
+
+        assert_eq!(recorder.flush().unwrap(), 3);
+        let records = db.query_audit_log(Some(temp_dir.path()), (None, None), &[], 0, 10).unwrap();
+        let kinds: Vec<_> = records.iter().map(|r| r.event_type).collect();
+        assert_eq!(
+            kinds,
+            vec![AuditEventType::Created, AuditEventType::Renamed, AuditEventType::Deleted]
+        );
+        assert_eq!(records[0].size, Some(5));
+        assert!(records[0].file_hash.is_some());
+        assert_eq!(records[2].path, removed);
+        assert_eq!(records[2].file_hash, None);
+        assert!(records.iter().all(|r| r.watch_id.as_deref() == Some("watch-1")));
+
+        let deleted = db
+            .query_audit_log(None, (None, None), &[AuditEventType::Deleted], 0, 10)
+            .unwrap();
+        assert_eq!(deleted.len(), 1);
+    }
+
+    #[test]
+    fn test_watch_with_audit_flushes_on_stop() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Arc::new(Database::in_memory(10).unwrap());
+        let config = Arc::new(SearchConfig {
+            watch_debounce_ms: 0,
+            ..Default::default()
+        });
+        let filter = Arc::new(ExclusionFilter::from_patterns(&[]).unwrap());
+
+        let mut monitor = FileSystemMonitor::new(Arc::clone(&db), config, filter);
+        let options = WatchOptions {
+            audit: true,
+            watch_id: Some("live".to_string()),
+            initial_scan: false,
+            ..Default::default()
+        };
+        monitor.start_with_options(temp_dir.path(), options).unwrap();
+        assert!(monitor.is_auditing());
+
+        thread::sleep(Duration::from_millis(200));
+        fs::write(temp_dir.path().join("new.txt"), "hello").unwrap();
+        thread::sleep(Duration::from_millis(500));
+        monitor.stop().unwrap();
+
+        let records = db.query_audit_log(None, (None, None), &[], 0, 100).unwrap();
+        assert!(records
+            .iter()
+            .any(|r| r.path.ends_with("new.txt") && r.watch_id.as_deref() == Some("live")));
+    }
+
+    #[test]
+    fn test_non_recursive_watch_ignores_nested_files() {
+        for recursive in [false, true] {
+            let temp_dir = TempDir::new().unwrap();
+            let root = dunce::canonicalize(temp_dir.path()).unwrap();
+            fs::create_dir_all(root.join("service/archive")).unwrap();
+
+            let db = Arc::new(Database::in_memory(4).unwrap());
+            let config = Arc::new(SearchConfig {
+                watch_debounce_ms: 0,
+                ..Default::default()
+            });
+            let filter = Arc::new(ExclusionFilter::from_patterns(&[]).unwrap());
+            let mut monitor = FileSystemMonitor::new(Arc::clone(&db), config, filter);
+            let options = WatchOptions {
+                initial_scan: false,
+                recursive,
+                ..Default::default()
+            };
+            monitor.start_with_options(&root, options).unwrap();
+            assert_eq!(monitor.status().recursive, recursive);
+
+            thread::sleep(Duration::from_millis(200));
+            let top = root.join("top.log");
+            let deep = root.join("service/archive/deep.log");
+            fs::write(&top, "top").unwrap();
+            fs::write(&deep, "deep").unwrap();
+            thread::sleep(Duration::from_millis(600));
+            monitor.stop().unwrap();
+
+            assert!(db.find_by_path(&top).unwrap().is_some(), "recursive: {}", recursive);
+            assert_eq!(
+                db.find_by_path(&deep).unwrap().is_some(),
+                recursive,
+                "recursive: {}",
+                recursive
+            );
+        }
+    }
 }
@@ -1,10 +1,14 @@
 use crate::core::config::SearchConfig;
 use crate::core::error::Result;
+use crate::core::metrics::EngineCounters;
+use crate::core::types::ProgressCallback;
 use crate::filters::ExclusionFilter;
-use crate::indexer::incremental::IncrementalIndexer;
-use crate::storage::Database;
+use crate::indexer::incremental::{IncrementalIndexer, UpdateStats};
+use crate::storage::{Database, WriteLeaseKeeper};
 use crate::watcher::debouncer::FileEventType;
-use std::path::PathBuf;
+use parking_lot::Mutex;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::mpsc;
 
@@ -15,9 +19,13 @@ pub struct FileEvent {
 }
 
 pub struct IndexSynchronizer {
-    indexer: Arc<IncrementalIndexer>,
-    event_receiver: Option<mpsc::UnboundedReceiver<FileEvent>>,
+    indexer: IncrementalIndexer,
+    event_receiver: Mutex<Option<mpsc::UnboundedReceiver<FileEvent>>>,
     event_sender: mpsc::UnboundedSender<FileEvent>,
+    /// Events taken off the queue while another process held the write lease.
+    backlog: Mutex<Vec<FileEvent>>,
+    write_lease: Arc<WriteLeaseKeeper>,
+    metrics: Arc<EngineCounters>,
 }
 
 impl IndexSynchronizer {
@@ -28,21 +36,38 @@ impl IndexSynchronizer {
     ) -> Self {
         let (sender, receiver) = mpsc::unbounded_channel();
 
-        let indexer = Arc::new(IncrementalIndexer::new(database, config, exclusion_filter));
+        let write_lease = Arc::new(WriteLeaseKeeper::new(Arc::clone(&database), &config));
+        let indexer = IncrementalIndexer::new(database, config, exclusion_filter);
 
         Self {
             indexer,
-            event_receiver: Some(receiver),
+            event_receiver: Mutex::new(Some(receiver)),
             event_sender: sender,
+            backlog: Mutex::new(Vec::new()),
+            write_lease,
+            metrics: Arc::new(EngineCounters::new()),
         }
     }
 
+    /// Shares the write lease of the engine this synchronizer writes for.
+    pub fn with_write_lease(mut self, write_lease: Arc<WriteLeaseKeeper>) -> Self {
+        self.write_lease = write_lease;
+        self
+    }
+
+    /// Records processed events and catch-up updates into `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<EngineCounters>) -> Self {
+        self.indexer = self.indexer.with_metrics(Arc::clone(&metrics));
+        self.metrics = metrics;
+        self
+    }
+
     pub fn get_sender(&self) -> mpsc::UnboundedSender<FileEvent> {
         self.event_sender.clone()
     }
 
     pub async fn start(&mut self) -> Result<()> {
-        let mut receiver = self.event_receiver.take().ok_or_else(|| {
+        let mut receiver = self.event_receiver.lock().take().ok_or_else(|| {
             crate::core::error::SearchError::NotInitialized(
                 "Synchronizer already started".to_string(),
             )
@@ -57,7 +82,81 @@ impl IndexSynchronizer {
         Ok(())
     }
 
+    /// Applies every event queued so far without waiting for more and returns how many
+    /// were handled. The write lease is held only while they are applied, so writers in
+    /// other processes get in between batches; while one of them holds it, the events stay
+    /// queued for a later call. Does nothing once [`start`](Self::start) has taken over the
+    /// queue.
+    pub fn apply_pending(&self) -> usize {
+        self.apply_queued(false)
+    }
+
+    /// Like [`apply_pending`](Self::apply_pending), but waits for the write lease instead of
+    /// leaving the events queued. Used when the watch stops.
+    pub fn flush_pending(&self) -> usize {
+        self.apply_queued(true)
+    }
+
+    fn apply_queued(&self, wait: bool) -> usize {
+        let mut backlog = self.backlog.lock();
+        {
+            let mut receiver = self.event_receiver.lock();
+            let Some(receiver) = receiver.as_mut() else {
+                return 0;
+            };
+            while let Ok(event) = receiver.try_recv() {
+                backlog.push(event);
+            }
+        }
+        if backlog.is_empty() {
+            return 0;
+        }
+
+        let lease = if wait {
+            self.write_lease.acquire().map(Some)
+        } else {
+            self.write_lease.try_acquire()
+        };
+        let _lease = match lease {
+            Ok(Some(lease)) => lease,
+            Ok(None) => return 0,
+            Err(e) => {
+                log::error!("Failed to take the write lease for file events: {}", e);
+                return 0;
+            }
+        };
+
+        let applied = backlog.len();
+        if let Err(e) = self.apply_events(backlog.drain(..)) {
+            log::error!("Failed to handle file events: {}", e);
+        }
+        applied
+    }
+
+    /// Applies queued events together, in batches the indexer sizes by commit latency. A
+    /// path changed several times is read once.
+    fn apply_events(&self, events: impl Iterator<Item = FileEvent>) -> Result<usize> {
+        let _active = self.metrics.begin_operation();
+        let mut seen = HashSet::new();
+        let mut paths = Vec::new();
+        for event in events {
+            self.metrics.record_watch_event();
+            if seen.insert(event.path.clone()) {
+                paths.push(event.path);
+            }
+        }
+        self.indexer.update_files(&paths)
+    }
+
     async fn handle_event(&self, event: FileEvent) -> Result<()> {
+        let _lease = self.write_lease.acquire()?;
+        self.apply_event(event)
+    }
+
+    fn apply_event(&self, event: FileEvent) -> Result<()> {
+        let _active = self.metrics.begin_operation();
+        self.metrics.record_watch_event();
+
         match event.event_type {
             FileEventType::Created | FileEventType::Modified => {
                 self.indexer.update_file(&event.path)?;
@@ -75,13 +174,29 @@ impl IndexSynchronizer {
     }
 
     pub fn sync_path(&self, path: PathBuf) -> Result<()> {
+        let _lease = self.write_lease.acquire()?;
         self.indexer.update_file(path)?;
         Ok(())
     }
 
     pub fn sync_paths(&self, paths: Vec<PathBuf>) -> Result<usize> {
+        let _lease = self.write_lease.acquire()?;
         self.indexer.update_files(&paths)
     }
+
+    pub fn catch_up<P: AsRef<Path>>(&self, root: P) -> Result<UpdateStats> {
+        let _lease = self.write_lease.acquire()?;
+        self.indexer.update(root, None)
+    }
+
+    pub fn catch_up_with_progress<P: AsRef<Path>>(
+        &self,
+        root: P,
+        progress: ProgressCallback,
+    ) -> Result<UpdateStats> {
+        let _lease = self.write_lease.acquire()?;
+        self.indexer.update(root, Some(progress))
+    }
 }
 
 #[cfg(test)]
@@ -107,4 +222,84 @@ mod tests {
         let result = synchronizer.sync_path(file_path);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_sync_uses_log_tail_sampling() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("app.log");
+        fs::write(&file_path, "INFO ok\n".repeat(10)).unwrap();
+
+        let db = Arc::new(Database::in_memory(4).unwrap());
+        let config = Arc::new(SearchConfig {
+            enable_content_search: true,
+            ..Default::default()
+        });
+        let filter = Arc::new(ExclusionFilter::default());
+        let synchronizer = IndexSynchronizer::new(db.clone(), config, filter);
+        synchronizer.sync_path(file_path.clone()).unwrap();
+
+        // Grow the log well past the head preview and re-sync as a watcher would.
+        let mut log = "INFO ok\n".repeat(5000);
+        log.push_str("ERROR zebracorn exhausted\n");
+        fs::write(&file_path, log).unwrap();
+        synchronizer.sync_path(file_path).unwrap();
+
+        assert_eq!(db.search_content_files("zebracorn", 10).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_events_and_catch_up_are_counted() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        fs::write(&file_path, "content").unwrap();
+
+        let db = Arc::new(Database::in_memory(2).unwrap());
+        let config = Arc::new(SearchConfig::default());
+        let filter = Arc::new(ExclusionFilter::default());
+        let metrics = Arc::new(EngineCounters::new());
+        let synchronizer =
+            IndexSynchronizer::new(db, config, filter).with_metrics(Arc::clone(&metrics));
+
+        for event_type in [FileEventType::Created, FileEventType::Modified] {
+            let event = FileEvent {
+                path: file_path.clone(),
+                event_type,
+            };
+            synchronizer.handle_event(event).await.unwrap();
+        }
+        synchronizer.catch_up(temp_dir.path()).unwrap();
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.watch_events_processed, 2);
+        assert_eq!(snapshot.index_operations, 1);
+    }
+
+    #[test]
+    fn test_pending_events_wait_for_another_writer_between_batches() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        fs::write(&file_path, "content").unwrap();
+
+        let db = Arc::new(Database::in_memory(4).unwrap());
+        let config = Arc::new(SearchConfig::default());
+        let filter = Arc::new(ExclusionFilter::default());
+        let synchronizer = IndexSynchronizer::new(db.clone(), Arc::clone(&config), filter);
+        let other_writer = Arc::new(WriteLeaseKeeper::new(db.clone(), &config));
+
+        let event = FileEvent {
+            path: file_path.clone(),
+            event_type: FileEventType::Created,
+        };
+        synchronizer.get_sender().send(event).unwrap();
+
+        let held = other_writer.acquire().unwrap();
+        assert_eq!(synchronizer.apply_pending(), 0);
+        assert!(db.find_by_path(&file_path).unwrap().is_none());
+
+        drop(held);
+        assert_eq!(synchronizer.apply_pending(), 1);
+        assert!(db.find_by_path(&file_path).unwrap().is_some());
+        // The lease is given back once the batch is applied.
+        assert!(db.write_lease().unwrap().is_none());
+    }
 }
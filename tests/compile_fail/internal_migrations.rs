@@ -0,0 +1,5 @@
+use rusty_files::storage::migrations::MigrationManager;
+
+fn main() {
+    let _ = MigrationManager;
+}
@@ -0,0 +1,144 @@
+//! Exercises the supported API through the crate root and the prelude only, so removing or
+//! renaming any of it fails here before it reaches users.
+
+use rusty_files::prelude::*;
+use rusty_files::{
+    format_date, format_size, parse_extensions, parse_relative_date, parse_size, EntryKind,
+    ErrorCode, ExclusionFilter, ExtensionCategory, FilterDecision, HiddenMode, IndexStats,
+    MatchLocation, MatchMode, ResultLimit, SearchScope, SortKey,
+};
+use std::fs;
+use tempfile::TempDir;
+
+/// Names every supported type; only needs to compile.
+#[allow(dead_code, clippy::type_complexity)]
+fn supported_types(
+    _: Option<(
+        rusty_files::SearchEngine,
+        rusty_files::SearchEngineBuilder,
+        rusty_files::SearchConfig,
+        rusty_files::SearchConfigBuilder,
+        rusty_files::ConfigError,
+        rusty_files::Query,
+        rusty_files::QueryParser,
+        rusty_files::EffectiveQuery,
+        rusty_files::SearchResult,
+        rusty_files::FileEntry,
+        rusty_files::IndexStats,
+        rusty_files::IndexReport,
+    )>,
+    _: Option<(
+        rusty_files::SearchError,
+        rusty_files::ErrorCode,
+        rusty_files::ErrorReport,
+        rusty_files::UpdateStats,
+        rusty_files::VerificationStats,
+        rusty_files::EngineMetrics,
+        rusty_files::BackupReport,
+        rusty_files::RestoreReport,
+        rusty_files::FtsRebuildReport,
+        rusty_files::Progress,
+        rusty_files::WatchEvent,
+        rusty_files::TimestampSanity,
+    )>,
+) {
+}
+
+fn engine_with_files() -> (TempDir, SearchEngine) {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("docs");
+    fs::create_dir(&root).unwrap();
+    fs::write(root.join("notes.md"), "quarterly planning notes").unwrap();
+    fs::write(root.join("main.rs"), "fn main() {}").unwrap();
+
+    let engine = SearchEngine::builder()
+        .index_path(temp_dir.path().join("index.db"))
+        .build()
+        .unwrap();
+    engine.index_directory(&root, None).unwrap();
+    (temp_dir, engine)
+}
+
+#[test]
+fn test_engine_indexes_and_searches() {
+    let (_temp_dir, engine) = engine_with_files();
+
+    let results: Vec<SearchResult> = engine.search("notes").unwrap();
+    assert_eq!(results.len(), 1);
+    let file: &FileEntry = results[0].file();
+    assert_eq!(file.name(), "notes.md");
+    assert_eq!(file.extension(), Some("md"));
+    assert_eq!(file.kind(), EntryKind::File);
+    assert!(results[0].score() > 0.0);
+
+    let query: Query = QueryParser::parse("main ext:rs").unwrap();
+    let results = engine.search_with_query(&query).unwrap();
+    assert_eq!(results[0].file().path().file_name().unwrap(), "main.rs");
+
+    let stats: IndexStats = engine.get_stats().unwrap();
+    assert_eq!(stats.total_files, 2);
+}
+
+#[test]
+fn test_config_builds_and_validates() {
+    let config: SearchConfig = SearchConfigBuilder::new()
+        .max_search_results(50)
+        .enable_fuzzy_search(false)
+        .build();
+    assert!(config.validate().is_ok());
+
+    let errors = SearchConfigBuilder::new().max_search_results(0).try_build().unwrap_err();
+    assert_eq!(errors[0].field, "max_search_results");
+}
+
+#[test]
+fn test_query_parser_surface() {
+    let query = QueryParser::parse("report ext:pdf hidden:only sort:-size limit:5").unwrap();
+    assert_eq!(query.pattern, "report");
+    assert_eq!(query.match_mode, MatchMode::CaseInsensitive);
+    assert_eq!(query.scope, SearchScope::Name);
+    assert_eq!(query.hidden, Some(HiddenMode::Only));
+    assert_eq!(query.sort_by, SortKey::parse("-size"));
+    let limit: ResultLimit = query.result_limit(100).unwrap();
+    assert_eq!(limit.effective_limit, 5);
+
+    let err: SearchError = QueryParser::parse("size:>huge").unwrap_err();
+    assert_eq!(err.code(), ErrorCode::InvalidQuery);
+}
+
+#[test]
+fn test_filter_helpers() {
+    assert_eq!(parse_size("1KB"), Some(1024));
+    assert_eq!(format_size(1024), "1.00 KB");
+    assert_eq!(parse_extensions("rs, md"), vec!["rs", "md"]);
+    assert!(parse_relative_date("7d").is_some());
+    assert!(!format_date(chrono::Utc::now()).is_empty());
+    assert_eq!(ExtensionCategory::parse("image"), Some(ExtensionCategory::Image));
+
+    let filter = ExclusionFilter::from_patterns(&["*.tmp".to_string()]).unwrap();
+    assert!(matches!(filter.decision("/tmp/a.tmp"), FilterDecision::Excluded(_)));
+}
+
+#[test]
+fn test_entry_and_result_builders() {
+    let file = FileEntry::new(PathBuf::from("/photos/beach.jpg"))
+        .with_size(2048)
+        .with_mime_type("image/jpeg");
+    assert_eq!(file.size(), 2048);
+    assert_eq!(file.mime_type(), Some("image/jpeg"));
+    assert_eq!(file.id(), None);
+
+    let dir = FileEntry::new(PathBuf::from("/photos")).with_kind(EntryKind::Directory);
+    assert_eq!(dir.kind(), EntryKind::Directory);
+
+    let result = SearchResult::new(file, 0.5)
+        .with_snippet("beach")
+        .with_matches(Vec::<MatchLocation>::new());
+    assert_eq!(result.snippet(), Some("beach"));
+    assert!(result.matches().is_empty());
+}
+
+#[test]
+fn test_internal_modules_are_not_nameable() {
+    trybuild::TestCases::new().compile_fail("tests/compile_fail/*.rs");
+}